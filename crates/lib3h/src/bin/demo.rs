@@ -69,6 +69,19 @@ impl<'lt> EngineContainer<GhostEngine<'lt>> {
             dht_gossip_interval: 100,
             dht_timeout_threshold: 1000,
             dht_custom_config: vec![],
+            dht_arc_resize_interval: default_dht_arc_resize_interval(),
+            dht_target_minimum_r_value: default_dht_target_minimum_r_value(),
+            dht_target_maximum_r_value: default_dht_target_maximum_r_value(),
+            transit_encodings: default_transit_encodings(),
+            relay_uri: None,
+            relay_quota: None,
+            keystore_passphrase: None,
+            stream_limits: None,
+            reconnect_policy: None,
+            compression: None,
+            chunking: None,
+            discovery: vec![],
+            sign_mdns_records: false,
         };
 
         let dht_factory = MirrorDht::new_with_config;
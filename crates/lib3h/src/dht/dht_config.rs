@@ -1,7 +1,12 @@
+use crate::rrdht_util::ARC_RADIUS_MAX;
 use lib3h_protocol::uri::Lib3hUri;
 
 pub const DEFAULT_GOSSIP_INTERVAL_MS: u64 = 2000;
 pub const DEFAULT_TIMEOUT_THRESHOLD_MS: u64 = 60000;
+pub const DEFAULT_ARC_RESIZE_INTERVAL_MS: u64 = 10000;
+pub const DEFAULT_TARGET_MINIMUM_R_VALUE: f64 = 25.0;
+pub const DEFAULT_TARGET_MAXIMUM_R_VALUE: f64 = 50.0;
+pub const DEFAULT_FULL_SYNC_ROUNDS: u64 = 30;
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct DhtConfig {
@@ -9,6 +14,11 @@ pub struct DhtConfig {
     custom: Vec<u8>,
     gossip_interval: u64,
     timeout_threshold: u64,
+    storage_arc_radius: u32,
+    arc_resize_interval: u64,
+    target_minimum_r_value: f64,
+    target_maximum_r_value: f64,
+    full_sync_rounds: u64,
 }
 
 impl DhtConfig {
@@ -18,6 +28,11 @@ impl DhtConfig {
             custom: vec![],
             gossip_interval: DEFAULT_GOSSIP_INTERVAL_MS,
             timeout_threshold: DEFAULT_TIMEOUT_THRESHOLD_MS,
+            storage_arc_radius: ARC_RADIUS_MAX,
+            arc_resize_interval: DEFAULT_ARC_RESIZE_INTERVAL_MS,
+            target_minimum_r_value: DEFAULT_TARGET_MINIMUM_R_VALUE,
+            target_maximum_r_value: DEFAULT_TARGET_MAXIMUM_R_VALUE,
+            full_sync_rounds: DEFAULT_FULL_SYNC_ROUNDS,
         }
     }
 
@@ -27,9 +42,48 @@ impl DhtConfig {
             custom: config.clone().dht_custom_config,
             gossip_interval: config.dht_gossip_interval,
             timeout_threshold: config.dht_timeout_threshold,
+            storage_arc_radius: ARC_RADIUS_MAX,
+            arc_resize_interval: config.dht_arc_resize_interval,
+            target_minimum_r_value: config.dht_target_minimum_r_value,
+            target_maximum_r_value: config.dht_target_maximum_r_value,
+            full_sync_rounds: DEFAULT_FULL_SYNC_ROUNDS,
         }
     }
 
+    /// Set the initial radius of the arc a sharded DHT claims to store,
+    /// centered on this peer's location
+    pub fn with_storage_arc_radius(mut self, storage_arc_radius: u32) -> Self {
+        self.storage_arc_radius = storage_arc_radius;
+        self
+    }
+
+    /// Set how often we gossip ourselves and open gossip sequences with our peers
+    pub fn with_gossip_interval(mut self, gossip_interval: u64) -> Self {
+        self.gossip_interval = gossip_interval;
+        self
+    }
+
+    /// Set every how many gossip rounds we list every aspect we hold to every peer again,
+    /// in case they missed some of the aspects we listed since
+    pub fn with_full_sync_rounds(mut self, full_sync_rounds: u64) -> Self {
+        self.full_sync_rounds = full_sync_rounds;
+        self
+    }
+
+    /// Set how often a sharded DHT re-evaluates its storage arc
+    pub fn with_arc_resize_interval(mut self, arc_resize_interval: u64) -> Self {
+        self.arc_resize_interval = arc_resize_interval;
+        self
+    }
+
+    /// Set the redundancy (r-value) range the storage arc is resized towards:
+    /// grow when estimated redundancy is below minimum, shrink when above maximum
+    pub fn with_target_r_value(mut self, minimum: f64, maximum: f64) -> Self {
+        self.target_minimum_r_value = minimum;
+        self.target_maximum_r_value = maximum;
+        self
+    }
+
     pub fn timeout_threshold(&self) -> u64 {
         self.timeout_threshold
     }
@@ -38,6 +92,26 @@ impl DhtConfig {
         self.gossip_interval
     }
 
+    pub fn full_sync_rounds(&self) -> u64 {
+        self.full_sync_rounds
+    }
+
+    pub fn storage_arc_radius(&self) -> u32 {
+        self.storage_arc_radius
+    }
+
+    pub fn arc_resize_interval(&self) -> u64 {
+        self.arc_resize_interval
+    }
+
+    pub fn target_minimum_r_value(&self) -> f64 {
+        self.target_minimum_r_value
+    }
+
+    pub fn target_maximum_r_value(&self) -> f64 {
+        self.target_maximum_r_value
+    }
+
     pub fn this_peer_name(&self) -> Lib3hUri {
         self.this_peer_name.clone()
     }
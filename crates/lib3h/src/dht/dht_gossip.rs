@@ -0,0 +1,288 @@
+//! Helpers for running the capnp p2p gossip sequence on top of a DHT's
+//! entry bookkeeping (see lib3h_p2p_protocol's `doc/p2p_protocol.md`):
+//!  - `MsgGspArcRequest`: open anti-entropy with the aspect hashes we hold in an arc
+//!  - `MsgGspArcResponse`: the hashes the remote holds in that arc that we did not list
+//!  - `MsgGspAspectDataRequest`: ask for the aspects we are missing
+//!  - `MsgGspAspectDataResponse` / `MsgGspAspectBroadcast`: carry aspect data
+
+use crate::{
+    error::{ErrorKind, Lib3hError, Lib3hResult},
+    rrdht_util::{Arc, Location, ARC_LENGTH_MAX},
+};
+use lib3h_p2p_protocol::p2p::*;
+use lib3h_protocol::{
+    data_types::{EntryAspectData, EntryData},
+    types::*,
+};
+use rmp_serde::{Deserializer, Serializer};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Entry bookkeeping as held by our DHT implementations
+pub type EntryAspectMap = HashMap<EntryHash, HashSet<AspectHash>>;
+
+/// Convert an rrdht arc to a gossip arc constraint.
+/// Both the empty and the full arc are sent with `arc_start == arc_end`,
+/// `arc_empty` tells them apart.
+pub fn arc_to_constraint(arc: Arc) -> AspectConstraintArc {
+    let arc_start = u32::from(arc.start());
+    let arc_end = arc_start.wrapping_add((arc.length() % ARC_LENGTH_MAX) as u32);
+    AspectConstraintArc {
+        arc_start,
+        arc_end,
+        arc_empty: arc.length() == 0,
+    }
+}
+
+/// Convert a gossip arc constraint back to an rrdht arc
+pub fn constraint_to_arc(constraint: &AspectConstraintArc) -> Arc {
+    let start = Location::from(constraint.arc_start);
+    if constraint.arc_empty {
+        return Arc::new(start, 0);
+    }
+    if constraint.arc_start == constraint.arc_end {
+        return Arc::new(start, ARC_LENGTH_MAX);
+    }
+    let length = constraint.arc_end.wrapping_sub(constraint.arc_start);
+    Arc::new(start, u64::from(length))
+}
+
+/// Build the message opening a gossip sequence over `arc`.
+/// We do not track publish time nor local counts so those are left unconstrained.
+pub fn create_gsp_arc(arc: Arc, aspect_hash_list: Vec<AspectHashList>) -> MsgGspArc {
+    MsgGspArc {
+        aspect_constraint_arc: arc_to_constraint(arc),
+        aspect_constraint_time: AspectConstraintTime { gte_epoch_ms: 0 },
+        aspect_constraint_count: AspectConstraintCount { gte_local_count: 0 },
+        aspect_hash_list,
+    }
+}
+
+fn entry_address_from_bytes(bytes: &[u8]) -> Lib3hResult<EntryHash> {
+    Ok(String::from_utf8(bytes.to_vec())
+        .map_err(|e| Lib3hError::from(format!("bad entry address: {:?}", e)))?
+        .into())
+}
+
+fn aspect_address_from_bytes(bytes: &[u8]) -> Lib3hResult<AspectHash> {
+    Ok(String::from_utf8(bytes.to_vec())
+        .map_err(|e| Lib3hError::from(format!("bad aspect address: {:?}", e)))?
+        .into())
+}
+
+/// Convert wire aspect hash lists into addresses
+pub fn read_aspect_hash_list(
+    aspect_hash_list: &[AspectHashList],
+) -> Lib3hResult<Vec<(EntryHash, Vec<AspectHash>)>> {
+    let mut out = Vec::new();
+    for item in aspect_hash_list {
+        let mut aspect_address_list = Vec::new();
+        for aspect_hash in &item.aspect_hash_list {
+            aspect_address_list.push(aspect_address_from_bytes(aspect_hash)?);
+        }
+        out.push((
+            entry_address_from_bytes(&item.entry_address)?,
+            aspect_address_list,
+        ));
+    }
+    Ok(out)
+}
+
+fn write_aspect_hash_list<'a>(
+    entry_address: &EntryHash,
+    aspect_address_list: impl Iterator<Item = &'a AspectHash>,
+) -> AspectHashList {
+    AspectHashList {
+        entry_address: entry_address.to_string().into_bytes(),
+        aspect_hash_list: aspect_address_list
+            .map(|aspect_address| aspect_address.to_string().into_bytes())
+            .collect(),
+    }
+}
+
+/// List all the aspect hashes we hold for entries passing `filter`
+pub fn aspect_hash_list_of(
+    entry_list: &EntryAspectMap,
+    mut filter: impl FnMut(&EntryHash) -> bool,
+) -> Vec<AspectHashList> {
+    entry_list
+        .iter()
+        .filter(|(entry_address, _)| filter(entry_address))
+        .map(|(entry_address, aspect_set)| write_aspect_hash_list(entry_address, aspect_set.iter()))
+        .collect()
+}
+
+/// Aspect hashes from a remote list, for entries passing `filter`, that we do not hold
+pub fn missing_aspect_hash_list(
+    entry_list: &EntryAspectMap,
+    remote_list: &[AspectHashList],
+    mut filter: impl FnMut(&EntryHash) -> bool,
+) -> Lib3hResult<Vec<AspectHashList>> {
+    let mut out = Vec::new();
+    for (entry_address, aspect_address_list) in read_aspect_hash_list(remote_list)? {
+        if !filter(&entry_address) {
+            continue;
+        }
+        let missing: Vec<_> = match entry_list.get(&entry_address) {
+            None => aspect_address_list,
+            Some(held) => aspect_address_list
+                .into_iter()
+                .filter(|aspect_address| !held.contains(aspect_address))
+                .collect(),
+        };
+        if !missing.is_empty() {
+            out.push(write_aspect_hash_list(&entry_address, missing.iter()));
+        }
+    }
+    Ok(out)
+}
+
+/// Aspect hashes we hold, for entries passing `filter`, that are not in a remote list
+pub fn unlisted_aspect_hash_list(
+    entry_list: &EntryAspectMap,
+    remote_list: &[AspectHashList],
+    mut filter: impl FnMut(&EntryHash) -> bool,
+) -> Lib3hResult<Vec<AspectHashList>> {
+    let mut remote_map: EntryAspectMap = HashMap::new();
+    for (entry_address, aspect_address_list) in read_aspect_hash_list(remote_list)? {
+        remote_map
+            .entry(entry_address)
+            .or_insert_with(HashSet::new)
+            .extend(aspect_address_list);
+    }
+    let empty = HashSet::new();
+    let mut out = Vec::new();
+    for (entry_address, held) in entry_list.iter() {
+        if !filter(entry_address) {
+            continue;
+        }
+        let listed = remote_map.get(entry_address).unwrap_or(&empty);
+        let unlisted: Vec<_> = held.difference(listed).collect();
+        if !unlisted.is_empty() {
+            out.push(write_aspect_hash_list(entry_address, unlisted.into_iter()));
+        }
+    }
+    Ok(out)
+}
+
+/// Convert an entry to wire aspect data.
+/// If `aspect_filter` is set, only those aspects are included.
+pub fn entry_to_aspect_data_list(
+    entry: &EntryData,
+    aspect_filter: Option<&[AspectHash]>,
+) -> AspectDataList {
+    let aspect_data_list = entry
+        .aspect_list
+        .iter()
+        .filter(|aspect| match aspect_filter {
+            None => true,
+            Some(filter) => filter.contains(&aspect.aspect_address),
+        })
+        .map(|aspect| {
+            let mut buf = Vec::new();
+            aspect
+                .serialize(&mut Serializer::new(&mut buf))
+                .expect("EntryAspectData serialization failed");
+            buf
+        })
+        .collect();
+    AspectDataList {
+        entry_address: entry.entry_address.to_string().into_bytes(),
+        aspect_data_list,
+    }
+}
+
+/// Convert wire aspect data back to an entry
+pub fn aspect_data_list_to_entry(aspect_data_list: &AspectDataList) -> Lib3hResult<EntryData> {
+    let mut aspect_list = Vec::new();
+    for aspect_data in &aspect_data_list.aspect_data_list {
+        let mut de = Deserializer::new(&aspect_data[..]);
+        let aspect: EntryAspectData = Deserialize::deserialize(&mut de)
+            .map_err(|e| Lib3hError::new(ErrorKind::RmpSerdeDecodeError(e)))?;
+        aspect_list.push(aspect);
+    }
+    Ok(EntryData {
+        entry_address: entry_address_from_bytes(&aspect_data_list.entry_address)?,
+        aspect_list,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_entry_list() -> EntryAspectMap {
+        let mut entry_list = HashMap::new();
+        let mut aspect_set = HashSet::new();
+        aspect_set.insert(AspectHash::from("aspect_addr_1"));
+        aspect_set.insert(AspectHash::from("aspect_addr_2"));
+        entry_list.insert(EntryHash::from("entry_addr_1"), aspect_set);
+        entry_list
+    }
+
+    #[test]
+    fn it_should_round_trip_arc_constraints() {
+        for arc in &[
+            Arc::new(0.into(), ARC_LENGTH_MAX),
+            Arc::new(0.into(), 0),
+            Arc::new_radius(42.into(), 0),
+            Arc::new(0xffff_fff0.into(), 0x20),
+            Arc::new_radius(42.into(), 1),
+        ] {
+            assert_eq!(*arc, constraint_to_arc(&arc_to_constraint(*arc)));
+        }
+    }
+
+    #[test]
+    fn it_should_diff_aspect_hash_lists() {
+        let entry_list = test_entry_list();
+        let remote_list = vec![AspectHashList {
+            entry_address: b"entry_addr_1".to_vec(),
+            aspect_hash_list: vec![b"aspect_addr_1".to_vec(), b"aspect_addr_3".to_vec()],
+        }];
+        let missing = missing_aspect_hash_list(&entry_list, &remote_list, |_| true).unwrap();
+        assert_eq!(
+            vec![(
+                EntryHash::from("entry_addr_1"),
+                vec![AspectHash::from("aspect_addr_3")]
+            )],
+            read_aspect_hash_list(&missing).unwrap(),
+        );
+        let unlisted = unlisted_aspect_hash_list(&entry_list, &remote_list, |_| true).unwrap();
+        assert_eq!(
+            vec![(
+                EntryHash::from("entry_addr_1"),
+                vec![AspectHash::from("aspect_addr_2")]
+            )],
+            read_aspect_hash_list(&unlisted).unwrap(),
+        );
+        // Filtered out entries are never listed
+        assert!(
+            missing_aspect_hash_list(&entry_list, &remote_list, |_| false)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn it_should_round_trip_aspect_data() {
+        let aspect = EntryAspectData {
+            aspect_address: "aspect_addr_1".into(),
+            type_hint: "content".to_string(),
+            aspect: "hello-1".as_bytes().to_vec().into(),
+            publish_ts: 42,
+        };
+        let entry = EntryData {
+            entry_address: "entry_addr_1".into(),
+            aspect_list: vec![aspect],
+        };
+        let aspect_data_list = entry_to_aspect_data_list(&entry, None);
+        assert_eq!(entry, aspect_data_list_to_entry(&aspect_data_list).unwrap());
+        // Filtering out every aspect leaves an empty entry
+        let aspect_data_list = entry_to_aspect_data_list(&entry, Some(&[]));
+        assert!(aspect_data_list_to_entry(&aspect_data_list)
+            .unwrap()
+            .aspect_list
+            .is_empty());
+    }
+}
@@ -4,7 +4,7 @@ use lib3h_protocol::{
     uri::Lib3hUri,
 };
 
-use crate::{dht::dht_config::DhtConfig, error::*};
+use crate::{dht::dht_config::DhtConfig, error::*, rrdht_util::Arc};
 use lib3h_ghost_actor::prelude::*;
 use lib3h_protocol::uri::UriScheme;
 
@@ -67,6 +67,8 @@ pub enum DhtRequestToChild {
 
     /// Parent notifies us that the binding changed
     UpdateAdvertise(Lib3hUri),
+    /// Parent notifies us that a peer stopped answering its pings
+    PeerUnresponsive(Lib3hUri),
 
     /// Requests
     /// Parent wants PeerData for a specific Peer
@@ -81,6 +83,8 @@ pub enum DhtRequestToChild {
     RequestAspectsOf(EntryHash),
     /// Parent wants a specific entry.
     RequestEntry(EntryHash),
+    /// Parent wants our current storage arc and estimated network size
+    RequestStorageArc,
 }
 
 #[derive(Debug, Clone)]
@@ -91,6 +95,7 @@ pub enum DhtRequestToChildResponse {
     RequestEntryAddressList(Vec<EntryHash>),
     RequestAspectsOf(Option<Vec<AspectHash>>),
     RequestEntry(EntryData),
+    RequestStorageArc(StorageArcData),
 }
 
 #[derive(Debug, Clone)]
@@ -161,6 +166,14 @@ impl PeerData {
     }
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub struct StorageArcData {
+    /// Arc of locations this peer currently claims to hold
+    pub storage_arc: Arc,
+    /// Total node count, as estimated from the peers we know about
+    pub estimated_network_size: f64,
+}
+
 #[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
 pub struct FetchDhtEntryData {
     pub msg_id: String,
@@ -1,12 +1,19 @@
 use crate::{
-    dht::{dht_config::DhtConfig, dht_protocol::*},
+    dht::{dht_config::DhtConfig, dht_gossip::*, dht_protocol::*},
     error::{ErrorKind, Lib3hError, Lib3hResult},
+    rrdht_util::{Arc, ARC_LENGTH_MAX},
     time,
 };
 use detach::prelude::*;
 use holochain_tracing::Span;
 use lib3h_ghost_actor::prelude::*;
-use lib3h_protocol::{data_types::EntryData, types::*, uri::Lib3hUri, DidWork};
+use lib3h_p2p_protocol::p2p::*;
+use lib3h_protocol::{
+    data_types::{EntryData, Opaque},
+    types::*,
+    uri::Lib3hUri,
+    DidWork,
+};
 use rmp_serde::{Deserializer, Serializer};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
@@ -16,7 +23,8 @@ type HasTimedOut = bool;
 /// Enum holding all types of gossip messages used by MirrorDht
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 enum MirrorGossip {
-    Entry(EntryData),
+    /// capnp encoded P2pMessage of the entry gossip sequence
+    Gsp(Opaque),
     Peer(PeerData),
 }
 
@@ -35,6 +43,12 @@ pub struct MirrorDht {
     this_peer: PeerData,
     /// Keep track of last time this peer gossiped self to others
     last_gossip_of_self: u64,
+    /// Aspects we started holding since the last gossip round
+    unsynced_entry_list: EntryAspectMap,
+    /// Peers that acknowledged the list of every aspect we hold
+    synced_peer_set: HashSet<Lib3hUri>,
+    /// Gossip rounds since we last listed every aspect we hold to every peer
+    gossip_round: u64,
     /// Store Dht config used by this peer
     config: DhtConfig,
 
@@ -81,6 +95,9 @@ impl MirrorDht {
             entry_list: HashMap::new(),
             this_peer,
             last_gossip_of_self: timestamp,
+            unsynced_entry_list: HashMap::new(),
+            synced_peer_set: HashSet::new(),
+            gossip_round: 0,
             config: config.clone(),
             endpoint_parent: Some(endpoint_parent),
             endpoint_self: Detach::new(
@@ -168,6 +185,7 @@ impl MirrorDht {
         }
         // Mark peers that timed out
         for peer_name in timed_out_list {
+            self.synced_peer_set.remove(&peer_name);
             self.timed_out_map.insert(peer_name, true);
         }
         // Check if must gossip self
@@ -178,11 +196,14 @@ impl MirrorDht {
             self.config.gossip_interval(),
         );*/
         if now - self.last_gossip_of_self > self.config.gossip_interval() {
+            let last_gossip = self.last_gossip_of_self;
             self.last_gossip_of_self = now;
             let gossip_data = self.gossip_self(self.get_other_peer_list());
             if gossip_data.peer_name_list.len() > 0 {
                 outbox.push(DhtRequestToParent::GossipTo(gossip_data));
                 did_work = true;
+                // Also open anti-entropy gossip sequences
+                outbox.append(&mut self.gossip_arc_requests(last_gossip));
             }
         }
         // Done
@@ -201,6 +222,17 @@ impl MirrorDht {
             .collect()
     }
 
+    // Create gossipTo event of a P2pMessage of the entry gossip sequence
+    fn gossip_p2p_message(peer_name_list: Vec<Lib3hUri>, message: &P2pMessage) -> GossipToData {
+        let gossip = MirrorGossip::Gsp(message.to_bytes().into());
+        let mut buf = Vec::new();
+        gossip.serialize(&mut Serializer::new(&mut buf)).unwrap();
+        GossipToData {
+            peer_name_list,
+            bundle: buf.into(),
+        }
+    }
+
     // Create gossipTo event of your own PeerData (but not to yourself)
     fn gossip_self(&mut self, peer_name_list: Vec<Lib3hUri>) -> GossipToData {
         let gossip_this_peer = MirrorGossip::Peer(self.this_peer.clone());
@@ -248,6 +280,7 @@ impl MirrorDht {
                     peer_info.timestamp, peer.timestamp,
                 );
                 peer.timestamp = peer_info.timestamp;
+                peer.peer_location = peer_info.peer_location.clone();
                 if crate::time::since_epoch_ms() - peer.timestamp < self.config.timeout_threshold()
                 {
                     self.timed_out_map
@@ -258,6 +291,18 @@ impl MirrorDht {
         }
     }
 
+    /// Return true if peer is known and was not already timed-out
+    fn mark_peer_timed_out(&mut self, peer_name: &Lib3hUri) -> bool {
+        match self.timed_out_map.get_mut(peer_name) {
+            Some(has_timed_out) if !*has_timed_out => {
+                *has_timed_out = true;
+                self.synced_peer_set.remove(peer_name);
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Return aspect addresses diff between
     /// known aspects and aspects in the entry argument
     fn diff_aspects(&self, entry: &EntryData) -> HashSet<AspectHash> {
@@ -287,6 +332,10 @@ impl MirrorDht {
         if diff.len() == 0 {
             return false;
         }
+        self.unsynced_entry_list
+            .entry(entry.entry_address.clone())
+            .or_insert_with(HashSet::new)
+            .extend(diff.iter().cloned());
         let maybe_known_aspects = self.entry_list.get(&entry.entry_address);
         let new_aspects: HashSet<_> = match maybe_known_aspects {
             None => diff,
@@ -302,21 +351,193 @@ impl MirrorDht {
 
     /// Create GossipTo event for entry to all known peers
     fn gossip_entry(&self, entry: &EntryData) -> DhtRequestToParent {
-        let entry_gossip = MirrorGossip::Entry(entry.clone());
-        let mut buf = Vec::new();
-        entry_gossip
-            .serialize(&mut Serializer::new(&mut buf))
-            .unwrap();
-        let gossip_evt = GossipToData {
-            peer_name_list: self.get_other_peer_list(),
-            bundle: buf.into(),
-        };
+        let gossip_evt = Self::gossip_p2p_message(
+            self.get_other_peer_list(),
+            &P2pMessage::MsgGspAspectBroadcast(MsgGspAspectBroadcast {
+                aspect_data_list: vec![entry_to_aspect_data_list(entry, None)],
+            }),
+        );
         debug!(
             "@MirrorDht@ {:?} GossipTo: {:?}",
             self.this_peer, gossip_evt,
         );
         DhtRequestToParent::GossipTo(gossip_evt)
     }
+
+    /// Create GossipTo events opening a gossip sequence with all known peers.
+    /// Fullsync: the arc is always the full arc.
+    /// Peers that did not acknowledge a full list yet get every aspect we hold, the others
+    /// only get the aspects we started holding since `last_gossip`, constrained by time.
+    /// Every `full_sync_rounds` rounds, every peer gets every aspect we hold again.
+    fn gossip_arc_requests(&mut self, last_gossip: u64) -> Vec<DhtRequestToParent> {
+        self.gossip_round += 1;
+        if self.gossip_round >= self.config.full_sync_rounds() {
+            self.gossip_round = 0;
+            self.synced_peer_set.clear();
+        }
+        let full_arc = Arc::new(0.into(), ARC_LENGTH_MAX);
+        let mut request_list = Vec::new();
+        let (new_peer_list, synced_peer_list): (Vec<_>, Vec<_>) = self
+            .get_other_peer_list()
+            .into_iter()
+            .partition(|peer_name| !self.synced_peer_set.contains(peer_name));
+        if !new_peer_list.is_empty() {
+            let aspect_hash_list = aspect_hash_list_of(&self.entry_list, |_| true);
+            request_list.push(DhtRequestToParent::GossipTo(Self::gossip_p2p_message(
+                new_peer_list,
+                &P2pMessage::MsgGspArcRequest(create_gsp_arc(full_arc, aspect_hash_list)),
+            )));
+        }
+        let aspect_hash_list = aspect_hash_list_of(&self.unsynced_entry_list, |_| true);
+        self.unsynced_entry_list.clear();
+        if !synced_peer_list.is_empty() && !aspect_hash_list.is_empty() {
+            let mut gsp = create_gsp_arc(full_arc, aspect_hash_list);
+            gsp.aspect_constraint_time.gte_epoch_ms = last_gossip;
+            request_list.push(DhtRequestToParent::GossipTo(Self::gossip_p2p_message(
+                synced_peer_list,
+                &P2pMessage::MsgGspArcRequest(gsp),
+            )));
+        }
+        request_list
+    }
+
+    /// Ask a peer for the aspects in `remote_list` we are missing
+    fn request_missing_aspects(
+        &mut self,
+        span: Span,
+        from_peer_name: &Lib3hUri,
+        remote_list: &[AspectHashList],
+    ) -> Lib3hResult<()> {
+        let missing = missing_aspect_hash_list(&self.entry_list, remote_list, |_| true)?;
+        if missing.is_empty() {
+            return Ok(());
+        }
+        self.endpoint_self.publish(
+            span,
+            DhtRequestToParent::GossipTo(Self::gossip_p2p_message(
+                vec![from_peer_name.clone()],
+                &P2pMessage::MsgGspAspectDataRequest(MsgGspAspectDataRequest {
+                    aspect_hash_list: missing,
+                }),
+            )),
+        )?;
+        Ok(())
+    }
+
+    /// Fetch the requested aspects from owner and send them back to the requesting peer
+    fn respond_aspect_data(
+        &mut self,
+        span: Span,
+        from_peer_name: &Lib3hUri,
+        aspect_hash_list: &[AspectHashList],
+    ) -> Lib3hResult<()> {
+        for (entry_address, aspect_address_list) in read_aspect_hash_list(aspect_hash_list)? {
+            if !self.entry_list.contains_key(&entry_address) {
+                continue;
+            }
+            let from_peer_name = from_peer_name.clone();
+            let span_response = span.child("send MsgGspAspectDataResponse");
+            self.endpoint_self.request(
+                span.child("request DhtRequestToParent::RequestEntry"),
+                DhtRequestToParent::RequestEntry(entry_address),
+                Box::new(move |me, response| {
+                    let entry = match response {
+                        GhostCallbackData::Response(Ok(
+                            DhtRequestToParentResponse::RequestEntry(entry),
+                        )) => entry,
+                        _ => {
+                            warn!("@MirrorDht@ could not fetch aspect data: {:?}", response);
+                            return Ok(());
+                        }
+                    };
+                    let gossip_evt = Self::gossip_p2p_message(
+                        vec![from_peer_name],
+                        &P2pMessage::MsgGspAspectDataResponse(MsgGspAspectDataResponse {
+                            aspect_data_list: vec![entry_to_aspect_data_list(
+                                &entry,
+                                Some(&aspect_address_list),
+                            )],
+                        }),
+                    );
+                    me.endpoint_self
+                        .publish(span_response, DhtRequestToParent::GossipTo(gossip_evt))?;
+                    Ok(())
+                }),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Handle a P2pMessage of the entry gossip sequence.
+    /// Fullsync: arc constraints are ignored since we hold everything.
+    fn handle_gossiped_p2p_message(
+        &mut self,
+        span: Span,
+        from_peer_name: Lib3hUri,
+        message: P2pMessage,
+    ) -> Lib3hResult<()> {
+        match message {
+            // Ask for what we are missing, and tell about what they did not list,
+            // acknowledging full lists even when we hold nothing more
+            P2pMessage::MsgGspArcRequest(gsp) => {
+                self.request_missing_aspects(
+                    span.child("send MsgGspAspectDataRequest"),
+                    &from_peer_name,
+                    &gsp.aspect_hash_list,
+                )?;
+                // A time constrained list only holds what they got since their last round,
+                // so what they did not list they most likely hold already
+                if gsp.aspect_constraint_time.gte_epoch_ms > 0 {
+                    return Ok(());
+                }
+                let unlisted =
+                    unlisted_aspect_hash_list(&self.entry_list, &gsp.aspect_hash_list, |_| true)?;
+                let gsp = MsgGspArc {
+                    aspect_hash_list: unlisted,
+                    ..gsp
+                };
+                self.endpoint_self.publish(
+                    span.child("send MsgGspArcResponse"),
+                    DhtRequestToParent::GossipTo(Self::gossip_p2p_message(
+                        vec![from_peer_name],
+                        &P2pMessage::MsgGspArcResponse(gsp),
+                    )),
+                )?;
+            }
+            // They got our full list, from now on they only need what is new
+            P2pMessage::MsgGspArcResponse(gsp) => {
+                self.request_missing_aspects(span, &from_peer_name, &gsp.aspect_hash_list)?;
+                if self.peer_map.contains_key(&from_peer_name) {
+                    self.synced_peer_set.insert(from_peer_name);
+                }
+            }
+            P2pMessage::MsgGspAspectDataRequest(request) => {
+                self.respond_aspect_data(span, &from_peer_name, &request.aspect_hash_list)?;
+            }
+            P2pMessage::MsgGspAspectDataResponse(MsgGspAspectDataResponse { aspect_data_list })
+            | P2pMessage::MsgGspAspectBroadcast(MsgGspAspectBroadcast { aspect_data_list }) => {
+                for aspect_data in aspect_data_list {
+                    let entry = aspect_data_list_to_entry(&aspect_data)?;
+                    trace!("DhtRequestToChild::HandleGossip: Entry = {:?}", entry);
+                    let diff = self.diff_aspects(&entry);
+                    if diff.len() > 0 {
+                        self.endpoint_self.publish(
+                            span.follower("DhtRequestToParent::HoldEntryRequested"),
+                            DhtRequestToParent::HoldEntryRequested {
+                                from_peer_name: self.this_peer.peer_name.clone(),
+                                entry,
+                            },
+                        )?;
+                    }
+                }
+            }
+            _ => warn!(
+                "@MirrorDht@ unexpected gossip from {}: {:?}",
+                from_peer_name, message
+            ),
+        }
+        Ok(())
+    }
 }
 
 /// Impl DhtActor interface
@@ -337,8 +558,10 @@ impl
         detach_run!(&mut self.endpoint_self, |es| es.process(self))?;
         for request in self.endpoint_self.as_mut().drain_messages() {
             //debug!("@MirrorDht@ serving request: {:?}", request);
-            self.handle_request_from_parent(request)
-                .expect("no ghost errors");
+            // a malformed gossip bundle from a remote node must not bring us down
+            if let Err(e) = self.handle_request_from_parent(request) {
+                error!("@MirrorDht@ dropping request: {:?}", e);
+            }
         }
         let (did_work, command_list) = self.internal_process().unwrap(); // FIXME unwrap
         for command in command_list {
@@ -372,18 +595,9 @@ impl MirrorDht {
                 }
                 // Handle gossiped data
                 match maybe_gossip.unwrap() {
-                    MirrorGossip::Entry(entry) => {
-                        trace!("DhtRequestToChild::HandleGossip: Entry = {:?}", entry);
-                        let diff = self.diff_aspects(&entry);
-                        if diff.len() > 0 {
-                            self.endpoint_self.publish(
-                                span,
-                                DhtRequestToParent::HoldEntryRequested {
-                                    from_peer_name: self.this_peer.peer_name.clone(),
-                                    entry,
-                                },
-                            )?;
-                        }
+                    MirrorGossip::Gsp(bytes) => {
+                        let message = P2pMessage::from_bytes(bytes.into())?;
+                        self.handle_gossiped_p2p_message(span, msg.from_peer_name, message)?;
                     }
                     MirrorGossip::Peer(gossiped_peer) => {
                         trace!(
@@ -524,6 +738,17 @@ impl MirrorDht {
                 self.this_peer.peer_location = peer_location;
             }
 
+            // Owner is not getting pongs back from this peer anymore
+            DhtRequestToChild::PeerUnresponsive(peer_name) => {
+                if self.mark_peer_timed_out(&peer_name) {
+                    debug!("@MirrorDht@ peer {} stopped answering pings", peer_name);
+                    self.endpoint_self.publish(
+                        span.follower("DhtRequestToChild::PeerUnresponsive"),
+                        DhtRequestToParent::PeerTimedOut(peer_name),
+                    )?;
+                }
+            }
+
             DhtRequestToChild::RequestPeer(peer_name) => {
                 trace!("DhtRequestToChild::RequestPeer: {:?}", peer_name);
                 let maybe_peer = self.get_peer(&peer_name);
@@ -560,6 +785,17 @@ impl MirrorDht {
                 request.respond(payload)?;
             }
 
+            // Fullsync: we always hold everything
+            DhtRequestToChild::RequestStorageArc => {
+                let payload = Ok(DhtRequestToChildResponse::RequestStorageArc(
+                    StorageArcData {
+                        storage_arc: Arc::new(0.into(), ARC_LENGTH_MAX),
+                        estimated_network_size: (self.get_other_peer_list().len() + 1) as f64,
+                    },
+                ));
+                request.respond(payload)?;
+            }
+
             // Ask owner to respond to self
             DhtRequestToChild::RequestEntry(entry_address) => {
                 trace!("DhtRequestToChild::RequestEntry: {:?}", entry_address);
@@ -1,23 +1,29 @@
 pub mod dht_config;
+pub mod dht_gossip;
 pub mod dht_protocol;
 pub mod mirror_dht;
-// TODO - Fix rrdht
-//pub mod rrdht;
+pub mod rrdht;
 
 #[cfg(test)]
 pub mod tests {
     use crate::{
-        dht::{dht_protocol::*, mirror_dht::MirrorDht},
+        dht::{
+            dht_config::DhtConfig, dht_gossip::*, dht_protocol::*, mirror_dht::MirrorDht,
+            rrdht::RrDht,
+        },
+        rrdht_util::{Arc, ARC_LENGTH_MAX, ARC_RADIUS_MAX},
         tests::enable_logging_for_test,
     };
     use detach::prelude::*;
     use holochain_tracing::test_span;
     use lib3h_ghost_actor::prelude::*;
+    use lib3h_p2p_protocol::p2p::*;
     use lib3h_protocol::{
-        data_types::{EntryAspectData, EntryData},
+        data_types::{EntryAspectData, EntryData, Opaque},
         types::*,
         uri::Lib3hUri,
     };
+    use serde::{Deserialize, Serialize};
 
     lazy_static! {
         /// CONSTS
@@ -52,6 +58,7 @@ pub mod tests {
         peer_list: Vec<PeerData>,
         entry_list: Vec<EntryHash>,
         maybe_aspect_list: Option<Vec<AspectHash>>,
+        maybe_storage_arc: Option<StorageArcData>,
     }
 
     impl DhtData {
@@ -66,6 +73,7 @@ pub mod tests {
                 peer_list: Vec::new(),
                 entry_list: Vec::new(),
                 maybe_aspect_list: None,
+                maybe_storage_arc: None,
             }
         }
     }
@@ -113,21 +121,38 @@ pub mod tests {
         }
     }
 
-    fn new_dht(_is_mirror: bool, peer_name: &Lib3hUri) -> Box<DhtActor> {
-        //if is_mirror {
-        return MirrorDht::new(peer_name);
-        //}
-        //Box::new(RrDht::new())
+    fn new_dht(is_mirror: bool, peer_name: &Lib3hUri) -> Box<DhtActor> {
+        if is_mirror {
+            return MirrorDht::new(peer_name);
+        }
+        RrDht::new(peer_name)
     }
 
     fn new_dht_wrapper(
-        _is_mirror: bool,
+        is_mirror: bool,
+        peer_name: &Lib3hUri,
+    ) -> Detach<ChildDhtWrapperDyn<DhtData>> {
+        let dht = new_dht(is_mirror, peer_name);
+        Detach::new(ChildDhtWrapperDyn::new(dht, "dht_parent_"))
+    }
+
+    fn new_rrdht_wrapper_with_radius(
         peer_name: &Lib3hUri,
+        storage_arc_radius: u32,
     ) -> Detach<ChildDhtWrapperDyn<DhtData>> {
-        let dht = new_dht(true, peer_name);
+        let config = DhtConfig::new(peer_name).with_storage_arc_radius(storage_arc_radius);
+        let dht = RrDht::new_with_config(&config, None).unwrap();
         Detach::new(ChildDhtWrapperDyn::new(dht, "dht_parent_"))
     }
 
+    /// Drain all requests from a DHT and return their payloads
+    fn drain_payloads(dht: &mut Detach<ChildDhtWrapperDyn<DhtData>>) -> Vec<DhtRequestToParent> {
+        dht.drain_messages()
+            .into_iter()
+            .map(|mut request| request.take_message().expect("exists"))
+            .collect()
+    }
+
     fn get_this_peer(dht: &mut Detach<ChildDhtWrapperDyn<DhtData>>) -> PeerData {
         let mut ud = DhtData::new();
         dht.request(
@@ -157,6 +182,35 @@ pub mod tests {
         ud.this_peer
     }
 
+    fn get_storage_arc(dht: &mut Detach<ChildDhtWrapperDyn<DhtData>>) -> StorageArcData {
+        let mut ud = DhtData::new();
+        dht.request(
+            test_span(),
+            DhtRequestToChild::RequestStorageArc,
+            Box::new(|mut ud, response| {
+                let response = {
+                    match response {
+                        GhostCallbackData::Timeout(bt) => panic!("timeout: {:?}", bt),
+                        GhostCallbackData::Response(response) => match response {
+                            Err(e) => panic!("{:?}", e),
+                            Ok(response) => response,
+                        },
+                    }
+                };
+                if let DhtRequestToChildResponse::RequestStorageArc(storage_arc) = response {
+                    ud.maybe_storage_arc = Some(storage_arc);
+                } else {
+                    panic!("bad response to RequestStorageArc: {:?}", response);
+                }
+                Ok(())
+            }),
+        )
+        .unwrap();
+        dht.process(&mut ud).unwrap();
+        ud.maybe_storage_arc
+            .expect("Should have received storage arc")
+    }
+
     fn get_peer(
         dht: &mut Detach<ChildDhtWrapperDyn<DhtData>>,
         peer_name: &Lib3hUri,
@@ -331,6 +385,39 @@ pub mod tests {
         assert_eq!(peer_list.len(), 2);
     }
 
+    #[test]
+    fn test_unresponsive_peer_times_out() {
+        enable_logging_for_test(true);
+        for is_mirror in &[true, false] {
+            let mut dht = new_dht_wrapper(*is_mirror, &*PEER_A);
+            let mut ud = DhtData::new();
+            dht.publish(
+                test_span(),
+                DhtRequestToChild::HoldPeer(create_PeerData(&*PEER_B)),
+            )
+            .unwrap();
+            dht.process(&mut ud).unwrap();
+            let _ = drain_payloads(&mut dht);
+            // Unknown peers are ignored, known ones time out only once
+            for peer_name in &[&*PEER_C, &*PEER_B, &*PEER_B] {
+                dht.publish(
+                    test_span(),
+                    DhtRequestToChild::PeerUnresponsive((*peer_name).clone()),
+                )
+                .unwrap();
+            }
+            dht.process(&mut ud).unwrap();
+            let timed_out_list: Vec<_> = drain_payloads(&mut dht)
+                .into_iter()
+                .filter_map(|payload| match payload {
+                    DhtRequestToParent::PeerTimedOut(peer_name) => Some(peer_name),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(timed_out_list, vec![(*PEER_B).clone()]);
+        }
+    }
+
     #[test]
     fn test_get_own_entry() {
         enable_logging_for_test(true);
@@ -630,4 +717,482 @@ pub mod tests {
         let peer_info = get_peer(&mut dht_b, &*PEER_C).unwrap();
         assert_eq!(peer_info, peer_c_data);
     }
+
+    #[test]
+    fn test_rrdht_this_peer() {
+        enable_logging_for_test(true);
+        let mut dht = new_dht_wrapper(false, &*PEER_A);
+        let this_peer = get_this_peer(&mut dht);
+        assert_eq!(this_peer.peer_name, *PEER_A);
+    }
+
+    #[test]
+    fn test_rrdht_hold_entry_in_arc() {
+        enable_logging_for_test(true);
+        let mut dht_b = new_dht_wrapper(false, &*PEER_B);
+        let mut ud = DhtData::new();
+        let entry_data =
+            create_EntryData(&*ENTRY_ADDRESS_1, &*ASPECT_ADDRESS_1, &*ASPECT_CONTENT_1);
+        let bundle = create_rrdht_broadcast_bundle(&entry_data);
+        dht_b
+            .publish(
+                test_span(),
+                DhtRequestToChild::HandleGossip(RemoteGossipBundleData {
+                    from_peer_name: (*PEER_A).clone(),
+                    bundle: bundle.into(),
+                }),
+            )
+            .unwrap();
+        dht_b.process(&mut ud).unwrap();
+        // Full arc by default: should be asked to hold it
+        let did_get_hold_entry = drain_payloads(&mut dht_b).into_iter().any(|payload| {
+            if let DhtRequestToParent::HoldEntryRequested { entry, .. } = payload {
+                assert_eq!(entry, entry_data);
+                return true;
+            }
+            false
+        });
+        assert!(did_get_hold_entry);
+    }
+
+    #[test]
+    fn test_rrdht_skip_entry_outside_arc() {
+        enable_logging_for_test(true);
+        let mut dht_b = new_rrdht_wrapper_with_radius(&*PEER_B, 0);
+        let mut ud = DhtData::new();
+        let entry_data =
+            create_EntryData(&*ENTRY_ADDRESS_1, &*ASPECT_ADDRESS_1, &*ASPECT_CONTENT_1);
+        let bundle = create_rrdht_broadcast_bundle(&entry_data);
+        dht_b
+            .publish(
+                test_span(),
+                DhtRequestToChild::HandleGossip(RemoteGossipBundleData {
+                    from_peer_name: (*PEER_A).clone(),
+                    bundle: bundle.into(),
+                }),
+            )
+            .unwrap();
+        dht_b.process(&mut ud).unwrap();
+        // Zero length arc: should never be asked to hold it
+        for payload in drain_payloads(&mut dht_b) {
+            if let DhtRequestToParent::HoldEntryRequested { .. } = payload {
+                panic!("Should not hold entry outside of storage arc");
+            }
+        }
+    }
+
+    #[test]
+    fn test_rrdht_gossip_entry_only_to_peers_holding_it() {
+        enable_logging_for_test(true);
+        let mut dht_a = new_dht_wrapper(false, &*PEER_A);
+        let mut dht_b = new_rrdht_wrapper_with_radius(&*PEER_B, 0);
+        let mut ud = DhtData::new();
+        // Get B to gossip its PeerData and storage arc, by having it hold C
+        dht_b
+            .publish(
+                test_span(),
+                DhtRequestToChild::HoldPeer(create_PeerData(&*PEER_C)),
+            )
+            .unwrap();
+        dht_b.process(&mut ud).unwrap();
+        let mut bundle_list = Vec::new();
+        for payload in drain_payloads(&mut dht_b) {
+            if let DhtRequestToParent::GossipTo(gossip_to) = payload {
+                if gossip_to.peer_name_list == vec![(*PEER_C).clone()] {
+                    bundle_list.push(gossip_to.bundle);
+                }
+            }
+        }
+        assert_eq!(bundle_list.len(), 1);
+        // A receives B's gossip and accepts to hold B
+        for bundle in bundle_list {
+            dht_a
+                .publish(
+                    test_span(),
+                    DhtRequestToChild::HandleGossip(RemoteGossipBundleData {
+                        from_peer_name: (*PEER_B).clone(),
+                        bundle,
+                    }),
+                )
+                .unwrap();
+        }
+        dht_a.process(&mut ud).unwrap();
+        for payload in drain_payloads(&mut dht_a) {
+            if let DhtRequestToParent::HoldPeerRequested(peer) = payload {
+                dht_a
+                    .publish(test_span(), DhtRequestToChild::HoldPeer(peer))
+                    .unwrap();
+            }
+        }
+        dht_a.process(&mut ud).unwrap();
+        assert!(get_peer(&mut dht_a, &*PEER_B).is_some());
+        let _ = drain_payloads(&mut dht_a);
+        // A broadcasts an entry: B does not hold anything so should not get it
+        let entry_data =
+            create_EntryData(&*ENTRY_ADDRESS_1, &*ASPECT_ADDRESS_1, &*ASPECT_CONTENT_1);
+        dht_a
+            .publish(test_span(), DhtRequestToChild::BroadcastEntry(entry_data))
+            .unwrap();
+        dht_a.process(&mut ud).unwrap();
+        for payload in drain_payloads(&mut dht_a) {
+            if let DhtRequestToParent::GossipTo(gossip_to) = payload {
+                assert!(!gossip_to.peer_name_list.contains(&*PEER_B));
+            }
+        }
+        // A still bookkeeps it
+        assert_eq!(get_entry_address_list(&mut dht_a).len(), 1);
+    }
+
+    #[test]
+    fn test_rrdht_storage_arc() {
+        enable_logging_for_test(true);
+        let mut dht = new_dht_wrapper(false, &*PEER_A);
+        let storage_arc = get_storage_arc(&mut dht);
+        // Alone in the network: hold everything
+        assert_eq!(storage_arc.storage_arc.radius(), ARC_RADIUS_MAX);
+        assert_eq!(storage_arc.estimated_network_size, 1.0);
+    }
+
+    #[test]
+    fn test_rrdht_shrink_arc_when_over_replicated() {
+        enable_logging_for_test(true);
+        let config = DhtConfig::new(&*PEER_A)
+            .with_target_r_value(0.5, 1.0)
+            .with_arc_resize_interval(0);
+        let dht = RrDht::new_with_config(&config, None).unwrap();
+        let mut dht_a = Detach::new(ChildDhtWrapperDyn::new(dht, "dht_parent_"));
+        let mut ud = DhtData::new();
+        dht_a
+            .publish(
+                test_span(),
+                DhtRequestToChild::HoldPeer(create_PeerData(&*PEER_B)),
+            )
+            .unwrap();
+        dht_a.process(&mut ud).unwrap();
+        dht_a.process(&mut ud).unwrap();
+        // Two full arcs is more redundancy than targeted
+        let storage_arc = get_storage_arc(&mut dht_a);
+        assert!(storage_arc.storage_arc.radius() < ARC_RADIUS_MAX);
+        assert!(storage_arc.estimated_network_size > 1.0);
+    }
+
+    #[test]
+    fn test_rrdht_gossip_arc_sequence() {
+        enable_logging_for_test(true);
+        let mut dht_a = new_dht_wrapper(false, &*PEER_A);
+        let mut dht_b = new_dht_wrapper(false, &*PEER_B);
+        let mut ud = DhtData::new();
+        // A holds an entry B does not know about
+        let entry_data =
+            create_EntryData(&*ENTRY_ADDRESS_1, &*ASPECT_ADDRESS_1, &*ASPECT_CONTENT_1);
+        dht_a
+            .publish(
+                test_span(),
+                DhtRequestToChild::BroadcastEntry(entry_data.clone()),
+            )
+            .unwrap();
+        dht_a.process(&mut ud).unwrap();
+        let _ = drain_payloads(&mut dht_a);
+        // B opens a gossip sequence over the full arc, listing nothing
+        let arc_request = P2pMessage::MsgGspArcRequest(create_gsp_arc(
+            Arc::new(0.into(), ARC_LENGTH_MAX),
+            Vec::new(),
+        ));
+        let bundle = create_rrdht_gsp_bundle(&arc_request);
+        handle_gossip_bundles(&mut dht_a, &*PEER_B, vec![bundle]);
+        // A responds with the aspects B did not list
+        let bundle_list = take_gossip_bundles_to(&mut dht_a, &*PEER_B);
+        assert_eq!(bundle_list.len(), 1);
+        // B asks A for the missing aspect data
+        handle_gossip_bundles(&mut dht_b, &*PEER_A, bundle_list);
+        let bundle_list = take_gossip_bundles_to(&mut dht_b, &*PEER_A);
+        assert_eq!(bundle_list.len(), 1);
+        handle_gossip_bundles(&mut dht_a, &*PEER_B, bundle_list);
+        // A fetches the data from its owner
+        let mut did_request_entry = false;
+        for mut request in dht_a.drain_messages() {
+            if let DhtRequestToParent::RequestEntry(entry_address) =
+                request.take_message().expect("exists")
+            {
+                assert_eq!(entry_address, *ENTRY_ADDRESS_1);
+                request
+                    .respond(Ok(DhtRequestToParentResponse::RequestEntry(
+                        entry_data.clone(),
+                    )))
+                    .unwrap();
+                did_request_entry = true;
+            }
+        }
+        assert!(did_request_entry);
+        dht_a.process(&mut ud).unwrap();
+        // A sends the aspect data to B, which should be asked to hold it
+        let bundle_list = take_gossip_bundles_to(&mut dht_a, &*PEER_B);
+        assert_eq!(bundle_list.len(), 1);
+        handle_gossip_bundles(&mut dht_b, &*PEER_A, bundle_list);
+        let mut did_get_hold_entry = false;
+        for payload in drain_payloads(&mut dht_b) {
+            if let DhtRequestToParent::HoldEntryRequested { entry, .. } = payload {
+                assert_eq!(entry, entry_data);
+                did_get_hold_entry = true;
+            }
+        }
+        assert!(did_get_hold_entry);
+    }
+
+    #[test]
+    fn test_rrdht_drop_malformed_gossip() {
+        enable_logging_for_test(true);
+        let mut dht = new_dht_wrapper(false, &*PEER_A);
+        let mut garbage_gsp = Vec::new();
+        RrDhtGossipForTest::Gsp(vec![0xff, 0x00, 0x13].into())
+            .serialize(&mut rmp_serde::Serializer::new(&mut garbage_gsp))
+            .unwrap();
+        // neither undecodable rmp nor undecodable capnp takes the DHT down
+        handle_gossip_bundles(
+            &mut dht,
+            &*PEER_B,
+            vec![vec![0xc1, 0x42].into(), garbage_gsp.into()],
+        );
+        assert_eq!(get_this_peer(&mut dht).peer_name, *PEER_A);
+    }
+
+    #[test]
+    fn test_mirror_drop_malformed_gossip() {
+        enable_logging_for_test(true);
+        let mut dht = new_dht_wrapper(true, &*PEER_A);
+        let mut garbage_gsp = Vec::new();
+        MirrorGossipForTest::Gsp(vec![0xff, 0x00, 0x13].into())
+            .serialize(&mut rmp_serde::Serializer::new(&mut garbage_gsp))
+            .unwrap();
+        // neither undecodable rmp nor undecodable capnp takes the DHT down
+        handle_gossip_bundles(
+            &mut dht,
+            &*PEER_B,
+            vec![vec![0xc1, 0x42].into(), garbage_gsp.into()],
+        );
+        assert_eq!(get_this_peer(&mut dht).peer_name, *PEER_A);
+    }
+
+    #[test]
+    fn test_mirror_gossip_only_new_aspects_to_synced_peers() {
+        enable_logging_for_test(true);
+        let config = DhtConfig::new(&*PEER_A).with_gossip_interval(0);
+        let mut dht = new_mirror_wrapper_with_peer_b(&config);
+        let entry_1 = create_EntryData(&*ENTRY_ADDRESS_1, &*ASPECT_ADDRESS_1, &*ASPECT_CONTENT_1);
+        let entry_2 = create_EntryData(&*ENTRY_ADDRESS_2, &*ASPECT_ADDRESS_2, &*ASPECT_CONTENT_2);
+        // B was never synced with, so it gets everything we hold
+        let gsp_list = hold_and_gossip_arc_requests(&mut dht, Some(entry_1));
+        assert_eq!(gsp_list.len(), 1);
+        assert_eq!(gsp_list[0].aspect_constraint_time.gte_epoch_ms, 0);
+        assert_eq!(
+            read_aspect_hash_list(&gsp_list[0].aspect_hash_list).unwrap(),
+            vec![(
+                (*ENTRY_ADDRESS_1).clone(),
+                vec![(*ASPECT_ADDRESS_1).clone()]
+            )],
+        );
+        // and again until it acknowledges it
+        let gsp_list = hold_and_gossip_arc_requests(&mut dht, None);
+        assert_eq!(gsp_list.len(), 1);
+        assert_eq!(gsp_list[0].aspect_constraint_time.gte_epoch_ms, 0);
+        acknowledge_arc_request(&mut dht);
+        // then only what we got since the last round
+        let gsp_list = hold_and_gossip_arc_requests(&mut dht, Some(entry_2));
+        assert_eq!(gsp_list.len(), 1);
+        assert!(gsp_list[0].aspect_constraint_time.gte_epoch_ms > 0);
+        assert_eq!(
+            read_aspect_hash_list(&gsp_list[0].aspect_hash_list).unwrap(),
+            vec![(
+                (*ENTRY_ADDRESS_2).clone(),
+                vec![(*ASPECT_ADDRESS_2).clone()]
+            )],
+        );
+        // and nothing when nothing changed
+        assert!(hold_and_gossip_arc_requests(&mut dht, None).is_empty());
+    }
+
+    #[test]
+    fn test_mirror_gossip_everything_to_synced_peers_periodically() {
+        enable_logging_for_test(true);
+        let config = DhtConfig::new(&*PEER_A)
+            .with_gossip_interval(0)
+            .with_full_sync_rounds(1);
+        let mut dht = new_mirror_wrapper_with_peer_b(&config);
+        let entry_1 = create_EntryData(&*ENTRY_ADDRESS_1, &*ASPECT_ADDRESS_1, &*ASPECT_CONTENT_1);
+        let gsp_list = hold_and_gossip_arc_requests(&mut dht, Some(entry_1));
+        assert_eq!(gsp_list.len(), 1);
+        acknowledge_arc_request(&mut dht);
+        // B acknowledged, but every round is a full round
+        let gsp_list = hold_and_gossip_arc_requests(&mut dht, None);
+        assert_eq!(gsp_list.len(), 1);
+        assert_eq!(gsp_list[0].aspect_constraint_time.gte_epoch_ms, 0);
+        assert_eq!(
+            read_aspect_hash_list(&gsp_list[0].aspect_hash_list).unwrap(),
+            vec![(
+                (*ENTRY_ADDRESS_1).clone(),
+                vec![(*ASPECT_ADDRESS_1).clone()]
+            )],
+        );
+    }
+
+    #[test]
+    fn test_mirror_acknowledge_full_arc_requests() {
+        enable_logging_for_test(true);
+        let mut dht = new_dht_wrapper(true, &*PEER_A);
+        // B lists nothing and we hold nothing, still we let it know we got its list
+        let arc_request = P2pMessage::MsgGspArcRequest(create_gsp_arc(
+            Arc::new(0.into(), ARC_LENGTH_MAX),
+            Vec::new(),
+        ));
+        let bundle = create_mirror_gsp_bundle(&arc_request);
+        handle_gossip_bundles(&mut dht, &*PEER_B, vec![bundle]);
+        let did_acknowledge =
+            take_gossip_bundles_to(&mut dht, &*PEER_B)
+                .into_iter()
+                .any(|bundle| {
+                    let mut de = rmp_serde::Deserializer::new(&bundle[..]);
+                    match Deserialize::deserialize(&mut de).unwrap() {
+                        MirrorGossipForTest::Gsp(bytes) => {
+                            match P2pMessage::from_bytes(bytes.into()) {
+                                Ok(P2pMessage::MsgGspArcResponse(gsp)) => {
+                                    gsp.aspect_hash_list.is_empty()
+                                }
+                                _ => false,
+                            }
+                        }
+                        MirrorGossipForTest::Peer(_) => false,
+                    }
+                });
+        assert!(did_acknowledge);
+    }
+
+    /// A MirrorDht configured with `config` knowing about PEER_B
+    fn new_mirror_wrapper_with_peer_b(config: &DhtConfig) -> Detach<ChildDhtWrapperDyn<DhtData>> {
+        let dht = MirrorDht::new_with_config(config, None).unwrap();
+        let mut dht = Detach::new(ChildDhtWrapperDyn::new(dht, "dht_parent_"));
+        dht.publish(
+            test_span(),
+            DhtRequestToChild::HoldPeer(create_PeerData(&*PEER_B)),
+        )
+        .unwrap();
+        dht
+    }
+
+    /// Hold `entry_data` if any, run a gossip round and return the MsgGspArcRequest to PEER_B
+    fn hold_and_gossip_arc_requests(
+        dht: &mut Detach<ChildDhtWrapperDyn<DhtData>>,
+        entry_data: Option<EntryData>,
+    ) -> Vec<MsgGspArc> {
+        if let Some(entry_data) = entry_data {
+            dht.publish(
+                test_span(),
+                DhtRequestToChild::HoldEntryAspectAddress(entry_data),
+            )
+            .unwrap();
+        }
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        dht.process(&mut DhtData::new()).unwrap();
+        take_gossip_bundles_to(dht, &*PEER_B)
+            .into_iter()
+            .filter_map(|bundle| {
+                let mut de = rmp_serde::Deserializer::new(&bundle[..]);
+                match Deserialize::deserialize(&mut de).unwrap() {
+                    MirrorGossipForTest::Gsp(bytes) => {
+                        match P2pMessage::from_bytes(bytes.into()).unwrap() {
+                            P2pMessage::MsgGspArcRequest(gsp) => Some(gsp),
+                            _ => None,
+                        }
+                    }
+                    MirrorGossipForTest::Peer(_) => None,
+                }
+            })
+            .collect::<Vec<_>>()
+    }
+
+    /// Have PEER_B acknowledge our full list, holding nothing more, and drop what we sent since
+    fn acknowledge_arc_request(dht: &mut Detach<ChildDhtWrapperDyn<DhtData>>) {
+        let arc_response = P2pMessage::MsgGspArcResponse(create_gsp_arc(
+            Arc::new(0.into(), ARC_LENGTH_MAX),
+            Vec::new(),
+        ));
+        handle_gossip_bundles(dht, &*PEER_B, vec![create_mirror_gsp_bundle(&arc_response)]);
+        let _ = drain_payloads(dht);
+    }
+
+    /// Feed gossip bundles from a remote peer to a DHT
+    fn handle_gossip_bundles(
+        dht: &mut Detach<ChildDhtWrapperDyn<DhtData>>,
+        from_peer_name: &Lib3hUri,
+        bundle_list: Vec<Opaque>,
+    ) {
+        for bundle in bundle_list {
+            dht.publish(
+                test_span(),
+                DhtRequestToChild::HandleGossip(RemoteGossipBundleData {
+                    from_peer_name: from_peer_name.clone(),
+                    bundle,
+                }),
+            )
+            .unwrap();
+        }
+        dht.process(&mut DhtData::new()).unwrap();
+    }
+
+    /// Drain all requests from a DHT and return the bundles gossiped to a peer
+    fn take_gossip_bundles_to(
+        dht: &mut Detach<ChildDhtWrapperDyn<DhtData>>,
+        to_peer_name: &Lib3hUri,
+    ) -> Vec<Opaque> {
+        drain_payloads(dht)
+            .into_iter()
+            .filter_map(|payload| match payload {
+                DhtRequestToParent::GossipTo(gossip_to) => {
+                    if gossip_to.peer_name_list.contains(to_peer_name) {
+                        Some(gossip_to.bundle)
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn create_rrdht_broadcast_bundle(entry_data: &EntryData) -> Opaque {
+        create_rrdht_gsp_bundle(&P2pMessage::MsgGspAspectBroadcast(MsgGspAspectBroadcast {
+            aspect_data_list: vec![entry_to_aspect_data_list(entry_data, None)],
+        }))
+    }
+
+    fn create_rrdht_gsp_bundle(message: &P2pMessage) -> Opaque {
+        let mut bundle = Vec::new();
+        RrDhtGossipForTest::Gsp(message.to_bytes().into())
+            .serialize(&mut rmp_serde::Serializer::new(&mut bundle))
+            .unwrap();
+        bundle.into()
+    }
+
+    fn create_mirror_gsp_bundle(message: &P2pMessage) -> Opaque {
+        let mut bundle = Vec::new();
+        MirrorGossipForTest::Gsp(message.to_bytes().into())
+            .serialize(&mut rmp_serde::Serializer::new(&mut bundle))
+            .unwrap();
+        bundle.into()
+    }
+
+    /// Mirror of MirrorDht's private gossip enum, for crafting and reading bundles
+    #[derive(Serialize, Deserialize)]
+    #[allow(dead_code)]
+    enum MirrorGossipForTest {
+        Gsp(Opaque),
+        Peer(PeerData),
+    }
+
+    /// Mirror of RrDht's private gossip enum, for crafting bundles
+    #[derive(Serialize)]
+    #[allow(dead_code)]
+    enum RrDhtGossipForTest {
+        Peer(PeerData, String),
+        Gsp(Opaque),
+    }
 }
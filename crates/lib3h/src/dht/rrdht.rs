@@ -1,82 +1,924 @@
 use crate::{
-    dht::{dht_config::DhtConfig, dht_protocol::*, Lib3hUri},
-    error::Lib3hResult,
+    dht::{dht_config::DhtConfig, dht_gossip::*, dht_protocol::*},
+    error::{ErrorKind, Lib3hError, Lib3hResult},
+    rrdht_util::{
+        calc_location_for_bytes, calc_location_for_id, get_recommended_storage_arc_radius, Arc,
+        Location, RValuePeerRecord, RValuePeerRecordSet, ARC_LENGTH_MAX, ARC_RADIUS_MAX,
+    },
+    time,
 };
-use lib3h_protocol::{Address, DidWork};
-use std::collections::VecDeque;
+use detach::prelude::*;
+use holochain_tracing::Span;
+use lib3h_crypto_api::CryptoSystem;
+use lib3h_ghost_actor::prelude::*;
+use lib3h_p2p_protocol::p2p::*;
+use lib3h_protocol::{
+    data_types::{EntryData, Opaque},
+    types::*,
+    uri::Lib3hUri,
+    DidWork,
+};
+use lib3h_sodium::SodiumCryptoSystem;
+use rmp_serde::{Deserializer, Serializer};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+type HasTimedOut = bool;
+
+/// Enum holding all types of gossip messages used by RrDht
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+enum RrDhtGossip {
+    /// PeerData and the storage arc (canonical repr) that peer claims to hold
+    Peer(PeerData, String),
+    /// capnp encoded P2pMessage of the entry gossip sequence
+    Gsp(Opaque),
+}
+
+/// What we observed of a peer's reachability, for r-value estimation
+struct PeerUptime {
+    first_seen: u64,
+    last_seen: u64,
+    online_ms: u64,
+}
+
+impl PeerUptime {
+    fn new(now: u64) -> Self {
+        PeerUptime {
+            first_seen: now,
+            last_seen: now,
+            online_ms: 0,
+        }
+    }
+
+    /// We heard from the peer: the time since we last did counts as online
+    /// unless it was long enough for the peer to be considered timed-out
+    fn observe(&mut self, now: u64, timeout_threshold: u64) {
+        let gap = now.saturating_sub(self.last_seen);
+        if gap < timeout_threshold {
+            self.online_ms += gap;
+        }
+        self.last_seen = now;
+    }
+
+    /// - 0 = never reachable
+    /// - 1 = always reachable
+    fn uptime_0_to_1(&self, now: u64, timeout_threshold: u64) -> f64 {
+        let observed = now.saturating_sub(self.first_seen);
+        if observed == 0 {
+            return 1.0;
+        }
+        // give the benefit of the doubt until the peer times out
+        let pending = std::cmp::min(now.saturating_sub(self.last_seen), timeout_threshold);
+        ((self.online_ms + pending) as f64 / observed as f64).min(1.0)
+    }
+}
 
-/// RedRibbon DHT implementation
-/// TODO #167
+/// RedRibbon DHT implementation: sharded storage
+///  - Every peer and entry address is mapped to a u32 `Location`
+///  - This peer only holds entries whose location is within its storage arc
+///  - Entries are only gossiped to peers whose storage arc covers them
 pub struct RrDht {
-    /// FIFO of DhtCommands send to us
-    inbox: VecDeque<DhtCommand>,
-    ///
+    /// Crypto used for calculating locations
+    crypto: Box<dyn CryptoSystem>,
+    /// Storage of EntryData with empty aspect content
+    entry_list: HashMap<EntryHash, HashSet<AspectHash>>,
+    /// Monotonic Storage of PeerData
+    peer_map: HashMap<Lib3hUri, PeerData>,
+    /// Storage arc claimed by each peer, if we received it through gossip
+    peer_arc_map: HashMap<Lib3hUri, Arc>,
+    /// Track if peer timed out
+    timed_out_map: HashMap<Lib3hUri, HasTimedOut>,
+    /// Observed reachability of each peer
+    peer_uptime_map: HashMap<Lib3hUri, PeerUptime>,
+    /// PeerData of this peer
     this_peer: PeerData,
+    /// Location of this peer on the rrdht circle
+    this_location: Location,
+    /// Arc of locations this peer claims to hold, centered on this_location
+    storage_arc: Arc,
+    /// Total node count, as estimated on last storage arc resize
+    estimated_network_size: f64,
+    /// Keep track of last time this peer resized its storage arc
+    last_arc_resize: u64,
+    /// Keep track of last time this peer gossiped self to others
+    last_gossip_of_self: u64,
+    /// Store Dht config used by this peer
+    config: DhtConfig,
+
+    /// ghost stuff
+    endpoint_parent: Option<DhtEndpoint>,
+    endpoint_self: Detach<DhtEndpointWithContext<Self>>,
 }
 
+/// Constructors
 impl RrDht {
-    pub fn new() -> Self {
-        RrDht {
-            inbox: VecDeque::new(),
-            this_peer: PeerData {
-                peer_name: Lib3hUri::with_undefined(),
+    pub fn new(this_peer_name: &Lib3hUri) -> Box<DhtActor> {
+        let dht_config = DhtConfig::new(this_peer_name);
+        Self::new_with_config(&dht_config, None).expect("Failed creating default RrDht")
+    }
+
+    pub fn new_with_config(
+        config: &DhtConfig,
+        maybe_this_peer: Option<PeerData>,
+    ) -> Lib3hResult<Box<DhtActor>> {
+        let timestamp = time::since_epoch_ms();
+        let (endpoint_parent, endpoint_self) = create_ghost_channel();
+
+        let this_peer = match maybe_this_peer {
+            None => PeerData {
+                peer_name: config.this_peer_name(),
                 peer_location: Lib3hUri::with_undefined(),
-                timestamp: 0, // TODO #166
+                timestamp,
             },
-        }
+            Some(this_peer) => this_peer,
+        };
+
+        let crypto: Box<dyn CryptoSystem> = Box::new(SodiumCryptoSystem::new());
+        let this_location = calc_location_for_peer_name(&crypto, &this_peer.peer_name)?;
+        let storage_arc = Arc::new_radius(this_location, config.storage_arc_radius());
+        debug!(
+            "@RrDht@ {} located at {:?} holding {}",
+            this_peer.peer_name, this_location, storage_arc,
+        );
+
+        let this = RrDht {
+            crypto,
+            peer_map: HashMap::new(),
+            peer_arc_map: HashMap::new(),
+            timed_out_map: HashMap::new(),
+            peer_uptime_map: HashMap::new(),
+            entry_list: HashMap::new(),
+            this_peer,
+            this_location,
+            storage_arc,
+            estimated_network_size: 1.0,
+            last_arc_resize: timestamp,
+            last_gossip_of_self: timestamp,
+            config: config.clone(),
+            endpoint_parent: Some(endpoint_parent),
+            endpoint_self: Detach::new(
+                endpoint_self
+                    .as_context_endpoint_builder()
+                    .request_id_prefix("dht_to_parent_")
+                    .build(),
+            ),
+        };
+        Ok(Box::new(this))
     }
+}
 
-    pub fn new_with_raw_config(_config: &[u8]) -> Lib3hResult<Self> {
-        Ok(Self::new())
+#[allow(clippy::borrowed_box)]
+/// Calculate the location of a peer from its agent or node id.
+/// Ids that are not HCID strings (e.g. in tests) are hashed as raw bytes.
+fn calc_location_for_peer_name(
+    crypto: &Box<dyn CryptoSystem>,
+    peer_name: &Lib3hUri,
+) -> Lib3hResult<Location> {
+    let id = peer_name.path();
+    match calc_location_for_id(crypto, id) {
+        Ok(location) => Ok(location),
+        Err(_) => calc_location_for_bytes(crypto, id.as_bytes()),
     }
 }
 
-impl Dht for RrDht {
+// Dht Interface
+impl RrDht {
     // -- Peer info -- //
 
     fn get_peer_list(&self) -> Vec<PeerData> {
-        // FIXME
-        vec![]
-    }
-
-    fn get_peer(&self, _peer_name: &Lib3hUri) -> Option<PeerData> {
-        // FIXME
-        None
+        self.peer_map.values().cloned().collect()
     }
 
-    fn this_peer(&self) -> &PeerData {
-        &self.this_peer
+    fn get_peer(&self, peer_name: &Lib3hUri) -> Option<PeerData> {
+        if peer_name == &self.this_peer.peer_name {
+            return Some(self.this_peer.clone());
+        }
+        self.peer_map.get(peer_name).cloned()
     }
 
     // -- Entry -- //
 
-    fn get_entry_address_list(&self) -> Vec<&EntryHash> {
-        // FIXME
-        vec![]
+    fn get_entry_address_list(&self) -> Vec<EntryHash> {
+        self.entry_list.keys().cloned().collect()
     }
-    fn get_aspects_of(&self, _entry_address: &EntryHash) -> Option<Vec<AspectHash>> {
-        // FIXME
-        None
+
+    fn get_aspects_of(&self, entry_address: &EntryHash) -> Option<Vec<AspectHash>> {
+        self.entry_list
+            .get(entry_address)
+            .map(|set| set.iter().cloned().collect())
     }
+
     // -- Processing -- //
 
-    fn post(&mut self, cmd: DhtCommand) -> Lib3hResult<()> {
-        self.inbox.push_back(cmd);
-        Ok(())
+    /// Check for timed-out peers and periodically gossip self
+    fn internal_process(&mut self) -> Lib3hResult<(DidWork, Vec<DhtRequestToParent>)> {
+        let now = time::since_epoch_ms();
+        let mut outbox = Vec::new();
+        let mut did_work = false;
+        // Check if others timed-out
+        let mut timed_out_list = Vec::new();
+        for (peer_name, peer) in self.peer_map.iter() {
+            if peer_name == &self.this_peer.peer_name {
+                continue;
+            }
+            let has_timed_out = self
+                .timed_out_map
+                .get(peer_name)
+                .expect("Should always have time_out value for a peer");
+            if *has_timed_out {
+                continue;
+            }
+            if now.saturating_sub(peer.timestamp) > self.config.timeout_threshold() {
+                debug!(
+                    "@RrDht@ peer {} timed-out ({} > {})",
+                    peer_name,
+                    now - peer.timestamp,
+                    self.config.timeout_threshold()
+                );
+                outbox.push(DhtRequestToParent::PeerTimedOut(peer_name.clone()));
+                timed_out_list.push(peer_name.clone());
+                did_work = true;
+            }
+        }
+        for peer_name in timed_out_list {
+            self.timed_out_map.insert(peer_name, true);
+        }
+        // Check if must resize our storage arc
+        if now.saturating_sub(self.last_arc_resize) >= self.config.arc_resize_interval() {
+            self.last_arc_resize = now;
+            let mut resize_outbox = self.resize_storage_arc(now)?;
+            did_work = did_work || !resize_outbox.is_empty();
+            outbox.append(&mut resize_outbox);
+        }
+        // Check if must gossip self
+        if now.saturating_sub(self.last_gossip_of_self) > self.config.gossip_interval() {
+            self.last_gossip_of_self = now;
+            let gossip_data = self.gossip_self(self.get_other_peer_list());
+            if !gossip_data.peer_name_list.is_empty() {
+                outbox.push(DhtRequestToParent::GossipTo(gossip_data));
+                did_work = true;
+            }
+            // Also open an anti-entropy gossip sequence over our storage arc
+            if let Some(gossip_evt) = self.gossip_arc_request() {
+                outbox.push(gossip_evt);
+                did_work = true;
+            }
+        }
+        Ok((did_work, outbox))
     }
 
-    fn process(&mut self) -> Lib3hResult<(DidWork, Vec<DhtEvent>)> {
-        let outbox = Vec::new();
-        let mut did_work = false;
-        loop {
-            let cmd = match self.inbox.pop_front() {
-                None => break,
-                Some(msg) => msg,
+    /// Feed the peers we know about into the r-value estimator,
+    /// and grow or shrink our storage arc towards the target redundancy.
+    /// Entries that end up outside of a shrunk arc are pruned.
+    fn resize_storage_arc(&mut self, now: u64) -> Lib3hResult<Vec<DhtRequestToParent>> {
+        let timeout_threshold = self.config.timeout_threshold();
+        // Sample the peers within our own arc, or everyone if we hold nothing
+        let sample_arc = if self.storage_arc.length() == 0 {
+            Arc::new(0.into(), ARC_LENGTH_MAX)
+        } else {
+            self.storage_arc
+        };
+        let mut peer_record_set = RValuePeerRecordSet::default()
+            .arc_of_included_peer_records(sample_arc)
+            .push_peer_record(
+                RValuePeerRecord::default()
+                    .storage_arc(self.storage_arc)
+                    .uptime_0_to_1(1.0),
+            );
+        for peer_name in self.get_other_peer_list() {
+            let peer_arc = match self.peer_arc_map.get(&peer_name) {
+                Some(arc) => *arc,
+                None => Arc::new_radius(
+                    calc_location_for_peer_name(&self.crypto, &peer_name)?,
+                    ARC_RADIUS_MAX,
+                ),
             };
-            did_work = true;
-            trace!("RrDht.process(): {:?}", cmd)
-            // FIXME
+            if !sample_arc.contains_location(peer_arc.center()) {
+                continue;
+            }
+            let uptime = self
+                .peer_uptime_map
+                .get(&peer_name)
+                .map(|uptime| uptime.uptime_0_to_1(now, timeout_threshold))
+                .unwrap_or(0.0);
+            peer_record_set = peer_record_set.push_peer_record(
+                RValuePeerRecord::default()
+                    .storage_arc(peer_arc)
+                    .uptime_0_to_1(uptime),
+            );
         }
-        Ok((did_work, outbox))
+        self.estimated_network_size = peer_record_set.guess_total_node_count();
+        let radius = get_recommended_storage_arc_radius(
+            &peer_record_set,
+            self.config.target_minimum_r_value(),
+            self.config.target_maximum_r_value(),
+            Some(self.storage_arc.radius()),
+        );
+        let new_arc = Arc::new_radius(self.this_location, radius);
+        let mut outbox = Vec::new();
+        if new_arc == self.storage_arc {
+            return Ok(outbox);
+        }
+        debug!(
+            "@RrDht@ {} resizing storage arc {} -> {} (estimated network size: {})",
+            self.this_peer.peer_name, self.storage_arc, new_arc, self.estimated_network_size,
+        );
+        // Prune entries we are not holding anymore
+        if new_arc.length() < self.storage_arc.length() {
+            let mut pruned_list = Vec::new();
+            for entry_address in self.entry_list.keys() {
+                if !new_arc.contains_location(self.calc_entry_location(entry_address)?) {
+                    pruned_list.push(entry_address.clone());
+                }
+            }
+            for entry_address in pruned_list {
+                self.entry_list.remove(&entry_address);
+                outbox.push(DhtRequestToParent::EntryPruned(entry_address));
+            }
+        }
+        self.storage_arc = new_arc;
+        // Let everyone know about our new arc
+        let gossip_data = self.gossip_self(self.get_other_peer_list());
+        if !gossip_data.peer_name_list.is_empty() {
+            outbox.push(DhtRequestToParent::GossipTo(gossip_data));
+        }
+        Ok(outbox)
+    }
+}
+
+/// Internals
+impl RrDht {
+    /// Location of an entry on the rrdht circle
+    fn calc_entry_location(&self, entry_address: &EntryHash) -> Lib3hResult<Location> {
+        calc_location_for_bytes(&self.crypto, entry_address.to_string().as_bytes())
+    }
+
+    /// Check if an entry falls within the given arc
+    fn is_entry_in_arc(&self, entry_address: &EntryHash, arc: Arc) -> bool {
+        match self.calc_entry_location(entry_address) {
+            Ok(location) => arc.contains_location(location),
+            Err(_) => false,
+        }
+    }
+
+    /// Get all known peers except self
+    fn get_other_peer_list(&self) -> Vec<Lib3hUri> {
+        self.peer_map
+            .keys()
+            .filter(|peer_name| *peer_name != &self.this_peer.peer_name)
+            .cloned()
+            .collect()
+    }
+
+    /// Get all known peers, except self, that claim to hold the given location.
+    /// Peers we have not received a storage arc for are assumed to hold everything.
+    fn get_peers_holding(&self, location: Location) -> Vec<Lib3hUri> {
+        self.get_other_peer_list()
+            .into_iter()
+            .filter(|peer_name| match self.peer_arc_map.get(peer_name) {
+                None => true,
+                Some(arc) => arc.contains_location(location),
+            })
+            .collect()
+    }
+
+    fn serialize_gossip(gossip: &RrDhtGossip) -> Vec<u8> {
+        let mut buf = Vec::new();
+        gossip
+            .serialize(&mut Serializer::new(&mut buf))
+            .expect("RrDhtGossip serialization failed");
+        buf
+    }
+
+    /// Create gossipTo event of a P2pMessage of the entry gossip sequence
+    fn gossip_p2p_message(peer_name_list: Vec<Lib3hUri>, message: &P2pMessage) -> GossipToData {
+        let gossip = RrDhtGossip::Gsp(message.to_bytes().into());
+        GossipToData {
+            peer_name_list,
+            bundle: Self::serialize_gossip(&gossip).into(),
+        }
+    }
+
+    /// Create gossipTo event of your own PeerData and storage arc
+    fn gossip_self(&self, peer_name_list: Vec<Lib3hUri>) -> GossipToData {
+        let gossip = RrDhtGossip::Peer(self.this_peer.clone(), self.storage_arc.into());
+        GossipToData {
+            peer_name_list,
+            bundle: Self::serialize_gossip(&gossip).into(),
+        }
+    }
+
+    /// Return true if new peer or updated peer
+    fn add_peer(&mut self, peer_info: &PeerData) -> bool {
+        debug!(
+            "@RrDht@ {:?} Adding peer: {:?}",
+            self.this_peer.peer_name, peer_info
+        );
+        let now = time::since_epoch_ms();
+        let timeout_threshold = self.config.timeout_threshold();
+        self.peer_uptime_map
+            .entry(peer_info.peer_name.clone())
+            .or_insert_with(|| PeerUptime::new(now))
+            .observe(now, timeout_threshold);
+        match self.peer_map.get_mut(&peer_info.peer_name) {
+            None => {
+                self.peer_map
+                    .insert(peer_info.peer_name.clone(), peer_info.clone());
+                self.timed_out_map
+                    .insert(peer_info.peer_name.clone(), false);
+                true
+            }
+            Some(peer) => {
+                if peer_info.timestamp < peer.timestamp {
+                    debug!(
+                        "@RrDht@ Adding peer - BAD {:?} has earlier timestamp than {:?}",
+                        peer_info.timestamp, peer.timestamp
+                    );
+                    return false;
+                }
+                peer.timestamp = peer_info.timestamp;
+                if now.saturating_sub(peer.timestamp) < timeout_threshold {
+                    self.timed_out_map
+                        .insert(peer_info.peer_name.clone(), false);
+                }
+                true
+            }
+        }
+    }
+
+    /// Return true if peer is known and was not already timed-out
+    fn mark_peer_timed_out(&mut self, peer_name: &Lib3hUri) -> bool {
+        match self.timed_out_map.get_mut(peer_name) {
+            Some(has_timed_out) if !*has_timed_out => {
+                *has_timed_out = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Return aspect addresses diff between
+    /// known aspects and aspects in the entry argument
+    fn diff_aspects(&self, entry: &EntryData) -> HashSet<AspectHash> {
+        let aspect_address_set: HashSet<_> = entry
+            .aspect_list
+            .iter()
+            .map(|aspect| aspect.aspect_address.clone())
+            .collect();
+        match self.entry_list.get(&entry.entry_address) {
+            None => aspect_address_set,
+            Some(held_aspects) => aspect_address_set
+                .difference(held_aspects)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Add aspect addresses for an entry in our local storage.
+    /// Return true if at least one new aspect address was added.
+    fn add_entry_aspects(&mut self, entry: &EntryData) -> bool {
+        let diff = self.diff_aspects(entry);
+        if diff.is_empty() {
+            return false;
+        }
+        self.entry_list
+            .entry(entry.entry_address.clone())
+            .or_insert_with(HashSet::new)
+            .extend(diff);
+        true
+    }
+
+    /// Create GossipTo event for entry to all known peers holding its location
+    fn gossip_entry(&self, entry: &EntryData) -> Lib3hResult<Option<DhtRequestToParent>> {
+        let location = self.calc_entry_location(&entry.entry_address)?;
+        let peer_name_list = self.get_peers_holding(location);
+        if peer_name_list.is_empty() {
+            return Ok(None);
+        }
+        let gossip_evt = Self::gossip_p2p_message(
+            peer_name_list,
+            &P2pMessage::MsgGspAspectBroadcast(MsgGspAspectBroadcast {
+                aspect_data_list: vec![entry_to_aspect_data_list(entry, None)],
+            }),
+        );
+        debug!(
+            "@RrDht@ {:?} GossipTo: {:?}",
+            self.this_peer.peer_name, gossip_evt,
+        );
+        Ok(Some(DhtRequestToParent::GossipTo(gossip_evt)))
+    }
+
+    /// Create GossipTo event opening a gossip sequence with all known peers,
+    /// listing the aspects we hold within our storage arc
+    fn gossip_arc_request(&self) -> Option<DhtRequestToParent> {
+        let peer_name_list = self.get_other_peer_list();
+        if peer_name_list.is_empty() || self.storage_arc.length() == 0 {
+            return None;
+        }
+        let storage_arc = self.storage_arc;
+        let aspect_hash_list = aspect_hash_list_of(&self.entry_list, |entry_address| {
+            self.is_entry_in_arc(entry_address, storage_arc)
+        });
+        Some(DhtRequestToParent::GossipTo(Self::gossip_p2p_message(
+            peer_name_list,
+            &P2pMessage::MsgGspArcRequest(create_gsp_arc(storage_arc, aspect_hash_list)),
+        )))
+    }
+
+    /// Ask a peer for the aspects in `remote_list` we are missing within our storage arc
+    fn request_missing_aspects(
+        &mut self,
+        span: Span,
+        from_peer_name: &Lib3hUri,
+        remote_list: &[AspectHashList],
+    ) -> Lib3hResult<()> {
+        let storage_arc = self.storage_arc;
+        let missing = missing_aspect_hash_list(&self.entry_list, remote_list, |entry_address| {
+            self.is_entry_in_arc(entry_address, storage_arc)
+        })?;
+        if missing.is_empty() {
+            return Ok(());
+        }
+        self.endpoint_self.publish(
+            span,
+            DhtRequestToParent::GossipTo(Self::gossip_p2p_message(
+                vec![from_peer_name.clone()],
+                &P2pMessage::MsgGspAspectDataRequest(MsgGspAspectDataRequest {
+                    aspect_hash_list: missing,
+                }),
+            )),
+        )?;
+        Ok(())
+    }
+
+    /// Fetch the requested aspects from owner and send them back to the requesting peer
+    fn respond_aspect_data(
+        &mut self,
+        span: Span,
+        from_peer_name: &Lib3hUri,
+        aspect_hash_list: &[AspectHashList],
+    ) -> Lib3hResult<()> {
+        for (entry_address, aspect_address_list) in read_aspect_hash_list(aspect_hash_list)? {
+            if !self.entry_list.contains_key(&entry_address) {
+                continue;
+            }
+            let from_peer_name = from_peer_name.clone();
+            let span_response = span.child("send MsgGspAspectDataResponse");
+            self.endpoint_self.request(
+                span.child("request DhtRequestToParent::RequestEntry"),
+                DhtRequestToParent::RequestEntry(entry_address),
+                Box::new(move |me, response| {
+                    let entry = match response {
+                        GhostCallbackData::Response(Ok(
+                            DhtRequestToParentResponse::RequestEntry(entry),
+                        )) => entry,
+                        _ => {
+                            warn!("@RrDht@ could not fetch aspect data: {:?}", response);
+                            return Ok(());
+                        }
+                    };
+                    let gossip_evt = Self::gossip_p2p_message(
+                        vec![from_peer_name],
+                        &P2pMessage::MsgGspAspectDataResponse(MsgGspAspectDataResponse {
+                            aspect_data_list: vec![entry_to_aspect_data_list(
+                                &entry,
+                                Some(&aspect_address_list),
+                            )],
+                        }),
+                    );
+                    me.endpoint_self
+                        .publish(span_response, DhtRequestToParent::GossipTo(gossip_evt))?;
+                    Ok(())
+                }),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Handle a P2pMessage of the entry gossip sequence
+    fn handle_gossiped_p2p_message(
+        &mut self,
+        span: Span,
+        from_peer_name: Lib3hUri,
+        message: P2pMessage,
+    ) -> Lib3hResult<()> {
+        match message {
+            // Ask for what we are missing, and tell about what they did not list
+            P2pMessage::MsgGspArcRequest(gsp) => {
+                self.request_missing_aspects(
+                    span.child("send MsgGspAspectDataRequest"),
+                    &from_peer_name,
+                    &gsp.aspect_hash_list,
+                )?;
+                let constraint_arc = constraint_to_arc(&gsp.aspect_constraint_arc);
+                let unlisted = unlisted_aspect_hash_list(
+                    &self.entry_list,
+                    &gsp.aspect_hash_list,
+                    |entry_address| self.is_entry_in_arc(entry_address, constraint_arc),
+                )?;
+                if !unlisted.is_empty() {
+                    self.endpoint_self.publish(
+                        span.child("send MsgGspArcResponse"),
+                        DhtRequestToParent::GossipTo(Self::gossip_p2p_message(
+                            vec![from_peer_name],
+                            &P2pMessage::MsgGspArcResponse(create_gsp_arc(
+                                constraint_arc,
+                                unlisted,
+                            )),
+                        )),
+                    )?;
+                }
+            }
+            P2pMessage::MsgGspArcResponse(gsp) => {
+                self.request_missing_aspects(span, &from_peer_name, &gsp.aspect_hash_list)?;
+            }
+            P2pMessage::MsgGspAspectDataRequest(request) => {
+                self.respond_aspect_data(span, &from_peer_name, &request.aspect_hash_list)?;
+            }
+            P2pMessage::MsgGspAspectDataResponse(MsgGspAspectDataResponse { aspect_data_list })
+            | P2pMessage::MsgGspAspectBroadcast(MsgGspAspectBroadcast { aspect_data_list }) => {
+                for aspect_data in aspect_data_list {
+                    let entry = aspect_data_list_to_entry(&aspect_data)?;
+                    self.handle_gossiped_entry(span.follower("handle_gossiped_entry"), entry)?;
+                }
+            }
+            _ => warn!(
+                "@RrDht@ unexpected gossip from {}: {:?}",
+                from_peer_name, message
+            ),
+        }
+        Ok(())
+    }
+
+    /// Handle an entry received through gossip:
+    /// Only ask owner to hold the new aspects if the entry is in our storage arc
+    fn handle_gossiped_entry(&mut self, span: Span, entry: EntryData) -> Lib3hResult<()> {
+        let location = self.calc_entry_location(&entry.entry_address)?;
+        if !self.storage_arc.contains_location(location) {
+            trace!(
+                "@RrDht@ entry {} at {:?} is outside of our arc {} - skipping",
+                entry.entry_address,
+                location,
+                self.storage_arc,
+            );
+            return Ok(());
+        }
+        let diff = self.diff_aspects(&entry);
+        if diff.is_empty() {
+            return Ok(());
+        }
+        let aspect_list = entry
+            .aspect_list
+            .into_iter()
+            .filter(|aspect| diff.contains(&aspect.aspect_address))
+            .collect();
+        self.endpoint_self.publish(
+            span,
+            DhtRequestToParent::HoldEntryRequested {
+                from_peer_name: self.this_peer.peer_name.clone(),
+                entry: EntryData {
+                    entry_address: entry.entry_address,
+                    aspect_list,
+                },
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Handle a peer received through gossip
+    fn handle_gossiped_peer(
+        &mut self,
+        span: Span,
+        gossiped_peer: PeerData,
+        storage_arc: String,
+    ) -> Lib3hResult<()> {
+        let storage_arc = Arc::try_new_repr(&storage_arc)?;
+        self.peer_arc_map
+            .insert(gossiped_peer.peer_name.clone(), storage_arc);
+        match self.get_peer(&gossiped_peer.peer_name) {
+            None => {
+                self.endpoint_self
+                    .publish(span, DhtRequestToParent::HoldPeerRequested(gossiped_peer))?;
+            }
+            Some(known_peer) => {
+                if gossiped_peer.timestamp > known_peer.timestamp {
+                    let _is_new_content = self.add_peer(&gossiped_peer);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Impl DhtActor interface
+impl
+    GhostActor<
+        DhtRequestToParent,
+        DhtRequestToParentResponse,
+        DhtRequestToChild,
+        DhtRequestToChildResponse,
+        Lib3hError,
+    > for RrDht
+{
+    fn take_parent_endpoint(&mut self) -> Option<DhtEndpoint> {
+        std::mem::replace(&mut self.endpoint_parent, None)
+    }
+
+    fn process_concrete(&mut self) -> GhostResult<WorkWasDone> {
+        detach_run!(&mut self.endpoint_self, |es| es.process(self))?;
+        for request in self.endpoint_self.as_mut().drain_messages() {
+            // a malformed gossip bundle from a remote node must not bring us down
+            if let Err(e) = self.handle_request_from_parent(request) {
+                error!("@RrDht@ dropping request: {:?}", e);
+            }
+        }
+        let (did_work, command_list) = self.internal_process()?;
+        for command in command_list {
+            self.endpoint_self.publish(
+                Span::todo("get span from engine.process() in ghost-actor-v2 ?"),
+                command,
+            )?;
+        }
+        Ok(did_work.into())
+    }
+}
+
+impl RrDht {
+    fn handle_request_from_parent(&mut self, mut request: DhtToChildMessage) -> Lib3hResult<()> {
+        let span = request.span().child("handle_request_from_parent");
+        let msg = request.take_message().expect("exists");
+        match msg {
+            // Received gossip from remote node. Bundle must be a serialized RrDhtGossip
+            DhtRequestToChild::HandleGossip(msg) => {
+                trace!("@RrDht@ HandleGossip: {:?}", msg);
+                let mut de = Deserializer::new(&msg.bundle[..]);
+                let maybe_gossip: Result<RrDhtGossip, rmp_serde::decode::Error> =
+                    Deserialize::deserialize(&mut de);
+                match maybe_gossip {
+                    Err(e) => {
+                        error!("Failed to deserialize gossip.");
+                        return Err(Lib3hError::new(ErrorKind::RmpSerdeDecodeError(e)));
+                    }
+                    Ok(RrDhtGossip::Peer(peer, storage_arc)) => {
+                        self.handle_gossiped_peer(span, peer, storage_arc)?
+                    }
+                    Ok(RrDhtGossip::Gsp(bytes)) => {
+                        let message = P2pMessage::from_bytes(bytes.into())?;
+                        self.handle_gossiped_p2p_message(span, msg.from_peer_name, message)?
+                    }
+                }
+            }
+
+            // Owner is asking us to hold a peer info
+            DhtRequestToChild::HoldPeer(new_peer_data) => {
+                trace!("@RrDht@ HoldPeer: {:?}", new_peer_data);
+                let others_list = self.get_other_peer_list();
+                if !self.add_peer(&new_peer_data) {
+                    return Ok(());
+                }
+                // Gossip new peer to everyone, peer discovery is not sharded
+                let peer_arc = self
+                    .peer_arc_map
+                    .get(&new_peer_data.peer_name)
+                    .cloned()
+                    .unwrap_or_else(|| Arc::new(0.into(), ARC_LENGTH_MAX));
+                let gossip = RrDhtGossip::Peer(new_peer_data.clone(), peer_arc.into());
+                self.endpoint_self.publish(
+                    span.child("send event DhtRequestToParent::GossipTo all the received PeerData"),
+                    DhtRequestToParent::GossipTo(GossipToData {
+                        peer_name_list: others_list,
+                        bundle: Self::serialize_gossip(&gossip).into(),
+                    }),
+                )?;
+                // Gossip back our own PeerData (but not to ourself)
+                if new_peer_data.peer_name != self.this_peer.peer_name {
+                    let gossip_data = self.gossip_self(vec![new_peer_data.peer_name.clone()]);
+                    self.endpoint_self.publish(
+                        span.child("send event DhtRequestToParent::GossipTo our own PeerData"),
+                        DhtRequestToParent::GossipTo(gossip_data),
+                    )?;
+                }
+            }
+
+            // Owner is holding some entry. Store its address for bookkeeping
+            // and gossip it to the peers whose arc covers it.
+            DhtRequestToChild::HoldEntryAspectAddress(entry) => {
+                trace!("@RrDht@ HoldEntryAspectAddress: {:?}", entry);
+                // if its shallow, ask for actual data
+                if !entry.aspect_list.is_empty() && entry.aspect_list[0].aspect.is_empty() {
+                    self.endpoint_self.publish(
+                        span.follower("DhtRequestToChild::HoldEntryAspectAddress"),
+                        DhtRequestToParent::RequestEntry(entry.entry_address),
+                    )?;
+                    return Ok(());
+                }
+                if !self.add_entry_aspects(&entry) {
+                    return Ok(());
+                }
+                if let Some(gossip_evt) = self.gossip_entry(&entry)? {
+                    self.endpoint_self.publish(
+                        span.follower("DhtRequestToChild::HoldEntryAspectAddress"),
+                        gossip_evt,
+                    )?;
+                }
+            }
+
+            // Owner has some entry and wants it stored on the network
+            DhtRequestToChild::BroadcastEntry(entry) => {
+                trace!("@RrDht@ BroadcastEntry: {:?}", entry);
+                if !self.add_entry_aspects(&entry) {
+                    return Ok(());
+                }
+                if let Some(gossip_evt) = self.gossip_entry(&entry)? {
+                    self.endpoint_self.publish(
+                        span.follower("DhtRequestToChild::BroadcastEntry"),
+                        gossip_evt,
+                    )?;
+                }
+            }
+
+            // Owner is not holding this entry anymore, stop bookkeeping it
+            DhtRequestToChild::DropEntryAddress(entry_address) => {
+                self.entry_list.remove(&entry_address);
+            }
+
+            DhtRequestToChild::UpdateAdvertise(peer_location) => {
+                trace!(
+                    "({}).DhtRequestToChild::UpdateAdvertise: {:?}",
+                    self.config.this_peer_name(),
+                    peer_location
+                );
+                self.this_peer.peer_location = peer_location;
+            }
+
+            // Owner is not getting pongs back from this peer anymore
+            DhtRequestToChild::PeerUnresponsive(peer_name) => {
+                if self.mark_peer_timed_out(&peer_name) {
+                    debug!("@RrDht@ peer {} stopped answering pings", peer_name);
+                    self.endpoint_self.publish(
+                        span.follower("DhtRequestToChild::PeerUnresponsive"),
+                        DhtRequestToParent::PeerTimedOut(peer_name),
+                    )?;
+                }
+            }
+
+            DhtRequestToChild::RequestPeer(peer_name) => {
+                let maybe_peer = self.get_peer(&peer_name);
+                request.respond(Ok(DhtRequestToChildResponse::RequestPeer(maybe_peer)))?;
+            }
+
+            DhtRequestToChild::RequestPeerList => {
+                let list = self.get_peer_list();
+                request.respond(Ok(DhtRequestToChildResponse::RequestPeerList(list)))?;
+            }
+
+            DhtRequestToChild::RequestThisPeer => {
+                request.respond(Ok(DhtRequestToChildResponse::RequestThisPeer(
+                    self.this_peer.clone(),
+                )))?;
+            }
+
+            DhtRequestToChild::RequestEntryAddressList => {
+                let list = self.get_entry_address_list();
+                request.respond(Ok(DhtRequestToChildResponse::RequestEntryAddressList(list)))?;
+            }
+
+            DhtRequestToChild::RequestAspectsOf(address) => {
+                let maybe_list = self.get_aspects_of(&address);
+                request.respond(Ok(DhtRequestToChildResponse::RequestAspectsOf(maybe_list)))?;
+            }
+
+            DhtRequestToChild::RequestStorageArc => {
+                request.respond(Ok(DhtRequestToChildResponse::RequestStorageArc(
+                    StorageArcData {
+                        storage_arc: self.storage_arc,
+                        estimated_network_size: self.estimated_network_size,
+                    },
+                )))?;
+            }
+
+            // Ask owner to respond to self
+            DhtRequestToChild::RequestEntry(entry_address) => {
+                self.endpoint_self.request(
+                    span.child("DhtRequestToChild::RequestEntry"),
+                    DhtRequestToParent::RequestEntry(entry_address),
+                    Box::new(|_me, response| {
+                        match response {
+                            GhostCallbackData::Response(Ok(
+                                DhtRequestToParentResponse::RequestEntry(entry_response),
+                            )) => request.respond(Ok(DhtRequestToChildResponse::RequestEntry(
+                                entry_response,
+                            )))?,
+                            GhostCallbackData::Response(Err(e)) => request.respond(Err(e))?,
+                            GhostCallbackData::Timeout(bt) => {
+                                request.respond(Err(format!("timeout: {:?}", bt).into()))?
+                            }
+                        }
+                        Ok(())
+                    }),
+                )?;
+            }
+        };
+        Ok(())
     }
 }
@@ -0,0 +1,203 @@
+//! Discovery of seed nodes published in the TXT records of a domain,
+//! each string of the records being the uri of a node, e.g.
+//! `_seeds.holo.host. TXT "wss://203.0.113.7:64159"`.
+//!
+//! The query goes over udp straight to a configured resolver,
+//! so the seeds do not depend on how the host resolves names.
+
+use crate::discovery::DEFAULT_TIMEOUT_MS;
+use lib3h_mdns::dns::{DnsMessage, RecordData, TYPE_TXT};
+use lib3h_protocol::{
+    discovery::{
+        error::{DiscoveryError, DiscoveryResult},
+        Discovery,
+    },
+    uri::Lib3hUri,
+};
+use std::{
+    convert::TryFrom,
+    io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+/// Largest DNS message we expect over udp, with EDNS
+const MAX_DNS_MESSAGE_SIZE: usize = 4096;
+
+pub struct DnsSeedDiscovery {
+    domain: String,
+    resolver: SocketAddr,
+    timeout_ms: u64,
+}
+
+impl DnsSeedDiscovery {
+    pub fn new(domain: &str, resolver: SocketAddr) -> Self {
+        DnsSeedDiscovery {
+            domain: domain.to_string(),
+            resolver,
+            timeout_ms: DEFAULT_TIMEOUT_MS,
+        }
+    }
+
+    /// How long we wait for the resolver to answer
+    pub fn with_timeout_ms(mut self, timeout_ms: u64) -> Self {
+        self.timeout_ms = timeout_ms;
+        self
+    }
+
+    /// Ask the resolver for the TXT records of our domain
+    fn query(&self) -> DiscoveryResult<DnsMessage> {
+        let bind_address: SocketAddr = if self.resolver.is_ipv4() {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let socket = UdpSocket::bind(bind_address)?;
+        let query = DnsMessage::unicast_query(rand::random(), &self.domain, TYPE_TXT);
+        socket.send_to(&query.to_raw_dns_sd()?, self.resolver)?;
+
+        let deadline = Instant::now() + Duration::from_millis(self.timeout_ms);
+        let mut buffer = vec![0; MAX_DNS_MESSAGE_SIZE];
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(DiscoveryError::from(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("no answer from dns resolver {}", self.resolver),
+                )));
+            }
+            socket.set_read_timeout(Some(deadline - now))?;
+            let (len, from) = match socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(ref e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    continue
+                }
+                Err(e) => return Err(e.into()),
+            };
+            // only the resolver answers our query, anything else is ignored
+            if from != self.resolver {
+                continue;
+            }
+            let packet = buffer[..len].to_vec();
+            if let Ok(response) = DnsMessage::from_raw_dns_sd(&packet) {
+                if response.is_response() && response.trans_id() == query.trans_id() {
+                    return Ok(response);
+                }
+            }
+        }
+    }
+}
+
+impl Discovery for DnsSeedDiscovery {
+    /// seeds are published by whoever administers the domain
+    fn advertise(&mut self) -> DiscoveryResult<()> {
+        Ok(())
+    }
+
+    fn discover(&mut self) -> DiscoveryResult<Vec<Lib3hUri>> {
+        let response = self.query()?;
+        if response.response_code() != 0 {
+            return Err(DiscoveryError::new_other(&format!(
+                "dns resolver {} failed to resolve {} with response code {}",
+                self.resolver,
+                self.domain,
+                response.response_code()
+            )));
+        }
+        let domain = self.domain.trim_end_matches('.');
+        let mut nodes = Vec::new();
+        for answer in response.answers() {
+            if !answer
+                .domain_name()
+                .trim_end_matches('.')
+                .eq_ignore_ascii_case(domain)
+            {
+                continue;
+            }
+            if let RecordData::Txt(strings) = answer.data() {
+                for s in strings {
+                    match Lib3hUri::try_from(s.as_str()) {
+                        Ok(uri) => nodes.push(uri),
+                        Err(e) => debug!("ignoring seed '{}' of {}: {:?}", s, domain, e),
+                    }
+                }
+            }
+        }
+        Ok(nodes)
+    }
+
+    fn release(&mut self) -> DiscoveryResult<()> {
+        Ok(())
+    }
+
+    fn flush(&mut self) -> DiscoveryResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lib3h_mdns::dns::AnswerSection;
+
+    const SEED_DOMAIN: &str = "_seeds.holo.host";
+
+    /// Answers a single query with `txt` as the TXT record of our domain,
+    /// along with a record of another domain
+    fn stand_in_resolver(txt: Vec<String>) -> (SocketAddr, std::thread::JoinHandle<()>) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = socket.local_addr().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut buffer = vec![0; MAX_DNS_MESSAGE_SIZE];
+            let (len, from) = socket.recv_from(&mut buffer).unwrap();
+            let query = DnsMessage::from_raw_dns_sd(&buffer[..len].to_vec()).unwrap();
+            assert_eq!(SEED_DOMAIN, query.questions()[0].domain_name());
+            assert_eq!(TYPE_TXT, query.questions()[0].query_type());
+            let answers = vec![
+                AnswerSection::new_record(SEED_DOMAIN, RecordData::Txt(txt), 300).unwrap(),
+                AnswerSection::new_record(
+                    "_other.holo.host",
+                    RecordData::Txt(vec!["wss://203.0.113.66:64159".to_string()]),
+                    300,
+                )
+                .unwrap(),
+            ];
+            let response = query.unicast_response(answers);
+            socket
+                .send_to(&response.to_raw_dns_sd().unwrap(), from)
+                .unwrap();
+        });
+        (address, handle)
+    }
+
+    #[test]
+    fn it_should_discover_seeds_from_txt_records() {
+        let (resolver, handle) = stand_in_resolver(vec![
+            "wss://203.0.113.7:64159".to_string(),
+            "not a uri".to_string(),
+            "wss://[2001:db8::7]:64159".to_string(),
+        ]);
+        let mut discovery = DnsSeedDiscovery::new(SEED_DOMAIN, resolver);
+        let nodes = discovery.discover().unwrap();
+        handle.join().unwrap();
+        assert_eq!(
+            vec![
+                Lib3hUri::try_from("wss://203.0.113.7:64159").unwrap(),
+                Lib3hUri::try_from("wss://[2001:db8::7]:64159").unwrap(),
+            ],
+            nodes
+        );
+    }
+
+    #[test]
+    fn it_should_time_out_without_resolver() {
+        // a resolver that never answers
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut discovery =
+            DnsSeedDiscovery::new(SEED_DOMAIN, socket.local_addr().unwrap()).with_timeout_ms(100);
+        assert!(discovery.discover().is_err());
+    }
+}
@@ -0,0 +1,338 @@
+//! Client of a bootstrap service, an http server keeping
+//! the uris of the nodes of each network:
+//!
+//! - `GET <url>/networks/<network id>/nodes` answers a JSON array of uris
+//! - `POST` on the same path with a JSON string uri registers a node
+//! - `DELETE` on the same path with the same body unregisters it
+//!
+//! Requests are plain HTTP/1.0, over tls for `https` urls.
+
+use crate::discovery::DEFAULT_TIMEOUT_MS;
+use lib3h_protocol::{
+    discovery::{
+        error::{DiscoveryError, DiscoveryResult},
+        Discovery,
+    },
+    uri::Lib3hUri,
+};
+use std::{
+    convert::TryFrom,
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+/// Largest response we accept from the bootstrap service
+const MAX_RESPONSE_BYTES: u64 = 1024 * 1024;
+
+pub struct HttpBootstrapDiscovery {
+    url: Lib3hUri,
+    network_id: String,
+    /// what we register, we only look for nodes without it
+    this_node: Option<Lib3hUri>,
+    timeout_ms: u64,
+}
+
+impl HttpBootstrapDiscovery {
+    pub fn new(url: Lib3hUri, network_id: &str) -> Self {
+        HttpBootstrapDiscovery {
+            url,
+            network_id: network_id.to_string(),
+            this_node: None,
+            timeout_ms: DEFAULT_TIMEOUT_MS,
+        }
+    }
+
+    /// Register `this_node` when advertising
+    pub fn with_this_node(mut self, this_node: Lib3hUri) -> Self {
+        self.this_node = Some(this_node);
+        self
+    }
+
+    /// How long we wait for the service to answer
+    pub fn with_timeout_ms(mut self, timeout_ms: u64) -> Self {
+        self.timeout_ms = timeout_ms;
+        self
+    }
+
+    /// The path of the nodes of our network, the network id percent-encoded
+    fn nodes_path(&self) -> DiscoveryResult<String> {
+        let mut url = self.url.0.clone();
+        url.path_segments_mut()
+            .map_err(|()| {
+                DiscoveryError::new_other(&format!("bad bootstrap service url {}", self.url))
+            })?
+            .pop_if_empty()
+            .extend(&["networks", &self.network_id, "nodes"]);
+        Ok(url.path().to_string())
+    }
+
+    /// Send a request on the nodes of our network,
+    /// returning the status code and body of the response
+    fn request(&self, method: &str, body: Option<&str>) -> DiscoveryResult<(u16, String)> {
+        let host = self.url.host_str().ok_or_else(|| {
+            DiscoveryError::new_other(&format!("no host in bootstrap service url {}", self.url))
+        })?;
+        let port = self.url.port_or_known_default().ok_or_else(|| {
+            DiscoveryError::new_other(&format!("no port in bootstrap service url {}", self.url))
+        })?;
+        let address = (host, port).to_socket_addrs()?.next().ok_or_else(|| {
+            DiscoveryError::new_other(&format!("cannot resolve bootstrap service {}", host))
+        })?;
+        let timeout = Duration::from_millis(self.timeout_ms);
+        let stream = TcpStream::connect_timeout(&address, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        let mut request = format!(
+            "{} {} HTTP/1.0\r\nHost: {}\r\nAccept: application/json\r\n",
+            method,
+            self.nodes_path()?,
+            host
+        );
+        match body {
+            Some(body) => request.push_str(&format!(
+                "Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )),
+            None => request.push_str("\r\n"),
+        }
+
+        let response = if self.url.scheme() == "https" {
+            let connector = native_tls::TlsConnector::new()
+                .map_err(|e| DiscoveryError::new_other(&format!("{:?}", e)))?;
+            let mut stream = connector
+                .connect(host, stream)
+                .map_err(|e| DiscoveryError::new_other(&format!("{:?}", e)))?;
+            exchange(&mut stream, &request)?
+        } else {
+            let mut stream = stream;
+            exchange(&mut stream, &request)?
+        };
+        parse_response(&response)
+    }
+
+    /// Send a request that only has to succeed
+    fn expect_success(&self, method: &str, body: Option<&str>) -> DiscoveryResult<String> {
+        let (status, response_body) = self.request(method, body)?;
+        if !(200..300).contains(&status) {
+            return Err(DiscoveryError::new_other(&format!(
+                "bootstrap service {} answered {} {} with status {}",
+                self.url,
+                method,
+                self.nodes_path()?,
+                status
+            )));
+        }
+        Ok(response_body)
+    }
+
+    fn this_node_body(&self) -> Option<String> {
+        self.this_node
+            .as_ref()
+            .map(|uri| serde_json::Value::String(uri.to_string()).to_string())
+    }
+}
+
+/// Write `request` and read the response until the server closes the connection,
+/// failing if it is larger than `MAX_RESPONSE_BYTES`
+fn exchange<S: Read + Write>(stream: &mut S, request: &str) -> io::Result<Vec<u8>> {
+    stream.write_all(request.as_bytes())?;
+    stream.flush()?;
+    let mut response = Vec::new();
+    stream
+        .by_ref()
+        .take(MAX_RESPONSE_BYTES + 1)
+        .read_to_end(&mut response)?;
+    if response.len() as u64 > MAX_RESPONSE_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "bootstrap service response too large",
+        ));
+    }
+    Ok(response)
+}
+
+fn parse_response(response: &[u8]) -> DiscoveryResult<(u16, String)> {
+    let response = String::from_utf8_lossy(response);
+    let mut parts = response.splitn(2, "\r\n\r\n");
+    let head = parts.next().unwrap_or("");
+    let body = parts.next().unwrap_or("").to_string();
+    let status = head
+        .lines()
+        .next()
+        .and_then(|status_line| status_line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| DiscoveryError::new_other("bad response from bootstrap service"))?;
+    Ok((status, body))
+}
+
+impl Discovery for HttpBootstrapDiscovery {
+    fn advertise(&mut self) -> DiscoveryResult<()> {
+        if let Some(body) = self.this_node_body() {
+            self.expect_success("POST", Some(&body))?;
+        }
+        Ok(())
+    }
+
+    fn discover(&mut self) -> DiscoveryResult<Vec<Lib3hUri>> {
+        let body = self.expect_success("GET", None)?;
+        let uris: Vec<String> = serde_json::from_str(&body).map_err(|e| {
+            DiscoveryError::new_other(&format!("bad node list from bootstrap service: {:?}", e))
+        })?;
+        let mut nodes = Vec::new();
+        for uri in uris {
+            match Lib3hUri::try_from(uri.as_str()) {
+                Ok(node) => {
+                    if Some(&node) != self.this_node.as_ref() {
+                        nodes.push(node);
+                    }
+                }
+                Err(e) => debug!("ignoring node '{}' from bootstrap service: {:?}", uri, e),
+            }
+        }
+        Ok(nodes)
+    }
+
+    fn release(&mut self) -> DiscoveryResult<()> {
+        if let Some(body) = self.this_node_body() {
+            self.expect_success("DELETE", Some(&body))?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> DiscoveryResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+    };
+
+    /// Serves `connections` requests like a bootstrap service would,
+    /// keeping the nodes of the test network in memory
+    fn stand_in_service(connections: usize) -> (Lib3hUri, std::thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Lib3hUri::try_from(
+            format!("http://{}/bootstrap/", listener.local_addr().unwrap()).as_str(),
+        )
+        .unwrap();
+        let mut nodes = vec!["mem://node_b".to_string()];
+        let handle = std::thread::spawn(move || {
+            for stream in listener.incoming().take(connections) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    let lower = header.to_lowercase();
+                    if lower.starts_with("content-length:") {
+                        content_length = lower["content-length:".len()..].trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                let mut words = request_line.split_whitespace();
+                let method = words.next().unwrap().to_string();
+                let path = words.next().unwrap().to_string();
+                let (status, response_body) = if path != "/bootstrap/networks/test_net/nodes" {
+                    ("404 Not Found", String::new())
+                } else {
+                    match method.as_str() {
+                        "GET" => ("200 OK", serde_json::to_string(&nodes).unwrap()),
+                        "POST" => {
+                            nodes.push(serde_json::from_slice(&body).unwrap());
+                            ("201 Created", String::new())
+                        }
+                        "DELETE" => {
+                            let node: String = serde_json::from_slice(&body).unwrap();
+                            nodes.retain(|n| *n != node);
+                            ("204 No Content", String::new())
+                        }
+                        _ => ("405 Method Not Allowed", String::new()),
+                    }
+                };
+                write!(
+                    stream,
+                    "HTTP/1.0 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                    status,
+                    response_body.len(),
+                    response_body
+                )
+                .unwrap();
+            }
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn it_should_register_and_discover_nodes() {
+        let (url, handle) = stand_in_service(5);
+        let node_a = Lib3hUri::try_from("mem://node_a").unwrap();
+        let node_b = Lib3hUri::try_from("mem://node_b").unwrap();
+        let mut discovery =
+            HttpBootstrapDiscovery::new(url.clone(), "test_net").with_this_node(node_a.clone());
+        let mut other_node = HttpBootstrapDiscovery::new(url, "test_net");
+
+        discovery.advertise().unwrap();
+        // we do not discover ourselves, others do
+        assert_eq!(vec![node_b.clone()], discovery.discover().unwrap());
+        assert_eq!(vec![node_b.clone(), node_a], other_node.discover().unwrap());
+        discovery.release().unwrap();
+        assert_eq!(vec![node_b], other_node.discover().unwrap());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn it_should_fail_on_unknown_network() {
+        let (url, handle) = stand_in_service(1);
+        let mut discovery = HttpBootstrapDiscovery::new(url, "other_net");
+        assert!(discovery.discover().is_err());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn it_should_encode_the_network_id() {
+        let url = Lib3hUri::try_from("http://127.0.0.1:8080/bootstrap").unwrap();
+        let discovery = HttpBootstrapDiscovery::new(url, "test net/../other?");
+        assert_eq!(
+            "/bootstrap/networks/test%20net%2F..%2Fother%3F/nodes",
+            discovery.nodes_path().unwrap()
+        );
+    }
+
+    #[test]
+    fn it_should_fail_on_oversized_responses() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url =
+            Lib3hUri::try_from(format!("http://{}/", listener.local_addr().unwrap()).as_str())
+                .unwrap();
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request_line = String::new();
+            BufReader::new(stream.try_clone().unwrap())
+                .read_line(&mut request_line)
+                .unwrap();
+            let body = vec![b' '; MAX_RESPONSE_BYTES as usize];
+            // the client may hang up before reading it all
+            let _ =
+                write!(stream, "HTTP/1.0 200 OK\r\n\r\n[]").and_then(|()| stream.write_all(&body));
+        });
+        let mut discovery = HttpBootstrapDiscovery::new(url, "test_net");
+        assert!(discovery.discover().is_err());
+        handle.join().unwrap();
+    }
+}
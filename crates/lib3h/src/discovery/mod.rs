@@ -0,0 +1,503 @@
+//! Discovery actors finding the nodes of our network,
+//! independently of the transport we reach them with.
+//!
+//! Any `Discovery` backend runs as a `DiscoveryActor`: the engine asks it
+//! to advertise us on start, and it publishes the nodes it discovers
+//! every refresh interval. The engine merges what all its backends find
+//! and bootstraps each node once.
+//!
+//! Backends looking nodes up over the network run on a worker thread, so
+//! the engine keeps processing while they wait for an answer.
+
+pub mod dns_seed;
+pub mod http_bootstrap;
+pub mod static_list;
+
+use crate::{
+    discovery::{
+        dns_seed::DnsSeedDiscovery, http_bootstrap::HttpBootstrapDiscovery,
+        static_list::StaticDiscovery,
+    },
+    engine::DiscoveryConfig,
+};
+
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use detach::prelude::*;
+use lib3h_ghost_actor::prelude::*;
+use lib3h_protocol::{
+    discovery::{
+        error::{DiscoveryError, DiscoveryResult},
+        Discovery,
+    },
+    uri::Lib3hUri,
+};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// How often network backends look for new nodes
+pub const DEFAULT_REFRESH_INTERVAL_MS: u64 = 60_000;
+
+/// How long network backends wait for an answer
+pub const DEFAULT_TIMEOUT_MS: u64 = 2_000;
+
+pub mod discovery_protocol {
+    use lib3h_protocol::uri::Lib3hUri;
+
+    #[derive(Debug, Clone)]
+    pub enum RequestToChild {
+        /// let the nodes of our network find us
+        Advertise,
+        /// look for nodes right away
+        Discover,
+        /// stop being found, before leaving the network
+        Release,
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum RequestToChildResponse {
+        AdvertiseSuccess,
+        Discover(Vec<Lib3hUri>),
+        ReleaseSuccess,
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum RequestToParent {
+        /// nodes found on a refresh, some of them possibly known already
+        Discovered(Vec<Lib3hUri>),
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum RequestToParentResponse {
+        // N/A
+    }
+}
+
+use discovery_protocol::*;
+
+pub type DynDiscoveryActor = Box<
+    dyn GhostActor<
+        RequestToParent,
+        RequestToParentResponse,
+        RequestToChild,
+        RequestToChildResponse,
+        DiscoveryError,
+    >,
+>;
+
+pub type DiscoveryActorParentEndpoint = GhostEndpoint<
+    RequestToChild,
+    RequestToChildResponse,
+    RequestToParent,
+    RequestToParentResponse,
+    DiscoveryError,
+>;
+
+pub type DiscoveryActorParentWrapperDyn<C> = GhostParentWrapperDyn<
+    C,
+    RequestToParent,
+    RequestToParentResponse,
+    RequestToChild,
+    RequestToChildResponse,
+    DiscoveryError,
+>;
+
+type DiscoveryActorSelfEndpoint<D> = GhostContextEndpoint<
+    DiscoveryActor<D>,
+    RequestToParent,
+    RequestToParentResponse,
+    RequestToChild,
+    RequestToChildResponse,
+    DiscoveryError,
+>;
+
+type DiscoveryMessageFromParent =
+    GhostMessage<RequestToChild, RequestToParent, RequestToChildResponse, DiscoveryError>;
+
+/// The backend of each discovery config, `network_id` naming our network
+/// and `this_node` where we are reached
+pub fn backend_for_config(
+    config: &DiscoveryConfig,
+    network_id: &str,
+    this_node: &Lib3hUri,
+) -> DynDiscoveryActor {
+    match config {
+        DiscoveryConfig::DnsSeed { domain, resolver } => Box::new(
+            DiscoveryActor::new_on_worker_thread(DnsSeedDiscovery::new(domain, *resolver))
+                .with_refresh_interval_ms(DEFAULT_REFRESH_INTERVAL_MS),
+        ),
+        DiscoveryConfig::HttpBootstrap { url } => Box::new(
+            DiscoveryActor::new_on_worker_thread(
+                HttpBootstrapDiscovery::new(url.clone(), network_id)
+                    .with_this_node(this_node.clone()),
+            )
+            .with_refresh_interval_ms(DEFAULT_REFRESH_INTERVAL_MS),
+        ),
+    }
+}
+
+/// The backend of the `bootstrap_nodes` of the engine config
+pub fn backend_for_bootstrap_nodes(nodes: Vec<Lib3hUri>) -> DynDiscoveryActor {
+    Box::new(DiscoveryActor::new(StaticDiscovery::new(nodes)))
+}
+
+/// What a backend running on a worker thread is asked to do
+enum Job {
+    Refresh,
+    Request(RequestToChild),
+}
+
+/// What a backend running on a worker thread did
+enum JobDone {
+    Refresh(DiscoveryResult<Vec<Lib3hUri>>),
+    Request(DiscoveryResult<RequestToChildResponse>),
+}
+
+/// Where a backend runs
+enum Backend<D> {
+    /// on the thread of the actor
+    Local(D),
+    /// on a worker thread, taking jobs in order and answering them in order
+    Worker {
+        jobs: Sender<Job>,
+        done: Receiver<JobDone>,
+    },
+}
+
+/// Runs a `Discovery` backend as a ghost actor
+pub struct DiscoveryActor<D: Discovery + 'static> {
+    backend: Backend<D>,
+    /// requests of our parent the worker thread has yet to answer, oldest first
+    pending: VecDeque<DiscoveryMessageFromParent>,
+    /// discover again this often, or only once if `None`
+    refresh_interval_ms: Option<u64>,
+    /// when we last published what we discovered
+    last_refresh: Option<Instant>,
+    endpoint_parent: Option<DiscoveryActorParentEndpoint>,
+    endpoint_self: Detach<DiscoveryActorSelfEndpoint<D>>,
+}
+
+/// Runs `request` on `backend`
+fn run_request<D: Discovery>(
+    backend: &mut D,
+    request: RequestToChild,
+) -> DiscoveryResult<RequestToChildResponse> {
+    match request {
+        RequestToChild::Advertise => backend
+            .advertise()
+            .map(|()| RequestToChildResponse::AdvertiseSuccess),
+        RequestToChild::Discover => backend.discover().map(RequestToChildResponse::Discover),
+        RequestToChild::Release => backend
+            .release()
+            .and_then(|()| backend.flush())
+            .map(|()| RequestToChildResponse::ReleaseSuccess),
+    }
+}
+
+impl<D: Discovery + Send + 'static> DiscoveryActor<D> {
+    /// Runs `backend` on a worker thread, which stops with the actor
+    pub fn new_on_worker_thread(mut backend: D) -> Self {
+        let (jobs, job_receiver) = crossbeam_channel::unbounded();
+        let (done_sender, done) = crossbeam_channel::unbounded();
+        std::thread::spawn(move || {
+            for job in job_receiver.iter() {
+                let job_done = match job {
+                    Job::Refresh => JobDone::Refresh(backend.discover()),
+                    Job::Request(request) => JobDone::Request(run_request(&mut backend, request)),
+                };
+                if done_sender.send(job_done).is_err() {
+                    break;
+                }
+            }
+        });
+        Self::with_backend(Backend::Worker { jobs, done })
+    }
+}
+
+impl<D: Discovery + 'static> DiscoveryActor<D> {
+    pub fn new(backend: D) -> Self {
+        Self::with_backend(Backend::Local(backend))
+    }
+
+    fn with_backend(backend: Backend<D>) -> Self {
+        let (endpoint_parent, endpoint_self) = create_ghost_channel();
+        let endpoint_parent = Some(endpoint_parent);
+        let endpoint_self = Detach::new(
+            endpoint_self
+                .as_context_endpoint_builder()
+                .request_id_prefix("discovery_to_parent_")
+                .build(),
+        );
+        Self {
+            backend,
+            pending: VecDeque::new(),
+            refresh_interval_ms: None,
+            last_refresh: None,
+            endpoint_parent,
+            endpoint_self,
+        }
+    }
+
+    /// Discover again every `refresh_interval_ms`
+    pub fn with_refresh_interval_ms(mut self, refresh_interval_ms: u64) -> Self {
+        self.refresh_interval_ms = Some(refresh_interval_ms);
+        self
+    }
+
+    fn should_refresh(&self) -> bool {
+        match (self.last_refresh, self.refresh_interval_ms) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(last_refresh), Some(interval_ms)) => {
+                last_refresh.elapsed() >= Duration::from_millis(interval_ms)
+            }
+        }
+    }
+
+    /// Discover again if it is time to, publishing right away what a local
+    /// backend finds
+    fn refresh(&mut self) -> GhostResult<bool> {
+        if !self.should_refresh() {
+            return Ok(false);
+        }
+        self.last_refresh = Some(Instant::now());
+        match &mut self.backend {
+            Backend::Local(backend) => {
+                let discovered = backend.discover();
+                self.publish_discovered(discovered)?;
+            }
+            Backend::Worker { jobs, .. } => {
+                if jobs.send(Job::Refresh).is_err() {
+                    warn!("discovery worker stopped");
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn publish_discovered(
+        &mut self,
+        discovered: DiscoveryResult<Vec<Lib3hUri>>,
+    ) -> GhostResult<()> {
+        match discovered {
+            Ok(nodes) => {
+                if !nodes.is_empty() {
+                    self.endpoint_self.publish(
+                        crate::new_root_span("send event discovery::RequestToParent::Discovered"),
+                        RequestToParent::Discovered(nodes),
+                    )?;
+                }
+            }
+            // we will try again on the next refresh
+            Err(e) => warn!("discovery failed: {:?}", e),
+        }
+        Ok(())
+    }
+
+    fn handle_msg_from_parent(&mut self, mut msg: DiscoveryMessageFromParent) -> GhostResult<()> {
+        let request = msg.take_message().expect("exists");
+        match &mut self.backend {
+            Backend::Local(backend) => msg.respond(run_request(backend, request)),
+            Backend::Worker { jobs, .. } => {
+                if jobs.send(Job::Request(request)).is_err() {
+                    return msg.respond(Err(DiscoveryError::new_other("discovery worker stopped")));
+                }
+                self.pending.push_back(msg);
+                Ok(())
+            }
+        }
+    }
+
+    /// Publish or respond with what the worker thread did since we last looked
+    fn poll_worker(&mut self) -> GhostResult<bool> {
+        let mut did_work = false;
+        loop {
+            let job_done = match &self.backend {
+                Backend::Local(_) => return Ok(did_work),
+                Backend::Worker { done, .. } => done.try_recv(),
+            };
+            match job_done {
+                Ok(JobDone::Refresh(discovered)) => self.publish_discovered(discovered)?,
+                Ok(JobDone::Request(result)) => {
+                    if let Some(msg) = self.pending.pop_front() {
+                        msg.respond(result)?;
+                    }
+                }
+                Err(TryRecvError::Empty) => return Ok(did_work),
+                Err(TryRecvError::Disconnected) => {
+                    for msg in self.pending.drain(..) {
+                        msg.respond(Err(DiscoveryError::new_other("discovery worker stopped")))?;
+                    }
+                    return Ok(did_work);
+                }
+            }
+            did_work = true;
+        }
+    }
+}
+
+impl<D: Discovery + 'static>
+    GhostActor<
+        RequestToParent,
+        RequestToParentResponse,
+        RequestToChild,
+        RequestToChildResponse,
+        DiscoveryError,
+    > for DiscoveryActor<D>
+{
+    fn take_parent_endpoint(&mut self) -> Option<DiscoveryActorParentEndpoint> {
+        std::mem::replace(&mut self.endpoint_parent, None)
+    }
+
+    fn process_concrete(&mut self) -> GhostResult<WorkWasDone> {
+        let mut did_work = detach_run!(&mut self.endpoint_self, |es| es.process(self))?;
+        for msg in self.endpoint_self.as_mut().drain_messages() {
+            did_work = true.into();
+            self.handle_msg_from_parent(msg)?;
+        }
+        if self.refresh()? {
+            did_work = true.into();
+        }
+        if self.poll_worker()? {
+            did_work = true.into();
+        }
+        Ok(did_work)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    fn uri(s: &str) -> Lib3hUri {
+        Lib3hUri::try_from(s).unwrap()
+    }
+
+    /// A backend taking its time to find its nodes
+    struct SlowDiscovery {
+        nodes: Vec<Lib3hUri>,
+        delay_ms: u64,
+    }
+
+    impl Discovery for SlowDiscovery {
+        fn advertise(&mut self) -> DiscoveryResult<()> {
+            Ok(())
+        }
+        fn discover(&mut self) -> DiscoveryResult<Vec<Lib3hUri>> {
+            std::thread::sleep(Duration::from_millis(self.delay_ms));
+            Ok(self.nodes.clone())
+        }
+        fn release(&mut self) -> DiscoveryResult<()> {
+            Ok(())
+        }
+        fn flush(&mut self) -> DiscoveryResult<()> {
+            Ok(())
+        }
+    }
+
+    /// Process `discovery` until `discovered` holds `len` nodes
+    fn process_until(
+        discovery: &mut DiscoveryActorParentWrapperDyn<Vec<Lib3hUri>>,
+        discovered: &mut Vec<Lib3hUri>,
+        len: usize,
+    ) {
+        let start = Instant::now();
+        while discovered.len() < len {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "nothing discovered"
+            );
+            discovery.process(discovered).unwrap();
+            for mut msg in discovery.drain_messages() {
+                match msg.take_message().expect("exists") {
+                    RequestToParent::Discovered(list) => discovered.extend(list),
+                }
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn it_should_publish_static_nodes_once() {
+        let nodes = vec![uri("wss://192.168.0.88:64159"), uri("mem://node_b")];
+        let mut discovery: DiscoveryActorParentWrapperDyn<Vec<Lib3hUri>> =
+            GhostParentWrapperDyn::new(backend_for_bootstrap_nodes(nodes.clone()), "test");
+        let mut discovered = Vec::new();
+
+        discovery.process(&mut discovered).unwrap();
+        for mut msg in discovery.drain_messages() {
+            match msg.take_message().expect("exists") {
+                RequestToParent::Discovered(list) => discovered.extend(list),
+            }
+        }
+        assert_eq!(nodes, discovered);
+
+        // without a refresh interval, the list is only published once
+        discovery.process(&mut discovered).unwrap();
+        assert!(discovery.drain_messages().is_empty());
+
+        // but can still be asked for
+        discovery
+            .request(
+                holochain_tracing::test_span(),
+                RequestToChild::Discover,
+                Box::new(|discovered: &mut Vec<Lib3hUri>, resp| {
+                    match resp {
+                        GhostCallbackData::Response(Ok(RequestToChildResponse::Discover(list))) => {
+                            discovered.extend(list)
+                        }
+                        _ => panic!("bad response: {:?}", resp),
+                    }
+                    Ok(())
+                }),
+            )
+            .unwrap();
+        discovery.process(&mut discovered).unwrap();
+        assert_eq!(4, discovered.len());
+    }
+
+    #[test]
+    fn it_should_discover_on_a_worker_thread() {
+        let nodes = vec![uri("wss://192.168.0.88:64159")];
+        let backend = DiscoveryActor::new_on_worker_thread(SlowDiscovery {
+            nodes: nodes.clone(),
+            delay_ms: 500,
+        });
+        let mut discovery: DiscoveryActorParentWrapperDyn<Vec<Lib3hUri>> =
+            GhostParentWrapperDyn::new(Box::new(backend), "test");
+        let mut discovered = Vec::new();
+
+        // the lookup does not hold us up
+        let start = Instant::now();
+        discovery.process(&mut discovered).unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert!(discovery.drain_messages().is_empty());
+
+        process_until(&mut discovery, &mut discovered, 1);
+        assert_eq!(nodes, discovered);
+
+        // requests are answered once the worker is done with them
+        discovery
+            .request(
+                holochain_tracing::test_span(),
+                RequestToChild::Discover,
+                Box::new(|discovered: &mut Vec<Lib3hUri>, resp| {
+                    match resp {
+                        GhostCallbackData::Response(Ok(RequestToChildResponse::Discover(list))) => {
+                            discovered.extend(list)
+                        }
+                        _ => panic!("bad response: {:?}", resp),
+                    }
+                    Ok(())
+                }),
+            )
+            .unwrap();
+        let start = Instant::now();
+        discovery.process(&mut discovered).unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert_eq!(1, discovered.len());
+        process_until(&mut discovery, &mut discovered, 2);
+    }
+}
@@ -0,0 +1,36 @@
+//! Discovery of a fixed list of nodes, such as the `bootstrap_nodes`
+//! of the engine config.
+
+use lib3h_protocol::{
+    discovery::{error::DiscoveryResult, Discovery},
+    uri::Lib3hUri,
+};
+
+pub struct StaticDiscovery {
+    nodes: Vec<Lib3hUri>,
+}
+
+impl StaticDiscovery {
+    pub fn new(nodes: Vec<Lib3hUri>) -> Self {
+        StaticDiscovery { nodes }
+    }
+}
+
+impl Discovery for StaticDiscovery {
+    /// nobody learns about us from a static list
+    fn advertise(&mut self) -> DiscoveryResult<()> {
+        Ok(())
+    }
+
+    fn discover(&mut self) -> DiscoveryResult<Vec<Lib3hUri>> {
+        Ok(self.nodes.clone())
+    }
+
+    fn release(&mut self) -> DiscoveryResult<()> {
+        Ok(())
+    }
+
+    fn flush(&mut self) -> DiscoveryResult<()> {
+        Ok(())
+    }
+}
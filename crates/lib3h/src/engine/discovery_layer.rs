@@ -0,0 +1,110 @@
+use crate::{
+    discovery::{self, discovery_protocol::*, DiscoveryActorParentWrapperDyn, DynDiscoveryActor},
+    engine::GhostEngine,
+    error::*,
+    gateway::protocol::*,
+};
+use holochain_tracing::Span;
+use lib3h_ghost_actor::prelude::*;
+use lib3h_protocol::{data_types::*, uri::Lib3hUri, DidWork};
+
+/// Discovery layer related private methods
+impl<'engine> GhostEngine<'engine> {
+    /// Start the discovery backends of our config,
+    /// advertising us and bootstrapping what they already know
+    pub(crate) fn priv_start_discovery(&mut self, span: Span) -> Lib3hResult<()> {
+        let network_id = String::from(self.config.network_id.id.clone());
+        let this_node = self.this_net_peer.peer_location.clone();
+        let mut backends: Vec<DynDiscoveryActor> = Vec::new();
+        let bootstrap_nodes: Vec<Lib3hUri> = self.config.bootstrap_nodes.drain(..).collect();
+        if !bootstrap_nodes.is_empty() {
+            backends.push(discovery::backend_for_bootstrap_nodes(bootstrap_nodes));
+        }
+        for discovery_config in self.config.discovery.iter() {
+            backends.push(discovery::backend_for_config(
+                discovery_config,
+                &network_id,
+                &this_node,
+            ));
+        }
+        for (index, backend) in backends.into_iter().enumerate() {
+            let mut backend: DiscoveryActorParentWrapperDyn<GhostEngine<'engine>> =
+                DiscoveryActorParentWrapperDyn::new(
+                    backend,
+                    &format!("engine_to_discovery_{}_", index),
+                );
+            backend.request(
+                span.child("request discovery::RequestToChild::Advertise"),
+                RequestToChild::Advertise,
+                Box::new(|_me, response| {
+                    match response {
+                        GhostCallbackData::Timeout(bt) => {
+                            warn!("discovery advertise timeout: {:?}", bt)
+                        }
+                        GhostCallbackData::Response(Err(e)) => {
+                            warn!("discovery advertise failed: {:?}", e)
+                        }
+                        GhostCallbackData::Response(Ok(_)) => (),
+                    }
+                    Ok(())
+                }),
+            )?;
+            self.discovery_backends.push(backend);
+        }
+        self.process_discovery()?;
+        Ok(())
+    }
+
+    /// Process the discovery backends and bootstrap the nodes they found
+    pub(crate) fn process_discovery(&mut self) -> Lib3hResult<DidWork> {
+        let mut did_work = false;
+        let mut discovery_backends: Vec<DiscoveryActorParentWrapperDyn<GhostEngine<'engine>>> =
+            self.discovery_backends.drain(..).collect();
+        for backend in discovery_backends.iter_mut() {
+            if bool::from(backend.process(self)?) {
+                did_work = true;
+            }
+            for mut msg in backend.drain_messages() {
+                did_work = true;
+                let span = msg.span().child("process_discovery");
+                match msg.take_message().expect("exists") {
+                    RequestToParent::Discovered(nodes) => {
+                        self.priv_bootstrap_discovered(span, nodes)?;
+                    }
+                }
+            }
+        }
+        self.discovery_backends = discovery_backends;
+        Ok(did_work)
+    }
+
+    /// Bootstrap the nodes we did not know about yet
+    fn priv_bootstrap_discovered(&mut self, span: Span, nodes: Vec<Lib3hUri>) -> GhostResult<()> {
+        for node in nodes {
+            if node == self.this_net_peer.peer_location
+                || !self.discovered_nodes.insert(node.clone())
+            {
+                continue;
+            }
+            debug!("{} discovered {}", self.name, node);
+            // can't use handle_bootstrap() because it assumes a message to respond to
+            let cmd = GatewayRequestToChild::Bootstrap(BootstrapData {
+                network_or_space_address: self.config.network_id.id.clone(),
+                bootstrap_uri: node,
+            });
+            self.multiplexer.request(
+                span.child("request GatewayRequestToChild::Bootstrap"),
+                cmd,
+                Box::new(|_, response| {
+                    match response {
+                        GhostCallbackData::Timeout(bt) => error!("bootstrap timeout: {:?}", bt),
+                        GhostCallbackData::Response(Err(e)) => error!("bootstrap failed: {:?}", e),
+                        GhostCallbackData::Response(Ok(_)) => (),
+                    }
+                    Ok(())
+                }),
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -54,12 +54,23 @@ impl<'engine>
             did_work = true;
         }
 
+        // Process the keystore
+        if self.process_keystore()? {
+            did_work = true;
+        }
+
         // Process network layer
         did_work = did_work || self.process_multiplexer()?;
 
         // Process the space layer
         did_work = did_work || self.process_space_gateways()?;
 
+        // Process the discovery backends, whether or not other layers did work
+        // as they refresh on their own schedule
+        if self.process_discovery()? {
+            did_work = true;
+        }
+
         // Done
         // trace!("({}).process_concrete() did_work = {}", self.name, did_work);
         Ok(did_work.into())
@@ -1,21 +1,27 @@
+#[cfg(not(target_os = "windows"))]
+use crate::transport::uds::actor::GhostTransportUds;
 use crate::{
     dht::{dht_config::DhtConfig, dht_protocol::*},
     engine::{
         engine_actor::*, p2p_protocol::*, CanAdvertise, ChainId, EngineConfig, GatewayId,
-        GhostEngine, TransportConfig, TransportKeys,
+        GhostEngine, TransportConfig,
     },
     error::{ErrorKind, Lib3hError, Lib3hResult},
     gateway::{protocol::*, GatewayOutputWrapType, P2pGateway},
+    keystore::{Keystore, KeystoreActorParentWrapper},
+    message_encoding::TransitIdentity,
     track::Tracker,
     transport::{
-        self, memory_mock::ghost_transport_memory::*, protocol::*,
-        websocket::actor::GhostTransportWebsocket, TransportMultiplex,
+        self, memory_mock::ghost_transport_memory::*, protocol::*, tcp::actor::GhostTransportTcp,
+        udp::actor::GhostTransportUdp, websocket::actor::GhostTransportWebsocket,
+        TransportMultiplex,
     },
 };
 use detach::Detach;
 use holochain_tracing::Span;
-use lib3h_crypto_api::CryptoSystem;
+use lib3h_crypto_api::{Buffer, CryptoSystem};
 use lib3h_ghost_actor::{prelude::*, RequestId};
+use lib3h_p2p_protocol::p2p::P2pMessage;
 use lib3h_protocol::{
     data_types::*,
     protocol::*,
@@ -23,8 +29,6 @@ use lib3h_protocol::{
     uri::Lib3hUri,
     Address,
 };
-use rmp_serde::Serializer;
-use serde::Serialize;
 use std::collections::{HashMap, HashSet};
 
 impl<'engine> CanAdvertise for GhostEngine<'engine> {
@@ -33,6 +37,24 @@ impl<'engine> CanAdvertise for GhostEngine<'engine> {
     }
 }
 impl<'engine> GhostEngine<'engine> {
+    /// Our keys are only persisted if we have somewhere to put them
+    /// and a passphrase to encrypt them with
+    fn open_keystore(crypto: &dyn CryptoSystem, config: &EngineConfig) -> Lib3hResult<Keystore> {
+        if config.work_dir.as_os_str().is_empty() {
+            return Ok(Keystore::new(crypto.box_clone()));
+        }
+        match &config.keystore_passphrase {
+            Some(passphrase) => {
+                let passphrase: Box<dyn Buffer> = Box::new(passphrase.as_bytes().to_vec());
+                Keystore::open(crypto.box_clone(), &config.work_dir, &passphrase)
+            }
+            None => {
+                warn!("no keystore passphrase, this node will get a new identity on restart");
+                Ok(Keystore::new(crypto.box_clone()))
+            }
+        }
+    }
+
     /// Constructor with for GhostEngine
     pub fn new(
         span: Span,
@@ -41,8 +63,8 @@ impl<'engine> GhostEngine<'engine> {
         name: &str,
         dht_factory: DhtFactory,
     ) -> Lib3hResult<Self> {
-        let transport_keys = TransportKeys::new(crypto.as_crypto_system())?;
-
+        let mut keystore = Self::open_keystore(crypto.as_crypto_system(), &config)?;
+        let transport_keys = keystore.transport_keys()?;
         // This will change when multi-transport is impelmented
         assert_eq!(config.transport_configs.len(), 1);
         let transport_config = config.transport_configs[0].clone();
@@ -52,13 +74,35 @@ impl<'engine> GhostEngine<'engine> {
         let transport: DynTransportActor = match &transport_config {
             TransportConfig::Websocket(tls_config) => {
                 let tls = tls_config.clone();
-                Box::new(GhostTransportWebsocket::new(
+                let mut transport = GhostTransportWebsocket::new(
                     node_id,
                     tls,
                     config.network_id.id.clone().into(),
-                ))
+                    crypto.box_clone(),
+                );
+                if let Some(stream_limits) = &config.stream_limits {
+                    transport.set_stream_limits(stream_limits.clone());
+                }
+                if config.sign_mdns_records {
+                    transport.sign_mdns_records(transport_keys.transport_secret_key.box_clone());
+                }
+                Box::new(transport)
             }
             TransportConfig::Memory(net) => Box::new(GhostTransportMemory::new(node_id, &net)),
+            TransportConfig::Tcp(tls_config) => Box::new(GhostTransportTcp::new(
+                node_id,
+                tls_config.clone(),
+                crypto.box_clone(),
+            )),
+            TransportConfig::Udp => Box::new(GhostTransportUdp::new(node_id)),
+            #[cfg(not(target_os = "windows"))]
+            TransportConfig::Uds => Box::new(GhostTransportUds::new(node_id)),
+            #[cfg(target_os = "windows")]
+            TransportConfig::Uds => {
+                return Err(Lib3hError::from(
+                    "the uds transport is not available on windows",
+                ));
+            }
         };
 
         let prebound_binding = Lib3hUri::with_undefined();
@@ -70,15 +114,35 @@ impl<'engine> GhostEngine<'engine> {
         // Create DhtConfig
         let dht_config = DhtConfig::with_engine_config(&node_uri, &config);
         debug!("New MOCK Engine {} -> {:?}", name, this_net_peer);
+        let mut transit_identity =
+            TransitIdentity::new(crypto.box_clone(), &transport_keys, &config.network_id.id)
+                .with_encodings(config.transit_encodings.clone())?;
+        if let Some(compression) = &config.compression {
+            transit_identity = transit_identity.with_compression(compression);
+        }
+        let mut network_gateway = P2pGateway::new(
+            GatewayOutputWrapType::DoNotWrapOutput,
+            config.network_id.clone(),
+            prebound_binding,
+            transport,
+            dht_factory,
+            &dht_config,
+        )
+        .with_transit_identity(transit_identity);
+        if let Some(relay_uri) = &config.relay_uri {
+            network_gateway = network_gateway.with_relay(relay_uri.clone());
+        }
+        if let Some(relay_quota) = &config.relay_quota {
+            network_gateway = network_gateway.with_relay_quota(relay_quota.clone());
+        }
+        if let Some(reconnect_policy) = &config.reconnect_policy {
+            network_gateway = network_gateway.with_reconnect_policy(reconnect_policy.clone());
+        }
+        if let Some(chunking) = &config.chunking {
+            network_gateway = network_gateway.with_chunking(chunking.clone());
+        }
         let mut multiplexer = Detach::new(GatewayParentWrapper::new(
-            TransportMultiplex::new(P2pGateway::new(
-                GatewayOutputWrapType::DoNotWrapOutput,
-                config.network_id.clone(),
-                prebound_binding,
-                transport,
-                dht_factory,
-                &dht_config,
-            )),
+            TransportMultiplex::new(network_gateway),
             "engine_to_multiplexer_",
         ));
 
@@ -103,7 +167,13 @@ impl<'engine> GhostEngine<'engine> {
                     bind_data,
                 )) = response
                 {
-                    me.this_net_peer.peer_location = bind_data.bound_url;
+                    // nodes behind a relay are reached through it
+                    me.this_net_peer.peer_location = match &me.config.relay_uri {
+                        Some(relay_uri) => {
+                            Lib3hUri::with_relay(relay_uri, &me.transport_keys.node_id.to_string())
+                        }
+                        None => bind_data.bound_url,
+                    };
                 } else {
                     panic!("bad response to bind: {:?}", response);
                 }
@@ -118,9 +188,15 @@ impl<'engine> GhostEngine<'engine> {
             name: name.to_string(),
             dht_factory,
             request_track: Tracker::new("real_engine_", 2000),
+            keystore: Detach::new(KeystoreActorParentWrapper::new(
+                keystore,
+                "engine_to_keystore_",
+            )),
             multiplexer,
             this_net_peer,
             network_connections: HashSet::new(),
+            discovery_backends: Vec::new(),
+            discovered_nodes: HashSet::new(),
             space_gateway_map: HashMap::new(),
             transport_keys,
             multiplexer_defered_sends: Vec::new(),
@@ -141,34 +217,19 @@ impl<'engine> GhostEngine<'engine> {
             )),
         )?;
         detach_run!(engine.multiplexer, |e| e.process(&mut engine))?;
-        engine.priv_connect_bootstraps(span.child("priv_connect_bootstraps"))?;
+        engine.priv_start_discovery(span.child("priv_start_discovery"))?;
         Ok(engine)
     }
 
-    fn priv_connect_bootstraps(&mut self, span: Span) -> GhostResult<()> {
-        let nodes: Vec<Lib3hUri> = self.config.bootstrap_nodes.drain(..).collect();
-        for bs in nodes {
-            // can't use handle_bootstrap() because it assumes a message to respond to
-            let cmd = GatewayRequestToChild::Bootstrap(BootstrapData {
-                network_or_space_address: self.config.network_id.id.clone(),
-                bootstrap_uri: bs,
-            });
-            self.multiplexer.request(
-                span.child("request GatewayRequestToChild::Bootstrap"),
-                cmd,
-                Box::new(|_, response| {
-                    let response = match response {
-                        GhostCallbackData::Timeout(bt) => panic!("bootstrap timeout: {:?}", bt),
-                        GhostCallbackData::Response(r) => r,
-                    };
-                    if let Err(e) = response {
-                        panic!("{:?}", e);
-                    }
-                    Ok(())
-                }),
-            )?;
-        }
-        Ok(())
+    /// The keystore holding our keys, to sign, verify or derive session keys with
+    pub fn keystore(&mut self) -> &mut KeystoreActorParentWrapper<GhostEngine<'engine>> {
+        self.keystore.as_mut()
+    }
+
+    /// Process the keystore, calling back the requests made to it
+    pub(crate) fn process_keystore(&mut self) -> GhostResult<bool> {
+        let did_work = detach_run!(&mut self.keystore, |keystore| keystore.process(self))?;
+        Ok(did_work.into())
     }
 
     pub fn this_space_peer(&mut self, chain_id: ChainId) -> Lib3hResult<PeerData> {
@@ -208,6 +269,65 @@ impl<'engine> GhostEngine<'engine> {
         )
     }
 
+    /// Process latency queries by asking the network gateway for its ping measurements
+    fn handle_query_peer_latency(
+        &mut self,
+        msg: ClientToLib3hMessage,
+        data: QueryPeerLatencyData,
+    ) -> GhostResult<()> {
+        self.multiplexer.request(
+            msg.span().child("handle_query_peer_latency"),
+            GatewayRequestToChild::QueryPeerLatency,
+            Box::new(move |_me, response| {
+                match response {
+                    GhostCallbackData::Response(Ok(
+                        GatewayRequestToChildResponse::QueryPeerLatency(peer_latency_list),
+                    )) => msg.respond(Ok(ClientToLib3hResponse::QueryPeerLatencyResult(
+                        PeerLatencyListData {
+                            request_id: data.request_id,
+                            peer_latency_list,
+                        },
+                    )))?,
+                    GhostCallbackData::Response(Err(e)) => msg.respond(Err(e))?,
+                    GhostCallbackData::Timeout(bt) => {
+                        msg.respond(Err(format!("timeout: {:?}", bt).into()))?
+                    }
+                    _ => msg.respond(Err(format!("bad response: {:?}", response).into()))?,
+                }
+                Ok(())
+            }),
+        )
+    }
+
+    /// Abandon a chunked transfer through the network gateway
+    fn handle_cancel_transfer(
+        &mut self,
+        msg: ClientToLib3hMessage,
+        data: CancelTransferData,
+    ) -> GhostResult<()> {
+        self.multiplexer.request(
+            msg.span().child("handle_cancel_transfer"),
+            GatewayRequestToChild::CancelTransfer {
+                uri: data.peer_location,
+                transfer_id: data.transfer_id,
+                outgoing: data.outgoing,
+            },
+            Box::new(move |_me, response| {
+                match response {
+                    GhostCallbackData::Response(Ok(
+                        GatewayRequestToChildResponse::CancelTransferSuccess,
+                    )) => msg.respond(Ok(ClientToLib3hResponse::CancelTransferResult))?,
+                    GhostCallbackData::Response(Err(e)) => msg.respond(Err(e))?,
+                    GhostCallbackData::Timeout(bt) => {
+                        msg.respond(Err(format!("timeout: {:?}", bt).into()))?
+                    }
+                    _ => msg.respond(Err(format!("bad response: {:?}", response).into()))?,
+                }
+                Ok(())
+            }),
+        )
+    }
+
     /// Process any Client events or requests
     pub(crate) fn handle_msg_from_client(
         &mut self,
@@ -218,6 +338,14 @@ impl<'engine> GhostEngine<'engine> {
                 trace!("ClientToLib3h::Bootstrap: {:?}", &data);
                 self.handle_bootstrap(msg, data)
             }
+            ClientToLib3h::QueryPeerLatency(data) => {
+                trace!("ClientToLib3h::QueryPeerLatency: {:?}", data);
+                self.handle_query_peer_latency(msg, data)
+            }
+            ClientToLib3h::CancelTransfer(data) => {
+                trace!("ClientToLib3h::CancelTransfer: {:?}", data);
+                self.handle_cancel_transfer(msg, data)
+            }
             ClientToLib3h::JoinSpace(data) => {
                 trace!("ClientToLib3h::JoinSpace: {:?}", data);
                 let result = self
@@ -306,11 +434,8 @@ impl<'engine> GhostEngine<'engine> {
         peer: PeerData,
     ) -> GhostResult<()> {
         // TODO #150 - Send JoinSpace to all known peers
-        let mut payload = Vec::new();
-        let p2p_msg = P2pProtocol::BroadcastJoinSpace(space_address.clone(), peer.clone());
-        p2p_msg
-            .serialize(&mut Serializer::new(&mut payload))
-            .unwrap();
+        let payload =
+            P2pMessage::MsgJoinSpace(join_space_to_p2p(&space_address, &peer)).into_bytes();
         trace!(
             "{} - Broadcasting JoinSpace: {}, {}",
             self.name,
@@ -534,7 +659,7 @@ impl<'engine> GhostEngine<'engine> {
         space_address: SpaceHash,
         from_agent_id: AgentPubKey,
         _to_agent_id: AgentPubKey,
-        net_msg: P2pProtocol,
+        net_msg: P2pMessage,
     ) -> Lib3hResult<(
         &mut GatewayParentWrapper<GhostEngine<'engine>, P2pGateway>,
         Opaque,
@@ -552,10 +677,7 @@ impl<'engine> GhostEngine<'engine> {
         }*/
 
         // Serialize payload
-        let mut payload = Vec::new();
-        net_msg
-            .serialize(&mut Serializer::new(&mut payload))
-            .unwrap();
+        let payload = net_msg.into_bytes();
 
         let space_gateway = self
             .space_gateway_map
@@ -587,7 +709,7 @@ impl<'engine> GhostEngine<'engine> {
             msg.space_address.clone(),
             msg.from_agent_id.clone(),
             msg.to_agent_id.clone(),
-            P2pProtocol::DirectMessage(msg),
+            P2pMessage::MsgDirectRequest(direct_message_to_p2p(&msg)),
         ) {
             Ok(r) => r,
             Err(e) => {
@@ -713,6 +835,8 @@ fn includes(list_a: &[AspectHash], list_b: &[AspectHash]) -> bool {
     set_b.is_subset(&set_a)
 }
 
+/// Send the gossip bundle to every peer in the list,
+/// `reliable: false` lets the transport drop it
 #[allow(non_snake_case)]
 pub fn handle_GossipTo<
     G: GhostActor<
@@ -728,10 +852,11 @@ pub fn handle_GossipTo<
     gateway: &mut GatewayParentWrapper<GhostEngine, G>,
     from_peer_name: &Lib3hUri,
     gossip_data: GossipToData,
+    reliable: bool,
 ) -> Lib3hResult<()> {
     debug!(
-        "({}) handle_GossipTo: {:?}",
-        gateway_identifier, gossip_data,
+        "({}) handle_GossipTo (reliable: {}): {:?}",
+        gateway_identifier, reliable, gossip_data,
     );
 
     for to_peer_name in gossip_data.peer_name_list {
@@ -744,19 +869,19 @@ pub fn handle_GossipTo<
         //            // TODO END
 
         // Convert DHT *GossipTo* to P2P *Gossip*
-        let p2p_gossip = P2pProtocol::Gossip(GossipData {
+        let payload = P2pMessage::MsgGossip(gossip_to_p2p(&GossipData {
             space_address: gateway_identifier.clone().into(),
             to_peer_name: to_peer_name.clone(),
             from_peer_name: from_peer_name.clone(),
             bundle: gossip_data.bundle.clone(),
-        });
-        let mut payload = Vec::new();
-        p2p_gossip
-            .serialize(&mut Serializer::new(&mut payload))
-            .expect("P2pProtocol::Gossip serialization failed");
+        }))
+        .into_bytes();
         // Forward gossip to the inner_transport
-        let msg =
-            transport::protocol::RequestToChild::create_send_message(to_peer_name, payload.into());
+        let msg = transport::protocol::RequestToChild::SendMessage {
+            uri: to_peer_name,
+            payload: payload.into(),
+            reliable,
+        };
         gateway.publish(
             span.child("Transport Transport::SendMessage"),
             GatewayRequestToChild::Transport(msg),
@@ -769,7 +894,10 @@ mod tests {
     use super::*;
     use crate::{
         dht::mirror_dht::MirrorDht,
-        engine::GatewayId,
+        engine::{
+            default_dht_arc_resize_interval, default_dht_target_maximum_r_value,
+            default_dht_target_minimum_r_value, default_transit_encodings, GatewayId,
+        },
         tests::enable_logging_for_test,
         transport::{memory_mock::memory_server, websocket::tls::TlsConfig},
     };
@@ -799,6 +927,19 @@ mod tests {
             dht_gossip_interval: 100,
             dht_timeout_threshold: 10000,
             dht_custom_config: vec![],
+            dht_arc_resize_interval: default_dht_arc_resize_interval(),
+            dht_target_minimum_r_value: default_dht_target_minimum_r_value(),
+            dht_target_maximum_r_value: default_dht_target_maximum_r_value(),
+            transit_encodings: default_transit_encodings(),
+            relay_uri: None,
+            relay_quota: None,
+            keystore_passphrase: None,
+            stream_limits: None,
+            reconnect_policy: None,
+            compression: None,
+            chunking: None,
+            discovery: vec![],
+            sign_mdns_records: false,
         };
         let dht_factory = MirrorDht::new_with_config;
 
@@ -819,6 +960,19 @@ mod tests {
             dht_gossip_interval: 100,
             dht_timeout_threshold: 1000,
             dht_custom_config: vec![],
+            dht_arc_resize_interval: default_dht_arc_resize_interval(),
+            dht_target_minimum_r_value: default_dht_target_minimum_r_value(),
+            dht_target_maximum_r_value: default_dht_target_maximum_r_value(),
+            transit_encodings: default_transit_encodings(),
+            relay_uri: None,
+            relay_quota: None,
+            keystore_passphrase: None,
+            stream_limits: None,
+            reconnect_policy: None,
+            compression: None,
+            chunking: None,
+            discovery: vec![],
+            sign_mdns_records: false,
         };
         let dht_factory = MirrorDht::new_with_config;
 
@@ -834,6 +988,38 @@ mod tests {
         lib3h
     }
 
+    #[test]
+    fn test_ghost_engine_keystore() {
+        use crate::keystore::keystore_protocol::*;
+        use std::sync::{Arc, Mutex};
+
+        let mut engine = make_test_engine("test_ghost_engine_keystore");
+        let listed: Arc<Mutex<Option<Vec<KeyInfo>>>> = Arc::new(Mutex::new(None));
+        let listed_clone = listed.clone();
+        engine
+            .keystore()
+            .request(
+                test_span(),
+                RequestToChild::ListKeys,
+                Box::new(move |_: &mut GhostEngine, response| {
+                    match response {
+                        GhostCallbackData::Response(Ok(RequestToChildResponse::ListKeys(keys))) => {
+                            *listed_clone.lock().unwrap() = Some(keys)
+                        }
+                        _ => panic!("bad response: {:?}", response),
+                    }
+                    Ok(())
+                }),
+            )
+            .unwrap();
+        engine.process().unwrap();
+
+        // the keystore the engine took its node id from answers it
+        let keys = listed.lock().unwrap().take().expect("keys were listed");
+        let node_id = engine.transport_keys.node_id.to_string();
+        assert!(keys.iter().any(|key| key.id == node_id));
+    }
+
     #[test]
     fn test_ghost_engine_construct() {
         let lib3h = make_test_engine_wrapper("test_ghost_engine_construct");
@@ -999,6 +1185,41 @@ mod tests {
         );*/
     }
 
+    #[test]
+    fn test_ghost_engine_report_pruned_entry() {
+        let mut core = MockCore {};
+        let mut engine = make_test_engine_wrapper("test_ghost_engine_report_pruned_entry");
+        let req_data = make_test_join_request();
+        let result = engine.as_mut().handle_join_space(test_span(), &req_data);
+        assert!(result.is_ok());
+        let _ = engine.process(&mut core);
+        let _ = engine.drain_messages();
+
+        // the space DHT pruned an entry that fell out of its storage arc
+        let chain_id = (req_data.space_address.clone(), req_data.agent_id.clone());
+        let mut msg = GhostMessage::test_constructor();
+        msg.put_message(GatewayRequestToParent::Dht(
+            DhtRequestToParent::EntryPruned("entry_addr".into()),
+        ));
+        let result = engine
+            .as_mut()
+            .handle_space_request(test_span(), &chain_id, msg);
+        assert!(result.is_ok());
+        engine.process(&mut core).unwrap();
+
+        let dropped: Vec<DropEntryData> = engine
+            .drain_messages()
+            .into_iter()
+            .filter_map(|mut msg| match msg.take_message() {
+                Some(Lib3hToClient::HandleDropEntry(drop_entry_data)) => Some(drop_entry_data),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(1, dropped.len());
+        assert_eq!(req_data.space_address, dropped[0].space_address);
+        assert_eq!(Address::from("entry_addr"), dropped[0].entry_address);
+    }
+
     fn make_test_entry() -> ProvidedEntryData {
         let aspect_list = Vec::new();
         let entry_data = EntryData {
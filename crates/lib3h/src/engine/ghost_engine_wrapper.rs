@@ -110,6 +110,14 @@ where
                                     uri: Lib3hUri::with_undefined(), // client should have this already deprecated
                                 })
                             }
+                            ClientToLib3hResponse::QueryPeerLatencyResult(sent_data) => {
+                                let mut data = sent_data;
+                                data.request_id = request_id.clone();
+                                Lib3hServerProtocol::QueryPeerLatencyResult(data)
+                            }
+                            ClientToLib3hResponse::CancelTransferResult => {
+                                server_success(request_id.clone(), space_addr, agent)
+                            }
                             ClientToLib3hResponse::JoinSpaceResult => {
                                 server_success(request_id.clone(), space_addr, agent)
                             }
@@ -164,6 +172,16 @@ where
                 SpaceHash::from("bogus_address"),
                 "bogus_agent".into(),
             ),
+            Lib3hClientProtocol::QueryPeerLatency(data) => (
+                data.request_id.to_string(),
+                SpaceHash::from("bogus_address"),
+                "bogus_agent".into(),
+            ),
+            Lib3hClientProtocol::CancelTransfer(data) => (
+                data.request_id.to_string(),
+                SpaceHash::from("bogus_address"),
+                "bogus_agent".into(),
+            ),
             Lib3hClientProtocol::JoinSpace(data) => (
                 data.request_id.to_string(),
                 data.space_address.clone(),
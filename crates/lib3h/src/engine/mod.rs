@@ -1,3 +1,4 @@
+mod discovery_layer;
 pub mod engine_actor;
 pub mod ghost_engine;
 pub mod ghost_engine_wrapper;
@@ -6,19 +7,32 @@ pub mod p2p_protocol;
 mod space_layer;
 
 use crate::{
-    dht::dht_protocol::*,
+    dht::{
+        dht_config::{
+            DEFAULT_ARC_RESIZE_INTERVAL_MS, DEFAULT_TARGET_MAXIMUM_R_VALUE,
+            DEFAULT_TARGET_MINIMUM_R_VALUE,
+        },
+        dht_protocol::*,
+    },
+    discovery::DiscoveryActorParentWrapperDyn,
     engine::engine_actor::ClientToLib3hMessage,
     error::*,
     gateway::{protocol::*, P2pGateway},
+    keystore::KeystoreActorParentWrapper,
     track::Tracker,
-    transport::{websocket::tls::TlsConfig, TransportMultiplex},
+    transport::{
+        websocket::{streams::StreamLimits, tls::TlsConfig},
+        TransportMultiplex,
+    },
 };
 use detach::Detach;
 use lib3h_crypto_api::{Buffer, CryptoSystem};
 use lib3h_ghost_actor::{prelude::*, RequestId};
+use lib3h_p2p_protocol::transit_encoding::{CompressionType, TransitEncodingType};
 use lib3h_protocol::{protocol::*, types::*, uri::Lib3hUri, Address};
 use std::{
     collections::{HashMap, HashSet},
+    net::SocketAddr,
     path::PathBuf,
 };
 
@@ -63,6 +77,14 @@ enum RealEngineTrackerData {
 pub enum TransportConfig {
     Websocket(TlsConfig),
     Memory(String),
+    /// length-prefixed frames over raw tcp connections
+    Tcp(TlsConfig),
+    /// datagrams from a udp socket, acked and retransmitted
+    /// unless sent unreliably
+    Udp,
+    /// length-prefixed frames over unix domain sockets,
+    /// not available on windows
+    Uds,
 }
 
 /// Struct holding all config settings for the Engine
@@ -77,6 +99,159 @@ pub struct EngineConfig {
     pub dht_gossip_interval: u64,
     pub dht_timeout_threshold: u64,
     pub dht_custom_config: Vec<u8>,
+    /// how often space DHTs resize their storage arc, in milliseconds
+    #[serde(default = "default_dht_arc_resize_interval")]
+    pub dht_arc_resize_interval: u64,
+    /// space DHTs grow their storage arc while their estimated
+    /// r-value is below this
+    #[serde(default = "default_dht_target_minimum_r_value")]
+    pub dht_target_minimum_r_value: f64,
+    /// and shrink it while it is above this
+    #[serde(default = "default_dht_target_maximum_r_value")]
+    pub dht_target_maximum_r_value: f64,
+    /// transit encodings we accept between nodes, in order of preference
+    #[serde(default = "default_transit_encodings")]
+    pub transit_encodings: Vec<TransitEncodingType>,
+    /// if this node cannot be connected to directly,
+    /// the publicly reachable node to ask to relay its traffic
+    #[serde(default)]
+    pub relay_uri: Option<Lib3hUri>,
+    /// relay traffic for nodes that cannot be connected to directly
+    #[serde(default)]
+    pub relay_quota: Option<RelayQuota>,
+    /// persist our keys in `work_dir`, encrypted under this passphrase,
+    /// so the node keeps its identity across restarts, never serialized
+    #[serde(default, skip_serializing)]
+    pub keystore_passphrase: Option<String>,
+    /// bound the connections and outgoing queues of the websocket transport
+    #[serde(default)]
+    pub stream_limits: Option<StreamLimits>,
+    /// reconnect to the nodes we lose the connection to,
+    /// we do not unless configured
+    #[serde(default)]
+    pub reconnect_policy: Option<ReconnectPolicy>,
+    /// compress large payloads on the connections to nodes that agree,
+    /// we do not unless configured
+    #[serde(default)]
+    pub compression: Option<CompressionConfig>,
+    /// send large payloads in chunks to the nodes able to reassemble them,
+    /// we do not unless configured
+    #[serde(default)]
+    pub chunking: Option<ChunkingConfig>,
+    /// where to look for the nodes of our network besides `bootstrap_nodes`,
+    /// all of them are merged
+    #[serde(default)]
+    pub discovery: Vec<DiscoveryConfig>,
+    /// sign the mDNS records of the websocket transport with our node key,
+    /// and ignore the ones not signed by the node they name
+    #[serde(default)]
+    pub sign_mdns_records: bool,
+}
+
+/// A discovery backend finding the nodes of our network
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type", content = "data")]
+pub enum DiscoveryConfig {
+    /// uris of seed nodes in the TXT records of `domain`,
+    /// as answered by the dns server at `resolver`
+    DnsSeed {
+        domain: String,
+        resolver: SocketAddr,
+    },
+    /// a bootstrap service at `url` registering the nodes of each network
+    HttpBootstrap { url: Lib3hUri },
+}
+
+/// Limits on the traffic a node relays for others
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RelayQuota {
+    /// how many nodes we relay for at once
+    pub max_clients: usize,
+    /// how much we forward to and from each of them
+    pub max_bytes_per_minute: u64,
+}
+
+/// How a network gateway retries connecting to a node it lost
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReconnectPolicy {
+    /// delay before the first attempt, doubled after each one
+    pub initial_delay_ms: u64,
+    /// the delay stops doubling there
+    pub max_delay_ms: u64,
+    /// up to this much is randomly added to every delay,
+    /// so nodes that lost each other do not retry in lockstep
+    pub jitter_ms: u64,
+    /// attempts made before we give up on the node
+    pub max_attempts: u32,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial_delay_ms: 500,
+            max_delay_ms: 30_000,
+            jitter_ms: 250,
+            max_attempts: 10,
+        }
+    }
+}
+
+/// Which payloads a network gateway compresses, and how
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CompressionConfig {
+    /// compressions we accept, offered in this order when connecting
+    pub compressions: Vec<CompressionType>,
+    /// smaller payloads are not worth compressing
+    pub threshold_bytes: usize,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            compressions: vec![CompressionType::Deflate],
+            threshold_bytes: 512,
+        }
+    }
+}
+
+/// Which payloads a network gateway sends in chunks, and how large they are
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChunkingConfig {
+    /// larger payloads are sent in chunks
+    pub threshold_bytes: usize,
+    /// size of the chunks, capped to half the threshold
+    pub chunk_bytes: usize,
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        ChunkingConfig {
+            threshold_bytes: 256 * 1024,
+            chunk_bytes: 64 * 1024,
+        }
+    }
+}
+
+pub fn default_dht_arc_resize_interval() -> u64 {
+    DEFAULT_ARC_RESIZE_INTERVAL_MS
+}
+
+pub fn default_dht_target_minimum_r_value() -> f64 {
+    DEFAULT_TARGET_MINIMUM_R_VALUE
+}
+
+pub fn default_dht_target_maximum_r_value() -> f64 {
+    DEFAULT_TARGET_MAXIMUM_R_VALUE
+}
+
+/// Only encrypted transit encodings are accepted unless configured otherwise,
+/// the open ones are meant for debugging
+pub fn default_transit_encodings() -> Vec<TransitEncodingType> {
+    vec![
+        TransitEncodingType::SodiumPacked,
+        TransitEncodingType::SodiumJson,
+    ]
 }
 
 pub struct TransportKeys {
@@ -114,6 +289,9 @@ pub struct GhostEngine<'engine> {
     dht_factory: DhtFactory,
     /// Tracking request_id's sent to core
     request_track: Tracker<RealEngineTrackerData>,
+    /// Keystore holding our keys, answering the signing and key exchange
+    /// requests made through `keystore()`
+    keystore: Detach<KeystoreActorParentWrapper<GhostEngine<'engine>>>,
     /// Multiplexer holding the network gateway
     multiplexer: Detach<GatewayParentWrapper<GhostEngine<'engine>, TransportMultiplex<P2pGateway>>>,
     /// Cached this_peer of the multiplexer
@@ -121,6 +299,10 @@ pub struct GhostEngine<'engine> {
 
     /// Store active connections?
     network_connections: HashSet<Lib3hUri>,
+    /// Backends finding the nodes of our network
+    discovery_backends: Vec<DiscoveryActorParentWrapperDyn<GhostEngine<'engine>>>,
+    /// Nodes found by the discovery backends, each bootstrapped once
+    discovered_nodes: HashSet<Lib3hUri>,
     /// Map of P2p gateway per Space+Agent
     space_gateway_map:
         HashMap<ChainId, Detach<GatewayParentWrapper<GhostEngine<'engine>, P2pGateway>>>,
@@ -1,16 +1,15 @@
 use crate::{
     dht::dht_protocol::*,
-    engine::{ghost_engine::handle_GossipTo, p2p_protocol::P2pProtocol, GhostEngine},
-    error::{ErrorKind, Lib3hError, Lib3hResult},
+    engine::{ghost_engine::handle_GossipTo, p2p_protocol::*, GhostEngine},
+    error::{ErrorKind, Lib3hResult},
     gateway::protocol::*,
     new_root_span, transport,
 };
 
 use holochain_tracing::Span;
 use lib3h_ghost_actor::prelude::*;
+use lib3h_p2p_protocol::p2p::*;
 use lib3h_protocol::{data_types::*, protocol::*, uri::Lib3hUri, DidWork};
-use rmp_serde::{Deserializer, Serializer};
-use serde::{Deserialize, Serialize};
 
 /// Network layer related private methods
 impl<'engine> GhostEngine<'engine> {
@@ -31,6 +30,24 @@ impl<'engine> GhostEngine<'engine> {
                 GatewayRequestToParent::Transport(transport_request) => {
                     self.handle_network_transport_request(span, &transport_request)?;
                 }
+                GatewayRequestToParent::PeerReconnected(peer_data) => {
+                    self.lib3h_endpoint.publish(
+                        span.child("send event Lib3hToClient::PeerReconnected"),
+                        Lib3hToClient::PeerReconnected(peer_data),
+                    )?;
+                }
+                GatewayRequestToParent::PeerLost(peer_data) => {
+                    self.lib3h_endpoint.publish(
+                        span.child("send event Lib3hToClient::PeerLost"),
+                        Lib3hToClient::PeerLost(peer_data),
+                    )?;
+                }
+                GatewayRequestToParent::TransferProgress(progress_data) => {
+                    self.lib3h_endpoint.publish(
+                        span.child("send event Lib3hToClient::TransferProgress"),
+                        Lib3hToClient::TransferProgress(progress_data),
+                    )?;
+                }
             }
         }
 
@@ -65,13 +82,34 @@ impl<'engine> GhostEngine<'engine> {
                     self.multiplexer.as_mut(),
                     from_peer_name,
                     gossip_data,
+                    true,
                 )
                 .expect("Failed to gossip with multiplexer");
             }
-            DhtRequestToParent::GossipUnreliablyTo(_data) => {
-                // no-op
+            DhtRequestToParent::GossipUnreliablyTo(gossip_data) => {
+                let from_peer_name = &self.this_net_peer.peer_name;
+                handle_GossipTo(
+                    span.child("handle_GossipUnreliablyTo"),
+                    self.config.network_id.id.clone(),
+                    self.multiplexer.as_mut(),
+                    from_peer_name,
+                    gossip_data,
+                    false,
+                )
+                .expect("Failed to gossip with multiplexer");
             }
             DhtRequestToParent::HoldPeerRequested(peer_data) => {
+                // there is no connecting to a node behind a relay,
+                // nor from behind one: messages go through the relay
+                if peer_data.peer_location.get_relay_target().is_some()
+                    || self.config.relay_uri.is_some()
+                {
+                    self.multiplexer.publish(
+                        span.child("DhtRequestToParent::HoldPeerRequested"),
+                        GatewayRequestToChild::Dht(DhtRequestToChild::HoldPeer(peer_data)),
+                    )?;
+                    return Ok(());
+                }
                 // TODO #167 - hardcoded for MirrorDHT and thus should not appear here.
                 // Connect to every peer we are requested to hold.
                 info!(
@@ -132,6 +170,9 @@ impl<'engine> GhostEngine<'engine> {
             transport::protocol::RequestToParent::Disconnect(uri) => {
                 debug!("disconnect from {}", uri);
             }
+            transport::protocol::RequestToParent::Evicted(uri) => {
+                debug!("evicted connection to {}", uri);
+            }
             transport::protocol::RequestToParent::ErrorOccured { uri, error } => {
                 error!("unhandled error {} on {}", error, uri);
             }
@@ -146,16 +187,14 @@ impl<'engine> GhostEngine<'engine> {
                 if payload.len() == 0 {
                     panic!("We should no longer ever be sending zero length messages");
                 } else {
-                    let mut de = Deserializer::new(&payload[..]);
-                    let maybe_msg: Result<P2pProtocol, rmp_serde::decode::Error> =
-                        Deserialize::deserialize(&mut de);
+                    let maybe_msg = P2pMessage::from_bytes(payload.to_vec());
                     if let Err(e) = maybe_msg {
                         error!("Failed deserializing msg: {:?}", e);
-                        return Err(Lib3hError::new(ErrorKind::RmpSerdeDecodeError(e)));
+                        return Err(e.into());
                     }
                     let p2p_msg = maybe_msg.unwrap();
                     // debug!("p2p_msg: {:?}", p2p_msg);
-                    self.serve_P2pProtocol(span.child("serve_P2pProtocol"), uri, p2p_msg)?;
+                    self.serve_P2pMessage(span.child("serve_P2pMessage"), uri, p2p_msg)?;
                 }
             }
         };
@@ -195,11 +234,16 @@ impl<'engine> GhostEngine<'engine> {
                         // Send to other node our Joined Spaces
                         {
                             let space_list = me.get_all_spaces();
-                            let our_joined_space_list = P2pProtocol::AllJoinedSpaceList(space_list);
-                            let mut payload = Vec::new();
-                            our_joined_space_list
-                                .serialize(&mut Serializer::new(&mut payload))
-                                .unwrap();
+                            let our_joined_space_list =
+                                P2pMessage::MsgAllJoinedSpaceList(MsgAllJoinedSpaceList {
+                                    joined_space_list: space_list
+                                        .iter()
+                                        .map(|(space_address, peer)| {
+                                            join_space_to_p2p(space_address, peer)
+                                        })
+                                        .collect(),
+                                });
+                            let payload = our_joined_space_list.to_bytes();
                             trace!(
                                 "AllJoinedSpaceList: {:?} to {:?}",
                                 our_joined_space_list,
@@ -249,17 +293,17 @@ impl<'engine> GhostEngine<'engine> {
         Ok(())
     }
 
-    /// Serve a P2pProtocol sent to us by the network.
-    /// TODO #150
+    /// Serve a P2pMessage sent to us by the network.
     #[allow(non_snake_case)]
-    fn serve_P2pProtocol(
+    fn serve_P2pMessage(
         &mut self,
         span: Span,
-        _from: &Lib3hUri,
-        p2p_msg: P2pProtocol,
+        from: &Lib3hUri,
+        p2p_msg: P2pMessage,
     ) -> Lib3hResult<()> {
         match p2p_msg {
-            P2pProtocol::Gossip(msg) => {
+            P2pMessage::MsgGossip(msg) => {
+                let msg = gossip_from_p2p(msg)?;
                 // Prepare remoteGossipTo to post to dht
                 let gossip = RemoteGossipBundleData {
                     from_peer_name: msg.from_peer_name.clone(),
@@ -287,11 +331,13 @@ impl<'engine> GhostEngine<'engine> {
                     }
                 }
             }
-            P2pProtocol::DirectMessage(dm_data) => {
+            P2pMessage::MsgDirectRequest(direct) => {
                 // we got some data that should go up the multiplexer
                 // let's try decoding it : )
+                let dm_data = direct_message_from_p2p(direct);
 
-                self.multiplexer
+                let result = self
+                    .multiplexer
                     .as_mut()
                     .as_mut()
                     .received_data_for_agent_space_route(
@@ -299,30 +345,51 @@ impl<'engine> GhostEngine<'engine> {
                         &dm_data.to_agent_id,
                         &dm_data.from_agent_id,
                         dm_data.content,
-                    )?;
+                    );
+                if let Err(e) = result {
+                    match e.kind() {
+                        ErrorKind::BadSpaceHash(_) | ErrorKind::BadToId(_) => {
+                            // let the sender know rather than failing our process loop
+                            warn!("{} cannot deliver message from {}: {}", self.name, from, e);
+                            self.multiplexer.publish(
+                                span.follower("GatewayRequestToChild::RejectChannel"),
+                                GatewayRequestToChild::RejectChannel {
+                                    uri: from.clone(),
+                                    space_address: dm_data.space_address,
+                                    to_agent_id: dm_data.to_agent_id,
+                                    from_agent_id: dm_data.from_agent_id,
+                                    error: e,
+                                },
+                            )?;
+                        }
+                        _ => return Err(e),
+                    }
+                }
             }
-            P2pProtocol::DirectMessageResult(_dm_data) => {
-                panic!("we should never get a DirectMessageResult at this layer... only using DirectMessage");
+            P2pMessage::MsgDirectResponse(_direct) => {
+                panic!("we should never get a MsgDirectResponse at this layer... only using MsgDirectRequest");
             }
-            P2pProtocol::PeerName(_, _, _) => {
+            P2pMessage::MsgPeerName(_) => {
                 // no-op
             }
-            P2pProtocol::BroadcastJoinSpace(gateway_id, peer_data) => {
+            P2pMessage::MsgJoinSpace(join_space) => {
+                let (gateway_id, peer_data) = join_space_from_p2p(&join_space)?;
                 debug!("Received JoinSpace: {} {:?}", gateway_id, peer_data);
                 for (_, space_gateway) in self.space_gateway_map.iter_mut() {
                     space_gateway.publish(
-                        span.follower("P2pProtocol::BroadcastJoinSpace"),
+                        span.follower("P2pMessage::MsgJoinSpace"),
                         GatewayRequestToChild::Dht(DhtRequestToChild::HoldPeer(peer_data.clone())),
                     )?;
                 }
             }
-            P2pProtocol::AllJoinedSpaceList(join_list) => {
-                debug!("Received AllJoinedSpaceList: {:?}", join_list);
-                for (space_address, peer_data) in join_list {
+            P2pMessage::MsgAllJoinedSpaceList(all_joined) => {
+                debug!("Received AllJoinedSpaceList: {:?}", all_joined);
+                for join_space in all_joined.joined_space_list.iter() {
+                    let (space_address, peer_data) = join_space_from_p2p(join_space)?;
                     let maybe_space_gateway = self.get_first_space_mut(&space_address);
                     if let Some(space_gateway) = maybe_space_gateway {
                         let _ = space_gateway.publish(
-                            span.follower("P2pProtocol::AllJoinedSpaceList"),
+                            span.follower("P2pMessage::MsgAllJoinedSpaceList"),
                             GatewayRequestToChild::Dht(DhtRequestToChild::HoldPeer(
                                 peer_data.clone(),
                             )),
@@ -330,8 +397,8 @@ impl<'engine> GhostEngine<'engine> {
                     }
                 }
             }
-            P2pProtocol::CapnProtoMessage(_) => {
-                panic!("Gateway should handle this case and NOT pass it to us");
+            msg => {
+                warn!("unhandled P2pMessage at network layer: {:?}", msg);
             }
         };
         Ok(())
@@ -1,4 +1,18 @@
-use crate::dht::dht_protocol::PeerData;
+//! Conversions between lib3h data types and the capnp `P2pMessage` wire
+//! protocol from lib3h_p2p_protocol.
+//!
+//! Nodes that predate `P2P_PROTOCOL_VERSION_CAPNP` only understand the interim
+//! MessagePack `LegacyP2pProtocol` envelope. The network gateway keeps talking
+//! to a remote node with that envelope until the node reports its protocol
+//! version in a ping or pong, translating at the edge with `encode_for_peer`
+//! and `decode_p2p_message`, so the rest of lib3h only deals in `P2pMessage`.
+
+use crate::{
+    dht::dht_protocol::PeerData,
+    error::{Lib3hError, Lib3hResult},
+    message_encoding::map_encoded_payload,
+};
+use lib3h_p2p_protocol::{multiplex::MultiplexMessage, p2p::*};
 use lib3h_protocol::{
     data_types::{DirectMessageData, Opaque},
     types::SpaceHash,
@@ -6,27 +20,19 @@ use lib3h_protocol::{
 };
 use rmp_serde::{Deserializer, Serializer};
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 
-pub type GatewayId = String;
-pub type PeerTimestamp = u64;
-
-/// Enum holding all message types in the 'network module <-> network module' protocol.
-/// TODO #150 - replace this with the p2p-protocol crate
+/// The 'network module <-> network module' protocol spoken before we moved
+/// to `P2pMessage`. Only kept around to talk to older nodes during rollout.
+/// Variant order is part of the wire format.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub enum P2pProtocol {
+enum LegacyP2pProtocol {
     Gossip(GossipData),
     DirectMessage(DirectMessageData),
     DirectMessageResult(DirectMessageData),
-    /// Notify another node's our identify in a specific gateway/dht
-    PeerName(GatewayId, Lib3hUri, PeerTimestamp),
-    /// Broadcast JoinSpace to all when joining a space
+    PeerName(String, Lib3hUri, u64),
     BroadcastJoinSpace(SpaceHash, PeerData),
-    /// For sending a peer's 'JoinSpace' info to a newly connected peer
     AllJoinedSpaceList(Vec<(SpaceHash, PeerData)>),
-    /// We would like to transition to using our capnproto p2p protocol
-    /// during the transition phase, capnproto messages will be
-    /// doubly encoded in this P2pProtocol enum variant,
-    /// once all messages have transitioned, we can drop this layer
     CapnProtoMessage(Vec<u8>),
 }
 
@@ -39,22 +45,349 @@ pub struct GossipData {
     pub bundle: Opaque,
 }
 
-impl P2pProtocol {
-    /// rust messagepack decode these bytes into a P2pProtocol instance
-    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, rmp_serde::decode::Error> {
-        let mut de = Deserializer::new(&bytes[..]);
+impl LegacyP2pProtocol {
+    fn from_bytes(bytes: &[u8]) -> Result<Self, rmp_serde::decode::Error> {
+        let mut de = Deserializer::new(bytes);
         Deserialize::deserialize(&mut de)
     }
 
-    /// encode this P2pProtocol instance as rust messagepack bytes
-    pub fn to_bytes(&self) -> Vec<u8> {
+    fn to_bytes(&self) -> Vec<u8> {
         let mut out = Vec::new();
         self.serialize(&mut Serializer::new(&mut out)).unwrap();
         out
     }
+}
+
+// -- lib3h data type conversions -- //
+
+fn parse_uri(uri: &str) -> Lib3hResult<Lib3hUri> {
+    Ok(Lib3hUri::try_from(uri)?)
+}
+
+/// Build a `MsgDirect` out of DirectMessageData
+pub fn direct_message_to_p2p(dm_data: &DirectMessageData) -> MsgDirect {
+    MsgDirect {
+        request_id: dm_data.request_id.clone(),
+        data: dm_data.content.to_vec(),
+        space_address: dm_data.space_address.to_string(),
+        to_agent_id: dm_data.to_agent_id.to_string(),
+        from_agent_id: dm_data.from_agent_id.to_string(),
+    }
+}
+
+/// Convert a received `MsgDirect` back to DirectMessageData
+pub fn direct_message_from_p2p(direct: MsgDirect) -> DirectMessageData {
+    DirectMessageData {
+        space_address: direct.space_address.into(),
+        request_id: direct.request_id,
+        to_agent_id: direct.to_agent_id.into(),
+        from_agent_id: direct.from_agent_id.into(),
+        content: direct.data.into(),
+    }
+}
+
+/// Build a `MsgJoinSpace` announcing `peer` in `space_address`
+pub fn join_space_to_p2p(space_address: &SpaceHash, peer: &PeerData) -> MsgJoinSpace {
+    MsgJoinSpace {
+        space_address: space_address.to_string(),
+        peer_name: peer.peer_name.to_string(),
+        peer_location: peer.peer_location.to_string(),
+        timestamp: peer.timestamp,
+    }
+}
+
+/// Convert a received `MsgJoinSpace` back to a space address and peer
+pub fn join_space_from_p2p(join_space: &MsgJoinSpace) -> Lib3hResult<(SpaceHash, PeerData)> {
+    Ok((
+        join_space.space_address.clone().into(),
+        PeerData {
+            peer_name: parse_uri(&join_space.peer_name)?,
+            peer_location: parse_uri(&join_space.peer_location)?,
+            timestamp: join_space.timestamp,
+        },
+    ))
+}
+
+/// Build a `MsgGossip` out of GossipData
+pub fn gossip_to_p2p(gossip_data: &GossipData) -> MsgGossip {
+    MsgGossip {
+        space_address: gossip_data.space_address.to_string(),
+        to_peer_name: gossip_data.to_peer_name.to_string(),
+        from_peer_name: gossip_data.from_peer_name.to_string(),
+        bundle: gossip_data.bundle.to_vec(),
+    }
+}
+
+/// Convert a received `MsgGossip` back to GossipData
+pub fn gossip_from_p2p(gossip: MsgGossip) -> Lib3hResult<GossipData> {
+    Ok(GossipData {
+        space_address: gossip.space_address.into(),
+        to_peer_name: parse_uri(&gossip.to_peer_name)?,
+        from_peer_name: parse_uri(&gossip.from_peer_name)?,
+        bundle: gossip.bundle.into(),
+    })
+}
+
+/// Multiplex messages travel in a `MsgDirectRequest` without any
+/// addressing: the channel ids they carry stand for it.
+pub fn multiplex_to_p2p(msg: &MultiplexMessage) -> P2pMessage {
+    P2pMessage::MsgDirectRequest(MsgDirect {
+        request_id: String::new(),
+        data: msg.to_bytes(),
+        space_address: String::new(),
+        to_agent_id: String::new(),
+        from_agent_id: String::new(),
+    })
+}
+
+/// Does `direct` carry a multiplex message?
+pub fn is_multiplex_envelope(direct: &MsgDirect) -> bool {
+    direct.request_id.is_empty() && direct.space_address.is_empty() && direct.to_agent_id.is_empty()
+}
+
+/// Decode the multiplex message carried by `direct`
+pub fn multiplex_from_p2p(direct: &MsgDirect) -> Lib3hResult<MultiplexMessage> {
+    Ok(MultiplexMessage::from_bytes(&direct.data)?)
+}
+
+// -- relay envelope -- //
+
+/// Wrap a message for a relay to forward to `target`: a node id or a uri.
+/// The sender leaves `origin` empty, the relay fills it in when forwarding.
+pub fn relay_envelope_to_p2p(target: &str, origin: &str, payload: &[u8]) -> P2pMessage {
+    P2pMessage::MsgDirectRequest(MsgDirect {
+        request_id: String::new(),
+        data: payload.to_vec(),
+        space_address: String::new(),
+        to_agent_id: target.to_string(),
+        from_agent_id: origin.to_string(),
+    })
+}
+
+/// Does `direct` carry a message to relay?
+pub fn is_relay_envelope(direct: &MsgDirect) -> bool {
+    direct.request_id.is_empty()
+        && direct.space_address.is_empty()
+        && !direct.to_agent_id.is_empty()
+}
+
+// -- legacy envelope -- //
+
+/// Gateways carry space traffic in direct messages without a request id,
+/// the content of which is itself an encoded p2p message.
+fn is_gateway_wrapper(dm_data: &DirectMessageData) -> bool {
+    dm_data.request_id.is_empty()
+}
+
+fn from_legacy(legacy: LegacyP2pProtocol) -> Lib3hResult<P2pMessage> {
+    Ok(match legacy {
+        LegacyP2pProtocol::Gossip(gossip_data) => {
+            P2pMessage::MsgGossip(gossip_to_p2p(&gossip_data))
+        }
+        LegacyP2pProtocol::DirectMessage(mut dm_data) => {
+            if is_gateway_wrapper(&dm_data) {
+                dm_data.content = map_encoded_payload(dm_data.content, |payload| {
+                    Ok(decode_p2p_message(&payload)?.into_bytes().into())
+                })?;
+            }
+            P2pMessage::MsgDirectRequest(direct_message_to_p2p(&dm_data))
+        }
+        LegacyP2pProtocol::DirectMessageResult(dm_data) => {
+            P2pMessage::MsgDirectResponse(direct_message_to_p2p(&dm_data))
+        }
+        LegacyP2pProtocol::PeerName(gateway_id, peer_name, timestamp) => {
+            P2pMessage::MsgPeerName(MsgPeerName {
+                gateway_id,
+                peer_name: peer_name.to_string(),
+                timestamp,
+            })
+        }
+        LegacyP2pProtocol::BroadcastJoinSpace(space_address, peer) => {
+            P2pMessage::MsgJoinSpace(join_space_to_p2p(&space_address, &peer))
+        }
+        LegacyP2pProtocol::AllJoinedSpaceList(join_list) => {
+            P2pMessage::MsgAllJoinedSpaceList(MsgAllJoinedSpaceList {
+                joined_space_list: join_list
+                    .iter()
+                    .map(|(space_address, peer)| join_space_to_p2p(space_address, peer))
+                    .collect(),
+            })
+        }
+        LegacyP2pProtocol::CapnProtoMessage(bytes) => P2pMessage::from_bytes(bytes)?,
+    })
+}
+
+fn to_legacy(msg: P2pMessage) -> Lib3hResult<LegacyP2pProtocol> {
+    Ok(match msg {
+        P2pMessage::MsgGossip(gossip) => LegacyP2pProtocol::Gossip(gossip_from_p2p(gossip)?),
+        P2pMessage::MsgDirectRequest(direct) => {
+            let mut dm_data = direct_message_from_p2p(direct);
+            if is_gateway_wrapper(&dm_data) {
+                dm_data.content = map_encoded_payload(dm_data.content, |payload| {
+                    Ok(to_legacy(P2pMessage::from_bytes(payload.into())?)?
+                        .to_bytes()
+                        .into())
+                })?;
+            }
+            LegacyP2pProtocol::DirectMessage(dm_data)
+        }
+        P2pMessage::MsgDirectResponse(direct) => {
+            LegacyP2pProtocol::DirectMessageResult(direct_message_from_p2p(direct))
+        }
+        P2pMessage::MsgPeerName(peer_name) => LegacyP2pProtocol::PeerName(
+            peer_name.gateway_id,
+            parse_uri(&peer_name.peer_name)?,
+            peer_name.timestamp,
+        ),
+        P2pMessage::MsgJoinSpace(join_space) => {
+            let (space_address, peer) = join_space_from_p2p(&join_space)?;
+            LegacyP2pProtocol::BroadcastJoinSpace(space_address, peer)
+        }
+        P2pMessage::MsgAllJoinedSpaceList(all_joined) => LegacyP2pProtocol::AllJoinedSpaceList(
+            all_joined
+                .joined_space_list
+                .iter()
+                .map(join_space_from_p2p)
+                .collect::<Lib3hResult<Vec<_>>>()?,
+        ),
+        // older nodes tunnel everything else as capnp already
+        msg => LegacyP2pProtocol::CapnProtoMessage(msg.into_bytes()),
+    })
+}
+
+/// Decode a p2p message sent by a remote node, in either envelope.
+/// The legacy envelope is tried first: a capnp packed segment table starts
+/// with a bare integer, which is never a valid MessagePack enum.
+pub fn decode_p2p_message(bytes: &[u8]) -> Lib3hResult<P2pMessage> {
+    match LegacyP2pProtocol::from_bytes(bytes) {
+        Ok(legacy) => from_legacy(legacy),
+        Err(_) => Ok(P2pMessage::from_bytes(bytes.to_vec())?),
+    }
+}
+
+/// Re-encode a `P2pMessage` payload for a remote node speaking `protocol_version`.
+pub fn encode_for_peer(payload: Opaque, protocol_version: u32) -> Lib3hResult<Opaque> {
+    if protocol_version >= P2P_PROTOCOL_VERSION_CAPNP {
+        return Ok(payload);
+    }
+    let msg = P2pMessage::from_bytes(payload.into())
+        .map_err(|e| Lib3hError::from(format!("cannot encode for legacy peer: {:?}", e)))?;
+    Ok(to_legacy(msg)?.to_bytes().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_encoding::encode_payload;
+
+    fn test_peer() -> PeerData {
+        PeerData {
+            peer_name: Lib3hUri::with_agent_id(&"agent_1".into()),
+            peer_location: Lib3hUri::with_memory("addr_1"),
+            timestamp: 42,
+        }
+    }
+
+    fn test_dm_data(request_id: &str, content: Opaque) -> DirectMessageData {
+        DirectMessageData {
+            space_address: "space_1".into(),
+            request_id: request_id.to_string(),
+            to_agent_id: "agent_1".into(),
+            from_agent_id: "agent_2".into(),
+            content,
+        }
+    }
+
+    fn legacy_round_trip(msg: P2pMessage) {
+        let legacy = encode_for_peer(msg.to_bytes().into(), 0).unwrap();
+        assert!(LegacyP2pProtocol::from_bytes(&legacy).is_ok());
+        assert_eq!(msg, decode_p2p_message(&legacy).unwrap());
+    }
+
+    #[test]
+    fn it_should_pass_through_for_versioned_peers() {
+        let payload: Opaque = P2pMessage::create_ping(None).into_bytes().into();
+        let encoded = encode_for_peer(payload.clone(), P2P_PROTOCOL_VERSION).unwrap();
+        assert_eq!(payload, encoded);
+        assert!(decode_p2p_message(&encoded).is_ok());
+    }
+
+    #[test]
+    fn it_should_round_trip_legacy_messages() {
+        legacy_round_trip(P2pMessage::create_ping(Some(42)));
+        legacy_round_trip(P2pMessage::MsgDirectRequest(direct_message_to_p2p(
+            &test_dm_data("req_1", "hello".into()),
+        )));
+        legacy_round_trip(P2pMessage::MsgDirectResponse(direct_message_to_p2p(
+            &test_dm_data("req_1", "world".into()),
+        )));
+        legacy_round_trip(P2pMessage::MsgPeerName(MsgPeerName {
+            gateway_id: "network".to_string(),
+            peer_name: test_peer().peer_name.to_string(),
+            timestamp: 42,
+        }));
+        legacy_round_trip(P2pMessage::MsgJoinSpace(join_space_to_p2p(
+            &"space_1".into(),
+            &test_peer(),
+        )));
+        legacy_round_trip(P2pMessage::MsgAllJoinedSpaceList(MsgAllJoinedSpaceList {
+            joined_space_list: vec![join_space_to_p2p(&"space_1".into(), &test_peer())],
+        }));
+        legacy_round_trip(P2pMessage::MsgGossip(gossip_to_p2p(&GossipData {
+            space_address: "space_1".into(),
+            to_peer_name: test_peer().peer_name,
+            from_peer_name: test_peer().peer_name,
+            bundle: vec![1, 2, 3].into(),
+        })));
+    }
+
+    #[test]
+    fn it_should_tell_multiplex_messages_apart() {
+        let msg = MultiplexMessage::MsgChannelClose(42);
+        match decode_p2p_message(&multiplex_to_p2p(&msg).to_bytes()).unwrap() {
+            P2pMessage::MsgDirectRequest(direct) => {
+                assert!(is_multiplex_envelope(&direct));
+                assert_eq!(msg, multiplex_from_p2p(&direct).unwrap())
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        let wrapper = direct_message_to_p2p(&test_dm_data("", "hello".into()));
+        assert!(!is_multiplex_envelope(&wrapper));
+        assert!(!is_relay_envelope(&wrapper));
+        match relay_envelope_to_p2p("HcMnode", "", b"hello") {
+            P2pMessage::MsgDirectRequest(direct) => {
+                assert!(is_relay_envelope(&direct));
+                assert!(!is_multiplex_envelope(&direct));
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
 
-    /// convert this P2pProtocol instance into rust messagepack bytes
-    pub fn into_bytes(self) -> Vec<u8> {
-        self.to_bytes()
+    #[test]
+    fn it_should_convert_nested_gateway_messages() {
+        let inner = P2pMessage::MsgDirectRequest(direct_message_to_p2p(&test_dm_data(
+            "req_1",
+            "hello".into(),
+        )));
+        let wrapper = P2pMessage::MsgDirectRequest(direct_message_to_p2p(&test_dm_data(
+            "",
+            encode_payload(inner.to_bytes().into()),
+        )));
+        let legacy = encode_for_peer(wrapper.to_bytes().into(), 0).unwrap();
+        // older nodes expect the nested message in the legacy envelope too
+        match LegacyP2pProtocol::from_bytes(&legacy).unwrap() {
+            LegacyP2pProtocol::DirectMessage(dm_data) => {
+                map_encoded_payload(dm_data.content, |payload| {
+                    match LegacyP2pProtocol::from_bytes(&payload).unwrap() {
+                        LegacyP2pProtocol::DirectMessage(_) => (),
+                        other => panic!("unexpected nested message {:?}", other),
+                    }
+                    Ok(payload)
+                })
+                .unwrap();
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        assert_eq!(wrapper, decode_p2p_message(&legacy).unwrap());
     }
 }
@@ -11,9 +11,8 @@ use crate::{
 use detach::prelude::*;
 use holochain_tracing::Span;
 use lib3h_ghost_actor::prelude::*;
+use lib3h_p2p_protocol::p2p::P2pMessage;
 use lib3h_protocol::{data_types::*, protocol::*, types::SpaceHash, uri::Lib3hUri, DidWork};
-use rmp_serde::Deserializer;
-use serde::Deserialize;
 use std::collections::HashMap;
 use url::Url;
 
@@ -85,7 +84,7 @@ impl<'engine> GhostEngine<'engine> {
     }
 
     /// Handle a GatewayRequestToParent sent to us by one of our space gateway
-    fn handle_space_request(
+    pub(crate) fn handle_space_request(
         &mut self,
         span: Span,
         chain_id: &ChainId,
@@ -114,11 +113,21 @@ impl<'engine> GhostEngine<'engine> {
                             space_gateway,
                             from_peer_name,
                             gossip_data,
+                            true,
                         )
                         .expect("Failed to gossip with space_gateway");
                     }
-                    DhtRequestToParent::GossipUnreliablyTo(_data) => {
-                        // n/a - should have been handled by gateway
+                    DhtRequestToParent::GossipUnreliablyTo(gossip_data) => {
+                        let from_peer_name = &space_gateway.as_mut().as_mut().this_peer().peer_name;
+                        handle_GossipTo(
+                            span.child("handle_GossipUnreliablyTo"),
+                            chain_id.0.clone().into(),
+                            space_gateway,
+                            from_peer_name,
+                            gossip_data,
+                            false,
+                        )
+                        .expect("Failed to gossip with space_gateway");
                     }
                     // HoldPeerRequested from gossip
                     DhtRequestToParent::HoldPeerRequested(peer_data) => {
@@ -161,8 +170,18 @@ impl<'engine> GhostEngine<'engine> {
                             )?;
                         }
                     }
-                    DhtRequestToParent::EntryPruned(_address) => {
-                        // TODO #174
+                    // Our storage arc shrank past this entry:
+                    // -> Let Core drop it, nobody expects us to serve it anymore
+                    DhtRequestToParent::EntryPruned(entry_address) => {
+                        let lib3h_msg = DropEntryData {
+                            space_address: chain_id.0.clone(),
+                            request_id: self.request_track.reserve(),
+                            entry_address,
+                        };
+                        self.lib3h_endpoint.publish(
+                            span.child("send event Lib3hToClient::HandleDropEntry"),
+                            Lib3hToClient::HandleDropEntry(lib3h_msg),
+                        )?;
                     }
                     // EntryDataRequested: Change it into a Lib3hToClient::HandleFetchEntry.
                     DhtRequestToParent::RequestEntry(entry_address) => {
@@ -238,7 +257,7 @@ impl<'engine> GhostEngine<'engine> {
                     RequestToParent::Unbind(uri) => {
                         panic!("can't handle unbind {}", uri);
                     }
-                    RequestToParent::Disconnect(uri) => {
+                    RequestToParent::Disconnect(uri) | RequestToParent::Evicted(uri) => {
                         panic!("can't handle disconnect {}", uri);
                     }
                     RequestToParent::ErrorOccured { uri: _, error } => {
@@ -251,17 +270,15 @@ impl<'engine> GhostEngine<'engine> {
                         if payload.len() == 0 {
                             panic!("We should no longer ever be sending zero length messages");
                         } else {
-                            let mut de = Deserializer::new(&payload[..]);
-                            let maybe_msg: Result<P2pProtocol, rmp_serde::decode::Error> =
-                                Deserialize::deserialize(&mut de);
+                            let maybe_msg = P2pMessage::from_bytes(payload.to_vec());
                             if let Err(e) = maybe_msg {
                                 error!("Failed deserializing msg: {:?}", e);
                                 return Err(e.into());
                             }
                             let p2p_msg = maybe_msg.unwrap();
                             trace!("space_layer about to handle p2p_msg: {:?}", p2p_msg);
-                            self.handle_p2p_protocol(
-                                span.child("handle_p2p_protocol"),
+                            self.handle_p2p_message(
+                                span.child("handle_p2p_message"),
                                 &uri,
                                 p2p_msg,
                             )?;
@@ -269,19 +286,29 @@ impl<'engine> GhostEngine<'engine> {
                     }
                 }
             }
+            // Only network gateways reconnect
+            GatewayRequestToParent::PeerReconnected(peer_data)
+            | GatewayRequestToParent::PeerLost(peer_data) => {
+                panic!("can't handle reconnection of {:?}", peer_data);
+            }
+            // Only network gateways send payloads in chunks
+            GatewayRequestToParent::TransferProgress(progress_data) => {
+                panic!("can't handle transfer progress {:?}", progress_data);
+            }
         }
         Ok(true /* fixme */)
     }
 
-    /// process P2pProtocol messages that have bubbled up to the space_layer
-    fn handle_p2p_protocol(
+    /// process P2pMessages that have bubbled up to the space_layer
+    fn handle_p2p_message(
         &mut self,
         span: Span,
         _from: &Url,
-        p2p_msg: P2pProtocol,
+        p2p_msg: P2pMessage,
     ) -> Lib3hResult<()> {
         match p2p_msg {
-            P2pProtocol::DirectMessage(dm_data) => {
+            P2pMessage::MsgDirectRequest(direct) => {
+                let dm_data = direct_message_from_p2p(direct);
                 self.lib3h_endpoint.request(
                     span.child("request Lib3hToClient::HandleSendDirectMessage"),
                     Lib3hToClient::HandleSendDirectMessage(dm_data),
@@ -294,13 +321,13 @@ impl<'engine> GhostEngine<'engine> {
                                 dm_data.space_address.clone(),
                                 dm_data.from_agent_id.clone(),
                                 dm_data.to_agent_id.clone(),
-                                P2pProtocol::DirectMessageResult(dm_data.clone()),
+                                P2pMessage::MsgDirectResponse(direct_message_to_p2p(&dm_data)),
                             ) {
                                 Ok(r) => r,
                                 Err(e) => panic!("{:?}", e),
                             };
                             trace!(
-                                "handle_p2p_protocol: Got p2p_msg for {}: {:?}",
+                                "handle_p2p_message: Got p2p_msg for {}: {:?}",
                                 to_agent_id.clone(),
                                 dm_data
                             );
@@ -322,7 +349,8 @@ impl<'engine> GhostEngine<'engine> {
                     }),
                 )?;
             }
-            P2pProtocol::DirectMessageResult(dm_data) => {
+            P2pMessage::MsgDirectResponse(direct) => {
+                let dm_data = direct_message_from_p2p(direct);
                 trace!(
                     "pending_client_messages: {:?}",
                     self.pending_client_direct_messages
@@ -343,7 +371,8 @@ impl<'engine> GhostEngine<'engine> {
                     );
                 }
             }
-            P2pProtocol::Gossip(gossip_data) => {
+            P2pMessage::MsgGossip(gossip) => {
+                let gossip_data = gossip_from_p2p(gossip)?;
                 let remote_gossip = RemoteGossipBundleData {
                     from_peer_name: gossip_data.from_peer_name.clone(),
                     bundle: gossip_data.bundle.clone(),
@@ -3,8 +3,11 @@
 use crate::transport::error::TransportError;
 use lib3h_crypto_api::CryptoError;
 use lib3h_ghost_actor::{Backtwrap, GhostError};
-use lib3h_p2p_protocol::error::P2pError;
-use lib3h_protocol::error::{ErrorKind as Lib3hProtocolErrorKind, Lib3hProtocolError};
+use lib3h_p2p_protocol::{error::P2pError, multiplex::MultiplexErrorCode};
+use lib3h_protocol::{
+    error::{ErrorKind as Lib3hProtocolErrorKind, Lib3hProtocolError},
+    types::{AgentPubKey, SpaceHash},
+};
 use rmp_serde::decode::Error as RMPSerdeDecodeError;
 use std::{error::Error as StdError, fmt, io, result};
 
@@ -41,6 +44,18 @@ impl Lib3hError {
     pub fn into_kind(self) -> ErrorKind {
         *self.0
     }
+
+    /// The well-known code to report this error to a remote node with,
+    /// on a multiplex channel.
+    pub fn multiplex_error_code(&self) -> MultiplexErrorCode {
+        match *self.0 {
+            ErrorKind::BadChannelId(_) => MultiplexErrorCode::BadChannelId,
+            ErrorKind::BadSpaceHash(_) => MultiplexErrorCode::BadSpaceHash,
+            ErrorKind::BadToId(_) => MultiplexErrorCode::BadToId,
+            ErrorKind::BadFromId(_) => MultiplexErrorCode::BadFromId,
+            _ => MultiplexErrorCode::Unknown,
+        }
+    }
 }
 
 /// The specific type of an error.
@@ -62,6 +77,14 @@ pub enum ErrorKind {
     CryptoApiError(CryptoError),
     /// Error occuring when the key is not present in the Map.
     KeyNotFound(String),
+    /// A multiplex channel was used without being created first.
+    BadChannelId(u32),
+    /// This node is not part of the space.
+    BadSpaceHash(SpaceHash),
+    /// This node has no agent with this id in the space.
+    BadToId(AgentPubKey),
+    /// This node does not accept messages from this agent.
+    BadFromId(AgentPubKey),
     /// Yet undefined error.
     Other(String),
     /// Hints that destructuring should not be exhaustive.
@@ -84,6 +107,10 @@ impl Clone for ErrorKind {
             ErrorKind::RmpSerdeDecodeError(e) => ErrorKind::Other(format!("{:?}", e)),
             ErrorKind::CryptoApiError(e) => ErrorKind::CryptoApiError(e.clone()),
             ErrorKind::KeyNotFound(e) => ErrorKind::KeyNotFound(e.clone()),
+            ErrorKind::BadChannelId(id) => ErrorKind::BadChannelId(*id),
+            ErrorKind::BadSpaceHash(h) => ErrorKind::BadSpaceHash(h.clone()),
+            ErrorKind::BadToId(id) => ErrorKind::BadToId(id.clone()),
+            ErrorKind::BadFromId(id) => ErrorKind::BadFromId(id.clone()),
             ErrorKind::Other(e) => ErrorKind::Other(e.clone()),
             _ => ErrorKind::Other(format!("error cloning error: {:?}", self)),
         }
@@ -102,6 +129,10 @@ impl StdError for Lib3hError {
             ErrorKind::RmpSerdeDecodeError(ref err) => Some(err),
             ErrorKind::CryptoApiError(ref err) => Some(err),
             ErrorKind::Other(ref _s) | ErrorKind::KeyNotFound(ref _s) => None,
+            ErrorKind::BadChannelId(_)
+            | ErrorKind::BadSpaceHash(_)
+            | ErrorKind::BadToId(_)
+            | ErrorKind::BadFromId(_) => None,
             _ => unreachable!(),
         }
     }
@@ -118,6 +149,10 @@ impl fmt::Display for Lib3hError {
             ErrorKind::RmpSerdeDecodeError(ref err) => err.fmt(f),
             ErrorKind::CryptoApiError(ref err) => err.fmt(f),
             ErrorKind::KeyNotFound(ref s) => write!(f, "Key: '{}' not found", s),
+            ErrorKind::BadChannelId(id) => write!(f, "No multiplex channel with id {}", id),
+            ErrorKind::BadSpaceHash(ref h) => write!(f, "Not part of space '{}'", h),
+            ErrorKind::BadToId(ref id) => write!(f, "No agent '{}' here", id),
+            ErrorKind::BadFromId(ref id) => write!(f, "Not accepting messages from '{}'", id),
             ErrorKind::Other(ref s) => write!(f, "Unknown error encountered: '{}'.", s),
             _ => unreachable!(),
         }
@@ -1,11 +1,12 @@
 use crate::{
     dht::dht_protocol::*,
-    engine::p2p_protocol::P2pProtocol,
     error::*,
-    gateway::{protocol::*, send_data_types::*, P2pGateway},
+    gateway::{gateway_multiplex::ChannelSpec, protocol::*, send_data_types::*, P2pGateway},
+    message_encoding::encoding_protocol,
 };
 use lib3h_ghost_actor::prelude::*;
 use lib3h_p2p_protocol::p2p::P2pMessage;
+use std::collections::HashMap;
 
 impl
     GhostActor<
@@ -37,6 +38,11 @@ impl
 
         self.process_transport_pending_sends()?;
 
+        self.process_ping_loop()?;
+        self.process_reconnect_loop()?;
+        self.process_relay_request()?;
+        self.process_transfer_loop()?;
+
         // Update this_peer cache
         self.inner_dht.request(
             // new_root_span("Update this_peer cache"),
@@ -94,15 +100,13 @@ impl P2pGateway {
                 self.handle_dht_RequestToChild(span, dht_request, msg)
             }
             GatewayRequestToChild::Bootstrap(data) => {
-                let payload =
-                    P2pProtocol::CapnProtoMessage(P2pMessage::create_ping(None).into_bytes())
-                        .into_bytes()
-                        .into();
+                let payload = P2pMessage::create_ping(None).into_bytes().into();
                 self.send_with_full_low_uri(
                     SendWithFullLowUri {
                         span,
                         full_low_uri: data.bootstrap_uri,
                         payload,
+                        reliable: true,
                     },
                     Box::new(move |response| {
                         if response.is_ok() {
@@ -116,6 +120,62 @@ impl P2pGateway {
                 )?;
                 Ok(())
             }
+            GatewayRequestToChild::QueryPeerLatency => {
+                self.message_encoding.request(
+                    span.child("request compression stats"),
+                    encoding_protocol::RequestToChild::QueryCompressionStats,
+                    Box::new(move |me, response| {
+                        let mut compression_stats = match response {
+                            GhostCallbackData::Response(Ok(
+                                encoding_protocol::RequestToChildResponse::CompressionStats(
+                                    compression_stats,
+                                ),
+                            )) => compression_stats,
+                            _ => {
+                                warn!("failed to query compression stats: {:?}", response);
+                                HashMap::new()
+                            }
+                        };
+                        let mut peer_latency_list = me.peer_latency_list();
+                        for peer_latency in peer_latency_list.iter_mut() {
+                            peer_latency.compression =
+                                compression_stats.remove(&peer_latency.peer_location);
+                        }
+                        msg.respond(Ok(GatewayRequestToChildResponse::QueryPeerLatency(
+                            peer_latency_list,
+                        )))?;
+                        Ok(())
+                    }),
+                )?;
+                Ok(())
+            }
+            GatewayRequestToChild::RejectChannel {
+                uri,
+                space_address,
+                to_agent_id,
+                from_agent_id,
+                error,
+            } => {
+                let spec = ChannelSpec {
+                    space_address,
+                    to_agent_id,
+                    from_agent_id,
+                };
+                self.reject_channel(span, uri, spec, error)?;
+                msg.respond(Ok(GatewayRequestToChildResponse::RejectChannelSuccess))?;
+                Ok(())
+            }
+            GatewayRequestToChild::CancelTransfer {
+                uri,
+                transfer_id,
+                outgoing,
+            } => {
+                let result = self
+                    .cancel_transfer(span, uri, transfer_id, outgoing)
+                    .map(|_| GatewayRequestToChildResponse::CancelTransferSuccess);
+                msg.respond(result)?;
+                Ok(())
+            }
             GatewayRequestToChild::SendAll(payload) => {
                 trace!("send all: {:?}", String::from_utf8_lossy(&payload));
                 self.inner_dht.request(
@@ -139,6 +199,7 @@ impl P2pGateway {
                                             span: span.child("send_with_full_low_uri"),
                                             full_low_uri: uri,
                                             payload: payload.clone().into(),
+                                            reliable: true,
                                         },
                                         Box::new(move |response| {
                                             trace!(
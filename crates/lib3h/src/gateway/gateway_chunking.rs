@@ -0,0 +1,624 @@
+//! Chunked transfers of large payloads between network gateways.
+//!
+//! Toward nodes speaking `P2P_PROTOCOL_VERSION_CHUNKING`, a network gateway
+//! configured with a `ChunkingConfig` sends payloads above the threshold as
+//! `MsgTransferChunk`s, a few per process call, and the remote node handles
+//! the payload once it reassembled it. Both ends report the progress of
+//! every transfer to their parent, and either may cancel it with
+//! `MsgTransferCancel`. Transfers live as long as the connection they run on.
+
+use crate::{
+    engine::{p2p_protocol::multiplex_to_p2p, ChunkingConfig},
+    error::{Lib3hError, Lib3hResult},
+    gateway::{protocol::*, send_data_types::*, P2pGateway},
+    new_root_span,
+};
+use holochain_tracing::Span;
+use lib3h_ghost_actor::prelude::*;
+use lib3h_p2p_protocol::{
+    multiplex::{MsgTransferCancel, MsgTransferChunk, MultiplexMessage},
+    p2p::P2P_PROTOCOL_VERSION_CHUNKING,
+};
+use lib3h_protocol::{
+    data_types::{Opaque, TransferProgressData},
+    uri::Lib3hUri,
+};
+use std::collections::{BTreeMap, HashMap};
+
+/// chunks of each outgoing transfer sent per process call
+const CHUNKS_PER_PROCESS: usize = 4;
+
+/// we refuse to reassemble a payload any larger than this
+const MAX_TRANSFER_BYTES: u64 = 64 * 1024 * 1024;
+
+/// transfers we reassemble at once from a single node
+const MAX_INCOMING_TRANSFERS: usize = 16;
+
+/// an incoming transfer is dropped if no chunk came for this long
+const INCOMING_TRANSFER_TIMEOUT_MS: u64 = 60000;
+
+impl ChunkingConfig {
+    /// Chunks must stay well below the threshold, or they would be chunked too
+    fn effective_chunk_bytes(&self) -> usize {
+        self.chunk_bytes.min(self.threshold_bytes / 2).max(1)
+    }
+}
+
+/// A payload we are sending in chunks
+pub(crate) struct OutgoingTransfer {
+    payload: Opaque,
+    bytes_sent: usize,
+    /// answered once the last chunk is sent
+    cb: SendCallback,
+}
+
+impl OutgoingTransfer {
+    fn new(payload: Opaque, cb: SendCallback) -> Self {
+        OutgoingTransfer {
+            payload,
+            bytes_sent: 0,
+            cb,
+        }
+    }
+
+    /// The next chunk of at most `chunk_bytes`, None once all were sent
+    pub fn next_chunk(&mut self, transfer_id: u32, chunk_bytes: usize) -> Option<MsgTransferChunk> {
+        if self.bytes_sent >= self.payload.len() {
+            return None;
+        }
+        let end = (self.bytes_sent + chunk_bytes).min(self.payload.len());
+        let chunk = MsgTransferChunk {
+            transfer_id,
+            offset: self.bytes_sent as u64,
+            total_size: self.payload.len() as u64,
+            content: self.payload[self.bytes_sent..end].to_vec(),
+        };
+        self.bytes_sent = end;
+        Some(chunk)
+    }
+}
+
+/// A payload we are receiving in chunks
+#[derive(Debug)]
+pub(crate) struct IncomingTransfer {
+    total_size: u64,
+    bytes_received: u64,
+    /// chunk contents keyed by offset
+    chunks: BTreeMap<u64, Vec<u8>>,
+    last_chunk_ms: u64,
+}
+
+impl IncomingTransfer {
+    fn new(total_size: u64, now_ms: u64) -> Lib3hResult<Self> {
+        if total_size > MAX_TRANSFER_BYTES {
+            return Err(format!("transfer of {} bytes exceeds the limit", total_size).into());
+        }
+        Ok(IncomingTransfer {
+            total_size,
+            bytes_received: 0,
+            chunks: BTreeMap::new(),
+            last_chunk_ms: now_ms,
+        })
+    }
+
+    /// Keep a chunk, returning the whole payload once we have all of them
+    fn receive(&mut self, chunk: MsgTransferChunk, now_ms: u64) -> Lib3hResult<Option<Vec<u8>>> {
+        let size = chunk.content.len() as u64;
+        let in_bounds = chunk
+            .offset
+            .checked_add(size)
+            .map(|end| end <= self.total_size)
+            .unwrap_or(false);
+        if chunk.total_size != self.total_size
+            || size == 0
+            || !in_bounds
+            || self.chunks.contains_key(&chunk.offset)
+        {
+            return Err(format!(
+                "bad chunk at {} of transfer {}",
+                chunk.offset, chunk.transfer_id
+            )
+            .into());
+        }
+        self.bytes_received += size;
+        self.chunks.insert(chunk.offset, chunk.content);
+        self.last_chunk_ms = now_ms;
+        if self.bytes_received < self.total_size {
+            return Ok(None);
+        }
+        // chunks are in bounds and add up to the total, they only
+        // fail to line up if some of them overlap
+        let mut payload = Vec::with_capacity(self.total_size as usize);
+        for (offset, content) in std::mem::replace(&mut self.chunks, BTreeMap::new()) {
+            if offset != payload.len() as u64 {
+                return Err(format!("overlapping chunks at {}", offset).into());
+            }
+            payload.extend(content);
+        }
+        Ok(Some(payload))
+    }
+}
+
+/// The transfers running on one connection, in both directions
+#[derive(Default)]
+pub(crate) struct Transfers {
+    next_transfer_id: u32,
+    outgoing: HashMap<u32, OutgoingTransfer>,
+    incoming: HashMap<u32, IncomingTransfer>,
+}
+
+impl Transfers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start sending `payload`, returning the id of the transfer
+    pub fn start_outgoing(&mut self, payload: Opaque, cb: SendCallback) -> u32 {
+        let transfer_id = self.next_transfer_id;
+        self.next_transfer_id = self.next_transfer_id.wrapping_add(1);
+        self.outgoing
+            .insert(transfer_id, OutgoingTransfer::new(payload, cb));
+        transfer_id
+    }
+
+    /// Keep a chunk the remote node sent, returning how much of its transfer
+    /// we received and the whole payload once we have it. The transfer is
+    /// forgotten once complete, or if the chunk does not fit in it.
+    pub fn receive_chunk(
+        &mut self,
+        chunk: MsgTransferChunk,
+        now_ms: u64,
+    ) -> Lib3hResult<(u64, Option<Vec<u8>>)> {
+        let transfer_id = chunk.transfer_id;
+        if !self.incoming.contains_key(&transfer_id) {
+            if self.incoming.len() >= MAX_INCOMING_TRANSFERS {
+                return Err(Lib3hError::from(format!(
+                    "too many transfers, refusing transfer {}",
+                    transfer_id
+                )));
+            }
+            let incoming = IncomingTransfer::new(chunk.total_size, now_ms)?;
+            self.incoming.insert(transfer_id, incoming);
+        }
+        let incoming = self.incoming.get_mut(&transfer_id).expect("exists");
+        match incoming.receive(chunk, now_ms) {
+            Ok(None) => Ok((incoming.bytes_received, None)),
+            Ok(Some(payload)) => {
+                self.incoming.remove(&transfer_id);
+                Ok((payload.len() as u64, Some(payload)))
+            }
+            Err(e) => {
+                self.incoming.remove(&transfer_id);
+                Err(e)
+            }
+        }
+    }
+}
+
+fn to_progress_data(
+    low_uri: &Lib3hUri,
+    transfer_id: u32,
+    outgoing: bool,
+    bytes_done: u64,
+    total_bytes: u64,
+    canceled: bool,
+) -> TransferProgressData {
+    TransferProgressData {
+        peer_location: low_uri.clone(),
+        transfer_id,
+        outgoing,
+        bytes_done,
+        total_bytes,
+        canceled,
+    }
+}
+
+impl P2pGateway {
+    fn transfers(&mut self, low_uri: &Lib3hUri) -> &mut Transfers {
+        self.transfers
+            .entry(low_uri.clone())
+            .or_insert_with(Transfers::new)
+    }
+
+    /// True if `payload` is to be sent to the node at `low_uri` in chunks
+    pub(crate) fn should_chunk(&self, low_uri: &Lib3hUri, payload: &Opaque) -> bool {
+        match &self.chunking {
+            Some(chunking) => {
+                payload.len() > chunking.threshold_bytes
+                    && self.peer_protocol_version(low_uri) >= P2P_PROTOCOL_VERSION_CHUNKING
+            }
+            None => false,
+        }
+    }
+
+    /// Send `payload` to the node at `low_uri` in chunks over the next
+    /// process calls, `cb` is answered once the last one is sent
+    pub(crate) fn start_outgoing_transfer(
+        &mut self,
+        low_uri: Lib3hUri,
+        payload: Opaque,
+        cb: SendCallback,
+    ) {
+        let size = payload.len();
+        let transfer_id = self.transfers(&low_uri).start_outgoing(payload, cb);
+        debug!(
+            "({}) send {} bytes to {} in chunks, transfer {}",
+            self.identifier.nickname, size, low_uri, transfer_id
+        );
+    }
+
+    /// Send the next chunks of our outgoing transfers,
+    /// and drop the incoming ones that stalled
+    pub(crate) fn process_transfer_loop(&mut self) -> GhostResult<()> {
+        let chunk_bytes = self
+            .chunking
+            .as_ref()
+            .map(ChunkingConfig::effective_chunk_bytes)
+            .unwrap_or(1);
+        let now = crate::time::since_epoch_ms();
+        let mut chunk_list = Vec::new();
+        let mut progress_list = Vec::new();
+        for (uri, transfers) in self.transfers.iter_mut() {
+            for (transfer_id, outgoing) in transfers.outgoing.iter_mut() {
+                for _ in 0..CHUNKS_PER_PROCESS {
+                    match outgoing.next_chunk(*transfer_id, chunk_bytes) {
+                        Some(chunk) => chunk_list.push((uri.clone(), chunk)),
+                        None => break,
+                    }
+                }
+                progress_list.push(to_progress_data(
+                    uri,
+                    *transfer_id,
+                    true,
+                    outgoing.bytes_sent as u64,
+                    outgoing.payload.len() as u64,
+                    false,
+                ));
+            }
+            transfers.incoming.retain(|transfer_id, incoming| {
+                if now.saturating_sub(incoming.last_chunk_ms) < INCOMING_TRANSFER_TIMEOUT_MS {
+                    return true;
+                }
+                warn!("transfer {} from {} stalled, dropping it", transfer_id, uri);
+                progress_list.push(to_progress_data(
+                    uri,
+                    *transfer_id,
+                    false,
+                    incoming.bytes_received,
+                    incoming.total_size,
+                    true,
+                ));
+                false
+            });
+        }
+        for (uri, chunk) in chunk_list {
+            let transfer_id = chunk.transfer_id;
+            let is_last = chunk.offset + chunk.content.len() as u64 == chunk.total_size;
+            let cb: SendCallback = if is_last {
+                let outgoing = self
+                    .transfers(&uri)
+                    .outgoing
+                    .remove(&transfer_id)
+                    .expect("exists");
+                outgoing.cb
+            } else {
+                Box::new(move |response| {
+                    trace!("sent chunk of transfer {}: {:?}", transfer_id, response);
+                    Ok(())
+                })
+            };
+            self.send_transfer_message(
+                new_root_span("transfer loop"),
+                uri,
+                MultiplexMessage::MsgTransferChunk(chunk),
+                cb,
+            )?;
+        }
+        for progress_data in progress_list {
+            self.publish_transfer_progress(new_root_span("transfer loop"), progress_data)?;
+        }
+        Ok(())
+    }
+
+    /// Keep a chunk sent by the node at `low_uri`,
+    /// and handle the payload once we have all of them
+    pub(crate) fn handle_transfer_chunk(
+        &mut self,
+        span: Span,
+        low_uri: Lib3hUri,
+        chunk: MsgTransferChunk,
+    ) -> GhostResult<()> {
+        let transfer_id = chunk.transfer_id;
+        let total_size = chunk.total_size;
+        let now = crate::time::since_epoch_ms();
+        match self.transfers(&low_uri).receive_chunk(chunk, now) {
+            Ok((bytes_received, maybe_payload)) => {
+                self.publish_transfer_progress(
+                    span.follower("transfer progress"),
+                    to_progress_data(
+                        &low_uri,
+                        transfer_id,
+                        false,
+                        bytes_received,
+                        total_size,
+                        false,
+                    ),
+                )?;
+                if let Some(payload) = maybe_payload {
+                    trace!(
+                        "({}) received transfer {} from {}",
+                        self.identifier.nickname,
+                        transfer_id,
+                        low_uri
+                    );
+                    self.priv_on_receive(span, low_uri, payload.into())?;
+                }
+            }
+            Err(e) => {
+                warn!(
+                    "({}) refusing transfer {} from {}: {}",
+                    self.identifier.nickname, transfer_id, low_uri, e
+                );
+                self.send_transfer_message(
+                    span.follower("cancel transfer"),
+                    low_uri.clone(),
+                    MultiplexMessage::MsgTransferCancel(MsgTransferCancel {
+                        transfer_id,
+                        from_sender: false,
+                    }),
+                    Box::new(|response| {
+                        trace!("sent transfer cancel {:?}", response);
+                        Ok(())
+                    }),
+                )?;
+                self.publish_transfer_progress(
+                    span,
+                    to_progress_data(&low_uri, transfer_id, false, 0, total_size, true),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The node at `low_uri` canceled one of our transfers
+    pub(crate) fn handle_transfer_cancel(
+        &mut self,
+        span: Span,
+        low_uri: Lib3hUri,
+        cancel: MsgTransferCancel,
+    ) -> GhostResult<()> {
+        // if the sender canceled, it is one we receive
+        let outgoing = !cancel.from_sender;
+        if !self.drop_transfer(span, &low_uri, cancel.transfer_id, outgoing)? {
+            debug!(
+                "({}) {} canceled unknown transfer {}",
+                self.identifier.nickname, low_uri, cancel.transfer_id
+            );
+        }
+        Ok(())
+    }
+
+    /// Our parent abandons a transfer with the node at `low_uri`
+    pub(crate) fn cancel_transfer(
+        &mut self,
+        span: Span,
+        low_uri: Lib3hUri,
+        transfer_id: u32,
+        outgoing: bool,
+    ) -> Lib3hResult<()> {
+        if !self.drop_transfer(span.child("drop transfer"), &low_uri, transfer_id, outgoing)? {
+            return Err(format!("no transfer {} with {}", transfer_id, low_uri).into());
+        }
+        self.send_transfer_message(
+            span,
+            low_uri,
+            MultiplexMessage::MsgTransferCancel(MsgTransferCancel {
+                transfer_id,
+                from_sender: outgoing,
+            }),
+            Box::new(|response| {
+                trace!("sent transfer cancel {:?}", response);
+                Ok(())
+            }),
+        )?;
+        Ok(())
+    }
+
+    /// The connection to `low_uri` is gone, and every transfer on it
+    pub(crate) fn transfers_disconnected(
+        &mut self,
+        span: Span,
+        low_uri: &Lib3hUri,
+    ) -> GhostResult<()> {
+        let transfers = match self.transfers.remove(low_uri) {
+            Some(transfers) => transfers,
+            None => return Ok(()),
+        };
+        for (transfer_id, outgoing) in transfers.outgoing {
+            self.publish_transfer_progress(
+                span.child("transfer progress"),
+                to_progress_data(
+                    low_uri,
+                    transfer_id,
+                    true,
+                    outgoing.bytes_sent as u64,
+                    outgoing.payload.len() as u64,
+                    true,
+                ),
+            )?;
+            (outgoing.cb)(Err("connection lost during transfer".into()))?;
+        }
+        for (transfer_id, incoming) in transfers.incoming {
+            self.publish_transfer_progress(
+                span.child("transfer progress"),
+                to_progress_data(
+                    low_uri,
+                    transfer_id,
+                    false,
+                    incoming.bytes_received,
+                    incoming.total_size,
+                    true,
+                ),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Forget a transfer, failing its send if it is ours.
+    /// Returns false if there was no such transfer.
+    fn drop_transfer(
+        &mut self,
+        span: Span,
+        low_uri: &Lib3hUri,
+        transfer_id: u32,
+        outgoing: bool,
+    ) -> GhostResult<bool> {
+        let transfers = self.transfers(low_uri);
+        let progress_data = if outgoing {
+            let outgoing = match transfers.outgoing.remove(&transfer_id) {
+                Some(outgoing) => outgoing,
+                None => return Ok(false),
+            };
+            (outgoing.cb)(Err("transfer canceled".into()))?;
+            to_progress_data(
+                low_uri,
+                transfer_id,
+                true,
+                outgoing.bytes_sent as u64,
+                outgoing.payload.len() as u64,
+                true,
+            )
+        } else {
+            let incoming = match transfers.incoming.remove(&transfer_id) {
+                Some(incoming) => incoming,
+                None => return Ok(false),
+            };
+            to_progress_data(
+                low_uri,
+                transfer_id,
+                false,
+                incoming.bytes_received,
+                incoming.total_size,
+                true,
+            )
+        };
+        debug!(
+            "({}) dropped transfer {} with {}",
+            self.identifier.nickname, transfer_id, low_uri
+        );
+        self.publish_transfer_progress(span, progress_data)?;
+        Ok(true)
+    }
+
+    fn publish_transfer_progress(
+        &mut self,
+        span: Span,
+        progress_data: TransferProgressData,
+    ) -> GhostResult<()> {
+        self.endpoint_self.publish(
+            span,
+            GatewayRequestToParent::TransferProgress(progress_data),
+        )
+    }
+
+    fn send_transfer_message(
+        &mut self,
+        span: Span,
+        low_uri: Lib3hUri,
+        msg: MultiplexMessage,
+        cb: SendCallback,
+    ) -> GhostResult<()> {
+        let payload = multiplex_to_p2p(&msg).into_bytes().into();
+        self.send_with_full_low_uri(
+            SendWithFullLowUri {
+                span,
+                full_low_uri: low_uri,
+                payload,
+                reliable: true,
+            },
+            cb,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_cb() -> SendCallback {
+        Box::new(|_| Ok(()))
+    }
+
+    fn chunks_of(payload: &[u8], chunk_bytes: usize) -> Vec<MsgTransferChunk> {
+        let mut transfers = Transfers::new();
+        let transfer_id = transfers.start_outgoing(payload.into(), no_cb());
+        let outgoing = transfers.outgoing.get_mut(&transfer_id).unwrap();
+        let mut chunks = Vec::new();
+        while let Some(chunk) = outgoing.next_chunk(transfer_id, chunk_bytes) {
+            chunks.push(chunk);
+        }
+        chunks
+    }
+
+    #[test]
+    fn it_should_split_and_reassemble() {
+        let payload: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let mut chunks = chunks_of(&payload, 300);
+        assert_eq!(4, chunks.len());
+        assert_eq!(100, chunks[3].content.len());
+
+        // they may come out of order
+        chunks.swap(0, 2);
+        let mut receiver = Transfers::new();
+        let last = chunks.pop().unwrap();
+        for chunk in chunks {
+            let (_, maybe_payload) = receiver.receive_chunk(chunk, 0).unwrap();
+            assert!(maybe_payload.is_none());
+        }
+        assert_eq!(
+            (1000, Some(payload)),
+            receiver.receive_chunk(last, 0).unwrap()
+        );
+        assert!(receiver.incoming.is_empty());
+    }
+
+    #[test]
+    fn it_should_refuse_bad_chunks() {
+        let mut chunks = chunks_of(&[42; 100], 40);
+        let mut receiver = Transfers::new();
+        receiver.receive_chunk(chunks[0].clone(), 0).unwrap();
+        // sent twice
+        assert!(receiver.receive_chunk(chunks[0].clone(), 0).is_err());
+        assert!(receiver.incoming.is_empty());
+
+        // past the end
+        chunks[2].offset = 90;
+        assert!(receiver.receive_chunk(chunks[2].clone(), 0).is_err());
+
+        let mut too_large = chunks[0].clone();
+        too_large.total_size = MAX_TRANSFER_BYTES + 1;
+        assert!(receiver.receive_chunk(too_large, 0).is_err());
+    }
+
+    #[test]
+    fn it_should_limit_incoming_transfers() {
+        let mut receiver = Transfers::new();
+        let mut chunk = chunks_of(&[42; 100], 40).remove(0);
+        for transfer_id in 0..MAX_INCOMING_TRANSFERS as u32 {
+            chunk.transfer_id = transfer_id;
+            receiver.receive_chunk(chunk.clone(), 0).unwrap();
+        }
+        chunk.transfer_id = MAX_INCOMING_TRANSFERS as u32;
+        assert!(receiver.receive_chunk(chunk, 0).is_err());
+    }
+
+    #[test]
+    fn it_should_keep_chunks_below_the_threshold() {
+        let config = ChunkingConfig {
+            threshold_bytes: 1000,
+            chunk_bytes: 4000,
+        };
+        assert_eq!(500, config.effective_chunk_bytes());
+        assert_eq!(64 * 1024, ChunkingConfig::default().effective_chunk_bytes());
+    }
+}
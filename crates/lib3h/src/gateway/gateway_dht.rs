@@ -2,7 +2,6 @@
 
 use crate::{
     dht::dht_protocol::*,
-    engine::p2p_protocol::P2pProtocol,
     error::*,
     gateway::{protocol::*, send_data_types::*, P2pGateway},
 };
@@ -76,16 +75,15 @@ impl P2pGateway {
                     self.identifier.nickname, peer_data.peer_name, peer_data.peer_location,
                 );
                 // Send Ping so we connect to it
-                let payload =
-                    P2pProtocol::CapnProtoMessage(P2pMessage::create_ping(None).into_bytes())
-                        .into_bytes()
-                        .into();
+                let payload = P2pMessage::create_ping(None).into_bytes().into();
                 let uri = peer_data.get_uri();
+                self.track_peer_liveness(&uri, Some(peer_data.peer_name.clone()));
                 self.send_with_full_low_uri(
                     SendWithFullLowUri {
                         span: span.follower("DhtRequestToParent::HoldPeerRequested"),
                         full_low_uri: uri,
                         payload,
+                        reliable: true,
                     },
                     Box::new(|_| Ok(())),
                 )?;
@@ -101,7 +99,7 @@ impl P2pGateway {
                 // no-op
             }
             DhtRequestToParent::EntryPruned(_) => {
-                unreachable!();
+                // no-op: our storage arc shrank, forwarded below so the owner drops it
             }
             DhtRequestToParent::RequestEntry(_) => {
                 let span_request = span.child("request GatewayRequestToParent::Dht::RequestEntry");
@@ -110,7 +108,7 @@ impl P2pGateway {
                 self.endpoint_self.request(
                     span_request,
                     GatewayRequestToParent::Dht(payload),
-                    Box::new(|me, response| {
+                    Box::new(move |me, response| {
                         trace!("Received requestEntry response in Gateway");
                         let dht_response = match response {
                             GhostCallbackData::Response(Ok(
@@ -118,6 +116,11 @@ impl P2pGateway {
                             )) => d,
                             _ => panic!("invalid response type: {:?}", response),
                         };
+                        // DHT is fetching aspect data for a gossip sequence
+                        if request.is_request() {
+                            request.respond(Ok(dht_response))?;
+                            return Ok(());
+                        }
                         // #fullsync - received entry response after request from gossip list handling,
                         // treat it as an entry from author list handling.
                         if let DhtRequestToParentResponse::RequestEntry(entry) = dht_response {
@@ -0,0 +1,438 @@
+//! Multiplex channels between network gateways.
+//!
+//! Space gateways address every message with a space and a pair of agent
+//! ids. Toward nodes speaking `P2P_PROTOCOL_VERSION_MULTIPLEX`, the network
+//! gateway instead creates a channel per (space, to agent, from agent) once,
+//! with `MsgChannelCreate`, after which messages only carry the channel id.
+//! Channels live as long as the connection they were created on, and a
+//! node may only open `MAX_INCOMING_CHANNELS` of them toward us at once.
+
+use crate::{
+    engine::p2p_protocol::*,
+    error::{ErrorKind, Lib3hError, Lib3hResult},
+    gateway::{protocol::*, send_data_types::*, P2pGateway},
+    transport,
+};
+use holochain_tracing::Span;
+use lib3h_ghost_actor::prelude::*;
+use lib3h_p2p_protocol::{
+    multiplex::*,
+    p2p::{MsgDirect, P2pMessage},
+};
+use lib3h_protocol::{
+    data_types::{DirectMessageData, Opaque},
+    types::*,
+    uri::Lib3hUri,
+};
+use std::collections::HashMap;
+
+/// how many channels a remote node may have open toward us on one connection,
+/// further channels are refused with a `BadChannelId` error
+const MAX_INCOMING_CHANNELS: usize = 1024;
+
+/// What a multiplex channel stands for
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct ChannelSpec {
+    pub space_address: SpaceHash,
+    pub to_agent_id: AgentPubKey,
+    pub from_agent_id: AgentPubKey,
+}
+
+impl ChannelSpec {
+    fn from_direct(direct: &MsgDirect) -> Self {
+        ChannelSpec {
+            space_address: direct.space_address.clone().into(),
+            to_agent_id: direct.to_agent_id.clone().into(),
+            from_agent_id: direct.from_agent_id.clone().into(),
+        }
+    }
+
+    fn from_create(create: &MsgChannelCreate) -> Lib3hResult<Self> {
+        let parse = |id: &[u8]| {
+            String::from_utf8(id.to_vec())
+                .map_err(|e| Lib3hError::from(format!("bad channel id: {:?}", e)))
+        };
+        Ok(ChannelSpec {
+            space_address: parse(&create.space_hash)?.into(),
+            to_agent_id: parse(&create.to_id)?.into(),
+            from_agent_id: parse(&create.from_id)?.into(),
+        })
+    }
+
+    fn to_create(&self, channel_id: u32) -> MsgChannelCreate {
+        MsgChannelCreate {
+            channel_id,
+            space_hash: self.space_address.to_string().into_bytes(),
+            to_id: self.to_agent_id.to_string().into_bytes(),
+            from_id: self.from_agent_id.to_string().into_bytes(),
+        }
+    }
+}
+
+/// The channels established on one connection, in both directions
+#[derive(Debug, Default)]
+pub(crate) struct MultiplexChannels {
+    next_channel_id: u32,
+    outgoing: HashMap<ChannelSpec, u32>,
+    incoming: HashMap<u32, ChannelSpec>,
+}
+
+impl MultiplexChannels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The channel to send messages for `spec` on,
+    /// and whether the remote node has to be told about it first
+    pub fn open_outgoing(&mut self, spec: &ChannelSpec) -> (u32, bool) {
+        if let Some(channel_id) = self.outgoing.get(spec) {
+            return (*channel_id, false);
+        }
+        let channel_id = self.next_channel_id;
+        self.next_channel_id = self.next_channel_id.wrapping_add(1);
+        if self.next_channel_id == NO_CHANNEL_ID {
+            self.next_channel_id = 0;
+        }
+        self.outgoing.insert(spec.clone(), channel_id);
+        (channel_id, true)
+    }
+
+    /// Forget an outgoing channel the remote node dropped
+    pub fn close_outgoing(&mut self, channel_id: u32) -> Option<ChannelSpec> {
+        let spec = self
+            .outgoing
+            .iter()
+            .find(|(_, id)| **id == channel_id)
+            .map(|(spec, _)| spec.clone())?;
+        self.outgoing.remove(&spec);
+        Some(spec)
+    }
+
+    pub fn create_incoming(&mut self, create: &MsgChannelCreate) -> Lib3hResult<()> {
+        if create.channel_id == NO_CHANNEL_ID
+            || (!self.incoming.contains_key(&create.channel_id)
+                && self.incoming.len() >= MAX_INCOMING_CHANNELS)
+        {
+            return Err(Lib3hError::new(ErrorKind::BadChannelId(create.channel_id)));
+        }
+        let spec = ChannelSpec::from_create(create)?;
+        self.incoming.insert(create.channel_id, spec);
+        Ok(())
+    }
+
+    pub fn incoming(&self, channel_id: u32) -> Lib3hResult<ChannelSpec> {
+        self.incoming
+            .get(&channel_id)
+            .cloned()
+            .ok_or_else(|| Lib3hError::new(ErrorKind::BadChannelId(channel_id)))
+    }
+
+    pub fn close_incoming(&mut self, channel_id: u32) -> Option<ChannelSpec> {
+        self.incoming.remove(&channel_id)
+    }
+
+    /// Close the incoming channel standing for `spec`, returning its id
+    pub fn reject_incoming(&mut self, spec: &ChannelSpec) -> Option<u32> {
+        let channel_id = self
+            .incoming
+            .iter()
+            .find(|(_, incoming_spec)| *incoming_spec == spec)
+            .map(|(channel_id, _)| *channel_id)?;
+        self.incoming.remove(&channel_id);
+        Some(channel_id)
+    }
+}
+
+/// Convert an error reported by a remote node on one of our channels
+fn error_from_multiplex(error: &MsgMultiplexError, maybe_spec: Option<&ChannelSpec>) -> Lib3hError {
+    Lib3hError::new(match (error.error_code, maybe_spec) {
+        (MultiplexErrorCode::BadChannelId, _) => ErrorKind::BadChannelId(error.channel_id),
+        (MultiplexErrorCode::BadSpaceHash, Some(spec)) => {
+            ErrorKind::BadSpaceHash(spec.space_address.clone())
+        }
+        (MultiplexErrorCode::BadToId, Some(spec)) => ErrorKind::BadToId(spec.to_agent_id.clone()),
+        (MultiplexErrorCode::BadFromId, Some(spec)) => {
+            ErrorKind::BadFromId(spec.from_agent_id.clone())
+        }
+        _ => ErrorKind::Other(error.error_text.clone()),
+    })
+}
+
+impl P2pGateway {
+    fn multiplex_channels(&mut self, low_uri: &Lib3hUri) -> &mut MultiplexChannels {
+        self.multiplex_channels
+            .entry(low_uri.clone())
+            .or_insert_with(MultiplexChannels::new)
+    }
+
+    /// Replace the addressing of a space gateway message to `low_uri` with
+    /// a channel id, creating the channel first if needed.
+    /// Any other payload is returned as is.
+    pub(crate) fn multiplex_outgoing(
+        &mut self,
+        span: Span,
+        low_uri: &Lib3hUri,
+        payload: Opaque,
+    ) -> Lib3hResult<Opaque> {
+        let direct = match P2pMessage::from_bytes(payload.to_vec()) {
+            Ok(P2pMessage::MsgDirectRequest(direct)) => direct,
+            _ => return Ok(payload),
+        };
+        // space gateways do not use request ids, anything else is not ours
+        if !direct.request_id.is_empty() || direct.space_address.is_empty() {
+            return Ok(payload);
+        }
+        let spec = ChannelSpec::from_direct(&direct);
+        let (channel_id, is_new) = self.multiplex_channels(low_uri).open_outgoing(&spec);
+        if is_new {
+            trace!(
+                "({}) open channel {} to {} for {:?}",
+                self.identifier.nickname,
+                channel_id,
+                low_uri,
+                spec
+            );
+            self.send_multiplex_message(
+                span,
+                low_uri.clone(),
+                MultiplexMessage::MsgChannelCreate(spec.to_create(channel_id)),
+            )?;
+        }
+        Ok(
+            multiplex_to_p2p(&MultiplexMessage::MsgChannelMessage(MsgChannelMessage {
+                channel_id,
+                content: direct.data,
+            }))
+            .into_bytes()
+            .into(),
+        )
+    }
+
+    /// Serve a multiplex message sent by the node at `low_uri`
+    pub(crate) fn handle_multiplex_message(
+        &mut self,
+        span: Span,
+        low_uri: Lib3hUri,
+        msg: MultiplexMessage,
+    ) -> GhostResult<()> {
+        match msg {
+            MultiplexMessage::MsgChannelCreate(create) => {
+                if let Err(e) = self.multiplex_channels(&low_uri).create_incoming(&create) {
+                    return self.send_multiplex_error(span, low_uri, create.channel_id, &e);
+                }
+            }
+            MultiplexMessage::MsgChannelMessage(message) => {
+                let spec = match self
+                    .multiplex_channels(&low_uri)
+                    .incoming(message.channel_id)
+                {
+                    Ok(spec) => spec,
+                    Err(e) => {
+                        return self.send_multiplex_error(span, low_uri, message.channel_id, &e)
+                    }
+                };
+                // hand it to our parent the way non-multiplexing nodes send it
+                let dm_wrapper = DirectMessageData {
+                    space_address: spec.space_address,
+                    request_id: String::new(),
+                    to_agent_id: spec.to_agent_id,
+                    from_agent_id: spec.from_agent_id,
+                    content: message.content.into(),
+                };
+                self.endpoint_self.as_mut().publish(
+                    span.follower("bubble up to parent"),
+                    GatewayRequestToParent::Transport(
+                        transport::protocol::RequestToParent::ReceivedData {
+                            uri: low_uri,
+                            payload: P2pMessage::MsgDirectRequest(direct_message_to_p2p(
+                                &dm_wrapper,
+                            ))
+                            .into_bytes()
+                            .into(),
+                        },
+                    ),
+                )?;
+            }
+            MultiplexMessage::MsgChannelClose(channel_id) => {
+                self.multiplex_channels(&low_uri).close_incoming(channel_id);
+            }
+            MultiplexMessage::MsgError(error) => {
+                if error.channel_id == NO_CHANNEL_ID {
+                    self.handle_relay_error(&low_uri, &error.error_text);
+                    return Ok(());
+                }
+                // we will create a new channel if we have more to send
+                let maybe_spec = self
+                    .multiplex_channels(&low_uri)
+                    .close_outgoing(error.channel_id);
+                warn!(
+                    "({}) {} dropped channel {}: {}",
+                    self.identifier.nickname,
+                    low_uri,
+                    error.channel_id,
+                    error_from_multiplex(&error, maybe_spec.as_ref()),
+                );
+            }
+            MultiplexMessage::MsgRelayRequest => {
+                self.handle_relay_request(span, low_uri)?;
+            }
+            MultiplexMessage::MsgRelayAccept => {
+                self.handle_relay_accept(&low_uri);
+            }
+            MultiplexMessage::MsgTransferChunk(chunk) => {
+                self.handle_transfer_chunk(span, low_uri, chunk)?;
+            }
+            MultiplexMessage::MsgTransferCancel(cancel) => {
+                self.handle_transfer_cancel(span, low_uri, cancel)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Our parent could not deliver a message that came on the channel
+    /// standing for `spec`: close it and tell the remote node why
+    pub(crate) fn reject_channel(
+        &mut self,
+        span: Span,
+        low_uri: Lib3hUri,
+        spec: ChannelSpec,
+        error: Lib3hError,
+    ) -> GhostResult<()> {
+        match self.multiplex_channels(&low_uri).reject_incoming(&spec) {
+            Some(channel_id) => self.send_multiplex_error(span, low_uri, channel_id, &error),
+            None => {
+                // the remote node does not multiplex
+                debug!("({}) {}: {}", self.identifier.nickname, low_uri, error);
+                Ok(())
+            }
+        }
+    }
+
+    fn send_multiplex_error(
+        &mut self,
+        span: Span,
+        low_uri: Lib3hUri,
+        channel_id: u32,
+        error: &Lib3hError,
+    ) -> GhostResult<()> {
+        debug!(
+            "({}) reject channel {} from {}: {}",
+            self.identifier.nickname, channel_id, low_uri, error
+        );
+        self.send_multiplex_message(
+            span,
+            low_uri,
+            MultiplexMessage::create_error(
+                channel_id,
+                error.multiplex_error_code(),
+                &error.to_string(),
+            ),
+        )
+    }
+
+    pub(crate) fn send_multiplex_message(
+        &mut self,
+        span: Span,
+        low_uri: Lib3hUri,
+        msg: MultiplexMessage,
+    ) -> GhostResult<()> {
+        let payload = multiplex_to_p2p(&msg).into_bytes().into();
+        self.send_with_full_low_uri(
+            SendWithFullLowUri {
+                span,
+                full_low_uri: low_uri,
+                payload,
+                reliable: true,
+            },
+            Box::new(move |response| {
+                trace!("sent multiplex message {:?}: {:?}", msg, response);
+                Ok(())
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_spec(to_agent_id: &str) -> ChannelSpec {
+        ChannelSpec {
+            space_address: "space_1".into(),
+            to_agent_id: to_agent_id.into(),
+            from_agent_id: "agent_2".into(),
+        }
+    }
+
+    #[test]
+    fn it_should_create_one_channel_per_spec() {
+        let mut channels = MultiplexChannels::new();
+        assert_eq!((0, true), channels.open_outgoing(&test_spec("agent_1")));
+        assert_eq!((0, false), channels.open_outgoing(&test_spec("agent_1")));
+        assert_eq!((1, true), channels.open_outgoing(&test_spec("agent_3")));
+        assert_eq!(Some(test_spec("agent_1")), channels.close_outgoing(0));
+        assert_eq!((2, true), channels.open_outgoing(&test_spec("agent_1")));
+    }
+
+    #[test]
+    fn it_should_track_incoming_channels() {
+        let mut sender = MultiplexChannels::new();
+        let mut receiver = MultiplexChannels::new();
+        let spec = test_spec("agent_1");
+        let (channel_id, _) = sender.open_outgoing(&spec);
+        match receiver.incoming(channel_id).unwrap_err().kind() {
+            ErrorKind::BadChannelId(id) => assert_eq!(channel_id, *id),
+            other => panic!("unexpected error {:?}", other),
+        }
+        receiver
+            .create_incoming(&spec.to_create(channel_id))
+            .unwrap();
+        assert_eq!(spec, receiver.incoming(channel_id).unwrap());
+        assert_eq!(Some(channel_id), receiver.reject_incoming(&spec));
+        assert!(receiver.incoming(channel_id).is_err());
+    }
+
+    #[test]
+    fn it_should_limit_incoming_channels() {
+        let mut receiver = MultiplexChannels::new();
+        let spec = test_spec("agent_1");
+        for channel_id in 0..MAX_INCOMING_CHANNELS as u32 {
+            receiver
+                .create_incoming(&spec.to_create(channel_id))
+                .unwrap();
+        }
+        let excess = MAX_INCOMING_CHANNELS as u32;
+        match receiver
+            .create_incoming(&spec.to_create(excess))
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::BadChannelId(id) => assert_eq!(excess, *id),
+            other => panic!("unexpected error {:?}", other),
+        }
+        assert!(receiver.incoming(excess).is_err());
+        // an open channel can be recreated, closing one makes room
+        receiver.create_incoming(&spec.to_create(0)).unwrap();
+        receiver.close_incoming(0);
+        receiver.create_incoming(&spec.to_create(excess)).unwrap();
+    }
+
+    #[test]
+    fn it_should_convert_remote_errors() {
+        let spec = test_spec("agent_1");
+        let error = Lib3hError::new(ErrorKind::BadToId(spec.to_agent_id.clone()));
+        let msg = MsgMultiplexError {
+            channel_id: 0,
+            error_code: error.multiplex_error_code(),
+            error_text: error.to_string(),
+        };
+        match error_from_multiplex(&msg, Some(&spec)).kind() {
+            ErrorKind::BadToId(agent_id) => assert_eq!(&spec.to_agent_id, agent_id),
+            other => panic!("unexpected error {:?}", other),
+        }
+        match error_from_multiplex(&msg, None).kind() {
+            ErrorKind::Other(_) => (),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Ping / pong liveness of the nodes a network gateway is connected to.
+//!
+//! Every connected node is pinged each `PING_INTERVAL_MS`. Pongs give us
+//! the round trip time and, since they carry the responder's clock at
+//! reception, an estimate of how far its clock is from ours. A node that
+//! misses `MAX_MISSED_PONGS` pongs in a row is reported to our dht as
+//! unresponsive, which times it out.
+
+use crate::{
+    dht::dht_protocol::*,
+    gateway::{send_data_types::*, GatewayOutputWrapType, P2pGateway},
+    new_root_span,
+};
+use lib3h_ghost_actor::prelude::*;
+use lib3h_p2p_protocol::p2p::{MsgPong, P2pMessage};
+use lib3h_protocol::{data_types::PeerLatencyData, uri::Lib3hUri};
+
+/// How often we ping each connected node
+pub const PING_INTERVAL_MS: u64 = 5000;
+
+/// Number of pongs in a row a node may miss before it is timed-out
+pub const MAX_MISSED_PONGS: u32 = 3;
+
+/// Ping / pong bookkeeping for one connected node
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PeerLiveness {
+    /// set once the node told us its PeerName
+    peer_name: Option<Lib3hUri>,
+    last_ping_ms: u64,
+    awaiting_pong: bool,
+    missed_pongs: u32,
+    rtt_ms: Option<u64>,
+    smoothed_rtt_ms: Option<u64>,
+    clock_skew_ms: Option<i64>,
+    timed_out: bool,
+}
+
+impl PeerLiveness {
+    pub fn new() -> Self {
+        PeerLiveness {
+            peer_name: None,
+            last_ping_ms: 0,
+            awaiting_pong: false,
+            missed_pongs: 0,
+            rtt_ms: None,
+            smoothed_rtt_ms: None,
+            clock_skew_ms: None,
+            timed_out: false,
+        }
+    }
+
+    pub fn is_ping_due(&self, now_ms: u64) -> bool {
+        now_ms.saturating_sub(self.last_ping_ms) >= PING_INTERVAL_MS
+    }
+
+    /// We are sending a new ping: if the previous one is still unanswered
+    /// count it as missed. Return true if that makes the node time out.
+    pub fn on_ping_sent(&mut self, now_ms: u64) -> bool {
+        self.last_ping_ms = now_ms;
+        if self.awaiting_pong {
+            self.missed_pongs += 1;
+        }
+        self.awaiting_pong = true;
+        if self.missed_pongs >= MAX_MISSED_PONGS && !self.timed_out {
+            self.timed_out = true;
+            return true;
+        }
+        false
+    }
+
+    /// Any pong proves the node is alive. Since it carries the send time of
+    /// its ping, a late pong still yields a correct round trip time.
+    pub fn on_pong(&mut self, now_ms: u64, pong: &MsgPong) {
+        let rtt_ms = now_ms.saturating_sub(pong.ping_send_epoch_ms);
+        self.rtt_ms = Some(rtt_ms);
+        // same smoothing factor as TCP's SRTT
+        self.smoothed_rtt_ms = Some(match self.smoothed_rtt_ms {
+            None => rtt_ms,
+            Some(smoothed_rtt_ms) => (7 * smoothed_rtt_ms + rtt_ms) / 8,
+        });
+        // assume the ping took half the round trip to get there
+        let remote_received_at_ms = pong.ping_send_epoch_ms + rtt_ms / 2;
+        self.clock_skew_ms =
+            Some(pong.ping_received_epoch_ms as i64 - remote_received_at_ms as i64);
+        self.awaiting_pong = false;
+        self.missed_pongs = 0;
+        self.timed_out = false;
+    }
+
+    pub fn to_latency_data(&self, peer_location: &Lib3hUri) -> PeerLatencyData {
+        PeerLatencyData {
+            peer_name: self
+                .peer_name
+                .clone()
+                .unwrap_or_else(Lib3hUri::with_undefined),
+            peer_location: peer_location.clone(),
+            rtt_ms: self.rtt_ms,
+            smoothed_rtt_ms: self.smoothed_rtt_ms,
+            clock_skew_ms: self.clock_skew_ms,
+            missed_pongs: self.missed_pongs,
+            timed_out: self.timed_out,
+            // filled in from the message encoding's statistics
+            compression: None,
+        }
+    }
+}
+
+impl P2pGateway {
+    /// Start pinging a node we are connected to, if we are a network gateway
+    pub(crate) fn track_peer_liveness(
+        &mut self,
+        uri: &Lib3hUri,
+        maybe_peer_name: Option<Lib3hUri>,
+    ) {
+        if let GatewayOutputWrapType::WrapOutputWithP2pDirectMessage = self.wrap_output_type {
+            return;
+        }
+        let liveness = self
+            .peer_liveness
+            .entry(uri.clone())
+            .or_insert_with(PeerLiveness::new);
+        if maybe_peer_name.is_some() {
+            liveness.peer_name = maybe_peer_name;
+        }
+    }
+
+    /// The name the node at `uri` told us, if we are pinging it
+    pub(crate) fn peer_name_of(&self, uri: &Lib3hUri) -> Option<Lib3hUri> {
+        self.peer_liveness.get(uri)?.peer_name.clone()
+    }
+
+    pub(crate) fn handle_pong(&mut self, uri: &Lib3hUri, pong: &MsgPong) {
+        self.track_peer_liveness(uri, None);
+        if let Some(liveness) = self.peer_liveness.get_mut(uri) {
+            liveness.on_pong(crate::time::since_epoch_ms(), pong);
+            trace!(
+                "({}) pong from {}: rtt = {:?} ms, clock skew = {:?} ms",
+                self.identifier.nickname,
+                uri,
+                liveness.rtt_ms,
+                liveness.clock_skew_ms,
+            );
+        }
+    }
+
+    /// Latency measurements of every node we are pinging
+    pub(crate) fn peer_latency_list(&self) -> Vec<PeerLatencyData> {
+        self.peer_liveness
+            .iter()
+            .map(|(uri, liveness)| liveness.to_latency_data(uri))
+            .collect()
+    }
+
+    /// Ping every node whose ping interval elapsed,
+    /// and report those that stopped answering to our dht
+    pub(crate) fn process_ping_loop(&mut self) -> GhostResult<()> {
+        let now = crate::time::since_epoch_ms();
+        let mut ping_list = Vec::new();
+        let mut unresponsive_list = Vec::new();
+        for (uri, liveness) in self.peer_liveness.iter_mut() {
+            if !liveness.is_ping_due(now) {
+                continue;
+            }
+            if liveness.on_ping_sent(now) {
+                match &liveness.peer_name {
+                    Some(peer_name) => unresponsive_list.push(peer_name.clone()),
+                    None => warn!("{} stopped answering pings before naming itself", uri),
+                }
+            }
+            ping_list.push(uri.clone());
+        }
+        for peer_name in unresponsive_list {
+            debug!(
+                "({}) {} missed {} pongs",
+                self.identifier.nickname, peer_name, MAX_MISSED_PONGS
+            );
+            self.inner_dht.publish(
+                new_root_span("ping loop"),
+                DhtRequestToChild::PeerUnresponsive(peer_name),
+            )?;
+        }
+        for uri in ping_list {
+            let payload = P2pMessage::create_ping(Some(now)).into_bytes().into();
+            self.send_with_full_low_uri(
+                SendWithFullLowUri {
+                    span: new_root_span("ping loop"),
+                    full_low_uri: uri,
+                    payload,
+                    reliable: true,
+                },
+                Box::new(|response| {
+                    trace!("sent ping {:?}", response);
+                    Ok(())
+                }),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pong(ping_send_epoch_ms: u64, ping_received_epoch_ms: u64) -> MsgPong {
+        MsgPong {
+            ping_send_epoch_ms,
+            ping_received_epoch_ms,
+            protocol_version: 0,
+        }
+    }
+
+    #[test]
+    fn it_should_measure_rtt_and_clock_skew() {
+        let mut liveness = PeerLiveness::new();
+        liveness.on_ping_sent(1000);
+        // remote clock is 500 ms ahead, ping took 50 ms each way
+        liveness.on_pong(1100, &pong(1000, 1550));
+        assert_eq!(Some(100), liveness.rtt_ms);
+        assert_eq!(Some(100), liveness.smoothed_rtt_ms);
+        assert_eq!(Some(500), liveness.clock_skew_ms);
+        liveness.on_ping_sent(6000);
+        liveness.on_pong(6180, &pong(6000, 6590));
+        assert_eq!(Some(180), liveness.rtt_ms);
+        assert_eq!(Some(110), liveness.smoothed_rtt_ms);
+        assert_eq!(Some(500), liveness.clock_skew_ms);
+    }
+
+    #[test]
+    fn it_should_time_out_on_missed_pongs() {
+        let mut liveness = PeerLiveness::new();
+        let mut now = 0;
+        assert!(liveness.is_ping_due(PING_INTERVAL_MS));
+        // the first ping cannot have been missed yet
+        for _ in 0..MAX_MISSED_PONGS {
+            now += PING_INTERVAL_MS;
+            assert!(!liveness.on_ping_sent(now));
+        }
+        now += PING_INTERVAL_MS;
+        assert!(liveness.on_ping_sent(now));
+        assert_eq!(MAX_MISSED_PONGS, liveness.missed_pongs);
+        // only reported once
+        now += PING_INTERVAL_MS;
+        assert!(!liveness.on_ping_sent(now));
+        // back to life
+        liveness.on_pong(now + 10, &pong(now, now + 5));
+        let latency = liveness.to_latency_data(&Lib3hUri::with_undefined());
+        assert_eq!(0, latency.missed_pongs);
+        assert!(!latency.timed_out);
+    }
+}
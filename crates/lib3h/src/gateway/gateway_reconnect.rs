@@ -0,0 +1,226 @@
+//! Reconnection to the nodes a network gateway loses.
+//!
+//! When the connection to a node that told us its PeerName drops, we ping
+//! its last low-level uri again after a delay that doubles with every
+//! attempt, plus some jitter. Sending makes the transport connect. The node
+//! is reconnected once it answers, or names itself on a new connection, and
+//! lost once `ReconnectPolicy::max_attempts` attempts went unanswered.
+
+use crate::{
+    engine::ReconnectPolicy,
+    gateway::{protocol::*, send_data_types::*, P2pGateway},
+    new_root_span,
+};
+use lib3h_ghost_actor::prelude::*;
+use lib3h_p2p_protocol::p2p::P2pMessage;
+use lib3h_protocol::{data_types::PeerConnectionData, uri::Lib3hUri};
+use rand::Rng;
+
+impl ReconnectPolicy {
+    /// Delay before attempt number `attempt`, counting from 0, without jitter
+    pub fn backoff_ms(&self, attempt: u32) -> u64 {
+        let factor = 1u64.checked_shl(attempt).unwrap_or(std::u64::MAX);
+        self.initial_delay_ms
+            .saturating_mul(factor)
+            .min(self.max_delay_ms)
+    }
+
+    fn random_jitter_ms(&self) -> u64 {
+        rand::thread_rng().gen_range(0, self.jitter_ms.saturating_add(1))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ReconnectStep {
+    Wait,
+    Attempt,
+    GiveUp,
+}
+
+/// Reconnection bookkeeping for one lost node
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Reconnect {
+    peer_name: Lib3hUri,
+    attempts: u32,
+    next_attempt_ms: u64,
+}
+
+impl Reconnect {
+    pub fn new(peer_name: Lib3hUri, now_ms: u64, policy: &ReconnectPolicy, jitter_ms: u64) -> Self {
+        Reconnect {
+            peer_name,
+            attempts: 0,
+            next_attempt_ms: now_ms + policy.backoff_ms(0) + jitter_ms,
+        }
+    }
+
+    /// What to do now. We only give up a whole delay after the last
+    /// attempt, so that it gets a chance to be answered.
+    pub fn step(&mut self, now_ms: u64, policy: &ReconnectPolicy, jitter_ms: u64) -> ReconnectStep {
+        if now_ms < self.next_attempt_ms {
+            return ReconnectStep::Wait;
+        }
+        if self.attempts >= policy.max_attempts {
+            return ReconnectStep::GiveUp;
+        }
+        self.attempts += 1;
+        self.next_attempt_ms = now_ms + policy.backoff_ms(self.attempts) + jitter_ms;
+        ReconnectStep::Attempt
+    }
+
+    fn to_peer_connection_data(&self, peer_location: &Lib3hUri) -> PeerConnectionData {
+        PeerConnectionData {
+            peer_name: self.peer_name.clone(),
+            peer_location: peer_location.clone(),
+            attempts: self.attempts,
+        }
+    }
+}
+
+impl P2pGateway {
+    /// Start reconnecting to the node we just lost the connection to at `uri`,
+    /// if we have a policy and know which node it was
+    pub(crate) fn schedule_reconnect(&mut self, uri: &Lib3hUri) {
+        let policy = match &self.reconnect_policy {
+            Some(policy) => policy,
+            None => return,
+        };
+        // a failed attempt, it is already scheduled
+        if self.reconnecting.contains_key(uri) {
+            return;
+        }
+        let peer_name = match self.peer_name_of(uri) {
+            Some(peer_name) => peer_name,
+            None => return,
+        };
+        debug!(
+            "({}) lost {} at {}, reconnecting",
+            self.identifier.nickname, peer_name, uri
+        );
+        let reconnect = Reconnect::new(
+            peer_name,
+            crate::time::since_epoch_ms(),
+            policy,
+            policy.random_jitter_ms(),
+        );
+        self.reconnecting.insert(uri.clone(), reconnect);
+    }
+
+    /// The node at `uri` answered us, or named itself `maybe_peer_name`:
+    /// if it is one we lost, it is back
+    pub(crate) fn confirm_reconnected(
+        &mut self,
+        uri: &Lib3hUri,
+        maybe_peer_name: Option<&Lib3hUri>,
+    ) -> GhostResult<()> {
+        let lost_uri = match self
+            .reconnecting
+            .iter()
+            .find(|(lost_uri, reconnect)| {
+                *lost_uri == uri || Some(&reconnect.peer_name) == maybe_peer_name
+            })
+            .map(|(lost_uri, _)| lost_uri.clone())
+        {
+            Some(lost_uri) => lost_uri,
+            None => return Ok(()),
+        };
+        let reconnect = self.reconnecting.remove(&lost_uri).expect("exists");
+        info!(
+            "({}) reconnected to {} at {} after {} attempts",
+            self.identifier.nickname, reconnect.peer_name, uri, reconnect.attempts
+        );
+        self.endpoint_self.publish(
+            new_root_span("send event GatewayRequestToParent::PeerReconnected"),
+            GatewayRequestToParent::PeerReconnected(reconnect.to_peer_connection_data(uri)),
+        )
+    }
+
+    /// Attempt reconnecting to every lost node whose delay elapsed,
+    /// and report those we give up on
+    pub(crate) fn process_reconnect_loop(&mut self) -> GhostResult<()> {
+        let policy = match &self.reconnect_policy {
+            Some(policy) => policy.clone(),
+            None => return Ok(()),
+        };
+        let now = crate::time::since_epoch_ms();
+        let mut attempt_list = Vec::new();
+        let mut lost_list = Vec::new();
+        for (uri, reconnect) in self.reconnecting.iter_mut() {
+            match reconnect.step(now, &policy, policy.random_jitter_ms()) {
+                ReconnectStep::Wait => (),
+                ReconnectStep::Attempt => {
+                    attempt_list.push((uri.clone(), reconnect.peer_name.clone()))
+                }
+                ReconnectStep::GiveUp => lost_list.push(uri.clone()),
+            }
+        }
+        for uri in lost_list {
+            let reconnect = self.reconnecting.remove(&uri).expect("exists");
+            info!(
+                "({}) gave up reconnecting to {} at {}",
+                self.identifier.nickname, reconnect.peer_name, uri
+            );
+            self.endpoint_self.publish(
+                new_root_span("send event GatewayRequestToParent::PeerLost"),
+                GatewayRequestToParent::PeerLost(reconnect.to_peer_connection_data(&uri)),
+            )?;
+        }
+        for (uri, peer_name) in attempt_list {
+            // its pong will tell us it is back
+            self.track_peer_liveness(&uri, Some(peer_name));
+            let payload = P2pMessage::create_ping(None).into_bytes().into();
+            self.send_with_full_low_uri(
+                SendWithFullLowUri {
+                    span: new_root_span("reconnect loop"),
+                    full_low_uri: uri,
+                    payload,
+                    reliable: true,
+                },
+                Box::new(|response| {
+                    trace!("sent reconnect ping {:?}", response);
+                    Ok(())
+                }),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial_delay_ms: 100,
+            max_delay_ms: 1000,
+            jitter_ms: 0,
+            max_attempts: 3,
+        }
+    }
+
+    #[test]
+    fn it_should_double_the_delay_up_to_the_max() {
+        let policy = policy();
+        assert_eq!(100, policy.backoff_ms(0));
+        assert_eq!(200, policy.backoff_ms(1));
+        assert_eq!(800, policy.backoff_ms(3));
+        assert_eq!(1000, policy.backoff_ms(4));
+        assert_eq!(1000, policy.backoff_ms(200));
+    }
+
+    #[test]
+    fn it_should_give_up_after_max_attempts() {
+        let policy = policy();
+        let mut reconnect = Reconnect::new(Lib3hUri::with_undefined(), 0, &policy, 5);
+        assert_eq!(ReconnectStep::Wait, reconnect.step(104, &policy, 0));
+        assert_eq!(ReconnectStep::Attempt, reconnect.step(105, &policy, 0));
+        assert_eq!(ReconnectStep::Wait, reconnect.step(304, &policy, 0));
+        assert_eq!(ReconnectStep::Attempt, reconnect.step(305, &policy, 0));
+        assert_eq!(ReconnectStep::Attempt, reconnect.step(705, &policy, 0));
+        assert_eq!(3, reconnect.attempts);
+        // the last attempt gets its delay to be answered
+        assert_eq!(ReconnectStep::Wait, reconnect.step(1504, &policy, 0));
+        assert_eq!(ReconnectStep::GiveUp, reconnect.step(1505, &policy, 0));
+    }
+}
@@ -0,0 +1,505 @@
+//! Relaying traffic for nodes that cannot be connected to directly.
+//!
+//! A node behind a NAT asks a publicly reachable node, its relay, to
+//! forward traffic for it with `MsgRelayRequest`, and advertises
+//! `relay_location#relay=node_id` as its location. Other nodes reach it
+//! by wrapping their messages in a relay envelope sent to the relay, which
+//! forwards it with the sender filled in. Since nobody can connect back to
+//! it, the node behind the relay sends all its own traffic through it too.
+//! Nodes with a transit identity run their transit handshake through the
+//! relay too, so a relay cannot speak for the nodes it forwards traffic of.
+
+use crate::{
+    dht::dht_protocol::*,
+    engine::{p2p_protocol::relay_envelope_to_p2p, RelayQuota},
+    error::{Lib3hError, Lib3hResult},
+    gateway::{send_data_types::*, P2pGateway},
+    new_root_span,
+};
+use holochain_tracing::Span;
+use lib3h_ghost_actor::prelude::*;
+use lib3h_p2p_protocol::{
+    multiplex::{MultiplexErrorCode, MultiplexMessage, NO_CHANNEL_ID},
+    p2p::{MsgDirect, P2P_PROTOCOL_VERSION_MULTIPLEX},
+};
+use lib3h_protocol::{
+    types::NodePubKey,
+    uri::{Lib3hUri, UriScheme},
+};
+use std::{collections::HashMap, convert::TryFrom};
+
+/// Relay quotas are accounted over windows of this length
+const RELAY_QUOTA_WINDOW_MS: u64 = 60000;
+
+/// Where we stand with the relay we asked to forward our traffic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RelayState {
+    /// waiting to learn whether it can relay at all
+    Pending,
+    /// `MsgRelayRequest` sent
+    Requested,
+    Accepted,
+    Refused,
+}
+
+/// The relay we asked to forward our traffic
+#[derive(Debug, Clone)]
+pub(crate) struct OurRelay {
+    pub uri: Lib3hUri,
+    pub state: RelayState,
+}
+
+impl OurRelay {
+    pub fn new(uri: Lib3hUri) -> Self {
+        OurRelay {
+            uri,
+            state: RelayState::Pending,
+        }
+    }
+}
+
+/// A node we forward traffic for
+#[derive(Debug)]
+struct RelayClient {
+    low_uri: Lib3hUri,
+    window_start_ms: u64,
+    window_bytes: u64,
+}
+
+/// The nodes we forward traffic for, within our quota
+#[derive(Debug)]
+pub(crate) struct RelayClients {
+    /// relaying is disabled without a quota
+    quota: Option<RelayQuota>,
+    clients: HashMap<NodePubKey, RelayClient>,
+}
+
+impl RelayClients {
+    pub fn new(quota: Option<RelayQuota>) -> Self {
+        RelayClients {
+            quota,
+            clients: HashMap::new(),
+        }
+    }
+
+    /// Start relaying for `node_id`, connected at `low_uri`
+    pub fn accept(&mut self, node_id: &NodePubKey, low_uri: &Lib3hUri) -> Result<(), String> {
+        let max_clients = match &self.quota {
+            Some(quota) => quota.max_clients,
+            None => return Err("relaying is not enabled".to_string()),
+        };
+        if let Some(client) = self.clients.get_mut(node_id) {
+            // it reconnected
+            client.low_uri = low_uri.clone();
+            return Ok(());
+        }
+        if self.clients.len() >= max_clients {
+            return Err(format!("already relaying for {} nodes", self.clients.len()));
+        }
+        self.clients.insert(
+            node_id.clone(),
+            RelayClient {
+                low_uri: low_uri.clone(),
+                window_start_ms: 0,
+                window_bytes: 0,
+            },
+        );
+        Ok(())
+    }
+
+    /// Where the client with id `node_id` is connected
+    pub fn low_uri(&self, node_id: &str) -> Option<Lib3hUri> {
+        self.clients
+            .get(&NodePubKey::from(node_id))
+            .map(|client| client.low_uri.clone())
+    }
+
+    /// The client connected at `low_uri`, if any
+    pub fn node_id_at(&self, low_uri: &Lib3hUri) -> Option<NodePubKey> {
+        self.clients
+            .iter()
+            .find(|(_, client)| &client.low_uri == low_uri)
+            .map(|(node_id, _)| node_id.clone())
+    }
+
+    /// Stop relaying for the client connected at `low_uri`
+    pub fn remove(&mut self, low_uri: &Lib3hUri) -> Option<NodePubKey> {
+        let node_id = self.node_id_at(low_uri)?;
+        self.clients.remove(&node_id);
+        Some(node_id)
+    }
+
+    /// Account for `bytes` relayed to or from `node_id`,
+    /// return false if it would exceed its quota
+    pub fn charge(&mut self, node_id: &NodePubKey, bytes: u64, now_ms: u64) -> bool {
+        let max_bytes = match &self.quota {
+            Some(quota) => quota.max_bytes_per_minute,
+            None => return false,
+        };
+        let client = match self.clients.get_mut(node_id) {
+            Some(client) => client,
+            None => return false,
+        };
+        if now_ms.saturating_sub(client.window_start_ms) >= RELAY_QUOTA_WINDOW_MS {
+            client.window_start_ms = now_ms;
+            client.window_bytes = 0;
+        }
+        if client.window_bytes + bytes > max_bytes {
+            return false;
+        }
+        client.window_bytes += bytes;
+        true
+    }
+}
+
+impl P2pGateway {
+    /// Relay addresses of the nodes we relay for stand for their connection
+    pub(crate) fn relay_canonical_uri(&self, low_uri: Lib3hUri) -> Lib3hUri {
+        if let Some(target) = low_uri.get_relay_target() {
+            if low_uri.relay_location() == self.this_peer.peer_location {
+                if let Some(client_uri) = self.relay_clients.low_uri(&target) {
+                    return client_uri;
+                }
+            }
+        }
+        low_uri
+    }
+
+    /// Where to hand messages for `low_uri` to our transport, and the node
+    /// the relay there has to forward them to if they go through one.
+    /// None if that relay is not ready yet.
+    pub(crate) fn relay_next_hop(
+        &mut self,
+        low_uri: &Lib3hUri,
+    ) -> Lib3hResult<Option<(Lib3hUri, Option<String>)>> {
+        let (relay_uri, target) = match (low_uri.get_relay_target(), self.our_relay.clone()) {
+            // the node is behind a relay
+            (Some(target), maybe_our_relay) => {
+                let relay_uri = low_uri.relay_location();
+                if let Some(our_relay) = maybe_our_relay {
+                    if our_relay.uri != relay_uri {
+                        return Err(Lib3hError::from(format!(
+                            "cannot reach {} from behind another relay",
+                            low_uri
+                        )));
+                    }
+                }
+                (relay_uri, target)
+            }
+            // we are behind a relay, all but the relay itself goes through it
+            (None, Some(our_relay)) => {
+                if &our_relay.uri == low_uri {
+                    return Ok(Some((low_uri.clone(), None)));
+                }
+                match our_relay.state {
+                    RelayState::Accepted => (),
+                    RelayState::Refused => {
+                        return Err(Lib3hError::from(format!(
+                            "{} refused to relay for us",
+                            our_relay.uri
+                        )))
+                    }
+                    RelayState::Pending | RelayState::Requested => return Ok(None),
+                }
+                (our_relay.uri, low_uri.to_string())
+            }
+            (None, None) => return Ok(Some((low_uri.clone(), None))),
+        };
+        if self.peer_protocol_version(&relay_uri) < P2P_PROTOCOL_VERSION_MULTIPLEX {
+            // its pongs will tell us whether it can relay at all
+            self.track_peer_liveness(&relay_uri, None);
+            return Ok(None);
+        }
+        // nodes we reach through a relay are pinged like connected ones
+        self.track_peer_liveness(low_uri, None);
+        Ok(Some((relay_uri, Some(target))))
+    }
+
+    /// Ask our relay to forward our traffic, once it told us it can
+    pub(crate) fn process_relay_request(&mut self) -> GhostResult<()> {
+        let relay_uri = match &self.our_relay {
+            Some(relay) if relay.state == RelayState::Pending => relay.uri.clone(),
+            _ => return Ok(()),
+        };
+        if self.peer_protocol_version(&relay_uri) < P2P_PROTOCOL_VERSION_MULTIPLEX {
+            self.track_peer_liveness(&relay_uri, None);
+            return Ok(());
+        }
+        debug!(
+            "({}) asking {} to relay for us",
+            self.identifier.nickname, relay_uri
+        );
+        if let Some(relay) = self.our_relay.as_mut() {
+            relay.state = RelayState::Requested;
+        }
+        self.send_multiplex_message(
+            new_root_span("relay request"),
+            relay_uri,
+            MultiplexMessage::MsgRelayRequest,
+        )
+    }
+
+    /// The node at `low_uri` asks us to relay its traffic
+    pub(crate) fn handle_relay_request(
+        &mut self,
+        span: Span,
+        low_uri: Lib3hUri,
+    ) -> GhostResult<()> {
+        let maybe_node_id = self.transit_node_ids.get(&low_uri).cloned().or_else(|| {
+            self.peer_name_of(&low_uri)
+                .filter(|peer_name| peer_name.is_scheme(UriScheme::Node))
+                .map(|peer_name| peer_name.node_id())
+        });
+        let node_id = match maybe_node_id {
+            Some(node_id) => node_id,
+            None => {
+                return self.send_relay_error(span, low_uri, "cannot relay for an unnamed node")
+            }
+        };
+        if let Err(reason) = self.relay_clients.accept(&node_id, &low_uri) {
+            return self.send_relay_error(span, low_uri, &reason);
+        }
+        info!(
+            "({}) relaying for {} at {}",
+            self.identifier.nickname, node_id, low_uri
+        );
+        // from now on it is reached through us
+        let peer = PeerData {
+            peer_name: Lib3hUri::with_node_id(&node_id),
+            peer_location: Lib3hUri::with_relay(
+                &self.this_peer.peer_location,
+                &node_id.to_string(),
+            ),
+            timestamp: crate::time::since_epoch_ms(),
+        };
+        self.inner_dht.publish(
+            span.child("hold relay client"),
+            DhtRequestToChild::HoldPeer(peer),
+        )?;
+        self.send_multiplex_message(span, low_uri, MultiplexMessage::MsgRelayAccept)
+    }
+
+    pub(crate) fn handle_relay_accept(&mut self, low_uri: &Lib3hUri) {
+        match self.our_relay.as_mut() {
+            Some(relay) if &relay.uri == low_uri && relay.state == RelayState::Requested => {
+                info!("({}) {} relays for us", self.identifier.nickname, low_uri);
+                relay.state = RelayState::Accepted;
+            }
+            _ => debug!(
+                "({}) unexpected relay accept from {}",
+                self.identifier.nickname, low_uri
+            ),
+        }
+    }
+
+    /// The node at `low_uri` refused to relay for us, or could not forward
+    /// one of our messages
+    pub(crate) fn handle_relay_error(&mut self, low_uri: &Lib3hUri, error_text: &str) {
+        match self.our_relay.as_mut() {
+            Some(relay) if &relay.uri == low_uri && relay.state == RelayState::Requested => {
+                error!(
+                    "({}) {} refused to relay for us: {}",
+                    self.identifier.nickname, low_uri, error_text
+                );
+                relay.state = RelayState::Refused;
+            }
+            _ => warn!(
+                "({}) {} could not relay: {}",
+                self.identifier.nickname, low_uri, error_text
+            ),
+        }
+    }
+
+    pub(crate) fn relay_disconnected(&mut self, low_uri: &Lib3hUri) {
+        if let Some(node_id) = self.relay_clients.remove(low_uri) {
+            debug!(
+                "({}) stopped relaying for {}",
+                self.identifier.nickname, node_id
+            );
+        }
+        if let Some(relay) = self.our_relay.as_mut() {
+            if &relay.uri == low_uri && relay.state != RelayState::Refused {
+                // ask again once reconnected
+                relay.state = RelayState::Pending;
+            }
+        }
+    }
+
+    /// Serve a relay envelope sent by the node at `low_uri`
+    pub(crate) fn handle_relay_envelope(
+        &mut self,
+        span: Span,
+        low_uri: Lib3hUri,
+        direct: &MsgDirect,
+    ) -> GhostResult<()> {
+        if low_uri.get_relay_target().is_some() {
+            warn!(
+                "({}) relays do not chain, dropping envelope from {}",
+                self.identifier.nickname, low_uri
+            );
+            return Ok(());
+        }
+        if direct.from_agent_id.is_empty() {
+            return self.relay_forward(span, low_uri, &direct.to_agent_id, &direct.data);
+        }
+        // our relay forwarded this to us
+        let from = match Lib3hUri::try_from(direct.from_agent_id.as_str()) {
+            // we are behind that relay, and reach this node through it anyway
+            Ok(uri) => uri,
+            Err(_) => Lib3hUri::with_relay(&low_uri, &direct.from_agent_id),
+        };
+        if self.authenticate_node_ids {
+            // the relay could have forged it, trust what the transit
+            // session with the node itself authenticates
+            let relay_route = Some((low_uri, direct.from_agent_id.clone()));
+            return self.priv_decode_on_receive(span, from, direct.data.clone().into(), relay_route);
+        }
+        self.priv_on_receive(span, from, direct.data.clone().into())
+    }
+
+    /// Forward a message from the node at `low_uri` to `target`.
+    /// We only relay to our clients, or from them.
+    fn relay_forward(
+        &mut self,
+        span: Span,
+        low_uri: Lib3hUri,
+        target: &str,
+        data: &[u8],
+    ) -> GhostResult<()> {
+        let sender = self.relay_clients.node_id_at(&low_uri);
+        let route = match (self.relay_clients.low_uri(target), &sender) {
+            (Some(client_uri), _) => Some((client_uri, NodePubKey::from(target))),
+            (None, Some(node_id)) => match Lib3hUri::try_from(target) {
+                Ok(target_uri) if target_uri.get_relay_target().is_none() => {
+                    Some((target_uri, node_id.clone()))
+                }
+                _ => None,
+            },
+            (None, None) => None,
+        };
+        let (next_uri, client_id) = match route {
+            Some(route) => route,
+            None => {
+                let error_text = format!("not relaying to {}", target);
+                return self.send_relay_error(span, low_uri, &error_text);
+            }
+        };
+        if !self
+            .relay_clients
+            .charge(&client_id, data.len() as u64, crate::time::since_epoch_ms())
+        {
+            let error_text = format!("relay quota of {} exceeded", client_id);
+            return self.send_relay_error(span, low_uri, &error_text);
+        }
+        let origin = match sender {
+            Some(node_id) => node_id.to_string(),
+            None => low_uri.to_string(),
+        };
+        trace!(
+            "({}) relaying {} bytes from {} to {}",
+            self.identifier.nickname,
+            data.len(),
+            origin,
+            next_uri
+        );
+        let payload = relay_envelope_to_p2p(target, &origin, data)
+            .into_bytes()
+            .into();
+        self.send_with_full_low_uri(
+            SendWithFullLowUri {
+                span,
+                full_low_uri: next_uri,
+                payload,
+                reliable: true,
+            },
+            Box::new(|response| {
+                trace!("relayed message {:?}", response);
+                Ok(())
+            }),
+        )
+    }
+
+    fn send_relay_error(
+        &mut self,
+        span: Span,
+        low_uri: Lib3hUri,
+        error_text: &str,
+    ) -> GhostResult<()> {
+        debug!(
+            "({}) relay error for {}: {}",
+            self.identifier.nickname, low_uri, error_text
+        );
+        self.send_multiplex_message(
+            span,
+            low_uri,
+            MultiplexMessage::create_error(NO_CHANNEL_ID, MultiplexErrorCode::Unknown, error_text),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_quota() -> RelayQuota {
+        RelayQuota {
+            max_clients: 2,
+            max_bytes_per_minute: 100,
+        }
+    }
+
+    #[test]
+    fn it_should_only_relay_when_enabled() {
+        let mut clients = RelayClients::new(None);
+        assert!(clients
+            .accept(&"HcMnode_1".into(), &Lib3hUri::with_memory("addr_1"))
+            .is_err());
+    }
+
+    #[test]
+    fn it_should_limit_clients() {
+        let mut clients = RelayClients::new(Some(test_quota()));
+        for i in 1..=2 {
+            clients
+                .accept(
+                    &format!("HcMnode_{}", i).as_str().into(),
+                    &Lib3hUri::with_memory(&format!("addr_{}", i)),
+                )
+                .unwrap();
+        }
+        assert!(clients
+            .accept(&"HcMnode_3".into(), &Lib3hUri::with_memory("addr_3"))
+            .is_err());
+        // a client may reconnect elsewhere
+        clients
+            .accept(&"HcMnode_1".into(), &Lib3hUri::with_memory("addr_4"))
+            .unwrap();
+        assert_eq!(
+            Some(Lib3hUri::with_memory("addr_4")),
+            clients.low_uri("HcMnode_1")
+        );
+        assert_eq!(
+            Some(NodePubKey::from("HcMnode_2")),
+            clients.remove(&Lib3hUri::with_memory("addr_2"))
+        );
+        clients
+            .accept(&"HcMnode_3".into(), &Lib3hUri::with_memory("addr_3"))
+            .unwrap();
+    }
+
+    #[test]
+    fn it_should_enforce_byte_quota() {
+        let mut clients = RelayClients::new(Some(test_quota()));
+        let node_id: NodePubKey = "HcMnode_1".into();
+        clients
+            .accept(&node_id, &Lib3hUri::with_memory("addr_1"))
+            .unwrap();
+        let now = RELAY_QUOTA_WINDOW_MS;
+        assert!(clients.charge(&node_id, 60, now));
+        assert!(!clients.charge(&node_id, 60, now + 1));
+        assert!(clients.charge(&node_id, 40, now + 2));
+        // next window
+        assert!(clients.charge(&node_id, 60, now + RELAY_QUOTA_WINDOW_MS));
+        assert!(!clients.charge(&"HcMnode_2".into(), 1, now));
+    }
+}
@@ -2,7 +2,9 @@
 
 use crate::{
     dht::dht_protocol::*,
-    engine::p2p_protocol::P2pProtocol,
+    engine::p2p_protocol::{
+        decode_p2p_message, is_multiplex_envelope, is_relay_envelope, multiplex_from_p2p,
+    },
     error::*,
     gateway::{protocol::*, send_data_types::*, P2pGateway},
     message_encoding::encoding_protocol,
@@ -10,8 +12,12 @@ use crate::{
 };
 use holochain_tracing::Span;
 use lib3h_ghost_actor::prelude::*;
-use lib3h_p2p_protocol::p2p::P2pMessage;
-use lib3h_protocol::{data_types::*, uri::Lib3hUri};
+use lib3h_p2p_protocol::p2p::{MsgPeerName, P2pMessage, P2P_PROTOCOL_VERSION_TRANSIT_ENCODING};
+use lib3h_protocol::{
+    data_types::*,
+    uri::{Lib3hUri, UriScheme},
+};
+use std::convert::TryFrom;
 
 /// Private internals
 impl P2pGateway {
@@ -19,6 +25,7 @@ impl P2pGateway {
     fn handle_incoming_connection(&mut self, span: Span, uri: Lib3hUri) -> TransportResult<()> {
         // TODO #199: This is prbably wrong in that a different level of URI should be being bubbled up.
         // depends on how & what we decide to send up to the client.
+        self.track_peer_liveness(&uri, None);
         self.endpoint_self.publish(
             span.child("send event GatewayRequestToParent::Transport::IncomingConnection"),
             GatewayRequestToParent::Transport(
@@ -41,13 +48,13 @@ impl P2pGateway {
                 };
                 if let DhtRequestToChildResponse::RequestThisPeer(this_peer) = response {
                     // Send to other node our PeerName
-                    let our_peer_name = P2pProtocol::PeerName(
-                        me.identifier.id.to_owned().into(),
-                        this_peer.peer_name,
-                        this_peer.timestamp,
-                    );
+                    let our_peer_name = P2pMessage::MsgPeerName(MsgPeerName {
+                        gateway_id: me.identifier.id.to_string(),
+                        peer_name: this_peer.peer_name.to_string(),
+                        timestamp: this_peer.timestamp,
+                    });
                     trace!(
-                        "({}) sending P2pProtocol::PeerName: {:?} to {:?}",
+                        "({}) sending P2pMessage::MsgPeerName: {:?} to {:?}",
                         me.identifier.nickname,
                         our_peer_name,
                         uri,
@@ -58,6 +65,7 @@ impl P2pGateway {
                             span: span.follower("SendWithFullLowUri"),
                             full_low_uri: uri,
                             payload: buf,
+                            reliable: true,
                         },
                         Box::new(|response| {
                             match response {
@@ -83,92 +91,210 @@ impl P2pGateway {
         Ok(())
     }
 
-    fn priv_decode_on_receive(
+    /// `relay_route` is the relay and target to answer handshakes through,
+    /// for the transit session with a node behind a relay
+    pub(crate) fn priv_decode_on_receive(
         &mut self,
         span: Span,
         uri: Lib3hUri,
         payload: Opaque,
+        relay_route: Option<(Lib3hUri, String)>,
     ) -> GhostResult<()> {
         let e_span = span.child("on_receive");
         self.message_encoding.request(
             span,
-            encoding_protocol::RequestToChild::Decode { payload },
+            encoding_protocol::RequestToChild::Decode {
+                uri: uri.clone(),
+                payload,
+            },
             Box::new(move |me, resp| {
-                match resp {
+                let result = match resp {
                     GhostCallbackData::Response(Ok(
-                        encoding_protocol::RequestToChildResponse::DecodeResult {
-                            result: encoding_protocol::DecodeData::Payload { payload },
-                        },
-                    )) => {
+                        encoding_protocol::RequestToChildResponse::DecodeResult { result },
+                    )) => result,
+                    _ => {
+                        error!("failed to decode message from {}: {:?}", uri, resp);
+                        return Ok(());
+                    }
+                };
+                let response = match result {
+                    encoding_protocol::DecodeData::Payload { payload } => {
                         if payload.len() == 0 {
                             panic!("We should no longer ever be sending zero length messages");
-                        } else {
-                            me.priv_on_receive(e_span, uri, payload)?;
                         }
+                        return me.priv_on_receive(e_span, uri, payload);
+                    }
+                    encoding_protocol::DecodeData::Handshake { response } => {
+                        me.priv_speaks_transit_encoding(&uri);
+                        response
                     }
-                    _ => panic!("unexpected decode result: {:?}", resp),
+                    encoding_protocol::DecodeData::SessionEstablished {
+                        remote_node_id,
+                        encoding,
+                        compression,
+                        response,
+                    } => {
+                        debug!(
+                            "({}) transit session established with {} at {} using {:?}, {:?} compression",
+                            me.identifier.nickname, remote_node_id, uri, encoding, compression
+                        );
+                        me.priv_speaks_transit_encoding(&uri);
+                        me.transit_node_ids.insert(uri.clone(), remote_node_id);
+                        if let Some(peer_name) = me.pending_peer_names.remove(&uri) {
+                            me.priv_handle_peer_name(
+                                e_span.child("authenticated peer name"),
+                                uri.clone(),
+                                peer_name,
+                            )?;
+                        }
+                        response
+                    }
+                    encoding_protocol::DecodeData::Halted { halt, response } => {
+                        warn!(
+                            "({}) transit handshake with {} halted: {:?}",
+                            me.identifier.nickname, uri, halt
+                        );
+                        response
+                    }
+                };
+                match (response, relay_route) {
+                    (Some(response), Some((relay_uri, relay_target))) => {
+                        me.send_relayed_transit_handshake(e_span, relay_uri, relay_target, response)
+                    }
+                    (Some(response), None) => me.send_transit_handshake(e_span, uri, response)?,
+                    (None, _) => (),
                 }
                 Ok(())
             }),
         )
     }
 
-    fn priv_on_receive(&mut self, span: Span, uri: Lib3hUri, payload: Opaque) -> GhostResult<()> {
-        let maybe_p2p_msg = P2pProtocol::from_bytes(payload.into());
-        match maybe_p2p_msg {
-            Ok(P2pProtocol::PeerName(gateway_id, peer_name, timestamp)) => {
-                if self.identifier.id != gateway_id.clone().into() {
-                    panic!("BAD gateway {:?} != {:?}", self.identifier.id, gateway_id);
+    /// The node at `uri` runs a transit handshake with us, so it can decode
+    /// transit payloads even if it did not tell us its protocol version yet
+    fn priv_speaks_transit_encoding(&mut self, uri: &Lib3hUri) {
+        if self.peer_protocol_version(uri) < P2P_PROTOCOL_VERSION_TRANSIT_ENCODING {
+            self.set_peer_protocol_version(uri.clone(), P2P_PROTOCOL_VERSION_TRANSIT_ENCODING);
+        }
+    }
+
+    /// Remember the protocol version a ping or pong from `uri` reported.
+    /// Only frames from an authenticated node may lower it, or anyone could
+    /// talk us out of the transit session.
+    fn priv_on_protocol_version(&mut self, uri: &Lib3hUri, protocol_version: u32) {
+        if self.authenticate_node_ids
+            && !self.transit_node_ids.contains_key(uri)
+            && protocol_version < self.peer_protocol_version(uri)
+        {
+            warn!(
+                "({}) unauthenticated {} reports protocol version {}, ignoring",
+                self.identifier.nickname, uri, protocol_version
+            );
+            return;
+        }
+        self.set_peer_protocol_version(uri.clone(), protocol_version);
+    }
+
+    fn priv_handle_peer_name(
+        &mut self,
+        span: Span,
+        uri: Lib3hUri,
+        peer_name: MsgPeerName,
+    ) -> GhostResult<()> {
+        if self.identifier.id != peer_name.gateway_id.clone().into() {
+            panic!(
+                "BAD gateway {:?} != {:?}",
+                self.identifier.id, peer_name.gateway_id
+            );
+        }
+        let remote_peer_name =
+            Lib3hUri::try_from(peer_name.peer_name.as_str()).map_err(Lib3hError::from)?;
+        // a node may only claim the id it authenticated with
+        if remote_peer_name.is_scheme(UriScheme::Node) {
+            match self.transit_node_ids.get(&uri) {
+                Some(node_id) if &remote_peer_name.node_id() != node_id => {
+                    error!(
+                        "({}) {} authenticated as {} but claims to be {}, ignoring",
+                        self.identifier.nickname, uri, node_id, remote_peer_name
+                    );
+                    return Ok(());
                 }
-                let peer = PeerData {
-                    peer_name,
-                    peer_location: uri,
-                    timestamp,
-                };
-                debug!(
-                    "{:?} Received PeerName: ({}) {} : {:?}",
-                    self.this_peer, self.identifier.nickname, gateway_id, peer,
-                );
-                // HACK
-                self.inner_dht.publish(
-                    span.follower("transport::protocol::RequestToParent::ReceivedData"),
-                    DhtRequestToChild::HoldPeer(peer),
+                None if self.authenticate_node_ids => {
+                    debug!(
+                        "({}) {} claims to be {}, waiting for the transit handshake",
+                        self.identifier.nickname, uri, remote_peer_name
+                    );
+                    self.pending_peer_names.insert(uri, peer_name);
+                    return Ok(());
+                }
+                _ => (),
+            }
+        }
+        self.track_peer_liveness(&uri, Some(remote_peer_name.clone()));
+        self.confirm_reconnected(&uri, Some(&remote_peer_name))?;
+        let peer = PeerData {
+            peer_name: remote_peer_name,
+            peer_location: uri,
+            timestamp: peer_name.timestamp,
+        };
+        debug!(
+            "{:?} Received PeerName: ({}) {} : {:?}",
+            self.this_peer, self.identifier.nickname, peer_name.gateway_id, peer,
+        );
+        // HACK
+        self.inner_dht.publish(
+            span.follower("transport::protocol::RequestToParent::ReceivedData"),
+            DhtRequestToChild::HoldPeer(peer),
+        )?;
+        Ok(())
+    }
+
+    pub(crate) fn priv_on_receive(
+        &mut self,
+        span: Span,
+        uri: Lib3hUri,
+        payload: Opaque,
+    ) -> GhostResult<()> {
+        let maybe_p2p_msg = decode_p2p_message(&payload);
+        match maybe_p2p_msg {
+            Ok(P2pMessage::MsgPeerName(peer_name)) => {
+                self.priv_handle_peer_name(span, uri, peer_name)?;
+            }
+            Ok(P2pMessage::MsgPing(ping)) => {
+                debug!("got ping from {} {:?}", uri, ping);
+                self.priv_on_protocol_version(&uri, ping.protocol_version);
+                self.track_peer_liveness(&uri, None);
+                let pong = P2pMessage::create_pong(ping.send_epoch_ms, None)
+                    .into_bytes()
+                    .into();
+                self.send_with_full_low_uri(
+                    SendWithFullLowUri {
+                        span: span.child("send_with_full_low_uri"),
+                        full_low_uri: uri,
+                        payload: pong,
+                        reliable: true,
+                    },
+                    Box::new(move |response| {
+                        // we don't need to follow up on a pong
+                        // it can just be a fire-and-forget
+                        trace!("sent pong {:?}", response);
+                        Ok(())
+                    }),
                 )?;
             }
-            Ok(P2pProtocol::CapnProtoMessage(bytes)) => {
-                match P2pMessage::from_bytes(bytes) {
-                    Ok(P2pMessage::MsgPing(ping)) => {
-                        debug!("got ping from {} {:?}", uri, ping);
-                        let pong = P2pProtocol::CapnProtoMessage(
-                            P2pMessage::create_pong(ping.send_epoch_ms, None).into_bytes(),
-                        )
-                        .into_bytes()
-                        .into();
-                        self.send_with_full_low_uri(
-                            SendWithFullLowUri {
-                                span: span.child("send_with_full_low_uri"),
-                                full_low_uri: uri,
-                                payload: pong,
-                            },
-                            Box::new(move |response| {
-                                // we don't need to follow up on a pong
-                                // it can just be a fire-and-forget
-                                trace!("sent pong {:?}", response);
-                                Ok(())
-                            }),
-                        )?;
-                    }
-                    Ok(P2pMessage::MsgPong(pong)) => {
-                        let now = crate::time::since_epoch_ms();
-                        info!(
-                            "got pong from {} indicating latency = {} ms",
-                            uri,
-                            now - pong.ping_send_epoch_ms,
-                        );
-                    }
-                    _ => panic!("failed to decode P2pMessage"),
+            Ok(P2pMessage::MsgPong(pong)) => {
+                self.priv_on_protocol_version(&uri, pong.protocol_version);
+                self.handle_pong(&uri, &pong);
+                self.confirm_reconnected(&uri, None)?;
+            }
+            Ok(P2pMessage::MsgDirectRequest(ref direct)) if is_multiplex_envelope(direct) => {
+                match multiplex_from_p2p(direct) {
+                    Ok(msg) => self.handle_multiplex_message(span, uri, msg)?,
+                    Err(e) => error!("failed to decode multiplex message from {}: {:?}", uri, e),
                 }
             }
+            Ok(P2pMessage::MsgDirectRequest(ref direct)) if is_relay_envelope(direct) => {
+                self.handle_relay_envelope(span, uri, direct)?;
+            }
             Ok(msg) => {
                 // TODO XXX - nope!
                 // We should handle these cases, and pick the ones we want to
@@ -186,8 +312,8 @@ impl P2pGateway {
                     ),
                 )?;
             }
-            _ => {
-                panic!("unexpected received data type {:?}", maybe_p2p_msg);
+            Err(e) => {
+                error!("failed to decode p2p message from {}: {:?}", uri, e);
             }
         };
         Ok(())
@@ -201,7 +327,8 @@ impl P2pGateway {
         parent_request: GatewayToChildMessage,
     ) -> Lib3hResult<()> {
         match transport_request {
-            transport::protocol::RequestToChild::Bind { spec: _ } => {
+            transport::protocol::RequestToChild::Bind { .. }
+            | transport::protocol::RequestToChild::RotateTlsIdentity { .. } => {
                 // Forward to child transport
                 let _ = self.inner_transport.as_mut().request(
                     span.child("handle_transport_RequestToChild"),
@@ -225,7 +352,11 @@ impl P2pGateway {
                     }),
                 );
             }
-            transport::protocol::RequestToChild::SendMessage { uri, payload } => {
+            transport::protocol::RequestToChild::SendMessage {
+                uri,
+                payload,
+                reliable,
+            } => {
                 debug!(
                     "gateway_transport: SendMessage, first resolving address {:?}",
                     uri.clone()
@@ -235,6 +366,7 @@ impl P2pGateway {
                         span: span.child("send_with_partial_high_uri"),
                         partial_high_uri: uri,
                         payload,
+                        reliable,
                     },
                     Box::new(|response| {
                         parent_request
@@ -261,6 +393,9 @@ impl P2pGateway {
             transport::protocol::RequestToChildResponse::SendMessageSuccess => {
                 // no-op
             }
+            transport::protocol::RequestToChildResponse::RotateTlsIdentitySuccess => {
+                // no-op
+            }
         };
         Ok(())
     }
@@ -284,7 +419,23 @@ impl P2pGateway {
                     GatewayRequestToParent::Transport(msg.clone()),
                 )?;
             }
-            transport::protocol::RequestToParent::Disconnect(_uri) => {
+            transport::protocol::RequestToParent::Disconnect(uri)
+            | transport::protocol::RequestToParent::Evicted(uri) => {
+                // connections we evicted ourselves stay closed
+                if let transport::protocol::RequestToParent::Disconnect(_) = msg {
+                    self.schedule_reconnect(uri);
+                }
+                self.transit_node_ids.remove(uri);
+                self.pending_peer_names.remove(uri);
+                self.peer_liveness.remove(uri);
+                self.peer_protocol_versions.remove(uri);
+                self.multiplex_channels.remove(uri);
+                self.transfers_disconnected(span.child("transfers_disconnected"), uri)?;
+                self.relay_disconnected(uri);
+                self.message_encoding.publish(
+                    span.child("publish encoding_protocol::RequestToChild::CloseSession"),
+                    encoding_protocol::RequestToChild::CloseSession { uri: uri.clone() },
+                )?;
                 self.endpoint_self.publish(
                     span.child("send event GatewayRequestToParent::Transport::Disconnect"),
                     GatewayRequestToParent::Transport(msg.clone()),
@@ -320,7 +471,7 @@ impl P2pGateway {
                 if payload.len() == 0 {
                     panic!("We should no longer ever be sending zero length messages");
                 } else {
-                    self.priv_decode_on_receive(span, uri.clone(), payload.clone())?;
+                    self.priv_decode_on_receive(span, uri.clone(), payload.clone(), None)?;
                 }
             }
         };
@@ -1,14 +1,17 @@
 use crate::{
     dht::dht_protocol::*,
-    engine::p2p_protocol::P2pProtocol,
+    engine::p2p_protocol::{direct_message_to_p2p, encode_for_peer, relay_envelope_to_p2p},
+    error::Lib3hError,
     gateway::{protocol::*, send_data_types::*, GatewayOutputWrapType, P2pGateway},
-    message_encoding::encoding_protocol,
+    message_encoding::{encoding_protocol, HANDSHAKE_TIMEOUT_MS},
     transport,
 };
+use holochain_tracing::Span;
 use lib3h_ghost_actor::prelude::*;
-use lib3h_protocol::{data_types::*, types::*};
-use rmp_serde::Serializer;
-use serde::Serialize;
+use lib3h_p2p_protocol::p2p::{
+    P2pMessage, P2P_PROTOCOL_VERSION_MULTIPLEX, P2P_PROTOCOL_VERSION_TRANSIT_ENCODING,
+};
+use lib3h_protocol::{data_types::*, types::*, uri::Lib3hUri};
 
 const SEND_RETRY_INTERVAL_MS: u64 = 20;
 const SEND_RETRY_TIMEOUT_MS: u64 = 60000;
@@ -23,6 +26,27 @@ fn last_attempt_run_on_next_process() -> std::time::Instant {
 
 /// Private internals
 impl P2pGateway {
+    /// p2p protocol version spoken by the node at `low_uri`.
+    /// Until it tells us in a ping or pong, assume it predates versioning.
+    pub(crate) fn peer_protocol_version(&self, low_uri: &Lib3hUri) -> u32 {
+        self.peer_protocol_versions
+            .get(low_uri)
+            .cloned()
+            .unwrap_or(0)
+    }
+
+    /// remember the p2p protocol version a remote node reported
+    pub(crate) fn set_peer_protocol_version(&mut self, low_uri: Lib3hUri, protocol_version: u32) {
+        if self.peer_protocol_version(&low_uri) != protocol_version {
+            debug!(
+                "({}) {} speaks p2p protocol version {}",
+                self.identifier.nickname, low_uri, protocol_version
+            );
+        }
+        self.peer_protocol_versions
+            .insert(low_uri, protocol_version);
+    }
+
     /// check / dispatch all pending sends
     pub(crate) fn process_transport_pending_sends(&mut self) -> GhostResult<()> {
         let mut errors: Vec<GhostError> = Vec::new();
@@ -107,6 +131,19 @@ impl P2pGateway {
                 SendData::WithFullLowUri(send_data) => {
                     self.priv_send_with_full_low_uri(send_data, expires_at, cb)
                 }
+                SendData::Encoded(SendEncoded {
+                    send_data,
+                    transport_uri,
+                    encoded_payload,
+                }) => self.priv_send_with_full_low_uri_inner(
+                    send_data,
+                    transport_uri,
+                    encoded_payload,
+                    now,
+                    expires_at,
+                    cb,
+                ),
+                SendData::Relayed(relayed) => self.priv_send_relayed(relayed, expires_at, cb),
             },
         }
     }
@@ -141,6 +178,7 @@ impl P2pGateway {
                                 span: send_data.span.child("SendWithFullLowUri"),
                                 full_low_uri: uri,
                                 payload: send_data.payload,
+                                reliable: send_data.reliable,
                             },
                             expires_at,
                             cb,
@@ -196,25 +234,149 @@ impl P2pGateway {
         // capture this first so our interval doesn't drift too much
         let last_attempt = std::time::Instant::now();
 
-        let payload = send_data.payload.clone();
+        let (transport_uri, relay_target, request) = match self.wrap_output_type {
+            // we are talking to the remote node directly,
+            // make sure it can understand us
+            GatewayOutputWrapType::DoNotWrapOutput => {
+                let mut low_uri = send_data.full_low_uri.clone();
+                low_uri.clear_agent_id();
+                let low_uri = self.relay_canonical_uri(low_uri);
+                let (transport_uri, maybe_relay_target) = match self.relay_next_hop(&low_uri) {
+                    Ok(Some(next_hop)) => next_hop,
+                    // wait for the relay to be ready
+                    Ok(None) => {
+                        return self.priv_send_queue_pending(SendMetaData {
+                            send_data: SendData::WithFullLowUri(send_data),
+                            last_attempt,
+                            expires_at,
+                            cb,
+                        })
+                    }
+                    Err(e) => return cb(Err(e.into())),
+                };
+                let protocol_version = self.peer_protocol_version(&low_uri);
+                let payload = if protocol_version >= P2P_PROTOCOL_VERSION_MULTIPLEX {
+                    self.multiplex_outgoing(
+                        send_data.span.child("multiplex_outgoing"),
+                        &low_uri,
+                        send_data.payload.clone(),
+                    )
+                } else {
+                    encode_for_peer(send_data.payload.clone(), protocol_version)
+                };
+                let payload = match payload {
+                    Ok(payload) => payload,
+                    Err(e) => return cb(Err(e.into())),
+                };
+                if self.should_chunk(&low_uri, &payload) {
+                    self.start_outgoing_transfer(low_uri, payload, cb);
+                    return Ok(());
+                }
+                // with an identity, we run a transit session with the node
+                // behind the relay itself: the relay cannot forge its id
+                let (payload, relay_target) = match maybe_relay_target {
+                    Some(target) if self.authenticate_node_ids => (payload, Some(target)),
+                    Some(target) => (
+                        relay_envelope_to_p2p(&target, "", &payload)
+                            .into_bytes()
+                            .into(),
+                        None,
+                    ),
+                    None => (payload, None),
+                };
+                // with an identity, nothing goes out of the transit session,
+                // not even to nodes telling us they do not speak it
+                if self.authenticate_node_ids
+                    && self.peer_protocol_version(&transport_uri)
+                        < P2P_PROTOCOL_VERSION_TRANSIT_ENCODING
+                    && self.peer_protocol_versions.contains_key(&transport_uri)
+                {
+                    return cb(Err(Lib3hError::from(format!(
+                        "{} does not speak transit encoding, refusing to send it plaintext",
+                        transport_uri
+                    ))
+                    .into()));
+                }
+                let request = if relay_target.is_some() {
+                    encoding_protocol::RequestToChild::EncodeTransitPayload {
+                        uri: low_uri,
+                        payload,
+                    }
+                } else if self.authenticate_node_ids
+                    || self.peer_protocol_version(&transport_uri)
+                        >= P2P_PROTOCOL_VERSION_TRANSIT_ENCODING
+                {
+                    encoding_protocol::RequestToChild::EncodeTransitPayload {
+                        uri: transport_uri.clone(),
+                        payload,
+                    }
+                } else {
+                    encoding_protocol::RequestToChild::EncodePayload { payload }
+                };
+                (transport_uri, relay_target, request)
+            }
+            GatewayOutputWrapType::WrapOutputWithP2pDirectMessage => {
+                let mut transport_uri = send_data.full_low_uri.clone();
+                transport_uri.clear_agent_id();
+                (
+                    transport_uri,
+                    None,
+                    encoding_protocol::RequestToChild::EncodePayload {
+                        payload: send_data.payload.clone(),
+                    },
+                )
+            }
+        };
 
         self.message_encoding.request(
-            send_data
-                .span
-                .child("request RequestToChild::EncodePayload"),
-            encoding_protocol::RequestToChild::EncodePayload { payload },
+            send_data.span.child("request message encoding"),
+            request,
             Box::new(move |me, resp| {
                 match resp {
                     GhostCallbackData::Response(Ok(
                         encoding_protocol::RequestToChildResponse::EncodePayloadResult { payload },
-                    )) => {
-                        me.priv_send_with_full_low_uri_inner(
+                    )) => match relay_target {
+                        Some(relay_target) => me.priv_send_relayed(
+                            SendRelayed {
+                                send_data,
+                                transport_uri,
+                                relay_target,
+                                relayed_payload: payload,
+                            },
+                            expires_at,
+                            cb,
+                        )?,
+                        None => me.priv_send_with_full_low_uri_inner(
                             send_data,
+                            transport_uri,
                             payload,
                             last_attempt,
                             expires_at,
                             cb,
-                        )?;
+                        )?,
+                    },
+                    GhostCallbackData::Response(Ok(
+                        encoding_protocol::RequestToChildResponse::HandshakePending { payload },
+                    )) => {
+                        if let Some(payload) = payload {
+                            let span = send_data.span.child("send transit handshake");
+                            match &relay_target {
+                                Some(relay_target) => me.send_relayed_transit_handshake(
+                                    span,
+                                    transport_uri,
+                                    relay_target.clone(),
+                                    payload,
+                                ),
+                                None => me.send_transit_handshake(span, transport_uri, payload)?,
+                            }
+                        }
+                        // try again once the handshake is done
+                        me.priv_send_queue_pending(SendMetaData {
+                            send_data: SendData::WithFullLowUri(send_data),
+                            last_attempt,
+                            expires_at,
+                            cb,
+                        })?;
                     }
                     _ => {
                         me.priv_send_queue_pending(SendMetaData {
@@ -230,10 +392,138 @@ impl P2pGateway {
         )
     }
 
+    /// transit handshake messages are sent as-is, outside of our retry logic,
+    /// the handshake is restarted if any of them are lost
+    pub(crate) fn send_transit_handshake(
+        &mut self,
+        span: Span,
+        uri: Lib3hUri,
+        payload: Opaque,
+    ) -> GhostResult<()> {
+        self.inner_transport.request(
+            span,
+            transport::protocol::RequestToChild::create_send_message(uri, payload),
+            Box::new(|_me, resp| {
+                match resp {
+                    GhostCallbackData::Response(Ok(
+                        transport::protocol::RequestToChildResponse::SendMessageSuccess,
+                    )) => trace!("sent transit handshake"),
+                    _ => debug!("failed to send transit handshake {:?}", resp),
+                }
+                Ok(())
+            }),
+        )
+    }
+
+    /// transit handshake messages with a node behind the relay at `relay_uri`
+    /// go out in an envelope, encoded for the relay like any other message
+    pub(crate) fn send_relayed_transit_handshake(
+        &mut self,
+        span: Span,
+        relay_uri: Lib3hUri,
+        relay_target: String,
+        payload: Opaque,
+    ) {
+        self.pending_send_queue.push(SendMetaData {
+            send_data: SendData::Relayed(SendRelayed {
+                send_data: SendWithFullLowUri {
+                    span,
+                    full_low_uri: relay_uri.clone(),
+                    payload: payload.clone(),
+                    reliable: true,
+                },
+                transport_uri: relay_uri,
+                relay_target,
+                relayed_payload: payload,
+            }),
+            last_attempt: last_attempt_run_on_next_process(),
+            // the handshake is restarted after that anyway
+            expires_at: std::time::Instant::now()
+                .checked_add(std::time::Duration::from_millis(HANDSHAKE_TIMEOUT_MS))
+                .expect("can add"),
+            cb: Box::new(|response| {
+                trace!("sent relayed transit handshake {:?}", response);
+                Ok(())
+            }),
+        });
+    }
+
+    /// wrap a payload encoded for the node behind a relay in an envelope,
+    /// and encode that for the relay
+    fn priv_send_relayed(
+        &mut self,
+        relayed: SendRelayed,
+        expires_at: std::time::Instant,
+        cb: SendCallback,
+    ) -> GhostResult<()> {
+        if !self.message_encoding.is_attached() {
+            return self.priv_send_queue_pending(SendMetaData {
+                send_data: SendData::Relayed(relayed),
+                last_attempt: last_attempt_run_on_next_process(),
+                expires_at,
+                cb,
+            });
+        }
+
+        // capture this first so our interval doesn't drift too much
+        let last_attempt = std::time::Instant::now();
+
+        let envelope =
+            relay_envelope_to_p2p(&relayed.relay_target, "", &relayed.relayed_payload)
+                .into_bytes()
+                .into();
+        self.message_encoding.request(
+            relayed.send_data.span.child("request relay envelope encoding"),
+            encoding_protocol::RequestToChild::EncodeTransitPayload {
+                uri: relayed.transport_uri.clone(),
+                payload: envelope,
+            },
+            Box::new(move |me, resp| {
+                match resp {
+                    GhostCallbackData::Response(Ok(
+                        encoding_protocol::RequestToChildResponse::EncodePayloadResult { payload },
+                    )) => {
+                        me.priv_send_with_full_low_uri_inner(
+                            relayed.send_data,
+                            relayed.transport_uri,
+                            payload,
+                            last_attempt,
+                            expires_at,
+                            cb,
+                        )?;
+                    }
+                    resp => {
+                        if let GhostCallbackData::Response(Ok(
+                            encoding_protocol::RequestToChildResponse::HandshakePending {
+                                payload: Some(payload),
+                            },
+                        )) = resp
+                        {
+                            me.send_transit_handshake(
+                                relayed.send_data.span.child("send transit handshake"),
+                                relayed.transport_uri.clone(),
+                                payload,
+                            )?;
+                        }
+                        // try again once the handshake with the relay is done
+                        me.priv_send_queue_pending(SendMetaData {
+                            send_data: SendData::Relayed(relayed),
+                            last_attempt,
+                            expires_at,
+                            cb,
+                        })?;
+                    }
+                }
+                Ok(())
+            }),
+        )
+    }
+
     /// finally, actually send the message out our inner transport
     fn priv_send_with_full_low_uri_inner(
         &mut self,
         send_data: SendWithFullLowUri,
+        transport_uri: Lib3hUri,
         encoded_payload: Opaque,
         last_attempt: std::time::Instant,
         expires_at: std::time::Instant,
@@ -251,26 +541,22 @@ impl P2pGateway {
                     request_id: String::new(),
                     to_agent_id,
                     from_agent_id: self.this_peer.peer_name.agent_id(),
-                    content: encoded_payload,
+                    content: encoded_payload.clone(),
                 };
-                let mut payload = Vec::new();
-                let p2p_msg = P2pProtocol::DirectMessage(dm_wrapper);
-                p2p_msg
-                    .serialize(&mut Serializer::new(&mut payload))
-                    .unwrap();
-                Opaque::from(payload)
+                P2pMessage::MsgDirectRequest(direct_message_to_p2p(&dm_wrapper))
+                    .into_bytes()
+                    .into()
             } else {
-                encoded_payload
+                encoded_payload.clone()
             };
 
-        let mut uri = send_data.full_low_uri.clone();
-
-        // at last, we need to drop the high-level agent id
-        uri.clear_agent_id();
-
         self.inner_transport.request(
             send_data.span.child("request RequestToChild::SendMessage"),
-            transport::protocol::RequestToChild::SendMessage { uri, payload },
+            transport::protocol::RequestToChild::SendMessage {
+                uri: transport_uri.clone(),
+                payload,
+                reliable: send_data.reliable,
+            },
             Box::new(move |me, resp| {
                 match resp {
                     GhostCallbackData::Response(Ok(
@@ -280,9 +566,17 @@ impl P2pGateway {
                             transport::protocol::RequestToChildResponse::SendMessageSuccess,
                         )))?;
                     }
+                    // the transport is full, retrying would only add to it
+                    GhostCallbackData::Response(Err(ref e)) if e.is_would_block() => {
+                        cb(Err(e.clone()))?;
+                    }
                     _ => {
                         me.priv_send_queue_pending(SendMetaData {
-                            send_data: SendData::WithFullLowUri(send_data),
+                            send_data: SendData::Encoded(SendEncoded {
+                                send_data,
+                                transport_uri,
+                                encoded_payload,
+                            }),
                             last_attempt,
                             expires_at,
                             cb,
@@ -1,6 +1,11 @@
 #[allow(non_snake_case)]
 pub mod gateway_actor;
+pub mod gateway_chunking;
 pub mod gateway_dht;
+pub mod gateway_multiplex;
+pub mod gateway_ping;
+pub mod gateway_reconnect;
+pub mod gateway_relay;
 pub mod gateway_transport;
 pub mod gateway_transport_send;
 pub mod p2p_gateway;
@@ -8,7 +13,7 @@ pub mod protocol;
 
 use crate::{
     dht::dht_protocol::*,
-    engine::GatewayId,
+    engine::{ChunkingConfig, GatewayId, ReconnectPolicy},
     gateway::protocol::*,
     message_encoding::*,
     transport::{self, error::TransportResult},
@@ -17,8 +22,9 @@ use crate::{
 use detach::prelude::*;
 use holochain_tracing::Span;
 use lib3h_ghost_actor::GhostResult;
-use lib3h_protocol::{data_types::Opaque, uri::Lib3hUri};
-use std::boxed::Box;
+use lib3h_p2p_protocol::p2p::MsgPeerName;
+use lib3h_protocol::{data_types::Opaque, types::NodePubKey, uri::Lib3hUri};
+use std::{boxed::Box, collections::HashMap};
 
 pub enum GatewayOutputWrapType {
     DoNotWrapOutput,
@@ -48,6 +54,49 @@ pub struct P2pGateway {
     this_peer: PeerData,
 
     pending_send_queue: Vec<send_data_types::SendMetaData>,
+
+    /// p2p protocol version reported by remote nodes, keyed by low-level uri
+    peer_protocol_versions: HashMap<Lib3hUri, u32>,
+
+    /// node ids authenticated by the transit encoding handshake,
+    /// keyed by low-level uri
+    transit_node_ids: HashMap<Lib3hUri, NodePubKey>,
+
+    /// true if we have a transit identity: node ids must then be
+    /// authenticated by the transit encoding handshake
+    authenticate_node_ids: bool,
+
+    /// node ids claimed before the transit handshake authenticated them,
+    /// keyed by low-level uri
+    pending_peer_names: HashMap<Lib3hUri, MsgPeerName>,
+
+    /// ping / pong bookkeeping of the nodes we are connected to,
+    /// keyed by low-level uri
+    peer_liveness: HashMap<Lib3hUri, gateway_ping::PeerLiveness>,
+
+    /// multiplex channels established with the nodes we are connected to,
+    /// keyed by low-level uri
+    multiplex_channels: HashMap<Lib3hUri, gateway_multiplex::MultiplexChannels>,
+
+    /// the relay we asked to forward traffic for us, if we are behind one
+    our_relay: Option<gateway_relay::OurRelay>,
+
+    /// the nodes we forward traffic for
+    relay_clients: gateway_relay::RelayClients,
+
+    /// how we reconnect to the nodes we lose, we do not if None
+    reconnect_policy: Option<ReconnectPolicy>,
+
+    /// the nodes we lost and are reconnecting to,
+    /// keyed by their last low-level uri
+    reconnecting: HashMap<Lib3hUri, gateway_reconnect::Reconnect>,
+
+    /// which payloads we send in chunks, we do not if None
+    chunking: Option<ChunkingConfig>,
+
+    /// chunked transfers running with the nodes we are connected to,
+    /// keyed by low-level uri
+    transfers: HashMap<Lib3hUri, gateway_chunking::Transfers>,
 }
 
 pub(crate) mod send_data_types {
@@ -60,6 +109,8 @@ pub(crate) mod send_data_types {
         pub span: Span,
         pub partial_high_uri: Lib3hUri,
         pub payload: Opaque,
+        /// false if the transport may drop the message
+        pub reliable: bool,
     }
 
     #[derive(Debug)]
@@ -69,6 +120,28 @@ pub(crate) mod send_data_types {
         pub span: Span,
         pub full_low_uri: Lib3hUri,
         pub payload: Opaque,
+        /// false if the transport may drop the message
+        pub reliable: bool,
+    }
+
+    #[derive(Debug)]
+    /// we already encoded the payload for the node at `transport_uri`,
+    /// retries must send the same bytes: re-encoding would use up
+    /// another transit nonce
+    pub(crate) struct SendEncoded {
+        pub send_data: SendWithFullLowUri,
+        pub transport_uri: Lib3hUri,
+        pub encoded_payload: Opaque,
+    }
+
+    #[derive(Debug)]
+    /// we already encoded the payload for the node behind the relay at
+    /// `transport_uri`, retries only encode the envelope for the relay
+    pub(crate) struct SendRelayed {
+        pub send_data: SendWithFullLowUri,
+        pub transport_uri: Lib3hUri,
+        pub relay_target: String,
+        pub relayed_payload: Opaque,
     }
 
     #[derive(Debug)]
@@ -76,6 +149,8 @@ pub(crate) mod send_data_types {
     pub(crate) enum SendData {
         WithPartialHighUri(SendWithPartialHighUri),
         WithFullLowUri(SendWithFullLowUri),
+        Encoded(SendEncoded),
+        Relayed(SendRelayed),
     }
 
     /// metadata associated with send retry tracking
@@ -1,13 +1,14 @@
 use crate::{
     dht::{dht_config::DhtConfig, dht_protocol::*},
-    engine::GatewayId,
-    gateway::{GatewayOutputWrapType, P2pGateway},
+    engine::{ChunkingConfig, GatewayId, ReconnectPolicy, RelayQuota},
+    gateway::{gateway_relay::*, GatewayOutputWrapType, P2pGateway},
     message_encoding::*,
     transport,
 };
 use detach::prelude::*;
 use lib3h_ghost_actor::prelude::*;
 use lib3h_protocol::uri::{Lib3hUri, UriScheme};
+use std::collections::HashMap;
 
 //--------------------------------------------------------------------------------------------------
 // Constructors
@@ -59,9 +60,56 @@ impl P2pGateway {
             endpoint_self,
             this_peer,
             pending_send_queue: Vec::new(),
+            peer_protocol_versions: HashMap::new(),
+            transit_node_ids: HashMap::new(),
+            authenticate_node_ids: false,
+            pending_peer_names: HashMap::new(),
+            peer_liveness: HashMap::new(),
+            multiplex_channels: HashMap::new(),
+            our_relay: None,
+            relay_clients: RelayClients::new(None),
+            reconnect_policy: None,
+            reconnecting: HashMap::new(),
+            chunking: None,
+            transfers: HashMap::new(),
         }
     }
 
+    /// Authenticate and encrypt our connections to remote nodes
+    pub fn with_transit_identity(mut self, transit_identity: TransitIdentity) -> Self {
+        self.message_encoding = Detach::new(GhostParentWrapper::new(
+            MessageEncoding::with_transit_identity(transit_identity),
+            "gateway_to_message_encoding_",
+        ));
+        self.authenticate_node_ids = true;
+        self
+    }
+
+    /// We cannot be connected to directly,
+    /// ask the node at `relay_uri` to forward our traffic
+    pub fn with_relay(mut self, relay_uri: Lib3hUri) -> Self {
+        self.our_relay = Some(OurRelay::new(relay_uri));
+        self
+    }
+
+    /// Forward traffic for nodes that cannot be connected to directly
+    pub fn with_relay_quota(mut self, quota: RelayQuota) -> Self {
+        self.relay_clients = RelayClients::new(Some(quota));
+        self
+    }
+
+    /// Try connecting again to the nodes we lose the connection to
+    pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = Some(policy);
+        self
+    }
+
+    /// Send large payloads in chunks to the nodes able to reassemble them
+    pub fn with_chunking(mut self, chunking: ChunkingConfig) -> Self {
+        self.chunking = Some(chunking);
+        self
+    }
+
     pub fn this_peer(&self) -> PeerData {
         self.this_peer.clone()
     }
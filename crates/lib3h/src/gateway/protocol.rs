@@ -1,6 +1,6 @@
 use crate::{dht::dht_protocol::*, error::*, transport};
 use lib3h_ghost_actor::prelude::*;
-use lib3h_protocol::data_types::*;
+use lib3h_protocol::{data_types::*, types::*, uri::Lib3hUri};
 
 /// Gateway protocol enums for use with GhostActor implementation
 #[derive(Debug, Clone)]
@@ -9,6 +9,24 @@ pub enum GatewayRequestToChild {
     Dht(DhtRequestToChild),
     Bootstrap(BootstrapData),
     SendAll(Vec<u8>),
+    /// Latency measurements of the nodes we are pinging
+    QueryPeerLatency,
+    /// We could not deliver a space message received from the node at `uri`:
+    /// close the multiplex channel it came on and tell the node why
+    RejectChannel {
+        uri: Lib3hUri,
+        space_address: SpaceHash,
+        to_agent_id: AgentPubKey,
+        from_agent_id: AgentPubKey,
+        error: Lib3hError,
+    },
+    /// Abandon a chunked transfer with the node at `uri`
+    /// and tell it we did
+    CancelTransfer {
+        uri: Lib3hUri,
+        transfer_id: u32,
+        outgoing: bool,
+    },
 }
 
 #[derive(Debug)]
@@ -16,12 +34,21 @@ pub enum GatewayRequestToChildResponse {
     Transport(transport::protocol::RequestToChildResponse),
     Dht(DhtRequestToChildResponse),
     BootstrapSuccess,
+    QueryPeerLatency(Vec<PeerLatencyData>),
+    RejectChannelSuccess,
+    CancelTransferSuccess,
 }
 
 #[derive(Debug)]
 pub enum GatewayRequestToParent {
     Transport(transport::protocol::RequestToParent),
     Dht(DhtRequestToParent),
+    /// We are connected again to a node we lost
+    PeerReconnected(PeerConnectionData),
+    /// We gave up reconnecting to a node we lost
+    PeerLost(PeerConnectionData),
+    /// A large payload we send or receive in chunks progressed
+    TransferProgress(TransferProgressData),
 }
 
 #[derive(Debug)]
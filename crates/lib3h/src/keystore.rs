@@ -1,19 +1,95 @@
-//! This is a stub of the keystore so we can prove out the encoding transport
+//! Keystore actor holding the signing and key exchange keys of a node
+//! and its agents.
+//!
+//! Keypairs are generated with the `CryptoSystem`. When opened on a
+//! directory with a passphrase, the keystore persists them there, each
+//! secret key sealed with `aead` under a key derived from the passphrase
+//! by `pwhash`, so that a node keeps its identity across restarts.
+//! Otherwise keys only live as long as the keystore.
 
-use crate::error::{Lib3hError, Lib3hResult};
+use crate::{
+    engine::TransportKeys,
+    error::{Lib3hError, Lib3hResult},
+};
 
 use detach::prelude::*;
+use lib3h_crypto_api::{Buffer, CryptoSystem};
 use lib3h_ghost_actor::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// Name of the file the keystore persists to, in its directory
+pub const KEYSTORE_FILE_NAME: &str = "keystore.json";
 
 pub mod keystore_protocol {
+    use lib3h_crypto_api::Buffer;
+
+    /// What a keypair is used for
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum KeyType {
+        Sign,
+        Kx,
+    }
+
+    /// Who a keypair belongs to
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum KeyOwner {
+        Node,
+        Agent,
+    }
+
+    /// Public part of a keypair held by the keystore
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct KeyInfo {
+        /// hcid encoding of the public key,
+        /// `HcM` prefixed for the node, `HcS` for agents
+        pub id: String,
+        pub key_type: KeyType,
+        pub owner: KeyOwner,
+        pub public_key: Vec<u8>,
+    }
+
     #[derive(Debug)]
     pub enum RequestToChild {
-        Sign { id: String, payload: Vec<u8> },
+        /// generate and store a new keypair
+        GenerateKey {
+            key_type: KeyType,
+            owner: KeyOwner,
+        },
+        /// sign `payload` with the signing keypair `id`
+        Sign {
+            id: String,
+            payload: Vec<u8>,
+        },
+        /// check the `signature` of `payload` by `public_key`
+        Verify {
+            public_key: Vec<u8>,
+            payload: Vec<u8>,
+            signature: Vec<u8>,
+        },
+        /// derive session keys between our key exchange keypair `id` and
+        /// `remote_public_key`, `is_client` if we started the exchange
+        DeriveKx {
+            id: String,
+            remote_public_key: Vec<u8>,
+            is_client: bool,
+        },
+        ListKeys,
     }
 
     #[derive(Debug)]
     pub enum RequestToChildResponse {
-        Sign { signature: Vec<u8> },
+        GenerateKey(KeyInfo),
+        Sign {
+            signature: Vec<u8>,
+        },
+        Verify {
+            is_valid: bool,
+        },
+        DeriveKx {
+            rx: Box<dyn Buffer>,
+            tx: Box<dyn Buffer>,
+        },
+        ListKeys(Vec<KeyInfo>),
     }
 
     #[derive(Debug)]
@@ -43,6 +119,16 @@ pub type KeystoreActorParentEndpoint = GhostEndpoint<
     Lib3hError,
 >;
 
+pub type KeystoreActorParentWrapper<C> = GhostParentWrapper<
+    C,
+    RequestToParent,
+    RequestToParentResponse,
+    RequestToChild,
+    RequestToChildResponse,
+    Lib3hError,
+    Keystore,
+>;
+
 pub type KeystoreActorParentWrapperDyn<C> = GhostParentWrapperDyn<
     C,
     RequestToParent,
@@ -61,7 +147,7 @@ type KeystoreParentEndpoint = GhostEndpoint<
 >;
 
 type KeystoreSelfEndpoint = GhostContextEndpoint<
-    KeystoreStub,
+    Keystore,
     RequestToParent,
     RequestToParentResponse,
     RequestToChild,
@@ -72,13 +158,49 @@ type KeystoreSelfEndpoint = GhostContextEndpoint<
 type KeystoreMessageFromParent =
     GhostMessage<RequestToChild, RequestToParent, RequestToChildResponse, Lib3hError>;
 
-pub struct KeystoreStub {
+/// A keypair held in memory
+struct Keypair {
+    info: KeyInfo,
+    secret_key: Box<dyn Buffer>,
+}
+
+/// On disk format of a keypair, its secret key sealed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SealedKeypair {
+    id: String,
+    key_type: KeyType,
+    owner: KeyOwner,
+    public_key: Vec<u8>,
+    nonce: Vec<u8>,
+    sealed_secret_key: Vec<u8>,
+}
+
+/// On disk format of the keystore
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct KeystoreFile {
+    /// salt of the passphrase hash
+    salt: Vec<u8>,
+    keys: Vec<SealedKeypair>,
+}
+
+/// Where keys are persisted, and the key sealing them
+struct KeystoreStorage {
+    path: PathBuf,
+    salt: Vec<u8>,
+    seal_key: Box<dyn Buffer>,
+}
+
+pub struct Keystore {
+    crypto: Box<dyn CryptoSystem>,
+    storage: Option<KeystoreStorage>,
+    keys: Vec<Keypair>,
     endpoint_parent: Option<KeystoreParentEndpoint>,
     endpoint_self: Detach<KeystoreSelfEndpoint>,
 }
 
-impl KeystoreStub {
-    pub fn new() -> Self {
+impl Keystore {
+    /// A keystore that does not persist its keys
+    pub fn new(crypto: Box<dyn CryptoSystem>) -> Self {
         let (endpoint_parent, endpoint_self) = create_ghost_channel();
         let endpoint_parent = Some(endpoint_parent);
         let endpoint_self = Detach::new(
@@ -88,27 +210,321 @@ impl KeystoreStub {
                 .build(),
         );
         Self {
+            crypto,
+            storage: None,
+            keys: Vec::new(),
             endpoint_parent,
             endpoint_self,
         }
     }
 
-    fn handle_msg_from_parent(&mut self, mut msg: KeystoreMessageFromParent) -> Lib3hResult<()> {
-        match msg.take_message().expect("exists") {
-            RequestToChild::Sign { id, payload } => self.handle_sign(msg, id, payload),
+    /// Load the keystore persisted in `dir`, or create it,
+    /// sealing its keys under `passphrase`
+    #[allow(clippy::borrowed_box)]
+    pub fn open(
+        crypto: Box<dyn CryptoSystem>,
+        dir: &Path,
+        passphrase: &Box<dyn Buffer>,
+    ) -> Lib3hResult<Self> {
+        let path = dir.join(KEYSTORE_FILE_NAME);
+        let mut keystore = Keystore::new(crypto);
+        let maybe_file = if path.exists() {
+            let file: KeystoreFile = serde_json::from_slice(&std::fs::read(&path)?)
+                .map_err(|e| Lib3hError::from(format!("bad keystore {:?}: {:?}", path, e)))?;
+            Some(file)
+        } else {
+            None
+        };
+        let salt = match &maybe_file {
+            Some(file) => file.salt.clone(),
+            None => keystore.random_bytes(keystore.crypto.pwhash_salt_bytes())?,
+        };
+        let seal_key = keystore.derive_seal_key(passphrase, &salt)?;
+        if let Some(file) = maybe_file {
+            for sealed in file.keys {
+                let secret_key = keystore.unseal(&seal_key, &sealed)?;
+                keystore.keys.push(Keypair {
+                    info: KeyInfo {
+                        id: sealed.id,
+                        key_type: sealed.key_type,
+                        owner: sealed.owner,
+                        public_key: sealed.public_key,
+                    },
+                    secret_key,
+                });
+            }
+        }
+        keystore.storage = Some(KeystoreStorage {
+            path,
+            salt,
+            seal_key,
+        });
+        keystore.persist()?;
+        Ok(keystore)
+    }
+
+    /// The node signing keypair, generated on first use
+    pub fn transport_keys(&mut self) -> Lib3hResult<TransportKeys> {
+        let maybe_index = self.keys.iter().position(|keypair| {
+            keypair.info.key_type == KeyType::Sign && keypair.info.owner == KeyOwner::Node
+        });
+        let index = match maybe_index {
+            Some(index) => index,
+            None => {
+                self.generate_key(KeyType::Sign, KeyOwner::Node)?;
+                self.keys.len() - 1
+            }
+        };
+        let keypair = &self.keys[index];
+        Ok(TransportKeys {
+            node_id: keypair.info.id.as_str().into(),
+            transport_public_key: Box::new(keypair.info.public_key.clone()),
+            transport_secret_key: keypair.secret_key.box_clone(),
+        })
+    }
+
+    pub fn generate_key(&mut self, key_type: KeyType, owner: KeyOwner) -> Lib3hResult<KeyInfo> {
+        let (mut public_key, mut secret_key): (Box<dyn Buffer>, _) = match key_type {
+            KeyType::Sign => (
+                Box::new(vec![0; self.crypto.sign_public_key_bytes()]),
+                self.crypto
+                    .buf_new_secure(self.crypto.sign_secret_key_bytes()),
+            ),
+            KeyType::Kx => (
+                Box::new(vec![0; self.crypto.kx_public_key_bytes()]),
+                self.crypto
+                    .buf_new_secure(self.crypto.kx_secret_key_bytes()),
+            ),
+        };
+        match key_type {
+            KeyType::Sign => self.crypto.sign_keypair(&mut public_key, &mut secret_key)?,
+            KeyType::Kx => self.crypto.kx_keypair(&mut public_key, &mut secret_key)?,
+        }
+        let kind = match owner {
+            KeyOwner::Node => "hcm0",
+            KeyOwner::Agent => "hcs0",
+        };
+        let info = KeyInfo {
+            id: hcid::HcidEncoding::with_kind(kind)?.encode(&public_key)?,
+            key_type,
+            owner,
+            public_key: public_key.to_vec(),
+        };
+        self.keys.push(Keypair {
+            info: info.clone(),
+            secret_key,
+        });
+        self.persist()?;
+        Ok(info)
+    }
+
+    pub fn sign(&self, id: &str, payload: &[u8]) -> Lib3hResult<Vec<u8>> {
+        let keypair = self.keypair(id, KeyType::Sign)?;
+        let payload: Box<dyn Buffer> = Box::new(payload.to_vec());
+        let mut signature: Box<dyn Buffer> = Box::new(vec![0; self.crypto.sign_bytes()]);
+        self.crypto
+            .sign(&mut signature, &payload, &keypair.secret_key)?;
+        Ok(signature.to_vec())
+    }
+
+    pub fn verify(&self, public_key: &[u8], payload: &[u8], signature: &[u8]) -> bool {
+        if signature.len() != self.crypto.sign_bytes()
+            || public_key.len() != self.crypto.sign_public_key_bytes()
+        {
+            return false;
+        }
+        let public_key: Box<dyn Buffer> = Box::new(public_key.to_vec());
+        let payload: Box<dyn Buffer> = Box::new(payload.to_vec());
+        let signature: Box<dyn Buffer> = Box::new(signature.to_vec());
+        self.crypto
+            .sign_verify(&signature, &payload, &public_key)
+            .unwrap_or(false)
+    }
+
+    /// Session keys (rx, tx) between our key exchange keypair `id`
+    /// and `remote_public_key`
+    pub fn derive_kx(
+        &self,
+        id: &str,
+        remote_public_key: &[u8],
+        is_client: bool,
+    ) -> Lib3hResult<(Box<dyn Buffer>, Box<dyn Buffer>)> {
+        let keypair = self.keypair(id, KeyType::Kx)?;
+        if remote_public_key.len() != self.crypto.kx_public_key_bytes() {
+            return Err(Lib3hError::from("bad remote key exchange public key"));
         }
+        let public_key: Box<dyn Buffer> = Box::new(keypair.info.public_key.clone());
+        let remote_public_key: Box<dyn Buffer> = Box::new(remote_public_key.to_vec());
+        let mut rx = self
+            .crypto
+            .buf_new_secure(self.crypto.kx_session_key_bytes());
+        let mut tx = self
+            .crypto
+            .buf_new_secure(self.crypto.kx_session_key_bytes());
+        if is_client {
+            self.crypto.kx_client_session_keys(
+                &mut rx,
+                &mut tx,
+                &public_key,
+                &keypair.secret_key,
+                &remote_public_key,
+            )?;
+        } else {
+            self.crypto.kx_server_session_keys(
+                &mut rx,
+                &mut tx,
+                &public_key,
+                &keypair.secret_key,
+                &remote_public_key,
+            )?;
+        }
+        Ok((rx, tx))
+    }
+
+    pub fn list_keys(&self) -> Vec<KeyInfo> {
+        self.keys
+            .iter()
+            .map(|keypair| keypair.info.clone())
+            .collect()
+    }
+
+    fn keypair(&self, id: &str, key_type: KeyType) -> Lib3hResult<&Keypair> {
+        self.keys
+            .iter()
+            .find(|keypair| keypair.info.id == id && keypair.info.key_type == key_type)
+            .ok_or_else(|| Lib3hError::from(format!("no {:?} key {}", key_type, id)))
+    }
+
+    fn random_bytes(&self, size: usize) -> Lib3hResult<Vec<u8>> {
+        let mut bytes: Box<dyn Buffer> = Box::new(vec![0; size]);
+        self.crypto.randombytes_buf(&mut bytes)?;
+        Ok(bytes.to_vec())
+    }
+
+    #[allow(clippy::borrowed_box)]
+    fn derive_seal_key(
+        &self,
+        passphrase: &Box<dyn Buffer>,
+        salt: &[u8],
+    ) -> Lib3hResult<Box<dyn Buffer>> {
+        if self.crypto.pwhash_bytes() != self.crypto.aead_secret_bytes() {
+            return Err(Lib3hError::from(
+                "this crypto system cannot derive an aead key from a passphrase",
+            ));
+        }
+        let salt: Box<dyn Buffer> = Box::new(salt.to_vec());
+        let mut seal_key = self.crypto.buf_new_secure(self.crypto.pwhash_bytes());
+        self.crypto.pwhash(&mut seal_key, passphrase, &salt)?;
+        Ok(seal_key)
+    }
+
+    #[allow(clippy::borrowed_box)]
+    fn seal(&self, seal_key: &Box<dyn Buffer>, keypair: &Keypair) -> Lib3hResult<SealedKeypair> {
+        let nonce: Box<dyn Buffer> = Box::new(self.random_bytes(self.crypto.aead_nonce_bytes())?);
+        // the public part is authenticated along with the secret key
+        let adata: Box<dyn Buffer> = Box::new(keypair.info.public_key.clone());
+        let mut cipher: Box<dyn Buffer> = Box::new(vec![
+            0;
+            keypair.secret_key.len()
+                + self.crypto.aead_auth_bytes()
+        ]);
+        self.crypto.aead_encrypt(
+            &mut cipher,
+            &keypair.secret_key,
+            Some(&adata),
+            &nonce,
+            seal_key,
+        )?;
+        Ok(SealedKeypair {
+            id: keypair.info.id.clone(),
+            key_type: keypair.info.key_type,
+            owner: keypair.info.owner,
+            public_key: keypair.info.public_key.clone(),
+            nonce: nonce.to_vec(),
+            sealed_secret_key: cipher.to_vec(),
+        })
+    }
+
+    #[allow(clippy::borrowed_box)]
+    fn unseal(
+        &self,
+        seal_key: &Box<dyn Buffer>,
+        sealed: &SealedKeypair,
+    ) -> Lib3hResult<Box<dyn Buffer>> {
+        if sealed.sealed_secret_key.len() < self.crypto.aead_auth_bytes()
+            || sealed.nonce.len() != self.crypto.aead_nonce_bytes()
+        {
+            return Err(Lib3hError::from(format!("bad sealed key {}", sealed.id)));
+        }
+        let nonce: Box<dyn Buffer> = Box::new(sealed.nonce.clone());
+        let adata: Box<dyn Buffer> = Box::new(sealed.public_key.clone());
+        let cipher: Box<dyn Buffer> = Box::new(sealed.sealed_secret_key.clone());
+        let mut secret_key = self
+            .crypto
+            .buf_new_secure(cipher.len() - self.crypto.aead_auth_bytes());
+        self.crypto
+            .aead_decrypt(&mut secret_key, &cipher, Some(&adata), &nonce, seal_key)
+            .map_err(|_| {
+                Lib3hError::from(format!(
+                    "cannot unseal key {}: wrong passphrase or corrupt keystore",
+                    sealed.id
+                ))
+            })?;
+        Ok(secret_key)
+    }
+
+    /// Write all our keys to storage, if we have one
+    fn persist(&self) -> Lib3hResult<()> {
+        let storage = match &self.storage {
+            Some(storage) => storage,
+            None => return Ok(()),
+        };
+        let mut keys = Vec::new();
+        for keypair in self.keys.iter() {
+            keys.push(self.seal(&storage.seal_key, keypair)?);
+        }
+        let file = KeystoreFile {
+            salt: storage.salt.clone(),
+            keys,
+        };
+        if let Some(dir) = storage.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // never leave a half written keystore behind
+        let tmp_path = storage.path.with_extension("json.tmp");
+        std::fs::write(
+            &tmp_path,
+            serde_json::to_vec_pretty(&file).map_err(|e| Lib3hError::from(format!("{:?}", e)))?,
+        )?;
+        std::fs::rename(&tmp_path, &storage.path)?;
+        Ok(())
     }
 
-    fn handle_sign(
-        &mut self,
-        msg: KeystoreMessageFromParent,
-        _id: String,
-        _payload: Vec<u8>,
-    ) -> Lib3hResult<()> {
-        // THIS IS A STUB, just responding with empty signature
-        msg.respond(Ok(RequestToChildResponse::Sign {
-            signature: b"".to_vec(),
-        }))?;
+    fn handle_msg_from_parent(&mut self, mut msg: KeystoreMessageFromParent) -> Lib3hResult<()> {
+        let result = match msg.take_message().expect("exists") {
+            RequestToChild::GenerateKey { key_type, owner } => self
+                .generate_key(key_type, owner)
+                .map(RequestToChildResponse::GenerateKey),
+            RequestToChild::Sign { id, payload } => self
+                .sign(&id, &payload)
+                .map(|signature| RequestToChildResponse::Sign { signature }),
+            RequestToChild::Verify {
+                public_key,
+                payload,
+                signature,
+            } => Ok(RequestToChildResponse::Verify {
+                is_valid: self.verify(&public_key, &payload, &signature),
+            }),
+            RequestToChild::DeriveKx {
+                id,
+                remote_public_key,
+                is_client,
+            } => self
+                .derive_kx(&id, &remote_public_key, is_client)
+                .map(|(rx, tx)| RequestToChildResponse::DeriveKx { rx, tx }),
+            RequestToChild::ListKeys => Ok(RequestToChildResponse::ListKeys(self.list_keys())),
+        };
+        msg.respond(result)?;
         Ok(())
     }
 }
@@ -120,17 +536,136 @@ impl
         RequestToChild,
         RequestToChildResponse,
         Lib3hError,
-    > for KeystoreStub
+    > for Keystore
 {
     fn take_parent_endpoint(&mut self) -> Option<KeystoreParentEndpoint> {
         std::mem::replace(&mut self.endpoint_parent, None)
     }
 
     fn process_concrete(&mut self) -> GhostResult<WorkWasDone> {
-        detach_run!(&mut self.endpoint_self, |es| es.process(self))?;
+        let mut did_work = detach_run!(&mut self.endpoint_self, |es| es.process(self))?;
         for msg in self.endpoint_self.as_mut().drain_messages() {
+            did_work = true.into();
             self.handle_msg_from_parent(msg).expect("no ghost errors");
         }
-        Ok(false.into())
+        Ok(did_work)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_crypto() -> Box<dyn CryptoSystem> {
+        Box::new(lib3h_sodium::SodiumCryptoSystem::new().set_pwhash_interactive())
+    }
+
+    fn passphrase(passphrase: &str) -> Box<dyn Buffer> {
+        Box::new(passphrase.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn it_should_sign_and_verify() {
+        let mut keystore = Keystore::new(test_crypto());
+        let node_key = keystore
+            .generate_key(KeyType::Sign, KeyOwner::Node)
+            .unwrap();
+        let agent_key = keystore
+            .generate_key(KeyType::Sign, KeyOwner::Agent)
+            .unwrap();
+        assert!(node_key.id.starts_with("HcM"));
+        assert!(agent_key.id.starts_with("HcS"));
+        let signature = keystore.sign(&agent_key.id, b"hello").unwrap();
+        assert!(keystore.verify(&agent_key.public_key, b"hello", &signature));
+        assert!(!keystore.verify(&agent_key.public_key, b"hellO", &signature));
+        assert!(!keystore.verify(&node_key.public_key, b"hello", &signature));
+        assert!(keystore.sign("HcSunknown", b"hello").is_err());
+    }
+
+    #[test]
+    fn it_should_derive_matching_session_keys() {
+        let mut keystore = Keystore::new(test_crypto());
+        let client = keystore.generate_key(KeyType::Kx, KeyOwner::Node).unwrap();
+        let server = keystore.generate_key(KeyType::Kx, KeyOwner::Agent).unwrap();
+        let (c_rx, c_tx) = keystore
+            .derive_kx(&client.id, &server.public_key, true)
+            .unwrap();
+        let (s_rx, s_tx) = keystore
+            .derive_kx(&server.id, &client.public_key, false)
+            .unwrap();
+        assert_eq!(c_rx.read_lock().to_vec(), s_tx.read_lock().to_vec());
+        assert_eq!(c_tx.read_lock().to_vec(), s_rx.read_lock().to_vec());
+        // kx keys cannot sign
+        assert!(keystore.sign(&client.id, b"hello").is_err());
+    }
+
+    #[test]
+    fn it_should_persist_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let (node_id, agent_key) = {
+            let mut keystore =
+                Keystore::open(test_crypto(), dir.path(), &passphrase("secret")).unwrap();
+            let agent_key = keystore.generate_key(KeyType::Kx, KeyOwner::Agent).unwrap();
+            (keystore.transport_keys().unwrap().node_id, agent_key)
+        };
+        let mut keystore =
+            Keystore::open(test_crypto(), dir.path(), &passphrase("secret")).unwrap();
+        assert_eq!(node_id, keystore.transport_keys().unwrap().node_id);
+        assert_eq!(2, keystore.list_keys().len());
+        assert!(keystore.list_keys().contains(&agent_key));
+        // the secret keys were restored too
+        let transport_keys = keystore.transport_keys().unwrap();
+        let signature = keystore.sign(&node_id.to_string(), b"hello").unwrap();
+        assert!(keystore.verify(&transport_keys.transport_public_key, b"hello", &signature));
+
+        assert!(Keystore::open(test_crypto(), dir.path(), &passphrase("wrong")).is_err());
+    }
+
+    #[test]
+    fn it_should_serve_requests() {
+        let mut keystore: KeystoreActorParentWrapper<Vec<KeyInfo>> =
+            GhostParentWrapper::new(Keystore::new(test_crypto()), "test");
+        let mut keys = Vec::new();
+        for key_type in &[KeyType::Sign, KeyType::Kx] {
+            keystore
+                .request(
+                    holochain_tracing::test_span(),
+                    RequestToChild::GenerateKey {
+                        key_type: *key_type,
+                        owner: KeyOwner::Node,
+                    },
+                    Box::new(|_keys: &mut Vec<KeyInfo>, resp| {
+                        match resp {
+                            GhostCallbackData::Response(Ok(
+                                RequestToChildResponse::GenerateKey(_),
+                            )) => (),
+                            _ => panic!("bad response: {:?}", resp),
+                        }
+                        Ok(())
+                    }),
+                )
+                .unwrap();
+        }
+        keystore.process(&mut keys).unwrap();
+        keystore
+            .request(
+                holochain_tracing::test_span(),
+                RequestToChild::ListKeys,
+                Box::new(|keys: &mut Vec<KeyInfo>, resp| {
+                    match resp {
+                        GhostCallbackData::Response(Ok(RequestToChildResponse::ListKeys(list))) => {
+                            keys.extend(list)
+                        }
+                        _ => panic!("bad response: {:?}", resp),
+                    }
+                    Ok(())
+                }),
+            )
+            .unwrap();
+        keystore.process(&mut keys).unwrap();
+        assert_eq!(
+            vec![KeyType::Sign, KeyType::Kx],
+            keys.iter().map(|key| key.key_type).collect::<Vec<_>>()
+        );
     }
 }
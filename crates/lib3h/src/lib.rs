@@ -28,6 +28,7 @@ extern crate url2;
 // -- mod -- //
 
 pub mod dht;
+pub mod discovery;
 pub mod engine;
 pub mod error;
 pub mod gateway;
@@ -0,0 +1,140 @@
+//! Payload compression agreed on in the transit handshake.
+//!
+//! Once a session agreed on a compression, every payload starts with a byte
+//! telling whether the rest is compressed. Payloads below the threshold, or
+//! that compression would not shrink, are sent as they are. Sessions that
+//! did not agree on a compression send payloads without that byte.
+
+use crate::error::Lib3hResult;
+use flate2::{read::DeflateDecoder, write::DeflateEncoder};
+use lib3h_p2p_protocol::transit_encoding::CompressionType;
+use lib3h_protocol::data_types::CompressionStatsData;
+use std::io::{Read, Write};
+
+const FLAG_RAW: u8 = 0;
+const FLAG_DEFLATE: u8 = 1;
+
+/// we refuse to inflate a payload any larger than this
+const MAX_INFLATED_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Statistics for a session that agreed on `compression`
+pub(crate) fn new_stats(compression: CompressionType) -> CompressionStatsData {
+    CompressionStatsData {
+        compression: format!("{:?}", compression),
+        uncompressed_bytes: 0,
+        compressed_bytes: 0,
+        compressed_messages: 0,
+    }
+}
+
+fn record(stats: &mut CompressionStatsData, payload: &[u8], wire: &[u8], compressed: bool) {
+    stats.uncompressed_bytes += payload.len() as u64;
+    stats.compressed_bytes += wire.len() as u64;
+    if compressed {
+        stats.compressed_messages += 1;
+    }
+}
+
+/// Compress `payload` if it is at least `threshold` bytes,
+/// accounting for it in `stats`
+pub(crate) fn compress(
+    compression: CompressionType,
+    threshold: usize,
+    payload: Vec<u8>,
+    stats: &mut CompressionStatsData,
+) -> Lib3hResult<Vec<u8>> {
+    if compression == CompressionType::None {
+        record(stats, &payload, &payload, false);
+        return Ok(payload);
+    }
+    if payload.len() >= threshold {
+        let mut encoder = DeflateEncoder::new(vec![FLAG_DEFLATE], flate2::Compression::default());
+        encoder.write_all(&payload)?;
+        let wire = encoder.finish()?;
+        if wire.len() <= payload.len() {
+            record(stats, &payload, &wire, true);
+            return Ok(wire);
+        }
+    }
+    let mut wire = Vec::with_capacity(payload.len() + 1);
+    wire.push(FLAG_RAW);
+    wire.extend_from_slice(&payload);
+    record(stats, &payload, &wire, false);
+    Ok(wire)
+}
+
+/// Undo `compress` on a payload received from the remote,
+/// accounting for it in `stats`
+pub(crate) fn decompress(
+    compression: CompressionType,
+    wire: Vec<u8>,
+    stats: &mut CompressionStatsData,
+) -> Lib3hResult<Vec<u8>> {
+    if compression == CompressionType::None {
+        record(stats, &wire, &wire, false);
+        return Ok(wire);
+    }
+    let payload = match wire.split_first() {
+        Some((&FLAG_RAW, payload)) => payload.to_vec(),
+        Some((&FLAG_DEFLATE, deflated)) => {
+            let mut payload = Vec::new();
+            DeflateDecoder::new(deflated)
+                .take(MAX_INFLATED_BYTES + 1)
+                .read_to_end(&mut payload)?;
+            if payload.len() as u64 > MAX_INFLATED_BYTES {
+                return Err("compressed payload inflates beyond the limit".into());
+            }
+            payload
+        }
+        _ => return Err("unexpected compressed payload framing".into()),
+    };
+    record(stats, &payload, &wire, wire[0] == FLAG_DEFLATE);
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(compression: CompressionType, payload: &[u8]) -> (Vec<u8>, CompressionStatsData) {
+        let mut stats = new_stats(compression);
+        let wire = compress(compression, 64, payload.to_vec(), &mut stats).unwrap();
+        assert_eq!(
+            payload.to_vec(),
+            decompress(compression, wire.clone(), &mut new_stats(compression)).unwrap()
+        );
+        (wire, stats)
+    }
+
+    #[test]
+    fn it_should_compress_above_the_threshold() {
+        let payload = vec![42; 4096];
+        let (wire, stats) = round_trip(CompressionType::Deflate, &payload);
+        assert!(wire.len() < 100);
+        assert_eq!(1, stats.compressed_messages);
+        assert_eq!(4096 - wire.len() as i64, stats.bytes_saved());
+    }
+
+    #[test]
+    fn it_should_send_small_or_incompressible_payloads_as_they_are() {
+        let (wire, stats) = round_trip(CompressionType::Deflate, b"small");
+        assert_eq!(b"\0small".to_vec(), wire);
+        assert_eq!(0, stats.compressed_messages);
+        assert_eq!(-1, stats.bytes_saved());
+
+        // deflate cannot shrink random bytes
+        let payload: Vec<u8> = (0..256).map(|_| rand::random::<u8>()).collect();
+        let (wire, _) = round_trip(CompressionType::Deflate, &payload);
+        assert_eq!(FLAG_RAW, wire[0]);
+
+        let (wire, _) = round_trip(CompressionType::None, &[42; 4096]);
+        assert_eq!(vec![42; 4096], wire);
+    }
+
+    #[test]
+    fn it_should_reject_bad_framing() {
+        let mut stats = new_stats(CompressionType::Deflate);
+        assert!(decompress(CompressionType::Deflate, vec![], &mut stats).is_err());
+        assert!(decompress(CompressionType::Deflate, vec![7, 1, 2], &mut stats).is_err());
+    }
+}
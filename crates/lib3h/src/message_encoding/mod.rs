@@ -0,0 +1,634 @@
+//! utility actor for encoding / decoding messages
+//!
+//! Messages between nodes are sent through a per-connection transit
+//! encoding session (see `transit_session`), compressing their payloads
+//! if both nodes agreed to (see `compression`). Messages nested inside
+//! another gateway's payload are wrapped in an open json envelope.
+
+mod compression;
+mod transit_session;
+
+pub use transit_session::TransitIdentity;
+pub(crate) use transit_session::HANDSHAKE_TIMEOUT_MS;
+
+use crate::error::{Lib3hError, Lib3hResult};
+use detach::prelude::*;
+use lib3h_ghost_actor::prelude::*;
+use lib3h_p2p_protocol::transit_encoding::{CompressionType, Halt, TransitEncodingType};
+use lib3h_protocol::{
+    data_types::{CompressionStatsData, Opaque},
+    types::NodePubKey,
+    uri::Lib3hUri,
+};
+use std::collections::HashMap;
+use transit_session::{TransitEvent, TransitSession};
+
+/// open json envelope for payloads nested in another gateway's payload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum InterimEncodingProtocol {
+    Payload { payload: Opaque },
+}
+
+impl InterimEncodingProtocol {
+    fn to_opaque(&self) -> Opaque {
+        serde_json::to_vec(self).unwrap().into()
+    }
+
+    fn from_slice(v: &[u8]) -> Lib3hResult<Self> {
+        serde_json::from_slice(v).map_err(|e| {
+            format!(
+                "failed to decode {:?} - {:?}",
+                String::from_utf8_lossy(v),
+                e
+            )
+            .into()
+        })
+    }
+}
+
+/// Encode `payload` the way our `EncodePayload` request does.
+pub(crate) fn encode_payload(payload: Opaque) -> Opaque {
+    InterimEncodingProtocol::Payload { payload }.to_opaque()
+}
+
+/// Apply `f` to the payload carried by an already encoded message.
+/// Gateways nest encoded messages inside one another, so converting a
+/// message between wire formats has to reach through this envelope.
+pub(crate) fn map_encoded_payload(
+    encoded: Opaque,
+    f: impl FnOnce(Opaque) -> Lib3hResult<Opaque>,
+) -> Lib3hResult<Opaque> {
+    match serde_json::from_slice(&encoded) {
+        Ok(InterimEncodingProtocol::Payload { payload }) => Ok(encode_payload(f(payload)?)),
+        Err(e) => Err(format!("failed to decode {:?}", e).into()),
+    }
+}
+
+pub mod encoding_protocol {
+    use super::*;
+
+    #[derive(Debug)]
+    pub enum RequestToChild {
+        /// decode data received from `uri`
+        Decode { uri: Lib3hUri, payload: Opaque },
+        /// wrap a payload in the open json envelope
+        EncodePayload { payload: Opaque },
+        /// encode a payload for the transit session with `uri`,
+        /// starting a handshake if there is none yet
+        EncodeTransitPayload { uri: Lib3hUri, payload: Opaque },
+        /// forget the transit session with `uri` (publish only)
+        CloseSession { uri: Lib3hUri },
+        /// what compression saved on each transit session
+        QueryCompressionStats,
+    }
+
+    #[derive(Debug)]
+    pub enum DecodeData {
+        /// a transit handshake is in progress,
+        /// send `response` back to the remote if set
+        Handshake {
+            response: Option<Opaque>,
+        },
+        /// the remote authenticated as `remote_node_id` and we agreed
+        /// on `encoding` and `compression`, send `response` back to the
+        /// remote if set
+        SessionEstablished {
+            remote_node_id: NodePubKey,
+            encoding: TransitEncodingType,
+            compression: CompressionType,
+            response: Option<Opaque>,
+        },
+        /// the transit handshake failed,
+        /// send `response` back to the remote if set
+        Halted {
+            halt: Halt,
+            response: Option<Opaque>,
+        },
+        Payload {
+            payload: Opaque,
+        },
+    }
+
+    #[derive(Debug)]
+    pub enum RequestToChildResponse {
+        DecodeResult {
+            result: DecodeData,
+        },
+        EncodePayloadResult {
+            payload: Opaque,
+        },
+        /// the transit session is not established yet, send `payload`
+        /// (the start of a handshake) to the remote if set, then try again later
+        HandshakePending {
+            payload: Option<Opaque>,
+        },
+        /// keyed by low-level uri
+        CompressionStats(HashMap<Lib3hUri, CompressionStatsData>),
+    }
+
+    #[derive(Debug)]
+    pub enum RequestToParent {}
+
+    #[derive(Debug)]
+    pub enum RequestToParentResponse {}
+}
+
+use encoding_protocol::*;
+
+pub type MessageEncodingActorParentEndpoint = GhostEndpoint<
+    RequestToChild,
+    RequestToChildResponse,
+    RequestToParent,
+    RequestToParentResponse,
+    Lib3hError,
+>;
+
+pub type MessageEncodingActorParentWrapper<T> = GhostParentWrapper<
+    T,
+    RequestToParent,
+    RequestToParentResponse,
+    RequestToChild,
+    RequestToChildResponse,
+    Lib3hError,
+    MessageEncoding,
+>;
+
+type MessageEncodingParentEndpoint = GhostEndpoint<
+    RequestToChild,
+    RequestToChildResponse,
+    RequestToParent,
+    RequestToParentResponse,
+    Lib3hError,
+>;
+
+type MessageEncodingSelfEndpoint = GhostContextEndpoint<
+    MessageEncoding,
+    RequestToParent,
+    RequestToParentResponse,
+    RequestToChild,
+    RequestToChildResponse,
+    Lib3hError,
+>;
+
+type MessageEncodingMessageFromParent =
+    GhostMessage<RequestToChild, RequestToParent, RequestToChildResponse, Lib3hError>;
+
+pub struct MessageEncoding {
+    endpoint_parent: Option<MessageEncodingParentEndpoint>,
+    endpoint_self: Detach<MessageEncodingSelfEndpoint>,
+    /// without an identity, transit payloads fall back to the open envelope
+    transit_identity: Option<TransitIdentity>,
+    /// transit encoding sessions, keyed by low-level uri
+    transit_sessions: HashMap<Lib3hUri, TransitSession>,
+    /// what compression saved on each transit session
+    compression_stats: HashMap<Lib3hUri, CompressionStatsData>,
+}
+
+impl MessageEncoding {
+    pub fn new() -> Self {
+        let (endpoint_parent, endpoint_self) = create_ghost_channel();
+        let endpoint_parent = Some(endpoint_parent);
+        let endpoint_self = Detach::new(
+            endpoint_self
+                .as_context_endpoint_builder()
+                .request_id_prefix("encoding_to_parent_")
+                .build(),
+        );
+        Self {
+            endpoint_parent,
+            endpoint_self,
+            transit_identity: None,
+            transit_sessions: HashMap::new(),
+            compression_stats: HashMap::new(),
+        }
+    }
+
+    /// A MessageEncoding authenticating and encrypting transit payloads
+    pub fn with_transit_identity(transit_identity: TransitIdentity) -> Self {
+        let mut encoding = Self::new();
+        encoding.transit_identity = Some(transit_identity);
+        encoding
+    }
+
+    fn handle_msg_from_parent(
+        &mut self,
+        mut msg: MessageEncodingMessageFromParent,
+    ) -> Lib3hResult<()> {
+        match msg.take_message().expect("exists") {
+            RequestToChild::Decode { uri, payload } => self.handle_decode(msg, uri, payload),
+            RequestToChild::EncodePayload { payload } => self.handle_encode_payload(msg, payload),
+            RequestToChild::EncodeTransitPayload { uri, payload } => {
+                self.handle_encode_transit_payload(msg, uri, payload)
+            }
+            RequestToChild::CloseSession { uri } => {
+                self.transit_sessions.remove(&uri);
+                self.compression_stats.remove(&uri);
+                Ok(())
+            }
+            RequestToChild::QueryCompressionStats => {
+                msg.respond(Ok(RequestToChildResponse::CompressionStats(
+                    self.compression_stats.clone(),
+                )))?;
+                Ok(())
+            }
+        }
+    }
+
+    fn handle_decode(
+        &mut self,
+        msg: MessageEncodingMessageFromParent,
+        uri: Lib3hUri,
+        payload: Opaque,
+    ) -> Lib3hResult<()> {
+        // nodes without a transit identity send json envelopes,
+        // anything else belongs to the transit session
+        let json = match payload.first() {
+            Some(b'{') => serde_json::from_slice(&payload).ok(),
+            _ => None,
+        };
+        let result = match (json, self.transit_identity.is_some()) {
+            // anyone can send a plaintext frame in the name of the node at `uri`,
+            // so with an identity only authenticated sessions carry payloads
+            (Some(InterimEncodingProtocol::Payload { .. }), true) => {
+                Err(format!("refusing plaintext payload from {}", uri).into())
+            }
+            (Some(InterimEncodingProtocol::Payload { payload }), false) => {
+                Ok(DecodeData::Payload { payload })
+            }
+            (None, true) => self.decode_transit(uri, &payload),
+            (None, false) => InterimEncodingProtocol::from_slice(&payload).map(
+                |InterimEncodingProtocol::Payload { payload }| DecodeData::Payload { payload },
+            ),
+        };
+        msg.respond(result.map(|result| RequestToChildResponse::DecodeResult { result }))?;
+        Ok(())
+    }
+
+    fn decode_transit(&mut self, uri: Lib3hUri, payload: &[u8]) -> Lib3hResult<DecodeData> {
+        let identity = self.transit_identity.as_ref().expect("checked by caller");
+        let session = self
+            .transit_sessions
+            .entry(uri.clone())
+            .or_insert_with(TransitSession::listen);
+        Ok(match session.receive(identity, payload)? {
+            TransitEvent::Handshake { response } => DecodeData::Handshake {
+                response: response.map(Opaque::from),
+            },
+            TransitEvent::Established {
+                remote_sig_pub_key,
+                encoding,
+                compression,
+                response,
+            } => {
+                // keep counting across handshakes with the same remote
+                self.compression_stats
+                    .entry(uri)
+                    .or_insert_with(|| compression::new_stats(compression))
+                    .compression = format!("{:?}", compression);
+                DecodeData::SessionEstablished {
+                    encoding,
+                    compression,
+                    remote_node_id: hcid::HcidEncoding::with_kind("hcm0")?
+                        .encode(&remote_sig_pub_key)?
+                        .as_str()
+                        .into(),
+                    response: response.map(Opaque::from),
+                }
+            }
+            TransitEvent::Halted { halt, response } => DecodeData::Halted {
+                halt,
+                response: response.map(Opaque::from),
+            },
+            TransitEvent::Payload(payload) => {
+                let compression = session.compression();
+                let stats = self
+                    .compression_stats
+                    .entry(uri)
+                    .or_insert_with(|| compression::new_stats(compression));
+                DecodeData::Payload {
+                    payload: compression::decompress(compression, payload, stats)?.into(),
+                }
+            }
+        })
+    }
+
+    fn handle_encode_payload(
+        &mut self,
+        msg: MessageEncodingMessageFromParent,
+        payload: Opaque,
+    ) -> Lib3hResult<()> {
+        let payload = encode_payload(payload);
+        msg.respond(Ok(RequestToChildResponse::EncodePayloadResult { payload }))?;
+        Ok(())
+    }
+
+    fn handle_encode_transit_payload(
+        &mut self,
+        msg: MessageEncodingMessageFromParent,
+        uri: Lib3hUri,
+        payload: Opaque,
+    ) -> Lib3hResult<()> {
+        let identity = match &self.transit_identity {
+            Some(identity) => identity,
+            None => {
+                let payload = encode_payload(payload);
+                msg.respond(Ok(RequestToChildResponse::EncodePayloadResult { payload }))?;
+                return Ok(());
+            }
+        };
+        let now_ms = crate::time::since_epoch_ms();
+        let response = match self.transit_sessions.get_mut(&uri) {
+            Some(session) if session.is_established() => {
+                let compression = session.compression();
+                let stats = self
+                    .compression_stats
+                    .entry(uri)
+                    .or_insert_with(|| compression::new_stats(compression));
+                compression::compress(
+                    compression,
+                    identity.compression_threshold(),
+                    payload.to_vec(),
+                    stats,
+                )
+                .and_then(|payload| session.encode(identity, payload))
+                .map(|payload| RequestToChildResponse::EncodePayloadResult {
+                    payload: payload.into(),
+                })
+            }
+            // the handshake is underway
+            Some(session) if !session.is_stale(now_ms) => {
+                Ok(RequestToChildResponse::HandshakePending { payload: None })
+            }
+            _ => match TransitSession::connect(identity) {
+                Ok((session, step1)) => {
+                    self.transit_sessions.insert(uri, session);
+                    Ok(RequestToChildResponse::HandshakePending {
+                        payload: Some(step1.into()),
+                    })
+                }
+                Err(e) => Err(e),
+            },
+        };
+        msg.respond(response)?;
+        Ok(())
+    }
+}
+
+impl
+    GhostActor<
+        RequestToParent,
+        RequestToParentResponse,
+        RequestToChild,
+        RequestToChildResponse,
+        Lib3hError,
+    > for MessageEncoding
+{
+    fn take_parent_endpoint(&mut self) -> Option<MessageEncodingParentEndpoint> {
+        std::mem::replace(&mut self.endpoint_parent, None)
+    }
+
+    fn process_concrete(&mut self) -> GhostResult<WorkWasDone> {
+        let mut did_work = detach_run!(&mut self.endpoint_self, |es| es.process(self))?;
+
+        for msg in self.endpoint_self.as_mut().drain_messages() {
+            did_work = true.into();
+            self.handle_msg_from_parent(msg).expect("no ghost errors");
+        }
+
+        Ok(did_work)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_transit_encoding() -> MessageEncodingActorParentWrapper<Vec<Opaque>> {
+        let crypto: Box<dyn lib3h_crypto_api::CryptoSystem> =
+            Box::new(lib3h_sodium::SodiumCryptoSystem::new());
+        let transport_keys = crate::engine::TransportKeys::new(&*crypto).unwrap();
+        GhostParentWrapper::new(
+            MessageEncoding::with_transit_identity(TransitIdentity::new(
+                crypto,
+                &transport_keys,
+                &"test-network".into(),
+            )),
+            "test",
+        )
+    }
+
+    /// decode `payload` on `e`, collecting anything to send back
+    /// (or the decoded payload) into `out`
+    fn decode_into(
+        e: &mut MessageEncodingActorParentWrapper<Vec<Opaque>>,
+        out: &mut Vec<Opaque>,
+        uri: &Lib3hUri,
+        payload: Opaque,
+    ) {
+        e.request(
+            holochain_tracing::test_span(),
+            RequestToChild::Decode {
+                uri: uri.clone(),
+                payload,
+            },
+            Box::new(|out: &mut Vec<Opaque>, resp| {
+                match resp {
+                    GhostCallbackData::Response(Ok(RequestToChildResponse::DecodeResult {
+                        result,
+                    })) => match result {
+                        DecodeData::Handshake { response }
+                        | DecodeData::SessionEstablished { response, .. } => out.extend(response),
+                        DecodeData::Payload { payload } => out.push(payload),
+                        DecodeData::Halted { halt, .. } => panic!("halted: {:?}", halt),
+                    },
+                    _ => panic!("bad type: {:?}", resp),
+                }
+                Ok(())
+            }),
+        )
+        .unwrap();
+        e.process(out).unwrap();
+    }
+
+    /// encode `payload` for the transit session of `e` with `uri`,
+    /// collecting what to send (a handshake or the encoded payload) into `out`
+    fn encode_transit_into(
+        e: &mut MessageEncodingActorParentWrapper<Vec<Opaque>>,
+        out: &mut Vec<Opaque>,
+        uri: &Lib3hUri,
+        payload: &[u8],
+    ) {
+        e.request(
+            holochain_tracing::test_span(),
+            RequestToChild::EncodeTransitPayload {
+                uri: uri.clone(),
+                payload: payload.to_vec().into(),
+            },
+            Box::new(|out: &mut Vec<Opaque>, resp| {
+                match resp {
+                    GhostCallbackData::Response(Ok(RequestToChildResponse::HandshakePending {
+                        payload,
+                    })) => out.extend(payload),
+                    GhostCallbackData::Response(Ok(
+                        RequestToChildResponse::EncodePayloadResult { payload },
+                    )) => out.push(payload),
+                    _ => panic!("bad type: {:?}", resp),
+                }
+                Ok(())
+            }),
+        )
+        .unwrap();
+        e.process(out).unwrap();
+    }
+
+    /// run the handshake started by `c` with `l` until both sides are established
+    fn establish_session(
+        c: &mut MessageEncodingActorParentWrapper<Vec<Opaque>>,
+        l: &mut MessageEncodingActorParentWrapper<Vec<Opaque>>,
+        c_uri: &Lib3hUri,
+        l_uri: &Lib3hUri,
+    ) {
+        // the first send starts the handshake
+        let mut to_l = Vec::new();
+        encode_transit_into(c, &mut to_l, l_uri, b"test");
+        assert_eq!(1, to_l.len());
+
+        // relay messages until both sides are established
+        let mut to_c = Vec::new();
+        while !to_l.is_empty() || !to_c.is_empty() {
+            for payload in to_l.drain(..).collect::<Vec<_>>() {
+                decode_into(l, &mut to_c, c_uri, payload);
+            }
+            for payload in to_c.drain(..).collect::<Vec<_>>() {
+                decode_into(c, &mut to_l, l_uri, payload);
+            }
+        }
+    }
+
+    #[test]
+    fn it_should_handshake() {
+        let mut c = test_transit_encoding();
+        let mut l = test_transit_encoding();
+        let c_uri = Lib3hUri::with_memory("c");
+        let l_uri = Lib3hUri::with_memory("l");
+        establish_session(&mut c, &mut l, &c_uri, &l_uri);
+
+        let mut to_l = Vec::new();
+        encode_transit_into(&mut c, &mut to_l, &l_uri, b"test");
+        assert_eq!(1, to_l.len());
+        assert_ne!(b"test".to_vec(), to_l[0].to_vec());
+        let mut received = Vec::new();
+        decode_into(&mut l, &mut received, &c_uri, to_l.remove(0));
+        assert_eq!(vec![Opaque::from(b"test".to_vec())], received);
+    }
+
+    /// decode `payload` on `e`, which must refuse it
+    fn decode_refused(
+        e: &mut MessageEncodingActorParentWrapper<Vec<Opaque>>,
+        uri: &Lib3hUri,
+        payload: Opaque,
+    ) {
+        let mut refused = Vec::new();
+        e.request(
+            holochain_tracing::test_span(),
+            RequestToChild::Decode {
+                uri: uri.clone(),
+                payload,
+            },
+            Box::new(|refused: &mut Vec<Opaque>, resp| {
+                match resp {
+                    GhostCallbackData::Response(Err(e)) => {
+                        refused.push(format!("{:?}", e).into_bytes().into())
+                    }
+                    _ => panic!("plaintext accepted: {:?}", resp),
+                }
+                Ok(())
+            }),
+        )
+        .unwrap();
+        e.process(&mut refused).unwrap();
+        assert_eq!(1, refused.len());
+    }
+
+    #[test]
+    fn it_should_refuse_plaintext_on_transit_session() {
+        let mut c = test_transit_encoding();
+        let mut l = test_transit_encoding();
+        let c_uri = Lib3hUri::with_memory("c");
+        let l_uri = Lib3hUri::with_memory("l");
+        // plaintext is refused before the remote authenticated
+        decode_refused(&mut l, &c_uri, encode_payload(b"hello".to_vec().into()));
+        establish_session(&mut c, &mut l, &c_uri, &l_uri);
+        // and after
+        decode_refused(&mut l, &c_uri, encode_payload(b"spoofed".to_vec().into()));
+
+        // nodes without an identity still talk plaintext
+        let mut e: MessageEncodingActorParentWrapper<Vec<Opaque>> =
+            GhostParentWrapper::new(MessageEncoding::new(), "test");
+        let mut received = Vec::new();
+        decode_into(
+            &mut e,
+            &mut received,
+            &c_uri,
+            encode_payload(b"hello".to_vec().into()),
+        );
+        assert_eq!(vec![Opaque::from(b"hello".to_vec())], received);
+    }
+
+    #[test]
+    fn it_should_payload() {
+        let mut e: MessageEncodingActorParentWrapper<String> =
+            GhostParentWrapper::new(MessageEncoding::new(), "test");
+
+        let mut in_out = "".to_string();
+
+        e.request(
+            holochain_tracing::test_span(),
+            RequestToChild::EncodePayload {
+                payload: b"test".to_vec().into(),
+            },
+            Box::new(|out: &mut String, resp| {
+                out.clear();
+                match resp {
+                    GhostCallbackData::Response(Ok(
+                        RequestToChildResponse::EncodePayloadResult { payload },
+                    )) => {
+                        out.push_str(&String::from_utf8_lossy(&payload));
+                    }
+                    _ => panic!("bad type: {:?}", resp),
+                }
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+        e.process(&mut in_out).unwrap();
+
+        assert_eq!("{\"Payload\":{\"payload\":\"dGVzdA==\"}}", &in_out);
+
+        e.request(
+            holochain_tracing::test_span(),
+            RequestToChild::Decode {
+                uri: Lib3hUri::with_memory("test"),
+                payload: in_out.as_bytes().into(),
+            },
+            Box::new(|out: &mut String, resp| {
+                out.clear();
+                match resp {
+                    GhostCallbackData::Response(Ok(RequestToChildResponse::DecodeResult {
+                        result: DecodeData::Payload { payload },
+                    })) => {
+                        out.push_str(&format!("{:?}", payload));
+                    }
+                    _ => panic!("bad type: {:?}", resp),
+                }
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+        e.process(&mut in_out).unwrap();
+
+        assert_eq!("\"test\"", &in_out);
+    }
+}
@@ -0,0 +1,1668 @@
+//! Per-connection state for the `transit_encoding.capnp` handshake.
+//!
+//! The connecting node (C) and the listening node (L) exchange:
+//!  1. C -> L: magic, encodings, network id and C's kx public key
+//!  2. L -> C: L's kx public key and the picked encoding, then, encrypted
+//!     with the kx session keys, L's signature public key and a random l2c
+//!     session key
+//!  3. C -> L: kx encrypted, C's signature public key, a random c2l session
+//!     key and C's signature of the l2c session key
+//!  4. L -> C: L's signature of the c2l session key, using nonce-0 of the
+//!     l2c session key
+//!  5. C -> L: an empty message using nonce-0 of the c2l session key
+//!
+//! From then on each side sends `EncodedMessage`s with its own session key
+//! and sequential nonces. Messages may be lost or arrive out of order, but
+//! no nonce is accepted twice. A node refusing to proceed sends a `Halt` instead.
+//!
+//! Step 1 lists every encoding the connecting node accepts, in order of
+//! preference, and step 2 names the first of them the listening node accepts
+//! too. Every later message uses that encoding: json or packed capnp, and
+//! with the open encodings nothing is encrypted (the session keys are still
+//! signed, so the remote is authenticated). Both signatures also cover the
+//! offered list and the picked encoding, so a list tampered with on the way
+//! fails the handshake instead of downgrading it.
+//!
+//! Step 3 also lists the payload compressions the connecting node accepts,
+//! and step 4 carries the first of them the listening node accepts too.
+
+use crate::{
+    engine::{CompressionConfig, TransportKeys},
+    error::Lib3hResult,
+};
+use lib3h_crypto_api::{Buffer, CryptoSystem};
+use lib3h_p2p_protocol::transit_encoding::*;
+use lib3h_protocol::Address;
+
+/// handshakes that have not completed after this long are started over
+pub const HANDSHAKE_TIMEOUT_MS: u64 = 20000;
+
+/// an established session answers at most one step 1 this often
+const STEP1_INTERVAL_MS: u64 = 1000;
+
+/// how many consecutive messages may be lost before a session
+/// can no longer be decoded
+const MAX_NONCE_SKIP: u64 = 16;
+
+/// how far behind the latest message another one may arrive
+const REPLAY_WINDOW: u64 = 64;
+
+/// Everything needed to authenticate ourselves to remote nodes
+pub struct TransitIdentity {
+    crypto: Box<dyn CryptoSystem>,
+    sig_pub_key: Box<dyn Buffer>,
+    sig_sec_key: Box<dyn Buffer>,
+    network_id: Vec<u8>,
+    /// encodings we accept, in order of preference
+    encodings: Vec<TransitEncodingType>,
+    /// compressions we accept, in order of preference
+    compressions: Vec<CompressionType>,
+    /// payloads smaller than this are sent uncompressed
+    compression_threshold: usize,
+}
+
+impl TransitIdentity {
+    pub fn new(
+        crypto: Box<dyn CryptoSystem>,
+        transport_keys: &TransportKeys,
+        network_id: &Address,
+    ) -> Self {
+        Self {
+            crypto,
+            sig_pub_key: transport_keys.transport_public_key.box_clone(),
+            sig_sec_key: transport_keys.transport_secret_key.box_clone(),
+            network_id: network_id.to_string().into_bytes(),
+            encodings: vec![TransitEncodingType::SodiumPacked],
+            compressions: Vec::new(),
+            compression_threshold: 0,
+        }
+    }
+
+    /// Only accept `encodings`, offering them in the given order
+    /// when connecting to a remote node
+    pub fn with_encodings(mut self, encodings: Vec<TransitEncodingType>) -> Lib3hResult<Self> {
+        self.encodings = encodings
+            .into_iter()
+            .filter(|encoding| *encoding != TransitEncodingType::Unknown)
+            .collect();
+        if self.encodings.is_empty() {
+            return Err("at least one transit encoding is required".into());
+        }
+        Ok(self)
+    }
+
+    /// Compress payloads as configured with the remote nodes that agree
+    pub fn with_compression(mut self, config: &CompressionConfig) -> Self {
+        self.compressions = config
+            .compressions
+            .iter()
+            .cloned()
+            .filter(|compression| *compression != CompressionType::None)
+            .collect();
+        self.compression_threshold = config.threshold_bytes;
+        self
+    }
+
+    /// payloads smaller than this are not worth compressing
+    pub fn compression_threshold(&self) -> usize {
+        self.compression_threshold
+    }
+
+    fn accepts(&self, encoding: TransitEncodingType) -> bool {
+        encoding != TransitEncodingType::Unknown && self.encodings.contains(&encoding)
+    }
+
+    /// the first of the remote's `encodings` we accept
+    fn pick_encoding(&self, encodings: &[TransitEncodingType]) -> Option<TransitEncodingType> {
+        encodings
+            .iter()
+            .cloned()
+            .find(|encoding| self.accepts(*encoding))
+    }
+
+    /// the first of the remote's `compressions` we accept
+    fn pick_compression(&self, compressions: &[CompressionType]) -> CompressionType {
+        compressions
+            .iter()
+            .cloned()
+            .find(|compression| self.compressions.contains(compression))
+            .unwrap_or(CompressionType::None)
+    }
+
+    fn random_secret(&self, size: usize) -> Lib3hResult<Box<dyn Buffer>> {
+        let mut secret = self.crypto.buf_new_secure(size);
+        self.crypto.randombytes_buf(&mut secret)?;
+        Ok(secret)
+    }
+
+    /// open encodings send an empty kx nonce
+    fn kx_nonce_bytes(&self, encoding: TransitEncodingType) -> usize {
+        if encoding.is_sodium() {
+            self.crypto.aead_nonce_bytes()
+        } else {
+            0
+        }
+    }
+
+    fn random_nonce(&self, encoding: TransitEncodingType) -> Lib3hResult<Box<dyn Buffer>> {
+        let mut nonce: Box<dyn Buffer> = Box::new(vec![0; self.kx_nonce_bytes(encoding)]);
+        if nonce.is_empty() {
+            return Ok(nonce);
+        }
+        self.crypto.randombytes_buf(&mut nonce)?;
+        Ok(nonce)
+    }
+
+    /// sequential nonces are the little-endian message count
+    fn counter_nonce(&self, count: u64) -> Box<dyn Buffer> {
+        let mut nonce = vec![0; self.crypto.aead_nonce_bytes()];
+        nonce[..8].copy_from_slice(&count.to_le_bytes());
+        Box::new(nonce)
+    }
+
+    #[allow(clippy::borrowed_box)]
+    fn seal(
+        &self,
+        encoding: TransitEncodingType,
+        message: Vec<u8>,
+        nonce: &Box<dyn Buffer>,
+        secret: &Box<dyn Buffer>,
+    ) -> Lib3hResult<Vec<u8>> {
+        if !encoding.is_sodium() {
+            return Ok(message);
+        }
+        let message: Box<dyn Buffer> = Box::new(message);
+        let mut cipher: Box<dyn Buffer> =
+            Box::new(vec![0; message.len() + self.crypto.aead_auth_bytes()]);
+        self.crypto
+            .aead_encrypt(&mut cipher, &message, None, nonce, secret)?;
+        Ok(cipher.to_vec())
+    }
+
+    #[allow(clippy::borrowed_box)]
+    fn open(
+        &self,
+        encoding: TransitEncodingType,
+        cipher: &[u8],
+        nonce: &Box<dyn Buffer>,
+        secret: &Box<dyn Buffer>,
+    ) -> Lib3hResult<Vec<u8>> {
+        if !encoding.is_sodium() {
+            return Ok(cipher.to_vec());
+        }
+        if cipher.len() < self.crypto.aead_auth_bytes() {
+            return Err("cipher too short".into());
+        }
+        let cipher: Box<dyn Buffer> = Box::new(cipher.to_vec());
+        let mut message: Box<dyn Buffer> =
+            Box::new(vec![0; cipher.len() - self.crypto.aead_auth_bytes()]);
+        self.crypto
+            .aead_decrypt(&mut message, &cipher, None, nonce, secret)?;
+        Ok(message.to_vec())
+    }
+
+    #[allow(clippy::borrowed_box)]
+    fn sign(&self, message: &Box<dyn Buffer>) -> Lib3hResult<Vec<u8>> {
+        let mut signature: Box<dyn Buffer> = Box::new(vec![0; self.crypto.sign_bytes()]);
+        self.crypto
+            .sign(&mut signature, message, &self.sig_sec_key)?;
+        Ok(signature.to_vec())
+    }
+
+    #[allow(clippy::borrowed_box)]
+    fn verify(&self, signature: &[u8], message: &Box<dyn Buffer>, public_key: &[u8]) -> bool {
+        if signature.len() != self.crypto.sign_bytes() {
+            return false;
+        }
+        let signature: Box<dyn Buffer> = Box::new(signature.to_vec());
+        let public_key: Box<dyn Buffer> = Box::new(public_key.to_vec());
+        self.crypto
+            .sign_verify(&signature, message, &public_key)
+            .unwrap_or(false)
+    }
+
+    fn sig_pub_key(&self) -> Vec<u8> {
+        self.sig_pub_key.read_lock().to_vec()
+    }
+
+    fn secret_from(&self, bytes: &[u8]) -> Lib3hResult<Box<dyn Buffer>> {
+        let mut secret = self.crypto.buf_new_secure(bytes.len());
+        secret.write(0, bytes)?;
+        Ok(secret)
+    }
+}
+
+/// What came out of feeding a message to a `TransitSession`
+#[derive(Debug)]
+pub enum TransitEvent {
+    /// the handshake is progressing, send `response` to the remote if set
+    Handshake { response: Option<Vec<u8>> },
+    /// the remote proved it owns `remote_sig_pub_key`, we can now exchange
+    /// messages with `encoding`, compressing payloads with `compression`.
+    /// Send `response` to the remote if set.
+    Established {
+        remote_sig_pub_key: Vec<u8>,
+        encoding: TransitEncodingType,
+        compression: CompressionType,
+        response: Option<Vec<u8>>,
+    },
+    /// data sent by the remote
+    Payload(Vec<u8>),
+    /// the handshake failed, send `response` to the remote if set
+    Halted {
+        halt: Halt,
+        response: Option<Vec<u8>>,
+    },
+}
+
+/// The nonces received on an established session: `next` is one past
+/// the highest, bit `i` of `seen` is set if `next - 1 - i` was received
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ReplayWindow {
+    next: u64,
+    seen: u64,
+}
+
+impl ReplayWindow {
+    /// nonce 0 was used by the handshake
+    fn new() -> Self {
+        ReplayWindow { next: 1, seen: 1 }
+    }
+
+    /// the nonces a message could have been sent with, most likely first:
+    /// the next ones, then the ones that were skipped
+    fn candidates(&self) -> impl Iterator<Item = u64> {
+        let ReplayWindow { next, seen } = *self;
+        (next..next + MAX_NONCE_SKIP).chain(
+            (0..std::cmp::min(next, REPLAY_WINDOW))
+                .filter(move |i| seen & (1 << i) == 0)
+                .map(move |i| next - 1 - i),
+        )
+    }
+
+    fn accept(&mut self, nonce: u64) {
+        if nonce >= self.next {
+            let shift = nonce - self.next + 1;
+            self.seen = if shift >= REPLAY_WINDOW {
+                0
+            } else {
+                self.seen << shift
+            };
+            self.seen |= 1;
+            self.next = nonce + 1;
+        } else {
+            self.seen |= 1 << (self.next - 1 - nonce);
+        }
+    }
+}
+
+enum SessionState {
+    /// waiting for a remote to connect
+    ListenAwaitStep1,
+    /// we sent step 1, awaiting step 2
+    ConnectSentStep1 {
+        kx_pub_key: Box<dyn Buffer>,
+        kx_sec_key: Box<dyn Buffer>,
+    },
+    /// we sent step 2, awaiting step 3
+    ListenSentStep2 {
+        kx_rx: Box<dyn Buffer>,
+        l2c_session_key: Box<dyn Buffer>,
+    },
+    /// we sent step 3, awaiting step 4
+    ConnectSentStep3 {
+        remote_sig_pub_key: Vec<u8>,
+        l2c_session_key: Box<dyn Buffer>,
+        c2l_session_key: Box<dyn Buffer>,
+    },
+    /// we sent step 4, awaiting step 5
+    ListenSentStep4 {
+        remote_sig_pub_key: Vec<u8>,
+        l2c_session_key: Box<dyn Buffer>,
+        c2l_session_key: Box<dyn Buffer>,
+    },
+    /// the handshake is complete
+    Established {
+        remote_sig_pub_key: Vec<u8>,
+        tx_key: Box<dyn Buffer>,
+        tx_nonce: u64,
+        rx_key: Box<dyn Buffer>,
+        rx_window: ReplayWindow,
+    },
+    /// the handshake failed, nothing more can be exchanged
+    Halted,
+}
+
+/// Transit encoding state for a single connection
+pub struct TransitSession {
+    state: SessionState,
+    started_at_ms: u64,
+    /// the encoding picked in step 2
+    encoding: TransitEncodingType,
+    /// the encodings offered in step 1, signed along with the session keys
+    offered_encodings: Vec<TransitEncodingType>,
+    /// the compression agreed on in steps 3 and 4
+    compression: CompressionType,
+    /// once a remote has authenticated, any later handshake on this
+    /// connection must present the same signature public key
+    pinned_sig_pub_key: Option<Vec<u8>>,
+    /// a handshake the remote started over while we were established,
+    /// we keep our keys until it authenticates the pinned key
+    rehandshake: Option<Box<TransitSession>>,
+    /// when we last answered a step 1 on an established session
+    last_step1_ms: u64,
+}
+
+fn halt_event(reason_code: HaltReasonCode, reason_text: &str) -> TransitEvent {
+    TransitEvent::Halted {
+        halt: Halt::new(reason_code, reason_text),
+        response: None,
+    }
+}
+
+/// like step 1, step 2 is always packed capnp
+fn step2_halt(reason_code: HaltReasonCode, reason_text: &str) -> TransitEvent {
+    let halt = Halt::new(reason_code, reason_text);
+    TransitEvent::Halted {
+        response: Some(MsgStep2FromListen::Halt(halt.clone()).to_bytes()),
+        halt,
+    }
+}
+
+fn step3_halt(
+    encoding: TransitEncodingType,
+    reason_code: HaltReasonCode,
+    reason_text: &str,
+) -> TransitEvent {
+    let halt = Halt::new(reason_code, reason_text);
+    TransitEvent::Halted {
+        response: Some(MsgStep3FromConnect::Halt(halt.clone()).encode_as(encoding)),
+        halt,
+    }
+}
+
+/// after step 3, halts are sent on their own
+fn bare_halt(
+    encoding: TransitEncodingType,
+    reason_code: HaltReasonCode,
+    reason_text: &str,
+) -> TransitEvent {
+    let halt = Halt::new(reason_code, reason_text);
+    TransitEvent::Halted {
+        response: Some(halt.encode_as(encoding)),
+        halt,
+    }
+}
+
+/// a remote that (re)starts a handshake sends a step 1 with our magic,
+/// step 1 is always packed capnp
+fn parse_step1(bytes: &[u8]) -> Option<MsgStep1FromConnect> {
+    match MsgStep1FromConnect::from_bytes(bytes) {
+        Ok(step1) if step1.magic == TRANSIT_ENCODING_MAGIC => Some(step1),
+        _ => None,
+    }
+}
+
+fn encoding_byte(encoding: TransitEncodingType) -> u8 {
+    match encoding {
+        TransitEncodingType::Unknown => 0,
+        TransitEncodingType::OpenJson => 1,
+        TransitEncodingType::OpenPacked => 2,
+        TransitEncodingType::SodiumJson => 3,
+        TransitEncodingType::SodiumPacked => 4,
+    }
+}
+
+/// a message we could not decrypt may be the remote halting
+fn parse_halt(encoding: TransitEncodingType, bytes: &[u8], what: &str) -> TransitEvent {
+    match Halt::decode_as(bytes, encoding) {
+        Ok(halt) => TransitEvent::Halted {
+            halt,
+            response: None,
+        },
+        Err(_) => halt_event(
+            HaltReasonCode::Unspecified,
+            &format!("failed to decode {}", what),
+        ),
+    }
+}
+
+impl TransitSession {
+    /// Begin a handshake as the connecting node.
+    /// Returns the new session and the step 1 message to send.
+    pub fn connect(identity: &TransitIdentity) -> Lib3hResult<(Self, Vec<u8>)> {
+        let crypto = &identity.crypto;
+        let mut kx_pub_key: Box<dyn Buffer> = Box::new(vec![0; crypto.kx_public_key_bytes()]);
+        let mut kx_sec_key = crypto.buf_new_secure(crypto.kx_secret_key_bytes());
+        crypto.kx_keypair(&mut kx_pub_key, &mut kx_sec_key)?;
+        let step1 = MsgStep1FromConnect {
+            magic: TRANSIT_ENCODING_MAGIC,
+            encoding: identity.encodings[0],
+            network_id: identity.network_id.clone(),
+            kx_pub_key: kx_pub_key.to_vec(),
+            encodings: identity.encodings.clone(),
+        };
+        let mut session = Self::listen();
+        session.state = SessionState::ConnectSentStep1 {
+            kx_pub_key,
+            kx_sec_key,
+        };
+        session.offered_encodings = step1.encodings.clone();
+        Ok((session, step1.to_bytes()))
+    }
+
+    /// Wait for a remote node to begin a handshake
+    pub fn listen() -> Self {
+        Self {
+            state: SessionState::ListenAwaitStep1,
+            started_at_ms: crate::time::since_epoch_ms(),
+            encoding: TransitEncodingType::Unknown,
+            offered_encodings: Vec::new(),
+            compression: CompressionType::None,
+            pinned_sig_pub_key: None,
+            rehandshake: None,
+            last_step1_ms: 0,
+        }
+    }
+
+    /// what each side signs: a session key, the encodings offered in step 1
+    /// and the one picked in step 2
+    #[allow(clippy::borrowed_box)]
+    fn transcript(
+        &self,
+        identity: &TransitIdentity,
+        session_key: &Box<dyn Buffer>,
+    ) -> Lib3hResult<Box<dyn Buffer>> {
+        let mut transcript = session_key.read_lock().to_vec();
+        transcript.extend(self.offered_encodings.iter().cloned().map(encoding_byte));
+        transcript.push(encoding_byte(self.encoding));
+        identity.secret_from(&transcript)
+    }
+
+    /// can we exchange encoded messages yet?
+    pub fn is_established(&self) -> bool {
+        match self.state {
+            SessionState::Established { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// has this handshake failed or been pending for too long?
+    pub fn is_stale(&self, now_ms: u64) -> bool {
+        match self.state {
+            SessionState::Established { .. } => false,
+            SessionState::Halted => true,
+            _ => now_ms.saturating_sub(self.started_at_ms) > HANDSHAKE_TIMEOUT_MS,
+        }
+    }
+
+    /// how payloads exchanged with the remote are compressed
+    pub fn compression(&self) -> CompressionType {
+        self.compression
+    }
+
+    /// the signature public key the remote authenticated with
+    pub fn remote_sig_pub_key(&self) -> Option<&[u8]> {
+        match &self.state {
+            SessionState::Established {
+                remote_sig_pub_key, ..
+            } => Some(remote_sig_pub_key),
+            _ => None,
+        }
+    }
+
+    /// encode a payload for the remote, the session must be established
+    pub fn encode(&mut self, identity: &TransitIdentity, payload: Vec<u8>) -> Lib3hResult<Vec<u8>> {
+        match &mut self.state {
+            SessionState::Established {
+                tx_key, tx_nonce, ..
+            } => {
+                let nonce = identity.counter_nonce(*tx_nonce);
+                *tx_nonce += 1;
+                let message = EncodedMessage {
+                    padding: Vec::new(),
+                    content: payload,
+                };
+                identity.seal(
+                    self.encoding,
+                    message.encode_as(self.encoding),
+                    &nonce,
+                    tx_key,
+                )
+            }
+            _ => Err("transit session is not established".into()),
+        }
+    }
+
+    /// feed a message received from the remote through this session
+    pub fn receive(
+        &mut self,
+        identity: &TransitIdentity,
+        bytes: &[u8],
+    ) -> Lib3hResult<TransitEvent> {
+        let state = std::mem::replace(&mut self.state, SessionState::Halted);
+        let (state, event) = match state {
+            SessionState::ListenAwaitStep1 => match parse_step1(bytes) {
+                Some(step1) => self.on_step1(identity, step1)?,
+                None => (
+                    SessionState::Halted,
+                    step2_halt(HaltReasonCode::BadMagic, "expected a handshake"),
+                ),
+            },
+            SessionState::ConnectSentStep1 {
+                kx_pub_key,
+                kx_sec_key,
+            } => match parse_step1(bytes) {
+                // we both connected at once, the lowest kx public key
+                // stays the connecting node
+                Some(ref step1) if kx_pub_key[..] < step1.kx_pub_key[..] => (
+                    SessionState::ConnectSentStep1 {
+                        kx_pub_key,
+                        kx_sec_key,
+                    },
+                    TransitEvent::Handshake { response: None },
+                ),
+                Some(step1) => self.on_step1(identity, step1)?,
+                None => match MsgStep2FromListen::from_bytes(bytes) {
+                    Ok(step2) => self.on_step2(identity, &kx_pub_key, &kx_sec_key, step2)?,
+                    // the remote keeps sealing for its previous session
+                    // until our handshake completes
+                    Err(_) => (
+                        SessionState::ConnectSentStep1 {
+                            kx_pub_key,
+                            kx_sec_key,
+                        },
+                        TransitEvent::Handshake { response: None },
+                    ),
+                },
+            },
+            SessionState::ListenSentStep2 {
+                kx_rx,
+                l2c_session_key,
+            } => match MsgStep3FromConnect::decode_as(bytes, self.encoding) {
+                Ok(step3) => self.on_step3(identity, &kx_rx, l2c_session_key, step3)?,
+                Err(_) => {
+                    let encoding = self.encoding;
+                    self.restart_or(identity, bytes, || {
+                        bare_halt(
+                            encoding,
+                            HaltReasonCode::Unspecified,
+                            "failed to decode step 3",
+                        )
+                    })?
+                }
+            },
+            SessionState::ConnectSentStep3 {
+                remote_sig_pub_key,
+                l2c_session_key,
+                c2l_session_key,
+            } => {
+                let encoding = self.encoding;
+                let step4 = identity
+                    .open(
+                        encoding,
+                        bytes,
+                        &identity.counter_nonce(0),
+                        &l2c_session_key,
+                    )
+                    .and_then(|step4| Ok(MsgStep4FromListenEncoded::decode_as(&step4, encoding)?));
+                match step4 {
+                    Ok(step4) => self.on_step4(
+                        identity,
+                        remote_sig_pub_key,
+                        l2c_session_key,
+                        c2l_session_key,
+                        step4,
+                    )?,
+                    Err(_) => match (parse_step1(bytes), Halt::decode_as(bytes, encoding)) {
+                        (Some(step1), _) => self.on_step1(identity, step1)?,
+                        (None, Ok(halt)) => (
+                            SessionState::Halted,
+                            TransitEvent::Halted {
+                                halt,
+                                response: None,
+                            },
+                        ),
+                        // sealed for the previous session of the remote
+                        (None, Err(_)) => (
+                            SessionState::ConnectSentStep3 {
+                                remote_sig_pub_key,
+                                l2c_session_key,
+                                c2l_session_key,
+                            },
+                            TransitEvent::Handshake { response: None },
+                        ),
+                    },
+                }
+            }
+            SessionState::ListenSentStep4 {
+                remote_sig_pub_key,
+                l2c_session_key,
+                c2l_session_key,
+            } => {
+                let encoding = self.encoding;
+                let step5 = identity
+                    .open(
+                        encoding,
+                        bytes,
+                        &identity.counter_nonce(0),
+                        &c2l_session_key,
+                    )
+                    .and_then(|step5| Ok(MsgStep5FromConnectEncoded::decode_as(&step5, encoding)?));
+                match step5 {
+                    Ok(_) => {
+                        self.pinned_sig_pub_key = Some(remote_sig_pub_key.clone());
+                        (
+                            SessionState::Established {
+                                remote_sig_pub_key: remote_sig_pub_key.clone(),
+                                tx_key: l2c_session_key,
+                                tx_nonce: 1,
+                                rx_key: c2l_session_key,
+                                rx_window: ReplayWindow::new(),
+                            },
+                            TransitEvent::Established {
+                                remote_sig_pub_key,
+                                encoding,
+                                compression: self.compression,
+                                response: None,
+                            },
+                        )
+                    }
+                    Err(_) => {
+                        self.restart_or(identity, bytes, || parse_halt(encoding, bytes, "step 5"))?
+                    }
+                }
+            }
+            SessionState::Established {
+                remote_sig_pub_key,
+                tx_key,
+                tx_nonce,
+                rx_key,
+                mut rx_window,
+            } => {
+                let encoding = self.encoding;
+                let decoded = if encoding.is_sodium() {
+                    // tolerate lost and reordered messages, but never accept a nonce twice
+                    rx_window.candidates().find_map(|n| {
+                        identity
+                            .open(encoding, bytes, &identity.counter_nonce(n), &rx_key)
+                            .ok()
+                            .map(|message| (Some(n), message))
+                    })
+                } else if parse_step1(bytes).is_none() {
+                    Some((None, bytes.to_vec()))
+                } else {
+                    None
+                };
+                let message = decoded.and_then(|(nonce, message)| {
+                    EncodedMessage::decode_as(&message, encoding)
+                        .ok()
+                        .map(|message| (nonce, message))
+                });
+                match message {
+                    Some((nonce, message)) => {
+                        if let Some(nonce) = nonce {
+                            rx_window.accept(nonce);
+                        }
+                        (
+                            SessionState::Established {
+                                remote_sig_pub_key,
+                                tx_key,
+                                tx_nonce,
+                                rx_key,
+                                rx_window,
+                            },
+                            TransitEvent::Payload(message.content),
+                        )
+                    }
+                    None => {
+                        self.state = SessionState::Established {
+                            remote_sig_pub_key,
+                            tx_key,
+                            tx_nonce,
+                            rx_key,
+                            rx_window,
+                        };
+                        return self.receive_rehandshake(identity, bytes);
+                    }
+                }
+            }
+            SessionState::Halted => match parse_step1(bytes) {
+                Some(step1) => self.on_step1(identity, step1)?,
+                None => (
+                    SessionState::Halted,
+                    halt_event(HaltReasonCode::Unspecified, "session was halted"),
+                ),
+            },
+        };
+        self.state = state;
+        Ok(event)
+    }
+
+    /// the remote may have lost its session and be starting over, anyone can
+    /// send a step 1 though: the handshake runs beside our session, which we
+    /// only replace once the remote authenticated with the pinned key
+    fn receive_rehandshake(
+        &mut self,
+        identity: &TransitIdentity,
+        bytes: &[u8],
+    ) -> Lib3hResult<TransitEvent> {
+        let now_ms = crate::time::since_epoch_ms();
+        if parse_step1(bytes).is_some() {
+            if now_ms.saturating_sub(self.last_step1_ms) < STEP1_INTERVAL_MS {
+                return Err("ignoring a step 1, the last one was too recent".into());
+            }
+            self.last_step1_ms = now_ms;
+            let mut rehandshake = Self::listen();
+            rehandshake.pinned_sig_pub_key = self.pinned_sig_pub_key.clone();
+            self.rehandshake = Some(Box::new(rehandshake));
+        }
+        let mut rehandshake = match self.rehandshake.take() {
+            Some(rehandshake) if !rehandshake.is_stale(now_ms) => rehandshake,
+            _ => return Err("failed to decode transit encoded message".into()),
+        };
+        let event = rehandshake.receive(identity, bytes)?;
+        match event {
+            TransitEvent::Established { .. } => {
+                let last_step1_ms = self.last_step1_ms;
+                *self = *rehandshake;
+                self.last_step1_ms = last_step1_ms;
+            }
+            TransitEvent::Halted { .. } => (),
+            _ => self.rehandshake = Some(rehandshake),
+        }
+        Ok(event)
+    }
+
+    /// the remote may have given up on an earlier handshake and started over,
+    /// otherwise halt as described by `f`
+    fn restart_or(
+        &mut self,
+        identity: &TransitIdentity,
+        bytes: &[u8],
+        f: impl FnOnce() -> TransitEvent,
+    ) -> Lib3hResult<(SessionState, TransitEvent)> {
+        match parse_step1(bytes) {
+            Some(step1) => self.on_step1(identity, step1),
+            None => Ok((SessionState::Halted, f())),
+        }
+    }
+
+    /// we are the listening node, validate step 1 and respond with step 2
+    fn on_step1(
+        &mut self,
+        identity: &TransitIdentity,
+        step1: MsgStep1FromConnect,
+    ) -> Lib3hResult<(SessionState, TransitEvent)> {
+        let crypto = &identity.crypto;
+        self.started_at_ms = crate::time::since_epoch_ms();
+        self.compression = CompressionType::None;
+        self.encoding = match identity.pick_encoding(&step1.encodings) {
+            Some(encoding) => encoding,
+            None => {
+                return Ok((
+                    SessionState::Halted,
+                    step2_halt(
+                        HaltReasonCode::BadEncoding,
+                        &format!("no supported encoding in {:?}", step1.encodings),
+                    ),
+                ))
+            }
+        };
+        self.offered_encodings = step1.encodings;
+        let encoding = self.encoding;
+        if step1.network_id != identity.network_id {
+            return Ok((
+                SessionState::Halted,
+                step2_halt(HaltReasonCode::BadNetworkId, "unexpected network id"),
+            ));
+        }
+        if step1.kx_pub_key.len() != crypto.kx_public_key_bytes() {
+            return Ok((
+                SessionState::Halted,
+                step2_halt(HaltReasonCode::KxDecodeFail, "bad kx public key"),
+            ));
+        }
+
+        let mut kx_pub_key: Box<dyn Buffer> = Box::new(vec![0; crypto.kx_public_key_bytes()]);
+        let mut kx_sec_key = crypto.buf_new_secure(crypto.kx_secret_key_bytes());
+        crypto.kx_keypair(&mut kx_pub_key, &mut kx_sec_key)?;
+        let remote_kx_pub_key: Box<dyn Buffer> = Box::new(step1.kx_pub_key);
+        let mut kx_rx = crypto.buf_new_secure(crypto.kx_session_key_bytes());
+        let mut kx_tx = crypto.buf_new_secure(crypto.kx_session_key_bytes());
+        crypto.kx_server_session_keys(
+            &mut kx_rx,
+            &mut kx_tx,
+            &kx_pub_key,
+            &kx_sec_key,
+            &remote_kx_pub_key,
+        )?;
+
+        let l2c_session_key = identity.random_secret(crypto.aead_secret_bytes())?;
+        let kx_encoded = MsgStep2FromListenKxEncoded {
+            padding: Vec::new(),
+            sig_pub_key: identity.sig_pub_key(),
+            l2c_session_key: l2c_session_key.read_lock().to_vec(),
+        };
+        let kx_nonce = identity.random_nonce(encoding)?;
+        let kx_secret =
+            identity.seal(encoding, kx_encoded.encode_as(encoding), &kx_nonce, &kx_tx)?;
+        let step2 = MsgStep2FromListen::Continue(MsgStep2Continue {
+            kx_pub_key: kx_pub_key.to_vec(),
+            kx_nonce: kx_nonce.to_vec(),
+            kx_secret,
+            encoding,
+        });
+        Ok((
+            SessionState::ListenSentStep2 {
+                kx_rx,
+                l2c_session_key,
+            },
+            TransitEvent::Handshake {
+                response: Some(step2.to_bytes()),
+            },
+        ))
+    }
+
+    /// is this the signature public key we expect from the remote?
+    fn check_sig_pub_key(&self, identity: &TransitIdentity, sig_pub_key: &[u8]) -> bool {
+        if sig_pub_key.len() != identity.crypto.sign_public_key_bytes() {
+            return false;
+        }
+        match &self.pinned_sig_pub_key {
+            Some(pinned) => pinned[..] == sig_pub_key[..],
+            None => true,
+        }
+    }
+
+    /// we are the connecting node, decrypt step 2 and respond with step 3
+    #[allow(clippy::borrowed_box)]
+    fn on_step2(
+        &mut self,
+        identity: &TransitIdentity,
+        kx_pub_key: &Box<dyn Buffer>,
+        kx_sec_key: &Box<dyn Buffer>,
+        step2: MsgStep2FromListen,
+    ) -> Lib3hResult<(SessionState, TransitEvent)> {
+        let crypto = &identity.crypto;
+        let step2 = match step2 {
+            MsgStep2FromListen::Halt(halt) => {
+                return Ok((
+                    SessionState::Halted,
+                    TransitEvent::Halted {
+                        halt,
+                        response: None,
+                    },
+                ))
+            }
+            MsgStep2FromListen::Continue(step2) => step2,
+        };
+        if !identity.accepts(step2.encoding) {
+            return Ok((
+                SessionState::Halted,
+                step3_halt(
+                    self.encoding,
+                    HaltReasonCode::BadEncoding,
+                    &format!("unexpected encoding {:?}", step2.encoding),
+                ),
+            ));
+        }
+        self.encoding = step2.encoding;
+        let encoding = self.encoding;
+        if step2.kx_pub_key.len() != crypto.kx_public_key_bytes()
+            || step2.kx_nonce.len() != identity.kx_nonce_bytes(encoding)
+        {
+            return Ok((
+                SessionState::Halted,
+                step3_halt(
+                    encoding,
+                    HaltReasonCode::KxDecodeFail,
+                    "bad kx public key or nonce",
+                ),
+            ));
+        }
+
+        let remote_kx_pub_key: Box<dyn Buffer> = Box::new(step2.kx_pub_key);
+        let mut kx_rx = crypto.buf_new_secure(crypto.kx_session_key_bytes());
+        let mut kx_tx = crypto.buf_new_secure(crypto.kx_session_key_bytes());
+        crypto.kx_client_session_keys(
+            &mut kx_rx,
+            &mut kx_tx,
+            kx_pub_key,
+            kx_sec_key,
+            &remote_kx_pub_key,
+        )?;
+
+        let kx_nonce: Box<dyn Buffer> = Box::new(step2.kx_nonce);
+        let kx_encoded = match identity
+            .open(encoding, &step2.kx_secret, &kx_nonce, &kx_rx)
+            .and_then(|kx_encoded| {
+                Ok(MsgStep2FromListenKxEncoded::decode_as(
+                    &kx_encoded,
+                    encoding,
+                )?)
+            }) {
+            Ok(kx_encoded) => kx_encoded,
+            Err(_) => {
+                return Ok((
+                    SessionState::Halted,
+                    step3_halt(
+                        encoding,
+                        HaltReasonCode::KxDecodeFail,
+                        "failed to decode kx secret",
+                    ),
+                ))
+            }
+        };
+        if !self.check_sig_pub_key(identity, &kx_encoded.sig_pub_key) {
+            return Ok((
+                SessionState::Halted,
+                step3_halt(
+                    encoding,
+                    HaltReasonCode::UnexpectedSigPubKey,
+                    "unexpected signature public key",
+                ),
+            ));
+        }
+        if kx_encoded.l2c_session_key.len() != crypto.aead_secret_bytes() {
+            return Ok((
+                SessionState::Halted,
+                step3_halt(encoding, HaltReasonCode::KxDecodeFail, "bad session key"),
+            ));
+        }
+
+        let l2c_session_key = identity.secret_from(&kx_encoded.l2c_session_key)?;
+        let c2l_session_key = identity.random_secret(crypto.aead_secret_bytes())?;
+        let kx_encoded_reply = MsgStep3FromConnectKxEncoded {
+            padding: Vec::new(),
+            sig_pub_key: identity.sig_pub_key(),
+            c2l_session_key: c2l_session_key.read_lock().to_vec(),
+            c2l_signature: identity.sign(&self.transcript(identity, &l2c_session_key)?)?,
+            compressions: identity.compressions.clone(),
+        };
+        let kx_nonce = identity.random_nonce(encoding)?;
+        let kx_secret = identity.seal(
+            encoding,
+            kx_encoded_reply.encode_as(encoding),
+            &kx_nonce,
+            &kx_tx,
+        )?;
+        let step3 = MsgStep3FromConnect::Continue(MsgStep3Continue {
+            kx_nonce: kx_nonce.to_vec(),
+            kx_secret,
+        });
+        Ok((
+            SessionState::ConnectSentStep3 {
+                remote_sig_pub_key: kx_encoded.sig_pub_key,
+                l2c_session_key,
+                c2l_session_key,
+            },
+            TransitEvent::Handshake {
+                response: Some(step3.encode_as(encoding)),
+            },
+        ))
+    }
+
+    /// we are the listening node, authenticate the remote and respond with step 4
+    #[allow(clippy::borrowed_box)]
+    fn on_step3(
+        &mut self,
+        identity: &TransitIdentity,
+        kx_rx: &Box<dyn Buffer>,
+        l2c_session_key: Box<dyn Buffer>,
+        step3: MsgStep3FromConnect,
+    ) -> Lib3hResult<(SessionState, TransitEvent)> {
+        let crypto = &identity.crypto;
+        let encoding = self.encoding;
+        let step3 = match step3 {
+            MsgStep3FromConnect::Halt(halt) => {
+                return Ok((
+                    SessionState::Halted,
+                    TransitEvent::Halted {
+                        halt,
+                        response: None,
+                    },
+                ))
+            }
+            MsgStep3FromConnect::Continue(step3) => step3,
+        };
+        if step3.kx_nonce.len() != identity.kx_nonce_bytes(encoding) {
+            return Ok((
+                SessionState::Halted,
+                bare_halt(encoding, HaltReasonCode::KxDecodeFail, "bad kx nonce"),
+            ));
+        }
+
+        let kx_nonce: Box<dyn Buffer> = Box::new(step3.kx_nonce);
+        let kx_encoded = match identity
+            .open(encoding, &step3.kx_secret, &kx_nonce, kx_rx)
+            .and_then(|kx_encoded| {
+                Ok(MsgStep3FromConnectKxEncoded::decode_as(
+                    &kx_encoded,
+                    encoding,
+                )?)
+            }) {
+            Ok(kx_encoded) => kx_encoded,
+            Err(_) => {
+                return Ok((
+                    SessionState::Halted,
+                    bare_halt(
+                        encoding,
+                        HaltReasonCode::KxDecodeFail,
+                        "failed to decode kx secret",
+                    ),
+                ))
+            }
+        };
+        if !self.check_sig_pub_key(identity, &kx_encoded.sig_pub_key) {
+            return Ok((
+                SessionState::Halted,
+                bare_halt(
+                    encoding,
+                    HaltReasonCode::UnexpectedSigPubKey,
+                    "unexpected signature public key",
+                ),
+            ));
+        }
+        if !identity.verify(
+            &kx_encoded.c2l_signature,
+            &self.transcript(identity, &l2c_session_key)?,
+            &kx_encoded.sig_pub_key,
+        ) {
+            return Ok((
+                SessionState::Halted,
+                bare_halt(
+                    encoding,
+                    HaltReasonCode::BadSignature,
+                    "bad l2c session key signature",
+                ),
+            ));
+        }
+        if kx_encoded.c2l_session_key.len() != crypto.aead_secret_bytes() {
+            return Ok((
+                SessionState::Halted,
+                bare_halt(encoding, HaltReasonCode::KxDecodeFail, "bad session key"),
+            ));
+        }
+
+        let c2l_session_key = identity.secret_from(&kx_encoded.c2l_session_key)?;
+        self.compression = identity.pick_compression(&kx_encoded.compressions);
+        let step4 = MsgStep4FromListenEncoded {
+            padding: Vec::new(),
+            l2c_signature: identity.sign(&self.transcript(identity, &c2l_session_key)?)?,
+            compression: self.compression,
+        };
+        let step4 = identity.seal(
+            encoding,
+            step4.encode_as(encoding),
+            &identity.counter_nonce(0),
+            &l2c_session_key,
+        )?;
+        Ok((
+            SessionState::ListenSentStep4 {
+                remote_sig_pub_key: kx_encoded.sig_pub_key,
+                l2c_session_key,
+                c2l_session_key,
+            },
+            TransitEvent::Handshake {
+                response: Some(step4),
+            },
+        ))
+    }
+
+    /// we are the connecting node, authenticate the remote and finish with step 5
+    fn on_step4(
+        &mut self,
+        identity: &TransitIdentity,
+        remote_sig_pub_key: Vec<u8>,
+        l2c_session_key: Box<dyn Buffer>,
+        c2l_session_key: Box<dyn Buffer>,
+        step4: MsgStep4FromListenEncoded,
+    ) -> Lib3hResult<(SessionState, TransitEvent)> {
+        let transcript = self.transcript(identity, &c2l_session_key)?;
+        if !identity.verify(&step4.l2c_signature, &transcript, &remote_sig_pub_key) {
+            // step 5 cannot carry a halt, the remote will time out
+            return Ok((
+                SessionState::Halted,
+                halt_event(
+                    HaltReasonCode::BadSignature,
+                    "bad c2l session key signature",
+                ),
+            ));
+        }
+        if step4.compression != CompressionType::None
+            && !identity.compressions.contains(&step4.compression)
+        {
+            return Ok((
+                SessionState::Halted,
+                halt_event(
+                    HaltReasonCode::BadEncoding,
+                    &format!("unexpected compression {:?}", step4.compression),
+                ),
+            ));
+        }
+        self.compression = step4.compression;
+        let step5 = MsgStep5FromConnectEncoded {
+            padding: Vec::new(),
+        };
+        let step5 = identity.seal(
+            self.encoding,
+            step5.encode_as(self.encoding),
+            &identity.counter_nonce(0),
+            &c2l_session_key,
+        )?;
+        self.pinned_sig_pub_key = Some(remote_sig_pub_key.clone());
+        Ok((
+            SessionState::Established {
+                remote_sig_pub_key: remote_sig_pub_key.clone(),
+                tx_key: c2l_session_key,
+                tx_nonce: 1,
+                rx_key: l2c_session_key,
+                rx_window: ReplayWindow::new(),
+            },
+            TransitEvent::Established {
+                remote_sig_pub_key,
+                encoding: self.encoding,
+                compression: self.compression,
+                response: Some(step5),
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::memory_mock::network_faults::{
+        FaultConfig, LatencyDistribution, LinkFaults, NetworkFaults,
+    };
+    use lib3h_protocol::uri::Lib3hUri;
+    use lib3h_sodium::SodiumCryptoSystem;
+    use std::collections::{HashMap, HashSet};
+
+    fn test_identity(network_id: &str) -> TransitIdentity {
+        let crypto: Box<dyn CryptoSystem> = Box::new(SodiumCryptoSystem::new());
+        let transport_keys = TransportKeys::new(&*crypto).unwrap();
+        TransitIdentity::new(crypto, &transport_keys, &network_id.into())
+    }
+
+    fn expect_response(event: TransitEvent) -> Vec<u8> {
+        match event {
+            TransitEvent::Handshake {
+                response: Some(response),
+            } => response,
+            _ => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    /// run the handshake between two sessions, returning them established
+    fn handshake(
+        c_identity: &TransitIdentity,
+        l_identity: &TransitIdentity,
+    ) -> (TransitSession, TransitSession) {
+        let (mut c_session, step1) = TransitSession::connect(c_identity).unwrap();
+        let mut l_session = TransitSession::listen();
+        let step2 = expect_response(l_session.receive(l_identity, &step1).unwrap());
+        let step3 = expect_response(c_session.receive(c_identity, &step2).unwrap());
+        let step4 = expect_response(l_session.receive(l_identity, &step3).unwrap());
+        let step5 = match c_session.receive(c_identity, &step4).unwrap() {
+            TransitEvent::Established {
+                remote_sig_pub_key,
+                response: Some(step5),
+                ..
+            } => {
+                assert_eq!(l_identity.sig_pub_key(), remote_sig_pub_key);
+                step5
+            }
+            event => panic!("unexpected event {:?}", event),
+        };
+        match l_session.receive(l_identity, &step5).unwrap() {
+            TransitEvent::Established {
+                remote_sig_pub_key,
+                response: None,
+                ..
+            } => assert_eq!(c_identity.sig_pub_key(), remote_sig_pub_key),
+            event => panic!("unexpected event {:?}", event),
+        }
+        (c_session, l_session)
+    }
+
+    fn expect_payload(
+        session: &mut TransitSession,
+        identity: &TransitIdentity,
+        bytes: &[u8],
+    ) -> Vec<u8> {
+        match session.receive(identity, bytes).unwrap() {
+            TransitEvent::Payload(payload) => payload,
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn it_should_handshake_and_exchange_messages() {
+        let c_identity = test_identity("net");
+        let l_identity = test_identity("net");
+        let (mut c_session, mut l_session) = handshake(&c_identity, &l_identity);
+        assert!(c_session.is_established());
+        assert!(l_session.is_established());
+
+        for i in 0..3_u8 {
+            let encoded = c_session.encode(&c_identity, vec![i; 4]).unwrap();
+            assert_ne!(vec![i; 4], encoded);
+            assert_eq!(
+                vec![i; 4],
+                expect_payload(&mut l_session, &l_identity, &encoded)
+            );
+            let encoded = l_session.encode(&l_identity, vec![i]).unwrap();
+            assert_eq!(
+                vec![i],
+                expect_payload(&mut c_session, &c_identity, &encoded)
+            );
+        }
+    }
+
+    #[test]
+    fn it_should_speak_every_encoding() {
+        for encoding in &[
+            TransitEncodingType::OpenJson,
+            TransitEncodingType::OpenPacked,
+            TransitEncodingType::SodiumJson,
+            TransitEncodingType::SodiumPacked,
+        ] {
+            let c_identity = test_identity("net")
+                .with_encodings(vec![*encoding])
+                .unwrap();
+            let l_identity = test_identity("net")
+                .with_encodings(vec![*encoding])
+                .unwrap();
+            let (mut c_session, mut l_session) = handshake(&c_identity, &l_identity);
+            let encoded = c_session.encode(&c_identity, b"test".to_vec()).unwrap();
+            assert_eq!(encoding.is_json(), encoded[0] == b'{');
+            assert_eq!(
+                b"test".to_vec(),
+                expect_payload(&mut l_session, &l_identity, &encoded)
+            );
+        }
+    }
+
+    #[test]
+    fn it_should_require_an_encoding() {
+        assert!(test_identity("net").with_encodings(Vec::new()).is_err());
+        assert!(test_identity("net")
+            .with_encodings(vec![TransitEncodingType::Unknown])
+            .is_err());
+    }
+
+    #[test]
+    fn it_should_negotiate_a_mutual_encoding() {
+        let c_identity = test_identity("net")
+            .with_encodings(vec![
+                TransitEncodingType::SodiumPacked,
+                TransitEncodingType::SodiumJson,
+                TransitEncodingType::OpenJson,
+            ])
+            .unwrap();
+        // the connecting node's preferences win
+        let l_identity = test_identity("net")
+            .with_encodings(vec![
+                TransitEncodingType::OpenJson,
+                TransitEncodingType::SodiumJson,
+            ])
+            .unwrap();
+        let (mut c_session, step1) = TransitSession::connect(&c_identity).unwrap();
+        let mut l_session = TransitSession::listen();
+        let step2 = expect_response(l_session.receive(&l_identity, &step1).unwrap());
+        let step3 = expect_response(c_session.receive(&c_identity, &step2).unwrap());
+        let step4 = expect_response(l_session.receive(&l_identity, &step3).unwrap());
+        match c_session.receive(&c_identity, &step4).unwrap() {
+            TransitEvent::Established { encoding, .. } => {
+                assert_eq!(TransitEncodingType::SodiumJson, encoding)
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn it_should_halt_without_a_mutual_encoding() {
+        let c_identity = test_identity("net")
+            .with_encodings(vec![TransitEncodingType::SodiumPacked])
+            .unwrap();
+        let l_identity = test_identity("net")
+            .with_encodings(vec![TransitEncodingType::OpenJson])
+            .unwrap();
+        let (mut c_session, step1) = TransitSession::connect(&c_identity).unwrap();
+        let mut l_session = TransitSession::listen();
+        let step2 = match l_session.receive(&l_identity, &step1).unwrap() {
+            TransitEvent::Halted {
+                halt,
+                response: Some(step2),
+            } => {
+                assert_eq!(HaltReasonCode::BadEncoding, halt.reason_code);
+                step2
+            }
+            event => panic!("unexpected event {:?}", event),
+        };
+        match c_session.receive(&c_identity, &step2).unwrap() {
+            TransitEvent::Halted { halt, .. } => {
+                assert_eq!(HaltReasonCode::BadEncoding, halt.reason_code)
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn it_should_refuse_a_downgraded_encoding() {
+        let encodings = vec![
+            TransitEncodingType::SodiumPacked,
+            TransitEncodingType::OpenPacked,
+        ];
+        let c_identity = test_identity("net")
+            .with_encodings(encodings.clone())
+            .unwrap();
+        let l_identity = test_identity("net").with_encodings(encodings).unwrap();
+        let (mut c_session, step1) = TransitSession::connect(&c_identity).unwrap();
+
+        // someone on the way strips the encrypted encodings from step 1
+        let mut step1 = MsgStep1FromConnect::from_bytes(&step1).unwrap();
+        step1.encoding = TransitEncodingType::OpenPacked;
+        step1.encodings = vec![TransitEncodingType::OpenPacked];
+        let mut l_session = TransitSession::listen();
+        let step2 = expect_response(l_session.receive(&l_identity, &step1.to_bytes()).unwrap());
+        let step3 = expect_response(c_session.receive(&c_identity, &step2).unwrap());
+        match l_session.receive(&l_identity, &step3).unwrap() {
+            TransitEvent::Halted { halt, .. } => {
+                assert_eq!(HaltReasonCode::BadSignature, halt.reason_code)
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert!(!l_session.is_established());
+    }
+
+    #[test]
+    fn it_should_negotiate_a_compression() {
+        let config = CompressionConfig::default();
+        // we only compress if both sides agree to
+        let (c_session, l_session) = handshake(
+            &test_identity("net").with_compression(&config),
+            &test_identity("net"),
+        );
+        assert_eq!(CompressionType::None, c_session.compression());
+        assert_eq!(CompressionType::None, l_session.compression());
+        let (c_session, l_session) = handshake(
+            &test_identity("net").with_compression(&config),
+            &test_identity("net").with_compression(&config),
+        );
+        assert_eq!(CompressionType::Deflate, c_session.compression());
+        assert_eq!(CompressionType::Deflate, l_session.compression());
+    }
+
+    #[test]
+    fn it_should_track_received_nonces() {
+        let mut window = ReplayWindow::new();
+        assert_eq!(Some(1), window.candidates().next());
+        window.accept(3);
+        // the skipped nonces can still arrive, the handshake's cannot
+        let candidates: Vec<_> = window.candidates().collect();
+        assert_eq!(4, candidates[0]);
+        assert!(candidates.contains(&1) && candidates.contains(&2));
+        assert!(!candidates.contains(&0) && !candidates.contains(&3));
+        window.accept(1);
+        assert!(!window.candidates().any(|n| n == 1));
+        // nonces too far behind are forgotten
+        window.accept(3 + REPLAY_WINDOW);
+        assert!(!window.candidates().any(|n| n == 2));
+        assert_eq!(
+            MAX_NONCE_SKIP + REPLAY_WINDOW - 1,
+            window.candidates().count() as u64
+        );
+    }
+
+    #[test]
+    fn it_should_accept_reordered_messages_once() {
+        let c_identity = test_identity("net");
+        let l_identity = test_identity("net");
+        let (mut c_session, mut l_session) = handshake(&c_identity, &l_identity);
+
+        let encoded: Vec<_> = (0..4_u8)
+            .map(|i| c_session.encode(&c_identity, vec![i]).unwrap())
+            .collect();
+        for i in &[2, 0, 3, 1] {
+            assert_eq!(
+                vec![*i as u8],
+                expect_payload(&mut l_session, &l_identity, &encoded[*i])
+            );
+        }
+        for bytes in &encoded {
+            assert!(l_session.receive(&l_identity, bytes).is_err());
+        }
+        assert!(l_session.is_established());
+    }
+
+    #[test]
+    fn it_should_survive_a_lossy_reordering_network() {
+        let c_identity = test_identity("net");
+        let l_identity = test_identity("net");
+        let (mut c_session, mut l_session) = handshake(&c_identity, &l_identity);
+        let c_uri = Lib3hUri::with_memory("c");
+        let l_uri = Lib3hUri::with_memory("l");
+        let mut faults = NetworkFaults::new(FaultConfig {
+            seed: [7, 42],
+            link_faults: LinkFaults {
+                latency: LatencyDistribution::Uniform {
+                    min_ms: 0,
+                    max_ms: 20,
+                },
+                loss_rate: 0.2,
+                duplicate_rate: 0.1,
+                reorder_rate: 0.2,
+                reorder_delay_ms: 35,
+                bandwidth_bytes_per_sec: None,
+            },
+            manual_clock: true,
+        });
+
+        let mut sent = HashMap::new();
+        let mut received = HashSet::new();
+        let receive = |faults: &mut NetworkFaults,
+                       l_session: &mut TransitSession,
+                       sent: &HashMap<Vec<u8>, u8>,
+                       received: &mut HashSet<u8>| {
+            for msg in faults.take_due() {
+                let i = sent[&msg.payload];
+                if received.insert(i) {
+                    assert_eq!(
+                        vec![i],
+                        expect_payload(l_session, &l_identity, &msg.payload)
+                    );
+                } else {
+                    // a duplicate is a replay
+                    assert!(l_session.receive(&l_identity, &msg.payload).is_err());
+                }
+            }
+        };
+        for i in 0..100_u8 {
+            let encoded = c_session.encode(&c_identity, vec![i]).unwrap();
+            faults.send(&c_uri, &l_uri, &encoded);
+            sent.insert(encoded, i);
+            faults.advance_clock(10);
+            receive(&mut faults, &mut l_session, &sent, &mut received);
+        }
+        faults.advance_clock(1000);
+        receive(&mut faults, &mut l_session, &sent, &mut received);
+
+        let stats = faults.stats();
+        assert!(stats.lost > 0 && stats.duplicated > 0 && stats.reordered > 0);
+        assert_eq!(100 - stats.lost, received.len() as u64);
+        assert!(l_session.is_established());
+    }
+
+    #[test]
+    fn it_should_tolerate_lost_messages_but_not_replays() {
+        let c_identity = test_identity("net");
+        let l_identity = test_identity("net");
+        let (mut c_session, mut l_session) = handshake(&c_identity, &l_identity);
+
+        let first = c_session.encode(&c_identity, b"first".to_vec()).unwrap();
+        let _lost = c_session.encode(&c_identity, b"lost".to_vec()).unwrap();
+        let third = c_session.encode(&c_identity, b"third".to_vec()).unwrap();
+        assert_eq!(
+            b"first".to_vec(),
+            expect_payload(&mut l_session, &l_identity, &first)
+        );
+        assert_eq!(
+            b"third".to_vec(),
+            expect_payload(&mut l_session, &l_identity, &third)
+        );
+        assert!(l_session.receive(&l_identity, &first).is_err());
+        // a bad message does not tear down the session
+        assert!(l_session.is_established());
+    }
+
+    #[test]
+    fn it_should_halt_on_bad_network_id() {
+        let c_identity = test_identity("net-a");
+        let l_identity = test_identity("net-b");
+        let (mut c_session, step1) = TransitSession::connect(&c_identity).unwrap();
+        let mut l_session = TransitSession::listen();
+        let step2 = match l_session.receive(&l_identity, &step1).unwrap() {
+            TransitEvent::Halted {
+                halt,
+                response: Some(step2),
+            } => {
+                assert_eq!(HaltReasonCode::BadNetworkId, halt.reason_code);
+                step2
+            }
+            event => panic!("unexpected event {:?}", event),
+        };
+        match c_session.receive(&c_identity, &step2).unwrap() {
+            TransitEvent::Halted {
+                halt,
+                response: None,
+            } => assert_eq!(HaltReasonCode::BadNetworkId, halt.reason_code),
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert!(c_session.is_stale(crate::time::since_epoch_ms()));
+    }
+
+    #[test]
+    fn it_should_resolve_simultaneous_connects() {
+        let a_identity = test_identity("net");
+        let b_identity = test_identity("net");
+        let (mut a_session, a_step1) = TransitSession::connect(&a_identity).unwrap();
+        let (mut b_session, b_step1) = TransitSession::connect(&b_identity).unwrap();
+
+        // exactly one side yields and answers the other's step 1
+        let a_event = a_session.receive(&a_identity, &b_step1).unwrap();
+        let b_event = b_session.receive(&b_identity, &a_step1).unwrap();
+        let (a_response, b_response) = match (a_event, b_event) {
+            (TransitEvent::Handshake { response: a }, TransitEvent::Handshake { response: b }) => {
+                (a, b)
+            }
+            events => panic!("unexpected events {:?}", events),
+        };
+        assert!(a_response.is_some() != b_response.is_some());
+    }
+
+    #[test]
+    fn it_should_reject_a_different_identity_on_rehandshake() {
+        let c_identity = test_identity("net");
+        let l_identity = test_identity("net");
+        let (_, mut l_session) = handshake(&c_identity, &l_identity);
+
+        // someone else tries to take over the connection
+        let m_identity = test_identity("net");
+        let (mut m_session, step1) = TransitSession::connect(&m_identity).unwrap();
+        let step2 = expect_response(l_session.receive(&l_identity, &step1).unwrap());
+        let step3 = expect_response(m_session.receive(&m_identity, &step2).unwrap());
+        match l_session.receive(&l_identity, &step3).unwrap() {
+            TransitEvent::Halted { halt, .. } => {
+                assert_eq!(HaltReasonCode::UnexpectedSigPubKey, halt.reason_code)
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert!(l_session.is_established());
+    }
+
+    #[test]
+    fn it_should_keep_the_session_through_a_strangers_handshake() {
+        let c_identity = test_identity("net");
+        let l_identity = test_identity("net");
+        let (mut c_session, mut l_session) = handshake(&c_identity, &l_identity);
+
+        let m_identity = test_identity("net");
+        let (_, step1) = TransitSession::connect(&m_identity).unwrap();
+        expect_response(l_session.receive(&l_identity, &step1).unwrap());
+        assert!(l_session.is_established());
+
+        // both sides still talk with the keys they agreed on
+        let encoded = c_session.encode(&c_identity, b"hello".to_vec()).unwrap();
+        assert_eq!(
+            b"hello".to_vec(),
+            expect_payload(&mut l_session, &l_identity, &encoded)
+        );
+        let encoded = l_session.encode(&l_identity, b"world".to_vec()).unwrap();
+        assert_eq!(
+            b"world".to_vec(),
+            expect_payload(&mut c_session, &c_identity, &encoded)
+        );
+    }
+
+    #[test]
+    fn it_should_swap_keys_once_the_same_node_handshakes_again() {
+        let c_identity = test_identity("net");
+        let l_identity = test_identity("net");
+        let (_, mut l_session) = handshake(&c_identity, &l_identity);
+
+        // the connecting node lost its session and starts over
+        let (mut c_session, step1) = TransitSession::connect(&c_identity).unwrap();
+        let step2 = expect_response(l_session.receive(&l_identity, &step1).unwrap());
+        // meanwhile we keep sending with the established keys
+        let sealed = l_session.encode(&l_identity, b"old".to_vec()).unwrap();
+        match c_session.receive(&c_identity, &sealed).unwrap() {
+            TransitEvent::Handshake { response: None } => (),
+            event => panic!("unexpected event {:?}", event),
+        }
+        let step3 = expect_response(c_session.receive(&c_identity, &step2).unwrap());
+        let step4 = expect_response(l_session.receive(&l_identity, &step3).unwrap());
+        let step5 = match c_session.receive(&c_identity, &step4).unwrap() {
+            TransitEvent::Established {
+                response: Some(step5),
+                ..
+            } => step5,
+            event => panic!("unexpected event {:?}", event),
+        };
+        match l_session.receive(&l_identity, &step5).unwrap() {
+            TransitEvent::Established {
+                remote_sig_pub_key,
+                ..
+            } => assert_eq!(c_identity.sig_pub_key(), remote_sig_pub_key),
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        let encoded = c_session.encode(&c_identity, b"new".to_vec()).unwrap();
+        assert_eq!(
+            b"new".to_vec(),
+            expect_payload(&mut l_session, &l_identity, &encoded)
+        );
+        let encoded = l_session.encode(&l_identity, b"new".to_vec()).unwrap();
+        assert_eq!(
+            b"new".to_vec(),
+            expect_payload(&mut c_session, &c_identity, &encoded)
+        );
+    }
+
+    #[test]
+    fn it_should_rate_limit_step1_on_established_sessions() {
+        let c_identity = test_identity("net");
+        let l_identity = test_identity("net");
+        let (_, mut l_session) = handshake(&c_identity, &l_identity);
+
+        let m_identity = test_identity("net");
+        let (_, step1) = TransitSession::connect(&m_identity).unwrap();
+        expect_response(l_session.receive(&l_identity, &step1).unwrap());
+        let (_, step1) = TransitSession::connect(&m_identity).unwrap();
+        assert!(l_session.receive(&l_identity, &step1).is_err());
+        assert!(l_session.is_established());
+    }
+}
+}
@@ -14,7 +14,19 @@ pub fn calc_location_for_id(crypto: &Box<dyn CryptoSystem>, id: &str) -> Lib3hRe
     };
 
     // first, get the raw bytes out of the hcid string encoding
-    let id_bytes: Box<dyn Buffer> = Box::new(enc.decode(id)?);
+    let id_bytes = enc.decode(id)?;
+
+    calc_location_for_bytes(crypto, &id_bytes)
+}
+
+#[allow(clippy::borrowed_box)]
+/// Given raw bytes that are not an HCID string, e.g. an entry address
+/// calculate the circular rrdht "location" u32 value.
+pub fn calc_location_for_bytes(
+    crypto: &Box<dyn CryptoSystem>,
+    bytes: &[u8],
+) -> Lib3hResult<Location> {
+    let id_bytes: Box<dyn Buffer> = Box::new(bytes.to_vec());
 
     let mut loc_hash = crypto.buf_new_insecure(16);
 
@@ -54,4 +66,15 @@ mod tests {
         .unwrap();
         assert_eq!(167996431_u32, location);
     }
+
+    #[test]
+    fn it_should_calc_same_location_for_same_bytes() {
+        let crypto: Box<dyn CryptoSystem> =
+            Box::new(SodiumCryptoSystem::new().set_pwhash_interactive());
+        let a = calc_location_for_bytes(&crypto, b"entry_addr_1").unwrap();
+        let b = calc_location_for_bytes(&crypto, b"entry_addr_1").unwrap();
+        let c = calc_location_for_bytes(&crypto, b"entry_addr_2").unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
 }
@@ -1,5 +1,6 @@
 //! Connection Error struct and TransportResult type
 
+use lib3h_protocol::types::NodePubKey;
 use tungstenite::handshake::{
     server::{NoCallback, ServerHandshake},
     HandshakeError,
@@ -41,6 +42,15 @@ impl TransportError {
             false
         }
     }
+
+    /// is this a WouldBlock kind error
+    pub fn is_would_block(&self) -> bool {
+        if let ErrorKind::WouldBlock(_) = self.kind() {
+            true
+        } else {
+            false
+        }
+    }
 }
 
 /// The specific type of an error.
@@ -48,6 +58,10 @@ impl TransportError {
 pub enum ErrorKind {
     Ignore(String),
     Other(String),
+    /// the certificate of a server we connected to failed our `TlsVerification`
+    TlsVerification(TlsVerificationError),
+    /// a connection or queue limit is reached, try again later
+    WouldBlock(String),
     /// Hints that destructuring should not be exhaustive.
     ///
     /// This enum may grow additional variants, so this makes sure clients
@@ -57,10 +71,27 @@ pub enum ErrorKind {
     __Nonexhaustive,
 }
 
+/// Why the certificate of a server failed our `TlsVerification`
+#[derive(Debug, PartialEq, Clone)]
+pub enum TlsVerificationError {
+    /// the server presented no certificate
+    NoCertificate,
+    /// the handshake failed while checking the certificate chain and host name
+    Untrusted(String),
+    /// the SHA-256 fingerprint of the certificate is not one we pinned
+    FingerprintMismatch(Vec<u8>),
+    /// the certificate is not bound to a node key
+    NodeBindingInvalid(String),
+    /// the certificate is bound to a node we do not accept
+    UnexpectedNode(NodePubKey),
+}
+
 impl std::fmt::Display for TransportError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match *self.0 {
             ErrorKind::Other(ref s) => write!(f, "{}", s),
+            ErrorKind::TlsVerification(ref e) => write!(f, "tls verification failed: {:?}", e),
+            ErrorKind::WouldBlock(ref s) => write!(f, "would block: {}", s),
             _ => unreachable!(),
         }
     }
@@ -71,6 +102,8 @@ impl std::error::Error for TransportError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match *self.0 {
             ErrorKind::Other(ref _s) => None,
+            ErrorKind::TlsVerification(ref _e) => None,
+            ErrorKind::WouldBlock(ref _s) => None,
             _ => unreachable!(),
         }
     }
@@ -88,6 +121,12 @@ impl From<&str> for TransportError {
     }
 }
 
+impl From<TlsVerificationError> for TransportError {
+    fn from(error: TlsVerificationError) -> Self {
+        TransportError::new_kind(ErrorKind::TlsVerification(error))
+    }
+}
+
 impl From<lib3h_ghost_actor::GhostError> for TransportError {
     fn from(error: lib3h_ghost_actor::GhostError) -> Self {
         Self::new(format!("{:?}", error))
@@ -124,6 +163,18 @@ impl From<native_tls::Error> for TransportError {
     }
 }
 
+impl From<openssl::error::ErrorStack> for TransportError {
+    fn from(error: openssl::error::ErrorStack) -> Self {
+        Self::new(format!("{:?}", error))
+    }
+}
+
+impl From<lib3h_crypto_api::CryptoError> for TransportError {
+    fn from(error: lib3h_crypto_api::CryptoError) -> Self {
+        Self::new(format!("{:?}", error))
+    }
+}
+
 impl<S: std::fmt::Debug + std::io::Read + std::io::Write>
     From<HandshakeError<ServerHandshake<S, NoCallback>>> for TransportError
 {
@@ -177,6 +177,8 @@ impl
 
         // make sure we have bound and get our address if so
         if let Some(my_addr) = &self.maybe_my_address {
+            // messages held back by injected faults may have arrived
+            self.network.lock().deliver_due();
             // get our own server
             let (success, event_list) = {
                 match self.network.lock().get_server(&my_addr) {
@@ -297,8 +299,20 @@ impl
                             )))?;
                         }
                         Some(my_addr) => {
+                            // only connections the remote opened itself get
+                            // to an unreachable server
+                            if self.connections.get(&uri).is_none()
+                                && !self.network.lock().is_reachable(&uri)
+                            {
+                                msg.respond(Err(TransportError::new(format!(
+                                    "{} cannot be connected to",
+                                    uri
+                                ))))?;
+                                continue;
+                            }
                             // get destinations server
-                            match self.network.lock().get_server(&uri) {
+                            let mut network = self.network.lock();
+                            match network.get_server(&uri) {
                                 None => {
                                     msg.respond(Err(TransportError::new(format!(
                                         "No Memory server at this uri: {}",
@@ -332,8 +346,8 @@ impl
                                             uri,
                                             payload
                                         );
-                                        // Send it data from us
-                                        server.post(&my_addr, &payload).unwrap();
+                                        // Send it data from us, through the network's faults
+                                        network.transmit(&my_addr, &uri, &payload).unwrap();
                                     }
                                     msg.respond(Ok(SendMessageSuccess))?;
                                 }
@@ -341,6 +355,11 @@ impl
                         }
                     };
                 }
+                RequestToChild::RotateTlsIdentity { .. } => {
+                    msg.respond(Err(TransportError::new(
+                        "the memory transport has no tls identity".to_string(),
+                    )))?;
+                }
             }
         }
         Ok(true.into())
@@ -351,6 +370,9 @@ impl
 mod tests {
 
     use super::*;
+    use crate::transport::memory_mock::network_faults::{
+        FaultConfig, LatencyDistribution, LinkFaults,
+    };
     //use protocol::RequestToChildResponse;
     use holochain_tracing::test_span;
 
@@ -528,6 +550,170 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_gmem_transport_unreachable() {
+        let netname = "test_gmem_transport_unreachable";
+        let (mut transport1, mut t1_endpoint) = make_test_transport("1", netname);
+        let (mut transport2, mut t2_endpoint) = make_test_transport("2", netname);
+        let mut bound_transport1_address = Lib3hUri::with_undefined();
+        do_bind(&mut t1_endpoint);
+        let mut bound_transport2_address = Lib3hUri::with_undefined();
+        do_bind(&mut t2_endpoint);
+        transport1.process().unwrap();
+        let _ = t1_endpoint.process(&mut bound_transport1_address);
+        transport2.process().unwrap();
+        let _ = t2_endpoint.process(&mut bound_transport2_address);
+
+        // transport2 is behind a NAT
+        {
+            let network = {
+                let mut verse = get_memory_verse();
+                verse.get_network(netname)
+            };
+            network
+                .lock()
+                .set_reachable(&bound_transport2_address, false);
+        }
+
+        // transport1 cannot connect to it
+        t1_endpoint
+            .request(
+                test_span(),
+                RequestToChild::create_send_message(
+                    Lib3hUri::with_memory("addr_2"),
+                    b"test message".to_vec().into(),
+                ),
+                Box::new(|_: &mut Lib3hUri, r| {
+                    assert_eq!(
+                        "Response(Err(TransportError(Other(\"mem://addr_2/ cannot be connected to\"))))",
+                        &format!("{:?}", r)
+                    );
+                    Ok(())
+                }),
+            )
+            .unwrap();
+        transport1.process().unwrap();
+        let _ = t1_endpoint.process(&mut bound_transport1_address);
+
+        // but transport2 can connect to transport1
+        t2_endpoint
+            .request(
+                test_span(),
+                RequestToChild::create_send_message(
+                    Lib3hUri::with_memory("addr_1"),
+                    b"test message".to_vec().into(),
+                ),
+                Box::new(|_: &mut Lib3hUri, r| {
+                    assert_eq!("Response(Ok(SendMessageSuccess))", &format!("{:?}", r));
+                    Ok(())
+                }),
+            )
+            .unwrap();
+        transport2.process().unwrap();
+        let _ = t2_endpoint.process(&mut bound_transport2_address);
+        transport1.process().unwrap();
+        let _ = t1_endpoint.process(&mut bound_transport1_address);
+
+        // after which transport1 can answer over that connection
+        t1_endpoint
+            .request(
+                test_span(),
+                RequestToChild::create_send_message(
+                    Lib3hUri::with_memory("addr_2"),
+                    b"test answer".to_vec().into(),
+                ),
+                Box::new(|_: &mut Lib3hUri, r| {
+                    assert_eq!("Response(Ok(SendMessageSuccess))", &format!("{:?}", r));
+                    Ok(())
+                }),
+            )
+            .unwrap();
+        transport1.process().unwrap();
+        let _ = t1_endpoint.process(&mut bound_transport1_address);
+        transport2.process().unwrap();
+        let _ = t2_endpoint.process(&mut bound_transport2_address);
+
+        let mut requests = t2_endpoint.drain_messages();
+        assert_eq!(
+            "Some(ReceivedData { uri: Lib3hUri(\"mem://addr_1/\"), payload: \"test answer\" })",
+            format!("{:?}", requests.pop().unwrap().take_message())
+        );
+    }
+
+    #[test]
+    fn test_gmem_transport_faults() {
+        let netname = "test_gmem_transport_faults";
+        let (mut transport1, mut t1_endpoint) = make_test_transport("1", netname);
+        let (mut transport2, mut t2_endpoint) = make_test_transport("2", netname);
+        let mut bound_transport1_address = Lib3hUri::with_undefined();
+        do_bind(&mut t1_endpoint);
+        let mut bound_transport2_address = Lib3hUri::with_undefined();
+        do_bind(&mut t2_endpoint);
+        transport1.process().unwrap();
+        let _ = t1_endpoint.process(&mut bound_transport1_address);
+        transport2.process().unwrap();
+        let _ = t2_endpoint.process(&mut bound_transport2_address);
+
+        let network = {
+            let mut verse = get_memory_verse();
+            verse.get_network(netname)
+        };
+        network.lock().set_faults(FaultConfig {
+            seed: [1, 2],
+            link_faults: LinkFaults {
+                latency: LatencyDistribution::Constant(50),
+                ..LinkFaults::default()
+            },
+            manual_clock: true,
+        });
+
+        let t2_uri = bound_transport2_address.clone();
+        let mut send = |payload: &[u8]| {
+            t1_endpoint
+                .request(
+                    test_span(),
+                    RequestToChild::create_send_message(t2_uri.clone(), payload.to_vec().into()),
+                    Box::new(|_: &mut Lib3hUri, r| {
+                        assert_eq!("Response(Ok(SendMessageSuccess))", &format!("{:?}", r));
+                        Ok(())
+                    }),
+                )
+                .unwrap();
+            transport1.process().unwrap();
+            let _ = t1_endpoint.process(&mut bound_transport1_address);
+        };
+        let mut received = || {
+            transport2.process().unwrap();
+            let _ = t2_endpoint.process(&mut bound_transport2_address);
+            t2_endpoint
+                .drain_messages()
+                .iter_mut()
+                .map(|msg| format!("{:?}", msg.take_message()))
+                .filter(|msg| msg.contains("ReceivedData"))
+                .collect::<Vec<_>>()
+        };
+
+        // the message is in flight until its latency has passed
+        send(b"delayed");
+        assert!(received().is_empty());
+        network.lock().faults_mut().unwrap().advance_clock(50);
+        assert_eq!(
+            vec!["Some(ReceivedData { uri: Lib3hUri(\"mem://addr_1/\"), payload: \"delayed\" })"],
+            received()
+        );
+
+        // nothing crosses a partition
+        network
+            .lock()
+            .faults_mut()
+            .unwrap()
+            .partition("cut", &[t2_uri.clone()]);
+        send(b"lost");
+        network.lock().faults_mut().unwrap().advance_clock(50);
+        assert!(received().is_empty());
+        assert_eq!(1, network.lock().faults_mut().unwrap().stats().partitioned);
+    }
+
     #[test]
     fn test_gmem_transport_send_to_self() {
         let (mut transport1, mut t1_endpoint) =
@@ -544,6 +730,7 @@ mod tests {
                 RequestToChild::SendMessage {
                     uri: Lib3hUri::with_memory("addr_1"),
                     payload: b"test message".to_vec().into(),
+                    reliable: true,
                 },
                 Box::new(|_: &mut Lib3hUri, r| {
                     // parent should see that the send request was OK
@@ -1,4 +1,7 @@
-use crate::transport::error::{TransportError, TransportResult};
+use crate::transport::{
+    error::{TransportError, TransportResult},
+    memory_mock::network_faults::{FaultConfig, NetworkFaults},
+};
 use lib3h_ghost_actor::{GhostMutex, GhostMutexGuard};
 use lib3h_protocol::{data_types::Opaque, types::*, uri::Lib3hUri, DidWork};
 use std::{
@@ -33,6 +36,10 @@ pub struct MemoryNet {
     pub server_map: HashMap<Lib3hUri, MemoryServer>,
     url_count: u32,
     advertised_nodes_list: HashSet<(Lib3hUri, NodePubKey)>,
+    /// Servers nobody can connect to, as if they were behind a NAT
+    unreachable_list: HashSet<Lib3hUri>,
+    /// Faults injected in the messages between servers, if any
+    faults: Option<NetworkFaults>,
 }
 
 impl MemoryNet {
@@ -42,13 +49,32 @@ impl MemoryNet {
             server_map: HashMap::new(),
             url_count: 0,
             advertised_nodes_list: HashSet::new(),
+            unreachable_list: HashSet::new(),
+            faults: None,
         }
     }
     pub fn advertise(&mut self, uri: Lib3hUri, node_id: NodePubKey) {
         self.advertised_nodes_list.insert((uri, node_id));
     }
     pub fn discover(&mut self) -> Vec<(Lib3hUri, NodePubKey)> {
-        self.advertised_nodes_list.iter().cloned().collect()
+        let unreachable_list = &self.unreachable_list;
+        self.advertised_nodes_list
+            .iter()
+            .filter(|(uri, _)| !unreachable_list.contains(uri))
+            .cloned()
+            .collect()
+    }
+    /// Simulates a node behind a NAT: an unreachable server can still
+    /// connect to others, but nobody can open a connection to it
+    pub fn set_reachable(&mut self, url: &Lib3hUri, reachable: bool) {
+        if reachable {
+            self.unreachable_list.remove(url);
+        } else {
+            self.unreachable_list.insert(url.clone());
+        }
+    }
+    pub fn is_reachable(&self, url: &Lib3hUri) -> bool {
+        !self.unreachable_list.contains(url)
     }
     pub fn new_url(&mut self) -> Lib3hUri {
         self.url_count += 1;
@@ -73,6 +99,58 @@ impl MemoryNet {
             false
         }
     }
+    /// Start injecting faults in the messages sent between servers
+    pub fn set_faults(&mut self, config: FaultConfig) {
+        self.faults = Some(NetworkFaults::new(config));
+    }
+    /// Deliver messages at once and reliably again, dropping those in flight
+    pub fn clear_faults(&mut self) {
+        self.faults = None;
+    }
+    /// Partitions, per link faults and clock of the injected faults
+    pub fn faults_mut(&mut self) -> Option<&mut NetworkFaults> {
+        self.faults.as_mut()
+    }
+    /// Send `payload` from the server at `from` to the one at `to`,
+    /// through the injected faults if any
+    pub fn transmit(
+        &mut self,
+        from: &Lib3hUri,
+        to: &Lib3hUri,
+        payload: &[u8],
+    ) -> TransportResult<()> {
+        if let Some(faults) = self.faults.as_mut() {
+            faults.send(from, to, payload);
+            self.deliver_due();
+            return Ok(());
+        }
+        match self.get_server(to) {
+            Some(server) => server.post(from, payload),
+            None => Err(TransportError::new(format!(
+                "No Memory server at this uri: {}",
+                to
+            ))),
+        }
+    }
+    /// Hand the messages in flight that are due to their destination
+    pub fn deliver_due(&mut self) {
+        let due = match self.faults.as_mut() {
+            Some(faults) => faults.take_due(),
+            None => return,
+        };
+        for msg in due {
+            let result = match self.server_map.get_mut(&msg.to) {
+                Some(server) => server.post(&msg.from, &msg.payload),
+                None => Err(TransportError::new(format!(
+                    "No Memory server at this uri: {}",
+                    msg.to
+                ))),
+            };
+            if let Err(err) = result {
+                trace!("dropping message in flight to {}: {:?}", msg.to, err);
+            }
+        }
+    }
 }
 
 /// Holds a universe of memory networks so we can run tests in separate universes
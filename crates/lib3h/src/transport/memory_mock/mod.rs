@@ -1,2 +1,3 @@
 pub mod ghost_transport_memory;
 pub mod memory_server;
+pub mod network_faults;
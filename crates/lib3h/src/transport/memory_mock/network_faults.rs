@@ -0,0 +1,383 @@
+//! Fault injection for the in-memory network.
+//!
+//! Without faults a `MemoryNet` hands every message to its destination
+//! server at once. With a `FaultConfig`, messages are dropped, duplicated,
+//! delayed or held back per link, queue behind each other on links with a
+//! bandwidth cap, and never cross the boundary of a named partition until it
+//! is healed. Every random decision is drawn from a PRNG seeded by the
+//! config, so a test replays the same network given the same sends.
+
+use lib3h_protocol::uri::Lib3hUri;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use xoroshiro128::{Rng, SeedableRng, Xoroshiro128Rng};
+
+/// How long messages take to cross a link
+#[derive(Debug, Clone, PartialEq)]
+pub enum LatencyDistribution {
+    /// every message takes this long
+    Constant(u64),
+    /// anywhere from `min_ms` to `max_ms`, evenly
+    Uniform { min_ms: u64, max_ms: u64 },
+    /// normally distributed, cut off at 0
+    Normal { mean_ms: f64, std_dev_ms: f64 },
+}
+
+/// What goes wrong on a link between two memory servers
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkFaults {
+    pub latency: LatencyDistribution,
+    /// probability that a message is lost
+    pub loss_rate: f64,
+    /// probability that a message is delivered twice
+    pub duplicate_rate: f64,
+    /// probability that a message is held back, letting later ones overtake it
+    pub reorder_rate: f64,
+    /// how much longer held back messages take
+    pub reorder_delay_ms: u64,
+    /// messages queue behind each other once a link carries this much
+    pub bandwidth_bytes_per_sec: Option<u64>,
+}
+
+impl Default for LinkFaults {
+    /// a perfect link
+    fn default() -> Self {
+        LinkFaults {
+            latency: LatencyDistribution::Constant(0),
+            loss_rate: 0.0,
+            duplicate_rate: 0.0,
+            reorder_rate: 0.0,
+            reorder_delay_ms: 0,
+            bandwidth_bytes_per_sec: None,
+        }
+    }
+}
+
+/// Faults injected in a `MemoryNet`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultConfig {
+    /// seed of the PRNG every random decision is drawn from
+    pub seed: [u64; 2],
+    /// faults of the links that were not given their own
+    pub link_faults: LinkFaults,
+    /// if true, time only passes with `NetworkFaults::advance_clock`,
+    /// otherwise it follows the system clock
+    pub manual_clock: bool,
+}
+
+impl FaultConfig {
+    pub fn new(seed: [u64; 2], link_faults: LinkFaults) -> Self {
+        FaultConfig {
+            seed,
+            link_faults,
+            manual_clock: false,
+        }
+    }
+}
+
+/// What happened to the messages sent since faults were injected
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FaultStats {
+    pub sent: u64,
+    pub delivered: u64,
+    pub lost: u64,
+    pub duplicated: u64,
+    pub reordered: u64,
+    /// dropped at the boundary of a partition
+    pub partitioned: u64,
+}
+
+/// A message on its way to a memory server
+#[derive(Debug)]
+pub(crate) struct InFlight {
+    pub from: Lib3hUri,
+    pub to: Lib3hUri,
+    pub payload: Vec<u8>,
+}
+
+/// Fault state of a `MemoryNet`
+pub struct NetworkFaults {
+    config: FaultConfig,
+    prng: Xoroshiro128Rng,
+    /// links with faults of their own, both ways
+    link_faults: HashMap<(Lib3hUri, Lib3hUri), LinkFaults>,
+    partitions: HashMap<String, HashSet<Lib3hUri>>,
+    /// when each bandwidth capped link is done sending what it was given
+    busy_until_ms: HashMap<(Lib3hUri, Lib3hUri), u64>,
+    /// keyed by delivery time, then order of sending
+    in_flight: BTreeMap<(u64, u64), InFlight>,
+    next_seq: u64,
+    clock_ms: u64,
+    stats: FaultStats,
+}
+
+impl NetworkFaults {
+    pub fn new(config: FaultConfig) -> Self {
+        NetworkFaults {
+            prng: Xoroshiro128Rng::from_seed(config.seed),
+            config,
+            link_faults: HashMap::new(),
+            partitions: HashMap::new(),
+            busy_until_ms: HashMap::new(),
+            in_flight: BTreeMap::new(),
+            next_seq: 0,
+            clock_ms: 0,
+            stats: FaultStats::default(),
+        }
+    }
+
+    /// Give the link between `a` and `b` faults of its own, both ways
+    pub fn set_link_faults(&mut self, a: &Lib3hUri, b: &Lib3hUri, link_faults: LinkFaults) {
+        self.link_faults
+            .insert((a.clone(), b.clone()), link_faults.clone());
+        self.link_faults.insert((b.clone(), a.clone()), link_faults);
+    }
+
+    /// Cut `nodes` off from the rest of the network until `heal(name)`.
+    /// Nodes in several partitions only reach the nodes in all of them.
+    pub fn partition(&mut self, name: &str, nodes: &[Lib3hUri]) {
+        self.partitions
+            .insert(name.to_string(), nodes.iter().cloned().collect());
+    }
+
+    /// Undo `partition(name)`, messages still in flight are not recovered
+    pub fn heal(&mut self, name: &str) -> bool {
+        self.partitions.remove(name).is_some()
+    }
+
+    pub fn is_partitioned(&self, from: &Lib3hUri, to: &Lib3hUri) -> bool {
+        self.partitions
+            .values()
+            .any(|nodes| nodes.contains(from) != nodes.contains(to))
+    }
+
+    /// Let time pass, with a manual clock
+    pub fn advance_clock(&mut self, ms: u64) {
+        self.clock_ms += ms;
+    }
+
+    pub fn now_ms(&self) -> u64 {
+        if self.config.manual_clock {
+            self.clock_ms
+        } else {
+            crate::time::since_epoch_ms()
+        }
+    }
+
+    pub fn stats(&self) -> FaultStats {
+        self.stats.clone()
+    }
+
+    /// Number of messages sent but not delivered yet
+    pub fn in_flight_count(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// a number in [0, 1)
+    fn next_unit(&mut self) -> f64 {
+        (self.prng.gen::<u64>() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn sample_latency_ms(&mut self, latency: &LatencyDistribution) -> u64 {
+        match latency {
+            LatencyDistribution::Constant(ms) => *ms,
+            LatencyDistribution::Uniform { min_ms, max_ms } => {
+                let spread = max_ms.saturating_sub(*min_ms) + 1;
+                min_ms + (self.next_unit() * spread as f64) as u64
+            }
+            LatencyDistribution::Normal {
+                mean_ms,
+                std_dev_ms,
+            } => {
+                // Box-Muller, 1 - unit is never 0
+                let radius = (-2.0 * (1.0 - self.next_unit()).ln()).sqrt();
+                let angle = 2.0 * std::f64::consts::PI * self.next_unit();
+                (mean_ms + std_dev_ms * radius * angle.cos())
+                    .max(0.0)
+                    .round() as u64
+            }
+        }
+    }
+
+    /// Decide what happens to a message sent from `from` to `to`
+    /// and put the copies that make it in flight
+    pub(crate) fn send(&mut self, from: &Lib3hUri, to: &Lib3hUri, payload: &[u8]) {
+        self.stats.sent += 1;
+        if self.is_partitioned(from, to) {
+            self.stats.partitioned += 1;
+            return;
+        }
+        let link = (from.clone(), to.clone());
+        let faults = self
+            .link_faults
+            .get(&link)
+            .unwrap_or(&self.config.link_faults)
+            .clone();
+        if self.next_unit() < faults.loss_rate {
+            self.stats.lost += 1;
+            return;
+        }
+        let copies = if self.next_unit() < faults.duplicate_rate {
+            self.stats.duplicated += 1;
+            2
+        } else {
+            1
+        };
+        let now = self.now_ms();
+        for _ in 0..copies {
+            let sent_at = match faults.bandwidth_bytes_per_sec {
+                Some(bandwidth) => {
+                    let bandwidth = bandwidth.max(1);
+                    let transmit_ms = (payload.len() as u64 * 1000 + bandwidth - 1) / bandwidth;
+                    let busy_until = self.busy_until_ms.entry(link.clone()).or_insert(now);
+                    *busy_until = (*busy_until).max(now) + transmit_ms;
+                    *busy_until
+                }
+                None => now,
+            };
+            let mut deliver_at = sent_at + self.sample_latency_ms(&faults.latency);
+            if self.next_unit() < faults.reorder_rate {
+                self.stats.reordered += 1;
+                deliver_at += faults.reorder_delay_ms;
+            }
+            self.in_flight.insert(
+                (deliver_at, self.next_seq),
+                InFlight {
+                    from: from.clone(),
+                    to: to.clone(),
+                    payload: payload.to_vec(),
+                },
+            );
+            self.next_seq += 1;
+        }
+    }
+
+    /// Take the messages due by now, in the order they arrive
+    pub(crate) fn take_due(&mut self) -> Vec<InFlight> {
+        let not_due = self.in_flight.split_off(&(self.now_ms() + 1, 0));
+        let due = std::mem::replace(&mut self.in_flight, not_due);
+        self.stats.delivered += due.len() as u64;
+        due.into_iter().map(|(_, msg)| msg).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri(name: &str) -> Lib3hUri {
+        Lib3hUri::with_memory(name)
+    }
+
+    fn faults(link_faults: LinkFaults) -> NetworkFaults {
+        NetworkFaults::new(FaultConfig {
+            seed: [98279182398273, 19287391287398273],
+            link_faults,
+            manual_clock: true,
+        })
+    }
+
+    /// delivery time of every message in flight, in order
+    fn schedule(faults: &NetworkFaults) -> Vec<u64> {
+        faults.in_flight.keys().map(|(at, _)| *at).collect()
+    }
+
+    fn bad_link() -> LinkFaults {
+        LinkFaults {
+            latency: LatencyDistribution::Normal {
+                mean_ms: 100.0,
+                std_dev_ms: 30.0,
+            },
+            loss_rate: 0.2,
+            duplicate_rate: 0.1,
+            reorder_rate: 0.1,
+            reorder_delay_ms: 200,
+            bandwidth_bytes_per_sec: None,
+        }
+    }
+
+    #[test]
+    fn it_should_replay_the_same_faults_from_the_same_seed() {
+        let mut first = faults(bad_link());
+        let mut second = faults(bad_link());
+        for i in 0..100 {
+            first.send(&uri("a"), &uri("b"), &[i]);
+            second.send(&uri("a"), &uri("b"), &[i]);
+        }
+        assert_eq!(first.stats(), second.stats());
+        assert_eq!(schedule(&first), schedule(&second));
+        let stats = first.stats();
+        assert!(stats.lost > 0 && stats.lost < 50);
+        assert!(stats.duplicated > 0);
+        assert!(stats.reordered > 0);
+        assert_eq!(
+            100 - stats.lost + stats.duplicated,
+            first.in_flight_count() as u64
+        );
+    }
+
+    #[test]
+    fn it_should_deliver_when_due() {
+        let mut faults = faults(LinkFaults {
+            latency: LatencyDistribution::Uniform {
+                min_ms: 10,
+                max_ms: 20,
+            },
+            ..LinkFaults::default()
+        });
+        faults.send(&uri("a"), &uri("b"), b"hello");
+        assert!(faults.take_due().is_empty());
+        faults.advance_clock(9);
+        assert!(faults.take_due().is_empty());
+        faults.advance_clock(11);
+        let due = faults.take_due();
+        assert_eq!(1, due.len());
+        assert_eq!(b"hello".to_vec(), due[0].payload);
+        assert_eq!(uri("b"), due[0].to);
+    }
+
+    #[test]
+    fn it_should_queue_behind_a_bandwidth_cap() {
+        let mut faults = faults(LinkFaults {
+            bandwidth_bytes_per_sec: Some(1000),
+            ..LinkFaults::default()
+        });
+        faults.send(&uri("a"), &uri("b"), &[0; 500]);
+        faults.send(&uri("a"), &uri("b"), &[0; 500]);
+        // other links are not slowed down
+        faults.send(&uri("b"), &uri("a"), &[0; 100]);
+        assert_eq!(vec![100, 500, 1000], schedule(&faults));
+    }
+
+    #[test]
+    fn it_should_drop_messages_across_partitions_until_healed() {
+        let mut faults = faults(LinkFaults::default());
+        faults.partition("split", &[uri("a"), uri("b")]);
+        faults.send(&uri("a"), &uri("b"), b"inside");
+        faults.send(&uri("a"), &uri("c"), b"across");
+        faults.send(&uri("c"), &uri("b"), b"across");
+        assert_eq!(2, faults.stats().partitioned);
+        assert_eq!(1, faults.take_due().len());
+
+        assert!(faults.heal("split"));
+        assert!(!faults.heal("split"));
+        faults.send(&uri("a"), &uri("c"), b"healed");
+        assert_eq!(1, faults.take_due().len());
+    }
+
+    #[test]
+    fn it_should_override_faults_per_link() {
+        let mut faults = faults(LinkFaults::default());
+        faults.set_link_faults(
+            &uri("a"),
+            &uri("b"),
+            LinkFaults {
+                loss_rate: 1.0,
+                ..LinkFaults::default()
+            },
+        );
+        faults.send(&uri("b"), &uri("a"), b"lost");
+        faults.send(&uri("a"), &uri("c"), b"delivered");
+        assert_eq!(1, faults.stats().lost);
+        assert_eq!(1, faults.in_flight_count());
+    }
+}
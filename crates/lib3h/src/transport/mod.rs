@@ -3,6 +3,10 @@
 pub mod error;
 pub mod memory_mock;
 pub mod protocol;
+pub mod tcp;
+pub mod udp;
+#[cfg(not(target_os = "windows"))]
+pub mod uds;
 pub mod websocket;
 
 #[macro_use]
@@ -1,4 +1,4 @@
-use crate::transport::error::TransportError;
+use crate::transport::{error::TransportError, websocket::tls::TlsCertificate};
 use detach::prelude::*;
 use lib3h_ghost_actor::prelude::*;
 use lib3h_protocol::{data_types::Opaque, uri::Lib3hUri};
@@ -11,13 +11,38 @@ pub struct BindResultData {
 /// Transport protocol enums for use with GhostActor implementation
 #[derive(Debug, Clone)]
 pub enum RequestToChild {
-    Bind { spec: Lib3hUri }, // wss://0.0.0.0:0 -> all network interfaces first available port
-    SendMessage { uri: Lib3hUri, payload: Opaque },
+    Bind {
+        spec: Lib3hUri,
+    }, // wss://0.0.0.0:0 -> all network interfaces first available port
+    /// `reliable: false` allows connectionless transports to drop the
+    /// message, transports over streams deliver it either way
+    SendMessage {
+        uri: Lib3hUri,
+        payload: Opaque,
+        reliable: bool,
+    },
+    /// present `certificate` on the tls connections accepted from now on,
+    /// established connections are kept, transports without tls fail it
+    RotateTlsIdentity {
+        certificate: TlsCertificate,
+    },
 }
 
 impl RequestToChild {
     pub fn create_send_message(uri: Lib3hUri, payload: Opaque) -> Self {
-        RequestToChild::SendMessage { uri, payload }
+        RequestToChild::SendMessage {
+            uri,
+            payload,
+            reliable: true,
+        }
+    }
+
+    pub fn create_unreliable_send_message(uri: Lib3hUri, payload: Opaque) -> Self {
+        RequestToChild::SendMessage {
+            uri,
+            payload,
+            reliable: false,
+        }
     }
 }
 
@@ -25,6 +50,7 @@ impl RequestToChild {
 pub enum RequestToChildResponse {
     Bind(BindResultData),
     SendMessageSuccess,
+    RotateTlsIdentitySuccess,
 }
 
 #[derive(Debug, Clone)]
@@ -33,6 +59,9 @@ pub enum RequestToParent {
     Unbind(Lib3hUri),
     // a connection has been dropped
     Disconnect(Lib3hUri),
+    // we closed a connection to make room for another one,
+    // it should not be reestablished
+    Evicted(Lib3hUri),
     // TODO remove `uri` field once we have refactored how we handle Connection/Disconnection
     ErrorOccured {
         uri: Lib3hUri,
@@ -0,0 +1,329 @@
+use crate::{
+    new_root_span,
+    transport::{
+        error::{TransportError, TransportResult},
+        protocol::*,
+        tcp::streams::TcpStreamManager,
+        websocket::{
+            streams::{ConnectionStatus, StreamEvent},
+            tls::TlsConfig,
+        },
+    },
+};
+use detach::Detach;
+use lib3h_crypto_api::CryptoSystem;
+use lib3h_ghost_actor::prelude::*;
+use lib3h_protocol::{data_types::Opaque, types::*, uri::Lib3hUri};
+
+pub type Message =
+    GhostMessage<RequestToChild, RequestToParent, RequestToChildResponse, TransportError>;
+
+pub struct GhostTransportTcp {
+    #[allow(dead_code)]
+    node_id: NodePubKey,
+    endpoint_parent: Option<GhostTransportTcpEndpoint>,
+    endpoint_self: Detach<GhostTransportTcpEndpointContext>,
+    streams: TcpStreamManager,
+    bound_url: Option<Lib3hUri>,
+    /// SendMessage requests waiting for their connection to be ready
+    pending: Vec<Message>,
+}
+
+impl Drop for GhostTransportTcp {
+    fn drop(&mut self) {
+        self.streams
+            .close_all()
+            .unwrap_or_else(|e| error!("Error closing streams: {:?}", e));
+    }
+}
+
+impl GhostTransportTcp {
+    pub fn new(
+        node_id: NodePubKey,
+        tls_config: TlsConfig,
+        crypto: Box<dyn CryptoSystem>,
+    ) -> GhostTransportTcp {
+        let (endpoint_parent, endpoint_self) = create_ghost_channel();
+        GhostTransportTcp {
+            node_id,
+            endpoint_parent: Some(endpoint_parent),
+            endpoint_self: Detach::new(
+                endpoint_self
+                    .as_context_endpoint_builder()
+                    .request_id_prefix("ttcp_to_parent")
+                    .build(),
+            ),
+            streams: TcpStreamManager::new(tls_config, crypto),
+            bound_url: None,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn bound_url(&self) -> Option<Lib3hUri> {
+        self.bound_url.clone()
+    }
+
+    /// Sends the message via its connection, which must be ready.
+    /// Errors sending are the response to the message.
+    fn handle_send_message(&mut self, mut msg: Message) -> TransportResult<()> {
+        if let RequestToChild::SendMessage { uri, payload, .. } =
+            msg.take_message().expect("exists")
+        {
+            trace!("(GhostTransportTcp).SendMessage to {} | {:?}", uri, payload);
+            let result = self
+                .streams
+                .send(&uri, &payload.as_bytes())
+                .map(|()| RequestToChildResponse::SendMessageSuccess);
+            msg.respond(result)?;
+        }
+        Ok(())
+    }
+
+    fn process_actor_inbox(&mut self) -> TransportResult<()> {
+        for mut msg in self.endpoint_self.drain_messages() {
+            match msg.take_message().expect("exists") {
+                RequestToChild::Bind { spec: url } => {
+                    let maybe_bound_url = self.streams.bind(&url);
+                    if let Ok(url) = &maybe_bound_url {
+                        trace!("Tcp binding to: {}", url);
+                        self.bound_url = Some(url.clone().into());
+                    }
+                    msg.respond(maybe_bound_url.map(|url| {
+                        RequestToChildResponse::Bind(BindResultData {
+                            bound_url: url.into(),
+                        })
+                    }))?;
+                }
+                RequestToChild::SendMessage { uri, payload, .. } => {
+                    if self.bound_url.is_none() {
+                        msg.respond(Err(TransportError::new(
+                            "Transport must be bound before sending".to_string(),
+                        )))?;
+                        continue;
+                    }
+                    if self.streams.connection_status(&uri) == ConnectionStatus::None {
+                        trace!("No open connection to {}, connecting...", uri);
+                        if let Err(error) = self.streams.connect(&uri) {
+                            msg.respond(Err(error))?;
+                            continue;
+                        }
+                    }
+                    msg.put_message(RequestToChild::create_send_message(uri, payload));
+                    self.pending.push(msg);
+                }
+                RequestToChild::RotateTlsIdentity { certificate } => {
+                    let result = self
+                        .streams
+                        .set_tls_certificate(certificate)
+                        .map(|()| RequestToChildResponse::RotateTlsIdentitySuccess);
+                    msg.respond(result)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn process_stream_events(&mut self, stream_events: Vec<StreamEvent>) -> TransportResult<()> {
+        for event in stream_events {
+            let span = new_root_span("StreamEvent");
+            let request = match event {
+                StreamEvent::ErrorOccured(uri, error) => {
+                    warn!(
+                        "Error in GhostTransportTcp connection to {}: {:?}",
+                        uri, error
+                    );
+                    RequestToParent::ErrorOccured {
+                        uri: uri.into(),
+                        error,
+                    }
+                }
+                StreamEvent::ConnectResult(uri, _) => {
+                    trace!("StreamEvent::ConnectResult: {}", uri);
+                    continue;
+                }
+                StreamEvent::IncomingConnectionEstablished(uri) => {
+                    RequestToParent::IncomingConnection { uri: uri.into() }
+                }
+                StreamEvent::ReceivedData(uri, payload) => RequestToParent::ReceivedData {
+                    uri: uri.into(),
+                    payload: Opaque::from(payload),
+                },
+                StreamEvent::ConnectionClosed(uri) => RequestToParent::Disconnect(uri.into()),
+                StreamEvent::ConnectionEvicted(uri) => RequestToParent::Evicted(uri.into()),
+            };
+            self.endpoint_self
+                .publish(span.child("publish tcp stream event"), request)?;
+        }
+        Ok(())
+    }
+
+    /// Send the messages whose connection is ready,
+    /// and fail those whose connection was lost
+    fn process_pending_messages(&mut self) -> TransportResult<()> {
+        for mut msg in self.pending.drain(..).collect::<Vec<_>>() {
+            let (uri, payload) = match msg.take_message().expect("exists") {
+                RequestToChild::SendMessage { uri, payload, .. } => (uri, payload),
+                _ => panic!("Found a non-SendMessage message in GhostTransportTcp::pending!"),
+            };
+            let status = self.streams.connection_status(&uri);
+            if status == ConnectionStatus::None {
+                msg.respond(Err(TransportError::new(format!(
+                    "connection to {} was lost",
+                    uri
+                ))))?;
+                continue;
+            }
+            msg.put_message(RequestToChild::create_send_message(uri, payload));
+            if status == ConnectionStatus::Ready {
+                self.handle_send_message(msg)?;
+            } else {
+                self.pending.push(msg);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub type GhostTransportTcpEndpoint = GhostEndpoint<
+    RequestToChild,
+    RequestToChildResponse,
+    RequestToParent,
+    RequestToParentResponse,
+    TransportError,
+>;
+
+pub type GhostTransportTcpEndpointContext = GhostContextEndpoint<
+    GhostTransportTcp,
+    RequestToParent,
+    RequestToParentResponse,
+    RequestToChild,
+    RequestToChildResponse,
+    TransportError,
+>;
+
+pub type GhostTransportTcpEndpointContextParent<T> = GhostContextEndpoint<
+    T,
+    RequestToChild,
+    RequestToChildResponse,
+    RequestToParent,
+    RequestToParentResponse,
+    TransportError,
+>;
+
+impl
+    GhostActor<
+        RequestToParent,
+        RequestToParentResponse,
+        RequestToChild,
+        RequestToChildResponse,
+        TransportError,
+    > for GhostTransportTcp
+{
+    fn take_parent_endpoint(&mut self) -> Option<GhostTransportTcpEndpoint> {
+        std::mem::replace(&mut self.endpoint_parent, None)
+    }
+
+    fn process_concrete(&mut self) -> GhostResult<WorkWasDone> {
+        detach_run!(self.endpoint_self, |endpoint_self| endpoint_self
+            .process(self))?;
+
+        self.process_actor_inbox()?;
+
+        if self.bound_url.is_none() {
+            return Ok(false.into());
+        }
+
+        let (did_work, stream_events) = self.streams.process()?;
+        self.process_stream_events(stream_events)?;
+        self.process_pending_messages()?;
+
+        Ok(did_work.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wait_for_bind_result;
+    use lib3h_ghost_actor::wait_for_message;
+    use lib3h_sodium::SodiumCryptoSystem;
+
+    /// Sends a message between two transports bound to `host`, `received` matching the uri
+    /// the message is received from
+    fn send_test(tls_config: TlsConfig, host: &str, received: &str) {
+        let mut transport1 = GhostTransportTcp::new(
+            "fake_node_id1".into(),
+            tls_config.clone(),
+            Box::new(SodiumCryptoSystem::new()),
+        );
+        let mut t1_endpoint: GhostTransportTcpEndpointContextParent<Option<String>> = transport1
+            .take_parent_endpoint()
+            .expect("exists")
+            .as_context_endpoint_builder()
+            .request_id_prefix("ttcp_to_child1")
+            .build::<Option<String>>();
+
+        let mut transport2 = GhostTransportTcp::new(
+            "fake_node_id2".into(),
+            tls_config,
+            Box::new(SodiumCryptoSystem::new()),
+        );
+        let mut t2_endpoint = transport2
+            .take_parent_endpoint()
+            .expect("exists")
+            .as_context_endpoint_builder()
+            .request_id_prefix("ttcp_to_child2")
+            .build::<Option<String>>();
+
+        let init_transport_address: Lib3hUri =
+            lib3h_protocol::uri::Builder::with_raw_url(format!("tcp://{}/", host).as_str())
+                .unwrap()
+                .with_port(4024)
+                .build();
+        let (_is_match, _transport1_address) =
+            wait_for_bind_result!(transport1, t1_endpoint, init_transport_address.clone());
+        let (_is_match, transport2_address) =
+            wait_for_bind_result!(transport2, t2_endpoint, init_transport_address);
+        assert_eq!(transport2.bound_url(), Some(transport2_address.clone()));
+
+        // connects on send
+        t1_endpoint
+            .request(
+                holochain_tracing::test_span(),
+                RequestToChild::create_send_message(
+                    transport2_address,
+                    b"test message".to_vec().into(),
+                ),
+                Box::new(|_: &mut _, r| {
+                    assert_eq!("Response(Ok(SendMessageSuccess))", &format!("{:?}", r));
+                    Ok(())
+                }),
+            )
+            .unwrap();
+
+        wait_for_message!(
+            vec![&mut transport1, &mut transport2],
+            t2_endpoint,
+            None,
+            &format!(
+                "ReceivedData \\{{ uri: Lib3hUri\\(\"tcp://{}:\\d+/\"\\), payload: \"test message\" \\}}",
+                received
+            )
+        );
+    }
+
+    #[test]
+    fn test_tcp_transport_send_message() {
+        send_test(TlsConfig::Unencrypted, "127.0.0.1", "127\\.0\\.0\\.1");
+    }
+
+    #[test]
+    fn test_tcp_transport_send_message_ipv6() {
+        send_test(TlsConfig::Unencrypted, "[::1]", "\\[::1\\]");
+    }
+
+    #[test]
+    fn test_tcp_transport_send_message_tls() {
+        send_test(TlsConfig::FakeServer, "127.0.0.1", "127\\.0\\.0\\.1");
+    }
+}
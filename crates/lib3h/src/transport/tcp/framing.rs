@@ -0,0 +1,97 @@
+//! Length-prefixed framing of messages over a byte stream
+
+use crate::transport::error::{TransportError, TransportResult};
+
+/// Size of the length prefix of a frame
+pub const FRAME_HEADER_SIZE: usize = 4;
+
+/// Largest payload a frame may carry, bigger frames are a protocol error
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Prefix `payload` with its length
+pub fn encode_frame(payload: &[u8]) -> TransportResult<Vec<u8>> {
+    if payload.len() > MAX_FRAME_SIZE {
+        return Err(TransportError::new(format!(
+            "cannot send a frame of {} bytes, the maximum is {}",
+            payload.len(),
+            MAX_FRAME_SIZE
+        )));
+    }
+    let mut frame = Vec::with_capacity(FRAME_HEADER_SIZE + payload.len());
+    frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    frame.extend_from_slice(payload);
+    Ok(frame)
+}
+
+/// Reassembles frames from the bytes read from a stream
+#[derive(Debug, Default)]
+pub struct FrameReader {
+    buffer: Vec<u8>,
+}
+
+impl FrameReader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// The payload of the next complete frame, if we read it all
+    pub fn next_frame(&mut self) -> TransportResult<Option<Vec<u8>>> {
+        if self.buffer.len() < FRAME_HEADER_SIZE {
+            return Ok(None);
+        }
+        let mut header = [0; FRAME_HEADER_SIZE];
+        header.copy_from_slice(&self.buffer[..FRAME_HEADER_SIZE]);
+        let size = u32::from_be_bytes(header) as usize;
+        if size > MAX_FRAME_SIZE {
+            return Err(TransportError::new(format!(
+                "received a frame of {} bytes, the maximum is {}",
+                size, MAX_FRAME_SIZE
+            )));
+        }
+        if self.buffer.len() < FRAME_HEADER_SIZE + size {
+            return Ok(None);
+        }
+        let frame = self.buffer[FRAME_HEADER_SIZE..FRAME_HEADER_SIZE + size].to_vec();
+        self.buffer.drain(..FRAME_HEADER_SIZE + size);
+        Ok(Some(frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_reassemble_split_frames() {
+        let mut bytes = encode_frame(b"hello").unwrap();
+        bytes.extend(encode_frame(b"").unwrap());
+        bytes.extend(encode_frame(b"world").unwrap());
+        assert_eq!(&[0, 0, 0, 5], &bytes[..FRAME_HEADER_SIZE]);
+
+        let mut reader = FrameReader::new();
+        let mut frames = Vec::new();
+        // one byte at a time is as split as it gets
+        for byte in bytes {
+            reader.push(&[byte]);
+            while let Some(frame) = reader.next_frame().unwrap() {
+                frames.push(frame);
+            }
+        }
+        assert_eq!(
+            vec![b"hello".to_vec(), Vec::new(), b"world".to_vec()],
+            frames
+        );
+    }
+
+    #[test]
+    fn it_should_reject_oversized_frames() {
+        assert!(encode_frame(&vec![0; MAX_FRAME_SIZE + 1]).is_err());
+        let mut reader = FrameReader::new();
+        reader.push(&((MAX_FRAME_SIZE + 1) as u32).to_be_bytes());
+        assert!(reader.next_frame().is_err());
+    }
+}
@@ -0,0 +1,10 @@
+//! Transport implementation that sends messages over raw TCP connections,
+//! optionally encrypted with TLS, without the websocket upgrade overhead.
+//!
+//! Each message is sent as one frame: its length as a big endian u32,
+//! followed by that many bytes of payload.
+//! The ghost actor in actor::GhostTransportTcp wraps streams::TcpStreamManager,
+//! which implements the connection pool.
+pub mod actor;
+pub mod framing;
+pub mod streams;
@@ -0,0 +1,399 @@
+//! Connection pool of non-blocking TCP streams exchanging frames
+
+use crate::transport::{
+    error::{TransportError, TransportResult},
+    tcp::framing::{encode_frame, FrameReader},
+    websocket::{
+        streams::{ConnectionStatus, StreamEvent},
+        tls::{TlsCertificate, TlsConfig},
+    },
+};
+use lib3h_crypto_api::CryptoSystem;
+use lib3h_protocol::DidWork;
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+};
+use url::{Host, Url};
+
+/// How many bytes we read from a stream at a time
+const READ_BUFFER_SIZE: usize = 4096;
+
+/// The host of `url` the way sockets want it, IPv6 addresses without their brackets
+fn socket_host(url: &Url) -> Option<String> {
+    match url.host()? {
+        Host::Ipv6(ip) => Some(ip.to_string()),
+        host => Some(host.to_string()),
+    }
+}
+
+type TlsHandshakeResult =
+    Result<native_tls::TlsStream<TcpStream>, native_tls::HandshakeError<TcpStream>>;
+
+/// A stream that is ready to exchange frames
+#[derive(Debug)]
+enum TcpSocket {
+    Plain(TcpStream),
+    Tls(Box<native_tls::TlsStream<TcpStream>>),
+}
+
+impl Read for TcpSocket {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            TcpSocket::Plain(socket) => socket.read(buf),
+            TcpSocket::Tls(socket) => socket.read(buf),
+        }
+    }
+}
+
+impl Write for TcpSocket {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            TcpSocket::Plain(socket) => socket.write(buf),
+            TcpSocket::Tls(socket) => socket.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            TcpSocket::Plain(socket) => socket.flush(),
+            TcpSocket::Tls(socket) => socket.flush(),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum TcpStreamState {
+    TlsMidHandshake(native_tls::MidHandshakeTlsStream<TcpStream>),
+    Ready(TcpSocket),
+}
+
+/// Represents an individual connection
+#[derive(Debug)]
+struct TcpConnection {
+    url: Url,
+    /// did the remote connect to us
+    is_server: bool,
+    /// None once the connection closed
+    state: Option<TcpStreamState>,
+    frames: FrameReader,
+    /// bytes the stream would not take yet
+    outgoing: Vec<u8>,
+}
+
+/// Pool of TCP connections exchanging length-prefixed frames
+pub struct TcpStreamManager {
+    tls_config: TlsConfig,
+    /// verifies the node bindings of tls certificates
+    crypto: Box<dyn CryptoSystem>,
+    listener: Option<(String, TcpListener)>,
+    connections: HashMap<Url, TcpConnection>,
+    event_queue: Vec<StreamEvent>,
+}
+
+impl TcpStreamManager {
+    pub fn new(tls_config: TlsConfig, crypto: Box<dyn CryptoSystem>) -> Self {
+        TcpStreamManager {
+            tls_config,
+            crypto,
+            listener: None,
+            connections: HashMap::new(),
+            event_queue: Vec::new(),
+        }
+    }
+
+    /// listen for connections on the host and port of `url`,
+    /// returns the url we actually bound to
+    pub fn bind(&mut self, url: &Url) -> TransportResult<Url> {
+        let host = socket_host(url).ok_or_else(|| TransportError::new("bad bind host".into()))?;
+        let listener = TcpListener::bind((host.as_str(), url.port().unwrap_or(0)))?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let bound_url = Url::parse(&format!("{}://{}/", url.scheme(), local_addr))?;
+        trace!("tcp bound to {}", bound_url);
+        self.listener = Some((url.scheme().to_string(), listener));
+        Ok(bound_url)
+    }
+
+    /// connect to a remote tcp transport
+    pub fn connect(&mut self, url: &Url) -> TransportResult<()> {
+        let host =
+            socket_host(url).ok_or_else(|| TransportError::new("bad connect host".into()))?;
+        let port = url
+            .port()
+            .ok_or_else(|| TransportError::new("bad connect port".into()))?;
+        let socket = TcpStream::connect((host.as_str(), port))?;
+        socket.set_nonblocking(true)?;
+        socket.set_nodelay(true)?;
+        let mut connection = TcpConnection {
+            url: url.clone(),
+            is_server: false,
+            state: None,
+            frames: FrameReader::new(),
+            outgoing: Vec::new(),
+        };
+        if let TlsConfig::Unencrypted = self.tls_config {
+            connection.state = Some(TcpStreamState::Ready(TcpSocket::Plain(socket)));
+            self.priv_on_ready(&connection);
+        } else {
+            let connector = self.tls_config.build_connector()?;
+            self.priv_tls_handshake(&mut connection, connector.connect(&host, socket))?;
+        }
+        self.connections.insert(url.clone(), connection);
+        Ok(())
+    }
+
+    /// present `certificate` on the tls connections we accept from now on,
+    /// established connections keep theirs
+    pub fn set_tls_certificate(&mut self, certificate: TlsCertificate) -> TransportResult<()> {
+        self.tls_config.set_certificate(certificate)
+    }
+
+    pub fn connection_status(&self, url: &Url) -> ConnectionStatus {
+        self.connections
+            .get(url)
+            .map(|connection| match connection.state {
+                Some(TcpStreamState::Ready(_)) => ConnectionStatus::Ready,
+                _ => ConnectionStatus::Initializing,
+            })
+            .unwrap_or(ConnectionStatus::None)
+    }
+
+    /// queue `payload` as a frame to a connected remote, and send what we can of it
+    pub fn send(&mut self, url: &Url, payload: &[u8]) -> TransportResult<()> {
+        let frame = encode_frame(payload)?;
+        let connection = self
+            .connections
+            .get_mut(url)
+            .ok_or_else(|| format!("No socket found for URL: {}", url))?;
+        match &mut connection.state {
+            Some(TcpStreamState::Ready(socket)) => {
+                connection.outgoing.extend(frame);
+                priv_flush(socket, &mut connection.outgoing)?;
+                Ok(())
+            }
+            _ => Err(TransportError::new("tcp stream not in Ready state".into())),
+        }
+    }
+
+    /// close a currently tracked connection
+    pub fn close(&mut self, url: &Url) -> TransportResult<()> {
+        if let Some(connection) = self.connections.remove(url) {
+            priv_shutdown(connection)?;
+        }
+        Ok(())
+    }
+
+    /// close all currently tracked connections
+    pub fn close_all(&mut self) -> TransportResult<()> {
+        let errors: Vec<TransportError> = self
+            .connections
+            .drain()
+            .filter_map(|(_, connection)| priv_shutdown(connection).err())
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.into())
+        }
+    }
+
+    /// this should be called frequently on the event loop,
+    /// accepts connections, drives handshakes and reads and writes frames
+    pub fn process(&mut self) -> TransportResult<(DidWork, Vec<StreamEvent>)> {
+        let mut did_work = self.priv_process_accept()?;
+
+        // take connections out, so we can mut ref into self and them at same time
+        let connections: Vec<(Url, TcpConnection)> = self.connections.drain().collect();
+        for (url, mut connection) in connections {
+            match self.priv_process_connection(&mut connection) {
+                Ok(connection_did_work) => did_work |= connection_did_work,
+                Err(e) => {
+                    did_work = true;
+                    self.event_queue
+                        .push(StreamEvent::ErrorOccured(url.clone(), e));
+                    connection.state = None;
+                }
+            }
+            if connection.state.is_none() {
+                self.event_queue.push(StreamEvent::ConnectionClosed(url));
+                continue;
+            }
+            self.connections.insert(url, connection);
+        }
+
+        Ok((did_work, self.event_queue.drain(..).collect()))
+    }
+
+    // -- private -- //
+
+    fn priv_process_accept(&mut self) -> TransportResult<DidWork> {
+        let mut accepted = Vec::new();
+        if let Some((scheme, listener)) = &self.listener {
+            loop {
+                match listener.accept() {
+                    Ok((socket, address)) => {
+                        let url = Url::parse(&format!("{}://{}/", scheme, address))?;
+                        trace!("tcp accepted connection from {}", url);
+                        accepted.push((url, socket));
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                    Err(e) => {
+                        error!("tcp accept error: {:?}", e);
+                        return Err(e.into());
+                    }
+                }
+            }
+        }
+        let did_work = !accepted.is_empty();
+        for (url, socket) in accepted {
+            socket.set_nonblocking(true)?;
+            socket.set_nodelay(true)?;
+            let mut connection = TcpConnection {
+                url: url.clone(),
+                is_server: true,
+                state: None,
+                frames: FrameReader::new(),
+                outgoing: Vec::new(),
+            };
+            if let TlsConfig::Unencrypted = self.tls_config {
+                connection.state = Some(TcpStreamState::Ready(TcpSocket::Plain(socket)));
+                self.priv_on_ready(&connection);
+            } else {
+                let acceptor =
+                    native_tls::TlsAcceptor::builder(self.tls_config.get_identity()?).build()?;
+                if let Err(e) = self.priv_tls_handshake(&mut connection, acceptor.accept(socket)) {
+                    // a failed handshake only costs us this connection
+                    self.event_queue.push(StreamEvent::ErrorOccured(url, e));
+                    continue;
+                }
+            }
+            self.connections.insert(url, connection);
+        }
+        Ok(did_work)
+    }
+
+    fn priv_process_connection(
+        &mut self,
+        connection: &mut TcpConnection,
+    ) -> TransportResult<DidWork> {
+        match connection.state.take() {
+            None => Ok(false),
+            Some(TcpStreamState::TlsMidHandshake(socket)) => {
+                self.priv_tls_handshake(connection, socket.handshake())?;
+                Ok(true)
+            }
+            Some(TcpStreamState::Ready(mut socket)) => {
+                let mut did_work = priv_flush(&mut socket, &mut connection.outgoing)?;
+                let mut buf = [0; READ_BUFFER_SIZE];
+                let mut is_closed = false;
+                loop {
+                    match socket.read(&mut buf) {
+                        Ok(0) => {
+                            trace!("tcp connection to {} closed by remote", connection.url);
+                            is_closed = true;
+                            break;
+                        }
+                        Ok(size) => {
+                            did_work = true;
+                            connection.frames.push(&buf[..size]);
+                        }
+                        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                        Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+                while let Some(frame) = connection.frames.next_frame()? {
+                    self.event_queue
+                        .push(StreamEvent::ReceivedData(connection.url.clone(), frame));
+                }
+                if !is_closed {
+                    connection.state = Some(TcpStreamState::Ready(socket));
+                }
+                Ok(did_work)
+            }
+        }
+    }
+
+    // move a connection along its tls handshake
+    fn priv_tls_handshake(
+        &mut self,
+        connection: &mut TcpConnection,
+        res: TlsHandshakeResult,
+    ) -> TransportResult<()> {
+        match res {
+            Err(native_tls::HandshakeError::WouldBlock(socket)) => {
+                connection.state = Some(TcpStreamState::TlsMidHandshake(socket));
+            }
+            Err(native_tls::HandshakeError::Failure(e)) if !connection.is_server => {
+                return Err(self.tls_config.handshake_failure(e))
+            }
+            Err(e) => return Err(e.into()),
+            Ok(socket) => {
+                if !connection.is_server {
+                    self.tls_config
+                        .verify_peer(self.crypto.as_crypto_system(), &socket)?;
+                }
+                connection.state = Some(TcpStreamState::Ready(TcpSocket::Tls(Box::new(socket))));
+                self.priv_on_ready(connection);
+            }
+        }
+        Ok(())
+    }
+
+    fn priv_on_ready(&mut self, connection: &TcpConnection) {
+        self.event_queue.push(if connection.is_server {
+            StreamEvent::IncomingConnectionEstablished(connection.url.clone())
+        } else {
+            StreamEvent::ConnectResult(connection.url.clone(), String::new())
+        });
+    }
+}
+
+// write as much of `outgoing` as the socket takes without blocking
+fn priv_flush(socket: &mut TcpSocket, outgoing: &mut Vec<u8>) -> TransportResult<DidWork> {
+    let mut did_work = false;
+    while !outgoing.is_empty() {
+        match socket.write(outgoing) {
+            Ok(0) => {
+                return Err(TransportError::new(
+                    "tcp stream closed while writing".into(),
+                ))
+            }
+            Ok(size) => {
+                did_work = true;
+                outgoing.drain(..size);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    if did_work {
+        if let Err(e) = socket.flush() {
+            if e.kind() != std::io::ErrorKind::WouldBlock {
+                return Err(e.into());
+            }
+        }
+    }
+    Ok(did_work)
+}
+
+fn priv_shutdown(connection: TcpConnection) -> TransportResult<()> {
+    match connection.state {
+        Some(TcpStreamState::Ready(TcpSocket::Plain(socket))) => {
+            if let Err(e) = socket.shutdown(std::net::Shutdown::Both) {
+                if e.kind() != std::io::ErrorKind::NotConnected {
+                    return Err(e.into());
+                }
+            }
+        }
+        Some(TcpStreamState::Ready(TcpSocket::Tls(mut socket))) => {
+            // the peer may already be gone, we are closing anyway
+            let _ = socket.shutdown();
+        }
+        _ => (),
+    }
+    Ok(())
+}
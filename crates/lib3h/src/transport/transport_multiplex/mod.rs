@@ -17,6 +17,13 @@
 //! if it is any other p2p_proto message, it will be forwarded to
 //! the engine or network gateway. If it is a direct message, it will be
 //! sent to the appropriate Route / AgentSpaceGateway
+//!
+//! Between nodes that both speak the multiplex protocol, the network gateway
+//! does not send that DirectMessage as is: it creates a channel for
+//! ("Qmyada", "a2", "a1") once, and then only sends the channel id along with
+//! the payload. The receiving network gateway puts the DirectMessage back
+//! together. If there is no route for it, the channel is rejected with a
+//! `badSpaceHash` or `badToId` error.
 
 mod mplex;
 pub use mplex::TransportMultiplex;
@@ -24,7 +31,11 @@ pub use mplex::TransportMultiplex;
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{error::Lib3hError, gateway::protocol::*, transport::protocol::*};
+    use crate::{
+        error::{ErrorKind, Lib3hError},
+        gateway::protocol::*,
+        transport::protocol::*,
+    };
     use detach::prelude::*;
     use holochain_persistence_api::hash::HashString;
     use lib3h_ghost_actor::prelude::*;
@@ -101,6 +112,7 @@ mod tests {
                                 RequestToChildResponse::SendMessageSuccess,
                             )))?;
                         }
+                        RequestToChild::RotateTlsIdentity { .. } => unimplemented!(),
                     },
                     _ => unimplemented!(),
                 }
@@ -220,5 +232,37 @@ mod tests {
         } else {
             panic!("bad type");
         }
+
+        // no route in that space at all
+        let err = mplex
+            .as_mut()
+            .received_data_for_agent_space_route(
+                &"space_z".into(),
+                &"agent_b".into(),
+                &"agent_x".into(),
+                "hello".into(),
+            )
+            .unwrap_err();
+        match err.kind() {
+            ErrorKind::BadSpaceHash(space_address) => {
+                assert_eq!("space_z", space_address.to_string())
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+
+        // no route for that agent
+        let err = mplex
+            .as_mut()
+            .received_data_for_agent_space_route(
+                &"space_b".into(),
+                &"agent_z".into(),
+                &"agent_x".into(),
+                "hello".into(),
+            )
+            .unwrap_err();
+        match err.kind() {
+            ErrorKind::BadToId(agent_id) => assert_eq!("agent_z", agent_id.to_string()),
+            other => panic!("unexpected error {:?}", other),
+        }
     }
 }
@@ -1,5 +1,5 @@
 use crate::{
-    error::{Lib3hError, Lib3hResult},
+    error::{ErrorKind, Lib3hError, Lib3hResult},
     gateway::protocol::*,
     new_root_span,
     transport::{error::*, protocol::*},
@@ -121,6 +121,8 @@ impl<
     /// these at this level are intended to be forwarded up to our routes.
     /// Collect all the un-packed info that will let us pass it back up the
     /// tree.
+    /// Fails with `BadSpaceHash` if we have no route in that space at all,
+    /// or `BadToId` if none of them is for `local_agent_id`.
     pub fn received_data_for_agent_space_route(
         &mut self,
         space_address: &SpaceHash,
@@ -133,11 +135,17 @@ impl<
             local_agent_id: local_agent_id.clone(),
         };
         let path = Lib3hUri::with_agent_id(remote_agent_id);
+        if !self
+            .route_endpoints
+            .keys()
+            .any(|spec| &spec.space_address == space_address)
+        {
+            return Err(Lib3hError::new(ErrorKind::BadSpaceHash(
+                space_address.clone(),
+            )));
+        }
         match self.route_endpoints.get_mut(&route_spec) {
-            None => Err(Lib3hError::new_other(&format!(
-                "no such route: {:?}",
-                route_spec
-            ))),
+            None => Err(Lib3hError::new(ErrorKind::BadToId(local_agent_id.clone()))),
             Some(ep) => {
                 let mut span = new_root_span("multiplexer ReceivedData");
                 span.set_tag(|| Tag::new("from", path.clone().to_string()));
@@ -224,25 +232,31 @@ impl<
         >,
     ) -> Lib3hResult<()> {
         match msg.take_message().expect("exists") {
-            RequestToChild::Bind { spec } => self.handle_route_bind(msg, spec),
-            RequestToChild::SendMessage { uri, payload, .. } => {
+            request @ RequestToChild::Bind { .. }
+            | request @ RequestToChild::RotateTlsIdentity { .. } => {
+                self.handle_route_forward(msg, request)
+            }
+            RequestToChild::SendMessage {
+                uri,
+                payload,
+                reliable,
+            } => {
                 debug!("handle_route_send to {}", uri.clone());
-                self.handle_route_send_message(msg, uri, payload)
+                self.handle_route_send_message(msg, uri, payload, reliable)
             }
         }
     }
 
-    /// private handler for Bind requests from a route
-    fn handle_route_bind(
+    /// private handler for requests from a route our inner_gateway answers
+    fn handle_route_forward(
         &mut self,
         msg: GhostMessage<RequestToChild, RequestToParent, RequestToChildResponse, TransportError>,
-        spec: Lib3hUri,
+        request: RequestToChild,
     ) -> Lib3hResult<()> {
-        // forward the bind to our inner_gateway
+        // forward the request to our inner_gateway
         self.inner_gateway.as_mut().request(
-            msg.span()
-                .child("request GatewayRequestToChild::Transport::Bind"),
-            GatewayRequestToChild::Transport(RequestToChild::Bind { spec }),
+            msg.span().child("request GatewayRequestToChild::Transport"),
+            GatewayRequestToChild::Transport(request),
             Box::new(|_, response| {
                 let response = {
                     match response {
@@ -278,12 +292,17 @@ impl<
         msg: GhostMessage<RequestToChild, RequestToParent, RequestToChildResponse, TransportError>,
         uri: Lib3hUri,
         payload: Opaque,
+        reliable: bool,
     ) -> Lib3hResult<()> {
         // forward the request to our inner_gateway
         self.inner_gateway.as_mut().request(
             msg.span()
                 .child("request GatewayRequestToChild::Transport::SendMessage"),
-            GatewayRequestToChild::Transport(RequestToChild::create_send_message(uri, payload)),
+            GatewayRequestToChild::Transport(RequestToChild::SendMessage {
+                uri,
+                payload,
+                reliable,
+            }),
             Box::new(|_, response| {
                 let response = {
                     match response {
@@ -0,0 +1,421 @@
+use crate::{
+    new_root_span,
+    transport::{
+        error::{TransportError, TransportResult},
+        protocol::*,
+        udp::{
+            packet::Packet,
+            reliability::{UdpPeer, MAX_REASSEMBLIES},
+        },
+    },
+};
+use detach::Detach;
+use lib3h_ghost_actor::prelude::*;
+use lib3h_protocol::{data_types::Opaque, types::*, uri::Lib3hUri};
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
+use url::Url;
+
+pub type Message =
+    GhostMessage<RequestToChild, RequestToParent, RequestToChildResponse, TransportError>;
+
+/// Largest datagram we can receive
+const RECV_BUFFER_SIZE: usize = 65536;
+
+/// A peer we have not heard from for this long is considered disconnected
+pub const PEER_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct GhostTransportUdp {
+    #[allow(dead_code)]
+    node_id: NodePubKey,
+    endpoint_parent: Option<GhostTransportUdpEndpoint>,
+    endpoint_self: Detach<GhostTransportUdpEndpointContext>,
+    socket: Option<UdpSocket>,
+    bound_url: Option<Lib3hUri>,
+    scheme: String,
+    peers: HashMap<SocketAddr, UdpPeer>,
+    /// reliable SendMessage requests waiting for the peer's acks,
+    /// keyed by peer address and message id
+    pending: HashMap<(SocketAddr, u32), Message>,
+}
+
+impl GhostTransportUdp {
+    pub fn new(node_id: NodePubKey) -> GhostTransportUdp {
+        let (endpoint_parent, endpoint_self) = create_ghost_channel();
+        GhostTransportUdp {
+            node_id,
+            endpoint_parent: Some(endpoint_parent),
+            endpoint_self: Detach::new(
+                endpoint_self
+                    .as_context_endpoint_builder()
+                    .request_id_prefix("tudp_to_parent")
+                    .build(),
+            ),
+            socket: None,
+            bound_url: None,
+            scheme: String::new(),
+            peers: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn bound_url(&self) -> Option<Lib3hUri> {
+        self.bound_url.clone()
+    }
+
+    fn priv_bind(&mut self, url: &Url) -> TransportResult<Url> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| TransportError::new("bad bind host".into()))?;
+        let socket = UdpSocket::bind((host, url.port().unwrap_or(0)))?;
+        socket.set_nonblocking(true)?;
+        let local_addr = socket.local_addr()?;
+        let bound_url = Url::parse(&format!("{}://{}/", url.scheme(), local_addr))?;
+        trace!("udp bound to {}", bound_url);
+        self.socket = Some(socket);
+        self.scheme = url.scheme().to_string();
+        Ok(bound_url)
+    }
+
+    fn handle_send_message(
+        &mut self,
+        msg: Message,
+        uri: Lib3hUri,
+        payload: Opaque,
+        reliable: bool,
+    ) -> TransportResult<()> {
+        if self.socket.is_none() {
+            msg.respond(Err(TransportError::new(
+                "Transport must be bound before sending".to_string(),
+            )))?;
+            return Ok(());
+        }
+        let address = match resolve(&uri) {
+            Ok(address) => address,
+            Err(error) => {
+                msg.respond(Err(error))?;
+                return Ok(());
+            }
+        };
+        trace!(
+            "(GhostTransportUdp).SendMessage to {} (reliable: {}) | {:?}",
+            uri,
+            reliable,
+            payload
+        );
+        let peer = self
+            .peers
+            .entry(address)
+            .or_insert_with(|| UdpPeer::new(Instant::now()));
+        if reliable {
+            match peer.send_reliable(&payload.as_bytes()) {
+                Ok(message_id) => {
+                    self.pending.insert((address, message_id), msg);
+                }
+                Err(error) => msg.respond(Err(error))?,
+            }
+        } else {
+            // we are done with it as soon as it is queued
+            let result = peer
+                .send_unreliable(&payload.as_bytes())
+                .map(|()| RequestToChildResponse::SendMessageSuccess);
+            msg.respond(result)?;
+        }
+        Ok(())
+    }
+
+    fn process_actor_inbox(&mut self) -> TransportResult<()> {
+        for mut msg in self.endpoint_self.drain_messages() {
+            match msg.take_message().expect("exists") {
+                RequestToChild::Bind { spec: url } => {
+                    let maybe_bound_url = self.priv_bind(&url);
+                    if let Ok(url) = &maybe_bound_url {
+                        self.bound_url = Some(url.clone().into());
+                    }
+                    msg.respond(maybe_bound_url.map(|url| {
+                        RequestToChildResponse::Bind(BindResultData {
+                            bound_url: url.into(),
+                        })
+                    }))?;
+                }
+                RequestToChild::SendMessage {
+                    uri,
+                    payload,
+                    reliable,
+                } => self.handle_send_message(msg, uri, payload, reliable)?,
+                RequestToChild::RotateTlsIdentity { .. } => {
+                    msg.respond(Err(TransportError::new(
+                        "the udp transport has no tls identity".to_string(),
+                    )))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand every datagram waiting on the socket to its peer,
+    /// bounding the messages reassembled across peers
+    fn process_socket(&mut self, now: Instant) -> TransportResult<bool> {
+        let socket = self.socket.as_ref().expect("bound");
+        let mut buffer = vec![0; RECV_BUFFER_SIZE];
+        let mut did_work = false;
+        let mut reassemblies: usize = self.peers.values().map(UdpPeer::reassemblies).sum();
+        loop {
+            let (size, address) = match socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    // e.g. an ICMP port unreachable for an earlier send,
+                    // we will read what is left on our next process
+                    debug!("udp recv error: {:?}", e);
+                    break;
+                }
+            };
+            did_work = true;
+            let packet = match Packet::decode(&buffer[..size]) {
+                Ok(packet) => packet,
+                Err(e) => {
+                    debug!("dropping bad udp packet from {}: {:?}", address, e);
+                    continue;
+                }
+            };
+            if !self.peers.contains_key(&address) {
+                self.peers.insert(address, UdpPeer::new(now));
+                self.endpoint_self.publish(
+                    new_root_span("udp incoming connection"),
+                    RequestToParent::IncomingConnection {
+                        uri: peer_uri(&self.scheme, &address),
+                    },
+                )?;
+            }
+            let peer = self.peers.get_mut(&address).expect("exists");
+            let peer_reassemblies = peer.reassemblies();
+            peer.receive(packet, now, reassemblies < MAX_REASSEMBLIES);
+            reassemblies = reassemblies - peer_reassemblies + peer.reassemblies();
+        }
+        Ok(did_work)
+    }
+
+    /// Let every peer send what it can, deliver what it received,
+    /// and forget the peers that failed or went silent
+    fn process_peers(&mut self, now: Instant) -> TransportResult<bool> {
+        let socket = self.socket.as_ref().expect("bound");
+        let mut did_work = false;
+        let mut lost = Vec::new();
+        for (address, peer) in self.peers.iter_mut() {
+            peer.process(now);
+            for datagram in peer.drain_datagrams() {
+                did_work = true;
+                // a datagram the socket cannot take now is just lost,
+                // reliable ones will be retransmitted
+                if let Err(e) = socket.send_to(&datagram, address) {
+                    debug!("udp send to {} failed: {:?}", address, e);
+                }
+            }
+            let uri = peer_uri(&self.scheme, address);
+            for payload in peer.drain_received() {
+                did_work = true;
+                self.endpoint_self.publish(
+                    new_root_span("udp received data"),
+                    RequestToParent::ReceivedData {
+                        uri: uri.clone(),
+                        payload: Opaque::from(payload),
+                    },
+                )?;
+            }
+            for message_id in peer.drain_acked() {
+                if let Some(msg) = self.pending.remove(&(*address, message_id)) {
+                    msg.respond(Ok(RequestToChildResponse::SendMessageSuccess))?;
+                }
+            }
+            if peer.has_failed()
+                || (peer.unacked_messages().is_empty() && peer.idle_for(now) >= PEER_IDLE_TIMEOUT)
+            {
+                lost.push(*address);
+            }
+        }
+        for address in lost {
+            did_work = true;
+            let uri = peer_uri(&self.scheme, &address);
+            debug!("udp peer {} lost", uri);
+            let peer = self.peers.remove(&address).expect("exists");
+            for message_id in peer.unacked_messages() {
+                if let Some(msg) = self.pending.remove(&(address, message_id)) {
+                    msg.respond(Err(TransportError::new(format!(
+                        "{} did not ack our message",
+                        uri
+                    ))))?;
+                }
+            }
+            self.endpoint_self.publish(
+                new_root_span("udp peer lost"),
+                RequestToParent::Disconnect(uri),
+            )?;
+        }
+        Ok(did_work)
+    }
+}
+
+fn resolve(uri: &Lib3hUri) -> TransportResult<SocketAddr> {
+    let host = uri
+        .host_str()
+        .ok_or_else(|| TransportError::new("bad send host".into()))?;
+    let port = uri
+        .port()
+        .ok_or_else(|| TransportError::new("bad send port".into()))?;
+    (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| TransportError::new(format!("could not resolve {}", uri)))
+}
+
+fn peer_uri(scheme: &str, address: &SocketAddr) -> Lib3hUri {
+    Url::parse(&format!("{}://{}/", scheme, address))
+        .expect("socket addresses make valid urls")
+        .into()
+}
+
+pub type GhostTransportUdpEndpoint = GhostEndpoint<
+    RequestToChild,
+    RequestToChildResponse,
+    RequestToParent,
+    RequestToParentResponse,
+    TransportError,
+>;
+
+pub type GhostTransportUdpEndpointContext = GhostContextEndpoint<
+    GhostTransportUdp,
+    RequestToParent,
+    RequestToParentResponse,
+    RequestToChild,
+    RequestToChildResponse,
+    TransportError,
+>;
+
+pub type GhostTransportUdpEndpointContextParent<T> = GhostContextEndpoint<
+    T,
+    RequestToChild,
+    RequestToChildResponse,
+    RequestToParent,
+    RequestToParentResponse,
+    TransportError,
+>;
+
+impl
+    GhostActor<
+        RequestToParent,
+        RequestToParentResponse,
+        RequestToChild,
+        RequestToChildResponse,
+        TransportError,
+    > for GhostTransportUdp
+{
+    fn take_parent_endpoint(&mut self) -> Option<GhostTransportUdpEndpoint> {
+        std::mem::replace(&mut self.endpoint_parent, None)
+    }
+
+    fn process_concrete(&mut self) -> GhostResult<WorkWasDone> {
+        detach_run!(self.endpoint_self, |endpoint_self| endpoint_self
+            .process(self))?;
+
+        self.process_actor_inbox()?;
+
+        if self.socket.is_none() {
+            return Ok(false.into());
+        }
+
+        let now = Instant::now();
+        let received = self.process_socket(now)?;
+        let sent = self.process_peers(now)?;
+
+        Ok((received || sent).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{transport::udp::packet::MAX_FRAGMENT_SIZE, wait_for_bind_result};
+    use lib3h_ghost_actor::wait_for_message;
+
+    #[test]
+    fn test_udp_transport_send_message() {
+        let mut transport1 = GhostTransportUdp::new("fake_node_id1".into());
+        let mut t1_endpoint: GhostTransportUdpEndpointContextParent<Option<String>> = transport1
+            .take_parent_endpoint()
+            .expect("exists")
+            .as_context_endpoint_builder()
+            .request_id_prefix("tudp_to_child1")
+            .build::<Option<String>>();
+
+        let mut transport2 = GhostTransportUdp::new("fake_node_id2".into());
+        let mut t2_endpoint = transport2
+            .take_parent_endpoint()
+            .expect("exists")
+            .as_context_endpoint_builder()
+            .request_id_prefix("tudp_to_child2")
+            .build::<Option<String>>();
+
+        let init_transport_address: Lib3hUri =
+            lib3h_protocol::uri::Builder::with_raw_url("udp://127.0.0.1/")
+                .unwrap()
+                .with_port(4124)
+                .build();
+        let (_is_match, _transport1_address) =
+            wait_for_bind_result!(transport1, t1_endpoint, init_transport_address.clone());
+        let (_is_match, transport2_address) =
+            wait_for_bind_result!(transport2, t2_endpoint, init_transport_address);
+        assert_eq!(transport2.bound_url(), Some(transport2_address.clone()));
+
+        // several fragments worth of reliable message
+        let big_payload = "x".repeat(MAX_FRAGMENT_SIZE * 4);
+        t1_endpoint
+            .request(
+                holochain_tracing::test_span(),
+                RequestToChild::create_send_message(
+                    transport2_address.clone(),
+                    big_payload.as_bytes().to_vec().into(),
+                ),
+                Box::new(|_: &mut _, r| {
+                    assert_eq!("Response(Ok(SendMessageSuccess))", &format!("{:?}", r));
+                    Ok(())
+                }),
+            )
+            .unwrap();
+        t1_endpoint
+            .request(
+                holochain_tracing::test_span(),
+                RequestToChild::create_unreliable_send_message(
+                    transport2_address,
+                    b"unreliable message".to_vec().into(),
+                ),
+                Box::new(|_: &mut _, r| {
+                    assert_eq!("Response(Ok(SendMessageSuccess))", &format!("{:?}", r));
+                    Ok(())
+                }),
+            )
+            .unwrap();
+
+        let big_payload_regex = format!(
+            "ReceivedData \\{{ uri: Lib3hUri\\(\"udp://127\\.0\\.0\\.1:\\d+/\"\\), payload: \"x{{{}}}\" \\}}",
+            MAX_FRAGMENT_SIZE * 4
+        );
+        wait_for_message!(
+            vec![&mut transport1, &mut transport2],
+            t2_endpoint,
+            None,
+            big_payload_regex.as_str()
+        );
+        // sent once the reliable message before it was acked
+        wait_for_message!(
+            vec![&mut transport1, &mut transport2],
+            t2_endpoint,
+            None,
+            "ReceivedData \\{ uri: Lib3hUri\\(\"udp://127\\.0\\.0\\.1:\\d+/\"\\), payload: \"unreliable message\" \\}"
+        );
+    }
+}
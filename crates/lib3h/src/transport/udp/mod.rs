@@ -0,0 +1,11 @@
+//! Connectionless transport implementation that sends messages as UDP
+//! datagrams from a single socket.
+//!
+//! Messages sent with `reliable: false` are fragmented and sent once.
+//! Reliable messages are acked, retransmitted and delivered in order per
+//! peer, within a congestion window; see reliability::UdpPeer.
+//! The ghost actor in actor::GhostTransportUdp keeps one UdpPeer per
+//! remote socket address.
+pub mod actor;
+pub mod packet;
+pub mod reliability;
@@ -0,0 +1,233 @@
+//! Wire format of the datagrams exchanged by the UDP transport
+
+use crate::transport::error::{TransportError, TransportResult};
+
+/// Size of the header of every packet:
+/// kind (u8), message id (u32), fragment index (u16), fragment count (u16)
+pub const PACKET_HEADER_SIZE: usize = 9;
+
+/// Largest datagram we send, small enough to avoid IP fragmentation
+/// on any path with the IPv6 minimum MTU
+pub const MAX_DATAGRAM_SIZE: usize = 1200;
+
+/// Largest chunk of a message a single packet carries
+pub const MAX_FRAGMENT_SIZE: usize = MAX_DATAGRAM_SIZE - PACKET_HEADER_SIZE;
+
+/// Largest message we send or reassemble
+pub const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Most fragments a message of MAX_MESSAGE_SIZE is split into
+pub const MAX_FRAGMENT_COUNT: usize =
+    (MAX_MESSAGE_SIZE + MAX_FRAGMENT_SIZE - 1) / MAX_FRAGMENT_SIZE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketKind {
+    /// fragment of a message the receiver acks and delivers in order
+    Reliable,
+    /// fragment of a message that may be lost
+    Unreliable,
+    /// receipt of a Reliable fragment
+    Ack,
+}
+
+impl PacketKind {
+    fn to_byte(self) -> u8 {
+        match self {
+            PacketKind::Reliable => 1,
+            PacketKind::Unreliable => 2,
+            PacketKind::Ack => 3,
+        }
+    }
+
+    fn from_byte(byte: u8) -> TransportResult<Self> {
+        match byte {
+            1 => Ok(PacketKind::Reliable),
+            2 => Ok(PacketKind::Unreliable),
+            3 => Ok(PacketKind::Ack),
+            _ => Err(TransportError::new(format!(
+                "unknown udp packet kind {}",
+                byte
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    pub kind: PacketKind,
+    pub message_id: u32,
+    pub fragment_index: u16,
+    pub fragment_count: u16,
+    pub payload: Vec<u8>,
+}
+
+impl Packet {
+    /// The receipt of the given Reliable fragment
+    pub fn ack(message_id: u32, fragment_index: u16) -> Self {
+        Packet {
+            kind: PacketKind::Ack,
+            message_id,
+            fragment_index,
+            fragment_count: 1,
+            payload: Vec::new(),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(PACKET_HEADER_SIZE + self.payload.len());
+        bytes.push(self.kind.to_byte());
+        bytes.extend_from_slice(&self.message_id.to_be_bytes());
+        bytes.extend_from_slice(&self.fragment_index.to_be_bytes());
+        bytes.extend_from_slice(&self.fragment_count.to_be_bytes());
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> TransportResult<Self> {
+        if bytes.len() < PACKET_HEADER_SIZE {
+            return Err(TransportError::new(format!(
+                "udp packet of {} bytes is shorter than its header",
+                bytes.len()
+            )));
+        }
+        let kind = PacketKind::from_byte(bytes[0])?;
+        let mut message_id = [0; 4];
+        message_id.copy_from_slice(&bytes[1..5]);
+        let mut fragment_index = [0; 2];
+        fragment_index.copy_from_slice(&bytes[5..7]);
+        let mut fragment_count = [0; 2];
+        fragment_count.copy_from_slice(&bytes[7..9]);
+        let packet = Packet {
+            kind,
+            message_id: u32::from_be_bytes(message_id),
+            fragment_index: u16::from_be_bytes(fragment_index),
+            fragment_count: u16::from_be_bytes(fragment_count),
+            payload: bytes[PACKET_HEADER_SIZE..].to_vec(),
+        };
+        if packet.fragment_index >= packet.fragment_count {
+            return Err(TransportError::new(format!(
+                "udp packet fragment {} out of {}",
+                packet.fragment_index, packet.fragment_count
+            )));
+        }
+        // so that no message we reassemble grows larger than MAX_MESSAGE_SIZE
+        if packet.fragment_count as usize > MAX_FRAGMENT_COUNT {
+            return Err(TransportError::new(format!(
+                "udp packet of a message of {} fragments, the maximum is {}",
+                packet.fragment_count, MAX_FRAGMENT_COUNT
+            )));
+        }
+        if packet.payload.len() > MAX_FRAGMENT_SIZE {
+            return Err(TransportError::new(format!(
+                "udp packet carrying {} bytes, the maximum is {}",
+                packet.payload.len(),
+                MAX_FRAGMENT_SIZE
+            )));
+        }
+        Ok(packet)
+    }
+}
+
+/// Split `payload` into the packets of message `message_id`,
+/// an empty payload still needs one packet
+pub fn fragment(kind: PacketKind, message_id: u32, payload: &[u8]) -> TransportResult<Vec<Packet>> {
+    if payload.len() > MAX_MESSAGE_SIZE {
+        return Err(TransportError::new(format!(
+            "cannot send a message of {} bytes, the maximum is {}",
+            payload.len(),
+            MAX_MESSAGE_SIZE
+        )));
+    }
+    let chunks: Vec<&[u8]> = if payload.is_empty() {
+        vec![payload]
+    } else {
+        payload.chunks(MAX_FRAGMENT_SIZE).collect()
+    };
+    let fragment_count = chunks.len() as u16;
+    Ok(chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, chunk)| Packet {
+            kind,
+            message_id,
+            fragment_index: index as u16,
+            fragment_count,
+            payload: chunk.to_vec(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_roundtrip_packets() {
+        let packet = Packet {
+            kind: PacketKind::Reliable,
+            message_id: 0x0102_0304,
+            fragment_index: 2,
+            fragment_count: 3,
+            payload: b"hello".to_vec(),
+        };
+        let bytes = packet.encode();
+        assert_eq!(&[1, 1, 2, 3, 4, 0, 2, 0, 3], &bytes[..PACKET_HEADER_SIZE]);
+        assert_eq!(packet, Packet::decode(&bytes).unwrap());
+
+        let ack = Packet::ack(7, 1);
+        assert_eq!(ack, Packet::decode(&ack.encode()).unwrap());
+
+        assert!(Packet::decode(&bytes[..PACKET_HEADER_SIZE - 1]).is_err());
+        let mut bad_kind = bytes.clone();
+        bad_kind[0] = 9;
+        assert!(Packet::decode(&bad_kind).is_err());
+        let mut bad_index = bytes;
+        bad_index[6] = 3;
+        assert!(Packet::decode(&bad_index).is_err());
+    }
+
+    #[test]
+    fn it_should_refuse_packets_of_oversized_messages() {
+        let mut packet = Packet {
+            kind: PacketKind::Reliable,
+            message_id: 1,
+            fragment_index: 0,
+            fragment_count: MAX_FRAGMENT_COUNT as u16,
+            payload: vec![0; MAX_FRAGMENT_SIZE],
+        };
+        assert!(Packet::decode(&packet.encode()).is_ok());
+
+        packet.fragment_count += 1;
+        assert!(Packet::decode(&packet.encode()).is_err());
+
+        packet.fragment_count -= 1;
+        packet.payload.push(0);
+        assert!(Packet::decode(&packet.encode()).is_err());
+
+        // the largest message we send fits
+        let payload = vec![0; MAX_MESSAGE_SIZE];
+        let last = fragment(PacketKind::Reliable, 2, &payload)
+            .unwrap()
+            .pop()
+            .unwrap();
+        assert_eq!(MAX_FRAGMENT_COUNT, last.fragment_count as usize);
+        assert!(Packet::decode(&last.encode()).is_ok());
+    }
+
+    #[test]
+    fn it_should_fragment_large_payloads() {
+        let payload: Vec<u8> = (0..MAX_FRAGMENT_SIZE * 2 + 10)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let packets = fragment(PacketKind::Unreliable, 3, &payload).unwrap();
+        assert_eq!(3, packets.len());
+        assert!(packets
+            .iter()
+            .all(|p| p.fragment_count == 3 && p.encode().len() <= MAX_DATAGRAM_SIZE));
+        let reassembled: Vec<u8> = packets.into_iter().flat_map(|p| p.payload).collect();
+        assert_eq!(payload, reassembled);
+
+        assert_eq!(1, fragment(PacketKind::Reliable, 0, &[]).unwrap().len());
+        assert!(fragment(PacketKind::Reliable, 0, &vec![0; MAX_MESSAGE_SIZE + 1]).is_err());
+    }
+}
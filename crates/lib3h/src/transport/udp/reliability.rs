@@ -0,0 +1,614 @@
+//! Per peer reliability layer of the UDP transport.
+//!
+//! Every fragment of a reliable message is acked by the receiver and
+//! retransmitted by the sender until it is, with a retransmission timeout
+//! computed as in RFC 6298.
+//! The number of unacked fragments is bounded by a congestion window
+//! which grows on acks (slow start, then additive increase)
+//! and is halved when a fragment times out.
+//! Reliable messages are delivered in the order they were sent.
+//! Unreliable messages are never retransmitted, but are held back until
+//! the reliable messages sent before them are acked, so that they do not
+//! overtake them.
+//! The messages we reassemble at once are bounded per peer, and by the
+//! transport across peers, and dropped when their fragments stop coming.
+
+use crate::transport::{
+    error::TransportResult,
+    udp::packet::{fragment, Packet, PacketKind},
+};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+/// Congestion window, in fragments, of a new peer
+pub const INITIAL_CWND: usize = 4;
+/// Smallest congestion window a loss can shrink it to
+pub const MIN_CWND: usize = 2;
+/// Largest congestion window
+pub const MAX_CWND: usize = 256;
+
+/// Retransmission timeout before we have measured the rtt
+pub const INITIAL_RTO: Duration = Duration::from_millis(500);
+pub const MIN_RTO: Duration = Duration::from_millis(100);
+pub const MAX_RTO: Duration = Duration::from_secs(10);
+
+/// Times a fragment is retransmitted before the peer is considered lost
+pub const MAX_RETRIES: u32 = 8;
+
+/// How far ahead of the next message to deliver we accept messages
+pub const RECEIVE_WINDOW: u32 = 1024;
+
+/// How long the fragments of an unreliable message wait for the others
+pub const UNRELIABLE_REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A reliable message missing fragments for this long means its sender gave up
+/// retransmitting them, and we give up on the peer: we acked the fragments we have
+pub const RELIABLE_REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Messages we reassemble at once from a single peer, besides the next one to deliver
+pub const MAX_REASSEMBLIES_PER_PEER: usize = 16;
+
+/// Messages the transport reassembles at once across all peers
+pub const MAX_REASSEMBLIES: usize = 256;
+
+/// Unreliable messages held back behind reliable ones, the oldest is dropped
+pub const MAX_HELD_UNRELIABLE: usize = 64;
+
+/// A fragment sent and not yet acked
+struct InFlight {
+    packet: Packet,
+    sent_at: Instant,
+    retries: u32,
+}
+
+/// The fragments of a message received so far
+struct Reassembly {
+    fragments: Vec<Option<Vec<u8>>>,
+    missing: usize,
+    started_at: Instant,
+    last_fragment_at: Instant,
+}
+
+impl Reassembly {
+    fn new(fragment_count: u16, now: Instant) -> Self {
+        Reassembly {
+            fragments: vec![None; fragment_count as usize],
+            missing: fragment_count as usize,
+            started_at: now,
+            last_fragment_at: now,
+        }
+    }
+
+    /// Returns false if the packet does not belong to this message
+    fn insert(&mut self, packet: Packet, now: Instant) -> bool {
+        if packet.fragment_count as usize != self.fragments.len() {
+            return false;
+        }
+        let slot = &mut self.fragments[packet.fragment_index as usize];
+        if slot.is_none() {
+            *slot = Some(packet.payload);
+            self.missing -= 1;
+            self.last_fragment_at = now;
+        }
+        true
+    }
+
+    fn is_complete(&self) -> bool {
+        self.missing == 0
+    }
+
+    fn into_message(self) -> Vec<u8> {
+        self.fragments
+            .into_iter()
+            .flat_map(|fragment| fragment.expect("complete"))
+            .collect()
+    }
+}
+
+/// State of our exchanges with one remote socket address
+pub struct UdpPeer {
+    // sending
+    next_reliable_id: u32,
+    next_unreliable_id: u32,
+    unsent: VecDeque<Packet>,
+    in_flight: BTreeMap<(u32, u16), InFlight>,
+    unacked_fragments: HashMap<u32, usize>,
+    held_unreliable: VecDeque<Vec<Packet>>,
+    cwnd: usize,
+    ssthresh: usize,
+    acks_since_increase: usize,
+    srtt: Option<Duration>,
+    rttvar: Duration,
+    rto: Duration,
+    failed: bool,
+
+    // receiving
+    next_deliver_id: u32,
+    reliable_incoming: BTreeMap<u32, Reassembly>,
+    unreliable_incoming: HashMap<u32, Reassembly>,
+    last_received: Instant,
+
+    // output, drained by the transport
+    datagrams: Vec<Vec<u8>>,
+    received: Vec<Vec<u8>>,
+    acked: Vec<u32>,
+}
+
+impl UdpPeer {
+    pub fn new(now: Instant) -> Self {
+        UdpPeer {
+            next_reliable_id: 0,
+            next_unreliable_id: 0,
+            unsent: VecDeque::new(),
+            in_flight: BTreeMap::new(),
+            unacked_fragments: HashMap::new(),
+            held_unreliable: VecDeque::new(),
+            cwnd: INITIAL_CWND,
+            ssthresh: MAX_CWND,
+            acks_since_increase: 0,
+            srtt: None,
+            rttvar: Duration::from_millis(0),
+            rto: INITIAL_RTO,
+            failed: false,
+            next_deliver_id: 0,
+            reliable_incoming: BTreeMap::new(),
+            unreliable_incoming: HashMap::new(),
+            last_received: now,
+            datagrams: Vec::new(),
+            received: Vec::new(),
+            acked: Vec::new(),
+        }
+    }
+
+    /// Queue a message to be delivered, returns its id
+    /// which `drain_acked` yields once the peer received all of it
+    pub fn send_reliable(&mut self, payload: &[u8]) -> TransportResult<u32> {
+        let message_id = self.next_reliable_id;
+        let packets = fragment(PacketKind::Reliable, message_id, payload)?;
+        self.next_reliable_id = self.next_reliable_id.wrapping_add(1);
+        self.unacked_fragments.insert(message_id, packets.len());
+        self.unsent.extend(packets);
+        Ok(message_id)
+    }
+
+    /// Send a message that may be lost
+    pub fn send_unreliable(&mut self, payload: &[u8]) -> TransportResult<()> {
+        let message_id = self.next_unreliable_id;
+        let packets = fragment(PacketKind::Unreliable, message_id, payload)?;
+        self.next_unreliable_id = self.next_unreliable_id.wrapping_add(1);
+        if self.held_unreliable.len() == MAX_HELD_UNRELIABLE {
+            self.held_unreliable.pop_front();
+        }
+        self.held_unreliable.push_back(packets);
+        Ok(())
+    }
+
+    /// Handle a packet the peer sent us, `can_reassemble` telling whether
+    /// the transport lets us start reassembling one more message
+    pub fn receive(&mut self, packet: Packet, now: Instant, can_reassemble: bool) {
+        self.last_received = now;
+        match packet.kind {
+            PacketKind::Ack => self.priv_on_ack(packet.message_id, packet.fragment_index, now),
+            PacketKind::Reliable => self.priv_on_reliable(packet, now, can_reassemble),
+            PacketKind::Unreliable => self.priv_on_unreliable(packet, now, can_reassemble),
+        }
+    }
+
+    /// Messages we are reassembling
+    pub fn reassemblies(&self) -> usize {
+        self.reliable_incoming.len() + self.unreliable_incoming.len()
+    }
+
+    /// Retransmit what timed out and send what the congestion window allows
+    pub fn process(&mut self, now: Instant) {
+        if self
+            .reliable_incoming
+            .values()
+            .any(|reassembly| now - reassembly.last_fragment_at >= RELIABLE_REASSEMBLY_TIMEOUT)
+        {
+            warn!("udp: a reliable message stopped getting fragments, giving up on the peer");
+            self.failed = true;
+            return;
+        }
+        self.priv_retransmit(now);
+        if self.failed {
+            return;
+        }
+        while self.in_flight.len() < self.cwnd {
+            let packet = match self.unsent.pop_front() {
+                Some(packet) => packet,
+                None => break,
+            };
+            self.datagrams.push(packet.encode());
+            self.in_flight.insert(
+                (packet.message_id, packet.fragment_index),
+                InFlight {
+                    packet,
+                    sent_at: now,
+                    retries: 0,
+                },
+            );
+        }
+        if self.in_flight.is_empty() && self.unsent.is_empty() {
+            for packets in self.held_unreliable.drain(..) {
+                self.datagrams
+                    .extend(packets.iter().map(|packet| packet.encode()));
+            }
+        }
+        self.unreliable_incoming
+            .retain(|_, reassembly| now - reassembly.started_at < UNRELIABLE_REASSEMBLY_TIMEOUT);
+    }
+
+    /// True once a fragment went unacked for MAX_RETRIES retransmissions,
+    /// or a reliable message we reassemble got no fragment for RELIABLE_REASSEMBLY_TIMEOUT
+    pub fn has_failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Ids of the reliable messages still waiting for acks
+    pub fn unacked_messages(&self) -> Vec<u32> {
+        self.unacked_fragments.keys().cloned().collect()
+    }
+
+    pub fn idle_for(&self, now: Instant) -> Duration {
+        now - self.last_received
+    }
+
+    pub fn drain_datagrams(&mut self) -> Vec<Vec<u8>> {
+        self.datagrams.drain(..).collect()
+    }
+
+    pub fn drain_received(&mut self) -> Vec<Vec<u8>> {
+        self.received.drain(..).collect()
+    }
+
+    pub fn drain_acked(&mut self) -> Vec<u32> {
+        self.acked.drain(..).collect()
+    }
+
+    fn priv_retransmit(&mut self, now: Instant) {
+        let rto = self.rto;
+        let mut timed_out = false;
+        for in_flight in self.in_flight.values_mut() {
+            if now - in_flight.sent_at < rto {
+                continue;
+            }
+            timed_out = true;
+            if in_flight.retries == MAX_RETRIES {
+                self.failed = true;
+                return;
+            }
+            in_flight.retries += 1;
+            in_flight.sent_at = now;
+            self.datagrams.push(in_flight.packet.encode());
+        }
+        if timed_out {
+            // react once per timeout, however many fragments it hit
+            self.ssthresh = std::cmp::max(self.cwnd / 2, MIN_CWND);
+            self.cwnd = self.ssthresh;
+            self.acks_since_increase = 0;
+            self.rto = std::cmp::min(self.rto * 2, MAX_RTO);
+        }
+    }
+
+    fn priv_on_ack(&mut self, message_id: u32, fragment_index: u16, now: Instant) {
+        let in_flight = match self.in_flight.remove(&(message_id, fragment_index)) {
+            Some(in_flight) => in_flight,
+            // duplicate ack
+            None => return,
+        };
+        // Karn's algorithm: the ack of a retransmission is ambiguous
+        if in_flight.retries == 0 {
+            self.priv_update_rto(now - in_flight.sent_at);
+        }
+        if self.cwnd < self.ssthresh {
+            self.cwnd += 1;
+        } else {
+            self.acks_since_increase += 1;
+            if self.acks_since_increase >= self.cwnd {
+                self.acks_since_increase = 0;
+                self.cwnd += 1;
+            }
+        }
+        self.cwnd = std::cmp::min(self.cwnd, MAX_CWND);
+        let remaining = self
+            .unacked_fragments
+            .get_mut(&message_id)
+            .expect("in flight fragments belong to unacked messages");
+        *remaining -= 1;
+        if *remaining == 0 {
+            self.unacked_fragments.remove(&message_id);
+            self.acked.push(message_id);
+        }
+    }
+
+    fn priv_update_rto(&mut self, sample: Duration) {
+        let srtt = match self.srtt {
+            None => {
+                self.rttvar = sample / 2;
+                sample
+            }
+            Some(srtt) => {
+                let delta = if srtt > sample {
+                    srtt - sample
+                } else {
+                    sample - srtt
+                };
+                self.rttvar = (self.rttvar * 3 + delta) / 4;
+                (srtt * 7 + sample) / 8
+            }
+        };
+        self.srtt = Some(srtt);
+        let rto = srtt + std::cmp::max(self.rttvar * 4, Duration::from_millis(10));
+        self.rto = std::cmp::min(std::cmp::max(rto, MIN_RTO), MAX_RTO);
+    }
+
+    fn priv_on_reliable(&mut self, packet: Packet, now: Instant, can_reassemble: bool) {
+        let message_id = packet.message_id;
+        let offset = message_id.wrapping_sub(self.next_deliver_id);
+        if offset >= RECEIVE_WINDOW {
+            if offset > u32::max_value() / 2 {
+                // already delivered, our ack must have been lost
+                self.datagrams
+                    .push(Packet::ack(message_id, packet.fragment_index).encode());
+            }
+            // otherwise too far ahead: no ack, the sender will retry
+            return;
+        }
+        if !self.reliable_incoming.contains_key(&message_id) {
+            // the next message to deliver always gets a slot, or a peer
+            // filling its slots with later messages would stall forever
+            let has_slot = message_id == self.next_deliver_id
+                || self.reassemblies() < MAX_REASSEMBLIES_PER_PEER;
+            if !can_reassemble || !has_slot {
+                // no ack, the sender will retry
+                trace!("udp: no room to reassemble message {}", message_id);
+                return;
+            }
+        }
+        let fragment_index = packet.fragment_index;
+        let reassembly = self
+            .reliable_incoming
+            .entry(message_id)
+            .or_insert_with(|| Reassembly::new(packet.fragment_count, now));
+        if !reassembly.insert(packet, now) {
+            warn!(
+                "udp: inconsistent fragment count for message {}",
+                message_id
+            );
+            return;
+        }
+        self.datagrams
+            .push(Packet::ack(message_id, fragment_index).encode());
+        while self
+            .reliable_incoming
+            .get(&self.next_deliver_id)
+            .map(Reassembly::is_complete)
+            .unwrap_or(false)
+        {
+            let reassembly = self
+                .reliable_incoming
+                .remove(&self.next_deliver_id)
+                .expect("exists");
+            self.received.push(reassembly.into_message());
+            self.next_deliver_id = self.next_deliver_id.wrapping_add(1);
+        }
+    }
+
+    fn priv_on_unreliable(&mut self, packet: Packet, now: Instant, can_reassemble: bool) {
+        let message_id = packet.message_id;
+        if !self.unreliable_incoming.contains_key(&message_id)
+            && (!can_reassemble || self.reassemblies() >= MAX_REASSEMBLIES_PER_PEER)
+        {
+            trace!(
+                "udp: no room to reassemble message {}, dropping it",
+                message_id
+            );
+            return;
+        }
+        let reassembly = self
+            .unreliable_incoming
+            .entry(message_id)
+            .or_insert_with(|| Reassembly::new(packet.fragment_count, now));
+        if !reassembly.insert(packet, now) {
+            // an old message with a reused id, start over
+            self.unreliable_incoming.remove(&message_id);
+            return;
+        }
+        if reassembly.is_complete() {
+            let reassembly = self
+                .unreliable_incoming
+                .remove(&message_id)
+                .expect("exists");
+            self.received.push(reassembly.into_message());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::udp::packet::MAX_FRAGMENT_SIZE;
+
+    /// Exchange the datagrams of `a` and `b` until neither has any left,
+    /// losing the ones `lose` says to, with `rtt` of virtual time per round
+    fn run_link(
+        a: &mut UdpPeer,
+        b: &mut UdpPeer,
+        now: &mut Instant,
+        rtt: Duration,
+        mut lose: impl FnMut(usize) -> bool,
+    ) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+        let mut sent = 0;
+        let mut received_by_a = Vec::new();
+        let mut received_by_b = Vec::new();
+        for _ in 0..10_000 {
+            a.process(*now);
+            b.process(*now);
+            let from_a = a.drain_datagrams();
+            let from_b = b.drain_datagrams();
+            *now += rtt;
+            for datagram in from_a {
+                sent += 1;
+                if !lose(sent) {
+                    b.receive(Packet::decode(&datagram).unwrap(), *now, true);
+                }
+            }
+            for datagram in from_b {
+                sent += 1;
+                if !lose(sent) {
+                    a.receive(Packet::decode(&datagram).unwrap(), *now, true);
+                }
+            }
+            received_by_a.extend(a.drain_received());
+            received_by_b.extend(b.drain_received());
+            if a.unacked_messages().is_empty() && b.unacked_messages().is_empty() {
+                break;
+            }
+        }
+        (received_by_a, received_by_b)
+    }
+
+    fn message(size: usize, seed: u8) -> Vec<u8> {
+        (0..size).map(|i| (i as u8).wrapping_add(seed)).collect()
+    }
+
+    #[test]
+    fn it_should_deliver_in_order_over_a_lossy_link() {
+        let mut now = Instant::now();
+        let mut a = UdpPeer::new(now);
+        let mut b = UdpPeer::new(now);
+        let messages: Vec<Vec<u8>> = (0..20)
+            .map(|i| message(i * MAX_FRAGMENT_SIZE / 3, i as u8))
+            .collect();
+        for payload in &messages {
+            a.send_reliable(payload).unwrap();
+        }
+        // lose one datagram out of three, in both directions
+        let (_, received) = run_link(&mut a, &mut b, &mut now, Duration::from_millis(50), |n| {
+            n % 3 == 0
+        });
+        assert_eq!(messages, received);
+        assert!(!a.has_failed());
+        let mut acked = a.drain_acked();
+        acked.sort_unstable();
+        assert_eq!((0..20).collect::<Vec<u32>>(), acked);
+    }
+
+    #[test]
+    fn it_should_hold_unreliable_messages_behind_reliable_ones() {
+        let mut now = Instant::now();
+        let mut a = UdpPeer::new(now);
+        let mut b = UdpPeer::new(now);
+        a.send_reliable(&message(MAX_FRAGMENT_SIZE * 10, 1))
+            .unwrap();
+        a.send_unreliable(b"after").unwrap();
+        let (_, mut received) =
+            run_link(&mut a, &mut b, &mut now, Duration::from_millis(10), |_| {
+                false
+            });
+        // the unreliable message is only sent once the reliable one is acked
+        a.process(now);
+        for datagram in a.drain_datagrams() {
+            b.receive(Packet::decode(&datagram).unwrap(), now, true);
+        }
+        received.extend(b.drain_received());
+        assert_eq!(
+            vec![message(MAX_FRAGMENT_SIZE * 10, 1), b"after".to_vec()],
+            received
+        );
+    }
+
+    #[test]
+    fn it_should_shrink_the_window_and_fail_on_loss() {
+        let mut now = Instant::now();
+        let mut a = UdpPeer::new(now);
+        for i in 0..100 {
+            a.send_reliable(&message(MAX_FRAGMENT_SIZE, i)).unwrap();
+        }
+        a.process(now);
+        assert_eq!(INITIAL_CWND, a.drain_datagrams().len());
+        // nothing is ever acked
+        for _ in 0..MAX_RETRIES {
+            now += MAX_RTO;
+            a.process(now);
+            assert!(!a.has_failed());
+            assert!(a.drain_datagrams().len() <= INITIAL_CWND);
+        }
+        assert_eq!(MIN_CWND, a.cwnd);
+        now += MAX_RTO;
+        a.process(now);
+        assert!(a.has_failed());
+        assert_eq!(100, a.unacked_messages().len());
+    }
+
+    /// The first of the two fragments of a message
+    fn first_fragment(kind: PacketKind, message_id: u32) -> Packet {
+        Packet {
+            kind,
+            message_id,
+            fragment_index: 0,
+            fragment_count: 2,
+            payload: message(10, message_id as u8),
+        }
+    }
+
+    #[test]
+    fn it_should_bound_reassemblies() {
+        let now = Instant::now();
+        let mut b = UdpPeer::new(now);
+        // messages ahead of the next one to deliver fill our slots
+        for message_id in 1..=(MAX_REASSEMBLIES_PER_PEER as u32 + 4) {
+            b.receive(first_fragment(PacketKind::Reliable, message_id), now, true);
+        }
+        b.receive(first_fragment(PacketKind::Unreliable, 0), now, true);
+        assert_eq!(MAX_REASSEMBLIES_PER_PEER, b.reassemblies());
+        // the ones past the limit were not acked, so the sender will retry them
+        assert_eq!(MAX_REASSEMBLIES_PER_PEER, b.drain_datagrams().len());
+        // the next one to deliver still gets in
+        b.receive(first_fragment(PacketKind::Reliable, 0), now, true);
+        assert_eq!(MAX_REASSEMBLIES_PER_PEER + 1, b.reassemblies());
+        // unless the transport is reassembling too much already
+        let mut c = UdpPeer::new(now);
+        c.receive(first_fragment(PacketKind::Reliable, 0), now, false);
+        c.receive(first_fragment(PacketKind::Unreliable, 0), now, false);
+        assert_eq!(0, c.reassemblies());
+        assert!(c.drain_datagrams().is_empty());
+    }
+
+    #[test]
+    fn it_should_expire_incomplete_reassemblies() {
+        let mut now = Instant::now();
+        let mut b = UdpPeer::new(now);
+        b.receive(first_fragment(PacketKind::Unreliable, 0), now, true);
+        now += UNRELIABLE_REASSEMBLY_TIMEOUT;
+        b.process(now);
+        assert_eq!(0, b.reassemblies());
+        assert!(!b.has_failed());
+
+        // we acked its fragments, so a reliable message missing the others
+        // for that long means the sender gave up on us
+        b.receive(first_fragment(PacketKind::Reliable, 0), now, true);
+        now += RELIABLE_REASSEMBLY_TIMEOUT - Duration::from_secs(1);
+        b.process(now);
+        assert!(!b.has_failed());
+        now += Duration::from_secs(1);
+        b.process(now);
+        assert!(b.has_failed());
+    }
+
+    #[test]
+    fn it_should_measure_the_rtt() {
+        let mut now = Instant::now();
+        let mut a = UdpPeer::new(now);
+        let mut b = UdpPeer::new(now);
+        a.send_reliable(b"ping").unwrap();
+        run_link(&mut a, &mut b, &mut now, Duration::from_millis(200), |_| {
+            false
+        });
+        assert_eq!(Some(Duration::from_millis(400)), a.srtt);
+        assert!(a.rto > Duration::from_millis(400) && a.rto < INITIAL_RTO * 3);
+    }
+}
@@ -0,0 +1,290 @@
+use crate::{
+    new_root_span,
+    transport::{
+        error::{TransportError, TransportResult},
+        protocol::*,
+        uds::streams::UdsStreamManager,
+        websocket::streams::{ConnectionStatus, StreamEvent},
+    },
+};
+use detach::Detach;
+use lib3h_ghost_actor::prelude::*;
+use lib3h_protocol::{data_types::Opaque, types::*, uri::Lib3hUri};
+
+pub struct GhostTransportUds {
+    #[allow(dead_code)]
+    node_id: NodePubKey,
+    endpoint_parent: Option<GhostTransportUdsEndpoint>,
+    endpoint_self: Detach<GhostTransportUdsEndpointContext>,
+    streams: UdsStreamManager,
+    bound_url: Option<Lib3hUri>,
+}
+
+impl Drop for GhostTransportUds {
+    fn drop(&mut self) {
+        self.streams
+            .close_all()
+            .unwrap_or_else(|e| error!("Error closing streams: {:?}", e));
+    }
+}
+
+impl GhostTransportUds {
+    pub fn new(node_id: NodePubKey) -> GhostTransportUds {
+        let (endpoint_parent, endpoint_self) = create_ghost_channel();
+        GhostTransportUds {
+            node_id,
+            endpoint_parent: Some(endpoint_parent),
+            endpoint_self: Detach::new(
+                endpoint_self
+                    .as_context_endpoint_builder()
+                    .request_id_prefix("tuds_to_parent")
+                    .build(),
+            ),
+            streams: UdsStreamManager::new(),
+            bound_url: None,
+        }
+    }
+
+    pub fn bound_url(&self) -> Option<Lib3hUri> {
+        self.bound_url.clone()
+    }
+
+    /// Connecting to a unix socket completes at once,
+    /// so messages are sent, or fail, right away
+    fn handle_send_message(&mut self, uri: Lib3hUri, payload: Opaque) -> TransportResult<()> {
+        if self.bound_url.is_none() {
+            return Err(TransportError::new(
+                "Transport must be bound before sending".to_string(),
+            ));
+        }
+        if self.streams.connection_status(&uri) == ConnectionStatus::None {
+            trace!("No open connection to {}, connecting...", uri);
+            self.streams.connect(&uri)?;
+        }
+        trace!("(GhostTransportUds).SendMessage to {} | {:?}", uri, payload);
+        self.streams.send(&uri, &payload.as_bytes())
+    }
+
+    fn process_actor_inbox(&mut self) -> TransportResult<()> {
+        for mut msg in self.endpoint_self.drain_messages() {
+            match msg.take_message().expect("exists") {
+                RequestToChild::Bind { spec: url } => {
+                    let maybe_bound_url = self.streams.bind(&url);
+                    if let Ok(url) = &maybe_bound_url {
+                        trace!("Uds binding to: {}", url);
+                        self.bound_url = Some(url.clone().into());
+                    }
+                    msg.respond(maybe_bound_url.map(|url| {
+                        RequestToChildResponse::Bind(BindResultData {
+                            bound_url: url.into(),
+                        })
+                    }))?;
+                }
+                RequestToChild::SendMessage { uri, payload, .. } => {
+                    let result = self
+                        .handle_send_message(uri, payload)
+                        .map(|()| RequestToChildResponse::SendMessageSuccess);
+                    msg.respond(result)?;
+                }
+                RequestToChild::RotateTlsIdentity { .. } => {
+                    msg.respond(Err(TransportError::new(
+                        "the uds transport has no tls identity".to_string(),
+                    )))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn process_stream_events(&mut self, stream_events: Vec<StreamEvent>) -> TransportResult<()> {
+        for event in stream_events {
+            let span = new_root_span("StreamEvent");
+            let request = match event {
+                StreamEvent::ErrorOccured(uri, error) => {
+                    warn!(
+                        "Error in GhostTransportUds connection to {}: {:?}",
+                        uri, error
+                    );
+                    RequestToParent::ErrorOccured {
+                        uri: uri.into(),
+                        error,
+                    }
+                }
+                StreamEvent::ConnectResult(uri, _) => {
+                    trace!("StreamEvent::ConnectResult: {}", uri);
+                    continue;
+                }
+                StreamEvent::IncomingConnectionEstablished(uri) => {
+                    RequestToParent::IncomingConnection { uri: uri.into() }
+                }
+                StreamEvent::ReceivedData(uri, payload) => RequestToParent::ReceivedData {
+                    uri: uri.into(),
+                    payload: Opaque::from(payload),
+                },
+                StreamEvent::ConnectionClosed(uri) => RequestToParent::Disconnect(uri.into()),
+                StreamEvent::ConnectionEvicted(uri) => RequestToParent::Evicted(uri.into()),
+            };
+            self.endpoint_self
+                .publish(span.child("publish uds stream event"), request)?;
+        }
+        Ok(())
+    }
+}
+
+pub type GhostTransportUdsEndpoint = GhostEndpoint<
+    RequestToChild,
+    RequestToChildResponse,
+    RequestToParent,
+    RequestToParentResponse,
+    TransportError,
+>;
+
+pub type GhostTransportUdsEndpointContext = GhostContextEndpoint<
+    GhostTransportUds,
+    RequestToParent,
+    RequestToParentResponse,
+    RequestToChild,
+    RequestToChildResponse,
+    TransportError,
+>;
+
+pub type GhostTransportUdsEndpointContextParent<T> = GhostContextEndpoint<
+    T,
+    RequestToChild,
+    RequestToChildResponse,
+    RequestToParent,
+    RequestToParentResponse,
+    TransportError,
+>;
+
+impl
+    GhostActor<
+        RequestToParent,
+        RequestToParentResponse,
+        RequestToChild,
+        RequestToChildResponse,
+        TransportError,
+    > for GhostTransportUds
+{
+    fn take_parent_endpoint(&mut self) -> Option<GhostTransportUdsEndpoint> {
+        std::mem::replace(&mut self.endpoint_parent, None)
+    }
+
+    fn process_concrete(&mut self) -> GhostResult<WorkWasDone> {
+        detach_run!(self.endpoint_self, |endpoint_self| endpoint_self
+            .process(self))?;
+
+        self.process_actor_inbox()?;
+
+        if self.bound_url.is_none() {
+            return Ok(false.into());
+        }
+
+        let (did_work, stream_events) = self.streams.process()?;
+        self.process_stream_events(stream_events)?;
+
+        Ok(did_work.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lib3h_ghost_actor::{wait1_for_callback, wait_for_message};
+
+    fn bind(
+        transport: &mut GhostTransportUds,
+        endpoint: &mut GhostTransportUdsEndpointContextParent<Option<String>>,
+        uri: Lib3hUri,
+    ) {
+        // bound to exactly the socket we asked for
+        let re = format!(
+            "Response\\(Ok\\(Bind\\(BindResultData \\{{ bound_url: {} \\}}\\)\\)\\)",
+            regex::escape(&format!("{:?}", uri))
+        );
+        wait1_for_callback!(
+            transport,
+            endpoint,
+            RequestToChild::Bind { spec: uri },
+            re.as_str()
+        );
+    }
+
+    #[test]
+    fn test_uds_transport_send_message_and_disconnect() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut transport1 = GhostTransportUds::new("fake_node_id1".into());
+        let mut t1_endpoint: GhostTransportUdsEndpointContextParent<Option<String>> = transport1
+            .take_parent_endpoint()
+            .expect("exists")
+            .as_context_endpoint_builder()
+            .request_id_prefix("tuds_to_child1")
+            .build::<Option<String>>();
+
+        let mut transport2 = GhostTransportUds::new("fake_node_id2".into());
+        let mut t2_endpoint = transport2
+            .take_parent_endpoint()
+            .expect("exists")
+            .as_context_endpoint_builder()
+            .request_id_prefix("tuds_to_child2")
+            .build::<Option<String>>();
+
+        let transport1_address = Lib3hUri::with_uds(&dir.path().join("t1.sock"));
+        let transport2_address = Lib3hUri::with_uds(&dir.path().join("t2.sock"));
+        bind(&mut transport1, &mut t1_endpoint, transport1_address);
+        bind(
+            &mut transport2,
+            &mut t2_endpoint,
+            transport2_address.clone(),
+        );
+        assert_eq!(transport2.bound_url(), Some(transport2_address.clone()));
+
+        // connects on send
+        t1_endpoint
+            .request(
+                holochain_tracing::test_span(),
+                RequestToChild::create_send_message(
+                    transport2_address.clone(),
+                    b"test message".to_vec().into(),
+                ),
+                Box::new(|_: &mut _, r| {
+                    assert_eq!("Response(Ok(SendMessageSuccess))", &format!("{:?}", r));
+                    Ok(())
+                }),
+            )
+            .unwrap();
+
+        let accepted_uri = format!(
+            "Lib3hUri\\(\"uds://accepted1{}\"\\)",
+            regex::escape(transport2_address.path())
+        );
+        let incoming_regex = format!("IncomingConnection \\{{ uri: {} \\}}", accepted_uri);
+        wait_for_message!(
+            vec![&mut transport1, &mut transport2],
+            t2_endpoint,
+            None,
+            incoming_regex.as_str()
+        );
+        let received_regex = format!(
+            "ReceivedData \\{{ uri: {}, payload: \"test message\" \\}}",
+            accepted_uri
+        );
+        wait_for_message!(
+            vec![&mut transport1, &mut transport2],
+            t2_endpoint,
+            None,
+            received_regex.as_str()
+        );
+
+        // the remote closing its end is a disconnect
+        drop(transport1);
+        let disconnect_regex = format!("Disconnect\\({}\\)", accepted_uri);
+        wait_for_message!(
+            vec![&mut transport2],
+            t2_endpoint,
+            None,
+            disconnect_regex.as_str()
+        );
+    }
+}
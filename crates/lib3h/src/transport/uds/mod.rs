@@ -0,0 +1,8 @@
+//! Transport implementation for nodes on the same host, that sends messages
+//! over unix domain sockets, addressed by uds:///path/of/the.sock uris.
+//!
+//! Each message is sent as one length-prefixed frame, as in the tcp transport.
+//! The ghost actor in actor::GhostTransportUds wraps streams::UdsStreamManager,
+//! which implements the connection pool.
+pub mod actor;
+pub mod streams;
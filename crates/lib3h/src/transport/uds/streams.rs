@@ -0,0 +1,371 @@
+//! Connection pool of non-blocking unix domain socket streams exchanging frames
+
+use crate::transport::{
+    error::{TransportError, TransportResult},
+    tcp::framing::{encode_frame, FrameReader},
+    websocket::streams::{ConnectionStatus, StreamEvent},
+};
+use lib3h_protocol::DidWork;
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    os::unix::{
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+};
+use url::Url;
+
+/// How many bytes we read from a stream at a time
+const READ_BUFFER_SIZE: usize = 4096;
+
+/// Represents an individual connection
+#[derive(Debug)]
+struct UdsConnection {
+    url: Url,
+    /// None once the connection closed
+    stream: Option<UnixStream>,
+    frames: FrameReader,
+    /// bytes the stream would not take yet
+    outgoing: Vec<u8>,
+}
+
+impl UdsConnection {
+    fn new(url: Url, stream: UnixStream) -> Self {
+        UdsConnection {
+            url,
+            stream: Some(stream),
+            frames: FrameReader::new(),
+            outgoing: Vec::new(),
+        }
+    }
+}
+
+/// The socket we listen on, its file is removed when we drop it
+#[derive(Debug)]
+struct UdsListener {
+    scheme: String,
+    path: PathBuf,
+    listener: UnixListener,
+}
+
+impl Drop for UdsListener {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!(
+                "could not remove socket file {}: {:?}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Pool of unix domain socket connections exchanging length-prefixed frames.
+///
+/// A remote that connected to us has no socket path of its own,
+/// so its connection is named after our socket path, with a host
+/// unique to the connection: uds://accepted<n>/path/of/our.sock
+#[derive(Default)]
+pub struct UdsStreamManager {
+    listener: Option<UdsListener>,
+    accepted_count: u64,
+    connections: HashMap<Url, UdsConnection>,
+    event_queue: Vec<StreamEvent>,
+}
+
+impl UdsStreamManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// listen for connections on the socket file at the path of `url`,
+    /// replacing the file if it was left behind by a transport that is gone
+    pub fn bind(&mut self, url: &Url) -> TransportResult<Url> {
+        let path = socket_path(url)?;
+        remove_stale_socket(&path)?;
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+        let bound_url = Url::parse(&format!("{}://{}", url.scheme(), path.display()))?;
+        trace!("uds bound to {}", bound_url);
+        self.listener = Some(UdsListener {
+            scheme: url.scheme().to_string(),
+            path,
+            listener,
+        });
+        Ok(bound_url)
+    }
+
+    /// connect to the socket of a remote uds transport
+    pub fn connect(&mut self, url: &Url) -> TransportResult<()> {
+        let stream = UnixStream::connect(socket_path(url)?)?;
+        stream.set_nonblocking(true)?;
+        self.connections
+            .insert(url.clone(), UdsConnection::new(url.clone(), stream));
+        self.event_queue
+            .push(StreamEvent::ConnectResult(url.clone(), String::new()));
+        Ok(())
+    }
+
+    pub fn connection_status(&self, url: &Url) -> ConnectionStatus {
+        if self.connections.contains_key(url) {
+            ConnectionStatus::Ready
+        } else {
+            ConnectionStatus::None
+        }
+    }
+
+    /// queue `payload` as a frame to a connected remote, and send what we can of it
+    pub fn send(&mut self, url: &Url, payload: &[u8]) -> TransportResult<()> {
+        let frame = encode_frame(payload)?;
+        let connection = self
+            .connections
+            .get_mut(url)
+            .ok_or_else(|| format!("No socket found for URL: {}", url))?;
+        match &mut connection.stream {
+            Some(stream) => {
+                connection.outgoing.extend(frame);
+                priv_flush(stream, &mut connection.outgoing)?;
+                Ok(())
+            }
+            None => Err(TransportError::new("uds stream closed".into())),
+        }
+    }
+
+    /// close a currently tracked connection
+    pub fn close(&mut self, url: &Url) -> TransportResult<()> {
+        if let Some(connection) = self.connections.remove(url) {
+            priv_shutdown(connection)?;
+        }
+        Ok(())
+    }
+
+    /// close all currently tracked connections
+    pub fn close_all(&mut self) -> TransportResult<()> {
+        let errors: Vec<TransportError> = self
+            .connections
+            .drain()
+            .filter_map(|(_, connection)| priv_shutdown(connection).err())
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.into())
+        }
+    }
+
+    /// this should be called frequently on the event loop,
+    /// accepts connections and reads and writes frames
+    pub fn process(&mut self) -> TransportResult<(DidWork, Vec<StreamEvent>)> {
+        let mut did_work = self.priv_process_accept()?;
+
+        // take connections out, so we can mut ref into self and them at same time
+        let connections: Vec<(Url, UdsConnection)> = self.connections.drain().collect();
+        for (url, mut connection) in connections {
+            match self.priv_process_connection(&mut connection) {
+                Ok(connection_did_work) => did_work |= connection_did_work,
+                Err(e) => {
+                    did_work = true;
+                    self.event_queue
+                        .push(StreamEvent::ErrorOccured(url.clone(), e));
+                    connection.stream = None;
+                }
+            }
+            if connection.stream.is_none() {
+                self.event_queue.push(StreamEvent::ConnectionClosed(url));
+                continue;
+            }
+            self.connections.insert(url, connection);
+        }
+
+        Ok((did_work, self.event_queue.drain(..).collect()))
+    }
+
+    // -- private -- //
+
+    fn priv_process_accept(&mut self) -> TransportResult<DidWork> {
+        let mut did_work = false;
+        if let Some(listener) = &self.listener {
+            loop {
+                match listener.listener.accept() {
+                    Ok((stream, _)) => {
+                        did_work = true;
+                        self.accepted_count += 1;
+                        let url = Url::parse(&format!(
+                            "{}://accepted{}{}",
+                            listener.scheme,
+                            self.accepted_count,
+                            listener.path.display()
+                        ))?;
+                        trace!("uds accepted connection {}", url);
+                        stream.set_nonblocking(true)?;
+                        self.event_queue
+                            .push(StreamEvent::IncomingConnectionEstablished(url.clone()));
+                        self.connections
+                            .insert(url.clone(), UdsConnection::new(url, stream));
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                    Err(e) => {
+                        error!("uds accept error: {:?}", e);
+                        return Err(e.into());
+                    }
+                }
+            }
+        }
+        Ok(did_work)
+    }
+
+    fn priv_process_connection(
+        &mut self,
+        connection: &mut UdsConnection,
+    ) -> TransportResult<DidWork> {
+        let mut stream = match connection.stream.take() {
+            None => return Ok(false),
+            Some(stream) => stream,
+        };
+        let mut did_work = priv_flush(&mut stream, &mut connection.outgoing)?;
+        let mut buf = [0; READ_BUFFER_SIZE];
+        let mut is_closed = false;
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => {
+                    trace!("uds connection {} closed by remote", connection.url);
+                    is_closed = true;
+                    break;
+                }
+                Ok(size) => {
+                    did_work = true;
+                    connection.frames.push(&buf[..size]);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        while let Some(frame) = connection.frames.next_frame()? {
+            self.event_queue
+                .push(StreamEvent::ReceivedData(connection.url.clone(), frame));
+        }
+        if !is_closed {
+            connection.stream = Some(stream);
+        }
+        Ok(did_work)
+    }
+}
+
+/// The socket file a url addresses, urls of accepted connections have none
+fn socket_path(url: &Url) -> TransportResult<PathBuf> {
+    if url.host_str().map(|host| !host.is_empty()).unwrap_or(false) {
+        return Err(TransportError::new(format!(
+            "{} is not the address of a uds socket",
+            url
+        )));
+    }
+    let path = url.path();
+    // we do not decode urls, a path in need of encoding is not supported
+    if !path.starts_with('/') || path.contains('%') {
+        return Err(TransportError::new(format!(
+            "{} is not an absolute, url safe, socket path",
+            url
+        )));
+    }
+    Ok(PathBuf::from(path))
+}
+
+/// Remove the socket file at `path` if no one listens on it anymore.
+/// Anything else at `path` is left alone and is an error.
+fn remove_stale_socket(path: &Path) -> TransportResult<()> {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(TransportError::new(format!(
+            "cannot bind to {}: it exists and is not a socket",
+            path.display()
+        )));
+    }
+    match UnixStream::connect(path) {
+        Ok(_) => Err(TransportError::new(format!(
+            "cannot bind to {}: another transport is listening on it",
+            path.display()
+        ))),
+        Err(ref e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+            debug!("removing stale socket file {}", path.display());
+            std::fs::remove_file(path)?;
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+// write as much of `outgoing` as the stream takes without blocking
+fn priv_flush(stream: &mut UnixStream, outgoing: &mut Vec<u8>) -> TransportResult<DidWork> {
+    let mut did_work = false;
+    while !outgoing.is_empty() {
+        match stream.write(outgoing) {
+            Ok(0) => {
+                return Err(TransportError::new(
+                    "uds stream closed while writing".into(),
+                ))
+            }
+            Ok(size) => {
+                did_work = true;
+                outgoing.drain(..size);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(did_work)
+}
+
+fn priv_shutdown(connection: UdsConnection) -> TransportResult<()> {
+    if let Some(stream) = connection.stream {
+        if let Err(e) = stream.shutdown(std::net::Shutdown::Both) {
+            if e.kind() != std::io::ErrorKind::NotConnected {
+                return Err(e.into());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_replace_stale_socket_files_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("node.sock");
+        let url = Url::parse(&format!("uds://{}", path.display())).unwrap();
+
+        // a socket file nobody listens on anymore
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+        let mut streams = UdsStreamManager::new();
+        assert_eq!(url, streams.bind(&url).unwrap());
+
+        // but we do not steal a live socket
+        assert!(UdsStreamManager::new().bind(&url).is_err());
+
+        // nor clobber what is not a socket
+        let file_url = Url::parse(&format!("uds://{}/file", dir.path().display())).unwrap();
+        std::fs::write(dir.path().join("file"), b"data").unwrap();
+        assert!(UdsStreamManager::new().bind(&file_url).is_err());
+
+        // and clean up after ourselves
+        drop(streams);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn it_should_not_connect_to_accepted_connections() {
+        let url = Url::parse("uds://accepted1/tmp/node.sock").unwrap();
+        assert!(UdsStreamManager::new().connect(&url).is_err());
+    }
+}
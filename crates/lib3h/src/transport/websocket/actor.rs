@@ -1,12 +1,13 @@
 use crate::transport::{
-    error::{TransportError, TransportResult},
+    error::{ErrorKind, TransportError, TransportResult},
     protocol::*,
     websocket::{
-        streams::{ConnectionStatus, StreamEvent, StreamManager},
+        streams::{ConnectionStatus, StreamEvent, StreamLimits, StreamManager},
         tls::TlsConfig,
     },
 };
 use detach::Detach;
+use lib3h_crypto_api::{Buffer, CryptoSystem};
 use lib3h_ghost_actor::prelude::*;
 use lib3h_protocol::{
     data_types::Opaque,
@@ -28,17 +29,19 @@ pub type Message =
     GhostMessage<RequestToChild, RequestToParent, RequestToChildResponse, TransportError>;
 
 pub struct GhostTransportWebsocket {
-    #[allow(dead_code)]
     node_id: NodePubKey,
     network_id_address: NetworkHash,
     endpoint_parent: Option<GhostTransportWebsocketEndpoint>,
     endpoint_self: Detach<GhostTransportWebsocketEndpointContext>,
     streams: StreamManager<std::net::TcpStream>,
     bound_url: Option<Lib3hUri>,
-    pending: Vec<Message>,
+    pending: Vec<(Lib3hUri, Message)>,
+    crypto: Box<dyn CryptoSystem>,
 
     // mDNS specific variables
     mdns: Option<MulticastDns>,
+    /// our secret signing key, if we sign our mDNS records and verify the ones of others
+    mdns_signing_key: Option<Box<dyn Buffer>>,
     connections: HashSet<Lib3hUri>,
     last_discover: Option<Instant>,
     discover_interval_ms: u128,
@@ -57,9 +60,16 @@ impl Discovery for GhostTransportWebsocket {
 
             let network_id_str: String = HashString::from(self.network_id_address.clone()).into();
 
-            let mut mdns = MulticastDnsBuilder::new()
+            let mut builder = MulticastDnsBuilder::new();
+            builder
                 .own_record(&network_id_str, &[&uri.clone().into_string()])
-                .build()?;
+                .node_id(&self.node_id.to_string());
+            if let Some(secret_key) = &self.mdns_signing_key {
+                builder
+                    .sign_records(self.crypto.box_clone(), secret_key.box_clone())
+                    .verify_signatures(self.crypto.box_clone());
+            }
+            let mut mdns = builder.build()?;
             mdns.insert_record(&network_id_str, &[&uri.into_string()]);
 
             self.mdns = Some(mdns);
@@ -122,6 +132,7 @@ impl GhostTransportWebsocket {
         node_id: NodePubKey,
         tls_config: TlsConfig,
         network_id_address: NetworkHash,
+        crypto: Box<dyn CryptoSystem>,
     ) -> GhostTransportWebsocket {
         let (endpoint_parent, endpoint_self) = create_ghost_channel();
         GhostTransportWebsocket {
@@ -134,10 +145,12 @@ impl GhostTransportWebsocket {
                     .request_id_prefix("twss_to_parent")
                     .build(),
             ),
-            streams: StreamManager::with_std_tcp_stream(tls_config),
+            streams: StreamManager::with_std_tcp_stream(tls_config, crypto.box_clone()),
             bound_url: None,
             pending: Vec::new(),
+            crypto,
             mdns: None,
+            mdns_signing_key: None,
             connections: HashSet::new(),
             last_discover: None,
             discover_interval_ms: 1_000,
@@ -148,6 +161,38 @@ impl GhostTransportWebsocket {
         self.bound_url.clone()
     }
 
+    /// bound the connections and outgoing queues of this transport
+    pub fn set_stream_limits(&mut self, limits: StreamLimits) {
+        self.streams.set_limits(limits);
+    }
+
+    /// sign our mDNS records with `secret_key`, the signing key of our node id,
+    /// and ignore the records not signed by the node they name
+    pub fn sign_mdns_records(&mut self, secret_key: Box<dyn Buffer>) {
+        self.mdns_signing_key = Some(secret_key);
+    }
+
+    /// Saves a SendMessage for when the connection to `uri` is ready,
+    /// or fails it with a would block error if too many already wait.
+    fn queue_pending(&mut self, uri: Lib3hUri, payload: Opaque, mut msg: Message) {
+        if let Some(max_queued) = self.streams.limits().max_queued_messages {
+            let queued = self
+                .pending
+                .iter()
+                .filter(|(pending_uri, _)| *pending_uri == uri)
+                .count();
+            if queued >= max_queued {
+                trace!("Outgoing queue to {} is full", uri);
+                let _ = msg.respond(Err(TransportError::new_kind(ErrorKind::WouldBlock(
+                    format!("{} messages already queued for {}", queued, uri),
+                ))));
+                return;
+            }
+        }
+        msg.put_message(RequestToChild::create_send_message(uri.clone(), payload));
+        self.pending.push((uri, msg));
+    }
+
     /// Actually sends the message via an existing stream.
     /// Assumptions:
     /// * msg is a RequestToChild::SendMessage
@@ -234,6 +279,11 @@ impl GhostTransportWebsocket {
                                     Ok(()) => {
                                         trace!("New connection to {} initialized", uri.to_string())
                                     }
+                                    Err(ref error) if error.is_would_block() => {
+                                        // we are at our outbound connection limit
+                                        msg.respond(Err(error.clone()))?;
+                                        continue;
+                                    }
                                     Err(error) => {
                                         trace!(
                                             "Could not connect to {}! Transport error: {:?}",
@@ -244,28 +294,37 @@ impl GhostTransportWebsocket {
                                 }
 
                                 // And save message for later:
-                                msg.put_message(RequestToChild::create_send_message(uri, payload));
-                                self.pending.push(msg);
+                                self.queue_pending(uri, payload, msg);
                             }
                             ConnectionStatus::Initializing => {
                                 trace!("Send tried while initializing");
                                 // If the connection is there but not ready yet, save message for later
-                                msg.put_message(RequestToChild::create_send_message(uri, payload));
-                                self.pending.push(msg);
+                                self.queue_pending(uri, payload, msg);
                             }
                             ConnectionStatus::Ready => {
                                 trace!("Send via previously established connection");
                                 msg.put_message(RequestToChild::create_send_message(uri, payload));
-                                if let Err(msg) = self.handle_send_message(msg) {
+                                if let Err(mut msg) = self.handle_send_message(msg) {
                                     trace!(
                                         "Error while sending message, putting it in pending list"
                                     );
-                                    self.pending.push(msg);
+                                    if let RequestToChild::SendMessage { uri, payload, .. } =
+                                        msg.take_message().expect("exists")
+                                    {
+                                        self.queue_pending(uri, payload, msg);
+                                    }
                                 }
                             }
                         }
                     };
                 }
+                RequestToChild::RotateTlsIdentity { certificate } => {
+                    let result = self
+                        .streams
+                        .set_tls_certificate(certificate)
+                        .map(|()| RequestToChildResponse::RotateTlsIdentitySuccess);
+                    msg.respond(result)?;
+                }
             }
         }
         Ok(())
@@ -330,6 +389,13 @@ impl GhostTransportWebsocket {
                         RequestToParent::Disconnect(uri.into()),
                     )?;
                 }
+                StreamEvent::ConnectionEvicted(uri) => {
+                    trace!("StreamEvent::ConnectionEvicted: {}", uri);
+                    self.endpoint_self.publish(
+                        span.child("send event RequestToParent::Evicted"),
+                        RequestToParent::Evicted(uri.into()),
+                    )?;
+                }
             }
         }
         Ok(())
@@ -337,20 +403,20 @@ impl GhostTransportWebsocket {
 
     fn process_pending_messages(&mut self) -> TransportResult<()> {
         let mut temp = Vec::new();
-        while let Some(mut msg) = self.pending.pop() {
+        while let Some((_, mut msg)) = self.pending.pop() {
             trace!("Processing pending message...");
             let inner_msg = msg.take_message().expect("exists");
             if let RequestToChild::SendMessage { uri, payload, .. } = inner_msg {
                 if self.streams.connection_status(&uri) == ConnectionStatus::Ready {
                     trace!("Sending pending message to: {:?}", uri);
-                    msg.put_message(RequestToChild::create_send_message(uri, payload));
+                    msg.put_message(RequestToChild::create_send_message(uri.clone(), payload));
                     if let Err(msg) = self.handle_send_message(msg) {
                         trace!("Error while sending message, putting it back in pending list");
-                        temp.push(msg);
+                        temp.push((uri, msg));
                     }
                 } else {
-                    msg.put_message(RequestToChild::create_send_message(uri, payload));
-                    temp.push(msg);
+                    msg.put_message(RequestToChild::create_send_message(uri.clone(), payload));
+                    temp.push((uri, msg));
                 }
             } else {
                 panic!("Found a non-SendMessage message in GhostTransportWebsocket::pending!");
@@ -524,6 +590,7 @@ mod tests {
         tests::enable_logging_for_test, transport::websocket::tls::TlsConfig, wait_for_bind_result,
     };
     use lib3h_ghost_actor::{wait1_for_callback, wait_for_message, wait_until_no_work};
+    use lib3h_sodium::SodiumCryptoSystem;
     use std::net::TcpListener;
     use url::Url;
 
@@ -547,6 +614,7 @@ mod tests {
             node_id_1,
             TlsConfig::Unencrypted,
             network_id_address.clone(),
+            Box::new(SodiumCryptoSystem::new()),
         );
 
         let mut t1_endpoint: GhostTransportWebsocketEndpointContextParent<Option<String>> =
@@ -562,6 +630,7 @@ mod tests {
             node_id_2,
             TlsConfig::Unencrypted,
             network_id_address.clone(),
+            Box::new(SodiumCryptoSystem::new()),
         );
 
         let mut t2_endpoint = transport2
@@ -624,6 +693,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_websocket_transport_signs_mdns_records() {
+        let network_id_address: NetworkHash = "wss-signed-mdns-network-id.holo.host".into();
+        let crypto = SodiumCryptoSystem::new();
+        let keys = crate::engine::TransportKeys::new(&crypto).unwrap();
+
+        let bind = |node_id: NodePubKey| {
+            let mut transport = GhostTransportWebsocket::new(
+                node_id,
+                TlsConfig::Unencrypted,
+                network_id_address.clone(),
+                crypto.box_clone(),
+            );
+            transport.sign_mdns_records(keys.transport_secret_key.box_clone());
+            let mut endpoint: GhostTransportWebsocketEndpointContextParent<Option<String>> =
+                transport
+                    .take_parent_endpoint()
+                    .expect("exists")
+                    .as_context_endpoint_builder()
+                    .request_id_prefix("twss_to_child")
+                    .build::<Option<String>>();
+            let address: Lib3hUri = lib3h_protocol::uri::Builder::with_raw_url("wss://127.0.0.1/")
+                .unwrap()
+                .with_port(1024)
+                .build();
+            let _ = wait_for_bind_result!(transport, endpoint, address);
+            transport
+        };
+
+        let mut transport = bind(keys.node_id.clone());
+        transport.advertise().unwrap();
+        assert!(transport.mdns.as_ref().unwrap().verify_signatures());
+
+        // We cannot sign for another node
+        let mut impostor = bind("fake_node_id".into());
+        assert!(impostor.advertise().is_err());
+        assert!(impostor.mdns.is_none());
+    }
+
     #[test]
     fn test_websocket_transport_reconnect() {
         enable_logging_for_test(true);
@@ -635,6 +743,7 @@ mod tests {
             node_id_1,
             TlsConfig::Unencrypted,
             network_id_address.clone(),
+            Box::new(SodiumCryptoSystem::new()),
         );
 
         let mut t1_endpoint: GhostTransportWebsocketEndpointContextParent<_> = transport1
@@ -670,6 +779,7 @@ mod tests {
                     node_id_2,
                     TlsConfig::Unencrypted,
                     network_id_address.clone(),
+                    Box::new(SodiumCryptoSystem::new()),
                 );
 
                 let mut t2_endpoint = transport2
@@ -745,6 +855,7 @@ mod tests {
             node_id_1,
             TlsConfig::Unencrypted,
             network_id_address.clone(),
+            Box::new(SodiumCryptoSystem::new()),
         );
         let mut t1_endpoint: GhostTransportWebsocketEndpointContextParent<_> = transport1
             .take_parent_endpoint()
@@ -765,6 +876,7 @@ mod tests {
             node_id_2,
             TlsConfig::Unencrypted,
             network_id_address.clone(),
+            Box::new(SodiumCryptoSystem::new()),
         );
         let mut t2_endpoint = transport2
             .take_parent_endpoint()
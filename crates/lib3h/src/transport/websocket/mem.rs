@@ -2,15 +2,17 @@ use crate::transport::{
     error::*,
     websocket::{mem_stream::*, streams::*, tls::TlsConfig, wss_info::WssInfo},
 };
+use lib3h_crypto_api::CryptoSystem;
 use url2::prelude::*;
 
 impl StreamManager<MemStream> {
-    pub fn with_mem_stream(tls_config: TlsConfig) -> Self {
+    pub fn with_mem_stream(tls_config: TlsConfig, crypto: Box<dyn CryptoSystem>) -> Self {
         let bind: Bind<MemStream> = Box::new(move |url| Self::mem_bind(&Url2::from(url)));
         StreamManager::new(
             |uri| Ok(MemStream::connect(&Url2::parse(uri))?),
             bind,
             tls_config,
+            crypto,
         )
     }
 
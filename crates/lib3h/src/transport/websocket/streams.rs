@@ -1,17 +1,20 @@
 use crate::transport::{
-    error::{TransportError, TransportResult},
+    error::{ErrorKind, TransportError, TransportResult},
     websocket::{
-        tls::TlsConfig, wss_info::WssInfo, BaseStream, SocketMap, TlsConnectResult,
-        TlsMidHandshake, TlsSrvMidHandshake, TlsStream, WsConnectResult, WsMidHandshake,
-        WsSrvAcceptResult, WsSrvMidHandshake, WsStream, WssConnectResult, WssMidHandshake,
-        WssSrvAcceptResult, WssSrvMidHandshake, WssStream,
+        tls::{TlsCertificate, TlsConfig},
+        wss_info::WssInfo,
+        BaseStream, SocketMap, TlsConnectResult, TlsMidHandshake, TlsSrvMidHandshake, TlsStream,
+        WsConnectResult, WsMidHandshake, WsSrvAcceptResult, WsSrvMidHandshake, WsStream,
+        WssConnectResult, WssMidHandshake, WssSrvAcceptResult, WssSrvMidHandshake, WssStream,
     },
 };
+use lib3h_crypto_api::CryptoSystem;
 use lib3h_protocol::{uri::Lib3hUri, DidWork};
 use lib3h_zombie_actor::GhostMutex;
 use std::{
     io::{Read, Write},
     sync::Arc,
+    time::Duration,
 };
 
 use url::Url;
@@ -62,6 +65,47 @@ pub enum StreamEvent {
     ReceivedData(Url, Vec<u8>),
     /// A connection closed for whatever reason
     ConnectionClosed(Url),
+    /// We closed a connection to make room for another one
+    ConnectionEvicted(Url),
+}
+
+/// How a StreamManager at a connection limit makes room for a new connection
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum EvictionPolicy {
+    /// refuse the new connection instead
+    RejectNew,
+    /// close the connection we least recently exchanged messages on
+    LeastRecentlyUsed,
+    /// close the connection we exchanged the fewest messages on
+    LeastUseful,
+}
+
+/// Bounds on the connections of a StreamManager, `None` is unbounded
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StreamLimits {
+    /// connections remotes opened to us
+    pub max_inbound: Option<usize>,
+    /// connections we opened to remotes
+    pub max_outbound: Option<usize>,
+    /// close connections no message was exchanged on for this long,
+    /// heartbeats do not count
+    pub idle_timeout_ms: Option<u64>,
+    /// messages waiting to be sent to a single remote
+    pub max_queued_messages: Option<usize>,
+    /// how to make room for a new connection at the limit of its direction
+    pub eviction: EvictionPolicy,
+}
+
+impl Default for StreamLimits {
+    fn default() -> Self {
+        StreamLimits {
+            max_inbound: None,
+            max_outbound: None,
+            idle_timeout_ms: None,
+            max_queued_messages: None,
+            eviction: EvictionPolicy::LeastRecentlyUsed,
+        }
+    }
 }
 
 /// A factory callback for generating base streams of type T
@@ -81,25 +125,45 @@ pub type Bind<T> = Box<dyn FnMut(&Url) -> TransportResult<(Url2, Acceptor<T>)>>;
 /// any rust io Read/Write stream should be able to serve as the base
 pub struct StreamManager<T: Read + Write + std::fmt::Debug> {
     tls_config: TlsConfig,
+    /// verifies the node bindings of tls certificates
+    crypto: Box<dyn CryptoSystem>,
     stream_factory: StreamFactory<T>,
     stream_sockets: SocketMap<T>,
     event_queue: Vec<StreamEvent>,
     bind: Bind<T>,
     acceptor: TransportResult<Acceptor<T>>,
+    limits: StreamLimits,
 }
 
 impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
-    pub fn new(stream_factory: StreamFactory<T>, bind: Bind<T>, tls_config: TlsConfig) -> Self {
+    pub fn new(
+        stream_factory: StreamFactory<T>,
+        bind: Bind<T>,
+        tls_config: TlsConfig,
+        crypto: Box<dyn CryptoSystem>,
+    ) -> Self {
         StreamManager {
             tls_config,
+            crypto,
             stream_factory,
             stream_sockets: std::collections::HashMap::new(),
             event_queue: Vec::new(),
             bind,
             acceptor: Err(TransportError::new("acceptor not initialized".into())),
+            limits: StreamLimits::default(),
         }
     }
 
+    pub fn limits(&self) -> &StreamLimits {
+        &self.limits
+    }
+
+    /// connections already over new limits are not closed,
+    /// but make room for new ones
+    pub fn set_limits(&mut self, limits: StreamLimits) {
+        self.limits = limits;
+    }
+
     /// connect to a remote websocket service
     pub fn connect(&mut self, uri: &Url) -> TransportResult<()> {
         let host_port = format!(
@@ -109,6 +173,7 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
             uri.port()
                 .ok_or_else(|| TransportError::new("bad connect port".into()))?,
         );
+        self.priv_make_room(false)?;
         let socket = (self.stream_factory)(&host_port)?;
         let info = WssInfo::client(uri.clone(), socket);
         self.stream_sockets.insert(uri.clone().into(), info);
@@ -183,6 +248,9 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
             _ => Err(String::from("Websocket not in Ready state")),
         };
         info.stateful_socket = ws_stream;
+        if send_result.is_ok() {
+            info.touch();
+        }
         //println!("send() 3 {:?}", send_result);
         send_result.map_err(|error_string| {
             //println!("Error in send(): {}", error_string);
@@ -196,6 +264,12 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
         Ok(url.into())
     }
 
+    /// present `certificate` on the tls connections we accept from now on,
+    /// established connections keep theirs
+    pub fn set_tls_certificate(&mut self, certificate: TlsCertificate) -> TransportResult<()> {
+        self.tls_config.set_certificate(certificate)
+    }
+
     pub fn connection_status(&self, url: &Url) -> ConnectionStatus {
         self.stream_sockets
             .get(url)
@@ -218,9 +292,7 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
             }
             Ok(acceptor) => (acceptor)()
                 .map(move |wss_info| {
-                    let _insert_result = self
-                        .stream_sockets
-                        .insert(wss_info.url.clone().into(), wss_info);
+                    self.priv_track_accepted(wss_info);
                     true
                 })
                 .unwrap_or_else(|err| {
@@ -254,6 +326,15 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
                     .push(StreamEvent::ConnectionClosed(info.url));
                 continue;
             }
+            if self.priv_is_idle(&info) {
+                debug!("closing idle connection {}", info.url);
+                if let Err(e) = info.close() {
+                    warn!("error closing idle connection {}: {:?}", info.url, e);
+                }
+                self.event_queue
+                    .push(StreamEvent::ConnectionClosed(info.url));
+                continue;
+            }
             if info.last_msg.elapsed().as_millis() as usize > DEFAULT_HEARTBEAT_MS {
                 if let WebsocketStreamState::ReadyWss(socket) = &mut info.stateful_socket {
                     if let Err(e) = socket.write_message(tungstenite::Message::Ping(vec![])) {
@@ -312,13 +393,10 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
                         )?;
                     }
                     _ => {
-                        let connector = native_tls::TlsConnector::builder()
-                            .danger_accept_invalid_certs(true)
-                            .danger_accept_invalid_hostnames(true)
-                            .build()
-                            .expect("failed to build TlsConnector");
+                        let connector = self.tls_config.build_connector()?;
+                        let domain = info.url.host_str().unwrap_or("");
                         info.stateful_socket =
-                            self.priv_tls_handshake(connector.connect(info.url.as_str(), socket))?;
+                            self.priv_tls_handshake(connector.connect(domain, socket))?;
                     }
                 }
                 Ok(())
@@ -407,6 +485,7 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
                         };
 
                         if let Some(msg) = qmsg {
+                            info.touch();
                             self.event_queue
                                 .push(StreamEvent::ReceivedData(info.url.clone(), msg));
                         }
@@ -440,6 +519,7 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
                         };
 
                         if let Some(msg) = qmsg {
+                            info.touch();
                             self.event_queue
                                 .push(StreamEvent::ReceivedData(info.url.clone(), msg));
                         }
@@ -451,6 +531,79 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
         }
     }
 
+    // track a connection a remote opened, if we can make room for it
+    fn priv_track_accepted(&mut self, wss_info: WssInfo<T>) {
+        if let Err(e) = self.priv_make_room(true) {
+            // dropping the socket closes it
+            debug!("refusing connection from {}: {:?}", wss_info.url, e);
+            return;
+        }
+        self.stream_sockets
+            .insert(wss_info.url.clone().into(), wss_info);
+    }
+
+    // at the connection limit of a direction, close a connection
+    // the eviction policy picks, or fail if it picks none
+    fn priv_make_room(&mut self, is_server: bool) -> TransportResult<()> {
+        let (limit, direction) = if is_server {
+            (self.limits.max_inbound, "inbound")
+        } else {
+            (self.limits.max_outbound, "outbound")
+        };
+        let limit = match limit {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        let candidates: Vec<&WssInfo<T>> = self
+            .stream_sockets
+            .values()
+            .filter(|info| info.is_server == is_server)
+            .collect();
+        if candidates.len() < limit {
+            return Ok(());
+        }
+        let victim = match self.limits.eviction {
+            EvictionPolicy::RejectNew => None,
+            EvictionPolicy::LeastRecentlyUsed => {
+                candidates.into_iter().min_by_key(|info| info.last_used)
+            }
+            EvictionPolicy::LeastUseful => candidates
+                .into_iter()
+                .min_by_key(|info| (info.message_count, info.last_used)),
+        }
+        .map(|info| info.url.clone());
+        match victim {
+            None => Err(TransportError::new_kind(ErrorKind::WouldBlock(format!(
+                "at the limit of {} {} connections",
+                limit, direction
+            )))),
+            Some(url) => {
+                debug!("evicting {} connection {}", direction, url);
+                if let Some(mut info) = self.stream_sockets.remove(&url) {
+                    if let Err(e) = info.close() {
+                        warn!("error closing evicted connection {}: {:?}", url, e);
+                    }
+                }
+                self.event_queue.push(StreamEvent::ConnectionEvicted(url));
+                Ok(())
+            }
+        }
+    }
+
+    // only established connections go idle
+    fn priv_is_idle(&self, info: &WssInfo<T>) -> bool {
+        let idle_timeout_ms = match self.limits.idle_timeout_ms {
+            Some(idle_timeout_ms) => idle_timeout_ms,
+            None => return false,
+        };
+        match info.stateful_socket {
+            WebsocketStreamState::ReadyWs(_) | WebsocketStreamState::ReadyWss(_) => {
+                info.last_used.elapsed() > Duration::from_millis(idle_timeout_ms)
+            }
+            _ => false,
+        }
+    }
+
     // process tls handshaking
     fn priv_tls_handshake(
         &mut self,
@@ -460,8 +613,14 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
             Err(native_tls::HandshakeError::WouldBlock(socket)) => {
                 Ok(WebsocketStreamState::TlsMidHandshake(socket))
             }
-            Err(e) => Err(e.into()),
-            Ok(socket) => Ok(WebsocketStreamState::TlsReady(socket)),
+            Err(native_tls::HandshakeError::Failure(e)) => {
+                Err(self.tls_config.handshake_failure(e))
+            }
+            Ok(socket) => {
+                self.tls_config
+                    .verify_peer(self.crypto.as_crypto_system(), &socket)?;
+                Ok(WebsocketStreamState::TlsReady(socket))
+            }
         }
     }
 
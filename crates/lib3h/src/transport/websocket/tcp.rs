@@ -9,6 +9,7 @@ use crate::transport::{
         wss_info::WssInfo,
     },
 };
+use lib3h_crypto_api::CryptoSystem;
 use url2::prelude::*;
 
 use std::net::{TcpListener, TcpStream};
@@ -16,7 +17,7 @@ use std::net::{TcpListener, TcpStream};
 impl StreamManager<std::net::TcpStream> {
     /// convenience constructor for creating a websocket "Transport"
     /// instance that is based of the rust std TcpStream
-    pub fn with_std_tcp_stream(tls_config: TlsConfig) -> Self {
+    pub fn with_std_tcp_stream(tls_config: TlsConfig, crypto: Box<dyn CryptoSystem>) -> Self {
         let bind: Bind<TcpStream> = Box::new(|url| Self::tcp_bind(url));
         StreamManager::new(
             |uri| {
@@ -26,6 +27,7 @@ impl StreamManager<std::net::TcpStream> {
             },
             bind,
             tls_config,
+            crypto,
         )
     }
 
@@ -2,25 +2,43 @@ extern crate native_tls;
 extern crate openssl;
 
 use crate::transport::{
-    error::TransportResult,
+    error::{TlsVerificationError, TransportError, TransportResult},
     websocket::{FAKE_PASS, FAKE_PKCS12},
 };
 
+use lib3h_crypto_api::{Buffer, CryptoSystem};
+use lib3h_protocol::types::NodePubKey;
 use openssl::{
     asn1::Asn1Time,
     bn::{BigNum, MsbOption},
     hash::MessageDigest,
     pkey::{PKey, Private},
     rsa::Rsa,
-    x509::{self, X509Name, X509},
+    stack::Stack,
+    x509::{self, extension::SubjectAlternativeName, X509Name, X509},
 };
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use std::{io::Write, path::Path};
 
-// Generates a key/cert pair
-fn generate_pair() -> (PKey<Private>, x509::X509) {
+/// The file in `work_dir` `load_or_build_from_entropy` stores our certificate in
+pub const TLS_CERT_FILE_NAME: &str = "tls_cert.pem";
+/// The file in `work_dir` `load_or_build_from_entropy` stores our private key in
+pub const TLS_KEY_FILE_NAME: &str = "tls_key.pem";
+
+/// A certificate built for a node names it in a subjectAltName uri:
+/// lib3h-node:<node id>:<hex of the node signature of the certificate public key>
+const NODE_BINDING_PREFIX: &str = "lib3h-node:";
+
+static ACCEPT_ANY: TlsVerification = TlsVerification::AcceptAny;
+
+// Generates a key
+fn generate_key() -> PKey<Private> {
     let rsa = Rsa::generate(2048).unwrap();
-    let key = PKey::from_rsa(rsa).unwrap();
+    PKey::from_rsa(rsa).unwrap()
+}
 
+// Generates a self-signed cert for the key, naming `alt_uri` if any
+fn self_sign(key: &PKey<Private>, alt_uri: Option<&str>) -> x509::X509 {
     let mut name = X509Name::builder().unwrap();
     name.append_entry_by_nid(openssl::nid::Nid::COMMONNAME, "example.com")
         .unwrap();
@@ -37,16 +55,48 @@ fn generate_pair() -> (PKey<Private>, x509::X509) {
     builder.set_version(2).unwrap();
     builder.set_subject_name(&name).unwrap();
     builder.set_issuer_name(&name).unwrap();
-    builder.set_pubkey(&key).unwrap();
+    builder.set_pubkey(key).unwrap();
     let not_before = Asn1Time::days_from_now(0).unwrap();
     builder.set_not_before(&not_before).unwrap();
     let not_after = Asn1Time::days_from_now(3650).unwrap();
     builder.set_not_after(&not_after).unwrap();
-    builder.sign(&key, MessageDigest::sha256()).unwrap();
+    if let Some(uri) = alt_uri {
+        let alt_name = SubjectAlternativeName::new()
+            .uri(uri)
+            .build(&builder.x509v3_context(None, None))
+            .unwrap();
+        builder.append_extension(alt_name).unwrap();
+    }
+    builder.sign(key, MessageDigest::sha256()).unwrap();
 
-    let cert: openssl::x509::X509 = builder.build();
+    builder.build()
+}
 
-    (key, cert)
+// write `data` to `path`, readable by us only as it is a private key
+fn write_private(path: &Path, data: &[u8]) -> TransportResult<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(not(target_os = "windows"))]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(data)?;
+    Ok(())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -60,29 +110,144 @@ impl TlsCertificate {
     /// This makes it possible to use a TLS encrypted connection securely between two
     /// peers using the lib3h websockt actor.
     pub fn build_from_entropy() -> Self {
-        let (key, cert) = generate_pair();
+        let key = generate_key();
+        let cert = self_sign(&key, None);
+        Self::from_parts(&key, &cert, Vec::new()).unwrap()
+    }
+
+    /// Loads the certificate we generated and stored in `work_dir` before,
+    /// so that we keep our identity across restarts.
+    /// Generates it as `build_from_entropy` does, and stores it, the first time.
+    pub fn load_or_build_from_entropy(work_dir: &Path) -> TransportResult<Self> {
+        let cert_path = work_dir.join(TLS_CERT_FILE_NAME);
+        let key_path = work_dir.join(TLS_KEY_FILE_NAME);
+        if cert_path.exists() && key_path.exists() {
+            return Self::from_pem_files(work_dir, &cert_path, &key_path);
+        }
+        let key = generate_key();
+        let cert = self_sign(&key, None);
+        std::fs::create_dir_all(work_dir)?;
+        write_private(&key_path, &key.private_key_to_pem_pkcs8()?)?;
+        std::fs::write(&cert_path, cert.to_pem()?)?;
+        Self::from_parts(&key, &cert, Vec::new())
+    }
 
+    /// Loads a PEM encoded certificate, followed by its chain if any,
+    /// and its PEM encoded private key. Relative paths are under `work_dir`.
+    pub fn from_pem_files(
+        work_dir: &Path,
+        cert_path: &Path,
+        key_path: &Path,
+    ) -> TransportResult<Self> {
+        let mut certs = X509::stack_from_pem(&std::fs::read(work_dir.join(cert_path))?)?;
+        if certs.is_empty() {
+            return Err(TransportError::new(format!(
+                "no certificate in {}",
+                cert_path.display()
+            )));
+        }
+        let cert = certs.remove(0);
+        let key = PKey::private_key_from_pem(&std::fs::read(work_dir.join(key_path))?)?;
+        if !cert.public_key()?.public_eq(&key) {
+            return Err(TransportError::new(format!(
+                "{} is not the key of the certificate in {}",
+                key_path.display(),
+                cert_path.display()
+            )));
+        }
+        Self::from_parts(&key, &cert, certs)
+    }
+
+    /// Loads a PKCS#12 archive of our certificate and private key.
+    /// A relative path is under `work_dir`.
+    pub fn from_pkcs12_file(
+        work_dir: &Path,
+        path: &Path,
+        passphrase: &str,
+    ) -> TransportResult<Self> {
+        let pkcs12_data = std::fs::read(work_dir.join(path))?;
+        // fail now rather than on our first connection
+        openssl::pkcs12::Pkcs12::from_der(&pkcs12_data)?.parse(passphrase)?;
+        Ok(Self {
+            pkcs12_data,
+            passphrase: passphrase.to_string(),
+        })
+    }
+
+    /// Creates a self-signed certificate as `build_from_entropy` does, with its
+    /// public key signed by the key of node `node_id`.
+    /// Peers verifying with `TlsVerification::NodeIds` know which node they talk to.
+    #[allow(clippy::borrowed_box)]
+    pub fn build_for_node(
+        crypto: &dyn CryptoSystem,
+        node_id: &NodePubKey,
+        node_secret_key: &Box<dyn Buffer>,
+    ) -> TransportResult<Self> {
+        let key = generate_key();
+        let public_key: Box<dyn Buffer> = Box::new(key.public_key_to_der()?);
+        let mut signature: Box<dyn Buffer> = Box::new(vec![0; crypto.sign_bytes()]);
+        crypto.sign(&mut signature, &public_key, node_secret_key)?;
+        let binding = format!("{}{}:{}", NODE_BINDING_PREFIX, node_id, to_hex(&signature));
+        let cert = self_sign(&key, Some(&binding));
+        Self::from_parts(&key, &cert, Vec::new())
+    }
+
+    /// The SHA-256 of the DER encoded certificate, for peers to pin it
+    pub fn fingerprint(&self) -> TransportResult<Vec<u8>> {
+        let parsed =
+            openssl::pkcs12::Pkcs12::from_der(&self.pkcs12_data)?.parse(&self.passphrase)?;
+        fingerprint(&parsed.cert.to_der()?)
+    }
+
+    fn from_parts(key: &PKey<Private>, cert: &X509, chain: Vec<X509>) -> TransportResult<Self> {
         let random_passphrase: String = thread_rng().sample_iter(&Alphanumeric).take(30).collect();
 
-        let pkcs12 = openssl::pkcs12::Pkcs12::builder()
-            .build(&random_passphrase, "friendly_name", &*key, &cert)
-            .unwrap();
+        let mut builder = openssl::pkcs12::Pkcs12::builder();
+        if !chain.is_empty() {
+            let mut ca = Stack::new()?;
+            for cert in chain {
+                ca.push(cert)?;
+            }
+            builder.ca(ca);
+        }
+        let pkcs12 = builder.build(&random_passphrase, "friendly_name", key, cert)?;
 
         // The DER-encoded bytes of the archive
-        let der = pkcs12.to_der().unwrap();
+        let der = pkcs12.to_der()?;
 
-        Self {
+        Ok(Self {
             pkcs12_data: der,
             passphrase: random_passphrase,
-        }
+        })
     }
 }
 
+/// How we verify the certificates of the servers we connect to
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum TlsVerification {
+    /// accept any certificate
+    AcceptAny,
+    /// the certificate chain must lead to one of these PEM encoded CA
+    /// certificates, or to a system root, and name the host we connect to
+    CaBundle(Vec<u8>),
+    /// the SHA-256 of the DER encoded certificate must be one of these
+    PinnedFingerprints(Vec<Vec<u8>>),
+    /// the certificate must be built by `TlsCertificate::build_for_node`
+    /// for one of these nodes, or for any node if empty
+    NodeIds(Vec<NodePubKey>),
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum TlsConfig {
     Unencrypted,
     FakeServer,
     SuppliedCertificate(TlsCertificate),
+    /// `certificate` is our identity as with `SuppliedCertificate`, and we
+    /// verify the servers we connect to with `verification`
+    Verified {
+        certificate: TlsCertificate,
+        verification: TlsVerification,
+    },
 }
 
 impl TlsConfig {
@@ -90,24 +255,180 @@ impl TlsConfig {
         TlsConfig::SuppliedCertificate(TlsCertificate::build_from_entropy())
     }
 
+    /// Our stored certificate, see `TlsCertificate::load_or_build_from_entropy`
+    pub fn load_or_build_from_entropy(work_dir: &Path) -> TransportResult<Self> {
+        Ok(TlsConfig::SuppliedCertificate(
+            TlsCertificate::load_or_build_from_entropy(work_dir)?,
+        ))
+    }
+
+    /// Present `certificate` from now on, keeping our verification
+    pub fn set_certificate(&mut self, certificate: TlsCertificate) -> TransportResult<()> {
+        // a broken certificate must not replace a working one
+        native_tls::Identity::from_pkcs12(&certificate.pkcs12_data, &certificate.passphrase)?;
+        match self {
+            TlsConfig::Unencrypted => Err(TransportError::new(
+                "an unencrypted transport has no tls identity".to_string(),
+            )),
+            TlsConfig::Verified {
+                certificate: current,
+                ..
+            } => {
+                *current = certificate;
+                Ok(())
+            }
+            _ => {
+                *self = TlsConfig::SuppliedCertificate(certificate);
+                Ok(())
+            }
+        }
+    }
+
     pub fn get_identity(&self) -> TransportResult<native_tls::Identity> {
         Ok(match self {
             TlsConfig::Unencrypted => unimplemented!(),
             TlsConfig::FakeServer => native_tls::Identity::from_pkcs12(FAKE_PKCS12, FAKE_PASS)?,
-            TlsConfig::SuppliedCertificate(cert) => {
-                native_tls::Identity::from_pkcs12(&cert.pkcs12_data, &cert.passphrase)?
-            }
+            TlsConfig::SuppliedCertificate(cert)
+            | TlsConfig::Verified {
+                certificate: cert, ..
+            } => native_tls::Identity::from_pkcs12(&cert.pkcs12_data, &cert.passphrase)?,
         })
     }
+
+    /// How we verify the servers we connect to, only `Verified` checks anything
+    pub fn get_verification(&self) -> &TlsVerification {
+        match self {
+            TlsConfig::Verified { verification, .. } => verification,
+            _ => &ACCEPT_ANY,
+        }
+    }
+
+    /// A connector checking the certificate chain if our verification asks for it.
+    /// Other verifications are done by `verify_peer` once the handshake is done.
+    pub fn build_connector(&self) -> TransportResult<native_tls::TlsConnector> {
+        let mut builder = native_tls::TlsConnector::builder();
+        if let TlsVerification::CaBundle(pem) = self.get_verification() {
+            for cert in X509::stack_from_pem(pem)? {
+                builder.add_root_certificate(native_tls::Certificate::from_der(&cert.to_der()?)?);
+            }
+        } else {
+            builder
+                .danger_accept_invalid_certs(true)
+                .danger_accept_invalid_hostnames(true);
+        }
+        Ok(builder.build()?)
+    }
+
+    /// The error of a failed client handshake, typed if it failed verifying the chain
+    pub fn handshake_failure(&self, error: native_tls::Error) -> TransportError {
+        if let TlsVerification::CaBundle(_) = self.get_verification() {
+            TlsVerificationError::Untrusted(format!("{:?}", error)).into()
+        } else {
+            error.into()
+        }
+    }
+
+    /// Check the certificate of a server we finished a handshake with,
+    /// node bindings with `crypto`
+    pub fn verify_peer<S: std::io::Read + std::io::Write>(
+        &self,
+        crypto: &dyn CryptoSystem,
+        stream: &native_tls::TlsStream<S>,
+    ) -> TransportResult<()> {
+        match self.get_verification() {
+            TlsVerification::AcceptAny | TlsVerification::CaBundle(_) => Ok(()),
+            TlsVerification::PinnedFingerprints(fingerprints) => {
+                let fingerprint = fingerprint(&peer_certificate(stream)?)?;
+                if fingerprints.contains(&fingerprint) {
+                    Ok(())
+                } else {
+                    Err(TlsVerificationError::FingerprintMismatch(fingerprint).into())
+                }
+            }
+            TlsVerification::NodeIds(node_ids) => {
+                let node_id = node_binding(crypto, &peer_certificate(stream)?)?;
+                if node_ids.is_empty() || node_ids.contains(&node_id) {
+                    debug!("tls server proved to be node {}", node_id);
+                    Ok(())
+                } else {
+                    Err(TlsVerificationError::UnexpectedNode(node_id).into())
+                }
+            }
+        }
+    }
+}
+
+fn fingerprint(der: &[u8]) -> TransportResult<Vec<u8>> {
+    Ok(openssl::hash::hash(MessageDigest::sha256(), der)?.to_vec())
+}
+
+// the DER encoded certificate of the server
+fn peer_certificate<S: std::io::Read + std::io::Write>(
+    stream: &native_tls::TlsStream<S>,
+) -> TransportResult<Vec<u8>> {
+    match stream.peer_certificate()? {
+        Some(cert) => Ok(cert.to_der()?),
+        None => Err(TlsVerificationError::NoCertificate.into()),
+    }
+}
+
+// the node a certificate built by `TlsCertificate::build_for_node` is bound to
+fn node_binding(crypto: &dyn CryptoSystem, der: &[u8]) -> Result<NodePubKey, TlsVerificationError> {
+    let invalid = TlsVerificationError::NodeBindingInvalid;
+    let cert = X509::from_der(der).map_err(|e| invalid(format!("{:?}", e)))?;
+    let binding = cert
+        .subject_alt_names()
+        .into_iter()
+        .flatten()
+        .filter_map(|name| name.uri().map(|uri| uri.to_string()))
+        .find(|uri| uri.starts_with(NODE_BINDING_PREFIX))
+        .ok_or_else(|| invalid("the certificate names no node".to_string()))?;
+    let mut parts = binding[NODE_BINDING_PREFIX.len()..].splitn(2, ':');
+    let node_id = parts.next().unwrap_or("");
+    let signature = parts
+        .next()
+        .and_then(from_hex)
+        .ok_or_else(|| invalid(format!("malformed node binding {}", binding)))?;
+    let node_public_key = hcid::HcidEncoding::with_kind("hcm0")
+        .and_then(|hcm0| hcm0.decode(node_id))
+        .map_err(|e| invalid(format!("bad node id {}: {:?}", node_id, e)))?;
+    let public_key = cert
+        .public_key()
+        .and_then(|key| key.public_key_to_der())
+        .map_err(|e| invalid(format!("{:?}", e)))?;
+
+    if signature.len() != crypto.sign_bytes()
+        || node_public_key.len() != crypto.sign_public_key_bytes()
+    {
+        return Err(invalid(format!("malformed node binding {}", binding)));
+    }
+    let signature: Box<dyn Buffer> = Box::new(signature);
+    let public_key: Box<dyn Buffer> = Box::new(public_key);
+    let node_public_key: Box<dyn Buffer> = Box::new(node_public_key);
+    if crypto
+        .sign_verify(&signature, &public_key, &node_public_key)
+        .unwrap_or(false)
+    {
+        Ok(node_id.into())
+    } else {
+        Err(invalid(format!(
+            "node {} did not sign the certificate key",
+            node_id
+        )))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::transport::websocket::{
-        mem_stream::*,
-        streams::{StreamEvent, StreamManager},
+    use crate::transport::{
+        error::ErrorKind,
+        websocket::{
+            mem_stream::*,
+            streams::{ConnectionStatus, EvictionPolicy, StreamEvent, StreamLimits, StreamManager},
+        },
     };
+    use lib3h_sodium::SodiumCryptoSystem;
     use std::io::{Read, Write};
     use url2::prelude::*;
 
@@ -416,7 +737,10 @@ mod tests {
         }
 
         fn bind(&mut self, url: Url2) -> Url2 {
-            let mut new_manager = StreamManager::with_mem_stream(self.tls_config.clone());
+            let mut new_manager = StreamManager::with_mem_stream(
+                self.tls_config.clone(),
+                Box::new(SodiumCryptoSystem::new()),
+            );
             let url: Url2 = new_manager.bind(&url.into()).unwrap().into();
             self.managers.insert(url.clone(), new_manager);
             url
@@ -490,4 +814,268 @@ mod tests {
         t.send(&url1, &url2, b"hello");
         t.send(&url2, &url_a, b"hello2");
     }
+
+    #[test]
+    fn it_should_evict_least_recently_used_inbound() {
+        let mut t = StreamTester::new(TlsConfig::Unencrypted);
+        let url1 = t.bind(Url2::parse("mem://evict1"));
+        let url2 = t.bind(Url2::parse("mem://evict2"));
+        let url3 = t.bind(Url2::parse("mem://evict3"));
+        t.managers.get_mut(&url3).unwrap().set_limits(StreamLimits {
+            max_inbound: Some(1),
+            ..Default::default()
+        });
+        let url_a = t.connect(&url1, &url3);
+
+        t.managers.get_mut(&url2).unwrap().connect(&url3).unwrap();
+        let events = t.process();
+        assert!(events.iter().any(|ev| match ev {
+            StreamEvent::ConnectionEvicted(url) => *url == *url_a,
+            _ => false,
+        }));
+        assert!(events.iter().any(|ev| match ev {
+            StreamEvent::IncomingConnectionEstablished(_) => true,
+            _ => false,
+        }));
+    }
+
+    #[test]
+    fn it_should_reject_outbound_at_limit() {
+        let mut t = StreamTester::new(TlsConfig::Unencrypted);
+        let url1 = t.bind(Url2::parse("mem://reject1"));
+        let url2 = t.bind(Url2::parse("mem://reject2"));
+        let url3 = t.bind(Url2::parse("mem://reject3"));
+        t.managers.get_mut(&url1).unwrap().set_limits(StreamLimits {
+            max_outbound: Some(1),
+            eviction: EvictionPolicy::RejectNew,
+            ..Default::default()
+        });
+        t.connect(&url1, &url2);
+
+        let error = t
+            .managers
+            .get_mut(&url1)
+            .unwrap()
+            .connect(&url3)
+            .expect_err("the limit should be reached");
+        assert!(error.is_would_block());
+
+        // the established connection is kept
+        t.send(&url1, &url2, b"hello");
+    }
+
+    #[test]
+    fn it_should_reap_idle_connections() {
+        let mut t = StreamTester::new(TlsConfig::Unencrypted);
+        let url1 = t.bind(Url2::parse("mem://idle1"));
+        let url2 = t.bind(Url2::parse("mem://idle2"));
+        t.managers.get_mut(&url1).unwrap().set_limits(StreamLimits {
+            idle_timeout_ms: Some(10),
+            ..Default::default()
+        });
+        t.connect(&url1, &url2);
+
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(t.process().iter().any(|ev| match ev {
+            StreamEvent::ConnectionClosed(url) => *url == *url2,
+            _ => false,
+        }));
+        assert!(ConnectionStatus::None == t.managers.get(&url1).unwrap().connection_status(&url2));
+    }
+
+    #[test]
+    fn it_should_keep_connections_when_rotating_identity() {
+        let first = TlsCertificate::build_from_entropy();
+        let second = TlsCertificate::build_from_entropy();
+        let pin = |certificate: &TlsCertificate| {
+            verified(
+                certificate.clone(),
+                TlsVerification::PinnedFingerprints(vec![certificate.fingerprint().unwrap()]),
+            )
+        };
+
+        let mut t = StreamTester::new(pin(&first));
+        let url1 = t.bind(Url2::parse("mem://rotate1"));
+        let url2 = t.bind(Url2::parse("mem://rotate2"));
+        t.connect(&url1, &url2);
+
+        t.managers
+            .get_mut(&url2)
+            .unwrap()
+            .set_tls_certificate(second.clone())
+            .unwrap();
+
+        // new connections only accept the new certificate...
+        t.tls_config = pin(&second);
+        let url3 = t.bind(Url2::parse("mem://rotate3"));
+        t.connect(&url3, &url2);
+
+        // ...while the old one is still up
+        t.send(&url1, &url2, b"hello");
+    }
+
+    #[test]
+    fn it_should_not_rotate_unencrypted_identity() {
+        let mut tls_config = TlsConfig::Unencrypted;
+        assert!(tls_config
+            .set_certificate(TlsCertificate::build_from_entropy())
+            .is_err());
+        assert_eq!(TlsConfig::Unencrypted, tls_config);
+    }
+
+    #[test]
+    fn it_should_persist_entropy_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        let work_dir = dir.path().join("work");
+        let certificate = TlsCertificate::load_or_build_from_entropy(&work_dir).unwrap();
+        assert!(work_dir.join(TLS_KEY_FILE_NAME).exists());
+        let reloaded = TlsCertificate::load_or_build_from_entropy(&work_dir).unwrap();
+        assert_eq!(
+            certificate.fingerprint().unwrap(),
+            reloaded.fingerprint().unwrap()
+        );
+
+        // the stored pem files load as any other
+        let loaded = TlsCertificate::from_pem_files(
+            &work_dir,
+            Path::new(TLS_CERT_FILE_NAME),
+            Path::new(TLS_KEY_FILE_NAME),
+        )
+        .unwrap();
+        assert_eq!(
+            certificate.fingerprint().unwrap(),
+            loaded.fingerprint().unwrap()
+        );
+
+        // but not with the key of another certificate
+        std::fs::write(
+            work_dir.join("other_key.pem"),
+            generate_key().private_key_to_pem_pkcs8().unwrap(),
+        )
+        .unwrap();
+        assert!(TlsCertificate::from_pem_files(
+            &work_dir,
+            Path::new(TLS_CERT_FILE_NAME),
+            Path::new("other_key.pem"),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn it_should_load_pkcs12_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let certificate = TlsCertificate::build_from_entropy();
+        std::fs::write(dir.path().join("node.p12"), &certificate.pkcs12_data).unwrap();
+
+        let loaded = TlsCertificate::from_pkcs12_file(
+            dir.path(),
+            Path::new("node.p12"),
+            &certificate.passphrase,
+        )
+        .unwrap();
+        assert_eq!(certificate, loaded);
+        assert!(
+            TlsCertificate::from_pkcs12_file(dir.path(), Path::new("node.p12"), "wrong").is_err()
+        );
+    }
+
+    // the error the connecting side got, if any,
+    // mem addresses are global so each test needs its own `name`
+    fn connect_error(name: &str, tls_config: TlsConfig) -> Option<TransportError> {
+        let mut t = StreamTester::new(tls_config);
+        let url1 = t.bind(Url2::parse(&format!("mem://{}1", name)));
+        let url2 = t.bind(Url2::parse(&format!("mem://{}2", name)));
+        t.managers.get_mut(&url1).unwrap().connect(&url2).unwrap();
+        t.process().into_iter().find_map(|ev| match ev {
+            StreamEvent::ErrorOccured(ref url, ref e) if *url == *url2 => Some(e.clone()),
+            _ => None,
+        })
+    }
+
+    fn verified(certificate: TlsCertificate, verification: TlsVerification) -> TlsConfig {
+        TlsConfig::Verified {
+            certificate,
+            verification,
+        }
+    }
+
+    #[test]
+    fn it_should_verify_pinned_fingerprints() {
+        let certificate = TlsCertificate::build_from_entropy();
+        let fingerprint = certificate.fingerprint().unwrap();
+        assert_eq!(
+            None,
+            connect_error(
+                "pinned",
+                verified(
+                    certificate.clone(),
+                    TlsVerification::PinnedFingerprints(vec![fingerprint.clone()]),
+                )
+            )
+        );
+
+        let other = TlsCertificate::build_from_entropy().fingerprint().unwrap();
+        let error = connect_error(
+            "pinned",
+            verified(
+                certificate,
+                TlsVerification::PinnedFingerprints(vec![other]),
+            ),
+        )
+        .expect("the pin should not match");
+        assert_eq!(
+            &ErrorKind::TlsVerification(TlsVerificationError::FingerprintMismatch(fingerprint)),
+            error.kind()
+        );
+    }
+
+    #[test]
+    fn it_should_verify_node_bindings() {
+        let crypto = SodiumCryptoSystem::new();
+        let keys = crate::engine::TransportKeys::new(&crypto).unwrap();
+        let certificate =
+            TlsCertificate::build_for_node(&crypto, &keys.node_id, &keys.transport_secret_key)
+                .unwrap();
+
+        let node_ids = vec![keys.node_id.clone()];
+        assert_eq!(
+            None,
+            connect_error(
+                "node",
+                verified(certificate.clone(), TlsVerification::NodeIds(node_ids))
+            )
+        );
+        assert_eq!(
+            None,
+            connect_error(
+                "node",
+                verified(certificate.clone(), TlsVerification::NodeIds(vec![]))
+            )
+        );
+
+        let other = crate::engine::TransportKeys::new(&crypto).unwrap().node_id;
+        let error = connect_error(
+            "node",
+            verified(certificate, TlsVerification::NodeIds(vec![other])),
+        )
+        .expect("the node should not be accepted");
+        assert_eq!(
+            &ErrorKind::TlsVerification(TlsVerificationError::UnexpectedNode(keys.node_id)),
+            error.kind()
+        );
+
+        // a certificate not built for a node proves nothing
+        let error = connect_error(
+            "node",
+            verified(
+                TlsCertificate::build_from_entropy(),
+                TlsVerification::NodeIds(vec![]),
+            ),
+        )
+        .expect("the certificate should not be bound to a node");
+        match error.kind() {
+            ErrorKind::TlsVerification(TlsVerificationError::NodeBindingInvalid(_)) => (),
+            kind => panic!("unexpected {:?}", kind),
+        }
+    }
 }
@@ -7,6 +7,12 @@ pub struct WssInfo<T: std::io::Read + std::io::Write + std::fmt::Debug> {
     pub(in crate::transport::websocket) url: url::Url,
     pub(in crate::transport::websocket) last_msg: std::time::Instant,
     pub(in crate::transport::websocket) stateful_socket: WebsocketStreamState<T>,
+    /// true if the remote opened this connection
+    pub(in crate::transport::websocket) is_server: bool,
+    /// when we last sent or received data, heartbeats do not count
+    pub(in crate::transport::websocket) last_used: std::time::Instant,
+    /// how many messages we sent and received
+    pub(in crate::transport::websocket) message_count: u64,
 }
 
 impl<T: std::io::Read + std::io::Write + std::fmt::Debug> WssInfo<T> {
@@ -30,9 +36,18 @@ impl<T: std::io::Read + std::io::Write + std::fmt::Debug> WssInfo<T> {
                 false => WebsocketStreamState::Connecting(socket),
                 true => WebsocketStreamState::ConnectingSrv(socket),
             },
+            is_server,
+            last_used: std::time::Instant::now(),
+            message_count: 0,
         }
     }
 
+    /// record that a message was sent or received
+    pub fn touch(&mut self) {
+        self.last_used = std::time::Instant::now();
+        self.message_count += 1;
+    }
+
     pub fn client(url: url::Url, socket: BaseStream<T>) -> Self {
         Self::new(url, socket, false)
     }
@@ -18,7 +18,11 @@ use lib3h_ghost_actor::{wait1_for_callback, wait1_for_messages};
 use holochain_tracing::{tracer_console::*, Span};
 use lib3h::{
     dht::mirror_dht::MirrorDht,
-    engine::{EngineConfig, GhostEngine, TransportConfig},
+    engine::{
+        default_dht_arc_resize_interval, default_dht_target_maximum_r_value,
+        default_dht_target_minimum_r_value, default_transit_encodings, EngineConfig, GhostEngine,
+        TransportConfig,
+    },
     transport::websocket::tls::TlsConfig,
 };
 
@@ -71,6 +75,19 @@ fn basic_setup_mock_bootstrap<'engine>(
         dht_gossip_interval: 100,
         dht_timeout_threshold: 1000,
         dht_custom_config: vec![],
+        dht_arc_resize_interval: default_dht_arc_resize_interval(),
+        dht_target_minimum_r_value: default_dht_target_minimum_r_value(),
+        dht_target_maximum_r_value: default_dht_target_maximum_r_value(),
+        transit_encodings: default_transit_encodings(),
+        relay_uri: None,
+        relay_quota: None,
+        keystore_passphrase: None,
+        stream_limits: None,
+        reconnect_policy: None,
+        compression: None,
+        chunking: None,
+        discovery: vec![],
+        sign_mdns_records: false,
     };
     let root_span: Span = LIB3H_TRACER
         .lock()
@@ -109,6 +126,19 @@ fn basic_setup_wss<'engine>(name: &str) -> GhostEngine<'engine> {
         dht_gossip_interval: 200,
         dht_timeout_threshold: 2000,
         dht_custom_config: vec![],
+        dht_arc_resize_interval: default_dht_arc_resize_interval(),
+        dht_target_minimum_r_value: default_dht_target_minimum_r_value(),
+        dht_target_maximum_r_value: default_dht_target_maximum_r_value(),
+        transit_encodings: default_transit_encodings(),
+        relay_uri: None,
+        relay_quota: None,
+        keystore_passphrase: None,
+        stream_limits: None,
+        reconnect_policy: None,
+        compression: None,
+        chunking: None,
+        discovery: vec![],
+        sign_mdns_records: false,
     };
     let root_span: Span = LIB3H_TRACER
         .lock()
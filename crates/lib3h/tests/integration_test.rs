@@ -21,16 +21,25 @@ mod test_suites;
 use holochain_tracing::{tracer_console::*, Span};
 use lib3h::{
     dht::mirror_dht::MirrorDht,
-    engine::{ghost_engine_wrapper::WrappedGhostLib3h, EngineConfig, GhostEngine, TransportConfig},
+    engine::{
+        default_dht_arc_resize_interval, default_dht_target_maximum_r_value,
+        default_dht_target_minimum_r_value, default_transit_encodings,
+        ghost_engine_wrapper::WrappedGhostLib3h, EngineConfig, GhostEngine, RelayQuota,
+        TransportConfig,
+    },
     error::Lib3hResult,
-    transport::websocket::tls::TlsConfig,
+    transport::{
+        memory_mock::memory_server::get_memory_verse,
+        websocket::tls::{TlsCertificate, TlsConfig, TlsVerification},
+    },
     LIB3H_TRACER,
 };
 use lib3h_protocol::{types::*, uri::Lib3hUri};
 use node_mock::NodeMock;
 use std::path::PathBuf;
 use test_suites::{
-    mirror::*, three_basic::*, two_basic::*, two_connection::*, two_get_lists::*, two_spaces::*,
+    mirror::*, three_basic::*, three_relay::*, two_basic::*, two_connection::*, two_get_lists::*,
+    two_spaces::*,
 };
 use url::Url;
 use utils::{constants::*, processor_harness::ProcessingOptions, test_network_id};
@@ -136,8 +145,13 @@ fn construct_wss_engine(config: &EngineConfig, name: &str) -> Lib3hResult<Wrappe
 pub type NodeFactory = fn(name: &str, agent_id_arg: AgentPubKey) -> NodeMock;
 
 fn setup_memory_node(name: &str, agent_id_arg: AgentPubKey, fn_name: &str) -> NodeMock {
+    let config = memory_node_config(name, fn_name);
+    NodeMock::new_with_config(name, agent_id_arg, config, construct_mock_engine)
+}
+
+fn memory_node_config(name: &str, fn_name: &str) -> EngineConfig {
     let fn_name = fn_name.replace("::", "__");
-    let config = EngineConfig {
+    EngineConfig {
         network_id: test_network_id(),
         transport_configs: vec![TransportConfig::Memory(fn_name.clone())],
         bootstrap_nodes: vec![],
@@ -147,8 +161,20 @@ fn setup_memory_node(name: &str, agent_id_arg: AgentPubKey, fn_name: &str) -> No
         dht_gossip_interval: 300,
         dht_timeout_threshold: 180005,
         dht_custom_config: vec![],
-    };
-    NodeMock::new_with_config(name, agent_id_arg, config, construct_mock_engine)
+        dht_arc_resize_interval: default_dht_arc_resize_interval(),
+        dht_target_minimum_r_value: default_dht_target_minimum_r_value(),
+        dht_target_maximum_r_value: default_dht_target_maximum_r_value(),
+        transit_encodings: default_transit_encodings(),
+        relay_uri: None,
+        relay_quota: None,
+        keystore_passphrase: None,
+        stream_limits: None,
+        reconnect_policy: None,
+        compression: None,
+        chunking: None,
+        discovery: vec![],
+        sign_mdns_records: false,
+    }
 }
 
 fn setup_wss_node(
@@ -161,7 +187,7 @@ fn setup_wss_node(
     let port = generate_port();
     let protocol = match tls_config {
         TlsConfig::Unencrypted => "ws",
-        TlsConfig::SuppliedCertificate(_) | TlsConfig::FakeServer => "wss",
+        _ => "wss",
     };
     let bind_url = Url::parse(format!("{}://127.0.0.1:{}/{}", protocol, port, fn_name).as_str())
         .expect("invalid web socket url")
@@ -177,6 +203,125 @@ fn setup_wss_node(
         dht_gossip_interval: 300,
         dht_timeout_threshold: 60005,
         dht_custom_config: vec![],
+        dht_arc_resize_interval: default_dht_arc_resize_interval(),
+        dht_target_minimum_r_value: default_dht_target_minimum_r_value(),
+        dht_target_maximum_r_value: default_dht_target_maximum_r_value(),
+        transit_encodings: default_transit_encodings(),
+        relay_uri: None,
+        relay_quota: None,
+        keystore_passphrase: None,
+        stream_limits: None,
+        reconnect_policy: None,
+        compression: None,
+        chunking: None,
+        discovery: vec![],
+        sign_mdns_records: false,
+    };
+    NodeMock::new_with_config(name, agent_id_arg, config, construct_wss_engine)
+}
+
+fn setup_tcp_node(
+    name: &str,
+    agent_id_arg: AgentPubKey,
+    tls_config: TlsConfig,
+    fn_name: &str,
+) -> NodeMock {
+    let fn_name = fn_name.replace("::", "__");
+    let port = generate_port();
+    let bind_url = Url::parse(format!("tcp://127.0.0.1:{}/{}", port, fn_name).as_str())
+        .expect("invalid tcp url")
+        .into();
+
+    let config = EngineConfig {
+        network_id: test_network_id(),
+        transport_configs: vec![TransportConfig::Tcp(tls_config)],
+        bootstrap_nodes: vec![],
+        work_dir: PathBuf::new(),
+        log_level: 'd',
+        bind_url,
+        dht_gossip_interval: 300,
+        dht_timeout_threshold: 60005,
+        dht_custom_config: vec![],
+        dht_arc_resize_interval: default_dht_arc_resize_interval(),
+        dht_target_minimum_r_value: default_dht_target_minimum_r_value(),
+        dht_target_maximum_r_value: default_dht_target_maximum_r_value(),
+        transit_encodings: default_transit_encodings(),
+        relay_uri: None,
+        relay_quota: None,
+        keystore_passphrase: None,
+        stream_limits: None,
+        reconnect_policy: None,
+        compression: None,
+        chunking: None,
+        discovery: vec![],
+        sign_mdns_records: false,
+    };
+    NodeMock::new_with_config(name, agent_id_arg, config, construct_wss_engine)
+}
+
+fn setup_udp_node(name: &str, agent_id_arg: AgentPubKey, fn_name: &str) -> NodeMock {
+    let fn_name = fn_name.replace("::", "__");
+    let port = generate_port();
+    let bind_url = Url::parse(format!("udp://127.0.0.1:{}/{}", port, fn_name).as_str())
+        .expect("invalid udp url")
+        .into();
+
+    let config = EngineConfig {
+        network_id: test_network_id(),
+        transport_configs: vec![TransportConfig::Udp],
+        bootstrap_nodes: vec![],
+        work_dir: PathBuf::new(),
+        log_level: 'd',
+        bind_url,
+        dht_gossip_interval: 300,
+        dht_timeout_threshold: 60005,
+        dht_custom_config: vec![],
+        dht_arc_resize_interval: default_dht_arc_resize_interval(),
+        dht_target_minimum_r_value: default_dht_target_minimum_r_value(),
+        dht_target_maximum_r_value: default_dht_target_maximum_r_value(),
+        transit_encodings: default_transit_encodings(),
+        relay_uri: None,
+        relay_quota: None,
+        keystore_passphrase: None,
+        stream_limits: None,
+        reconnect_policy: None,
+        compression: None,
+        chunking: None,
+        discovery: vec![],
+        sign_mdns_records: false,
+    };
+    NodeMock::new_with_config(name, agent_id_arg, config, construct_wss_engine)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn setup_uds_node(name: &str, agent_id_arg: AgentPubKey) -> NodeMock {
+    // socket paths are limited to about a hundred bytes, keep it short
+    let path = std::env::temp_dir().join(format!("lib3h-{}-{}.sock", name, generate_port()));
+    let bind_url = Lib3hUri::with_uds(&path);
+
+    let config = EngineConfig {
+        network_id: test_network_id(),
+        transport_configs: vec![TransportConfig::Uds],
+        bootstrap_nodes: vec![],
+        work_dir: PathBuf::new(),
+        log_level: 'd',
+        bind_url,
+        dht_gossip_interval: 300,
+        dht_timeout_threshold: 60005,
+        dht_custom_config: vec![],
+        dht_arc_resize_interval: default_dht_arc_resize_interval(),
+        dht_target_minimum_r_value: default_dht_target_minimum_r_value(),
+        dht_target_maximum_r_value: default_dht_target_maximum_r_value(),
+        transit_encodings: default_transit_encodings(),
+        relay_uri: None,
+        relay_quota: None,
+        keystore_passphrase: None,
+        stream_limits: None,
+        reconnect_policy: None,
+        compression: None,
+        chunking: None,
+        discovery: vec![],
+        sign_mdns_records: false,
     };
     NodeMock::new_with_config(name, agent_id_arg, config, construct_wss_engine)
 }
@@ -260,6 +405,14 @@ fn test_three_memory_nodes_basic_suite() {
         launch_three_memory_nodes_test(*test_fn, *can_setup).unwrap();
     }
 }
+#[test]
+fn test_three_memory_nodes_relay_suite() {
+    enable_logging_for_test(true);
+    for (test_fn, can_setup) in THREE_NODES_RELAY_TEST_FNS.iter() {
+        launch_three_memory_nodes_relay_test(*test_fn, *can_setup).unwrap();
+    }
+}
+
 #[test]
 #[ignore]
 fn test_two_memory_nodes_connection_suite() {
@@ -339,6 +492,61 @@ fn launch_three_memory_nodes_test(test_fn: ThreeNodesTestFn, can_setup: bool) ->
     Ok(())
 }
 
+// Same as launch_three_memory_nodes_test() but Alex cannot be connected to,
+// and is reached through Billy
+fn launch_three_memory_nodes_relay_test(
+    test_fn: ThreeNodesTestFn,
+    can_setup: bool,
+) -> Result<(), ()> {
+    let test_fn_ptr = test_fn as *mut std::os::raw::c_void;
+    debug!("");
+    print_test_name("IN-MEMORY THREE NODES RELAY TEST: ", test_fn_ptr);
+    debug!("==========================");
+
+    // Setup
+    let fn_name = fn_name(test_fn_ptr);
+    let mut billy_config = memory_node_config("billy", &fn_name);
+    billy_config.relay_quota = Some(RelayQuota {
+        max_clients: 1,
+        max_bytes_per_minute: 1024 * 1024,
+    });
+    let mut billy = NodeMock::new_with_config(
+        "billy",
+        BILLY_AGENT_ID.clone(),
+        billy_config,
+        construct_mock_engine,
+    );
+    let mut alex_config = memory_node_config("alex", &fn_name);
+    alex_config.relay_uri = Some(billy.advertise());
+    let mut alex = NodeMock::new_with_config(
+        "alex",
+        ALEX_AGENT_ID.clone(),
+        alex_config,
+        construct_mock_engine,
+    );
+    // Alex was bound second
+    get_memory_verse()
+        .get_network(&fn_name.replace("::", "__"))
+        .lock()
+        .set_reachable(&Lib3hUri::with_memory("addr_2"), false);
+    let mut camille = setup_memory_node("camille", CAMILLE_AGENT_ID.clone(), &fn_name);
+    let options = &THREE_MEMORY_NODES_PROCESSING_OPTIONS;
+
+    if can_setup {
+        setup_three_relay_nodes(&mut alex, &mut billy, &mut camille, options);
+    }
+
+    // Execute test
+    test_fn(&mut alex, &mut billy, &mut camille, options);
+
+    // Wrap-up test
+    debug!("==========================");
+    print_test_name("IN-MEMORY THREE NODES RELAY TEST END: ", test_fn_ptr);
+
+    // Done
+    Ok(())
+}
+
 #[test]
 fn test_mirror_suite() {
     enable_logging_for_test(true);
@@ -471,6 +679,22 @@ fn test_two_wss_tls_nodes_connection_suite() {
     }
 }
 
+#[test]
+#[ignore]
+fn test_two_wss_pinned_tls_nodes_basic_suite() {
+    enable_logging_for_test(true);
+    // both nodes present the same certificate, and only accept it
+    let certificate = TlsCertificate::build_from_entropy();
+    let fingerprint = certificate.fingerprint().unwrap();
+    let tls_config = TlsConfig::Verified {
+        certificate,
+        verification: TlsVerification::PinnedFingerprints(vec![fingerprint]),
+    };
+    for (test_fn, can_setup) in TWO_NODES_BASIC_TEST_FNS.iter() {
+        launch_two_wss_nodes_test(*test_fn, tls_config.clone(), *can_setup).unwrap();
+    }
+}
+
 // Do general test with config
 fn launch_two_wss_nodes_test(
     test_fn: TwoNodesTestFn,
@@ -578,3 +802,132 @@ fn launch_three_wss_nodes_test(
     // Done
     Ok(())
 }
+
+// -- Tcp Transport Tests --
+#[test]
+fn test_two_tcp_nodes_basic_suite() {
+    enable_logging_for_test(true);
+    for (test_fn, can_setup) in TWO_NODES_BASIC_TEST_FNS.iter() {
+        launch_two_tcp_nodes_test(*test_fn, TlsConfig::Unencrypted, *can_setup).unwrap();
+    }
+}
+
+#[test]
+fn test_two_tcp_tls_nodes_basic_suite() {
+    enable_logging_for_test(true);
+    for (test_fn, can_setup) in TWO_NODES_BASIC_TEST_FNS.iter() {
+        launch_two_tcp_nodes_test(*test_fn, TlsConfig::FakeServer, *can_setup).unwrap();
+    }
+}
+
+fn launch_two_tcp_nodes_test(
+    test_fn: TwoNodesTestFn,
+    tls_config: TlsConfig,
+    can_setup: bool,
+) -> Result<(), ()> {
+    let test_fn_ptr = test_fn as *mut std::os::raw::c_void;
+    println!("");
+    print_test_name(
+        format!("TCP TWO NODES TEST ({:?}): ", tls_config.clone()).as_str(),
+        test_fn_ptr,
+    );
+    println!("========================");
+
+    // Setup
+    let mut alex = setup_tcp_node(
+        "alex",
+        ALEX_AGENT_ID.clone(),
+        tls_config.clone(),
+        &fn_name(test_fn_ptr),
+    );
+    let mut billy = setup_tcp_node(
+        "billy",
+        BILLY_AGENT_ID.clone(),
+        tls_config.clone(),
+        &fn_name(test_fn_ptr),
+    );
+    if can_setup {
+        setup_two_nodes(&mut alex, &mut billy, &TWO_WSS_NODES_PROCESSING_OPTIONS);
+    }
+
+    // Execute test
+    test_fn(&mut alex, &mut billy, &TWO_WSS_NODES_PROCESSING_OPTIONS);
+
+    // Wrap-up test
+    println!("========================");
+    print_test_name(
+        format!("TCP TWO NODES TEST END ({:?}):", tls_config.clone()).as_str(),
+        test_fn_ptr,
+    );
+
+    // Done
+    Ok(())
+}
+
+// -- Udp Transport Tests --
+#[test]
+fn test_two_udp_nodes_basic_suite() {
+    enable_logging_for_test(true);
+    for (test_fn, can_setup) in TWO_NODES_BASIC_TEST_FNS.iter() {
+        launch_two_udp_nodes_test(*test_fn, *can_setup).unwrap();
+    }
+}
+
+fn launch_two_udp_nodes_test(test_fn: TwoNodesTestFn, can_setup: bool) -> Result<(), ()> {
+    let test_fn_ptr = test_fn as *mut std::os::raw::c_void;
+    println!("");
+    print_test_name("UDP TWO NODES TEST: ", test_fn_ptr);
+    println!("========================");
+
+    // Setup
+    let mut alex = setup_udp_node("alex", ALEX_AGENT_ID.clone(), &fn_name(test_fn_ptr));
+    let mut billy = setup_udp_node("billy", BILLY_AGENT_ID.clone(), &fn_name(test_fn_ptr));
+    if can_setup {
+        setup_two_nodes(&mut alex, &mut billy, &TWO_WSS_NODES_PROCESSING_OPTIONS);
+    }
+
+    // Execute test
+    test_fn(&mut alex, &mut billy, &TWO_WSS_NODES_PROCESSING_OPTIONS);
+
+    // Wrap-up test
+    println!("========================");
+    print_test_name("UDP TWO NODES TEST END: ", test_fn_ptr);
+
+    // Done
+    Ok(())
+}
+
+// -- Uds Transport Tests --
+#[cfg(not(target_os = "windows"))]
+#[test]
+fn test_two_uds_nodes_basic_suite() {
+    enable_logging_for_test(true);
+    for (test_fn, can_setup) in TWO_NODES_BASIC_TEST_FNS.iter() {
+        launch_two_uds_nodes_test(*test_fn, *can_setup).unwrap();
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn launch_two_uds_nodes_test(test_fn: TwoNodesTestFn, can_setup: bool) -> Result<(), ()> {
+    let test_fn_ptr = test_fn as *mut std::os::raw::c_void;
+    println!("");
+    print_test_name("UDS TWO NODES TEST: ", test_fn_ptr);
+    println!("========================");
+
+    // Setup
+    let mut alex = setup_uds_node("alex", ALEX_AGENT_ID.clone());
+    let mut billy = setup_uds_node("billy", BILLY_AGENT_ID.clone());
+    if can_setup {
+        setup_two_nodes(&mut alex, &mut billy, &TWO_WSS_NODES_PROCESSING_OPTIONS);
+    }
+
+    // Execute test
+    test_fn(&mut alex, &mut billy, &TWO_WSS_NODES_PROCESSING_OPTIONS);
+
+    // Wrap-up test
+    println!("========================");
+    print_test_name("UDS TWO NODES TEST END: ", test_fn_ptr);
+
+    // Done
+    Ok(())
+}
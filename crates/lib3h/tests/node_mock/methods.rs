@@ -794,6 +794,18 @@ impl NodeMock {
                     );
                 }
             }
+            Lib3hServerProtocol::QueryPeerLatencyResult(_msg) => {
+                // no-op
+            }
+            Lib3hServerProtocol::PeerReconnected(_msg) => {
+                // no-op
+            }
+            Lib3hServerProtocol::PeerLost(_msg) => {
+                // no-op
+            }
+            Lib3hServerProtocol::TransferProgress(_msg) => {
+                // no-op
+            }
             Lib3hServerProtocol::HandleDropEntry(_msg) => {
                 // no-op
             }
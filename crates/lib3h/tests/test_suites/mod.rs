@@ -1,5 +1,6 @@
 pub mod mirror;
 pub mod three_basic;
+pub mod three_relay;
 pub mod two_basic;
 pub mod two_connection;
 pub mod two_get_lists;
@@ -0,0 +1,106 @@
+use crate::{
+    node_mock::{test_join_space, NodeMock},
+    test_suites::three_basic::ThreeNodesTestFn,
+    utils::{constants::*, processor_harness::ProcessingOptions},
+};
+use lib3h_protocol::protocol_server::Lib3hServerProtocol;
+
+lazy_static! {
+    pub static ref THREE_NODES_RELAY_TEST_FNS: Vec<(ThreeNodesTestFn, bool)> = vec![
+        (test_advertise_relay, true),
+        (test_send_message_through_relay, true),
+    ];
+}
+
+//--------------------------------------------------------------------------------------------------
+// Test setup
+//--------------------------------------------------------------------------------------------------
+
+/// Alex cannot be connected to and relays through Billy
+pub fn setup_three_relay_nodes(
+    alex: &mut NodeMock,
+    billy: &mut NodeMock,
+    camille: &mut NodeMock,
+    options: &ProcessingOptions,
+) {
+    // Connection
+    // ==========
+    // Connect Alex to its relay
+    let connect_data = alex.connect_to(&billy.advertise()).unwrap();
+    wait_connect!(alex, connect_data, billy);
+
+    billy.wait_until_no_work();
+    alex.wait_until_no_work();
+    billy.wait_until_no_work();
+
+    // Connect Camille to Billy
+    let connect_data = camille.connect_to(&billy.advertise()).unwrap();
+    wait_connect!(camille, connect_data, billy);
+
+    // Space joining
+    // =============
+    test_join_space(alex, &SPACE_ADDRESS_A, options);
+    test_join_space(billy, &SPACE_ADDRESS_A, options);
+    test_join_space(camille, &SPACE_ADDRESS_A, options);
+
+    // Extra processing required for relay negotiation and peer gossip
+    wait_engine_wrapper_until_no_work!(alex);
+    wait_engine_wrapper_until_no_work!(billy);
+    wait_engine_wrapper_until_no_work!(camille);
+    wait_engine_wrapper_until_no_work!(alex);
+    wait_engine_wrapper_until_no_work!(billy);
+    wait_engine_wrapper_until_no_work!(camille);
+
+    debug!(
+        "DONE setup_three_relay_nodes() DONE \n\n ============================================ \n"
+    );
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+/// Alex advertises its relay's location
+fn test_advertise_relay(
+    alex: &mut NodeMock,
+    billy: &mut NodeMock,
+    _camille: &mut NodeMock,
+    _options: &ProcessingOptions,
+) {
+    let advertise = alex.advertise();
+    assert!(advertise.get_relay_target().is_some());
+    assert_eq!(billy.advertise(), advertise.relay_location());
+}
+
+/// Camille reaches Alex through Billy, and Alex answers the same way
+fn test_send_message_through_relay(
+    alex: &mut NodeMock,
+    billy: &mut NodeMock,
+    camille: &mut NodeMock,
+    options: &ProcessingOptions,
+) {
+    // C sends DM to A
+    // ===============
+    debug!("\nCamille sends DM to Alex...\n");
+    let _req_id = camille.send_direct_message(&ALEX_AGENT_ID, "marco".as_bytes().to_vec());
+    camille.wait_until_no_work();
+    let expected = "HandleSendDirectMessage\\(DirectMessageData \\{ space_address: SpaceHash\\(HashString\\(\"\\w+\"\\)\\), request_id: \"[\\w\\d_~]+\", to_agent_id: AgentPubKey\\(HashString\\(\"alex\"\\)\\), from_agent_id: AgentPubKey\\(HashString\\(\"camille\"\\)\\), content: \"marco\" \\}\\)";
+    let results = assert2_msg_matches!(alex, billy, expected, options);
+    let handle_send_direct_msg = results.first().unwrap();
+    let event = handle_send_direct_msg.events.first().unwrap();
+    let msg = unwrap_to!(event => Lib3hServerProtocol::HandleSendDirectMessage);
+    let content = std::str::from_utf8(msg.content.as_slice()).unwrap();
+    debug!("HandleSendDirectMessage: {}", content);
+
+    // Send response
+    debug!("\n Alex responds to Camille...\n");
+    let response_content = format!("echo: {}", content).as_bytes().to_vec();
+    alex.send_response(
+        &msg.request_id,
+        &camille.agent_id(),
+        response_content.clone(),
+    );
+    alex.wait_until_no_work();
+    let expected = "SendDirectMessageResult\\(DirectMessageData \\{ space_address: SpaceHash\\(HashString\\(\"\\w+\"\\)\\), request_id: \"[\\w\\d_~]+\", to_agent_id: AgentPubKey\\(HashString\\(\"camille\"\\)\\), from_agent_id: AgentPubKey\\(HashString\\(\"alex\"\\)\\), content: \"echo: marco\" \\}\\)";
+    assert2_msg_matches!(camille, billy, expected, options);
+}
@@ -220,6 +220,115 @@ pub struct UnboundData {
     pub uri: Lib3hUri,
 }
 
+/// A node we lost the connection to and tried to reconnect to
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PeerConnectionData {
+    pub peer_name: Lib3hUri,
+    /// Transport level uri we are connected to, or were last connected to
+    pub peer_location: Lib3hUri,
+    /// Number of reconnection attempts made
+    pub attempts: u32,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Network health
+//--------------------------------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct QueryPeerLatencyData {
+    /// Identifier of this request
+    pub request_id: String,
+}
+
+/// What we measured of a connected node through ping / pong
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PeerLatencyData {
+    /// Node we are pinging, undefined until it told us its PeerName
+    pub peer_name: Lib3hUri,
+    /// Transport level uri we are pinging
+    pub peer_location: Lib3hUri,
+    /// Round trip time of the last pong received
+    pub rtt_ms: Option<u64>,
+    /// Round trip time averaged over recent pongs
+    pub smoothed_rtt_ms: Option<u64>,
+    /// Estimated offset of the node's clock from ours, positive if it is ahead
+    pub clock_skew_ms: Option<i64>,
+    /// Number of pings in a row that got no pong before the next one was sent
+    pub missed_pongs: u32,
+    /// True once too many pongs were missed, until the node answers again
+    pub timed_out: bool,
+    /// What payload compression saved us with the node,
+    /// none until a transit session is established with it
+    pub compression: Option<CompressionStatsData>,
+}
+
+/// Payload compression on the connection to a node
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CompressionStatsData {
+    /// Compression negotiated with the node, "None" if we do not compress
+    pub compression: String,
+    /// Bytes of the payloads sent to and received from the node
+    pub uncompressed_bytes: u64,
+    /// Bytes of the same payloads as they went over the wire
+    pub compressed_bytes: u64,
+    /// How many of the payloads were worth compressing
+    pub compressed_messages: u64,
+}
+
+impl CompressionStatsData {
+    /// Bytes compression kept off the wire, less the framing it added
+    pub fn bytes_saved(&self) -> i64 {
+        self.uncompressed_bytes as i64 - self.compressed_bytes as i64
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PeerLatencyListData {
+    /// Identifier of the `QueryPeerLatency` request we are responding to
+    pub request_id: String,
+    pub peer_latency_list: Vec<PeerLatencyData>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Chunked transfers
+//--------------------------------------------------------------------------------------------------
+
+/// Where a large payload sent to or received from a node in chunks stands
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TransferProgressData {
+    /// Transport level uri of the node on the other end
+    pub peer_location: Lib3hUri,
+    /// Chosen by the sending node, unique among its transfers to the receiving one
+    pub transfer_id: u32,
+    /// True if we are the one sending the payload
+    pub outgoing: bool,
+    /// Bytes of the payload sent or received so far
+    pub bytes_done: u64,
+    /// Size of the whole payload
+    pub total_bytes: u64,
+    /// True if either node canceled the transfer, it will not progress further
+    pub canceled: bool,
+}
+
+impl TransferProgressData {
+    /// True once the whole payload was sent or received
+    pub fn is_complete(&self) -> bool {
+        !self.canceled && self.bytes_done == self.total_bytes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CancelTransferData {
+    /// Identifier of this request
+    pub request_id: String,
+    /// Transport level uri of the node on the other end
+    pub peer_location: Lib3hUri,
+    /// As reported by `TransferProgress`
+    pub transfer_id: u32,
+    /// True if we are the one sending the payload
+    pub outgoing: bool,
+}
+
 //--------------------------------------------------------------------------------------------------
 // Space tracking
 //--------------------------------------------------------------------------------------------------
@@ -23,6 +23,10 @@ pub enum ClientToLib3h {
     // -- Connection -- //
     /// create an explicit connection to a remote peer
     Bootstrap(BootstrapData),
+    /// Request latency measurements of every node we are connected to
+    QueryPeerLatency(QueryPeerLatencyData),
+    /// Abandon sending or receiving a large payload in chunks
+    CancelTransfer(CancelTransferData),
 
     // -- Space -- //
     /// Order the engine to be part of the network of the specified space.
@@ -47,6 +51,10 @@ pub enum ClientToLib3h {
 pub enum ClientToLib3hResponse {
     /// we were able to bootstrap to the remote
     BootstrapSuccess,
+    /// Response to a `QueryPeerLatency` request
+    QueryPeerLatencyResult(PeerLatencyListData),
+    /// we abandoned the transfer and told the other node
+    CancelTransferResult,
 
     /// the response received from a previous `SendDirectMessage`
     SendDirectMessageResult(DirectMessageData),
@@ -67,6 +75,12 @@ pub enum Lib3hToClient {
     Connected(ConnectedData),
     /// Notification of disconnection from a network
     Unbound(UnboundData),
+    /// Notification that we are connected again to a node we lost
+    PeerReconnected(PeerConnectionData),
+    /// Notification that we gave up reconnecting to a node we lost
+    PeerLost(PeerConnectionData),
+    /// Notification that a large payload sent or received in chunks progressed
+    TransferProgress(TransferProgressData),
 
     // -- Direct Messaging -- //
     /// the response received from a previous `SendDirectMessage`
@@ -115,6 +129,12 @@ impl TryFrom<Lib3hClientProtocol> for ClientToLib3h {
                     bootstrap_uri: connect_data.peer_location,
                 }))
             }
+            Lib3hClientProtocol::QueryPeerLatency(query_data) => {
+                Ok(ClientToLib3h::QueryPeerLatency(query_data))
+            }
+            Lib3hClientProtocol::CancelTransfer(cancel_data) => {
+                Ok(ClientToLib3h::CancelTransfer(cancel_data))
+            }
             Lib3hClientProtocol::JoinSpace(space_data) => Ok(ClientToLib3h::JoinSpace(space_data)),
             Lib3hClientProtocol::LeaveSpace(space_data) => {
                 Ok(ClientToLib3h::LeaveSpace(space_data))
@@ -178,6 +198,13 @@ impl TryFrom<Lib3hServerProtocol> for Lib3hToClient {
                     uri: Lib3hUri::with_undefined(),
                 }))
             }
+            Lib3hServerProtocol::PeerReconnected(peer_data) => {
+                Ok(Lib3hToClient::PeerReconnected(peer_data))
+            }
+            Lib3hServerProtocol::PeerLost(peer_data) => Ok(Lib3hToClient::PeerLost(peer_data)),
+            Lib3hServerProtocol::TransferProgress(progress_data) => {
+                Ok(Lib3hToClient::TransferProgress(progress_data))
+            }
             Lib3hServerProtocol::SendDirectMessageResult(direct_message_data) => {
                 Ok(Lib3hToClient::SendDirectMessageResult(direct_message_data))
             }
@@ -226,6 +253,9 @@ impl From<Lib3hServerProtocol> for ClientToLib3hResponse {
             Lib3hServerProtocol::Connected(_connected_data) => {
                 ClientToLib3hResponse::BootstrapSuccess
             }
+            Lib3hServerProtocol::QueryPeerLatencyResult(latency_list_data) => {
+                ClientToLib3hResponse::QueryPeerLatencyResult(latency_list_data)
+            }
             variant => panic!("{:?} can't convert to ClientToLib3hResponse", variant),
         }
     }
@@ -239,6 +269,12 @@ impl From<ClientToLib3h> for Lib3hClientProtocol {
                 peer_location: bootstrap_data.bootstrap_uri,
                 network_id: bootstrap_data.network_or_space_address.into(),
             }),
+            ClientToLib3h::QueryPeerLatency(query_data) => {
+                Lib3hClientProtocol::QueryPeerLatency(query_data)
+            }
+            ClientToLib3h::CancelTransfer(cancel_data) => {
+                Lib3hClientProtocol::CancelTransfer(cancel_data)
+            }
             ClientToLib3h::JoinSpace(space_data) => Lib3hClientProtocol::JoinSpace(space_data),
             ClientToLib3h::LeaveSpace(space_data) => Lib3hClientProtocol::LeaveSpace(space_data),
             ClientToLib3h::SendDirectMessage(direct_message_data) => {
@@ -291,6 +327,13 @@ impl From<Lib3hToClient> for Lib3hServerProtocol {
                     network_id: NetworkHash::default(),
                 })
             }
+            Lib3hToClient::PeerReconnected(peer_data) => {
+                Lib3hServerProtocol::PeerReconnected(peer_data)
+            }
+            Lib3hToClient::PeerLost(peer_data) => Lib3hServerProtocol::PeerLost(peer_data),
+            Lib3hToClient::TransferProgress(progress_data) => {
+                Lib3hServerProtocol::TransferProgress(progress_data)
+            }
             Lib3hToClient::SendDirectMessageResult(direct_message_data) => {
                 Lib3hServerProtocol::SendDirectMessageResult(direct_message_data)
             }
@@ -338,6 +381,9 @@ impl From<ClientToLib3hResponse> for Lib3hServerProtocol {
                     uri: Lib3hUri::with_undefined(),
                 })
             }
+            ClientToLib3hResponse::QueryPeerLatencyResult(latency_list_data) => {
+                Lib3hServerProtocol::QueryPeerLatencyResult(latency_list_data)
+            }
             variant => panic!("{:?} can't convert to Lib3hServerProtocol", variant),
         }
     }
@@ -373,4 +419,52 @@ mod tests {
         let to_s: Lib3hClientProtocol = to_c.into();
         assert_eq!(to_s, s);
     }
+
+    #[test]
+    fn test_translate_query_peer_latency() {
+        let s = Lib3hClientProtocol::QueryPeerLatency(QueryPeerLatencyData {
+            request_id: "rid".to_string(),
+        });
+        let to_c: ClientToLib3h = s.clone().try_into().expect("A ClientToLib3h protocol");
+        let to_s: Lib3hClientProtocol = to_c.into();
+        assert_eq!(to_s, s);
+    }
+
+    #[test]
+    fn test_translate_peer_lost() {
+        let s = Lib3hServerProtocol::PeerLost(PeerConnectionData {
+            peer_name: Url::parse("hc:id").unwrap().into(),
+            peer_location: Url::parse("wss://192.168.0.102:58081/").unwrap().into(),
+            attempts: 10,
+        });
+        let to_c: Lib3hToClient = s.clone().try_into().expect("A Lib3hToClient protocol");
+        let to_s: Lib3hServerProtocol = to_c.into();
+        assert_eq!(to_s, s);
+    }
+
+    #[test]
+    fn test_translate_transfers() {
+        let peer_location: Lib3hUri = Url::parse("wss://192.168.0.102:58081/").unwrap().into();
+        let s = Lib3hClientProtocol::CancelTransfer(CancelTransferData {
+            request_id: "rid".to_string(),
+            peer_location: peer_location.clone(),
+            transfer_id: 3,
+            outgoing: true,
+        });
+        let to_c: ClientToLib3h = s.clone().try_into().expect("A ClientToLib3h protocol");
+        let to_s: Lib3hClientProtocol = to_c.into();
+        assert_eq!(to_s, s);
+
+        let s = Lib3hServerProtocol::TransferProgress(TransferProgressData {
+            peer_location,
+            transfer_id: 3,
+            outgoing: false,
+            bytes_done: 1024,
+            total_bytes: 4096,
+            canceled: false,
+        });
+        let to_c: Lib3hToClient = s.clone().try_into().expect("A Lib3hToClient protocol");
+        let to_s: Lib3hServerProtocol = to_c.into();
+        assert_eq!(to_s, s);
+    }
 }
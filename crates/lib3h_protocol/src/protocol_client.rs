@@ -19,6 +19,12 @@ pub enum Lib3hClientProtocol {
     /// Connect to the specified multiaddr
     Connect(ConnectData),
 
+    // -- Network health -- //
+    /// Request latency measurements of every node we are connected to
+    QueryPeerLatency(QueryPeerLatencyData),
+    /// Abandon sending or receiving a large payload in chunks
+    CancelTransfer(CancelTransferData),
+
     // -- Space -- //
     /// Order the p2p module to be part of the network of the specified space.
     JoinSpace(SpaceData),
@@ -23,6 +23,16 @@ pub enum Lib3hServerProtocol {
     Connected(ConnectedData),
     /// Notification of disconnection from a network
     Disconnected(DisconnectedData),
+    /// Notification that we are connected again to a node we lost
+    PeerReconnected(PeerConnectionData),
+    /// Notification that we gave up reconnecting to a node we lost
+    PeerLost(PeerConnectionData),
+    /// Notification that a large payload sent or received in chunks progressed
+    TransferProgress(TransferProgressData),
+
+    // -- Network health -- //
+    /// Response to a `QueryPeerLatency` request
+    QueryPeerLatencyResult(PeerLatencyListData),
 
     // -- Direct Messaging -- //
     /// the response received from a previous `SendDirectMessage`
@@ -9,13 +9,16 @@ use url::Url;
 static AGENT_SCHEME: &'static str = "agentpubkey";
 static NODE_SCHEME: &'static str = "nodepubkey";
 static MEMORY_SCHEME: &'static str = "mem";
+static UDS_SCHEME: &'static str = "uds";
 static UNDEFINED_SCHEME: &'static str = "none";
+static RELAY_FRAGMENT_PREFIX: &'static str = "relay=";
 
 pub enum UriScheme {
     Undefined,
     Agent,
     Node,
     Memory,
+    Uds,
     Other(String),
 }
 
@@ -26,6 +29,7 @@ impl From<UriScheme> for &str {
             UriScheme::Agent => AGENT_SCHEME,
             UriScheme::Node => NODE_SCHEME,
             UriScheme::Memory => MEMORY_SCHEME,
+            UriScheme::Uds => UDS_SCHEME,
             UriScheme::Other(_) => "",
         }
     }
@@ -38,6 +42,7 @@ impl From<UriScheme> for String {
             UriScheme::Agent => AGENT_SCHEME.into(),
             UriScheme::Node => NODE_SCHEME.into(),
             UriScheme::Memory => MEMORY_SCHEME.into(),
+            UriScheme::Uds => UDS_SCHEME.into(),
             UriScheme::Other(s) => s,
         }
     }
@@ -47,7 +52,9 @@ impl From<UriScheme> for String {
 // Lib3hUri
 //--------------------------------------------------------------------------------------------------
 
-#[derive(Shrinkwrap, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[derive(
+    Shrinkwrap, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
 #[shrinkwrap(mutable)]
 pub struct Lib3hUri(pub Url);
 
@@ -74,6 +81,12 @@ impl Lib3hUri {
         let url = Self::parse(&format!("{}://{}", MEMORY_SCHEME, other));
         Lib3hUri(url)
     }
+    /// unix domain socket at the absolute `path`, i.e. uds:///path
+    pub fn with_uds(path: &std::path::Path) -> Self {
+        assert!(path.is_absolute(), "{:?}", path);
+        let url = Self::parse(&format!("{}://{}", UDS_SCHEME, path.display()));
+        Lib3hUri(url)
+    }
 
     // -- Misc -- //
 
@@ -148,6 +161,31 @@ impl Lib3hUri {
         None
     }
 
+    /// Address of a node that is only reachable through the relay at
+    /// `relay_location`, i.e. relay_location#relay=target
+    pub fn with_relay(relay_location: &Lib3hUri, target: &str) -> Self {
+        let mut url = relay_location.0.clone();
+        url.set_fragment(Some(&format!("{}{}", RELAY_FRAGMENT_PREFIX, target)));
+        Lib3hUri(url)
+    }
+
+    /// the node a relay address points to, if this is one
+    pub fn get_relay_target(&self) -> Option<String> {
+        let fragment = self.0.fragment()?;
+        if fragment.starts_with(RELAY_FRAGMENT_PREFIX) {
+            Some(fragment[RELAY_FRAGMENT_PREFIX.len()..].to_string())
+        } else {
+            None
+        }
+    }
+
+    /// the location of the relay a relay address goes through
+    pub fn relay_location(&self) -> Self {
+        let mut url = self.0.clone();
+        url.set_fragment(None);
+        Lib3hUri(url)
+    }
+
     pub fn node_id(&self) -> NodePubKey {
         assert!(self.is_scheme(UriScheme::Node), "{:?}", self);
         self.0.path().into()
@@ -251,6 +289,8 @@ mod tests {
         assert_eq!(s, NODE_SCHEME);
         let s: &str = UriScheme::Memory.into();
         assert_eq!(s, MEMORY_SCHEME);
+        let s: &str = UriScheme::Uds.into();
+        assert_eq!(s, UDS_SCHEME);
         let s: &str = UriScheme::Undefined.into();
         assert_eq!(s, UNDEFINED_SCHEME);
     }
@@ -262,6 +302,8 @@ mod tests {
         assert_eq!(s, NODE_SCHEME.to_string());
         let s: String = UriScheme::Memory.into();
         assert_eq!(s, MEMORY_SCHEME.to_string());
+        let s: String = UriScheme::Uds.into();
+        assert_eq!(s, UDS_SCHEME.to_string());
         let s: String = UriScheme::Undefined.into();
         assert_eq!(s, UNDEFINED_SCHEME.to_string());
         let s: String = UriScheme::Other("http".to_string()).into();
@@ -323,6 +365,30 @@ mod tests {
         assert_eq!(None, uri.get_agent_id());
     }
 
+    #[test]
+    fn test_uri_uds() {
+        let uri = Lib3hUri::with_uds(std::path::Path::new("/tmp/lib3h/node.sock"));
+        assert_eq!("uds:///tmp/lib3h/node.sock", uri.to_string());
+        assert!(uri.is_scheme(UriScheme::Uds));
+        assert!(uri.is_transport());
+        assert_eq!("/tmp/lib3h/node.sock", uri.path());
+    }
+
+    #[test]
+    fn test_uri_relay() {
+        let relay = Lib3hUri::with_memory("addr_1");
+        assert_eq!(None, relay.get_relay_target());
+        let mut uri = Lib3hUri::with_relay(&relay, "HcMfake_node_id");
+        assert_eq!("mem://addr_1/#relay=HcMfake_node_id", uri.to_string());
+        assert_eq!(Some("HcMfake_node_id".to_string()), uri.get_relay_target());
+        assert_eq!(relay, uri.relay_location());
+        // the relay target is not a higher-level id
+        uri.clear_agent_id();
+        assert_eq!(Some("HcMfake_node_id".to_string()), uri.get_relay_target());
+        let uri = Lib3hUri::with_relay(&relay, "mem://addr_2/");
+        assert_eq!(Some("mem://addr_2/".to_string()), uri.get_relay_target());
+    }
+
     #[test]
     fn test_uri_builder() {
         let scheme = "wss";
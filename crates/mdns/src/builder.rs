@@ -3,8 +3,16 @@
 use crate::{
     error::MulticastDnsError,
     record::{HashMapRecord, MapRecord, Record},
-    Instant, MulticastDns, DEFAULT_BIND_ADRESS, DEFAULT_QUERY_INTERVAL_MS, DEFAULT_TTL,
-    MDNS_MULCAST_IPV4_ADRESS, READ_BUF_SIZE, SERVICE_LISTENER_PORT,
+    signature::{check_signing_key, sign_record},
+    Instant, IpStack, MulticastChannel, MulticastDns, ProbeState, WireFormat, DEFAULT_BIND_ADRESS,
+    DEFAULT_BIND_ADRESS_V6, DEFAULT_QUERY_INTERVAL_MS, DEFAULT_TTL, MDNS_MULCAST_IPV4_ADRESS,
+    MDNS_MULCAST_IPV6_ADRESS, READ_BUF_SIZE, SERVICE_LISTENER_PORT,
+};
+use lib3h_crypto_api::{Buffer, CryptoSystem};
+use net2::UdpSocketExt;
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6},
 };
 
 #[cfg(not(target_os = "windows"))]
@@ -22,9 +30,29 @@ pub struct MulticastDnsBuilder {
     pub(crate) multicast_ttl: u32,
     /// Multicast address used by the mDNS protocol: `224.0.0.251`
     pub(crate) multicast_address: String,
+    /// Which IP versions we multicast on: IPv4 only by default
+    pub(crate) ip_stack: IpStack,
+    /// Our IPv6 address bound to UDP Socket, default to `::`
+    pub(crate) bind_address_v6: String,
+    /// IPv6 multicast address used by the mDNS protocol: `ff02::fb`
+    pub(crate) multicast_address_v6: String,
+    /// Address of the IPv4 interface to multicast on, the bound one by default
+    pub(crate) interface_v4: Option<String>,
+    /// Index of the IPv6 interface to multicast on, `0` letting the system choose
+    pub(crate) interface_v6: u32,
     /// The amount of time we should wait between two queries.
     pub(crate) query_interval_ms: u128,
     pub(crate) own_map_record: MapRecord,
+    /// How DNS messages are laid out: DNS-SD records by default
+    pub(crate) wire_format: WireFormat,
+    /// Our node id, advertised in the TXT record of our DNS-SD service instances
+    pub(crate) node_id: Option<String>,
+    /// The crypto system signing our records and verifying the ones of others
+    pub(crate) crypto: Option<Box<dyn CryptoSystem>>,
+    /// The secret signing key of our node, if we sign our records
+    pub(crate) signing_key: Option<Box<dyn Buffer>>,
+    /// If true, answers not signed by the node they name are discarded
+    pub(crate) verify_signatures: bool,
 }
 
 impl MulticastDnsBuilder {
@@ -71,12 +99,78 @@ impl MulticastDnsBuilder {
         self
     }
 
+    /// Sets which IP versions we multicast on.
+    pub fn ip_stack(&mut self, ip_stack: IpStack) -> &mut Self {
+        self.ip_stack = ip_stack;
+        self
+    }
+
+    /// specify the IPv6 address to bind to
+    pub fn bind_address_v6(&mut self, addr: &str) -> &mut Self {
+        self.bind_address_v6 = addr.to_owned();
+        self
+    }
+
+    /// set the IPv6 multicast address
+    pub fn multicast_address_v6(&mut self, addr: &str) -> &mut Self {
+        self.multicast_address_v6 = addr.to_string();
+        self
+    }
+
+    /// Sets the address of the IPv4 interface to join the multicast group and send from.
+    pub fn interface_v4(&mut self, addr: &str) -> &mut Self {
+        self.interface_v4 = Some(addr.to_owned());
+        self
+    }
+
+    /// Sets the index of the IPv6 interface to join the multicast group and send from, as
+    /// listed by `ip link`. As `ff02::fb` is link-local, hosts with several interfaces should set
+    /// it rather than rely on the system's choice.
+    pub fn interface_v6(&mut self, index: u32) -> &mut Self {
+        self.interface_v6 = index;
+        self
+    }
+
     /// Sets the amount of time between two queries originating from ourself.
     pub fn query_interval_ms(&mut self, every_ms: u128) -> &mut Self {
         self.query_interval_ms = every_ms;
         self
     }
 
+    /// Sets how DNS messages are laid out. [`WireFormat::LegacyCname`] keeps talking to nodes
+    /// that predate DNS-SD, but other mDNS tools cannot see it.
+    pub fn wire_format(&mut self, wire_format: WireFormat) -> &mut Self {
+        self.wire_format = wire_format;
+        self
+    }
+
+    /// Sets the node id advertised along our URLs.
+    pub fn node_id(&mut self, node_id: &str) -> &mut Self {
+        self.node_id = Some(node_id.to_owned());
+        self
+    }
+
+    /// Signs our records with the secret signing key of our node, whose id is the hcid encoding
+    /// of its public key, as set with [`node_id`](Self::node_id). [`build`](Self::build) fails
+    /// if the key is not the one of the node id.
+    pub fn sign_records(
+        &mut self,
+        crypto: Box<dyn CryptoSystem>,
+        secret_key: Box<dyn Buffer>,
+    ) -> &mut Self {
+        self.crypto = Some(crypto);
+        self.signing_key = Some(secret_key);
+        self
+    }
+
+    /// Discards the answers that are not signed by the node they name, so no one on the LAN can
+    /// point us to their URL for our network id.
+    pub fn verify_signatures(&mut self, crypto: Box<dyn CryptoSystem>) -> &mut Self {
+        self.crypto = Some(crypto);
+        self.verify_signatures = true;
+        self
+    }
+
     /// Set the host's record.
     pub fn own_record(&mut self, networkid: &str, urls: &[&str]) -> &mut Self {
         let records: Vec<Record> = urls
@@ -89,17 +183,39 @@ impl MulticastDnsBuilder {
 
     /// construct the actual mdns struct
     pub fn build(&mut self) -> Result<MulticastDns, MulticastDnsError> {
-        let recv_socket = create_socket(&self.bind_address, self.bind_port)?;
-        recv_socket.set_nonblocking(true)?;
-        recv_socket.set_multicast_loop_v4(self.multicast_loop)?;
-        recv_socket.set_multicast_ttl_v4(self.multicast_ttl)?;
-        recv_socket.join_multicast_v4(
-            &self.multicast_address.parse()?,
-            &self.bind_address.parse()?,
-        )?;
+        let mut channels = Vec::with_capacity(2);
+        if self.ip_stack.has_ipv4() {
+            channels.push(self.build_channel_v4()?);
+        }
+        if self.ip_stack.has_ipv6() {
+            channels.push(self.build_channel_v6()?);
+        }
 
-        let send_socket = create_socket(&self.bind_address, self.bind_port)?;
-        send_socket.set_nonblocking(true)?;
+        // Signatures are carried by DNS-SD records only
+        if (self.signing_key.is_some() || self.verify_signatures)
+            && self.wire_format == WireFormat::LegacyCname
+        {
+            return Err(MulticastDnsError::new_other(
+                "Signed records require the DNS-SD wire format.",
+            ));
+        }
+
+        if let (Some(crypto), Some(signing_key)) = (&self.crypto, &self.signing_key) {
+            let node_id = self.node_id.as_ref().ok_or_else(|| {
+                MulticastDnsError::new_other("Signing records requires a node id.")
+            })?;
+            check_signing_key(&**crypto, signing_key, node_id)?;
+        }
+
+        let mut own_map_record = self.own_map_record.clone();
+        for (_netid, records) in own_map_record.iter_mut() {
+            for record in records.iter_mut() {
+                record.node_id = self.node_id.clone();
+                if let (Some(crypto), Some(signing_key)) = (&self.crypto, &self.signing_key) {
+                    sign_record(&**crypto, signing_key, record)?;
+                }
+            }
+        }
 
         Ok(MulticastDns {
             bind_address: self.bind_address.to_owned(),
@@ -107,13 +223,77 @@ impl MulticastDnsBuilder {
             multicast_loop: self.multicast_loop,
             multicast_ttl: self.multicast_ttl,
             multicast_address: self.multicast_address.to_owned(),
+            multicast_address_v6: self.multicast_address_v6.to_owned(),
+            ip_stack: self.ip_stack,
             timestamp: Instant::now(),
             query_interval_ms: self.query_interval_ms,
-            send_socket,
-            recv_socket,
+            channels,
             buffer: [0; READ_BUF_SIZE],
-            own_map_record: self.own_map_record.clone(),
+            own_map_record,
             map_record: MapRecord(HashMapRecord::with_capacity(32)),
+            wire_format: self.wire_format,
+            node_id: self.node_id.clone(),
+            probe_state: ProbeState::Idle,
+            crypto: self.crypto.as_ref().map(|crypto| crypto.box_clone()),
+            signing_key: self.signing_key.as_ref().map(|key| key.box_clone()),
+            verify_signatures: self.verify_signatures,
+            verified_seqs: HashMap::new(),
+        })
+    }
+
+    /// Joins the IPv4 multicast group.
+    fn build_channel_v4(&self) -> Result<MulticastChannel, MulticastDnsError> {
+        let multicast_address: Ipv4Addr = self.multicast_address.parse()?;
+        let interface: Ipv4Addr = self
+            .interface_v4
+            .as_ref()
+            .unwrap_or(&self.bind_address)
+            .parse()?;
+
+        let recv_socket = create_socket(&self.bind_address, self.bind_port)?;
+        recv_socket.set_nonblocking(true)?;
+        recv_socket.set_multicast_loop_v4(self.multicast_loop)?;
+        recv_socket.set_multicast_ttl_v4(self.multicast_ttl)?;
+        recv_socket.join_multicast_v4(&multicast_address, &interface)?;
+
+        let send_socket = create_socket(&self.bind_address, self.bind_port)?;
+        send_socket.set_nonblocking(true)?;
+        if self.interface_v4.is_some() {
+            send_socket.set_multicast_if_v4(&interface)?;
+        }
+
+        Ok(MulticastChannel {
+            group: SocketAddr::new(multicast_address.into(), self.bind_port),
+            send_socket,
+            recv_socket,
+        })
+    }
+
+    /// Joins the IPv6 multicast group, on its own sockets so IPv4 keeps working on systems
+    /// without dual-stack sockets.
+    fn build_channel_v6(&self) -> Result<MulticastChannel, MulticastDnsError> {
+        let multicast_address: Ipv6Addr = self.multicast_address_v6.parse()?;
+
+        let recv_socket = create_socket_v6(&self.bind_address_v6, self.bind_port)?;
+        recv_socket.set_nonblocking(true)?;
+        recv_socket.join_multicast_v6(&multicast_address, self.interface_v6)?;
+
+        let send_socket = create_socket_v6(&self.bind_address_v6, self.bind_port)?;
+        send_socket.set_nonblocking(true)?;
+        send_socket.set_multicast_loop_v6(self.multicast_loop)?;
+        send_socket.set_multicast_hops_v6(self.multicast_ttl)?;
+        send_socket.set_multicast_if_v6(self.interface_v6)?;
+
+        Ok(MulticastChannel {
+            // Link-local groups need the interface as scope
+            group: SocketAddr::V6(SocketAddrV6::new(
+                multicast_address,
+                self.bind_port,
+                0,
+                self.interface_v6,
+            )),
+            send_socket,
+            recv_socket,
         })
     }
 }
@@ -127,8 +307,18 @@ impl Default for MulticastDnsBuilder {
             multicast_loop: true,
             multicast_ttl: DEFAULT_TTL,
             multicast_address: String::from(MDNS_MULCAST_IPV4_ADRESS),
+            ip_stack: IpStack::V4,
+            bind_address_v6: String::from(DEFAULT_BIND_ADRESS_V6),
+            multicast_address_v6: String::from(MDNS_MULCAST_IPV6_ADRESS),
+            interface_v4: None,
+            interface_v6: 0,
             query_interval_ms: DEFAULT_QUERY_INTERVAL_MS,
             own_map_record: MapRecord::new(),
+            wire_format: WireFormat::DnsSd,
+            node_id: None,
+            crypto: None,
+            signing_key: None,
+            verify_signatures: false,
         }
     }
 }
@@ -149,3 +339,22 @@ fn create_socket(addr: &str, port: u16) -> Result<std::net::UdpSocket, Multicast
         .reuse_address(true)?
         .bind((addr, port))?)
 }
+
+/// non-windows IPv6 udp socket bind, leaving IPv4 to its own socket.
+#[cfg(not(target_os = "windows"))]
+fn create_socket_v6(addr: &str, port: u16) -> Result<std::net::UdpSocket, MulticastDnsError> {
+    Ok(net2::UdpBuilder::new_v6()?
+        .only_v6(true)?
+        .reuse_address(true)?
+        .reuse_port(true)?
+        .bind((addr, port))?)
+}
+
+/// windows IPv6 udp socket bind, leaving IPv4 to its own socket.
+#[cfg(target_os = "windows")]
+fn create_socket_v6(addr: &str, port: u16) -> Result<std::net::UdpSocket, MulticastDnsError> {
+    Ok(net2::UdpBuilder::new_v6()?
+        .only_v6(true)?
+        .reuse_address(true)?
+        .bind((addr, port))?)
+}
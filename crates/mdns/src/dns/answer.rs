@@ -1,9 +1,20 @@
 //! DNS Answer part.
 
-use crate::{error::MulticastDnsResult, DEFAULT_TTL};
+use crate::{
+    dns::{
+        name::{read_name, write_name},
+        CACHE_FLUSH_BIT, CLASS_IN, TYPE_A, TYPE_AAAA, TYPE_CNAME, TYPE_PTR, TYPE_SRV, TYPE_TXT,
+    },
+    error::{MulticastDnsError, MulticastDnsResult},
+    DEFAULT_TTL,
+};
 #[allow(unused_imports)]
 use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
-use std::{default::Default, io::Cursor};
+use std::{
+    default::Default,
+    io::{Cursor, Read},
+    net::{Ipv4Addr, Ipv6Addr},
+};
 
 /// Response answer
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -17,16 +28,17 @@ pub enum Answer {
 pub struct AnswerSection {
     /// The length of the "domain_name" string
     pub(crate) dn_len: u16,
-    /// Correspond to our networkId in our mDNS implementation
+    /// Correspond to our networkId with legacy CNAME records, or to the service type or
+    /// instance name with DNS-SD records
     pub(crate) domain_name: String,
-    /// Should be CNAME(5) from the standard
+    /// CNAME(5) for legacy records, PTR(12), SRV(33), TXT(16), A(1) or AAAA(28) for DNS-SD
     pub(crate) answer_type: u16,
     /// IN(1) for the Internet.
     pub(crate) answer_class: u16,
     /// "Time To Live" value of the node
     pub(crate) ttl: u32,
     pub(crate) data_len: u16,
-    pub(crate) data: Target,
+    pub(crate) data: RecordData,
 }
 
 impl Default for AnswerSection {
@@ -38,7 +50,7 @@ impl Default for AnswerSection {
             answer_class: 1,
             ttl: DEFAULT_TTL,
             data_len: 0,
-            data: Target::new(""),
+            data: RecordData::Target(Target::new("")),
         }
     }
 }
@@ -50,7 +62,7 @@ impl AnswerSection {
             dn_len: name.len() as u16,
             domain_name: name.to_owned(),
             data_len: target.target.len() as u16,
-            data: target.to_owned(),
+            data: RecordData::Target(target.to_owned()),
             ..Default::default()
         }
     }
@@ -62,7 +74,7 @@ impl AnswerSection {
             domain_name: name.to_owned(),
             ttl,
             data_len: target.target.len() as u16,
-            data: target.clone(),
+            data: RecordData::Target(target.clone()),
             ..Default::default()
         }
     }
@@ -78,7 +90,7 @@ impl AnswerSection {
         let answer_class = cursor.read_u16::<BigEndian>()?;
         let ttl = cursor.read_u32::<BigEndian>()?;
         let data_len = cursor.read_u16::<BigEndian>()?;
-        let data = Target::from_raw(&mut cursor)?;
+        let data = RecordData::Target(Target::from_raw(&mut cursor)?);
 
         Ok(Self {
             dn_len,
@@ -103,12 +115,215 @@ impl AnswerSection {
         packet.write_u32::<BigEndian>(self.ttl)?;
         packet.write_u16::<BigEndian>(self.data_len)?;
 
-        self.data.write(&mut packet)?;
+        match &self.data {
+            RecordData::Target(target) => target.write(&mut packet)?,
+            _ => {
+                return Err(MulticastDnsError::new_other(
+                    "Only CNAME answers can be written with the legacy encoding.",
+                ))
+            }
+        }
+
+        Ok(())
+    }
+
+    /// New DNS-SD record. Every type but PTR names a single owner, so it is flagged for the
+    /// receivers to flush what they cached for it.
+    pub fn new_record(name: &str, data: RecordData, ttl: u32) -> MulticastDnsResult<Self> {
+        let answer_type = data.record_type();
+        let answer_class = if answer_type == TYPE_PTR {
+            CLASS_IN
+        } else {
+            CLASS_IN | CACHE_FLUSH_BIT
+        };
+        Ok(Self {
+            dn_len: name.len() as u16,
+            domain_name: name.to_owned(),
+            answer_type,
+            answer_class,
+            ttl,
+            data_len: data.rdata()?.len() as u16,
+            data,
+        })
+    }
+
+    /// Returns true if this record is of the Internet class, whether the cache flush bit is set
+    /// or not.
+    pub fn is_internet_class(&self) -> bool {
+        self.answer_class & !CACHE_FLUSH_BIT == CLASS_IN
+    }
+
+    /// Returns the name the record is about.
+    pub fn domain_name(&self) -> &str {
+        &self.domain_name
+    }
+
+    /// Returns the data of the record.
+    pub fn data(&self) -> &RecordData {
+        &self.data
+    }
+
+    /// Builds an [`AnswerSection`] from a byte cursor over a standard DNS packet.
+    pub fn from_raw_dns_sd(cursor: &mut Cursor<&Vec<u8>>) -> MulticastDnsResult<Self> {
+        let domain_name = read_name(cursor)?;
+        let answer_type = cursor.read_u16::<BigEndian>()?;
+        let answer_class = cursor.read_u16::<BigEndian>()?;
+        let ttl = cursor.read_u32::<BigEndian>()?;
+        let data_len = cursor.read_u16::<BigEndian>()?;
+
+        let rdata_end = cursor.position() + u64::from(data_len);
+        if rdata_end > cursor.get_ref().len() as u64 {
+            return Err(MulticastDnsError::new_other(
+                "DNS record data goes past the end of the packet.",
+            ));
+        }
+        let data = RecordData::from_raw(answer_type, data_len, cursor)?;
+        cursor.set_position(rdata_end);
+
+        Ok(Self {
+            dn_len: domain_name.len() as u16,
+            domain_name,
+            answer_type,
+            answer_class,
+            ttl,
+            data_len,
+            data,
+        })
+    }
+
+    /// Writes this record to a standard DNS packet.
+    pub fn write_dns_sd(&self, packet: &mut Vec<u8>) -> MulticastDnsResult<()> {
+        let rdata = self.data.rdata()?;
+
+        write_name(packet, &self.domain_name)?;
+        packet.write_u16::<BigEndian>(self.answer_type)?;
+        packet.write_u16::<BigEndian>(self.answer_class)?;
+        packet.write_u32::<BigEndian>(self.ttl)?;
+        packet.write_u16::<BigEndian>(rdata.len() as u16)?;
+        packet.extend_from_slice(&rdata);
 
         Ok(())
     }
 }
 
+/// Data of a resource record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RecordData {
+    /// URL of a legacy CNAME record
+    Target(Target),
+    /// Instance name of a service
+    Ptr(String),
+    /// Where a service instance listens
+    Srv(ServiceLocation),
+    /// `key=value` attributes of a service instance
+    Txt(Vec<String>),
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    /// Data of a record type we do not handle, as received
+    Unknown(u16, Vec<u8>),
+}
+
+/// Data of an SRV record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServiceLocation {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    /// Host name of the service instance
+    pub target: String,
+}
+
+impl RecordData {
+    /// DNS type of the record holding this data.
+    pub fn record_type(&self) -> u16 {
+        match self {
+            RecordData::Target(_) => TYPE_CNAME,
+            RecordData::Ptr(_) => TYPE_PTR,
+            RecordData::Srv(_) => TYPE_SRV,
+            RecordData::Txt(_) => TYPE_TXT,
+            RecordData::A(_) => TYPE_A,
+            RecordData::Aaaa(_) => TYPE_AAAA,
+            RecordData::Unknown(record_type, _) => *record_type,
+        }
+    }
+
+    /// Reads `data_len` bytes of record data of type `record_type`.
+    pub fn from_raw(
+        record_type: u16,
+        data_len: u16,
+        cursor: &mut Cursor<&Vec<u8>>,
+    ) -> MulticastDnsResult<Self> {
+        Ok(match record_type {
+            TYPE_PTR => RecordData::Ptr(read_name(cursor)?),
+            TYPE_SRV => RecordData::Srv(ServiceLocation {
+                priority: cursor.read_u16::<BigEndian>()?,
+                weight: cursor.read_u16::<BigEndian>()?,
+                port: cursor.read_u16::<BigEndian>()?,
+                target: read_name(cursor)?,
+            }),
+            TYPE_TXT => {
+                let end = cursor.position() + u64::from(data_len);
+                let mut strings = Vec::new();
+                while cursor.position() < end {
+                    let len = cursor.read_u8()?;
+                    let mut string = Vec::with_capacity(len as usize);
+                    for _ in 0..len {
+                        string.push(cursor.read_u8()?);
+                    }
+                    strings.push(String::from(std::str::from_utf8(&string)?));
+                }
+                RecordData::Txt(strings)
+            }
+            TYPE_A if data_len == 4 => {
+                RecordData::A(Ipv4Addr::from(cursor.read_u32::<BigEndian>()?))
+            }
+            TYPE_AAAA if data_len == 16 => {
+                let mut octets = [0; 16];
+                cursor.read_exact(&mut octets)?;
+                RecordData::Aaaa(Ipv6Addr::from(octets))
+            }
+            _ => {
+                let mut data = Vec::with_capacity(data_len as usize);
+                for _ in 0..data_len {
+                    data.push(cursor.read_u8()?);
+                }
+                RecordData::Unknown(record_type, data)
+            }
+        })
+    }
+
+    /// The record data, as written in a standard DNS packet.
+    pub fn rdata(&self) -> MulticastDnsResult<Vec<u8>> {
+        let mut rdata = Vec::new();
+        match self {
+            RecordData::Target(target) => rdata.extend_from_slice(target.target.as_bytes()),
+            RecordData::Ptr(name) => write_name(&mut rdata, name)?,
+            RecordData::Srv(location) => {
+                rdata.write_u16::<BigEndian>(location.priority)?;
+                rdata.write_u16::<BigEndian>(location.weight)?;
+                rdata.write_u16::<BigEndian>(location.port)?;
+                write_name(&mut rdata, &location.target)?;
+            }
+            RecordData::Txt(strings) => {
+                for string in strings {
+                    if string.len() > 255 {
+                        return Err(MulticastDnsError::new_other(&format!(
+                            "TXT string '{}' is longer than 255 bytes.",
+                            string
+                        )));
+                    }
+                    rdata.write_u8(string.len() as u8)?;
+                    rdata.extend_from_slice(string.as_bytes());
+                }
+            }
+            RecordData::A(address) => rdata.extend_from_slice(&address.octets()),
+            RecordData::Aaaa(address) => rdata.extend_from_slice(&address.octets()),
+            RecordData::Unknown(_, data) => rdata.extend_from_slice(data),
+        }
+        Ok(rdata)
+    }
+}
+
 /// Correspond to the URL [`advertised`](https://docs.rs/lib3h_protocol/0.0.10/lib3h_protocol/network_engine/trait.NetworkEngine.html#tymethod.advertise)
 /// by the [`NetworkEngine`](https://docs.rs/lib3h_protocol/0.0.10/lib3h_protocol/network_engine/trait.NetworkEngine.html) from [`Lib3h_protocol`](https://crates.io/crates/lib3h_protocol).
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -180,3 +395,54 @@ fn answer_with_target_test() {
 
     assert_eq!(answer, answer_from_raw);
 }
+
+#[test]
+fn dns_sd_answers_test() {
+    let answers = vec![
+        AnswerSection::new_record(
+            "_lib3h._tcp.local",
+            RecordData::Ptr("hc0-1a2b3c4d._lib3h._tcp.local".to_string()),
+            120,
+        ),
+        AnswerSection::new_record(
+            "hc0-1a2b3c4d._lib3h._tcp.local",
+            RecordData::Srv(ServiceLocation {
+                priority: 0,
+                weight: 0,
+                port: 64159,
+                target: "holonaute.local".to_string(),
+            }),
+            120,
+        ),
+        AnswerSection::new_record(
+            "hc0-1a2b3c4d._lib3h._tcp.local",
+            RecordData::Txt(vec!["txtvers=1".to_string(), "scheme=wss".to_string()]),
+            120,
+        ),
+        AnswerSection::new_record(
+            "holonaute.local",
+            RecordData::A([192, 168, 0, 88].into()),
+            120,
+        ),
+        AnswerSection::new_record(
+            "holonaute.local",
+            RecordData::Aaaa(Ipv6Addr::LOCALHOST),
+            120,
+        ),
+    ];
+
+    for answer in answers {
+        let answer = answer.expect("Fail to build DNS-SD AnswerSection.");
+        let mut buffer = vec![];
+        answer
+            .write_dns_sd(&mut buffer)
+            .expect("Fail to write DNS-SD AnswerSection to buffer.");
+
+        let mut cursor = Cursor::new(&buffer);
+        let answer_from_raw = AnswerSection::from_raw_dns_sd(&mut cursor)
+            .expect("Fail to deserialize DNS-SD AnswerSection from byte buffer.");
+
+        assert_eq!(answer, answer_from_raw);
+        assert_eq!(cursor.position(), buffer.len() as u64);
+    }
+}
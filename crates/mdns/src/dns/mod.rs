@@ -10,11 +10,51 @@ use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
 use std::io::Cursor;
 
 pub mod answer;
+pub mod name;
 pub mod question;
 
-pub use answer::{AnswerSection, Target};
+pub use answer::{AnswerSection, RecordData, ServiceLocation, Target};
 pub use question::QuerySection;
 
+/// Address of a host (RFC 1035).
+pub const TYPE_A: u16 = 1;
+/// Alias of a name, used by the legacy encoding to carry a URL.
+pub const TYPE_CNAME: u16 = 5;
+/// Points a service type to its instances (RFC 6763).
+pub const TYPE_PTR: u16 = 12;
+/// Attributes of a service instance (RFC 6763).
+pub const TYPE_TXT: u16 = 16;
+/// IPv6 address of a host (RFC 3596).
+pub const TYPE_AAAA: u16 = 28;
+/// Host and port of a service instance (RFC 2782).
+pub const TYPE_SRV: u16 = 33;
+/// Any record type, in questions.
+pub const TYPE_ANY: u16 = 255;
+/// The Internet class.
+pub const CLASS_IN: u16 = 1;
+/// Top bit of the class: "cache flush" on answers and "unicast response" on questions
+/// (RFC 6762 sections 10.2 and 5.4).
+pub const CACHE_FLUSH_BIT: u16 = 0x8000;
+/// Header flags of a response with authority.
+pub const RESPONSE_FLAGS: u16 = 0x8400;
+/// Header flag of responses.
+pub const RESPONSE_FLAG: u16 = 0x8000;
+/// Header flag asking a unicast DNS server to resolve a query recursively.
+pub const RECURSION_DESIRED_FLAG: u16 = 0x0100;
+/// Header bits of the response code, zero when a query succeeded.
+const RESPONSE_CODE_MASK: u16 = 0x000F;
+
+/// How DNS messages are laid out on the wire.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WireFormat {
+    /// Standard DNS messages carrying DNS-SD PTR, SRV and TXT records, that other mDNS tools
+    /// understand.
+    DnsSd,
+    /// Our original encoding of length prefixed names and CNAME answers carrying a URL, kept to
+    /// talk to nodes that predate DNS-SD.
+    LegacyCname,
+}
+
 /// Structure matching a DNS message format.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct DnsMessage {
@@ -26,6 +66,10 @@ pub struct DnsMessage {
     pub(crate) nb_additional: u16,
     pub(crate) questions: Vec<QuerySection>,
     pub(crate) answers: Vec<AnswerSection>,
+    /// Only read and written by the DNS-SD encoding
+    pub(crate) authorities: Vec<AnswerSection>,
+    /// Only read and written by the DNS-SD encoding
+    pub(crate) additionals: Vec<AnswerSection>,
 }
 
 impl std::default::Default for DnsMessage {
@@ -39,6 +83,8 @@ impl std::default::Default for DnsMessage {
             nb_additional: 0,
             questions: Vec::new(),
             answers: Vec::new(),
+            authorities: Vec::new(),
+            additionals: Vec::new(),
         }
     }
 }
@@ -56,6 +102,56 @@ impl DnsMessage {
         self.nb_answers
     }
 
+    /// A query for the `query_type` records of `name`, as sent to a unicast DNS server rather
+    /// than multicast, asking it to resolve the query recursively.
+    pub fn unicast_query(trans_id: u16, name: &str, query_type: u16) -> Self {
+        DnsMessage {
+            trans_id,
+            parameters: RECURSION_DESIRED_FLAG,
+            nb_questions: 1,
+            questions: vec![QuerySection::with_type(name, query_type)],
+            ..Default::default()
+        }
+    }
+
+    /// The response of a unicast DNS server to this query, holding `answers`.
+    pub fn unicast_response(&self, answers: Vec<AnswerSection>) -> Self {
+        DnsMessage {
+            trans_id: self.trans_id,
+            parameters: RESPONSE_FLAG | (self.parameters & RECURSION_DESIRED_FLAG),
+            nb_questions: self.questions.len() as u16,
+            nb_answers: answers.len() as u16,
+            questions: self.questions.clone(),
+            answers,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the id matching a response to its query.
+    pub fn trans_id(&self) -> u16 {
+        self.trans_id
+    }
+
+    /// Returns true if this message is a response.
+    pub fn is_response(&self) -> bool {
+        self.parameters & RESPONSE_FLAG == RESPONSE_FLAG
+    }
+
+    /// Returns the response code of a response, zero when the query succeeded.
+    pub fn response_code(&self) -> u16 {
+        self.parameters & RESPONSE_CODE_MASK
+    }
+
+    /// Returns the questions of the message.
+    pub fn questions(&self) -> &[QuerySection] {
+        &self.questions
+    }
+
+    /// Returns the records of the answer section.
+    pub fn answers(&self) -> &[AnswerSection] {
+        &self.answers
+    }
+
     pub fn from_raw(packet: &Vec<u8>) -> MulticastDnsResult<Self> {
         let mut dmesg = DnsMessage::new();
 
@@ -103,6 +199,126 @@ impl DnsMessage {
 
         Ok(packet)
     }
+
+    /// Builds a [`DnsMessage`] from bytes laid out in `wire_format`.
+    pub fn from_bytes(packet: &Vec<u8>, wire_format: WireFormat) -> MulticastDnsResult<Self> {
+        match wire_format {
+            WireFormat::DnsSd => DnsMessage::from_raw_dns_sd(packet),
+            WireFormat::LegacyCname => DnsMessage::from_raw(packet),
+        }
+    }
+
+    /// Lays this message out in `wire_format`.
+    pub fn to_bytes(&self, wire_format: WireFormat) -> MulticastDnsResult<Vec<u8>> {
+        match wire_format {
+            WireFormat::DnsSd => self.to_raw_dns_sd(),
+            WireFormat::LegacyCname => self.to_raw(),
+        }
+    }
+
+    /// Every resource record of the message, whatever its section.
+    pub fn records(&self) -> impl Iterator<Item = &AnswerSection> {
+        self.answers
+            .iter()
+            .chain(self.authorities.iter())
+            .chain(self.additionals.iter())
+    }
+
+    /// Builds a [`DnsMessage`] from a standard DNS packet.
+    pub fn from_raw_dns_sd(packet: &Vec<u8>) -> MulticastDnsResult<Self> {
+        let mut dmesg = DnsMessage::new();
+
+        let mut cursor = Cursor::new(packet);
+
+        dmesg.trans_id = cursor.read_u16::<BigEndian>()?;
+        dmesg.parameters = cursor.read_u16::<BigEndian>()?;
+        dmesg.nb_questions = cursor.read_u16::<BigEndian>()?;
+        dmesg.nb_answers = cursor.read_u16::<BigEndian>()?;
+        dmesg.nb_authority = cursor.read_u16::<BigEndian>()?;
+        dmesg.nb_additional = cursor.read_u16::<BigEndian>()?;
+
+        for _ in 0..dmesg.nb_questions {
+            dmesg
+                .questions
+                .push(QuerySection::from_raw_dns_sd(&mut cursor)?);
+        }
+        for _ in 0..dmesg.nb_answers {
+            dmesg
+                .answers
+                .push(AnswerSection::from_raw_dns_sd(&mut cursor)?);
+        }
+        for _ in 0..dmesg.nb_authority {
+            dmesg
+                .authorities
+                .push(AnswerSection::from_raw_dns_sd(&mut cursor)?);
+        }
+        for _ in 0..dmesg.nb_additional {
+            dmesg
+                .additionals
+                .push(AnswerSection::from_raw_dns_sd(&mut cursor)?);
+        }
+
+        Ok(dmesg)
+    }
+
+    /// Lays this message out as a standard DNS packet. The section counts are the lengths of
+    /// the sections.
+    pub fn to_raw_dns_sd(&self) -> MulticastDnsResult<Vec<u8>> {
+        let mut packet = Vec::with_capacity(512);
+
+        packet.write_u16::<BigEndian>(self.trans_id)?;
+        packet.write_u16::<BigEndian>(self.parameters)?;
+        packet.write_u16::<BigEndian>(self.questions.len() as u16)?;
+        packet.write_u16::<BigEndian>(self.answers.len() as u16)?;
+        packet.write_u16::<BigEndian>(self.authorities.len() as u16)?;
+        packet.write_u16::<BigEndian>(self.additionals.len() as u16)?;
+
+        for question in self.questions.iter() {
+            question.write_dns_sd(&mut packet)?;
+        }
+        for record in self.records() {
+            record.write_dns_sd(&mut packet)?;
+        }
+
+        Ok(packet)
+    }
+}
+
+#[test]
+fn dns_message_unicast_test() {
+    let query = DnsMessage::unicast_query(0x1234, "_seeds.holo.host", TYPE_TXT);
+    let packet = query
+        .to_raw_dns_sd()
+        .expect("Fail to convert query to bytes.");
+    let query_from_raw =
+        DnsMessage::from_raw_dns_sd(&packet).expect("Fail to read query from bytes.");
+    assert!(!query_from_raw.is_response());
+    assert_eq!(
+        query_from_raw.questions()[0].domain_name(),
+        "_seeds.holo.host"
+    );
+    assert_eq!(query_from_raw.questions()[0].query_type(), TYPE_TXT);
+
+    let txt = AnswerSection::new_record(
+        "_seeds.holo.host",
+        RecordData::Txt(vec![String::from("wss://192.168.0.88:64159")]),
+        300,
+    )
+    .expect("Fail to build TXT record.");
+    let response = query_from_raw.unicast_response(vec![txt.clone()]);
+    let packet = response
+        .to_raw_dns_sd()
+        .expect("Fail to convert response to bytes.");
+    let response_from_raw =
+        DnsMessage::from_raw_dns_sd(&packet).expect("Fail to read response from bytes.");
+    assert!(response_from_raw.is_response());
+    assert_eq!(response_from_raw.trans_id(), 0x1234);
+    assert_eq!(response_from_raw.response_code(), 0);
+    assert_eq!(response_from_raw.answers(), &[txt]);
+    assert_eq!(
+        response_from_raw.answers()[0].domain_name(),
+        "_seeds.holo.host"
+    );
 }
 
 #[test]
@@ -184,3 +400,40 @@ fn dns_message_multiple_question_and_answer_test() {
 
     assert_eq!(dmesg, dmesg_from_raw);
 }
+
+#[test]
+fn dns_sd_message_test() {
+    let questions = vec![QuerySection::with_type("_lib3h._tcp.local", TYPE_PTR)];
+    let answers = vec![AnswerSection::new_record(
+        "_lib3h._tcp.local",
+        RecordData::Ptr("hc0-1a2b3c4d._lib3h._tcp.local".to_string()),
+        120,
+    )
+    .expect("Fail to build PTR record.")];
+    let additionals = vec![AnswerSection::new_record(
+        "hc0-1a2b3c4d._lib3h._tcp.local",
+        RecordData::Txt(vec!["txtvers=1".to_string()]),
+        120,
+    )
+    .expect("Fail to build TXT record.")];
+
+    let dmesg = DnsMessage {
+        parameters: RESPONSE_FLAGS,
+        nb_questions: questions.len() as u16,
+        nb_answers: answers.len() as u16,
+        nb_additional: additionals.len() as u16,
+        questions,
+        answers,
+        additionals,
+        ..Default::default()
+    };
+
+    let packet = dmesg
+        .to_bytes(WireFormat::DnsSd)
+        .expect("Fail to convert DnsMessage to bytes.");
+    let dmesg_from_raw = DnsMessage::from_bytes(&packet, WireFormat::DnsSd)
+        .expect("Fail to deserialize DnsMessage from bytes");
+
+    assert_eq!(dmesg, dmesg_from_raw);
+    assert_eq!(dmesg.records().count(), 2);
+}
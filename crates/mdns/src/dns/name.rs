@@ -0,0 +1,117 @@
+//! DNS domain names, as sequences of length prefixed labels
+//! ([RFC 1035 section 4.1.4](https://tools.ietf.org/html/rfc1035#section-4.1.4)).
+
+use crate::error::{MulticastDnsError, MulticastDnsResult};
+#[allow(unused_imports)]
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
+use std::io::Cursor;
+
+/// Longest label allowed by the standard.
+const MAX_LABEL_LEN: usize = 63;
+
+/// Two high bits set on a label length mark a compression pointer.
+const POINTER_MASK: u8 = 0xC0;
+
+/// Reads a domain name, following compression pointers, and returns it dotted without the
+/// trailing dot: `_lib3h._tcp.local`.
+pub fn read_name(cursor: &mut Cursor<&Vec<u8>>) -> MulticastDnsResult<String> {
+    let packet = *cursor.get_ref();
+    let mut labels: Vec<String> = Vec::new();
+    // Where the cursor resumes once we jumped to a pointer
+    let mut resume_at: Option<u64> = None;
+    let mut position = cursor.position();
+
+    loop {
+        let mut reader = Cursor::new(packet);
+        reader.set_position(position);
+        let len = reader.read_u8()?;
+
+        if len & POINTER_MASK == POINTER_MASK {
+            let offset = u64::from(u16::from_be_bytes([len & !POINTER_MASK, reader.read_u8()?]));
+            // Pointers must go backward, which also makes loops impossible
+            if offset >= position {
+                return Err(MulticastDnsError::new_other(
+                    "DNS name compression pointer does not point backward.",
+                ));
+            }
+            if resume_at.is_none() {
+                resume_at = Some(reader.position());
+            }
+            position = offset;
+            continue;
+        }
+        if len == 0 {
+            position = reader.position();
+            break;
+        }
+        if len as usize > MAX_LABEL_LEN {
+            return Err(MulticastDnsError::new_other(&format!(
+                "DNS label of {} bytes is too long.",
+                len
+            )));
+        }
+
+        let mut label = Vec::with_capacity(len as usize);
+        for _ in 0..len {
+            label.push(reader.read_u8()?);
+        }
+        labels.push(String::from(std::str::from_utf8(&label)?));
+        position = reader.position();
+    }
+
+    cursor.set_position(resume_at.unwrap_or(position));
+    Ok(labels.join("."))
+}
+
+/// Writes a dotted domain name as labels, without compression.
+pub fn write_name(packet: &mut Vec<u8>, name: &str) -> MulticastDnsResult<()> {
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        if label.len() > MAX_LABEL_LEN {
+            return Err(MulticastDnsError::new_other(&format!(
+                "DNS label '{}' is longer than {} bytes.",
+                label, MAX_LABEL_LEN
+            )));
+        }
+        packet.write_u8(label.len() as u8)?;
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.write_u8(0)?;
+
+    Ok(())
+}
+
+#[test]
+fn name_io_test() {
+    let mut buffer = Vec::new();
+    write_name(&mut buffer, "_lib3h._tcp.local.").expect("Fail to write name to buffer.");
+    assert_eq!(buffer[0], 6);
+    assert_eq!(buffer.len(), 19);
+
+    let mut cursor = Cursor::new(&buffer);
+    let name = read_name(&mut cursor).expect("Fail to read name from byte buffer.");
+
+    assert_eq!(name, "_lib3h._tcp.local");
+    assert_eq!(cursor.position(), buffer.len() as u64);
+}
+
+#[test]
+fn name_compression_test() {
+    let mut buffer = Vec::new();
+    write_name(&mut buffer, "_lib3h._tcp.local").expect("Fail to write name to buffer.");
+    let second = buffer.len() as u64;
+    // "node-1" followed by a pointer to the start of the packet
+    buffer.push(6);
+    buffer.extend_from_slice(b"node-1");
+    buffer.extend_from_slice(&[0xC0, 0x00]);
+
+    let mut cursor = Cursor::new(&buffer);
+    cursor.set_position(second);
+    let name = read_name(&mut cursor).expect("Fail to read compressed name.");
+
+    assert_eq!(name, "node-1._lib3h._tcp.local");
+    assert_eq!(cursor.position(), buffer.len() as u64);
+
+    // A pointer to itself is rejected instead of looping
+    let looping = vec![0xC0, 0x00];
+    assert!(read_name(&mut Cursor::new(&looping)).is_err());
+}
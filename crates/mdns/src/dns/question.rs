@@ -1,6 +1,12 @@
 //! DNS Question part.
 
-use crate::error::MulticastDnsResult;
+use crate::{
+    dns::{
+        name::{read_name, write_name},
+        CACHE_FLUSH_BIT, CLASS_IN, TYPE_CNAME,
+    },
+    error::MulticastDnsResult,
+};
 #[allow(unused_imports)]
 use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
 use std::io::Cursor;
@@ -17,9 +23,10 @@ pub enum Question {
 pub struct QuerySection {
     /// The length of the "domain_name" string we are querying
     pub(crate) dn_len: u16,
-    /// Correspond to the networkId we are querying in our mDNS implementation
+    /// Correspond to the networkId we are querying with legacy CNAME records, or to the
+    /// service type with DNS-SD records
     pub(crate) domain_name: String,
-    /// Should be CNAME(5) from the standard
+    /// CNAME(5) for legacy records, PTR(12) for DNS-SD
     pub(crate) query_type: u16,
     /// IN(1) for the Internet.
     pub(crate) query_class: u16,
@@ -27,13 +34,36 @@ pub struct QuerySection {
 
 impl QuerySection {
     pub fn new(name: &str) -> Self {
+        Self::with_type(name, TYPE_CNAME)
+    }
+
+    /// New question for records of type `query_type`.
+    pub fn with_type(name: &str, query_type: u16) -> Self {
         Self {
             dn_len: name.len() as u16,
             domain_name: name.to_owned(),
-            query_type: 5,
-            query_class: 1,
+            query_type,
+            query_class: CLASS_IN,
         }
     }
+
+    /// Returns true if this question is of the Internet class, whether it asks for a unicast
+    /// response or not.
+    pub fn is_internet_class(&self) -> bool {
+        // The cache flush bit of answers is the unicast response bit of questions
+        self.query_class & !CACHE_FLUSH_BIT == CLASS_IN
+    }
+
+    /// Returns the name asked about.
+    pub fn domain_name(&self) -> &str {
+        &self.domain_name
+    }
+
+    /// Returns the type of the records asked for.
+    pub fn query_type(&self) -> u16 {
+        self.query_type
+    }
+
     pub fn from_raw(dn_len: u16, cursor: &mut Cursor<&Vec<u8>>) -> MulticastDnsResult<Self> {
         let mut domain_name: Vec<u8> = Vec::with_capacity(dn_len as usize);
         for _ in 0..dn_len {
@@ -58,6 +88,26 @@ impl QuerySection {
         packet.write_u16::<BigEndian>(self.query_type)?;
         packet.write_u16::<BigEndian>(self.query_class)?;
 
+        Ok(())
+    }
+    /// Builds a [`QuerySection`] from a byte cursor over a standard DNS packet.
+    pub fn from_raw_dns_sd(cursor: &mut Cursor<&Vec<u8>>) -> MulticastDnsResult<Self> {
+        let domain_name = read_name(cursor)?;
+
+        Ok(Self {
+            dn_len: domain_name.len() as u16,
+            domain_name,
+            query_type: cursor.read_u16::<BigEndian>()?,
+            query_class: cursor.read_u16::<BigEndian>()?,
+        })
+    }
+
+    /// Writes this question to a standard DNS packet.
+    pub fn write_dns_sd(&self, packet: &mut Vec<u8>) -> MulticastDnsResult<()> {
+        write_name(packet, &self.domain_name)?;
+        packet.write_u16::<BigEndian>(self.query_type)?;
+        packet.write_u16::<BigEndian>(self.query_class)?;
+
         Ok(())
     }
 }
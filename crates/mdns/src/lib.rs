@@ -40,10 +40,12 @@ use url::Url;
 use zeroize::Zeroize;
 
 use std::{
-    net::{self, SocketAddr, ToSocketAddrs},
+    collections::HashMap,
+    net::{self, SocketAddr},
     time::Instant,
 };
 
+use lib3h_crypto_api::{Buffer, CryptoSystem};
 use lib3h_protocol::{
     discovery::{error::DiscoveryResult, Discovery},
     uri::Lib3hUri,
@@ -61,6 +63,11 @@ pub use builder::MulticastDnsBuilder;
 pub mod record;
 use record::{MapRecord, Record};
 
+pub mod probe;
+pub use probe::ProbeState;
+
+pub mod signature;
+
 // 20 byte IP header would mean 65_507... but funky configs can increase that
 // const READ_BUF_SIZE: usize = 60_000;
 // however... we don't want to accept any packets that big...
@@ -68,27 +75,69 @@ use record::{MapRecord, Record};
 const READ_BUF_SIZE: usize = 4_096;
 
 /// Delay between probe query, 250ms by default.
-const _PROBE_QUERY_DELAY_MS: u64 = 250;
+const PROBE_QUERY_DELAY_MS: u64 = 250;
 
 /// Listening port of this mDNS service.
 const SERVICE_LISTENER_PORT: u16 = 8585;
 
 /// Threshold value used to getting ourselves out of a potential
 /// infinite loop during probe
-const _FAIL_SAFE_TRESHOLD: u16 = 1_000;
+const FAIL_SAFE_TRESHOLD: u16 = 1_000;
 
 /// mDNS multicast IPv4 address.
 const MDNS_MULCAST_IPV4_ADRESS: &str = "224.0.0.251";
 
+/// mDNS multicast IPv6 address, link-local scope.
+const MDNS_MULCAST_IPV6_ADRESS: &str = "ff02::fb";
+
 /// Default bind adress.
 const DEFAULT_BIND_ADRESS: &str = "0.0.0.0";
 
+/// Default IPv6 bind adress.
+const DEFAULT_BIND_ADRESS_V6: &str = "::";
+
 /// Default "time to live" value for a new record.
 const DEFAULT_TTL: u32 = 255;
 
 /// Default amount of time between two queries.
 const DEFAULT_QUERY_INTERVAL_MS: u128 = 30_000;
 
+/// DNS-SD service type our nodes advertise their URLs under.
+pub const DNS_SD_SERVICE_TYPE: &str = "_lib3h._tcp.local";
+
+/// Which IP versions we multicast on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IpStack {
+    /// IPv4 only, on `224.0.0.251` by default.
+    V4,
+    /// IPv6 only, on `ff02::fb` by default.
+    V6,
+    /// Both IPv4 and IPv6, each on its own pair of sockets.
+    Dual,
+}
+
+impl IpStack {
+    /// Returns wether we multicast on IPv4.
+    pub fn has_ipv4(self) -> bool {
+        self != IpStack::V6
+    }
+
+    /// Returns wether we multicast on IPv6.
+    pub fn has_ipv6(self) -> bool {
+        self != IpStack::V4
+    }
+}
+
+/// The sockets sending to and receiving from the multicast group of one IP version.
+pub(crate) struct MulticastChannel {
+    /// The multicast group and port our packets are sent to
+    pub(crate) group: SocketAddr,
+    /// The socket used by the mDNS service protocol to send packets
+    pub(crate) send_socket: net::UdpSocket,
+    /// The socket used to receive mDNS packets
+    pub(crate) recv_socket: net::UdpSocket,
+}
+
 /// an mdns instance that can send and receive dns packets on LAN UDP multicast
 pub struct MulticastDns {
     /// Our IP address bound to UDP Socket, default to `0.0.0.0`
@@ -101,20 +150,37 @@ pub struct MulticastDns {
     pub(crate) multicast_ttl: u32,
     /// Multicast address used by the mDNS protocol: `224.0.0.251`
     pub(crate) multicast_address: String,
+    /// IPv6 multicast address used by the mDNS protocol: `ff02::fb`
+    pub(crate) multicast_address_v6: String,
+    /// Which IP versions we multicast on
+    pub(crate) ip_stack: IpStack,
     /// Determine if we need to query / announce.
     pub(crate) timestamp: Instant,
     /// The amount of time we should wait between two queries.
     pub(crate) query_interval_ms: u128,
-    /// The socket used by the mDNS service protocol to send packets
-    pub(crate) send_socket: net::UdpSocket,
-    /// The socket used to receive mDNS packets
-    pub(crate) recv_socket: net::UdpSocket,
+    /// One pair of sockets for each IP version we multicast on
+    pub(crate) channels: Vec<MulticastChannel>,
     /// The buffer used to store the packet to send/receive messages
     buffer: [u8; READ_BUF_SIZE],
     /// Reference the host's record
     pub(crate) own_map_record: MapRecord,
     /// The lookup table where the neighbors are stored
     pub(crate) map_record: MapRecord,
+    /// How our DNS messages are laid out: DNS-SD records by default
+    pub(crate) wire_format: WireFormat,
+    /// Our node id, advertised in the TXT record of our DNS-SD service instances
+    pub(crate) node_id: Option<String>,
+    /// Where we are in the probe / announce sequence of our DNS-SD names
+    pub(crate) probe_state: ProbeState,
+    /// The crypto system signing our records and verifying the ones of others
+    pub(crate) crypto: Option<Box<dyn CryptoSystem>>,
+    /// The secret signing key of our node, if we sign our records
+    pub(crate) signing_key: Option<Box<dyn Buffer>>,
+    /// If true, answers not signed by the node they name are discarded
+    pub(crate) verify_signatures: bool,
+    /// The sequence number of the last record we verified for each node id and URL, and the
+    /// time to live of this knowledge, counting down with the records of the cache
+    pub(crate) verified_seqs: HashMap<(String, String), (u64, u32)>,
 }
 
 impl MulticastDns {
@@ -143,6 +209,16 @@ impl MulticastDns {
         &self.multicast_address
     }
 
+    /// Returns the IPv6 multicast address used by mDNS
+    pub fn multicast_address_v6(&self) -> &str {
+        &self.multicast_address_v6
+    }
+
+    /// Returns which IP versions we multicast on.
+    pub fn ip_stack(&self) -> IpStack {
+        self.ip_stack
+    }
+
     /// Returns the lookup table of records as a [HashMap](std::collections::HashMap).
     pub fn records(&self) -> &MapRecord {
         &self.map_record
@@ -181,12 +257,33 @@ impl MulticastDns {
         self.query_interval_ms
     }
 
-    /// Insert a new record to our cache.
+    /// Returns how our DNS messages are laid out.
+    pub fn wire_format(&self) -> WireFormat {
+        self.wire_format
+    }
+
+    /// Returns the node id we advertise, if any.
+    pub fn node_id(&self) -> Option<&str> {
+        self.node_id.as_ref().map(|node_id| node_id.as_str())
+    }
+
+    /// Insert a new record to our cache, signed if we sign our records.
     pub fn insert_own_record(&mut self, netid: &str, records: &[&str]) {
-        let records: Vec<Record> = records
+        let node_id = self.node_id.clone();
+        let mut records: Vec<Record> = records
             .iter()
-            .map(|rec| Record::new(netid, rec, 255))
+            .map(|rec| Record {
+                node_id: node_id.clone(),
+                ..Record::new(netid, rec, 255)
+            })
             .collect();
+        if let (Some(crypto), Some(signing_key)) = (&self.crypto, &self.signing_key) {
+            for record in records.iter_mut() {
+                if let Err(e) = signature::sign_record(&**crypto, signing_key, record) {
+                    error!("Fail to sign our record for '{}': '{}'", record.url, e);
+                }
+            }
+        }
         self.own_map_record.insert(netid.to_string(), records);
     }
 
@@ -206,47 +303,68 @@ impl MulticastDns {
 
     /// Broadcasts a DNS message.
     pub fn broadcast_message(&self, dmesg: &DnsMessage) -> Result<usize, MulticastDnsError> {
-        let addr = (self.multicast_address.as_ref(), self.bind_port)
-            .to_socket_addrs()?
-            .next()?;
-        let data = dmesg.to_raw()?;
-
-        Ok(self.send_socket.send_to(&data, &addr)?)
+        let data = dmesg.to_bytes(self.wire_format)?;
+        self.broadcast(&data)
     }
 
-    /// Broadcasts a packet.
+    /// Broadcasts a packet to the multicast group of every IP version we are on. With both of
+    /// them, it only fails if it could not be sent on any.
     pub fn broadcast(&self, data: &[u8]) -> Result<usize, MulticastDnsError> {
-        let addr = (self.multicast_address.as_ref(), self.bind_port)
-            .to_socket_addrs()?
-            .next()?;
+        let mut result = Err(MulticastDnsError::new_other(
+            "No multicast socket to broadcast on.",
+        ));
+        for channel in self.channels.iter() {
+            match channel.send_socket.send_to(data, channel.group) {
+                Ok(num_bytes) => result = Ok(num_bytes),
+                Err(e) => {
+                    debug!("Fail to broadcast to {}: '{}'", channel.group, e);
+                    if result.is_err() {
+                        result = Err(e.into());
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Sends a packet directly to `addr`, from the socket of its IP version.
+    pub fn send_to(&self, data: &[u8], addr: SocketAddr) -> Result<usize, MulticastDnsError> {
+        let channel = self
+            .channels
+            .iter()
+            .find(|channel| channel.group.is_ipv6() == addr.is_ipv6())
+            .ok_or_else(|| {
+                MulticastDnsError::new_other(&format!("No multicast socket to reach {}.", addr))
+            })?;
 
-        Ok(self.send_socket.send_to(&data, &addr)?)
+        Ok(channel.send_socket.send_to(data, addr)?)
     }
 
-    /// try to receive a dns packet.
+    /// try to receive a dns packet, from any IP version we are on.
     /// will return None rather than blocking if none are queued
     pub fn recv(&mut self) -> MulticastDnsResult<Option<(Vec<u8>, SocketAddr)>> {
-        self.clear_buffer();
-
-        match self.recv_socket.recv_from(&mut self.buffer) {
-            Ok((0, _)) => Ok(None),
-            Ok((num_bytes, addr)) => {
-                debug!(
-                    "Received '{}' bytes: {:?}",
-                    num_bytes,
-                    &self.buffer.to_vec()[..num_bytes]
-                );
-                let packet = self.buffer[..num_bytes].to_vec();
-                Ok(Some((packet, addr)))
-            }
-            Err(e) => {
-                if e.kind() == std::io::ErrorKind::WouldBlock {
-                    Ok(None)
-                } else {
-                    Err(e.into())
+        for channel in self.channels.iter() {
+            self.buffer.zeroize();
+
+            match channel.recv_socket.recv_from(&mut self.buffer) {
+                Ok((0, _)) => continue,
+                Ok((num_bytes, addr)) => {
+                    debug!(
+                        "Received '{}' bytes: {:?}",
+                        num_bytes,
+                        &self.buffer.to_vec()[..num_bytes]
+                    );
+                    let packet = self.buffer[..num_bytes].to_vec();
+                    return Ok(Some((packet, addr)));
+                }
+                Err(e) => {
+                    if e.kind() != std::io::ErrorKind::WouldBlock {
+                        return Err(e.into());
+                    }
                 }
             }
         }
+        Ok(None)
     }
 
     /// Clean our buffer of bytes from previous messages.
@@ -261,6 +379,7 @@ impl MulticastDns {
         for (_, records) in self.map_record.iter_mut() {
             let _: Vec<Record> = records.drain_filter(|r| r.ttl == 0).collect();
         }
+        self.verified_seqs.retain(|_, (_, ttl)| *ttl > 0);
     }
 
     /// Update the `time to live` of every cached record.
@@ -273,6 +392,9 @@ impl MulticastDns {
                 }
             }
         }
+        for (_seq, ttl) in self.verified_seqs.values_mut() {
+            *ttl = ttl.saturating_sub(1);
+        }
     }
 
     /// mDNS Querier, implementing the "One-Shot Multicast DNS Queries" from the standard.
@@ -286,32 +408,68 @@ impl MulticastDns {
         Ok(())
     }
 
+    /// Updates our cache with the answers gathered from the network for our own network ids.
+    fn cache_answers(&mut self, mut new_map_record: MapRecord) {
+        self.retain_verified(&mut new_map_record);
+        let own_networkids: Vec<String> = self
+            .own_networkids()
+            .iter()
+            .map(|v| (*v).to_string())
+            .collect();
+
+        for (netid, new_records) in new_map_record.iter() {
+            // Let's only operate on the networks we belong to
+            if own_networkids.contains(netid) {
+                // Records with a zero TTL are goodbyes from nodes leaving
+                let (goodbyes, new_records): (Vec<Record>, Vec<Record>) =
+                    new_records.iter().cloned().partition(|r| r.ttl == 0);
+                self.map_record.evict(&goodbyes);
+
+                if !new_records.is_empty() {
+                    let tmp_new_map_record = MapRecord::with_record(netid, &new_records);
+                    self.update_cache(&tmp_new_map_record);
+                }
+            }
+        }
+    }
+
     /// A mDNS Responder that listen to the network in order to repond to the queries.
     fn responder(&mut self) -> MulticastDnsResult<()> {
         // Process all elements of the UDP socket stack
         loop {
             match self.recv() {
                 Ok(Some((packet, sender_addr))) => {
-                    let dmesg = DnsMessage::from_raw(&packet)?;
+                    // Other mDNS tools may share our port, so we skip what we cannot read
+                    let dmesg = match DnsMessage::from_bytes(&packet, self.wire_format) {
+                        Ok(dmesg) => dmesg,
+                        Err(e) => {
+                            debug!(
+                                "Skipping unreadable DNS message from {}: '{}'",
+                                sender_addr, e
+                            );
+                            continue;
+                        }
+                    };
+
+                    // Someone else may use the names of our DNS-SD records
+                    if self.wire_format == WireFormat::DnsSd
+                        && (self.probe_state == ProbeState::Probing
+                            || self.probe_state == ProbeState::Announced)
+                        && self.resolve_conflicts(&dmesg)
+                    {
+                        self.probe_state = ProbeState::Conflicted;
+                    }
 
                     // Here we update our cache with the responses gathered from the network
                     if dmesg.nb_answers > 0 {
-                        if let Some(new_map_record) = MapRecord::from_dns_message(&dmesg) {
-                            let own_networkids: Vec<String> = self
-                                .own_networkids()
-                                .iter()
-                                .map(|v| (*v).to_string())
-                                .collect();
-
-                            // Only update our cache with the answers for our own NetworkId
-                            for (netid, new_records) in new_map_record.iter() {
-                                // Let's only operate on the networks we belong to
-                                if own_networkids.contains(netid) {
-                                    let tmp_new_map_record =
-                                        MapRecord::with_record(netid, new_records);
-                                    self.update_cache(&tmp_new_map_record);
-                                }
+                        let new_map_record = match self.wire_format {
+                            WireFormat::DnsSd => {
+                                MapRecord::from_dns_sd_message(&dmesg, self.ip_stack)
                             }
+                            WireFormat::LegacyCname => MapRecord::from_dns_message(&dmesg),
+                        };
+                        if let Some(new_map_record) = new_map_record {
+                            self.cache_answers(new_map_record);
                         }
                     }
                     // According to the standard: "Multicast DNS responses MUST NOT contain
@@ -322,22 +480,8 @@ impl MulticastDns {
                     // We send the response directly to the sender instead of broadcasting it to
                     // avoid any unnecessary burden on the network.
                     else if dmesg.nb_questions > 0 {
-                        let question_list: Vec<&str> = dmesg
-                            .questions
-                            .iter()
-                            .filter_map(|q| {
-                                // Filter out all the queries that are not INET + CNAME
-                                if q.query_class == 1 && q.query_type == 5 {
-                                    Some(q.domain_name.as_str())
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect();
-                        if let Some(response) =
-                            self.own_map_record.to_dns_response_message(&question_list)
-                        {
-                            self.send_socket.send_to(&response.to_raw()?, sender_addr)?;
+                        if let Some(response) = self.build_response_message(&dmesg.questions) {
+                            self.send_to(&response.to_bytes(self.wire_format)?, sender_addr)?;
                             // As the direct send message to the querier tends to fail on a local
                             // machine during our tests, we broadcast the response as well for
                             // safety reasons
@@ -361,19 +505,83 @@ impl MulticastDns {
         Ok(())
     }
 
-    /// Builds mDNS probe packet with the proper bit set up in order to check if a host record is
-    /// available.
-    /// Not used çin our implementation because we don't need to be fully standart compliant.
-    fn _build_probe_packet(&self) -> DnsMessage {
-        let questions: Vec<QuerySection> = self
-            .own_map_record
-            .keys()
-            .map(|k| QuerySection::new(k))
-            .collect();
-        DnsMessage {
-            nb_questions: questions.len() as u16,
-            questions,
-            ..Default::default()
+    /// Builds the response to `questions` with the records we have authority on: all of them
+    /// if asked for our DNS-SD service type or instance names, or those of the asked network ids
+    /// with legacy CNAME records. We do not answer for names we are still probing or released.
+    fn build_response_message(&self, questions: &[QuerySection]) -> Option<DnsMessage> {
+        if self.probe_state == ProbeState::Probing || self.probe_state == ProbeState::Released {
+            return None;
+        }
+        match self.wire_format {
+            WireFormat::DnsSd => {
+                let own_instances: Vec<String> = self
+                    .own_map_record
+                    .values()
+                    .flat_map(|records| records.iter().map(|rec| rec.instance_name()))
+                    .collect();
+                let asks_for_us = questions.iter().any(|q| {
+                    q.is_internet_class()
+                        && ((q.domain_name == DNS_SD_SERVICE_TYPE
+                            && (q.query_type == TYPE_PTR || q.query_type == TYPE_ANY))
+                            || own_instances.contains(&q.domain_name))
+                });
+                if asks_for_us {
+                    self.build_own_response_message()
+                } else {
+                    None
+                }
+            }
+            WireFormat::LegacyCname => {
+                let question_list: Vec<&str> = questions
+                    .iter()
+                    .filter_map(|q| {
+                        // Filter out all the queries that are not INET + CNAME
+                        if q.query_class == 1 && q.query_type == 5 {
+                            Some(q.domain_name.as_str())
+                        } else {
+                            None
+                        }
+                    })
+                    .collect();
+                self.own_map_record.to_dns_response_message(&question_list)
+            }
+        }
+    }
+
+    /// Builds an unsolicited response holding all our records.
+    fn build_own_response_message(&self) -> Option<DnsMessage> {
+        self.build_records_message(&self.own_map_record)
+    }
+
+    /// Builds the goodbye packet holding all our records with a zero TTL, so the other nodes
+    /// evict them right away. Signed goodbyes are newer than our records, which we sign again
+    /// past them so announcing them later is not taken for a replay.
+    fn build_goodbye_message(&mut self) -> Option<DnsMessage> {
+        let mut goodbyes = self.own_map_record.to_goodbyes();
+        self.sign_map_record(&mut goodbyes);
+
+        if self.signing_key.is_some() {
+            let mut own_map_record = self.own_map_record.clone();
+            for (netid, records) in own_map_record.iter_mut() {
+                if let Some(goodbye_records) = goodbyes.get(netid) {
+                    for (rec, goodbye) in records.iter_mut().zip(goodbye_records) {
+                        rec.seq = goodbye.seq;
+                    }
+                }
+            }
+            self.sign_map_record(&mut own_map_record);
+            self.own_map_record = own_map_record;
+        }
+
+        self.build_records_message(&goodbyes)
+    }
+
+    /// Builds a response holding the records of `map_record` for our network ids.
+    fn build_records_message(&self, map_record: &MapRecord) -> Option<DnsMessage> {
+        let own_net_id_list = self.own_networkids();
+        match self.wire_format {
+            WireFormat::DnsSd => map_record.to_dns_sd_response_message(&own_net_id_list),
+            WireFormat::LegacyCname => map_record.to_dns_response_message(&own_net_id_list),
         }
     }
 
@@ -381,6 +589,12 @@ impl MulticastDns {
     pub fn build_query_message(&self) -> Option<DnsMessage> {
         if self.own_map_record.is_empty() {
             None
+        } else if self.wire_format == WireFormat::DnsSd {
+            Some(DnsMessage {
+                nb_questions: 1,
+                questions: vec![QuerySection::with_type(DNS_SD_SERVICE_TYPE, TYPE_PTR)],
+                ..Default::default()
+            })
         } else {
             let mut questions = Vec::new();
             for (_netid, records) in self.own_map_record.iter() {
@@ -400,26 +614,26 @@ impl MulticastDns {
     /// Sends unsolicited mDNS responses containing our node's resource records in the "Answer
     /// Section" of a DNS packet.
     fn announcing(&mut self) -> MulticastDnsResult<()> {
-        let own_net_id_list = self.own_networkids();
-
-        if let Some(dmesg) = self
-            .own_map_record
-            .to_dns_response_message(&own_net_id_list)
-        {
+        if let Some(dmesg) = self.build_own_response_message() {
             // Sends at least 2 time an unsolicited response, up to 8 times maximum (according to
             // the standard https://tools.ietf.org/html/rfc6762#section-8.3)
             self.broadcast_message(&dmesg)?;
             self.broadcast_message(&dmesg)?;
             self.broadcast_message(&dmesg)?;
         }
+        self.probe_state = ProbeState::Announced;
         Ok(())
     }
 }
 
 impl Discovery for MulticastDns {
     /// Make yourself known on the network.
+    /// Our DNS-SD names are probed first, see [`probe`](crate::probe).
     fn advertise(&mut self) -> DiscoveryResult<()> {
         self.query()?;
+        if self.wire_format == WireFormat::DnsSd {
+            self.probe()?;
+        }
         self.announcing()?;
         Ok(())
     }
@@ -428,6 +642,12 @@ impl Discovery for MulticastDns {
     fn discover(&mut self) -> DiscoveryResult<Vec<Lib3hUri>> {
         self.responder()?;
 
+        // Some of our names were taken since we announced them
+        if self.probe_state == ProbeState::Conflicted {
+            self.probe()?;
+            self.announcing()?;
+        }
+
         // We should query (and announce in the same time because we will anwser to our query in the
         // next iteration) "every amount of time"
         if self.timestamp.elapsed().as_millis() > self.query_interval_ms {
@@ -442,17 +662,11 @@ impl Discovery for MulticastDns {
     }
 
     /// Release itself from the available participants in a network.
+    /// Sends goodbye packets: our records with a "time to live" of zero.
     fn release(&mut self) -> DiscoveryResult<()> {
-        for (_netid, records) in self.own_map_record.iter_mut() {
-            for rec in records.iter_mut() {
-                // Since we want to leave the network space, we set our "time to live" to zero and let
-                // other know about it
-                rec.ttl = 0;
-            }
-        }
+        self.probe_state = ProbeState::Released;
 
-        let net_ids = self.own_networkids();
-        if let Some(release_dmesg) = self.own_map_record.to_dns_response_message(&net_ids) {
+        if let Some(release_dmesg) = self.build_goodbye_message() {
             self.broadcast_message(&release_dmesg)?;
             self.broadcast_message(&release_dmesg)?;
             self.broadcast_message(&release_dmesg)?;
@@ -506,6 +720,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn it_should_loop_question_on_dual_stack() {
+        // Not every test environment has IPv6 multicast
+        let mut mdns = match MulticastDnsBuilder::new()
+            .ip_stack(IpStack::Dual)
+            .multicast_address("224.0.0.246")
+            .bind_port(55246)
+            .multicast_loop(true)
+            .build()
+        {
+            Ok(mdns) => mdns,
+            Err(e) => {
+                eprintln!("Skipping dual stack test without IPv6 multicast: '{}'", e);
+                return;
+            }
+        };
+        assert_eq!(mdns.ip_stack(), IpStack::Dual);
+        assert_eq!(mdns.channels.len(), 2);
+        assert!(mdns.channels[0].group.is_ipv4());
+        assert!(mdns.channels[1].group.is_ipv6());
+        assert_eq!(mdns.channels[1].group.ip().to_string(), "ff02::fb");
+
+        let mut dmesg = DnsMessage::new();
+        dmesg.nb_questions = 1;
+        dmesg.questions = vec![QuerySection::new("lib3h.test.service")];
+
+        mdns.broadcast_message(&dmesg)
+            .expect("Fail to broadcast DNS Message.");
+
+        // The question comes back once per IP version it made it through
+        while let Some((resp, _addr)) = mdns.recv().expect("Fail to receive from the UDP socket.") {
+            let dmesg_from_resp = DnsMessage::from_raw(&resp).unwrap();
+            assert_eq!(
+                &dmesg_from_resp.questions[0].domain_name,
+                "lib3h.test.service"
+            );
+        }
+    }
+
     #[test]
     fn it_should_loop_answer() {
         let mut mdns = MulticastDnsBuilder::new()
@@ -514,6 +767,7 @@ mod tests {
             .bind_port(56248)
             .multicast_loop(true)
             .multicast_ttl(255)
+            .wire_format(WireFormat::LegacyCname)
             .build()
             .expect("build fail");
 
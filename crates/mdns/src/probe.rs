@@ -0,0 +1,288 @@
+//! Probing and announcing our DNS-SD names, following
+//! [RFC 6762 section 8](https://tools.ietf.org/html/rfc6762#section-8).
+//!
+//! Legacy CNAME records are shared by every node of a network on purpose, so only DNS-SD
+//! instance names are probed.
+
+use crate::{
+    dns::{AnswerSection, DnsMessage, QuerySection, CACHE_FLUSH_BIT, CLASS_IN, TYPE_ANY},
+    error::{ErrorKind, MulticastDnsError, MulticastDnsResult},
+    MulticastDns, FAIL_SAFE_TRESHOLD, PROBE_QUERY_DELAY_MS,
+};
+use log::{error, warn};
+use std::{cmp::Ordering, thread, time::Duration};
+
+/// Number of probe queries to send without hearing of a conflict before our names are ours.
+const PROBE_COUNT: u8 = 3;
+
+/// Where we are in the probe / announce sequence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProbeState {
+    /// We have not advertised ourselves yet.
+    Idle,
+    /// We are making sure no one else uses our names, and do not answer for them yet.
+    Probing,
+    /// Our names are ours, and we defend them.
+    Announced,
+    /// Someone else uses one of our names: it was renamed and must be probed again.
+    Conflicted,
+    /// We left the network with goodbye packets, and do not answer for our names anymore.
+    Released,
+}
+
+impl MulticastDns {
+    /// Returns where we are in the probe / announce sequence.
+    pub fn probe_state(&self) -> ProbeState {
+        self.probe_state
+    }
+
+    /// Probes the names of our DNS-SD records, renaming the ones someone else uses until all of
+    /// them went through [`PROBE_COUNT`] probe queries unchallenged.
+    pub(crate) fn probe(&mut self) -> MulticastDnsResult<()> {
+        self.probe_state = ProbeState::Probing;
+
+        let mut unchallenged = 0;
+        let mut attempts = 0;
+        while unchallenged < PROBE_COUNT {
+            attempts += 1;
+            if attempts > FAIL_SAFE_TRESHOLD {
+                return Err(MulticastDnsError::new(ErrorKind::ProbeError));
+            }
+
+            match self.build_probe_message() {
+                Some(probe) => self.broadcast_message(&probe)?,
+                None => break,
+            };
+            thread::sleep(Duration::from_millis(PROBE_QUERY_DELAY_MS));
+            self.responder()?;
+
+            if self.probe_state == ProbeState::Conflicted {
+                // Start over with the new names
+                self.probe_state = ProbeState::Probing;
+                unchallenged = 0;
+            } else {
+                unchallenged += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Builds a probe query: "any" questions for the names of our DNS-SD service instances,
+    /// asking for unicast responses, with the records we propose for them in the authority
+    /// section.
+    pub(crate) fn build_probe_message(&self) -> Option<DnsMessage> {
+        let mut questions = Vec::new();
+        let mut authorities = Vec::new();
+
+        for records in self.own_map_record.values() {
+            for record in records {
+                let instance = record.instance_name();
+                match record.to_dns_sd_records() {
+                    Ok(dns_sd_records) => {
+                        questions.push(QuerySection {
+                            query_class: CLASS_IN | CACHE_FLUSH_BIT,
+                            ..QuerySection::with_type(&instance, TYPE_ANY)
+                        });
+                        authorities.extend(
+                            dns_sd_records
+                                .into_iter()
+                                .filter(|rec| rec.domain_name == instance),
+                        );
+                    }
+                    Err(e) => error!("Fail to build DNS-SD records for {:?}: '{}'", record, e),
+                }
+            }
+        }
+
+        if questions.is_empty() {
+            None
+        } else {
+            Some(DnsMessage {
+                nb_questions: questions.len() as u16,
+                nb_authority: authorities.len() as u16,
+                questions,
+                authorities,
+                ..DnsMessage::default()
+            })
+        }
+    }
+
+    /// Renames our records whose instance name someone else answers for with other data, or
+    /// proposes other data for in a probe that wins over ours (section 8.2). Losing a
+    /// simultaneous probe renames right away rather than probing the same name again a second
+    /// later, since the winner is about to announce it. Returns true if any record was renamed.
+    pub(crate) fn resolve_conflicts(&mut self, dmesg: &DnsMessage) -> bool {
+        let probing = self.probe_state == ProbeState::Probing;
+        let mut renamed = false;
+
+        for records in self.own_map_record.values_mut() {
+            for record in records.iter_mut() {
+                let instance = record.instance_name();
+                let ours: Vec<AnswerSection> = match record.to_dns_sd_records() {
+                    Ok(dns_sd_records) => dns_sd_records
+                        .into_iter()
+                        .filter(|rec| rec.domain_name == instance)
+                        .collect(),
+                    Err(_) => continue,
+                };
+                let ours: Vec<&AnswerSection> = ours.iter().collect();
+
+                let answered_otherwise = dmesg
+                    .answers
+                    .iter()
+                    .chain(dmesg.additionals.iter())
+                    .filter(|rec| rec.domain_name == instance)
+                    .any(|rec| !ours.iter().any(|own| same_data(own, rec)));
+
+                let their_probe: Vec<&AnswerSection> = dmesg
+                    .authorities
+                    .iter()
+                    .filter(|rec| rec.domain_name == instance)
+                    .collect();
+                let lost_probe = probing
+                    && dmesg.nb_questions > 0
+                    && !their_probe.is_empty()
+                    && lexicographic_cmp(&ours, &their_probe) == Ordering::Less;
+
+                if answered_otherwise || lost_probe {
+                    warn!(
+                        "mDNS: someone else uses '{}', renaming our record for '{}'",
+                        instance, record.url
+                    );
+                    record.rename();
+                    renamed = true;
+                }
+            }
+        }
+
+        renamed
+    }
+}
+
+/// Two records hold the same data, whatever their TTL and cache flush bit.
+fn same_data(a: &AnswerSection, b: &AnswerSection) -> bool {
+    a.answer_type == b.answer_type && a.data == b.data
+}
+
+/// Orders two sets of records to break simultaneous probes: sorted record by record on their
+/// class, type and data, and the set running out first is the lesser.
+fn lexicographic_cmp(ours: &[&AnswerSection], theirs: &[&AnswerSection]) -> Ordering {
+    let sorted_keys = |records: &[&AnswerSection]| {
+        let mut keys: Vec<(u16, u16, Vec<u8>)> = records
+            .iter()
+            .map(|rec| {
+                (
+                    rec.answer_class & !CACHE_FLUSH_BIT,
+                    rec.answer_type,
+                    rec.data.rdata().unwrap_or_default(),
+                )
+            })
+            .collect();
+        keys.sort();
+        keys
+    };
+    sorted_keys(ours).cmp(&sorted_keys(theirs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MulticastDnsBuilder, RecordData, ServiceLocation};
+
+    const NETWORK_ID: &str = "holonaute-probe.holo.host";
+
+    fn own_instance(mdns: &MulticastDns) -> String {
+        mdns.own_map_record
+            .get(NETWORK_ID)
+            .expect("Fail to get our own records.")[0]
+            .instance_name()
+    }
+
+    #[test]
+    fn it_should_rename_on_conflicting_answer() {
+        let mut mdns = MulticastDnsBuilder::new()
+            .own_record(NETWORK_ID, &["wss://192.168.0.88:64159?a=hc0"])
+            .multicast_address("224.0.0.249")
+            .bind_port(57249)
+            .build()
+            .expect("Fail to build mDNS.");
+        mdns.probe_state = ProbeState::Announced;
+        let instance = own_instance(&mdns);
+
+        // Hearing our own announcement is no conflict
+        let ours = mdns
+            .build_own_response_message()
+            .expect("Fail to build our response.");
+        assert!(!mdns.resolve_conflicts(&ours));
+
+        // Someone else answering for our name with another port is
+        let srv = AnswerSection::new_record(
+            &instance,
+            RecordData::Srv(ServiceLocation {
+                priority: 0,
+                weight: 0,
+                port: 1,
+                target: String::from("mistral.local"),
+            }),
+            120,
+        )
+        .expect("Fail to build SRV record.");
+        let theirs = DnsMessage {
+            nb_answers: 1,
+            answers: vec![srv],
+            ..DnsMessage::default()
+        };
+        assert!(mdns.resolve_conflicts(&theirs));
+
+        let renamed = own_instance(&mdns);
+        assert_ne!(instance, renamed);
+        // And our new name is free
+        assert!(!mdns.resolve_conflicts(&theirs));
+    }
+
+    #[test]
+    fn it_should_break_simultaneous_probe_ties() {
+        let mut mdns = MulticastDnsBuilder::new()
+            .own_record(NETWORK_ID, &["wss://192.168.0.88:64159?a=hc0"])
+            .multicast_address("224.0.0.250")
+            .bind_port(57250)
+            .build()
+            .expect("Fail to build mDNS.");
+        mdns.probe_state = ProbeState::Probing;
+        let instance = own_instance(&mdns);
+
+        let probe = mdns
+            .build_probe_message()
+            .expect("Fail to build our probe.");
+        assert_eq!(probe.questions[0].domain_name, instance);
+        assert_eq!(probe.authorities.len(), 2);
+
+        // Hearing our own probe is no conflict
+        assert!(!mdns.resolve_conflicts(&probe));
+
+        let with_srv_priority = |priority: u16| {
+            let mut their_probe = probe.clone();
+            for rec in their_probe.authorities.iter_mut() {
+                if let RecordData::Srv(location) = &mut rec.data {
+                    location.priority = priority;
+                }
+            }
+            their_probe
+        };
+
+        // We win over a probe with lesser data: the same priority, but a lower port
+        let mut lesser = with_srv_priority(0);
+        for rec in lesser.authorities.iter_mut() {
+            if let RecordData::Srv(location) = &mut rec.data {
+                location.port = 1;
+            }
+        }
+        assert!(!mdns.resolve_conflicts(&lesser));
+        assert_eq!(instance, own_instance(&mdns));
+
+        // And lose to a probe with greater data
+        assert!(mdns.resolve_conflicts(&with_srv_priority(1)));
+        assert_ne!(instance, own_instance(&mdns));
+    }
+}
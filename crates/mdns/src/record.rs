@@ -1,15 +1,24 @@
 //! mDNS resource record definition.
 
 use crate::{
-    dns::{AnswerSection, DnsMessage, QuerySection, Target},
-    DEFAULT_BIND_ADRESS, DEFAULT_TTL,
+    dns::{
+        AnswerSection, DnsMessage, QuerySection, RecordData, ServiceLocation, Target,
+        RESPONSE_FLAGS,
+    },
+    error::MulticastDnsResult,
+    IpStack, DEFAULT_BIND_ADRESS, DEFAULT_TTL, DNS_SD_SERVICE_TYPE,
 };
 use hostname;
+use log::error;
 use std::{
     cmp::Ordering,
     collections::HashMap,
     ops::{Deref, DerefMut},
 };
+use url::{Host, Url};
+
+/// Longest DNS label, which our service instance names are.
+const MAX_INSTANCE_LABEL_LEN: usize = 63;
 
 /// Helper type corresponding to a HashMap<NetworkId, Vec<Url>>
 pub type HashMapRecord = HashMap<String, Vec<Record>>;
@@ -90,6 +99,26 @@ impl MapRecord {
         }
     }
 
+    /// Removes the records their owner left the network with, announced with a zero TTL.
+    pub fn evict(&mut self, goodbyes: &[Record]) {
+        for goodbye in goodbyes {
+            if let Some(records) = self.0.get_mut(&goodbye.networkid) {
+                records.retain(|rec| rec.url != goodbye.url);
+            }
+        }
+    }
+
+    /// Returns a copy of the [`MapRecord`] with a zero TTL, to announce we are leaving.
+    pub fn to_goodbyes(&self) -> MapRecord {
+        let mut goodbyes = self.clone();
+        for records in goodbyes.0.values_mut() {
+            for rec in records.iter_mut() {
+                rec.ttl = 0;
+            }
+        }
+        goodbyes
+    }
+
     /// Builds a [`MapRecord`] from a [`DnsMessage`].
     pub fn from_dns_message(dmesg: &DnsMessage) -> Option<MapRecord> {
         if !dmesg.answers.is_empty() {
@@ -98,16 +127,13 @@ impl MapRecord {
             let mut records: Vec<Record> = dmesg
                 .answers
                 .iter()
-                .filter_map(|a_sec| {
-                    if a_sec.answer_class == 1 && a_sec.answer_type == 5 {
-                        Some(Record::new(
-                            &a_sec.domain_name,
-                            &a_sec.data.target,
-                            a_sec.ttl,
-                        ))
-                    } else {
-                        None
+                .filter_map(|a_sec| match &a_sec.data {
+                    RecordData::Target(target)
+                        if a_sec.answer_class == 1 && a_sec.answer_type == 5 =>
+                    {
+                        Some(Record::new(&a_sec.domain_name, &target.target, a_sec.ttl))
                     }
+                    _ => None,
                 })
                 .collect();
 
@@ -154,6 +180,73 @@ impl MapRecord {
         }
     }
 
+    /// Builds a [`MapRecord`] from the DNS-SD records of a [`DnsMessage`]: one record for each
+    /// instance of our service type with a network id in its TXT record. URLs made from SRV
+    /// records use the address of the IP versions of `ip_stack` first.
+    pub fn from_dns_sd_message(dmesg: &DnsMessage, ip_stack: IpStack) -> Option<MapRecord> {
+        let service_suffix = format!(".{}", DNS_SD_SERVICE_TYPE);
+        let mut records: Vec<Record> = dmesg
+            .records()
+            .filter(|rec| rec.is_internet_class() && rec.domain_name.ends_with(&service_suffix))
+            .filter_map(|rec| match &rec.data {
+                RecordData::Txt(attributes) => {
+                    Record::from_dns_sd(&rec.domain_name, attributes, rec.ttl, dmesg, ip_stack)
+                }
+                _ => None,
+            })
+            .collect();
+
+        if records.is_empty() {
+            return None;
+        }
+
+        records.sort();
+        records.dedup_by(|a, b| a.url() == b.url());
+
+        let mut map_record = MapRecord(HashMapRecord::with_capacity(records.len()));
+        for new_record in records.iter() {
+            let tmp_map_record =
+                MapRecord::with_record(&new_record.networkid, &[new_record.clone()]);
+            map_record.update(&tmp_map_record);
+        }
+
+        Some(map_record)
+    }
+
+    /// Builds a DNS-SD response [`DnsMessage`] from a [`MapRecord`].
+    pub fn to_dns_sd_response_message(&self, networkids: &[&str]) -> Option<DnsMessage> {
+        let mut answers = Vec::new();
+
+        for &netid in networkids.iter() {
+            if let Some(records) = self.0.get(netid) {
+                for rec in records {
+                    match rec.to_dns_sd_records() {
+                        Ok(dns_sd_records) => {
+                            for answer in dns_sd_records {
+                                // Records sharing a host share its address record
+                                if !answers.contains(&answer) {
+                                    answers.push(answer);
+                                }
+                            }
+                        }
+                        Err(e) => error!("Fail to build DNS-SD records for {:?}: '{}'", rec, e),
+                    }
+                }
+            }
+        }
+
+        if answers.is_empty() {
+            None
+        } else {
+            Some(DnsMessage {
+                parameters: RESPONSE_FLAGS,
+                nb_answers: answers.len() as u16,
+                answers,
+                ..DnsMessage::default()
+            })
+        }
+    }
+
     /// Builds a [`DnsMessage`] from a [`MapRecord`].
     pub fn to_dns_message_query(&self, networkids: &[&str]) -> Option<DnsMessage> {
         // Let's make sure we have at least one networkid in our keys
@@ -187,6 +280,16 @@ pub struct Record {
     pub(crate) url: String,
     /// Time to live
     pub(crate) ttl: u32,
+    /// Id of the node owning the URL, carried by DNS-SD records only
+    pub(crate) node_id: Option<String>,
+    /// How many times someone else took the DNS-SD instance name of our own record
+    pub(crate) conflicts: u32,
+    /// Hex encoded signature of the record by the node of its node id, carried by DNS-SD
+    /// records only
+    pub(crate) signature: Option<String>,
+    /// When the node of its node id signed the record, in milliseconds since the epoch, so
+    /// replayed records are told apart from fresh ones
+    pub(crate) seq: u64,
 }
 
 impl Record {
@@ -198,6 +301,10 @@ impl Record {
             networkid: name.to_owned(),
             url: url.to_owned(),
             ttl,
+            node_id: None,
+            conflicts: 0,
+            signature: None,
+            seq: 0,
         }
     }
 
@@ -216,6 +323,21 @@ impl Record {
         self.ttl
     }
 
+    /// Returns the id of the node owning the URL, if known.
+    pub fn node_id(&self) -> Option<&str> {
+        self.node_id.as_ref().map(|node_id| node_id.as_str())
+    }
+
+    /// Returns the hex encoded signature of the record by its node, if signed.
+    pub fn signature(&self) -> Option<&str> {
+        self.signature.as_ref().map(|signature| signature.as_str())
+    }
+
+    /// Returns when the record was signed, in milliseconds since the epoch, or zero if unsigned.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
     /// Build a host own record. If there we fail to gather IPv4 addresses from the system,
     /// we fall back to "0.0.0.0" address.
     pub fn new_own() -> Self {
@@ -227,6 +349,10 @@ impl Record {
             networkid: hostname,
             url: String::from(DEFAULT_BIND_ADRESS),
             ttl: DEFAULT_TTL,
+            node_id: None,
+            conflicts: 0,
+            signature: None,
+            seq: 0,
         }
     }
 
@@ -255,6 +381,153 @@ impl Record {
     }
 }
 
+impl Record {
+    /// Name of the DNS-SD service instance advertising this record, made of the node id and a
+    /// hash of the network id and URL so every URL gets its own instance. Once someone else took
+    /// it, it ends with the number of the attempt, as in `-2`.
+    pub fn instance_name(&self) -> String {
+        let mut suffix = format!("{:08x}", fnv1a(&format!("{}{}", self.networkid, self.url)));
+        if self.conflicts > 0 {
+            suffix = format!("{}-{}", suffix, self.conflicts + 1);
+        }
+        let label: String = dns_label(self.node_id().unwrap_or("lib3h"))
+            .chars()
+            .take(MAX_INSTANCE_LABEL_LEN - suffix.len() - 1)
+            .collect();
+        format!("{}-{}.{}", label, suffix, DNS_SD_SERVICE_TYPE)
+    }
+
+    /// Picks another instance name, after someone else took this one.
+    pub fn rename(&mut self) {
+        self.conflicts += 1;
+    }
+
+    /// Converts a [`Record`] to the DNS-SD PTR, SRV and TXT records of its service instance,
+    /// followed by an A or AAAA record if its URL holds an IP address.
+    /// The TXT record carries the network id, node id, transport scheme and the whole URL, and
+    /// the signature and sequence number of the record when signed.
+    pub fn to_dns_sd_records(&self) -> MulticastDnsResult<Vec<AnswerSection>> {
+        let instance = self.instance_name();
+        let scheme = match self.url.find("://") {
+            Some(scheme_len) => &self.url[..scheme_len],
+            None => "",
+        };
+        let url = Url::parse(&self.url).ok();
+        let port = url
+            .as_ref()
+            .and_then(|url| url.port_or_known_default())
+            .unwrap_or(0);
+        let (target, address) = match url.as_ref().and_then(|url| url.host()) {
+            Some(Host::Domain(domain)) => (domain.to_string(), None),
+            Some(Host::Ipv4(ip)) => (local_hostname(), Some(RecordData::A(ip))),
+            Some(Host::Ipv6(ip)) => (local_hostname(), Some(RecordData::Aaaa(ip))),
+            None => (local_hostname(), None),
+        };
+
+        let mut attributes = vec![
+            String::from("txtvers=1"),
+            format!("nid={}", self.networkid),
+            format!("scheme={}", scheme),
+            format!("url={}", self.url),
+        ];
+        if let Some(node_id) = self.node_id() {
+            attributes.push(format!("node={}", node_id));
+        }
+        if let Some(signature) = self.signature() {
+            attributes.push(format!("sig={}", signature));
+            attributes.push(format!("seq={}", self.seq));
+        }
+
+        let mut records = vec![
+            AnswerSection::new_record(
+                DNS_SD_SERVICE_TYPE,
+                RecordData::Ptr(instance.clone()),
+                self.ttl,
+            )?,
+            AnswerSection::new_record(
+                &instance,
+                RecordData::Srv(ServiceLocation {
+                    priority: 0,
+                    weight: 0,
+                    port,
+                    target: target.clone(),
+                }),
+                self.ttl,
+            )?,
+            AnswerSection::new_record(&instance, RecordData::Txt(attributes), self.ttl)?,
+        ];
+        if let Some(address) = address {
+            records.push(AnswerSection::new_record(&target, address, self.ttl)?);
+        }
+
+        Ok(records)
+    }
+
+    /// Builds a [`Record`] from the TXT record of a service instance. Without a `url`
+    /// attribute, as other tools advertise, the URL is made of the `scheme` attribute and the
+    /// SRV and address records of the instance found in `dmesg`, preferring an AAAA record over
+    /// an A one on an IPv6 only stack. IPv6 addresses are bracketed as URLs want them.
+    fn from_dns_sd(
+        instance: &str,
+        attributes: &[String],
+        ttl: u32,
+        dmesg: &DnsMessage,
+        ip_stack: IpStack,
+    ) -> Option<Record> {
+        let attribute = |key: &str| {
+            attributes.iter().find_map(|attribute| {
+                let mut key_value = attribute.splitn(2, '=');
+                if key_value.next() == Some(key) {
+                    key_value.next().map(|value| value.to_string())
+                } else {
+                    None
+                }
+            })
+        };
+
+        let networkid = attribute("nid")?;
+        let url = match attribute("url") {
+            Some(url) => url,
+            None => {
+                let scheme = attribute("scheme")?;
+                let location = dmesg.records().find_map(|rec| match &rec.data {
+                    RecordData::Srv(location) if rec.domain_name == instance => Some(location),
+                    _ => None,
+                })?;
+                let ipv4 = dmesg.records().find_map(|rec| match &rec.data {
+                    RecordData::A(ip) if rec.domain_name == location.target => Some(ip.to_string()),
+                    _ => None,
+                });
+                let ipv6 = dmesg.records().find_map(|rec| match &rec.data {
+                    RecordData::Aaaa(ip) if rec.domain_name == location.target => {
+                        Some(format!("[{}]", ip))
+                    }
+                    _ => None,
+                });
+                let host = if ip_stack.has_ipv4() {
+                    ipv4.or(ipv6)
+                } else {
+                    ipv6.or(ipv4)
+                }
+                .unwrap_or_else(|| location.target.clone());
+                format!("{}://{}:{}", scheme, host, location.port)
+            }
+        };
+
+        Some(Record {
+            networkid,
+            url,
+            ttl,
+            node_id: attribute("node"),
+            conflicts: 0,
+            signature: attribute("sig"),
+            seq: attribute("seq")
+                .and_then(|seq| seq.parse().ok())
+                .unwrap_or(0),
+        })
+    }
+}
+
 impl Ord for Record {
     fn cmp(&self, other: &Self) -> Ordering {
         match self.networkid.cmp(&other.networkid) {
@@ -283,6 +556,30 @@ fn convert_to_mdns_hostname(hostname: &str) -> String {
     }
 }
 
+/// Keeps the characters allowed in a DNS label.
+fn dns_label(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect()
+}
+
+/// Host name of this machine in the `.local` domain, for the SRV records of URLs holding an IP
+/// address.
+fn local_hostname() -> String {
+    let hostname = hostname::get_hostname().unwrap_or_else(|| String::from("Anonymous-host"));
+    let label = dns_label(hostname.split('.').next().unwrap_or_default());
+    let label: String = label.chars().take(MAX_INSTANCE_LABEL_LEN).collect();
+    format!("{}.local", label)
+}
+
+/// 32 bits FNV-1a hash, which unlike the std hasher gives the same instance names across
+/// platforms and releases.
+fn fnv1a(data: &str) -> u32 {
+    data.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
 #[test]
 fn map_record_update_test() {
     let networkid = "hcnmynetworkid.hc-mdns-discovery.holo.host";
@@ -306,3 +603,165 @@ fn map_record_update_test() {
         assert_eq!(dedup_records, &[record_to_keep])
     }
 }
+
+#[test]
+fn map_record_dns_sd_test() {
+    let networkid = "hcnmynetworkid.hc-mdns-discovery.holo.host";
+    let mut record = Record::new(networkid, "wss://192.168.0.88:12345/?a=HcMmynodeid", 255);
+    record.node_id = Some(String::from("HcScmynodeid"));
+    // Not a valid URL, but still carried by the TXT record
+    let unparsable = Record::new(networkid, "wss://192.168.0.87:88088?a=hc0", 255);
+    let map_record = MapRecord::with_record(networkid, &[record.clone(), unparsable.clone()]);
+
+    let dmesg = map_record
+        .to_dns_sd_response_message(&[networkid])
+        .expect("Fail to build DNS-SD response.");
+    let instance = record.instance_name();
+    assert!(instance.starts_with("HcScmynodeid-"));
+    assert!(instance.ends_with("._lib3h._tcp.local"));
+    assert_ne!(instance, unparsable.instance_name());
+    // PTR, SRV and TXT for both, and an A record for the one holding an IP address
+    assert_eq!(dmesg.answers.len(), 7);
+
+    let packet = dmesg
+        .to_raw_dns_sd()
+        .expect("Fail to convert DnsMessage to bytes.");
+    let dmesg_from_raw =
+        DnsMessage::from_raw_dns_sd(&packet).expect("Fail to deserialize DnsMessage from bytes");
+    let map_record_from_dns_sd = MapRecord::from_dns_sd_message(&dmesg_from_raw, IpStack::V4)
+        .expect("Fail to get records from DNS-SD message.");
+
+    let mut records = map_record_from_dns_sd
+        .get(networkid)
+        .expect("Fail to get records from the networkid.")
+        .to_vec();
+    records.sort_by(|a, b| a.url.cmp(&b.url));
+    assert_eq!(records, vec![unparsable, record]);
+}
+
+#[test]
+fn map_record_dns_sd_without_url_test() {
+    let instance = "printer-1._lib3h._tcp.local";
+    let answers = vec![
+        AnswerSection::new_record(
+            instance,
+            RecordData::Srv(ServiceLocation {
+                priority: 0,
+                weight: 0,
+                port: 64159,
+                target: String::from("mistral.local"),
+            }),
+            120,
+        ),
+        AnswerSection::new_record(
+            instance,
+            RecordData::Txt(vec![
+                String::from("nid=mistral-net"),
+                String::from("scheme=wss"),
+            ]),
+            120,
+        ),
+        AnswerSection::new_record(
+            "mistral.local",
+            RecordData::A([192, 168, 0, 77].into()),
+            120,
+        ),
+        AnswerSection::new_record(
+            "mistral.local",
+            RecordData::Aaaa("fd00::77".parse().expect("Fail to parse IPv6 address.")),
+            120,
+        ),
+    ]
+    .into_iter()
+    .collect::<MulticastDnsResult<Vec<AnswerSection>>>()
+    .expect("Fail to build DNS-SD records.");
+    let dmesg = DnsMessage {
+        nb_answers: answers.len() as u16,
+        answers,
+        ..DnsMessage::default()
+    };
+
+    let map_record = MapRecord::from_dns_sd_message(&dmesg, IpStack::Dual)
+        .expect("Fail to get records from DNS-SD message.");
+    assert_eq!(
+        map_record.get("mistral-net"),
+        Some(&vec![Record::new(
+            "mistral-net",
+            "wss://192.168.0.77:64159",
+            120
+        )])
+    );
+
+    // An IPv6 only stack uses the AAAA record instead
+    let map_record = MapRecord::from_dns_sd_message(&dmesg, IpStack::V6)
+        .expect("Fail to get records from DNS-SD message.");
+    assert_eq!(
+        map_record.get("mistral-net"),
+        Some(&vec![Record::new(
+            "mistral-net",
+            "wss://[fd00::77]:64159",
+            120
+        )])
+    );
+}
+
+#[test]
+fn record_ipv6_dns_sd_test() {
+    let networkid = "hcnmynetworkid.hc-mdns-discovery.holo.host";
+    let record = Record::new(networkid, "wss://[fe80::1ff:fe23:4567:890a]:64159", 255);
+
+    let dns_sd_records = record
+        .to_dns_sd_records()
+        .expect("Fail to build DNS-SD records.");
+    let (target, port) = match &dns_sd_records[1].data {
+        RecordData::Srv(location) => (location.target.clone(), location.port),
+        other => panic!("Expected an SRV record, got {:?}", other),
+    };
+    assert_eq!(port, 64159);
+    assert_eq!(dns_sd_records[3].domain_name, target);
+    assert_eq!(
+        dns_sd_records[3].data,
+        RecordData::Aaaa("fe80::1ff:fe23:4567:890a".parse().unwrap())
+    );
+
+    let map_record = MapRecord::with_record(networkid, &[record.clone()]);
+    let dmesg = map_record
+        .to_dns_sd_response_message(&[networkid])
+        .expect("Fail to build DNS-SD response.");
+    let dmesg_from_raw = DnsMessage::from_raw_dns_sd(
+        &dmesg
+            .to_raw_dns_sd()
+            .expect("Fail to convert DnsMessage to bytes."),
+    )
+    .expect("Fail to deserialize DnsMessage from bytes");
+    let map_record_from_dns_sd = MapRecord::from_dns_sd_message(&dmesg_from_raw, IpStack::V6)
+        .expect("Fail to get records from DNS-SD message.");
+    assert_eq!(map_record_from_dns_sd.get(networkid), Some(&vec![record]));
+}
+
+#[test]
+fn map_record_evict_test() {
+    let networkid = "hcnmynetworkid.hc-mdns-discovery.holo.host";
+    let leaving = Record::new(networkid, "wss://1.2.3.4:12345?a=HcMleaving", 255);
+    let staying = Record::new(networkid, "wss://1.2.3.5:12345?a=HcMstaying", 255);
+    let mut map_record = MapRecord::with_record(networkid, &[leaving.clone(), staying.clone()]);
+
+    let goodbyes = MapRecord::with_record(networkid, &[leaving]).to_goodbyes();
+    let goodbye_records = goodbyes.get(networkid).expect("Fail to get goodbyes.");
+    assert_eq!(goodbye_records[0].ttl, 0);
+
+    map_record.evict(goodbye_records);
+    assert_eq!(map_record.get(networkid), Some(&vec![staying]));
+}
+
+#[test]
+fn record_rename_test() {
+    let mut record = Record::new("holonaute.holo.host", "wss://192.168.0.88:64159", 255);
+    let instance = record.instance_name();
+
+    record.rename();
+    let renamed = record.instance_name();
+    assert_ne!(instance, renamed);
+    assert!(renamed.ends_with("-2._lib3h._tcp.local"));
+    assert!(renamed.split('.').next().unwrap().len() <= MAX_INSTANCE_LABEL_LEN);
+}
@@ -0,0 +1,446 @@
+//! Signed records, so that nodes verifying signatures only connect to the URLs the nodes
+//! announcing them vouch for, and not to whoever injected a record for their network id.
+//!
+//! The node id of a record is the hcid encoding of the public signing key of its node, and the
+//! signature is carried hex encoded in the `sig` attribute of its DNS-SD TXT record.
+//!
+//! Signatures cover the TTL of a record and when it was signed, carried in the `seq` attribute,
+//! so a goodbye cannot be forged from an announcement, and a record older than the last one we
+//! verified for the same node and URL is a replay we ignore.
+
+use crate::{
+    error::{MulticastDnsError, MulticastDnsResult},
+    record::{MapRecord, Record},
+    MulticastDns, DEFAULT_TTL,
+};
+use lib3h_crypto_api::{Buffer, CryptoSystem};
+use log::{error, warn};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// hcid kind of node ids.
+const NODE_ID_KIND: &str = "hcm0";
+
+impl MulticastDns {
+    /// Returns wether we discard the answers that are not signed by the node they name.
+    pub fn verify_signatures(&self) -> bool {
+        self.verify_signatures
+    }
+
+    /// Drops the records of `map_record` that are not signed by the node they name, or that
+    /// are older than the last record we verified for the same node and URL, if we verify
+    /// signatures. Goodbyes must be newer than it, so replaying one evicts no one.
+    ///
+    /// We remember the last record of a node and URL until it expires, or for a goodbye, until
+    /// the announcement it ended would have expired.
+    pub(crate) fn retain_verified(&mut self, map_record: &mut MapRecord) {
+        let crypto = match &self.crypto {
+            Some(crypto) if self.verify_signatures => crypto,
+            _ => return,
+        };
+        let verified_seqs = &mut self.verified_seqs;
+        for records in map_record.values_mut() {
+            records.retain(|record| {
+                if !verify_record(&**crypto, record) {
+                    warn!("mDNS: discarding unverified record for '{}'", record.url);
+                    return false;
+                }
+                let key = (
+                    record.node_id().unwrap_or("").to_string(),
+                    record.url.clone(),
+                );
+                let last_seq = verified_seqs.get(&key).map(|(seq, _ttl)| *seq);
+                let fresh = match last_seq {
+                    Some(last_seq) if record.ttl == 0 => record.seq > last_seq,
+                    Some(last_seq) => record.seq >= last_seq,
+                    None => true,
+                };
+                if !fresh {
+                    warn!("mDNS: discarding replayed record for '{}'", record.url);
+                    return false;
+                }
+                let ttl = if record.ttl == 0 {
+                    DEFAULT_TTL
+                } else {
+                    record.ttl
+                };
+                verified_seqs.insert(key, (record.seq, ttl));
+                true
+            });
+        }
+    }
+
+    /// Signs the records of `map_record` if we sign our records.
+    pub(crate) fn sign_map_record(&self, map_record: &mut MapRecord) {
+        if let (Some(crypto), Some(signing_key)) = (&self.crypto, &self.signing_key) {
+            for record in map_record
+                .values_mut()
+                .flat_map(|records| records.iter_mut())
+            {
+                if let Err(e) = sign_record(&**crypto, signing_key, record) {
+                    error!("Fail to sign our record for '{}': '{}'", record.url, e);
+                }
+            }
+        }
+    }
+}
+
+/// Signs `record` with the secret signing key of the node of its node id, stamping it newer
+/// than its previous signature.
+#[allow(clippy::borrowed_box)]
+pub(crate) fn sign_record(
+    crypto: &dyn CryptoSystem,
+    secret_key: &Box<dyn Buffer>,
+    record: &mut Record,
+) -> MulticastDnsResult<()> {
+    if record.node_id.is_none() {
+        return Err(MulticastDnsError::new_other(
+            "Signing records requires a node id.",
+        ));
+    }
+    record.seq = now_ms().max(record.seq + 1);
+    let mut signature: Box<dyn Buffer> = Box::new(vec![0; crypto.sign_bytes()]);
+    crypto
+        .sign(&mut signature, &signed_payload(record), secret_key)
+        .map_err(|e| MulticastDnsError::new_other(&format!("Fail to sign record: {:?}", e)))?;
+    record.signature = Some(to_hex(&signature));
+    Ok(())
+}
+
+/// Checks that `secret_key` is the secret signing key of the node of `node_id`, by verifying a
+/// record signed with it.
+#[allow(clippy::borrowed_box)]
+pub(crate) fn check_signing_key(
+    crypto: &dyn CryptoSystem,
+    secret_key: &Box<dyn Buffer>,
+    node_id: &str,
+) -> MulticastDnsResult<()> {
+    let mut probe = Record::new("", "", DEFAULT_TTL);
+    probe.node_id = Some(node_id.to_owned());
+    sign_record(crypto, secret_key, &mut probe)?;
+    if verify_record(crypto, &probe) {
+        Ok(())
+    } else {
+        Err(MulticastDnsError::new_other(
+            "The signing key is not the one of the node id.",
+        ))
+    }
+}
+
+/// Returns wether `record` carries a valid signature of the node of its node id.
+pub(crate) fn verify_record(crypto: &dyn CryptoSystem, record: &Record) -> bool {
+    let (node_id, signature) = match (record.node_id(), record.signature()) {
+        (Some(node_id), Some(signature)) => (node_id, signature),
+        _ => return false,
+    };
+    let public_key =
+        match hcid::HcidEncoding::with_kind(NODE_ID_KIND).and_then(|hcm0| hcm0.decode(node_id)) {
+            Ok(public_key) => public_key,
+            Err(_) => return false,
+        };
+    let signature = match from_hex(signature) {
+        Some(signature) => signature,
+        None => return false,
+    };
+    if signature.len() != crypto.sign_bytes() || public_key.len() != crypto.sign_public_key_bytes()
+    {
+        return false;
+    }
+
+    let signature: Box<dyn Buffer> = Box::new(signature);
+    let public_key: Box<dyn Buffer> = Box::new(public_key);
+    crypto
+        .sign_verify(&signature, &signed_payload(record), &public_key)
+        .unwrap_or(false)
+}
+
+/// What a record signature covers: its network id, URL, node id, TTL and sequence number.
+fn signed_payload(record: &Record) -> Box<dyn Buffer> {
+    Box::new(
+        format!(
+            "{}\n{}\n{}\n{}\n{}",
+            record.networkid,
+            record.url,
+            record.node_id().unwrap_or(""),
+            record.ttl,
+            record.seq
+        )
+        .into_bytes(),
+    )
+}
+
+/// Milliseconds since the epoch, the sequence number of the records we sign.
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MulticastDnsBuilder;
+    use lib3h_protocol::discovery::Discovery;
+    use lib3h_sodium::SodiumCryptoSystem;
+
+    const NETWORK_ID: &str = "holonaute-signature.holo.host";
+
+    /// A node id and its secret signing key.
+    fn node_keys(crypto: &dyn CryptoSystem) -> (String, Box<dyn Buffer>) {
+        let mut public_key: Box<dyn Buffer> = Box::new(vec![0; crypto.sign_public_key_bytes()]);
+        let mut secret_key = crypto.buf_new_secure(crypto.sign_secret_key_bytes());
+        crypto
+            .sign_keypair(&mut public_key, &mut secret_key)
+            .expect("Fail to generate signing keypair.");
+        let node_id = hcid::HcidEncoding::with_kind(NODE_ID_KIND)
+            .and_then(|hcm0| hcm0.encode(&public_key))
+            .expect("Fail to encode node id.");
+        (node_id, secret_key)
+    }
+
+    fn signed_record(crypto: &dyn CryptoSystem, url: &str) -> Record {
+        let (node_id, secret_key) = node_keys(crypto);
+        let mut record = Record::new(NETWORK_ID, url, 255);
+        record.node_id = Some(node_id);
+        sign_record(crypto, &secret_key, &mut record).expect("Fail to sign record.");
+        record
+    }
+
+    #[test]
+    fn it_should_verify_signed_records() {
+        let crypto = SodiumCryptoSystem::new();
+        let record = signed_record(&crypto, "wss://192.168.0.88:64159?a=hc0");
+        assert!(verify_record(&crypto, &record));
+
+        // Someone forging a goodbye from our announcement
+        let forged_goodbye = Record {
+            ttl: 0,
+            ..record.clone()
+        };
+        assert!(!verify_record(&crypto, &forged_goodbye));
+
+        // Someone passing an old record for a newer one
+        let forged_seq = Record {
+            seq: record.seq + 1,
+            ..record.clone()
+        };
+        assert!(!verify_record(&crypto, &forged_seq));
+
+        // Someone pointing our node id to their URL
+        let spoofed = Record {
+            url: String::from("wss://192.168.0.66:64159?a=hc0"),
+            ..record.clone()
+        };
+        assert!(!verify_record(&crypto, &spoofed));
+
+        // Someone signing with another key than the one of the node id
+        let (other_node_id, _) = node_keys(&crypto);
+        let impersonated = Record {
+            node_id: Some(other_node_id),
+            ..record.clone()
+        };
+        assert!(!verify_record(&crypto, &impersonated));
+
+        let unsigned = Record {
+            signature: None,
+            ..record
+        };
+        assert!(!verify_record(&crypto, &unsigned));
+
+        // We cannot sign without a node id
+        let (_, secret_key) = node_keys(&crypto);
+        let mut anonymous = Record::new(NETWORK_ID, "wss://192.168.0.88:64159?a=hc0", 255);
+        assert!(sign_record(&crypto, &secret_key, &mut anonymous).is_err());
+    }
+
+    #[test]
+    fn it_should_refuse_a_signing_key_of_another_node() {
+        let crypto = SodiumCryptoSystem::new();
+        let (node_id, secret_key) = node_keys(&crypto);
+        let (other_node_id, _) = node_keys(&crypto);
+        assert!(check_signing_key(&crypto, &secret_key, &node_id).is_ok());
+        assert!(check_signing_key(&crypto, &secret_key, &other_node_id).is_err());
+
+        let built = MulticastDnsBuilder::new()
+            .own_record(NETWORK_ID, &["wss://192.168.0.88:64159?a=hc0"])
+            .node_id(&other_node_id)
+            .sign_records(crypto.box_clone(), secret_key.box_clone())
+            .multicast_address("224.0.0.242")
+            .bind_port(57242)
+            .build();
+        assert!(built.is_err());
+
+        // Nor can we sign without a node id
+        let built = MulticastDnsBuilder::new()
+            .own_record(NETWORK_ID, &["wss://192.168.0.88:64159?a=hc0"])
+            .sign_records(crypto.box_clone(), secret_key)
+            .multicast_address("224.0.0.242")
+            .bind_port(57242)
+            .build();
+        assert!(built.is_err());
+    }
+
+    #[test]
+    fn it_should_discard_unverified_answers() {
+        let crypto = SodiumCryptoSystem::new();
+        let (node_id, secret_key) = node_keys(&crypto);
+        let mut mdns = MulticastDnsBuilder::new()
+            .own_record(NETWORK_ID, &["wss://192.168.0.88:64159?a=hc0"])
+            .node_id(&node_id)
+            .sign_records(crypto.box_clone(), secret_key)
+            .verify_signatures(crypto.box_clone())
+            .multicast_address("224.0.0.245")
+            .bind_port(57245)
+            .build()
+            .expect("Fail to build mDNS.");
+        assert!(mdns.verify_signatures());
+
+        // Our own records are signed, and the signature goes through the TXT record
+        let own = mdns.own_map_record.clone();
+        let dmesg = own
+            .to_dns_sd_response_message(&[NETWORK_ID])
+            .expect("Fail to build our response.");
+        let mut answers = MapRecord::from_dns_sd_message(&dmesg, mdns.ip_stack())
+            .expect("Fail to get records from DNS-SD message.");
+        mdns.retain_verified(&mut answers);
+        assert_eq!(answers.get(NETWORK_ID), own.get(NETWORK_ID));
+
+        let genuine = signed_record(&crypto, "wss://192.168.0.77:64159?a=hc1");
+        let spoofed = Record {
+            url: String::from("wss://192.168.0.66:64159?a=hc1"),
+            ..genuine.clone()
+        };
+        let unsigned = Record::new(NETWORK_ID, "wss://192.168.0.55:64159?a=hc2", 255);
+        let mut answers =
+            MapRecord::with_record(NETWORK_ID, &[genuine.clone(), spoofed, unsigned.clone()]);
+        mdns.retain_verified(&mut answers);
+        assert_eq!(answers.get(NETWORK_ID), Some(&vec![genuine.clone()]));
+
+        // Nothing is discarded without verification
+        let mut trusting = MulticastDnsBuilder::new()
+            .own_record(NETWORK_ID, &["wss://192.168.0.88:64159?a=hc0"])
+            .multicast_address("224.0.0.244")
+            .bind_port(57244)
+            .build()
+            .expect("Fail to build mDNS.");
+        let mut answers = MapRecord::with_record(NETWORK_ID, &[genuine, unsigned]);
+        trusting.retain_verified(&mut answers);
+        assert_eq!(
+            answers.get(NETWORK_ID).map(|records| records.len()),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn it_should_ignore_replayed_goodbyes() {
+        let crypto = SodiumCryptoSystem::new();
+        let mut mdns = MulticastDnsBuilder::new()
+            .own_record(NETWORK_ID, &["wss://192.168.0.88:64159?a=hc0"])
+            .verify_signatures(crypto.box_clone())
+            .multicast_address("224.0.0.243")
+            .bind_port(57243)
+            .build()
+            .expect("Fail to build mDNS.");
+
+        let (node_id, secret_key) = node_keys(&crypto);
+        let mut announce = Record::new(NETWORK_ID, "wss://192.168.0.77:64159?a=hc1", 255);
+        announce.node_id = Some(node_id);
+        sign_record(&crypto, &secret_key, &mut announce).expect("Fail to sign record.");
+        let sign_goodbye = |record: &Record| {
+            let mut goodbye = Record {
+                ttl: 0,
+                ..record.clone()
+            };
+            sign_record(&crypto, &secret_key, &mut goodbye).expect("Fail to sign goodbye.");
+            goodbye
+        };
+        let cached = |mdns: &MulticastDns| {
+            mdns.records()
+                .get(NETWORK_ID)
+                .map(|records| records.len())
+                .unwrap_or(0)
+        };
+
+        mdns.cache_answers(MapRecord::with_record(NETWORK_ID, &[announce.clone()]));
+        assert_eq!(cached(&mdns), 1);
+
+        // A goodbye forged from the announcement does not evict the node
+        let forged_goodbye = Record {
+            ttl: 0,
+            ..announce.clone()
+        };
+        mdns.cache_answers(MapRecord::with_record(NETWORK_ID, &[forged_goodbye]));
+        assert_eq!(cached(&mdns), 1);
+
+        // The node leaves, then comes back
+        let goodbye = sign_goodbye(&announce);
+        mdns.cache_answers(MapRecord::with_record(NETWORK_ID, &[goodbye.clone()]));
+        assert_eq!(cached(&mdns), 0);
+        let mut back = Record {
+            seq: goodbye.seq,
+            ..announce.clone()
+        };
+        sign_record(&crypto, &secret_key, &mut back).expect("Fail to sign record.");
+        mdns.cache_answers(MapRecord::with_record(NETWORK_ID, &[back.clone()]));
+        assert_eq!(cached(&mdns), 1);
+
+        // Replaying its goodbye does not evict it again, nor does its old announcement come back
+        mdns.cache_answers(MapRecord::with_record(NETWORK_ID, &[goodbye]));
+        assert_eq!(cached(&mdns), 1);
+        mdns.flush().expect("Fail to flush.");
+        mdns.cache_answers(MapRecord::with_record(NETWORK_ID, &[announce]));
+        assert_eq!(cached(&mdns), 0);
+
+        // Its announcements can be repeated, and its next goodbye is honored
+        mdns.cache_answers(MapRecord::with_record(NETWORK_ID, &[back.clone()]));
+        assert_eq!(cached(&mdns), 1);
+        mdns.cache_answers(MapRecord::with_record(NETWORK_ID, &[sign_goodbye(&back)]));
+        assert_eq!(cached(&mdns), 0);
+    }
+
+    #[test]
+    fn it_should_forget_expired_sequence_numbers() {
+        let crypto = SodiumCryptoSystem::new();
+        let mut mdns = MulticastDnsBuilder::new()
+            .own_record(NETWORK_ID, &["wss://192.168.0.88:64159?a=hc0"])
+            .verify_signatures(crypto.box_clone())
+            .multicast_address("224.0.0.241")
+            .bind_port(57241)
+            .build()
+            .expect("Fail to build mDNS.");
+
+        let (node_id, secret_key) = node_keys(&crypto);
+        let mut record = Record::new(NETWORK_ID, "wss://192.168.0.77:64159?a=hc1", 2);
+        record.node_id = Some(node_id);
+        sign_record(&crypto, &secret_key, &mut record).expect("Fail to sign record.");
+        mdns.cache_answers(MapRecord::with_record(NETWORK_ID, &[record]));
+        assert_eq!(mdns.verified_seqs.len(), 1);
+
+        mdns.update_ttl();
+        mdns.prune_cache();
+        assert_eq!(mdns.verified_seqs.len(), 1);
+
+        // The record expires, and so does what we know of it
+        mdns.update_ttl();
+        mdns.prune_cache();
+        assert!(mdns
+            .records()
+            .get(NETWORK_ID)
+            .map_or(true, |r| r.is_empty()));
+        assert!(mdns.verified_seqs.is_empty());
+    }
+}
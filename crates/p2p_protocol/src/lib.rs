@@ -20,4 +20,6 @@ mod p2p_capnp;
 mod transit_encoding_capnp;
 
 pub mod error;
+pub mod multiplex;
 pub mod p2p;
+pub mod transit_encoding;
@@ -0,0 +1,319 @@
+//! Rust representations of the `multiplex.capnp` channel messages.
+//!
+//! Once two nodes are connected, many (space, to agent, from agent) triples
+//! talk over the same connection. Rather than repeating these ids in every
+//! message, the sender establishes a symbolic `channel_id` for each triple
+//! with `MsgChannelCreate`, and later messages only carry that id.
+
+use crate::{error::P2pResult, multiplex_capnp};
+
+/// `channel_id` of a `MsgError` that is not related to a specific channel
+pub const NO_CHANNEL_ID: u32 = 0xffff_ffff;
+
+/// well-known error codes for `MultiplexMessage::MsgError`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MultiplexErrorCode {
+    /// default if error is not well-known, or if remote is using a newer proto
+    Unknown,
+    /// usually, a message was sent without first sending `MsgChannelCreate`
+    BadChannelId,
+    /// this node is not a part of this space
+    BadSpaceHash,
+    /// this node does not have an agent matching this id
+    BadToId,
+    /// this node does not wish to accept messages from this remote id
+    BadFromId,
+}
+
+/// indicates an error
+/// if the error is not recoverable, the connection will be closed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgMultiplexError {
+    /// the channel this error relates to, or `NO_CHANNEL_ID`
+    pub channel_id: u32,
+    /// code indicating if error is well-known
+    pub error_code: MultiplexErrorCode,
+    /// text indicating details of error
+    pub error_text: String,
+}
+
+/// establish a new multiplexing channel
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgChannelCreate {
+    /// the channel id to establish. Must be unique to this communication session.
+    pub channel_id: u32,
+    /// the space to establish this channel for
+    pub space_hash: Vec<u8>,
+    /// the destination agent id to establish this channel for
+    pub to_id: Vec<u8>,
+    /// the source agent id to establish this channel for
+    pub from_id: Vec<u8>,
+}
+
+/// a message associated with a previously created channel
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgChannelMessage {
+    /// the previously created channel (see `MsgChannelCreate`)
+    pub channel_id: u32,
+    /// the content of the message
+    pub content: Vec<u8>,
+}
+
+/// one piece of a payload too large to be sent in a single message
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgTransferChunk {
+    /// chosen by the sender. Must be unique to this communication session.
+    pub transfer_id: u32,
+    /// where this chunk's content starts within the full payload
+    pub offset: u64,
+    /// the size of the full payload
+    pub total_size: u64,
+    /// the bytes of this chunk
+    pub content: Vec<u8>,
+}
+
+/// abandon a chunked transfer, from either end
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgTransferCancel {
+    /// the transfer to abandon
+    pub transfer_id: u32,
+    /// true if the node canceling is the one sending the payload
+    pub from_sender: bool,
+}
+
+/// enum holding all the multiplex message types
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MultiplexMessage {
+    MsgError(MsgMultiplexError),
+    MsgChannelCreate(MsgChannelCreate),
+    MsgChannelClose(u32),
+    MsgChannelMessage(MsgChannelMessage),
+    MsgRelayRequest,
+    MsgRelayAccept,
+    MsgTransferChunk(MsgTransferChunk),
+    MsgTransferCancel(MsgTransferCancel),
+}
+
+impl MultiplexMessage {
+    /// create a new error message
+    pub fn create_error(channel_id: u32, error_code: MultiplexErrorCode, error_text: &str) -> Self {
+        MultiplexMessage::MsgError(MsgMultiplexError {
+            channel_id,
+            error_code,
+            error_text: error_text.to_string(),
+        })
+    }
+
+    /// parse raw bytes into a MultiplexMessage enum instance
+    pub fn from_bytes(bytes: &[u8]) -> P2pResult<Self> {
+        let message = capnp::serialize_packed::read_message(
+            &mut std::io::Cursor::new(bytes),
+            capnp::message::ReaderOptions::new(),
+        )?;
+
+        let message = message.get_root::<multiplex_capnp::multiplex_message::Reader>()?;
+
+        use multiplex_capnp::multiplex_message::{msg_error::ErrorCode, Which};
+        match message.which() {
+            Ok(Which::MsgError(Ok(error))) => Ok(MultiplexMessage::MsgError(MsgMultiplexError {
+                channel_id: error.get_channel_id(),
+                // newer protos may send codes we do not know about
+                error_code: match error.get_error_code() {
+                    Ok(ErrorCode::BadChannelId) => MultiplexErrorCode::BadChannelId,
+                    Ok(ErrorCode::BadSpaceHash) => MultiplexErrorCode::BadSpaceHash,
+                    Ok(ErrorCode::BadToId) => MultiplexErrorCode::BadToId,
+                    Ok(ErrorCode::BadFromId) => MultiplexErrorCode::BadFromId,
+                    Ok(ErrorCode::Unknown) | Err(_) => MultiplexErrorCode::Unknown,
+                },
+                error_text: error.get_error_text()?.to_string(),
+            })),
+            Ok(Which::MsgChannelCreate(Ok(create))) => {
+                Ok(MultiplexMessage::MsgChannelCreate(MsgChannelCreate {
+                    channel_id: create.get_channel_id(),
+                    space_hash: create.get_space_hash()?.to_vec(),
+                    to_id: create.get_to_id()?.to_vec(),
+                    from_id: create.get_from_id()?.to_vec(),
+                }))
+            }
+            Ok(Which::MsgChannelClose(channel_id)) => {
+                Ok(MultiplexMessage::MsgChannelClose(channel_id))
+            }
+            Ok(Which::MsgChannelMessage(Ok(message))) => {
+                Ok(MultiplexMessage::MsgChannelMessage(MsgChannelMessage {
+                    channel_id: message.get_channel_id(),
+                    content: message.get_content()?.to_vec(),
+                }))
+            }
+            Ok(Which::MsgRelayRequest(())) => Ok(MultiplexMessage::MsgRelayRequest),
+            Ok(Which::MsgRelayAccept(())) => Ok(MultiplexMessage::MsgRelayAccept),
+            Ok(Which::MsgTransferChunk(Ok(chunk))) => {
+                Ok(MultiplexMessage::MsgTransferChunk(MsgTransferChunk {
+                    transfer_id: chunk.get_transfer_id(),
+                    offset: chunk.get_offset(),
+                    total_size: chunk.get_total_size(),
+                    content: chunk.get_content()?.to_vec(),
+                }))
+            }
+            Ok(Which::MsgTransferCancel(Ok(cancel))) => {
+                Ok(MultiplexMessage::MsgTransferCancel(MsgTransferCancel {
+                    transfer_id: cancel.get_transfer_id(),
+                    from_sender: cancel.get_from_sender(),
+                }))
+            }
+            _ => Err("failed to decode".into()),
+        }
+    }
+
+    /// generate encoded bytes for this MultiplexMessage enum instance
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut message = capnp::message::Builder::new_default();
+        {
+            use multiplex_capnp::multiplex_message::msg_error::ErrorCode;
+            let mut root = message.init_root::<multiplex_capnp::multiplex_message::Builder>();
+            match self {
+                MultiplexMessage::MsgError(error) => {
+                    let mut message = root.init_msg_error();
+
+                    message.set_channel_id(error.channel_id);
+                    message.set_error_code(match error.error_code {
+                        MultiplexErrorCode::Unknown => ErrorCode::Unknown,
+                        MultiplexErrorCode::BadChannelId => ErrorCode::BadChannelId,
+                        MultiplexErrorCode::BadSpaceHash => ErrorCode::BadSpaceHash,
+                        MultiplexErrorCode::BadToId => ErrorCode::BadToId,
+                        MultiplexErrorCode::BadFromId => ErrorCode::BadFromId,
+                    });
+                    message.set_error_text(&error.error_text);
+                }
+                MultiplexMessage::MsgChannelCreate(create) => {
+                    let mut message = root.init_msg_channel_create();
+
+                    message.set_channel_id(create.channel_id);
+                    message.set_space_hash(&create.space_hash);
+                    message.set_to_id(&create.to_id);
+                    message.set_from_id(&create.from_id);
+                }
+                MultiplexMessage::MsgChannelClose(channel_id) => {
+                    root.set_msg_channel_close(*channel_id);
+                }
+                MultiplexMessage::MsgChannelMessage(channel_message) => {
+                    let mut message = root.init_msg_channel_message();
+
+                    message.set_channel_id(channel_message.channel_id);
+                    message.set_content(&channel_message.content);
+                }
+                MultiplexMessage::MsgRelayRequest => {
+                    root.set_msg_relay_request(());
+                }
+                MultiplexMessage::MsgRelayAccept => {
+                    root.set_msg_relay_accept(());
+                }
+                MultiplexMessage::MsgTransferChunk(chunk) => {
+                    let mut message = root.init_msg_transfer_chunk();
+
+                    message.set_transfer_id(chunk.transfer_id);
+                    message.set_offset(chunk.offset);
+                    message.set_total_size(chunk.total_size);
+                    message.set_content(&chunk.content);
+                }
+                MultiplexMessage::MsgTransferCancel(cancel) => {
+                    let mut message = root.init_msg_transfer_cancel();
+
+                    message.set_transfer_id(cancel.transfer_id);
+                    message.set_from_sender(cancel.from_sender);
+                }
+            }
+        }
+        let mut bytes = Vec::new();
+        capnp::serialize_packed::write_message(&mut bytes, &message).unwrap();
+        bytes
+    }
+
+    /// convert this MultiplexMessage enum instance into encoded bytes
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.to_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(message: MultiplexMessage) {
+        let bytes = message.to_bytes();
+        assert_eq!(message, MultiplexMessage::from_bytes(&bytes).unwrap());
+    }
+
+    #[test]
+    fn it_can_encode_decode_error() {
+        for error_code in vec![
+            MultiplexErrorCode::Unknown,
+            MultiplexErrorCode::BadChannelId,
+            MultiplexErrorCode::BadSpaceHash,
+            MultiplexErrorCode::BadToId,
+            MultiplexErrorCode::BadFromId,
+        ] {
+            round_trip(MultiplexMessage::create_error(42, error_code, "test"));
+        }
+        round_trip(MultiplexMessage::create_error(
+            NO_CHANNEL_ID,
+            MultiplexErrorCode::Unknown,
+            "not channel related",
+        ));
+    }
+
+    #[test]
+    fn it_can_encode_decode_channel_messages() {
+        round_trip(MultiplexMessage::MsgChannelCreate(MsgChannelCreate {
+            channel_id: 1,
+            space_hash: b"space_1".to_vec(),
+            to_id: b"agent_1".to_vec(),
+            from_id: b"agent_2".to_vec(),
+        }));
+        round_trip(MultiplexMessage::MsgChannelMessage(MsgChannelMessage {
+            channel_id: 1,
+            content: b"hello".to_vec(),
+        }));
+        round_trip(MultiplexMessage::MsgChannelClose(1));
+    }
+
+    #[test]
+    fn it_can_encode_decode_relay_negotiation() {
+        round_trip(MultiplexMessage::MsgRelayRequest);
+        round_trip(MultiplexMessage::MsgRelayAccept);
+    }
+
+    #[test]
+    fn it_can_encode_decode_transfers() {
+        round_trip(MultiplexMessage::MsgTransferChunk(MsgTransferChunk {
+            transfer_id: 7,
+            offset: 1 << 33,
+            total_size: (1 << 33) + 5,
+            content: b"chunk".to_vec(),
+        }));
+        round_trip(MultiplexMessage::MsgTransferCancel(MsgTransferCancel {
+            transfer_id: 7,
+            from_sender: true,
+        }));
+        round_trip(MultiplexMessage::MsgTransferCancel(MsgTransferCancel {
+            transfer_id: 7,
+            from_sender: false,
+        }));
+    }
+
+    #[test]
+    fn it_should_only_carry_the_channel_id() {
+        let create = MultiplexMessage::MsgChannelCreate(MsgChannelCreate {
+            channel_id: 1,
+            space_hash: vec![0xab; 64],
+            to_id: vec![0xcd; 64],
+            from_id: vec![0xef; 64],
+        })
+        .into_bytes();
+        let message = MultiplexMessage::MsgChannelMessage(MsgChannelMessage {
+            channel_id: 1,
+            content: vec![],
+        })
+        .into_bytes();
+        assert!(message.len() * 8 < create.len());
+    }
+}
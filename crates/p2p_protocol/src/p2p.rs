@@ -1,10 +1,157 @@
 use crate::{error::P2pResult, p2p_capnp};
 
+/// the p2p protocol version spoken by this crate,
+/// exchanged in `MsgPing` / `MsgPong`.
+/// Nodes that predate version negotiation report 0, and still expect
+/// the legacy MessagePack envelope.
+pub const P2P_PROTOCOL_VERSION: u32 = 4;
+
+/// first version carrying messages as capnp `P2pMessage`s
+pub const P2P_PROTOCOL_VERSION_CAPNP: u32 = 1;
+
+/// first version speaking the transit encoding handshake
+/// (see `transit_encoding`) between nodes
+pub const P2P_PROTOCOL_VERSION_TRANSIT_ENCODING: u32 = 2;
+
+/// first version carrying space traffic over multiplex channels
+/// (see `multiplex`) instead of fully addressed direct messages
+pub const P2P_PROTOCOL_VERSION_MULTIPLEX: u32 = 3;
+
+/// first version able to reassemble large payloads sent as
+/// `MsgTransferChunk`s (see `multiplex`)
+pub const P2P_PROTOCOL_VERSION_CHUNKING: u32 = 4;
+
+/// well-known error codes for `MsgError`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MsgErrorCode {
+    /// default if error is not well-known, or if remote is using a newer proto
+    Unknown,
+}
+
+/// indicates an error
+/// if the error is not recoverable, the connection will be closed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgError {
+    /// code indicating if error is well-known
+    pub error_code: MsgErrorCode,
+    /// text indicating details of error
+    pub error_text: String,
+}
+
+/// on a new connection, tell the remote node about ourselves
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgHandshake {
+    /// the storage arc radius of the source transport / agent
+    pub store_arc_radius: u32,
+    /// the query arc radius of the source transport / agent
+    pub query_arc_radius: u32,
+}
+
+/// a list of aspect hashes associated with an entry address
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AspectHashList {
+    /// the entry address these aspect hashes belong to
+    pub entry_address: Vec<u8>,
+    /// the aspect hashes, probably not comprehensive:
+    /// only those that fall within constraints / are being requested / etc.
+    pub aspect_hash_list: Vec<Vec<u8>>,
+}
+
+/// a list of aspect data associated with an entry address
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AspectDataList {
+    /// the entry address this aspect data belongs to
+    pub entry_address: Vec<u8>,
+    /// the aspect data corresponding to requested / published aspect hashes
+    pub aspect_data_list: Vec<Vec<u8>>,
+}
+
+/// constrain aspects by rrdht location arc
+/// `arc_start` is inclusive, `arc_end` is exclusive.
+/// If they are equal, the constraint covers the full circle,
+/// unless `arc_empty` is set.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AspectConstraintArc {
+    pub arc_start: u32,
+    pub arc_end: u32,
+    pub arc_empty: bool,
+}
+
+/// constrain aspects by publish timestamp
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AspectConstraintTime {
+    /// aspects must have a publish time >= this value
+    pub gte_epoch_ms: u64,
+}
+
+/// constrain aspects by local store count
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AspectConstraintCount {
+    /// aspects must have a local index count >= this value
+    pub gte_local_count: u64,
+}
+
+/// data for `MsgGspArcRequest` and `MsgGspArcResponse`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgGspArc {
+    pub aspect_constraint_arc: AspectConstraintArc,
+    pub aspect_constraint_time: AspectConstraintTime,
+    pub aspect_constraint_count: AspectConstraintCount,
+    /// aspect hashes of entries that fall within all the above constraints
+    pub aspect_hash_list: Vec<AspectHashList>,
+}
+
+/// request the data for a list of aspect hashes
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgGspAspectDataRequest {
+    pub aspect_hash_list: Vec<AspectHashList>,
+}
+
+/// respond to an aspect data request with aspect data
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgGspAspectDataResponse {
+    pub aspect_data_list: Vec<AspectDataList>,
+}
+
+/// fast push newly published aspect data
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgGspAspectBroadcast {
+    pub aspect_data_list: Vec<AspectDataList>,
+}
+
+/// node-to-node message data
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgDirect {
+    /// for associating requests / responses
+    pub request_id: String,
+    /// the content of the direct message
+    pub data: Vec<u8>,
+    /// the space this message is sent within
+    pub space_address: String,
+    /// the agent this message is addressed to
+    pub to_agent_id: String,
+    /// the agent this message was sent by
+    pub from_agent_id: String,
+}
+
+/// dht query message data
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgQuery {
+    /// for associating requests / responses
+    pub request_id: String,
+    /// the entry address being queried
+    pub entry_address: Vec<u8>,
+    /// the message content (either request or response)
+    pub data: Vec<u8>,
+}
+
 /// a message used to verify connectivity with a remote node
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct MsgPing {
     /// set the milliseconds since unix epoch when sending this message
     pub send_epoch_ms: u64,
+    /// the p2p protocol version spoken by the sender
+    pub protocol_version: u32,
 }
 
 /// a response to a ping message
@@ -15,14 +162,74 @@ pub struct MsgPong {
     /// set our own machine milliseconds since unix epoch here
     /// can be used for heuristics about relative clock skew
     pub ping_received_epoch_ms: u64,
+    /// the p2p protocol version spoken by the responder
+    pub protocol_version: u32,
+}
+
+/// notify a newly connected node of our identity in a specific gateway
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgPeerName {
+    /// the gateway (network or space) this name applies to
+    pub gateway_id: String,
+    /// our peer name (uri) within that gateway
+    pub peer_name: String,
+    /// milliseconds since unix epoch of our peer record
+    pub timestamp: u64,
+}
+
+/// a peer joined a space
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgJoinSpace {
+    /// the space that was joined
+    pub space_address: String,
+    /// the peer name (agent uri) within that space
+    pub peer_name: String,
+    /// the low-level transport uri of the peer
+    pub peer_location: String,
+    /// milliseconds since unix epoch of the peer record
+    pub timestamp: u64,
+}
+
+/// all the spaces a node has joined
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgAllJoinedSpaceList {
+    pub joined_space_list: Vec<MsgJoinSpace>,
+}
+
+/// dht gossip within a space
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgGossip {
+    /// the space this gossip belongs to
+    pub space_address: String,
+    /// the peer this gossip is addressed to
+    pub to_peer_name: String,
+    /// the peer this gossip was sent by
+    pub from_peer_name: String,
+    /// the opaque dht gossip bundle
+    pub bundle: Vec<u8>,
 }
 
 /// an enum representing the various p2p message types that can be sent
 /// between lib3h nodes
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub enum P2pMessage {
+    MsgError(MsgError),
+    MsgHandshake(MsgHandshake),
+    MsgGspArcRequest(MsgGspArc),
+    MsgGspArcResponse(MsgGspArc),
+    MsgGspAspectDataRequest(MsgGspAspectDataRequest),
+    MsgGspAspectDataResponse(MsgGspAspectDataResponse),
+    MsgGspAspectBroadcast(MsgGspAspectBroadcast),
+    MsgDirectRequest(MsgDirect),
+    MsgDirectResponse(MsgDirect),
+    MsgQueryRequest(MsgQuery),
+    MsgQueryResponse(MsgQuery),
     MsgPing(MsgPing),
     MsgPong(MsgPong),
+    MsgPeerName(MsgPeerName),
+    MsgJoinSpace(MsgJoinSpace),
+    MsgAllJoinedSpaceList(MsgAllJoinedSpaceList),
+    MsgGossip(MsgGossip),
 }
 
 /// get the current system milliseconds since unix epoch
@@ -33,12 +240,171 @@ fn now_ms() -> u64 {
     out.as_secs() * 1000 + u64::from(out.subsec_nanos()) / 1_000_000
 }
 
+// -- capnp reader helpers -- //
+
+fn read_aspect_hash_list(
+    list: capnp::struct_list::Reader<p2p_capnp::p2p_message::aspect_hash_list::Owned>,
+) -> P2pResult<Vec<AspectHashList>> {
+    let mut out = Vec::new();
+    for item in list.iter() {
+        let mut aspect_hash_list = Vec::new();
+        for aspect_hash in item.get_aspect_hash_list()?.iter() {
+            aspect_hash_list.push(aspect_hash?.to_vec());
+        }
+        out.push(AspectHashList {
+            entry_address: item.get_entry_address()?.to_vec(),
+            aspect_hash_list,
+        });
+    }
+    Ok(out)
+}
+
+fn read_aspect_data_list(
+    list: capnp::struct_list::Reader<p2p_capnp::p2p_message::aspect_data_list::Owned>,
+) -> P2pResult<Vec<AspectDataList>> {
+    let mut out = Vec::new();
+    for item in list.iter() {
+        let mut aspect_data_list = Vec::new();
+        for aspect_data in item.get_aspect_data_list()?.iter() {
+            aspect_data_list.push(aspect_data?.to_vec());
+        }
+        out.push(AspectDataList {
+            entry_address: item.get_entry_address()?.to_vec(),
+            aspect_data_list,
+        });
+    }
+    Ok(out)
+}
+
+fn read_gsp_arc(gsp: p2p_capnp::p2p_message::msg_gsp_arc::Reader) -> P2pResult<MsgGspArc> {
+    let arc = gsp.get_aspect_constraint_arc()?;
+    Ok(MsgGspArc {
+        aspect_constraint_arc: AspectConstraintArc {
+            arc_start: arc.get_arc_start(),
+            arc_end: arc.get_arc_end(),
+            arc_empty: arc.get_arc_empty(),
+        },
+        aspect_constraint_time: AspectConstraintTime {
+            gte_epoch_ms: gsp.get_aspect_constraint_time()?.get_gte_epoch_ms(),
+        },
+        aspect_constraint_count: AspectConstraintCount {
+            gte_local_count: gsp.get_aspect_constraint_count()?.get_gte_local_count(),
+        },
+        aspect_hash_list: read_aspect_hash_list(gsp.get_aspect_hash_list()?)?,
+    })
+}
+
+fn read_direct(direct: p2p_capnp::p2p_message::msg_direct::Reader) -> P2pResult<MsgDirect> {
+    Ok(MsgDirect {
+        request_id: direct.get_request_id()?.to_string(),
+        data: direct.get_data()?.to_vec(),
+        space_address: direct.get_space_address()?.to_string(),
+        to_agent_id: direct.get_to_agent_id()?.to_string(),
+        from_agent_id: direct.get_from_agent_id()?.to_string(),
+    })
+}
+
+fn read_query(query: p2p_capnp::p2p_message::msg_query::Reader) -> P2pResult<MsgQuery> {
+    Ok(MsgQuery {
+        request_id: query.get_request_id()?.to_string(),
+        entry_address: query.get_entry_address()?.to_vec(),
+        data: query.get_data()?.to_vec(),
+    })
+}
+
+fn read_join_space(
+    join_space: p2p_capnp::p2p_message::msg_join_space::Reader,
+) -> P2pResult<MsgJoinSpace> {
+    Ok(MsgJoinSpace {
+        space_address: join_space.get_space_address()?.to_string(),
+        peer_name: join_space.get_peer_name()?.to_string(),
+        peer_location: join_space.get_peer_location()?.to_string(),
+        timestamp: join_space.get_timestamp(),
+    })
+}
+
+// -- capnp builder helpers -- //
+
+fn write_aspect_hash_list(
+    mut list: capnp::struct_list::Builder<p2p_capnp::p2p_message::aspect_hash_list::Owned>,
+    aspect_hash_list: &[AspectHashList],
+) {
+    for (i, item) in aspect_hash_list.iter().enumerate() {
+        let mut builder = list.reborrow().get(i as u32);
+        builder.set_entry_address(&item.entry_address);
+        let mut hashes = builder.init_aspect_hash_list(item.aspect_hash_list.len() as u32);
+        for (j, aspect_hash) in item.aspect_hash_list.iter().enumerate() {
+            hashes.set(j as u32, aspect_hash);
+        }
+    }
+}
+
+fn write_aspect_data_list(
+    mut list: capnp::struct_list::Builder<p2p_capnp::p2p_message::aspect_data_list::Owned>,
+    aspect_data_list: &[AspectDataList],
+) {
+    for (i, item) in aspect_data_list.iter().enumerate() {
+        let mut builder = list.reborrow().get(i as u32);
+        builder.set_entry_address(&item.entry_address);
+        let mut data = builder.init_aspect_data_list(item.aspect_data_list.len() as u32);
+        for (j, aspect_data) in item.aspect_data_list.iter().enumerate() {
+            data.set(j as u32, aspect_data);
+        }
+    }
+}
+
+fn write_gsp_arc(mut builder: p2p_capnp::p2p_message::msg_gsp_arc::Builder, gsp: &MsgGspArc) {
+    {
+        let mut arc = builder.reborrow().init_aspect_constraint_arc();
+        arc.set_arc_start(gsp.aspect_constraint_arc.arc_start);
+        arc.set_arc_end(gsp.aspect_constraint_arc.arc_end);
+        arc.set_arc_empty(gsp.aspect_constraint_arc.arc_empty);
+    }
+    builder
+        .reborrow()
+        .init_aspect_constraint_time()
+        .set_gte_epoch_ms(gsp.aspect_constraint_time.gte_epoch_ms);
+    builder
+        .reborrow()
+        .init_aspect_constraint_count()
+        .set_gte_local_count(gsp.aspect_constraint_count.gte_local_count);
+    write_aspect_hash_list(
+        builder.init_aspect_hash_list(gsp.aspect_hash_list.len() as u32),
+        &gsp.aspect_hash_list,
+    );
+}
+
+fn write_direct(mut builder: p2p_capnp::p2p_message::msg_direct::Builder, direct: &MsgDirect) {
+    builder.set_request_id(&direct.request_id);
+    builder.set_data(&direct.data);
+    builder.set_space_address(&direct.space_address);
+    builder.set_to_agent_id(&direct.to_agent_id);
+    builder.set_from_agent_id(&direct.from_agent_id);
+}
+
+fn write_query(mut builder: p2p_capnp::p2p_message::msg_query::Builder, query: &MsgQuery) {
+    builder.set_request_id(&query.request_id);
+    builder.set_entry_address(&query.entry_address);
+    builder.set_data(&query.data);
+}
+
+fn write_join_space(
+    mut builder: p2p_capnp::p2p_message::msg_join_space::Builder,
+    join_space: &MsgJoinSpace,
+) {
+    builder.set_space_address(&join_space.space_address);
+    builder.set_peer_name(&join_space.peer_name);
+    builder.set_peer_location(&join_space.peer_location);
+    builder.set_timestamp(join_space.timestamp);
+}
+
 impl P2pMessage {
     /// create a new ping message
     /// if `send_epoch_ms` is None, will be set to now
     pub fn create_ping(send_epoch_ms: Option<u64>) -> Self {
         P2pMessage::MsgPing(MsgPing {
             send_epoch_ms: send_epoch_ms.unwrap_or_else(now_ms),
+            protocol_version: P2P_PROTOCOL_VERSION,
         })
     }
 
@@ -48,6 +414,7 @@ impl P2pMessage {
         P2pMessage::MsgPong(MsgPong {
             ping_send_epoch_ms: send_epoch_ms,
             ping_received_epoch_ms: recv_epoch_ms.unwrap_or_else(now_ms),
+            protocol_version: P2P_PROTOCOL_VERSION,
         })
     }
 
@@ -58,17 +425,87 @@ impl P2pMessage {
             capnp::message::ReaderOptions::new(),
         )?;
 
-        let message = message
-            .get_root::<p2p_capnp::p2p_message::Reader>()
-            .unwrap();
+        let message = message.get_root::<p2p_capnp::p2p_message::Reader>()?;
 
+        use p2p_capnp::p2p_message::Which;
         match message.which() {
-            Ok(p2p_capnp::p2p_message::MsgPing(Ok(ping))) => Ok(P2pMessage::MsgPing(MsgPing {
+            Ok(Which::MsgError(Ok(error))) => Ok(P2pMessage::MsgError(MsgError {
+                // newer protos may send codes we do not know about
+                error_code: match error.get_error_code() {
+                    Ok(p2p_capnp::p2p_message::msg_error::ErrorCode::Unknown) | Err(_) => {
+                        MsgErrorCode::Unknown
+                    }
+                },
+                error_text: error.get_error_text()?.to_string(),
+            })),
+            Ok(Which::MsgHandshake(Ok(handshake))) => Ok(P2pMessage::MsgHandshake(MsgHandshake {
+                store_arc_radius: handshake.get_store_arc_radius(),
+                query_arc_radius: handshake.get_query_arc_radius(),
+            })),
+            Ok(Which::MsgGspArcRequest(Ok(gsp))) => {
+                Ok(P2pMessage::MsgGspArcRequest(read_gsp_arc(gsp)?))
+            }
+            Ok(Which::MsgGspArcResponse(Ok(gsp))) => {
+                Ok(P2pMessage::MsgGspArcResponse(read_gsp_arc(gsp)?))
+            }
+            Ok(Which::MsgGspAspectDataRequest(Ok(request))) => Ok(
+                P2pMessage::MsgGspAspectDataRequest(MsgGspAspectDataRequest {
+                    aspect_hash_list: read_aspect_hash_list(request.get_aspect_hash_list()?)?,
+                }),
+            ),
+            Ok(Which::MsgGspAspectDataResponse(Ok(response))) => Ok(
+                P2pMessage::MsgGspAspectDataResponse(MsgGspAspectDataResponse {
+                    aspect_data_list: read_aspect_data_list(response.get_aspect_data_list()?)?,
+                }),
+            ),
+            Ok(Which::MsgGspAspectBroadcast(Ok(broadcast))) => {
+                Ok(P2pMessage::MsgGspAspectBroadcast(MsgGspAspectBroadcast {
+                    aspect_data_list: read_aspect_data_list(broadcast.get_aspect_data_list()?)?,
+                }))
+            }
+            Ok(Which::MsgDirectRequest(Ok(direct))) => {
+                Ok(P2pMessage::MsgDirectRequest(read_direct(direct)?))
+            }
+            Ok(Which::MsgDirectResponse(Ok(direct))) => {
+                Ok(P2pMessage::MsgDirectResponse(read_direct(direct)?))
+            }
+            Ok(Which::MsgQueryRequest(Ok(query))) => {
+                Ok(P2pMessage::MsgQueryRequest(read_query(query)?))
+            }
+            Ok(Which::MsgQueryResponse(Ok(query))) => {
+                Ok(P2pMessage::MsgQueryResponse(read_query(query)?))
+            }
+            Ok(Which::MsgPing(Ok(ping))) => Ok(P2pMessage::MsgPing(MsgPing {
                 send_epoch_ms: ping.get_send_epoch_ms(),
+                protocol_version: ping.get_protocol_version(),
             })),
-            Ok(p2p_capnp::p2p_message::MsgPong(Ok(pong))) => Ok(P2pMessage::MsgPong(MsgPong {
+            Ok(Which::MsgPong(Ok(pong))) => Ok(P2pMessage::MsgPong(MsgPong {
                 ping_send_epoch_ms: pong.get_ping_send_epoch_ms(),
                 ping_received_epoch_ms: pong.get_ping_received_epoch_ms(),
+                protocol_version: pong.get_protocol_version(),
+            })),
+            Ok(Which::MsgPeerName(Ok(peer_name))) => Ok(P2pMessage::MsgPeerName(MsgPeerName {
+                gateway_id: peer_name.get_gateway_id()?.to_string(),
+                peer_name: peer_name.get_peer_name()?.to_string(),
+                timestamp: peer_name.get_timestamp(),
+            })),
+            Ok(Which::MsgJoinSpace(Ok(join_space))) => {
+                Ok(P2pMessage::MsgJoinSpace(read_join_space(join_space)?))
+            }
+            Ok(Which::MsgAllJoinedSpaceList(Ok(all_joined))) => {
+                let mut joined_space_list = Vec::new();
+                for join_space in all_joined.get_joined_space_list()?.iter() {
+                    joined_space_list.push(read_join_space(join_space)?);
+                }
+                Ok(P2pMessage::MsgAllJoinedSpaceList(MsgAllJoinedSpaceList {
+                    joined_space_list,
+                }))
+            }
+            Ok(Which::MsgGossip(Ok(gossip))) => Ok(P2pMessage::MsgGossip(MsgGossip {
+                space_address: gossip.get_space_address()?.to_string(),
+                to_peer_name: gossip.get_to_peer_name()?.to_string(),
+                from_peer_name: gossip.get_from_peer_name()?.to_string(),
+                bundle: gossip.get_bundle()?.to_vec(),
             })),
             _ => Err("failed to decode".into()),
         }
@@ -78,21 +515,101 @@ impl P2pMessage {
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut message = capnp::message::Builder::new_default();
         {
+            let root = message.init_root::<p2p_capnp::p2p_message::Builder>();
             match self {
+                P2pMessage::MsgError(error) => {
+                    let mut message = root.init_msg_error();
+
+                    message.set_error_code(match error.error_code {
+                        MsgErrorCode::Unknown => {
+                            p2p_capnp::p2p_message::msg_error::ErrorCode::Unknown
+                        }
+                    });
+                    message.set_error_text(&error.error_text);
+                }
+                P2pMessage::MsgHandshake(handshake) => {
+                    let mut message = root.init_msg_handshake();
+
+                    message.set_store_arc_radius(handshake.store_arc_radius);
+                    message.set_query_arc_radius(handshake.query_arc_radius);
+                }
+                P2pMessage::MsgGspArcRequest(gsp) => {
+                    write_gsp_arc(root.init_msg_gsp_arc_request(), gsp);
+                }
+                P2pMessage::MsgGspArcResponse(gsp) => {
+                    write_gsp_arc(root.init_msg_gsp_arc_response(), gsp);
+                }
+                P2pMessage::MsgGspAspectDataRequest(request) => {
+                    write_aspect_hash_list(
+                        root.init_msg_gsp_aspect_data_request()
+                            .init_aspect_hash_list(request.aspect_hash_list.len() as u32),
+                        &request.aspect_hash_list,
+                    );
+                }
+                P2pMessage::MsgGspAspectDataResponse(response) => {
+                    write_aspect_data_list(
+                        root.init_msg_gsp_aspect_data_response()
+                            .init_aspect_data_list(response.aspect_data_list.len() as u32),
+                        &response.aspect_data_list,
+                    );
+                }
+                P2pMessage::MsgGspAspectBroadcast(broadcast) => {
+                    write_aspect_data_list(
+                        root.init_msg_gsp_aspect_broadcast()
+                            .init_aspect_data_list(broadcast.aspect_data_list.len() as u32),
+                        &broadcast.aspect_data_list,
+                    );
+                }
+                P2pMessage::MsgDirectRequest(direct) => {
+                    write_direct(root.init_msg_direct_request(), direct);
+                }
+                P2pMessage::MsgDirectResponse(direct) => {
+                    write_direct(root.init_msg_direct_response(), direct);
+                }
+                P2pMessage::MsgQueryRequest(query) => {
+                    write_query(root.init_msg_query_request(), query);
+                }
+                P2pMessage::MsgQueryResponse(query) => {
+                    write_query(root.init_msg_query_response(), query);
+                }
                 P2pMessage::MsgPing(ping) => {
-                    let mut message = message
-                        .init_root::<p2p_capnp::p2p_message::Builder>()
-                        .init_msg_ping();
+                    let mut message = root.init_msg_ping();
 
                     message.set_send_epoch_ms(ping.send_epoch_ms);
+                    message.set_protocol_version(ping.protocol_version);
                 }
                 P2pMessage::MsgPong(pong) => {
-                    let mut message = message
-                        .init_root::<p2p_capnp::p2p_message::Builder>()
-                        .init_msg_pong();
+                    let mut message = root.init_msg_pong();
 
                     message.set_ping_send_epoch_ms(pong.ping_send_epoch_ms);
                     message.set_ping_received_epoch_ms(pong.ping_received_epoch_ms);
+                    message.set_protocol_version(pong.protocol_version);
+                }
+                P2pMessage::MsgPeerName(peer_name) => {
+                    let mut message = root.init_msg_peer_name();
+
+                    message.set_gateway_id(&peer_name.gateway_id);
+                    message.set_peer_name(&peer_name.peer_name);
+                    message.set_timestamp(peer_name.timestamp);
+                }
+                P2pMessage::MsgJoinSpace(join_space) => {
+                    write_join_space(root.init_msg_join_space(), join_space);
+                }
+                P2pMessage::MsgAllJoinedSpaceList(all_joined) => {
+                    let mut list = root
+                        .init_msg_all_joined_space_list()
+                        .init_joined_space_list(all_joined.joined_space_list.len() as u32);
+                    for (i, join_space) in all_joined.joined_space_list.iter().enumerate() {
+                        write_join_space(list.reborrow().get(i as u32), join_space);
+                    }
+                }
+                P2pMessage::MsgGossip(gossip) => {
+                    let mut message = root.init_msg_gossip();
+
+                    message.set_space_address(&gossip.space_address);
+                    message.set_to_peer_name(&gossip.to_peer_name);
+                    message.set_from_peer_name(&gossip.from_peer_name);
+                    message.set_bundle(&gossip.bundle);
                 }
             }
         }
@@ -113,18 +630,36 @@ mod tests {
 
     #[test]
     fn it_can_encode_decode_ping() {
-        let message = P2pMessage::MsgPing(MsgPing { send_epoch_ms: 42 });
+        let message = P2pMessage::MsgPing(MsgPing {
+            send_epoch_ms: 42,
+            protocol_version: 1,
+        });
 
         let bytes = message.into_bytes();
 
         assert_eq!(
-            "[16, 4, 80, 1, 1, 1, 11, 16, 1, 1, 42]",
+            "[16, 5, 80, 1, 1, 1, 11, 16, 2, 1, 42, 1, 1]",
             format!("{:?}", bytes),
         );
 
         match P2pMessage::from_bytes(bytes).unwrap() {
             P2pMessage::MsgPing(ping) => {
                 assert_eq!(42_u64, ping.send_epoch_ms);
+                assert_eq!(1_u32, ping.protocol_version);
+            }
+            _ => panic!("unexpected msg type"),
+        }
+    }
+
+    #[test]
+    fn it_can_decode_ping_from_unversioned_node() {
+        // a ping as encoded before `protocolVersion` was added
+        let bytes = vec![16, 4, 80, 1, 1, 1, 11, 16, 1, 1, 42];
+
+        match P2pMessage::from_bytes(bytes).unwrap() {
+            P2pMessage::MsgPing(ping) => {
+                assert_eq!(42_u64, ping.send_epoch_ms);
+                assert_eq!(0_u32, ping.protocol_version);
             }
             _ => panic!("unexpected msg type"),
         }
@@ -135,12 +670,13 @@ mod tests {
         let message = P2pMessage::MsgPong(MsgPong {
             ping_send_epoch_ms: 42,
             ping_received_epoch_ms: 99,
+            protocol_version: 1,
         });
 
         let bytes = message.into_bytes();
 
         assert_eq!(
-            "[16, 5, 80, 1, 1, 1, 12, 16, 2, 1, 42, 1, 99]",
+            "[16, 6, 80, 1, 1, 1, 12, 16, 3, 1, 42, 1, 99, 1, 1]",
             format!("{:?}", bytes),
         );
 
@@ -148,8 +684,162 @@ mod tests {
             P2pMessage::MsgPong(pong) => {
                 assert_eq!(42_u64, pong.ping_send_epoch_ms);
                 assert_eq!(99_u64, pong.ping_received_epoch_ms);
+                assert_eq!(1_u32, pong.protocol_version);
             }
             _ => panic!("unexpected msg type"),
         }
     }
+
+    fn round_trip(message: P2pMessage) {
+        let bytes = message.to_bytes();
+        assert_eq!(message, P2pMessage::from_bytes(bytes).unwrap());
+    }
+
+    fn test_aspect_hash_list() -> Vec<AspectHashList> {
+        vec![
+            AspectHashList {
+                entry_address: b"entry_addr_1".to_vec(),
+                aspect_hash_list: vec![b"aspect_addr_1".to_vec(), b"aspect_addr_2".to_vec()],
+            },
+            AspectHashList {
+                entry_address: b"entry_addr_2".to_vec(),
+                aspect_hash_list: vec![],
+            },
+        ]
+    }
+
+    fn test_aspect_data_list() -> Vec<AspectDataList> {
+        vec![AspectDataList {
+            entry_address: b"entry_addr_1".to_vec(),
+            aspect_data_list: vec![b"hello-1".to_vec(), b"l-2".to_vec()],
+        }]
+    }
+
+    fn test_gsp_arc() -> MsgGspArc {
+        MsgGspArc {
+            aspect_constraint_arc: AspectConstraintArc {
+                arc_start: 0xfff0_0000,
+                arc_end: 0x0000_1000,
+                arc_empty: false,
+            },
+            aspect_constraint_time: AspectConstraintTime { gte_epoch_ms: 42 },
+            aspect_constraint_count: AspectConstraintCount { gte_local_count: 7 },
+            aspect_hash_list: test_aspect_hash_list(),
+        }
+    }
+
+    #[test]
+    fn it_can_encode_decode_error() {
+        round_trip(P2pMessage::MsgError(MsgError {
+            error_code: MsgErrorCode::Unknown,
+            error_text: "oops".to_string(),
+        }));
+    }
+
+    #[test]
+    fn it_can_encode_decode_handshake() {
+        round_trip(P2pMessage::MsgHandshake(MsgHandshake {
+            store_arc_radius: 0x8000_0001,
+            query_arc_radius: 42,
+        }));
+    }
+
+    #[test]
+    fn it_can_encode_decode_gsp_arc() {
+        round_trip(P2pMessage::MsgGspArcRequest(test_gsp_arc()));
+        round_trip(P2pMessage::MsgGspArcResponse(test_gsp_arc()));
+        let mut empty_arc = test_gsp_arc();
+        empty_arc.aspect_constraint_arc = AspectConstraintArc {
+            arc_start: 42,
+            arc_end: 42,
+            arc_empty: true,
+        };
+        round_trip(P2pMessage::MsgGspArcRequest(empty_arc));
+    }
+
+    #[test]
+    fn it_can_encode_decode_gsp_aspect_data() {
+        round_trip(P2pMessage::MsgGspAspectDataRequest(
+            MsgGspAspectDataRequest {
+                aspect_hash_list: test_aspect_hash_list(),
+            },
+        ));
+        round_trip(P2pMessage::MsgGspAspectDataResponse(
+            MsgGspAspectDataResponse {
+                aspect_data_list: test_aspect_data_list(),
+            },
+        ));
+        round_trip(P2pMessage::MsgGspAspectBroadcast(MsgGspAspectBroadcast {
+            aspect_data_list: test_aspect_data_list(),
+        }));
+    }
+
+    #[test]
+    fn it_can_encode_decode_direct() {
+        let direct = MsgDirect {
+            request_id: "req_1".to_string(),
+            data: vec![1, 2, 3],
+            space_address: "space_1".to_string(),
+            to_agent_id: "agent_1".to_string(),
+            from_agent_id: "agent_2".to_string(),
+        };
+        round_trip(P2pMessage::MsgDirectRequest(direct.clone()));
+        round_trip(P2pMessage::MsgDirectResponse(direct));
+    }
+
+    fn test_join_space(space_address: &str) -> MsgJoinSpace {
+        MsgJoinSpace {
+            space_address: space_address.to_string(),
+            peer_name: "hc://agent_1".to_string(),
+            peer_location: "mem://addr_1".to_string(),
+            timestamp: 42,
+        }
+    }
+
+    #[test]
+    fn it_can_encode_decode_peer_name() {
+        round_trip(P2pMessage::MsgPeerName(MsgPeerName {
+            gateway_id: "network".to_string(),
+            peer_name: "hc://node_1".to_string(),
+            timestamp: 42,
+        }));
+    }
+
+    #[test]
+    fn it_can_encode_decode_join_space() {
+        round_trip(P2pMessage::MsgJoinSpace(test_join_space("space_1")));
+        round_trip(P2pMessage::MsgAllJoinedSpaceList(MsgAllJoinedSpaceList {
+            joined_space_list: vec![test_join_space("space_1"), test_join_space("space_2")],
+        }));
+    }
+
+    #[test]
+    fn it_can_encode_decode_gossip() {
+        round_trip(P2pMessage::MsgGossip(MsgGossip {
+            space_address: "space_1".to_string(),
+            to_peer_name: "hc://agent_1".to_string(),
+            from_peer_name: "hc://agent_2".to_string(),
+            bundle: vec![1, 2, 3],
+        }));
+    }
+
+    #[test]
+    fn it_can_encode_decode_query() {
+        let query = MsgQuery {
+            request_id: "req_1".to_string(),
+            entry_address: b"entry_addr_1".to_vec(),
+            data: vec![1, 2, 3],
+        };
+        round_trip(P2pMessage::MsgQueryRequest(query.clone()));
+        round_trip(P2pMessage::MsgQueryResponse(query));
+    }
+
+    #[test]
+    fn it_should_not_confuse_request_and_response() {
+        let bytes = P2pMessage::MsgGspArcRequest(test_gsp_arc()).into_bytes();
+        match P2pMessage::from_bytes(bytes).unwrap() {
+            P2pMessage::MsgGspArcRequest(_) => (),
+            _ => panic!("unexpected msg type"),
+        }
+    }
 }
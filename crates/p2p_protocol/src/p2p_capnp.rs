@@ -4,7 +4,7 @@
 
 
 pub mod p2p_message {
-  pub use self::Which::{MsgError,MsgHandshake,MsgGspArcRequest,MsgGspArcResponse,MsgGspAspectDataRequest,MsgGspAspectDataResponse,MsgGspAspectBroadcast,MsgDirectRequest,MsgDirectResponse,MsgQueryRequest,MsgQueryResponse,MsgPing,MsgPong};
+  pub use self::Which::{MsgError,MsgHandshake,MsgGspArcRequest,MsgGspArcResponse,MsgGspAspectDataRequest,MsgGspAspectDataResponse,MsgGspAspectBroadcast,MsgDirectRequest,MsgDirectResponse,MsgQueryRequest,MsgQueryResponse,MsgPing,MsgPong,MsgPeerName,MsgJoinSpace,MsgAllJoinedSpaceList,MsgGossip};
 
   #[derive(Copy, Clone)]
   pub struct Owned;
@@ -103,6 +103,22 @@ pub mod p2p_message {
       if self.reader.get_data_field::<u16>(0) != 12 { return false; }
       !self.reader.get_pointer_field(0).is_null()
     }
+    pub fn has_msg_peer_name(&self) -> bool {
+      if self.reader.get_data_field::<u16>(0) != 13 { return false; }
+      !self.reader.get_pointer_field(0).is_null()
+    }
+    pub fn has_msg_join_space(&self) -> bool {
+      if self.reader.get_data_field::<u16>(0) != 14 { return false; }
+      !self.reader.get_pointer_field(0).is_null()
+    }
+    pub fn has_msg_all_joined_space_list(&self) -> bool {
+      if self.reader.get_data_field::<u16>(0) != 15 { return false; }
+      !self.reader.get_pointer_field(0).is_null()
+    }
+    pub fn has_msg_gossip(&self) -> bool {
+      if self.reader.get_data_field::<u16>(0) != 16 { return false; }
+      !self.reader.get_pointer_field(0).is_null()
+    }
     #[inline]
     pub fn which(self) -> ::std::result::Result<WhichReader<'a,>, ::capnp::NotInSchema> {
       match self.reader.get_data_field::<u16>(0) {
@@ -171,6 +187,26 @@ pub mod p2p_message {
             ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(0), ::std::option::Option::None)
           ))
         }
+        13 => {
+          ::std::result::Result::Ok(MsgPeerName(
+            ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(0), ::std::option::Option::None)
+          ))
+        }
+        14 => {
+          ::std::result::Result::Ok(MsgJoinSpace(
+            ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(0), ::std::option::Option::None)
+          ))
+        }
+        15 => {
+          ::std::result::Result::Ok(MsgAllJoinedSpaceList(
+            ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(0), ::std::option::Option::None)
+          ))
+        }
+        16 => {
+          ::std::result::Result::Ok(MsgGossip(
+            ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(0), ::std::option::Option::None)
+          ))
+        }
         x => ::std::result::Result::Err(::capnp::NotInSchema(x))
       }
     }
@@ -407,6 +443,62 @@ pub mod p2p_message {
       !self.builder.get_pointer_field(0).is_null()
     }
     #[inline]
+    pub fn set_msg_peer_name<'b>(&mut self, value: crate::p2p_capnp::p2p_message::msg_peer_name::Reader<'b>) -> ::capnp::Result<()> {
+      self.builder.set_data_field::<u16>(0, 13);
+      ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(0), value, false)
+    }
+    #[inline]
+    pub fn init_msg_peer_name(self, ) -> crate::p2p_capnp::p2p_message::msg_peer_name::Builder<'a> {
+      self.builder.set_data_field::<u16>(0, 13);
+      ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(0), 0)
+    }
+    pub fn has_msg_peer_name(&self) -> bool {
+      if self.builder.get_data_field::<u16>(0) != 13 { return false; }
+      !self.builder.get_pointer_field(0).is_null()
+    }
+    #[inline]
+    pub fn set_msg_join_space<'b>(&mut self, value: crate::p2p_capnp::p2p_message::msg_join_space::Reader<'b>) -> ::capnp::Result<()> {
+      self.builder.set_data_field::<u16>(0, 14);
+      ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(0), value, false)
+    }
+    #[inline]
+    pub fn init_msg_join_space(self, ) -> crate::p2p_capnp::p2p_message::msg_join_space::Builder<'a> {
+      self.builder.set_data_field::<u16>(0, 14);
+      ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(0), 0)
+    }
+    pub fn has_msg_join_space(&self) -> bool {
+      if self.builder.get_data_field::<u16>(0) != 14 { return false; }
+      !self.builder.get_pointer_field(0).is_null()
+    }
+    #[inline]
+    pub fn set_msg_all_joined_space_list<'b>(&mut self, value: crate::p2p_capnp::p2p_message::msg_all_joined_space_list::Reader<'b>) -> ::capnp::Result<()> {
+      self.builder.set_data_field::<u16>(0, 15);
+      ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(0), value, false)
+    }
+    #[inline]
+    pub fn init_msg_all_joined_space_list(self, ) -> crate::p2p_capnp::p2p_message::msg_all_joined_space_list::Builder<'a> {
+      self.builder.set_data_field::<u16>(0, 15);
+      ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(0), 0)
+    }
+    pub fn has_msg_all_joined_space_list(&self) -> bool {
+      if self.builder.get_data_field::<u16>(0) != 15 { return false; }
+      !self.builder.get_pointer_field(0).is_null()
+    }
+    #[inline]
+    pub fn set_msg_gossip<'b>(&mut self, value: crate::p2p_capnp::p2p_message::msg_gossip::Reader<'b>) -> ::capnp::Result<()> {
+      self.builder.set_data_field::<u16>(0, 16);
+      ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(0), value, false)
+    }
+    #[inline]
+    pub fn init_msg_gossip(self, ) -> crate::p2p_capnp::p2p_message::msg_gossip::Builder<'a> {
+      self.builder.set_data_field::<u16>(0, 16);
+      ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(0), 0)
+    }
+    pub fn has_msg_gossip(&self) -> bool {
+      if self.builder.get_data_field::<u16>(0) != 16 { return false; }
+      !self.builder.get_pointer_field(0).is_null()
+    }
+    #[inline]
     pub fn which(self) -> ::std::result::Result<WhichBuilder<'a,>, ::capnp::NotInSchema> {
       match self.builder.get_data_field::<u16>(0) {
         0 => {
@@ -474,6 +566,26 @@ pub mod p2p_message {
             ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(0), ::std::option::Option::None)
           ))
         }
+        13 => {
+          ::std::result::Result::Ok(MsgPeerName(
+            ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(0), ::std::option::Option::None)
+          ))
+        }
+        14 => {
+          ::std::result::Result::Ok(MsgJoinSpace(
+            ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(0), ::std::option::Option::None)
+          ))
+        }
+        15 => {
+          ::std::result::Result::Ok(MsgAllJoinedSpaceList(
+            ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(0), ::std::option::Option::None)
+          ))
+        }
+        16 => {
+          ::std::result::Result::Ok(MsgGossip(
+            ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(0), ::std::option::Option::None)
+          ))
+        }
         x => ::std::result::Result::Err(::capnp::NotInSchema(x))
       }
     }
@@ -492,7 +604,7 @@ pub mod p2p_message {
     pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 1, pointers: 1 };
     pub const TYPE_ID: u64 = 0x8525_e0f5_2956_47a2;
   }
-  pub enum Which<A0,A1,A2,A3,A4,A5,A6,A7,A8,A9,A10,A11,A12> {
+  pub enum Which<A0,A1,A2,A3,A4,A5,A6,A7,A8,A9,A10,A11,A12,A13,A14,A15,A16> {
     MsgError(A0),
     MsgHandshake(A1),
     MsgGspArcRequest(A2),
@@ -506,9 +618,13 @@ pub mod p2p_message {
     MsgQueryResponse(A10),
     MsgPing(A11),
     MsgPong(A12),
+    MsgPeerName(A13),
+    MsgJoinSpace(A14),
+    MsgAllJoinedSpaceList(A15),
+    MsgGossip(A16),
   }
-  pub type WhichReader<'a,> = Which<::capnp::Result<crate::p2p_capnp::p2p_message::msg_error::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_handshake::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_arc::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_arc::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_aspect_data_request::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_aspect_data_response::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_aspect_broadcast::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_direct::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_direct::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_query::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_query::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_ping::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_pong::Reader<'a>>>;
-  pub type WhichBuilder<'a,> = Which<::capnp::Result<crate::p2p_capnp::p2p_message::msg_error::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_handshake::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_arc::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_arc::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_aspect_data_request::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_aspect_data_response::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_aspect_broadcast::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_direct::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_direct::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_query::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_query::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_ping::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_pong::Builder<'a>>>;
+  pub type WhichReader<'a,> = Which<::capnp::Result<crate::p2p_capnp::p2p_message::msg_error::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_handshake::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_arc::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_arc::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_aspect_data_request::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_aspect_data_response::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_aspect_broadcast::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_direct::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_direct::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_query::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_query::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_ping::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_pong::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_peer_name::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_join_space::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_all_joined_space_list::Reader<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gossip::Reader<'a>>>;
+  pub type WhichBuilder<'a,> = Which<::capnp::Result<crate::p2p_capnp::p2p_message::msg_error::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_handshake::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_arc::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_arc::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_aspect_data_request::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_aspect_data_response::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gsp_aspect_broadcast::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_direct::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_direct::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_query::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_query::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_ping::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_pong::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_peer_name::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_join_space::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_all_joined_space_list::Builder<'a>>,::capnp::Result<crate::p2p_capnp::p2p_message::msg_gossip::Builder<'a>>>;
 
   pub mod msg_error {
     #[derive(Copy, Clone)]
@@ -1492,6 +1608,27 @@ pub mod p2p_message {
       pub fn has_data(&self) -> bool {
         !self.reader.get_pointer_field(1).is_null()
       }
+      #[inline]
+      pub fn get_space_address(self) -> ::capnp::Result<::capnp::text::Reader<'a>> {
+        ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(2), ::std::option::Option::None)
+      }
+      pub fn has_space_address(&self) -> bool {
+        !self.reader.get_pointer_field(2).is_null()
+      }
+      #[inline]
+      pub fn get_to_agent_id(self) -> ::capnp::Result<::capnp::text::Reader<'a>> {
+        ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(3), ::std::option::Option::None)
+      }
+      pub fn has_to_agent_id(&self) -> bool {
+        !self.reader.get_pointer_field(3).is_null()
+      }
+      #[inline]
+      pub fn get_from_agent_id(self) -> ::capnp::Result<::capnp::text::Reader<'a>> {
+        ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(4), ::std::option::Option::None)
+      }
+      pub fn has_from_agent_id(&self) -> bool {
+        !self.reader.get_pointer_field(4).is_null()
+      }
     }
 
     pub struct Builder<'a> { builder: ::capnp::private::layout::StructBuilder<'a> }
@@ -1572,6 +1709,51 @@ pub mod p2p_message {
       pub fn has_data(&self) -> bool {
         !self.builder.get_pointer_field(1).is_null()
       }
+      #[inline]
+      pub fn get_space_address(self) -> ::capnp::Result<::capnp::text::Builder<'a>> {
+        ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(2), ::std::option::Option::None)
+      }
+      #[inline]
+      pub fn set_space_address(&mut self, value: ::capnp::text::Reader)  {
+        self.builder.get_pointer_field(2).set_text(value);
+      }
+      #[inline]
+      pub fn init_space_address(self, size: u32) -> ::capnp::text::Builder<'a> {
+        self.builder.get_pointer_field(2).init_text(size)
+      }
+      pub fn has_space_address(&self) -> bool {
+        !self.builder.get_pointer_field(2).is_null()
+      }
+      #[inline]
+      pub fn get_to_agent_id(self) -> ::capnp::Result<::capnp::text::Builder<'a>> {
+        ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(3), ::std::option::Option::None)
+      }
+      #[inline]
+      pub fn set_to_agent_id(&mut self, value: ::capnp::text::Reader)  {
+        self.builder.get_pointer_field(3).set_text(value);
+      }
+      #[inline]
+      pub fn init_to_agent_id(self, size: u32) -> ::capnp::text::Builder<'a> {
+        self.builder.get_pointer_field(3).init_text(size)
+      }
+      pub fn has_to_agent_id(&self) -> bool {
+        !self.builder.get_pointer_field(3).is_null()
+      }
+      #[inline]
+      pub fn get_from_agent_id(self) -> ::capnp::Result<::capnp::text::Builder<'a>> {
+        ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(4), ::std::option::Option::None)
+      }
+      #[inline]
+      pub fn set_from_agent_id(&mut self, value: ::capnp::text::Reader)  {
+        self.builder.get_pointer_field(4).set_text(value);
+      }
+      #[inline]
+      pub fn init_from_agent_id(self, size: u32) -> ::capnp::text::Builder<'a> {
+        self.builder.get_pointer_field(4).init_text(size)
+      }
+      pub fn has_from_agent_id(&self) -> bool {
+        !self.builder.get_pointer_field(4).is_null()
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -1584,7 +1766,7 @@ pub mod p2p_message {
     }
     mod _private {
       use capnp::private::layout;
-      pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 0, pointers: 2 };
+      pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 0, pointers: 5 };
       pub const TYPE_ID: u64 = 0xcaf3_3a76_558f_07e7;
     }
   }
@@ -1818,6 +2000,10 @@ pub mod p2p_message {
       pub fn get_send_epoch_ms(self) -> u64 {
         self.reader.get_data_field::<u64>(0)
       }
+      #[inline]
+      pub fn get_protocol_version(self) -> u32 {
+        self.reader.get_data_field::<u32>(2)
+      }
     }
 
     pub struct Builder<'a> { builder: ::capnp::private::layout::StructBuilder<'a> }
@@ -1876,6 +2062,14 @@ pub mod p2p_message {
       pub fn set_send_epoch_ms(&mut self, value: u64)  {
         self.builder.set_data_field::<u64>(0, value);
       }
+      #[inline]
+      pub fn get_protocol_version(self) -> u32 {
+        self.builder.get_data_field::<u32>(2)
+      }
+      #[inline]
+      pub fn set_protocol_version(&mut self, value: u32)  {
+        self.builder.set_data_field::<u32>(2, value);
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -1888,7 +2082,7 @@ pub mod p2p_message {
     }
     mod _private {
       use capnp::private::layout;
-      pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 1, pointers: 0 };
+      pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 2, pointers: 0 };
       pub const TYPE_ID: u64 = 0xfe22_a943_4e40_cf9d;
     }
   }
@@ -1947,6 +2141,10 @@ pub mod p2p_message {
       pub fn get_ping_received_epoch_ms(self) -> u64 {
         self.reader.get_data_field::<u64>(1)
       }
+      #[inline]
+      pub fn get_protocol_version(self) -> u32 {
+        self.reader.get_data_field::<u32>(4)
+      }
     }
 
     pub struct Builder<'a> { builder: ::capnp::private::layout::StructBuilder<'a> }
@@ -2013,6 +2211,14 @@ pub mod p2p_message {
       pub fn set_ping_received_epoch_ms(&mut self, value: u64)  {
         self.builder.set_data_field::<u64>(1, value);
       }
+      #[inline]
+      pub fn get_protocol_version(self) -> u32 {
+        self.builder.get_data_field::<u32>(4)
+      }
+      #[inline]
+      pub fn set_protocol_version(&mut self, value: u32)  {
+        self.builder.set_data_field::<u32>(4, value);
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -2025,12 +2231,12 @@ pub mod p2p_message {
     }
     mod _private {
       use capnp::private::layout;
-      pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 2, pointers: 0 };
+      pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 3, pointers: 0 };
       pub const TYPE_ID: u64 = 0xb3ea_fa36_7d22_5731;
     }
   }
 
-  pub mod aspect_hash_list {
+  pub mod msg_peer_name {
     #[derive(Copy, Clone)]
     pub struct Owned;
     impl <'a> ::capnp::traits::Owned<'a> for Owned { type Reader = Reader<'a>; type Builder = Builder<'a>; }
@@ -2077,19 +2283,23 @@ pub mod p2p_message {
         self.reader.total_size()
       }
       #[inline]
-      pub fn get_entry_address(self) -> ::capnp::Result<::capnp::data::Reader<'a>> {
+      pub fn get_gateway_id(self) -> ::capnp::Result<::capnp::text::Reader<'a>> {
         ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(0), ::std::option::Option::None)
       }
-      pub fn has_entry_address(&self) -> bool {
+      pub fn has_gateway_id(&self) -> bool {
         !self.reader.get_pointer_field(0).is_null()
       }
       #[inline]
-      pub fn get_aspect_hash_list(self) -> ::capnp::Result<::capnp::data_list::Reader<'a>> {
+      pub fn get_peer_name(self) -> ::capnp::Result<::capnp::text::Reader<'a>> {
         ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(1), ::std::option::Option::None)
       }
-      pub fn has_aspect_hash_list(&self) -> bool {
+      pub fn has_peer_name(&self) -> bool {
         !self.reader.get_pointer_field(1).is_null()
       }
+      #[inline]
+      pub fn get_timestamp(self) -> u64 {
+        self.reader.get_data_field::<u64>(0)
+      }
     }
 
     pub struct Builder<'a> { builder: ::capnp::private::layout::StructBuilder<'a> }
@@ -2141,35 +2351,43 @@ pub mod p2p_message {
         self.builder.into_reader().total_size()
       }
       #[inline]
-      pub fn get_entry_address(self) -> ::capnp::Result<::capnp::data::Builder<'a>> {
+      pub fn get_gateway_id(self) -> ::capnp::Result<::capnp::text::Builder<'a>> {
         ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(0), ::std::option::Option::None)
       }
       #[inline]
-      pub fn set_entry_address(&mut self, value: ::capnp::data::Reader)  {
-        self.builder.get_pointer_field(0).set_data(value);
+      pub fn set_gateway_id(&mut self, value: ::capnp::text::Reader)  {
+        self.builder.get_pointer_field(0).set_text(value);
       }
       #[inline]
-      pub fn init_entry_address(self, size: u32) -> ::capnp::data::Builder<'a> {
-        self.builder.get_pointer_field(0).init_data(size)
+      pub fn init_gateway_id(self, size: u32) -> ::capnp::text::Builder<'a> {
+        self.builder.get_pointer_field(0).init_text(size)
       }
-      pub fn has_entry_address(&self) -> bool {
+      pub fn has_gateway_id(&self) -> bool {
         !self.builder.get_pointer_field(0).is_null()
       }
       #[inline]
-      pub fn get_aspect_hash_list(self) -> ::capnp::Result<::capnp::data_list::Builder<'a>> {
+      pub fn get_peer_name(self) -> ::capnp::Result<::capnp::text::Builder<'a>> {
         ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(1), ::std::option::Option::None)
       }
       #[inline]
-      pub fn set_aspect_hash_list(&mut self, value: ::capnp::data_list::Reader<'a>) -> ::capnp::Result<()> {
-        ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(1), value, false)
+      pub fn set_peer_name(&mut self, value: ::capnp::text::Reader)  {
+        self.builder.get_pointer_field(1).set_text(value);
       }
       #[inline]
-      pub fn init_aspect_hash_list(self, size: u32) -> ::capnp::data_list::Builder<'a> {
-        ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(1), size)
+      pub fn init_peer_name(self, size: u32) -> ::capnp::text::Builder<'a> {
+        self.builder.get_pointer_field(1).init_text(size)
       }
-      pub fn has_aspect_hash_list(&self) -> bool {
+      pub fn has_peer_name(&self) -> bool {
         !self.builder.get_pointer_field(1).is_null()
       }
+      #[inline]
+      pub fn get_timestamp(self) -> u64 {
+        self.builder.get_data_field::<u64>(0)
+      }
+      #[inline]
+      pub fn set_timestamp(&mut self, value: u64)  {
+        self.builder.set_data_field::<u64>(0, value);
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -2182,12 +2400,12 @@ pub mod p2p_message {
     }
     mod _private {
       use capnp::private::layout;
-      pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 0, pointers: 2 };
-      pub const TYPE_ID: u64 = 0xc3f9_8bc7_9025_c948;
+      pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 1, pointers: 2 };
+      pub const TYPE_ID: u64 = 0xd4a1_6c0e_3b92_f58d;
     }
   }
 
-  pub mod aspect_data_list {
+  pub mod msg_join_space {
     #[derive(Copy, Clone)]
     pub struct Owned;
     impl <'a> ::capnp::traits::Owned<'a> for Owned { type Reader = Reader<'a>; type Builder = Builder<'a>; }
@@ -2234,19 +2452,30 @@ pub mod p2p_message {
         self.reader.total_size()
       }
       #[inline]
-      pub fn get_entry_address(self) -> ::capnp::Result<::capnp::data::Reader<'a>> {
+      pub fn get_space_address(self) -> ::capnp::Result<::capnp::text::Reader<'a>> {
         ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(0), ::std::option::Option::None)
       }
-      pub fn has_entry_address(&self) -> bool {
+      pub fn has_space_address(&self) -> bool {
         !self.reader.get_pointer_field(0).is_null()
       }
       #[inline]
-      pub fn get_aspect_data_list(self) -> ::capnp::Result<::capnp::data_list::Reader<'a>> {
+      pub fn get_peer_name(self) -> ::capnp::Result<::capnp::text::Reader<'a>> {
         ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(1), ::std::option::Option::None)
       }
-      pub fn has_aspect_data_list(&self) -> bool {
+      pub fn has_peer_name(&self) -> bool {
         !self.reader.get_pointer_field(1).is_null()
       }
+      #[inline]
+      pub fn get_peer_location(self) -> ::capnp::Result<::capnp::text::Reader<'a>> {
+        ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(2), ::std::option::Option::None)
+      }
+      pub fn has_peer_location(&self) -> bool {
+        !self.reader.get_pointer_field(2).is_null()
+      }
+      #[inline]
+      pub fn get_timestamp(self) -> u64 {
+        self.reader.get_data_field::<u64>(0)
+      }
     }
 
     pub struct Builder<'a> { builder: ::capnp::private::layout::StructBuilder<'a> }
@@ -2298,35 +2527,58 @@ pub mod p2p_message {
         self.builder.into_reader().total_size()
       }
       #[inline]
-      pub fn get_entry_address(self) -> ::capnp::Result<::capnp::data::Builder<'a>> {
+      pub fn get_space_address(self) -> ::capnp::Result<::capnp::text::Builder<'a>> {
         ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(0), ::std::option::Option::None)
       }
       #[inline]
-      pub fn set_entry_address(&mut self, value: ::capnp::data::Reader)  {
-        self.builder.get_pointer_field(0).set_data(value);
+      pub fn set_space_address(&mut self, value: ::capnp::text::Reader)  {
+        self.builder.get_pointer_field(0).set_text(value);
       }
       #[inline]
-      pub fn init_entry_address(self, size: u32) -> ::capnp::data::Builder<'a> {
-        self.builder.get_pointer_field(0).init_data(size)
+      pub fn init_space_address(self, size: u32) -> ::capnp::text::Builder<'a> {
+        self.builder.get_pointer_field(0).init_text(size)
       }
-      pub fn has_entry_address(&self) -> bool {
+      pub fn has_space_address(&self) -> bool {
         !self.builder.get_pointer_field(0).is_null()
       }
       #[inline]
-      pub fn get_aspect_data_list(self) -> ::capnp::Result<::capnp::data_list::Builder<'a>> {
+      pub fn get_peer_name(self) -> ::capnp::Result<::capnp::text::Builder<'a>> {
         ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(1), ::std::option::Option::None)
       }
       #[inline]
-      pub fn set_aspect_data_list(&mut self, value: ::capnp::data_list::Reader<'a>) -> ::capnp::Result<()> {
-        ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(1), value, false)
+      pub fn set_peer_name(&mut self, value: ::capnp::text::Reader)  {
+        self.builder.get_pointer_field(1).set_text(value);
       }
       #[inline]
-      pub fn init_aspect_data_list(self, size: u32) -> ::capnp::data_list::Builder<'a> {
-        ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(1), size)
+      pub fn init_peer_name(self, size: u32) -> ::capnp::text::Builder<'a> {
+        self.builder.get_pointer_field(1).init_text(size)
       }
-      pub fn has_aspect_data_list(&self) -> bool {
+      pub fn has_peer_name(&self) -> bool {
         !self.builder.get_pointer_field(1).is_null()
       }
+      #[inline]
+      pub fn get_peer_location(self) -> ::capnp::Result<::capnp::text::Builder<'a>> {
+        ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(2), ::std::option::Option::None)
+      }
+      #[inline]
+      pub fn set_peer_location(&mut self, value: ::capnp::text::Reader)  {
+        self.builder.get_pointer_field(2).set_text(value);
+      }
+      #[inline]
+      pub fn init_peer_location(self, size: u32) -> ::capnp::text::Builder<'a> {
+        self.builder.get_pointer_field(2).init_text(size)
+      }
+      pub fn has_peer_location(&self) -> bool {
+        !self.builder.get_pointer_field(2).is_null()
+      }
+      #[inline]
+      pub fn get_timestamp(self) -> u64 {
+        self.builder.get_data_field::<u64>(0)
+      }
+      #[inline]
+      pub fn set_timestamp(&mut self, value: u64)  {
+        self.builder.set_data_field::<u64>(0, value);
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -2339,12 +2591,12 @@ pub mod p2p_message {
     }
     mod _private {
       use capnp::private::layout;
-      pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 0, pointers: 2 };
-      pub const TYPE_ID: u64 = 0xfaab_efdd_33da_a362;
+      pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 1, pointers: 3 };
+      pub const TYPE_ID: u64 = 0xe86b_2f47_91c3_0a5e;
     }
   }
 
-  pub mod aspect_constraint_arc {
+  pub mod msg_all_joined_space_list {
     #[derive(Copy, Clone)]
     pub struct Owned;
     impl <'a> ::capnp::traits::Owned<'a> for Owned { type Reader = Reader<'a>; type Builder = Builder<'a>; }
@@ -2391,12 +2643,11 @@ pub mod p2p_message {
         self.reader.total_size()
       }
       #[inline]
-      pub fn get_arc_start(self) -> u32 {
-        self.reader.get_data_field::<u32>(0)
+      pub fn get_joined_space_list(self) -> ::capnp::Result<::capnp::struct_list::Reader<'a,crate::p2p_capnp::p2p_message::msg_join_space::Owned>> {
+        ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(0), ::std::option::Option::None)
       }
-      #[inline]
-      pub fn get_arc_end(self) -> u32 {
-        self.reader.get_data_field::<u32>(1)
+      pub fn has_joined_space_list(&self) -> bool {
+        !self.reader.get_pointer_field(0).is_null()
       }
     }
 
@@ -2449,20 +2700,19 @@ pub mod p2p_message {
         self.builder.into_reader().total_size()
       }
       #[inline]
-      pub fn get_arc_start(self) -> u32 {
-        self.builder.get_data_field::<u32>(0)
+      pub fn get_joined_space_list(self) -> ::capnp::Result<::capnp::struct_list::Builder<'a,crate::p2p_capnp::p2p_message::msg_join_space::Owned>> {
+        ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(0), ::std::option::Option::None)
       }
       #[inline]
-      pub fn set_arc_start(&mut self, value: u32)  {
-        self.builder.set_data_field::<u32>(0, value);
+      pub fn set_joined_space_list(&mut self, value: ::capnp::struct_list::Reader<'a,crate::p2p_capnp::p2p_message::msg_join_space::Owned>) -> ::capnp::Result<()> {
+        ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(0), value, false)
       }
       #[inline]
-      pub fn get_arc_end(self) -> u32 {
-        self.builder.get_data_field::<u32>(1)
+      pub fn init_joined_space_list(self, size: u32) -> ::capnp::struct_list::Builder<'a,crate::p2p_capnp::p2p_message::msg_join_space::Owned> {
+        ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(0), size)
       }
-      #[inline]
-      pub fn set_arc_end(&mut self, value: u32)  {
-        self.builder.set_data_field::<u32>(1, value);
+      pub fn has_joined_space_list(&self) -> bool {
+        !self.builder.get_pointer_field(0).is_null()
       }
     }
 
@@ -2476,7 +2726,671 @@ pub mod p2p_message {
     }
     mod _private {
       use capnp::private::layout;
-      pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 1, pointers: 0 };
+      pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 0, pointers: 1 };
+      pub const TYPE_ID: u64 = 0xb57c_d930_46e8_1f2b;
+    }
+  }
+
+  pub mod msg_gossip {
+    #[derive(Copy, Clone)]
+    pub struct Owned;
+    impl <'a> ::capnp::traits::Owned<'a> for Owned { type Reader = Reader<'a>; type Builder = Builder<'a>; }
+    impl <'a> ::capnp::traits::OwnedStruct<'a> for Owned { type Reader = Reader<'a>; type Builder = Builder<'a>; }
+    impl ::capnp::traits::Pipelined for Owned { type Pipeline = Pipeline; }
+
+    #[derive(Clone, Copy)]
+    pub struct Reader<'a> { reader: ::capnp::private::layout::StructReader<'a> }
+
+    impl <'a,> ::capnp::traits::HasTypeId for Reader<'a,>  {
+      #[inline]
+      fn type_id() -> u64 { _private::TYPE_ID }
+    }
+    impl <'a,> ::capnp::traits::FromStructReader<'a> for Reader<'a,>  {
+      fn new(reader: ::capnp::private::layout::StructReader<'a>) -> Reader<'a,> {
+        Reader { reader: reader,  }
+      }
+    }
+
+    impl <'a,> ::capnp::traits::FromPointerReader<'a> for Reader<'a,>  {
+      fn get_from_pointer(reader: &::capnp::private::layout::PointerReader<'a>, default: ::std::option::Option<&'a [::capnp::Word]>) -> ::capnp::Result<Reader<'a,>> {
+        ::std::result::Result::Ok(::capnp::traits::FromStructReader::new(reader.get_struct(default)?))
+      }
+    }
+
+    impl <'a,> ::capnp::traits::IntoInternalStructReader<'a> for Reader<'a,>  {
+      fn into_internal_struct_reader(self) -> ::capnp::private::layout::StructReader<'a> {
+        self.reader
+      }
+    }
+
+    impl <'a,> ::capnp::traits::Imbue<'a> for Reader<'a,>  {
+      fn imbue(&mut self, cap_table: &'a ::capnp::private::layout::CapTable) {
+        self.reader.imbue(::capnp::private::layout::CapTableReader::Plain(cap_table))
+      }
+    }
+
+    impl <'a,> Reader<'a,>  {
+      pub fn reborrow(&self) -> Reader<> {
+        Reader { .. *self }
+      }
+
+      pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
+        self.reader.total_size()
+      }
+      #[inline]
+      pub fn get_space_address(self) -> ::capnp::Result<::capnp::text::Reader<'a>> {
+        ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(0), ::std::option::Option::None)
+      }
+      pub fn has_space_address(&self) -> bool {
+        !self.reader.get_pointer_field(0).is_null()
+      }
+      #[inline]
+      pub fn get_to_peer_name(self) -> ::capnp::Result<::capnp::text::Reader<'a>> {
+        ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(1), ::std::option::Option::None)
+      }
+      pub fn has_to_peer_name(&self) -> bool {
+        !self.reader.get_pointer_field(1).is_null()
+      }
+      #[inline]
+      pub fn get_from_peer_name(self) -> ::capnp::Result<::capnp::text::Reader<'a>> {
+        ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(2), ::std::option::Option::None)
+      }
+      pub fn has_from_peer_name(&self) -> bool {
+        !self.reader.get_pointer_field(2).is_null()
+      }
+      #[inline]
+      pub fn get_bundle(self) -> ::capnp::Result<::capnp::data::Reader<'a>> {
+        ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(3), ::std::option::Option::None)
+      }
+      pub fn has_bundle(&self) -> bool {
+        !self.reader.get_pointer_field(3).is_null()
+      }
+    }
+
+    pub struct Builder<'a> { builder: ::capnp::private::layout::StructBuilder<'a> }
+    impl <'a,> ::capnp::traits::HasStructSize for Builder<'a,>  {
+      #[inline]
+      fn struct_size() -> ::capnp::private::layout::StructSize { _private::STRUCT_SIZE }
+    }
+    impl <'a,> ::capnp::traits::HasTypeId for Builder<'a,>  {
+      #[inline]
+      fn type_id() -> u64 { _private::TYPE_ID }
+    }
+    impl <'a,> ::capnp::traits::FromStructBuilder<'a> for Builder<'a,>  {
+      fn new(builder: ::capnp::private::layout::StructBuilder<'a>) -> Builder<'a, > {
+        Builder { builder: builder,  }
+      }
+    }
+
+    impl <'a,> ::capnp::traits::ImbueMut<'a> for Builder<'a,>  {
+      fn imbue_mut(&mut self, cap_table: &'a mut ::capnp::private::layout::CapTable) {
+        self.builder.imbue(::capnp::private::layout::CapTableBuilder::Plain(cap_table))
+      }
+    }
+
+    impl <'a,> ::capnp::traits::FromPointerBuilder<'a> for Builder<'a,>  {
+      fn init_pointer(builder: ::capnp::private::layout::PointerBuilder<'a>, _size: u32) -> Builder<'a,> {
+        ::capnp::traits::FromStructBuilder::new(builder.init_struct(_private::STRUCT_SIZE))
+      }
+      fn get_from_pointer(builder: ::capnp::private::layout::PointerBuilder<'a>, default: ::std::option::Option<&'a [::capnp::Word]>) -> ::capnp::Result<Builder<'a,>> {
+        ::std::result::Result::Ok(::capnp::traits::FromStructBuilder::new(builder.get_struct(_private::STRUCT_SIZE, default)?))
+      }
+    }
+
+    impl <'a,> ::capnp::traits::SetPointerBuilder<Builder<'a,>> for Reader<'a,>  {
+      fn set_pointer_builder<'b>(pointer: ::capnp::private::layout::PointerBuilder<'b>, value: Reader<'a,>, canonicalize: bool) -> ::capnp::Result<()> { pointer.set_struct(&value.reader, canonicalize) }
+    }
+
+    impl <'a,> Builder<'a,>  {
+      pub fn into_reader(self) -> Reader<'a,> {
+        ::capnp::traits::FromStructReader::new(self.builder.into_reader())
+      }
+      pub fn reborrow(&mut self) -> Builder<> {
+        Builder { .. *self }
+      }
+      pub fn reborrow_as_reader(&self) -> Reader<> {
+        ::capnp::traits::FromStructReader::new(self.builder.into_reader())
+      }
+
+      pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
+        self.builder.into_reader().total_size()
+      }
+      #[inline]
+      pub fn get_space_address(self) -> ::capnp::Result<::capnp::text::Builder<'a>> {
+        ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(0), ::std::option::Option::None)
+      }
+      #[inline]
+      pub fn set_space_address(&mut self, value: ::capnp::text::Reader)  {
+        self.builder.get_pointer_field(0).set_text(value);
+      }
+      #[inline]
+      pub fn init_space_address(self, size: u32) -> ::capnp::text::Builder<'a> {
+        self.builder.get_pointer_field(0).init_text(size)
+      }
+      pub fn has_space_address(&self) -> bool {
+        !self.builder.get_pointer_field(0).is_null()
+      }
+      #[inline]
+      pub fn get_to_peer_name(self) -> ::capnp::Result<::capnp::text::Builder<'a>> {
+        ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(1), ::std::option::Option::None)
+      }
+      #[inline]
+      pub fn set_to_peer_name(&mut self, value: ::capnp::text::Reader)  {
+        self.builder.get_pointer_field(1).set_text(value);
+      }
+      #[inline]
+      pub fn init_to_peer_name(self, size: u32) -> ::capnp::text::Builder<'a> {
+        self.builder.get_pointer_field(1).init_text(size)
+      }
+      pub fn has_to_peer_name(&self) -> bool {
+        !self.builder.get_pointer_field(1).is_null()
+      }
+      #[inline]
+      pub fn get_from_peer_name(self) -> ::capnp::Result<::capnp::text::Builder<'a>> {
+        ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(2), ::std::option::Option::None)
+      }
+      #[inline]
+      pub fn set_from_peer_name(&mut self, value: ::capnp::text::Reader)  {
+        self.builder.get_pointer_field(2).set_text(value);
+      }
+      #[inline]
+      pub fn init_from_peer_name(self, size: u32) -> ::capnp::text::Builder<'a> {
+        self.builder.get_pointer_field(2).init_text(size)
+      }
+      pub fn has_from_peer_name(&self) -> bool {
+        !self.builder.get_pointer_field(2).is_null()
+      }
+      #[inline]
+      pub fn get_bundle(self) -> ::capnp::Result<::capnp::data::Builder<'a>> {
+        ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(3), ::std::option::Option::None)
+      }
+      #[inline]
+      pub fn set_bundle(&mut self, value: ::capnp::data::Reader)  {
+        self.builder.get_pointer_field(3).set_data(value);
+      }
+      #[inline]
+      pub fn init_bundle(self, size: u32) -> ::capnp::data::Builder<'a> {
+        self.builder.get_pointer_field(3).init_data(size)
+      }
+      pub fn has_bundle(&self) -> bool {
+        !self.builder.get_pointer_field(3).is_null()
+      }
+    }
+
+    pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
+    impl ::capnp::capability::FromTypelessPipeline for Pipeline {
+      fn new(typeless: ::capnp::any_pointer::Pipeline) -> Pipeline {
+        Pipeline { _typeless: typeless,  }
+      }
+    }
+    impl Pipeline  {
+    }
+    mod _private {
+      use capnp::private::layout;
+      pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 0, pointers: 4 };
+      pub const TYPE_ID: u64 = 0xf0c2_8a5d_673e_b419;
+    }
+  }
+
+  pub mod aspect_hash_list {
+    #[derive(Copy, Clone)]
+    pub struct Owned;
+    impl <'a> ::capnp::traits::Owned<'a> for Owned { type Reader = Reader<'a>; type Builder = Builder<'a>; }
+    impl <'a> ::capnp::traits::OwnedStruct<'a> for Owned { type Reader = Reader<'a>; type Builder = Builder<'a>; }
+    impl ::capnp::traits::Pipelined for Owned { type Pipeline = Pipeline; }
+
+    #[derive(Clone, Copy)]
+    pub struct Reader<'a> { reader: ::capnp::private::layout::StructReader<'a> }
+
+    impl <'a,> ::capnp::traits::HasTypeId for Reader<'a,>  {
+      #[inline]
+      fn type_id() -> u64 { _private::TYPE_ID }
+    }
+    impl <'a,> ::capnp::traits::FromStructReader<'a> for Reader<'a,>  {
+      fn new(reader: ::capnp::private::layout::StructReader<'a>) -> Reader<'a,> {
+        Reader { reader: reader,  }
+      }
+    }
+
+    impl <'a,> ::capnp::traits::FromPointerReader<'a> for Reader<'a,>  {
+      fn get_from_pointer(reader: &::capnp::private::layout::PointerReader<'a>, default: ::std::option::Option<&'a [::capnp::Word]>) -> ::capnp::Result<Reader<'a,>> {
+        ::std::result::Result::Ok(::capnp::traits::FromStructReader::new(reader.get_struct(default)?))
+      }
+    }
+
+    impl <'a,> ::capnp::traits::IntoInternalStructReader<'a> for Reader<'a,>  {
+      fn into_internal_struct_reader(self) -> ::capnp::private::layout::StructReader<'a> {
+        self.reader
+      }
+    }
+
+    impl <'a,> ::capnp::traits::Imbue<'a> for Reader<'a,>  {
+      fn imbue(&mut self, cap_table: &'a ::capnp::private::layout::CapTable) {
+        self.reader.imbue(::capnp::private::layout::CapTableReader::Plain(cap_table))
+      }
+    }
+
+    impl <'a,> Reader<'a,>  {
+      pub fn reborrow(&self) -> Reader<> {
+        Reader { .. *self }
+      }
+
+      pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
+        self.reader.total_size()
+      }
+      #[inline]
+      pub fn get_entry_address(self) -> ::capnp::Result<::capnp::data::Reader<'a>> {
+        ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(0), ::std::option::Option::None)
+      }
+      pub fn has_entry_address(&self) -> bool {
+        !self.reader.get_pointer_field(0).is_null()
+      }
+      #[inline]
+      pub fn get_aspect_hash_list(self) -> ::capnp::Result<::capnp::data_list::Reader<'a>> {
+        ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(1), ::std::option::Option::None)
+      }
+      pub fn has_aspect_hash_list(&self) -> bool {
+        !self.reader.get_pointer_field(1).is_null()
+      }
+    }
+
+    pub struct Builder<'a> { builder: ::capnp::private::layout::StructBuilder<'a> }
+    impl <'a,> ::capnp::traits::HasStructSize for Builder<'a,>  {
+      #[inline]
+      fn struct_size() -> ::capnp::private::layout::StructSize { _private::STRUCT_SIZE }
+    }
+    impl <'a,> ::capnp::traits::HasTypeId for Builder<'a,>  {
+      #[inline]
+      fn type_id() -> u64 { _private::TYPE_ID }
+    }
+    impl <'a,> ::capnp::traits::FromStructBuilder<'a> for Builder<'a,>  {
+      fn new(builder: ::capnp::private::layout::StructBuilder<'a>) -> Builder<'a, > {
+        Builder { builder: builder,  }
+      }
+    }
+
+    impl <'a,> ::capnp::traits::ImbueMut<'a> for Builder<'a,>  {
+      fn imbue_mut(&mut self, cap_table: &'a mut ::capnp::private::layout::CapTable) {
+        self.builder.imbue(::capnp::private::layout::CapTableBuilder::Plain(cap_table))
+      }
+    }
+
+    impl <'a,> ::capnp::traits::FromPointerBuilder<'a> for Builder<'a,>  {
+      fn init_pointer(builder: ::capnp::private::layout::PointerBuilder<'a>, _size: u32) -> Builder<'a,> {
+        ::capnp::traits::FromStructBuilder::new(builder.init_struct(_private::STRUCT_SIZE))
+      }
+      fn get_from_pointer(builder: ::capnp::private::layout::PointerBuilder<'a>, default: ::std::option::Option<&'a [::capnp::Word]>) -> ::capnp::Result<Builder<'a,>> {
+        ::std::result::Result::Ok(::capnp::traits::FromStructBuilder::new(builder.get_struct(_private::STRUCT_SIZE, default)?))
+      }
+    }
+
+    impl <'a,> ::capnp::traits::SetPointerBuilder<Builder<'a,>> for Reader<'a,>  {
+      fn set_pointer_builder<'b>(pointer: ::capnp::private::layout::PointerBuilder<'b>, value: Reader<'a,>, canonicalize: bool) -> ::capnp::Result<()> { pointer.set_struct(&value.reader, canonicalize) }
+    }
+
+    impl <'a,> Builder<'a,>  {
+      pub fn into_reader(self) -> Reader<'a,> {
+        ::capnp::traits::FromStructReader::new(self.builder.into_reader())
+      }
+      pub fn reborrow(&mut self) -> Builder<> {
+        Builder { .. *self }
+      }
+      pub fn reborrow_as_reader(&self) -> Reader<> {
+        ::capnp::traits::FromStructReader::new(self.builder.into_reader())
+      }
+
+      pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
+        self.builder.into_reader().total_size()
+      }
+      #[inline]
+      pub fn get_entry_address(self) -> ::capnp::Result<::capnp::data::Builder<'a>> {
+        ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(0), ::std::option::Option::None)
+      }
+      #[inline]
+      pub fn set_entry_address(&mut self, value: ::capnp::data::Reader)  {
+        self.builder.get_pointer_field(0).set_data(value);
+      }
+      #[inline]
+      pub fn init_entry_address(self, size: u32) -> ::capnp::data::Builder<'a> {
+        self.builder.get_pointer_field(0).init_data(size)
+      }
+      pub fn has_entry_address(&self) -> bool {
+        !self.builder.get_pointer_field(0).is_null()
+      }
+      #[inline]
+      pub fn get_aspect_hash_list(self) -> ::capnp::Result<::capnp::data_list::Builder<'a>> {
+        ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(1), ::std::option::Option::None)
+      }
+      #[inline]
+      pub fn set_aspect_hash_list(&mut self, value: ::capnp::data_list::Reader<'a>) -> ::capnp::Result<()> {
+        ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(1), value, false)
+      }
+      #[inline]
+      pub fn init_aspect_hash_list(self, size: u32) -> ::capnp::data_list::Builder<'a> {
+        ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(1), size)
+      }
+      pub fn has_aspect_hash_list(&self) -> bool {
+        !self.builder.get_pointer_field(1).is_null()
+      }
+    }
+
+    pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
+    impl ::capnp::capability::FromTypelessPipeline for Pipeline {
+      fn new(typeless: ::capnp::any_pointer::Pipeline) -> Pipeline {
+        Pipeline { _typeless: typeless,  }
+      }
+    }
+    impl Pipeline  {
+    }
+    mod _private {
+      use capnp::private::layout;
+      pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 0, pointers: 2 };
+      pub const TYPE_ID: u64 = 0xc3f9_8bc7_9025_c948;
+    }
+  }
+
+  pub mod aspect_data_list {
+    #[derive(Copy, Clone)]
+    pub struct Owned;
+    impl <'a> ::capnp::traits::Owned<'a> for Owned { type Reader = Reader<'a>; type Builder = Builder<'a>; }
+    impl <'a> ::capnp::traits::OwnedStruct<'a> for Owned { type Reader = Reader<'a>; type Builder = Builder<'a>; }
+    impl ::capnp::traits::Pipelined for Owned { type Pipeline = Pipeline; }
+
+    #[derive(Clone, Copy)]
+    pub struct Reader<'a> { reader: ::capnp::private::layout::StructReader<'a> }
+
+    impl <'a,> ::capnp::traits::HasTypeId for Reader<'a,>  {
+      #[inline]
+      fn type_id() -> u64 { _private::TYPE_ID }
+    }
+    impl <'a,> ::capnp::traits::FromStructReader<'a> for Reader<'a,>  {
+      fn new(reader: ::capnp::private::layout::StructReader<'a>) -> Reader<'a,> {
+        Reader { reader: reader,  }
+      }
+    }
+
+    impl <'a,> ::capnp::traits::FromPointerReader<'a> for Reader<'a,>  {
+      fn get_from_pointer(reader: &::capnp::private::layout::PointerReader<'a>, default: ::std::option::Option<&'a [::capnp::Word]>) -> ::capnp::Result<Reader<'a,>> {
+        ::std::result::Result::Ok(::capnp::traits::FromStructReader::new(reader.get_struct(default)?))
+      }
+    }
+
+    impl <'a,> ::capnp::traits::IntoInternalStructReader<'a> for Reader<'a,>  {
+      fn into_internal_struct_reader(self) -> ::capnp::private::layout::StructReader<'a> {
+        self.reader
+      }
+    }
+
+    impl <'a,> ::capnp::traits::Imbue<'a> for Reader<'a,>  {
+      fn imbue(&mut self, cap_table: &'a ::capnp::private::layout::CapTable) {
+        self.reader.imbue(::capnp::private::layout::CapTableReader::Plain(cap_table))
+      }
+    }
+
+    impl <'a,> Reader<'a,>  {
+      pub fn reborrow(&self) -> Reader<> {
+        Reader { .. *self }
+      }
+
+      pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
+        self.reader.total_size()
+      }
+      #[inline]
+      pub fn get_entry_address(self) -> ::capnp::Result<::capnp::data::Reader<'a>> {
+        ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(0), ::std::option::Option::None)
+      }
+      pub fn has_entry_address(&self) -> bool {
+        !self.reader.get_pointer_field(0).is_null()
+      }
+      #[inline]
+      pub fn get_aspect_data_list(self) -> ::capnp::Result<::capnp::data_list::Reader<'a>> {
+        ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(1), ::std::option::Option::None)
+      }
+      pub fn has_aspect_data_list(&self) -> bool {
+        !self.reader.get_pointer_field(1).is_null()
+      }
+    }
+
+    pub struct Builder<'a> { builder: ::capnp::private::layout::StructBuilder<'a> }
+    impl <'a,> ::capnp::traits::HasStructSize for Builder<'a,>  {
+      #[inline]
+      fn struct_size() -> ::capnp::private::layout::StructSize { _private::STRUCT_SIZE }
+    }
+    impl <'a,> ::capnp::traits::HasTypeId for Builder<'a,>  {
+      #[inline]
+      fn type_id() -> u64 { _private::TYPE_ID }
+    }
+    impl <'a,> ::capnp::traits::FromStructBuilder<'a> for Builder<'a,>  {
+      fn new(builder: ::capnp::private::layout::StructBuilder<'a>) -> Builder<'a, > {
+        Builder { builder: builder,  }
+      }
+    }
+
+    impl <'a,> ::capnp::traits::ImbueMut<'a> for Builder<'a,>  {
+      fn imbue_mut(&mut self, cap_table: &'a mut ::capnp::private::layout::CapTable) {
+        self.builder.imbue(::capnp::private::layout::CapTableBuilder::Plain(cap_table))
+      }
+    }
+
+    impl <'a,> ::capnp::traits::FromPointerBuilder<'a> for Builder<'a,>  {
+      fn init_pointer(builder: ::capnp::private::layout::PointerBuilder<'a>, _size: u32) -> Builder<'a,> {
+        ::capnp::traits::FromStructBuilder::new(builder.init_struct(_private::STRUCT_SIZE))
+      }
+      fn get_from_pointer(builder: ::capnp::private::layout::PointerBuilder<'a>, default: ::std::option::Option<&'a [::capnp::Word]>) -> ::capnp::Result<Builder<'a,>> {
+        ::std::result::Result::Ok(::capnp::traits::FromStructBuilder::new(builder.get_struct(_private::STRUCT_SIZE, default)?))
+      }
+    }
+
+    impl <'a,> ::capnp::traits::SetPointerBuilder<Builder<'a,>> for Reader<'a,>  {
+      fn set_pointer_builder<'b>(pointer: ::capnp::private::layout::PointerBuilder<'b>, value: Reader<'a,>, canonicalize: bool) -> ::capnp::Result<()> { pointer.set_struct(&value.reader, canonicalize) }
+    }
+
+    impl <'a,> Builder<'a,>  {
+      pub fn into_reader(self) -> Reader<'a,> {
+        ::capnp::traits::FromStructReader::new(self.builder.into_reader())
+      }
+      pub fn reborrow(&mut self) -> Builder<> {
+        Builder { .. *self }
+      }
+      pub fn reborrow_as_reader(&self) -> Reader<> {
+        ::capnp::traits::FromStructReader::new(self.builder.into_reader())
+      }
+
+      pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
+        self.builder.into_reader().total_size()
+      }
+      #[inline]
+      pub fn get_entry_address(self) -> ::capnp::Result<::capnp::data::Builder<'a>> {
+        ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(0), ::std::option::Option::None)
+      }
+      #[inline]
+      pub fn set_entry_address(&mut self, value: ::capnp::data::Reader)  {
+        self.builder.get_pointer_field(0).set_data(value);
+      }
+      #[inline]
+      pub fn init_entry_address(self, size: u32) -> ::capnp::data::Builder<'a> {
+        self.builder.get_pointer_field(0).init_data(size)
+      }
+      pub fn has_entry_address(&self) -> bool {
+        !self.builder.get_pointer_field(0).is_null()
+      }
+      #[inline]
+      pub fn get_aspect_data_list(self) -> ::capnp::Result<::capnp::data_list::Builder<'a>> {
+        ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(1), ::std::option::Option::None)
+      }
+      #[inline]
+      pub fn set_aspect_data_list(&mut self, value: ::capnp::data_list::Reader<'a>) -> ::capnp::Result<()> {
+        ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(1), value, false)
+      }
+      #[inline]
+      pub fn init_aspect_data_list(self, size: u32) -> ::capnp::data_list::Builder<'a> {
+        ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(1), size)
+      }
+      pub fn has_aspect_data_list(&self) -> bool {
+        !self.builder.get_pointer_field(1).is_null()
+      }
+    }
+
+    pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
+    impl ::capnp::capability::FromTypelessPipeline for Pipeline {
+      fn new(typeless: ::capnp::any_pointer::Pipeline) -> Pipeline {
+        Pipeline { _typeless: typeless,  }
+      }
+    }
+    impl Pipeline  {
+    }
+    mod _private {
+      use capnp::private::layout;
+      pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 0, pointers: 2 };
+      pub const TYPE_ID: u64 = 0xfaab_efdd_33da_a362;
+    }
+  }
+
+  pub mod aspect_constraint_arc {
+    #[derive(Copy, Clone)]
+    pub struct Owned;
+    impl <'a> ::capnp::traits::Owned<'a> for Owned { type Reader = Reader<'a>; type Builder = Builder<'a>; }
+    impl <'a> ::capnp::traits::OwnedStruct<'a> for Owned { type Reader = Reader<'a>; type Builder = Builder<'a>; }
+    impl ::capnp::traits::Pipelined for Owned { type Pipeline = Pipeline; }
+
+    #[derive(Clone, Copy)]
+    pub struct Reader<'a> { reader: ::capnp::private::layout::StructReader<'a> }
+
+    impl <'a,> ::capnp::traits::HasTypeId for Reader<'a,>  {
+      #[inline]
+      fn type_id() -> u64 { _private::TYPE_ID }
+    }
+    impl <'a,> ::capnp::traits::FromStructReader<'a> for Reader<'a,>  {
+      fn new(reader: ::capnp::private::layout::StructReader<'a>) -> Reader<'a,> {
+        Reader { reader: reader,  }
+      }
+    }
+
+    impl <'a,> ::capnp::traits::FromPointerReader<'a> for Reader<'a,>  {
+      fn get_from_pointer(reader: &::capnp::private::layout::PointerReader<'a>, default: ::std::option::Option<&'a [::capnp::Word]>) -> ::capnp::Result<Reader<'a,>> {
+        ::std::result::Result::Ok(::capnp::traits::FromStructReader::new(reader.get_struct(default)?))
+      }
+    }
+
+    impl <'a,> ::capnp::traits::IntoInternalStructReader<'a> for Reader<'a,>  {
+      fn into_internal_struct_reader(self) -> ::capnp::private::layout::StructReader<'a> {
+        self.reader
+      }
+    }
+
+    impl <'a,> ::capnp::traits::Imbue<'a> for Reader<'a,>  {
+      fn imbue(&mut self, cap_table: &'a ::capnp::private::layout::CapTable) {
+        self.reader.imbue(::capnp::private::layout::CapTableReader::Plain(cap_table))
+      }
+    }
+
+    impl <'a,> Reader<'a,>  {
+      pub fn reborrow(&self) -> Reader<> {
+        Reader { .. *self }
+      }
+
+      pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
+        self.reader.total_size()
+      }
+      #[inline]
+      pub fn get_arc_start(self) -> u32 {
+        self.reader.get_data_field::<u32>(0)
+      }
+      #[inline]
+      pub fn get_arc_end(self) -> u32 {
+        self.reader.get_data_field::<u32>(1)
+      }
+      #[inline]
+      pub fn get_arc_empty(self) -> bool {
+        self.reader.get_bool_field(64)
+      }
+    }
+
+    pub struct Builder<'a> { builder: ::capnp::private::layout::StructBuilder<'a> }
+    impl <'a,> ::capnp::traits::HasStructSize for Builder<'a,>  {
+      #[inline]
+      fn struct_size() -> ::capnp::private::layout::StructSize { _private::STRUCT_SIZE }
+    }
+    impl <'a,> ::capnp::traits::HasTypeId for Builder<'a,>  {
+      #[inline]
+      fn type_id() -> u64 { _private::TYPE_ID }
+    }
+    impl <'a,> ::capnp::traits::FromStructBuilder<'a> for Builder<'a,>  {
+      fn new(builder: ::capnp::private::layout::StructBuilder<'a>) -> Builder<'a, > {
+        Builder { builder: builder,  }
+      }
+    }
+
+    impl <'a,> ::capnp::traits::ImbueMut<'a> for Builder<'a,>  {
+      fn imbue_mut(&mut self, cap_table: &'a mut ::capnp::private::layout::CapTable) {
+        self.builder.imbue(::capnp::private::layout::CapTableBuilder::Plain(cap_table))
+      }
+    }
+
+    impl <'a,> ::capnp::traits::FromPointerBuilder<'a> for Builder<'a,>  {
+      fn init_pointer(builder: ::capnp::private::layout::PointerBuilder<'a>, _size: u32) -> Builder<'a,> {
+        ::capnp::traits::FromStructBuilder::new(builder.init_struct(_private::STRUCT_SIZE))
+      }
+      fn get_from_pointer(builder: ::capnp::private::layout::PointerBuilder<'a>, default: ::std::option::Option<&'a [::capnp::Word]>) -> ::capnp::Result<Builder<'a,>> {
+        ::std::result::Result::Ok(::capnp::traits::FromStructBuilder::new(builder.get_struct(_private::STRUCT_SIZE, default)?))
+      }
+    }
+
+    impl <'a,> ::capnp::traits::SetPointerBuilder<Builder<'a,>> for Reader<'a,>  {
+      fn set_pointer_builder<'b>(pointer: ::capnp::private::layout::PointerBuilder<'b>, value: Reader<'a,>, canonicalize: bool) -> ::capnp::Result<()> { pointer.set_struct(&value.reader, canonicalize) }
+    }
+
+    impl <'a,> Builder<'a,>  {
+      pub fn into_reader(self) -> Reader<'a,> {
+        ::capnp::traits::FromStructReader::new(self.builder.into_reader())
+      }
+      pub fn reborrow(&mut self) -> Builder<> {
+        Builder { .. *self }
+      }
+      pub fn reborrow_as_reader(&self) -> Reader<> {
+        ::capnp::traits::FromStructReader::new(self.builder.into_reader())
+      }
+
+      pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
+        self.builder.into_reader().total_size()
+      }
+      #[inline]
+      pub fn get_arc_start(self) -> u32 {
+        self.builder.get_data_field::<u32>(0)
+      }
+      #[inline]
+      pub fn set_arc_start(&mut self, value: u32)  {
+        self.builder.set_data_field::<u32>(0, value);
+      }
+      #[inline]
+      pub fn get_arc_end(self) -> u32 {
+        self.builder.get_data_field::<u32>(1)
+      }
+      #[inline]
+      pub fn set_arc_end(&mut self, value: u32)  {
+        self.builder.set_data_field::<u32>(1, value);
+      }
+      #[inline]
+      pub fn get_arc_empty(self) -> bool {
+        self.builder.get_bool_field(64)
+      }
+      #[inline]
+      pub fn set_arc_empty(&mut self, value: bool)  {
+        self.builder.set_bool_field(64, value);
+      }
+    }
+
+    pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
+    impl ::capnp::capability::FromTypelessPipeline for Pipeline {
+      fn new(typeless: ::capnp::any_pointer::Pipeline) -> Pipeline {
+        Pipeline { _typeless: typeless,  }
+      }
+    }
+    impl Pipeline  {
+    }
+    mod _private {
+      use capnp::private::layout;
+      pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 2, pointers: 0 };
       pub const TYPE_ID: u64 = 0xc139_1ca6_5ff8_aba5;
     }
   }
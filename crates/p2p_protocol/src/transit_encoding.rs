@@ -0,0 +1,737 @@
+//! Rust representations of the `transit_encoding.capnp` handshake messages.
+//!
+//! Unlike `P2pMessage`, these are not wrapped in a root union:
+//! the handshake has a well-defined sequence, so the receiver always knows
+//! which struct to expect next. Anything unexpected should drop the connection.
+
+use crate::{error::P2pResult, transit_encoding_capnp};
+
+/// protocol identifier sent in `MsgStep1FromConnect`
+pub const TRANSIT_ENCODING_MAGIC: u16 = 0xa86c;
+
+/// well-known reasons for halting a handshake
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HaltReasonCode {
+    /// default if reason is not well-known, or if remote is using a newer proto
+    Unspecified,
+    BadMagic,
+    BadEncoding,
+    BadNetworkId,
+    KxDecodeFail,
+    UnexpectedSigPubKey,
+    BadSignature,
+}
+
+/// tell the remote why we will not proceed with the handshake
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Halt {
+    /// code indicating if reason is well-known
+    pub reason_code: HaltReasonCode,
+    /// the specific details of the halt reason
+    pub reason_text: String,
+}
+
+/// how a connection's stream should be encoded
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransitEncodingType {
+    /// default, you may get this if someone's using a newer protocol
+    Unknown,
+    /// un-encrypted json encoded
+    OpenJson,
+    /// un-encrypted packed capnproto encoded
+    OpenPacked,
+    /// libsodium-encrypted json encoded
+    SodiumJson,
+    /// libsodium-encrypted packed capnproto encoded
+    SodiumPacked,
+}
+
+impl TransitEncodingType {
+    /// is the stream encrypted with libsodium?
+    pub fn is_sodium(self) -> bool {
+        match self {
+            TransitEncodingType::SodiumJson | TransitEncodingType::SodiumPacked => true,
+            _ => false,
+        }
+    }
+
+    /// are messages json encoded (rather than packed capnproto)?
+    pub fn is_json(self) -> bool {
+        match self {
+            TransitEncodingType::OpenJson | TransitEncodingType::SodiumJson => true,
+            _ => false,
+        }
+    }
+}
+
+/// how payloads are compressed once a connection is established
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompressionType {
+    /// payloads are sent as is
+    None,
+    /// raw deflate (RFC 1951)
+    Deflate,
+}
+
+impl Default for CompressionType {
+    fn default() -> Self {
+        CompressionType::None
+    }
+}
+
+/// first message sent by the connecting node
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgStep1FromConnect {
+    /// protocol identifier, should be `TRANSIT_ENCODING_MAGIC`
+    pub magic: u16,
+    /// the connecting node's most preferred encoding
+    pub encoding: TransitEncodingType,
+    /// the network the connecting node is trying to join
+    pub network_id: Vec<u8>,
+    /// the connecting node's key exchange public key
+    pub kx_pub_key: Vec<u8>,
+    /// every encoding the connecting node accepts, in order of preference
+    pub encodings: Vec<TransitEncodingType>,
+}
+
+/// data for `MsgStep2FromListen::Continue`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgStep2Continue {
+    /// the listening node's key exchange public key
+    pub kx_pub_key: Vec<u8>,
+    /// nonce used to encrypt `kx_secret`
+    pub kx_nonce: Vec<u8>,
+    /// an encrypted `MsgStep2FromListenKxEncoded`
+    pub kx_secret: Vec<u8>,
+    /// the encoding picked from the connecting node's preferences
+    pub encoding: TransitEncodingType,
+}
+
+/// the listening node accepts or rejects a `MsgStep1FromConnect`,
+/// always packed capnproto
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MsgStep2FromListen {
+    Halt(Halt),
+    Continue(MsgStep2Continue),
+}
+
+/// encrypted into `MsgStep2Continue::kx_secret`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgStep2FromListenKxEncoded {
+    pub padding: Vec<u8>,
+    /// the listening node's signature public key
+    pub sig_pub_key: Vec<u8>,
+    /// pure entropy listening-to-connecting session key
+    pub l2c_session_key: Vec<u8>,
+}
+
+/// data for `MsgStep3FromConnect::Continue`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgStep3Continue {
+    /// nonce used to encrypt `kx_secret`
+    pub kx_nonce: Vec<u8>,
+    /// an encrypted `MsgStep3FromConnectKxEncoded`
+    pub kx_secret: Vec<u8>,
+}
+
+/// the connecting node accepts or rejects a `MsgStep2FromListen`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MsgStep3FromConnect {
+    Halt(Halt),
+    Continue(MsgStep3Continue),
+}
+
+/// encrypted into `MsgStep3Continue::kx_secret`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgStep3FromConnectKxEncoded {
+    pub padding: Vec<u8>,
+    /// the connecting node's signature public key
+    pub sig_pub_key: Vec<u8>,
+    /// pure entropy connecting-to-listening session key
+    pub c2l_session_key: Vec<u8>,
+    /// signature of the l2c session key, proving we own the sig secret key
+    pub c2l_signature: Vec<u8>,
+    /// compressions the connecting node accepts, in order of preference
+    #[serde(default)]
+    pub compressions: Vec<CompressionType>,
+}
+
+/// encrypted with nonce-0 of the l2c session key
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgStep4FromListenEncoded {
+    pub padding: Vec<u8>,
+    /// signature of the c2l session key, proving we own the sig secret key
+    pub l2c_signature: Vec<u8>,
+    /// the compression picked from the connecting node's preferences
+    #[serde(default)]
+    pub compression: CompressionType,
+}
+
+/// encrypted with nonce-0 of the c2l session key
+/// once received, the connection is established
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MsgStep5FromConnectEncoded {
+    pub padding: Vec<u8>,
+}
+
+/// framing for data exchanged after the handshake,
+/// encrypted with the session keys and sequential nonces
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EncodedMessage {
+    pub padding: Vec<u8>,
+    pub content: Vec<u8>,
+}
+
+// -- capnp helpers -- //
+
+fn read_packed(bytes: &[u8]) -> P2pResult<capnp::message::Reader<capnp::serialize::OwnedSegments>> {
+    Ok(capnp::serialize_packed::read_message(
+        &mut std::io::Cursor::new(bytes),
+        capnp::message::ReaderOptions::new(),
+    )?)
+}
+
+fn write_packed(message: &capnp::message::Builder<capnp::message::HeapAllocator>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    capnp::serialize_packed::write_message(&mut bytes, message).unwrap();
+    bytes
+}
+
+fn read_halt(halt: transit_encoding_capnp::halt::Reader) -> P2pResult<Halt> {
+    use transit_encoding_capnp::halt::ReasonCode;
+    Ok(Halt {
+        // newer protos may send codes we do not know about
+        reason_code: match halt.get_reason_code() {
+            Ok(ReasonCode::BadMagic) => HaltReasonCode::BadMagic,
+            Ok(ReasonCode::BadEncoding) => HaltReasonCode::BadEncoding,
+            Ok(ReasonCode::BadNetworkId) => HaltReasonCode::BadNetworkId,
+            Ok(ReasonCode::KxDecodeFail) => HaltReasonCode::KxDecodeFail,
+            Ok(ReasonCode::UnexpectedSigPubKey) => HaltReasonCode::UnexpectedSigPubKey,
+            Ok(ReasonCode::BadSignature) => HaltReasonCode::BadSignature,
+            Ok(ReasonCode::Unspecified) | Err(_) => HaltReasonCode::Unspecified,
+        },
+        reason_text: halt.get_reason_text()?.to_string(),
+    })
+}
+
+fn write_halt(mut builder: transit_encoding_capnp::halt::Builder, halt: &Halt) {
+    use transit_encoding_capnp::halt::ReasonCode;
+    builder.set_reason_code(match halt.reason_code {
+        HaltReasonCode::Unspecified => ReasonCode::Unspecified,
+        HaltReasonCode::BadMagic => ReasonCode::BadMagic,
+        HaltReasonCode::BadEncoding => ReasonCode::BadEncoding,
+        HaltReasonCode::BadNetworkId => ReasonCode::BadNetworkId,
+        HaltReasonCode::KxDecodeFail => ReasonCode::KxDecodeFail,
+        HaltReasonCode::UnexpectedSigPubKey => ReasonCode::UnexpectedSigPubKey,
+        HaltReasonCode::BadSignature => ReasonCode::BadSignature,
+    });
+    builder.set_reason_text(&halt.reason_text);
+}
+
+fn read_encoding(
+    encoding: Result<transit_encoding_capnp::msg_step1_from_connect::Encoding, capnp::NotInSchema>,
+) -> TransitEncodingType {
+    use transit_encoding_capnp::msg_step1_from_connect::Encoding;
+    match encoding {
+        Ok(Encoding::OpenJson) => TransitEncodingType::OpenJson,
+        Ok(Encoding::OpenPacked) => TransitEncodingType::OpenPacked,
+        Ok(Encoding::SodiumJson) => TransitEncodingType::SodiumJson,
+        Ok(Encoding::SodiumPacked) => TransitEncodingType::SodiumPacked,
+        Ok(Encoding::Unknown) | Err(_) => TransitEncodingType::Unknown,
+    }
+}
+
+fn to_capnp_encoding(
+    encoding: TransitEncodingType,
+) -> transit_encoding_capnp::msg_step1_from_connect::Encoding {
+    use transit_encoding_capnp::msg_step1_from_connect::Encoding;
+    match encoding {
+        TransitEncodingType::Unknown => Encoding::Unknown,
+        TransitEncodingType::OpenJson => Encoding::OpenJson,
+        TransitEncodingType::OpenPacked => Encoding::OpenPacked,
+        TransitEncodingType::SodiumJson => Encoding::SodiumJson,
+        TransitEncodingType::SodiumPacked => Encoding::SodiumPacked,
+    }
+}
+
+fn read_compression(
+    compression: Result<transit_encoding_capnp::Compression, capnp::NotInSchema>,
+) -> Option<CompressionType> {
+    use transit_encoding_capnp::Compression;
+    match compression {
+        Ok(Compression::None) => Some(CompressionType::None),
+        Ok(Compression::Deflate) => Some(CompressionType::Deflate),
+        // a newer proto may offer compressions we do not know about
+        Err(_) => None,
+    }
+}
+
+fn to_capnp_compression(compression: CompressionType) -> transit_encoding_capnp::Compression {
+    use transit_encoding_capnp::Compression;
+    match compression {
+        CompressionType::None => Compression::None,
+        CompressionType::Deflate => Compression::Deflate,
+    }
+}
+
+impl Halt {
+    /// create a new halt message
+    pub fn new(reason_code: HaltReasonCode, reason_text: &str) -> Self {
+        Self {
+            reason_code,
+            reason_text: reason_text.to_string(),
+        }
+    }
+
+    /// parse raw bytes into a Halt
+    pub fn from_bytes(bytes: &[u8]) -> P2pResult<Self> {
+        let message = read_packed(bytes)?;
+        read_halt(message.get_root::<transit_encoding_capnp::halt::Reader>()?)
+    }
+
+    /// generate encoded bytes for this Halt
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut message = capnp::message::Builder::new_default();
+        write_halt(message.init_root(), self);
+        write_packed(&message)
+    }
+}
+
+impl MsgStep1FromConnect {
+    /// parse raw bytes into a MsgStep1FromConnect
+    pub fn from_bytes(bytes: &[u8]) -> P2pResult<Self> {
+        let message = read_packed(bytes)?;
+        let step1 = message.get_root::<transit_encoding_capnp::msg_step1_from_connect::Reader>()?;
+        let encodings = step1.get_encodings()?;
+        Ok(Self {
+            magic: step1.get_magic(),
+            encoding: read_encoding(step1.get_encoding()),
+            network_id: step1.get_network_id()?.to_vec(),
+            kx_pub_key: step1.get_kx_pub_key()?.to_vec(),
+            // keep unknown entries, the offered list is signed as received
+            encodings: (0..encodings.len())
+                .map(|i| read_encoding(encodings.get(i)))
+                .collect(),
+        })
+    }
+
+    /// generate encoded bytes for this MsgStep1FromConnect
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut message = capnp::message::Builder::new_default();
+        {
+            let mut step1 =
+                message.init_root::<transit_encoding_capnp::msg_step1_from_connect::Builder>();
+            step1.set_magic(self.magic);
+            step1.set_encoding(to_capnp_encoding(self.encoding));
+            step1.set_network_id(&self.network_id);
+            step1.set_kx_pub_key(&self.kx_pub_key);
+            let mut encodings = step1.init_encodings(self.encodings.len() as u32);
+            for (i, encoding) in self.encodings.iter().enumerate() {
+                encodings.set(i as u32, to_capnp_encoding(*encoding));
+            }
+        }
+        write_packed(&message)
+    }
+}
+
+impl MsgStep2FromListen {
+    /// parse raw bytes into a MsgStep2FromListen
+    pub fn from_bytes(bytes: &[u8]) -> P2pResult<Self> {
+        use transit_encoding_capnp::msg_step2_from_listen::Which;
+        let message = read_packed(bytes)?;
+        let step2 = message.get_root::<transit_encoding_capnp::msg_step2_from_listen::Reader>()?;
+        match step2.which() {
+            Ok(Which::Halt(Ok(halt))) => Ok(MsgStep2FromListen::Halt(read_halt(halt)?)),
+            Ok(Which::Continue(Ok(cont))) => Ok(MsgStep2FromListen::Continue(MsgStep2Continue {
+                kx_pub_key: cont.get_kx_pub_key()?.to_vec(),
+                kx_nonce: cont.get_kx_nonce()?.to_vec(),
+                kx_secret: cont.get_kx_secret()?.to_vec(),
+                encoding: read_encoding(cont.get_encoding()),
+            })),
+            _ => Err("failed to decode".into()),
+        }
+    }
+
+    /// generate encoded bytes for this MsgStep2FromListen
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut message = capnp::message::Builder::new_default();
+        {
+            let step2 =
+                message.init_root::<transit_encoding_capnp::msg_step2_from_listen::Builder>();
+            match self {
+                MsgStep2FromListen::Halt(halt) => write_halt(step2.init_halt(), halt),
+                MsgStep2FromListen::Continue(cont) => {
+                    let mut builder = step2.init_continue();
+                    builder.set_kx_pub_key(&cont.kx_pub_key);
+                    builder.set_kx_nonce(&cont.kx_nonce);
+                    builder.set_kx_secret(&cont.kx_secret);
+                    builder.set_encoding(to_capnp_encoding(cont.encoding));
+                }
+            }
+        }
+        write_packed(&message)
+    }
+}
+
+impl MsgStep2FromListenKxEncoded {
+    /// parse raw bytes into a MsgStep2FromListenKxEncoded
+    pub fn from_bytes(bytes: &[u8]) -> P2pResult<Self> {
+        let message = read_packed(bytes)?;
+        let kx = message
+            .get_root::<transit_encoding_capnp::msg_step2_from_listen_kx_encoded::Reader>()?;
+        Ok(Self {
+            padding: kx.get_padding()?.to_vec(),
+            sig_pub_key: kx.get_sig_pub_key()?.to_vec(),
+            l2c_session_key: kx.get_l2c_session_key()?.to_vec(),
+        })
+    }
+
+    /// generate encoded bytes for this MsgStep2FromListenKxEncoded
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut message = capnp::message::Builder::new_default();
+        {
+            let mut kx = message
+                .init_root::<transit_encoding_capnp::msg_step2_from_listen_kx_encoded::Builder>();
+            kx.set_padding(&self.padding);
+            kx.set_sig_pub_key(&self.sig_pub_key);
+            kx.set_l2c_session_key(&self.l2c_session_key);
+        }
+        write_packed(&message)
+    }
+}
+
+impl MsgStep3FromConnect {
+    /// parse raw bytes into a MsgStep3FromConnect
+    pub fn from_bytes(bytes: &[u8]) -> P2pResult<Self> {
+        use transit_encoding_capnp::msg_step3_from_connect::Which;
+        let message = read_packed(bytes)?;
+        let step3 = message.get_root::<transit_encoding_capnp::msg_step3_from_connect::Reader>()?;
+        match step3.which() {
+            Ok(Which::Halt(Ok(halt))) => Ok(MsgStep3FromConnect::Halt(read_halt(halt)?)),
+            Ok(Which::Continue(Ok(cont))) => Ok(MsgStep3FromConnect::Continue(MsgStep3Continue {
+                kx_nonce: cont.get_kx_nonce()?.to_vec(),
+                kx_secret: cont.get_kx_secret()?.to_vec(),
+            })),
+            _ => Err("failed to decode".into()),
+        }
+    }
+
+    /// generate encoded bytes for this MsgStep3FromConnect
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut message = capnp::message::Builder::new_default();
+        {
+            let step3 =
+                message.init_root::<transit_encoding_capnp::msg_step3_from_connect::Builder>();
+            match self {
+                MsgStep3FromConnect::Halt(halt) => write_halt(step3.init_halt(), halt),
+                MsgStep3FromConnect::Continue(cont) => {
+                    let mut builder = step3.init_continue();
+                    builder.set_kx_nonce(&cont.kx_nonce);
+                    builder.set_kx_secret(&cont.kx_secret);
+                }
+            }
+        }
+        write_packed(&message)
+    }
+}
+
+impl MsgStep3FromConnectKxEncoded {
+    /// parse raw bytes into a MsgStep3FromConnectKxEncoded
+    pub fn from_bytes(bytes: &[u8]) -> P2pResult<Self> {
+        let message = read_packed(bytes)?;
+        let kx = message
+            .get_root::<transit_encoding_capnp::msg_step3_from_connect_kx_encoded::Reader>()?;
+        let compressions = kx.get_compressions()?;
+        Ok(Self {
+            padding: kx.get_padding()?.to_vec(),
+            sig_pub_key: kx.get_sig_pub_key()?.to_vec(),
+            c2l_session_key: kx.get_c2l_session_key()?.to_vec(),
+            c2l_signature: kx.get_c2l_signature()?.to_vec(),
+            compressions: (0..compressions.len())
+                .filter_map(|i| read_compression(compressions.get(i)))
+                .collect(),
+        })
+    }
+
+    /// generate encoded bytes for this MsgStep3FromConnectKxEncoded
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut message = capnp::message::Builder::new_default();
+        {
+            let mut kx = message
+                .init_root::<transit_encoding_capnp::msg_step3_from_connect_kx_encoded::Builder>();
+            kx.set_padding(&self.padding);
+            kx.set_sig_pub_key(&self.sig_pub_key);
+            kx.set_c2l_session_key(&self.c2l_session_key);
+            kx.set_c2l_signature(&self.c2l_signature);
+            let mut compressions = kx.init_compressions(self.compressions.len() as u32);
+            for (i, compression) in self.compressions.iter().enumerate() {
+                compressions.set(i as u32, to_capnp_compression(*compression));
+            }
+        }
+        write_packed(&message)
+    }
+}
+
+impl MsgStep4FromListenEncoded {
+    /// parse raw bytes into a MsgStep4FromListenEncoded
+    pub fn from_bytes(bytes: &[u8]) -> P2pResult<Self> {
+        let message = read_packed(bytes)?;
+        let step4 =
+            message.get_root::<transit_encoding_capnp::msg_step4_from_listen_encoded::Reader>()?;
+        Ok(Self {
+            padding: step4.get_padding()?.to_vec(),
+            l2c_signature: step4.get_l2c_signature()?.to_vec(),
+            compression: read_compression(step4.get_compression()).unwrap_or_default(),
+        })
+    }
+
+    /// generate encoded bytes for this MsgStep4FromListenEncoded
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut message = capnp::message::Builder::new_default();
+        {
+            let mut step4 = message
+                .init_root::<transit_encoding_capnp::msg_step4_from_listen_encoded::Builder>();
+            step4.set_padding(&self.padding);
+            step4.set_l2c_signature(&self.l2c_signature);
+            step4.set_compression(to_capnp_compression(self.compression));
+        }
+        write_packed(&message)
+    }
+}
+
+impl MsgStep5FromConnectEncoded {
+    /// parse raw bytes into a MsgStep5FromConnectEncoded
+    pub fn from_bytes(bytes: &[u8]) -> P2pResult<Self> {
+        let message = read_packed(bytes)?;
+        let step5 =
+            message.get_root::<transit_encoding_capnp::msg_step5_from_connect_encoded::Reader>()?;
+        Ok(Self {
+            padding: step5.get_padding()?.to_vec(),
+        })
+    }
+
+    /// generate encoded bytes for this MsgStep5FromConnectEncoded
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut message = capnp::message::Builder::new_default();
+        message
+            .init_root::<transit_encoding_capnp::msg_step5_from_connect_encoded::Builder>()
+            .set_padding(&self.padding);
+        write_packed(&message)
+    }
+}
+
+impl EncodedMessage {
+    /// parse raw bytes into an EncodedMessage
+    pub fn from_bytes(bytes: &[u8]) -> P2pResult<Self> {
+        let message = read_packed(bytes)?;
+        let encoded = message.get_root::<transit_encoding_capnp::encoded_message::Reader>()?;
+        Ok(Self {
+            padding: encoded.get_padding()?.to_vec(),
+            content: encoded.get_content()?.to_vec(),
+        })
+    }
+
+    /// generate encoded bytes for this EncodedMessage
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut message = capnp::message::Builder::new_default();
+        {
+            let mut encoded =
+                message.init_root::<transit_encoding_capnp::encoded_message::Builder>();
+            encoded.set_padding(&self.padding);
+            encoded.set_content(&self.content);
+        }
+        write_packed(&message)
+    }
+}
+
+/// Handshake messages following `MsgStep1FromConnect` are encoded
+/// according to the `TransitEncodingType` it requested
+pub trait TransitMessage: Sized + serde::Serialize + serde::de::DeserializeOwned {
+    /// parse packed capnproto bytes
+    fn from_packed(bytes: &[u8]) -> P2pResult<Self>;
+
+    /// generate packed capnproto bytes
+    fn to_packed(&self) -> Vec<u8>;
+
+    /// parse bytes encoded with `encoding`
+    fn decode_as(bytes: &[u8], encoding: TransitEncodingType) -> P2pResult<Self> {
+        if encoding.is_json() {
+            serde_json::from_slice(bytes).map_err(|e| format!("{:?}", e).into())
+        } else {
+            Self::from_packed(bytes)
+        }
+    }
+
+    /// generate bytes encoded with `encoding`
+    fn encode_as(&self, encoding: TransitEncodingType) -> Vec<u8> {
+        if encoding.is_json() {
+            serde_json::to_vec(self).expect("transit messages serialize to json")
+        } else {
+            self.to_packed()
+        }
+    }
+}
+
+macro_rules! impl_transit_message {
+    ($($t:ty),*) => {
+        $(
+            impl TransitMessage for $t {
+                fn from_packed(bytes: &[u8]) -> P2pResult<Self> {
+                    Self::from_bytes(bytes)
+                }
+
+                fn to_packed(&self) -> Vec<u8> {
+                    self.to_bytes()
+                }
+            }
+        )*
+    };
+}
+
+impl_transit_message!(
+    Halt,
+    MsgStep2FromListen,
+    MsgStep2FromListenKxEncoded,
+    MsgStep3FromConnect,
+    MsgStep3FromConnectKxEncoded,
+    MsgStep4FromListenEncoded,
+    MsgStep5FromConnectEncoded,
+    EncodedMessage
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_can_encode_decode_as_json() {
+        let encoded = EncodedMessage {
+            padding: vec![],
+            content: b"test".to_vec(),
+        };
+        let json = encoded.encode_as(TransitEncodingType::OpenJson);
+        assert_eq!(b'{', json[0]);
+        assert_eq!(
+            encoded,
+            EncodedMessage::decode_as(&json, TransitEncodingType::SodiumJson).unwrap()
+        );
+        assert_eq!(
+            encoded,
+            EncodedMessage::decode_as(
+                &encoded.encode_as(TransitEncodingType::OpenPacked),
+                TransitEncodingType::SodiumPacked
+            )
+            .unwrap()
+        );
+    }
+
+    fn test_halt() -> Halt {
+        Halt::new(HaltReasonCode::BadNetworkId, "bad network id")
+    }
+
+    #[test]
+    fn it_can_encode_decode_halt() {
+        let halt = test_halt();
+        assert_eq!(halt, Halt::from_bytes(&halt.to_bytes()).unwrap());
+    }
+
+    #[test]
+    fn it_can_encode_decode_step1() {
+        let step1 = MsgStep1FromConnect {
+            magic: TRANSIT_ENCODING_MAGIC,
+            encoding: TransitEncodingType::SodiumPacked,
+            network_id: b"test-net".to_vec(),
+            kx_pub_key: vec![1, 2, 3],
+            encodings: vec![
+                TransitEncodingType::SodiumPacked,
+                TransitEncodingType::OpenJson,
+            ],
+        };
+        assert_eq!(
+            step1,
+            MsgStep1FromConnect::from_bytes(&step1.to_bytes()).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_can_encode_decode_step2() {
+        for step2 in vec![
+            MsgStep2FromListen::Halt(test_halt()),
+            MsgStep2FromListen::Continue(MsgStep2Continue {
+                kx_pub_key: vec![1, 2],
+                kx_nonce: vec![3, 4],
+                kx_secret: vec![5, 6],
+                encoding: TransitEncodingType::SodiumJson,
+            }),
+        ] {
+            assert_eq!(
+                step2,
+                MsgStep2FromListen::from_bytes(&step2.to_bytes()).unwrap()
+            );
+        }
+        let kx = MsgStep2FromListenKxEncoded {
+            padding: vec![0; 8],
+            sig_pub_key: vec![1, 2],
+            l2c_session_key: vec![3, 4],
+        };
+        assert_eq!(
+            kx,
+            MsgStep2FromListenKxEncoded::from_bytes(&kx.to_bytes()).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_can_encode_decode_step3() {
+        for step3 in vec![
+            MsgStep3FromConnect::Halt(test_halt()),
+            MsgStep3FromConnect::Continue(MsgStep3Continue {
+                kx_nonce: vec![3, 4],
+                kx_secret: vec![5, 6],
+            }),
+        ] {
+            assert_eq!(
+                step3,
+                MsgStep3FromConnect::from_bytes(&step3.to_bytes()).unwrap()
+            );
+        }
+        let kx = MsgStep3FromConnectKxEncoded {
+            padding: vec![],
+            sig_pub_key: vec![1, 2],
+            c2l_session_key: vec![3, 4],
+            c2l_signature: vec![5, 6],
+            compressions: vec![CompressionType::Deflate, CompressionType::None],
+        };
+        assert_eq!(
+            kx,
+            MsgStep3FromConnectKxEncoded::from_bytes(&kx.to_bytes()).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_can_encode_decode_encoded_steps() {
+        let step4 = MsgStep4FromListenEncoded {
+            padding: vec![0; 3],
+            l2c_signature: vec![1, 2],
+            compression: CompressionType::Deflate,
+        };
+        assert_eq!(
+            step4,
+            MsgStep4FromListenEncoded::from_bytes(&step4.to_bytes()).unwrap()
+        );
+        let step5 = MsgStep5FromConnectEncoded {
+            padding: vec![0; 3],
+        };
+        assert_eq!(
+            step5,
+            MsgStep5FromConnectEncoded::from_bytes(&step5.to_bytes()).unwrap()
+        );
+        let encoded = EncodedMessage {
+            padding: vec![],
+            content: b"hello".to_vec(),
+        };
+        assert_eq!(
+            encoded,
+            EncodedMessage::from_bytes(&encoded.to_bytes()).unwrap()
+        );
+    }
+}
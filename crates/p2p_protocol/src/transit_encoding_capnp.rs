@@ -185,6 +185,31 @@ pub mod halt {
   }
 }
 
+#[repr(u16)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Compression {
+  None = 0,
+  Deflate = 1,
+}
+impl ::capnp::traits::FromU16 for Compression {
+  #[inline]
+  fn from_u16(value: u16) -> ::std::result::Result<Compression, ::capnp::NotInSchema> {
+    match value {
+      0 => ::std::result::Result::Ok(Compression::None),
+      1 => ::std::result::Result::Ok(Compression::Deflate),
+      n => ::std::result::Result::Err(::capnp::NotInSchema(n)),
+    }
+  }
+}
+impl ::capnp::traits::ToU16 for Compression {
+  #[inline]
+  fn to_u16(self) -> u16 { self as u16 }
+}
+impl ::capnp::traits::HasTypeId for Compression {
+  #[inline]
+  fn type_id() -> u64 { 0xd5b2_9a3e_41c7_f08eu64 }
+}
+
 pub mod msg_step1_from_connect {
   #[derive(Copy, Clone)]
   pub struct Owned;
@@ -253,6 +278,13 @@ pub mod msg_step1_from_connect {
     pub fn has_kx_pub_key(&self) -> bool {
       !self.reader.get_pointer_field(1).is_null()
     }
+    #[inline]
+    pub fn get_encodings(self) -> ::capnp::Result<::capnp::enum_list::Reader<'a,crate::transit_encoding_capnp::msg_step1_from_connect::Encoding>> {
+      ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(2), ::std::option::Option::None)
+    }
+    pub fn has_encodings(&self) -> bool {
+      !self.reader.get_pointer_field(2).is_null()
+    }
   }
 
   pub struct Builder<'a> { builder: ::capnp::private::layout::StructBuilder<'a> }
@@ -349,6 +381,21 @@ pub mod msg_step1_from_connect {
     pub fn has_kx_pub_key(&self) -> bool {
       !self.builder.get_pointer_field(1).is_null()
     }
+    #[inline]
+    pub fn get_encodings(self) -> ::capnp::Result<::capnp::enum_list::Builder<'a,crate::transit_encoding_capnp::msg_step1_from_connect::Encoding>> {
+      ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(2), ::std::option::Option::None)
+    }
+    #[inline]
+    pub fn set_encodings(&mut self, value: ::capnp::enum_list::Reader<'a,crate::transit_encoding_capnp::msg_step1_from_connect::Encoding>) -> ::capnp::Result<()> {
+      ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(2), value, false)
+    }
+    #[inline]
+    pub fn init_encodings(self, size: u32) -> ::capnp::enum_list::Builder<'a,crate::transit_encoding_capnp::msg_step1_from_connect::Encoding> {
+      ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(2), size)
+    }
+    pub fn has_encodings(&self) -> bool {
+      !self.builder.get_pointer_field(2).is_null()
+    }
   }
 
   pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -361,7 +408,7 @@ pub mod msg_step1_from_connect {
   }
   mod _private {
     use capnp::private::layout;
-    pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 1, pointers: 2 };
+    pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 1, pointers: 3 };
     pub const TYPE_ID: u64 = 0xbba4_bc46_7e9b_fa94;
   }
 
@@ -652,6 +699,10 @@ pub mod msg_step2_from_listen {
       pub fn has_kx_secret(&self) -> bool {
         !self.reader.get_pointer_field(2).is_null()
       }
+      #[inline]
+      pub fn get_encoding(self) -> ::std::result::Result<crate::transit_encoding_capnp::msg_step1_from_connect::Encoding,::capnp::NotInSchema> {
+        ::capnp::traits::FromU16::from_u16(self.reader.get_data_field::<u16>(0))
+      }
     }
 
     pub struct Builder<'a> { builder: ::capnp::private::layout::StructBuilder<'a> }
@@ -747,6 +798,14 @@ pub mod msg_step2_from_listen {
       pub fn has_kx_secret(&self) -> bool {
         !self.builder.get_pointer_field(2).is_null()
       }
+      #[inline]
+      pub fn get_encoding(self) -> ::std::result::Result<crate::transit_encoding_capnp::msg_step1_from_connect::Encoding,::capnp::NotInSchema> {
+        ::capnp::traits::FromU16::from_u16(self.builder.get_data_field::<u16>(0))
+      }
+      #[inline]
+      pub fn set_encoding(&mut self, value: crate::transit_encoding_capnp::msg_step1_from_connect::Encoding)  {
+        self.builder.set_data_field::<u16>(0, value as u16)
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -759,7 +818,7 @@ pub mod msg_step2_from_listen {
     }
     mod _private {
       use capnp::private::layout;
-      pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 0, pointers: 3 };
+      pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 1, pointers: 3 };
       pub const TYPE_ID: u64 = 0x9390_a450_36f5_0dca;
     }
   }
@@ -1364,6 +1423,13 @@ pub mod msg_step3_from_connect_kx_encoded {
     pub fn has_c2l_signature(&self) -> bool {
       !self.reader.get_pointer_field(3).is_null()
     }
+    #[inline]
+    pub fn get_compressions(self) -> ::capnp::Result<::capnp::enum_list::Reader<'a,crate::transit_encoding_capnp::Compression>> {
+      ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(4), ::std::option::Option::None)
+    }
+    pub fn has_compressions(&self) -> bool {
+      !self.reader.get_pointer_field(4).is_null()
+    }
   }
 
   pub struct Builder<'a> { builder: ::capnp::private::layout::StructBuilder<'a> }
@@ -1474,6 +1540,21 @@ pub mod msg_step3_from_connect_kx_encoded {
     pub fn has_c2l_signature(&self) -> bool {
       !self.builder.get_pointer_field(3).is_null()
     }
+    #[inline]
+    pub fn get_compressions(self) -> ::capnp::Result<::capnp::enum_list::Builder<'a,crate::transit_encoding_capnp::Compression>> {
+      ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(4), ::std::option::Option::None)
+    }
+    #[inline]
+    pub fn set_compressions(&mut self, value: ::capnp::enum_list::Reader<'a,crate::transit_encoding_capnp::Compression>) -> ::capnp::Result<()> {
+      ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(4), value, false)
+    }
+    #[inline]
+    pub fn init_compressions(self, size: u32) -> ::capnp::enum_list::Builder<'a,crate::transit_encoding_capnp::Compression> {
+      ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(4), size)
+    }
+    pub fn has_compressions(&self) -> bool {
+      !self.builder.get_pointer_field(4).is_null()
+    }
   }
 
   pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -1486,7 +1567,7 @@ pub mod msg_step3_from_connect_kx_encoded {
   }
   mod _private {
     use capnp::private::layout;
-    pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 0, pointers: 4 };
+    pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 0, pointers: 5 };
     pub const TYPE_ID: u64 = 0x9935_c4b8_3091_1b07;
   }
 }
@@ -1551,6 +1632,10 @@ pub mod msg_step4_from_listen_encoded {
     pub fn has_l2c_signature(&self) -> bool {
       !self.reader.get_pointer_field(1).is_null()
     }
+    #[inline]
+    pub fn get_compression(self) -> ::std::result::Result<crate::transit_encoding_capnp::Compression,::capnp::NotInSchema> {
+      ::capnp::traits::FromU16::from_u16(self.reader.get_data_field::<u16>(0))
+    }
   }
 
   pub struct Builder<'a> { builder: ::capnp::private::layout::StructBuilder<'a> }
@@ -1631,6 +1716,14 @@ pub mod msg_step4_from_listen_encoded {
     pub fn has_l2c_signature(&self) -> bool {
       !self.builder.get_pointer_field(1).is_null()
     }
+    #[inline]
+    pub fn get_compression(self) -> ::std::result::Result<crate::transit_encoding_capnp::Compression,::capnp::NotInSchema> {
+      ::capnp::traits::FromU16::from_u16(self.builder.get_data_field::<u16>(0))
+    }
+    #[inline]
+    pub fn set_compression(&mut self, value: crate::transit_encoding_capnp::Compression)  {
+      self.builder.set_data_field::<u16>(0, value as u16)
+    }
   }
 
   pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -1643,7 +1736,7 @@ pub mod msg_step4_from_listen_encoded {
   }
   mod _private {
     use capnp::private::layout;
-    pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 0, pointers: 2 };
+    pub const STRUCT_SIZE: layout::StructSize = layout::StructSize { data: 1, pointers: 2 };
     pub const TYPE_ID: u64 = 0xc7ca_df68_bbb8_15bf;
   }
 }
@@ -34,6 +34,19 @@ pub fn dump_lib3h_protocol_as_json_for_n3h() {
         network_id: "nid".into(),
     }));
 
+    test_client(Lib3hClientProtocol::QueryPeerLatency(
+        QueryPeerLatencyData {
+            request_id: "rid".to_string(),
+        },
+    ));
+
+    test_client(Lib3hClientProtocol::CancelTransfer(CancelTransferData {
+        request_id: "rid".to_string(),
+        peer_location: url::Url::parse("wss://1.2.3.4:55888").unwrap().into(),
+        transfer_id: 3,
+        outgoing: true,
+    }));
+
     test_client(Lib3hClientProtocol::JoinSpace(SpaceData {
         request_id: "rid".to_string(),
         space_address: "adr".to_string().into(),
@@ -175,6 +188,50 @@ pub fn dump_lib3h_protocol_as_json_for_n3h() {
         network_id: "nid".into(),
     }));
 
+    test_server(Lib3hServerProtocol::PeerReconnected(PeerConnectionData {
+        peer_name: url::Url::parse("hc:id").unwrap().into(),
+        peer_location: url::Url::parse("wss://1.2.3.4:55888").unwrap().into(),
+        attempts: 2,
+    }));
+
+    test_server(Lib3hServerProtocol::PeerLost(PeerConnectionData {
+        peer_name: url::Url::parse("hc:id").unwrap().into(),
+        peer_location: url::Url::parse("wss://1.2.3.4:55888").unwrap().into(),
+        attempts: 10,
+    }));
+
+    test_server(Lib3hServerProtocol::TransferProgress(
+        TransferProgressData {
+            peer_location: url::Url::parse("wss://1.2.3.4:55888").unwrap().into(),
+            transfer_id: 3,
+            outgoing: false,
+            bytes_done: 65536,
+            total_bytes: 1048576,
+            canceled: false,
+        },
+    ));
+
+    test_server(Lib3hServerProtocol::QueryPeerLatencyResult(
+        PeerLatencyListData {
+            request_id: "rid".to_string(),
+            peer_latency_list: vec![PeerLatencyData {
+                peer_name: url::Url::parse("hc:id").unwrap().into(),
+                peer_location: url::Url::parse("wss://1.2.3.4:55888").unwrap().into(),
+                rtt_ms: Some(42),
+                smoothed_rtt_ms: Some(40),
+                clock_skew_ms: Some(-3),
+                missed_pongs: 0,
+                timed_out: false,
+                compression: Some(CompressionStatsData {
+                    compression: "Deflate".to_string(),
+                    uncompressed_bytes: 4096,
+                    compressed_bytes: 1024,
+                    compressed_messages: 3,
+                }),
+            }],
+        },
+    ));
+
     test_server(Lib3hServerProtocol::SendDirectMessageResult(
         DirectMessageData {
             space_address: "adr".to_string().into(),